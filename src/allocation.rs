@@ -1,9 +1,40 @@
 use crate::device::{Device, DeviceShared};
-use crate::error::Error;
+use crate::error;
+use crate::error::{Error, Variant};
 use crate::instance::InstanceShared;
-use ash::vk::{DeviceMemory, ExternalMemoryHandleTypeFlags, ImportMemoryFdInfoKHR, MemoryAllocateInfo};
-use std::ffi::c_void;
-use std::sync::Arc;
+use crate::resources::{Image, MemoryRequirements};
+#[cfg(unix)]
+use ash::vk::MemoryGetFdInfoKHR;
+#[cfg(windows)]
+use ash::vk::MemoryGetWin32HandleInfoKHR;
+#[cfg(feature = "android")]
+use ash::vk::{
+    AHardwareBuffer, AndroidHardwareBufferFormatPropertiesANDROID, AndroidHardwareBufferPropertiesANDROID,
+    ImportAndroidHardwareBufferInfoANDROID,
+};
+use ash::vk::{
+    DeviceMemory, ExportMemoryAllocateInfo, ExternalMemoryHandleTypeFlags, ImportMemoryFdInfoKHR, ImportMemoryWin32HandleInfoKHR,
+    MappedMemoryRange, MemoryAllocateInfo, MemoryDedicatedAllocateInfo, MemoryMapFlags, MemoryPropertyFlags,
+    MemoryWin32HandlePropertiesKHR, HANDLE, WHOLE_SIZE,
+};
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// OS handle memory is exported as, owning (and eventually closing) it.
+#[cfg(unix)]
+pub type ExternalHandle = std::os::fd::OwnedFd;
+
+/// OS handle memory is exported as, owning (and eventually closing) it.
+#[cfg(windows)]
+pub type ExternalHandle = std::os::windows::io::OwnedHandle;
+
+/// Handle type of [`ExternalHandle`], to be used for images and buffers bound to exported memory.
+#[cfg(unix)]
+pub const EXTERNAL_HANDLE_TYPE: ExternalMemoryHandleTypeFlags = ExternalMemoryHandleTypeFlags::OPAQUE_FD;
+
+/// Handle type of [`ExternalHandle`], to be used for images and buffers bound to exported memory.
+#[cfg(windows)]
+pub const EXTERNAL_HANDLE_TYPE: ExternalMemoryHandleTypeFlags = ExternalMemoryHandleTypeFlags::OPAQUE_WIN32;
 
 #[derive(Clone, Copy, Debug)]
 pub struct MemoryTypeIndex(u32);
@@ -11,14 +42,43 @@ impl MemoryTypeIndex {
     pub fn new(type_index: u32) -> Self {
         Self(type_index)
     }
+
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Who frees the memory of an [`AllocationShared`].
+enum Owner {
+    /// We do, once dropped.
+    Crate,
+    /// Whoever wrapped it via [`Allocation::from_raw`].
+    Foreign,
+    /// The allocator it was sub-allocated from, by calling this once dropped.
+    Allocator(Option<Box<dyn FnOnce() + Send + Sync>>),
 }
 
+/// Host address of mapped memory.
+struct HostPointer(NonNull<u8>);
+
+// SAFETY: The pointer is only dereferenced while holding the mapping lock of its allocation.
+unsafe impl Send for HostPointer {}
+unsafe impl Sync for HostPointer {}
+
 pub(crate) struct AllocationShared {
     shared_instance: Arc<InstanceShared>,
     shared_device: Arc<DeviceShared>,
     device_memory: DeviceMemory,
-    // size: u64,
-    // type_index: MemoryTypeIndex,
+    /// Held while the memory is mapped, as it can only be mapped once at a time.
+    mapping: Mutex<()>,
+    /// Start of the memory if someone else keeps it mapped, e.g., the allocator it came from.
+    mapped: Option<HostPointer>,
+    /// Who frees the memory.
+    owner: Owner,
+    /// Handle types the memory was exported with or imported from, empty for regular allocations.
+    handle_types: ExternalMemoryHandleTypeFlags,
+    size: u64,
+    type_index: MemoryTypeIndex,
 }
 
 impl AllocationShared {
@@ -31,34 +91,348 @@ impl AllocationShared {
             shared_instance: shared_device.instance(),
             shared_device,
             device_memory,
-            // size,
-            // type_index,
-        })
+            mapping: Mutex::new(()),
+            mapped: None,
+            owner: Owner::Crate,
+            handle_types: ExternalMemoryHandleTypeFlags::empty(),
+            size,
+            type_index,
+        }
+        .tracked())
+    }
+
+    pub fn for_requirements(
+        shared_device: Arc<DeviceShared>,
+        requirements: &MemoryRequirements,
+        flags: MemoryPropertyFlags,
+    ) -> Result<Self, Error> {
+        let type_index = shared_device
+            .physical_device()
+            .heap_infos()
+            .find_type(requirements.memory_type_bits(), flags, MemoryPropertyFlags::empty())
+            .ok_or_else(|| {
+                error!(
+                    Variant::HeapNotFound,
+                    "No memory type with {flags:?} in {:#b}.",
+                    requirements.memory_type_bits()
+                )
+            })?;
+
+        Self::new(shared_device, requirements.size(), type_index)
+    }
+
+    /// # Safety
+    ///
+    /// The memory must have been allocated from `shared_device` with `size` and `type_index`.
+    pub unsafe fn from_native(
+        shared_device: Arc<DeviceShared>,
+        device_memory: DeviceMemory,
+        size: u64,
+        type_index: MemoryTypeIndex,
+        owned: bool,
+    ) -> Self {
+        let allocation = Self {
+            shared_instance: shared_device.instance(),
+            shared_device,
+            device_memory,
+            mapping: Mutex::new(()),
+            mapped: None,
+            owner: if owned { Owner::Crate } else { Owner::Foreign },
+            handle_types: ExternalMemoryHandleTypeFlags::empty(),
+            size,
+            type_index,
+        };
+
+        match owned {
+            true => allocation.tracked(),
+            false => allocation,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Like [`Self::from_native`], `mapped` must point to the start of the memory and stay valid until `free` ran.
+    pub unsafe fn from_allocator(
+        shared_device: Arc<DeviceShared>,
+        device_memory: DeviceMemory,
+        size: u64,
+        type_index: MemoryTypeIndex,
+        mapped: Option<NonNull<u8>>,
+        free: Box<dyn FnOnce() + Send + Sync>,
+    ) -> Self {
+        Self {
+            shared_instance: shared_device.instance(),
+            shared_device,
+            device_memory,
+            mapping: Mutex::new(()),
+            mapped: mapped.map(HostPointer),
+            owner: Owner::Allocator(Some(free)),
+            handle_types: ExternalMemoryHandleTypeFlags::empty(),
+            size,
+            type_index,
+        }
     }
 
-    pub fn new_external(shared_device: Arc<DeviceShared>, external: *mut c_void, size: u64) -> Result<Self, Error> {
+    /// Counts this (owned) allocation towards [`Device::stats`](crate::Device::stats), until dropped.
+    fn tracked(self) -> Self {
+        let heap_index = self.shared_device.physical_device().heap_infos().heap_index(self.type_index);
+        self.shared_device.counters().allocated(heap_index, self.size);
+        self
+    }
+
+    pub fn new_exportable(shared_device: Arc<DeviceShared>, size: u64, type_index: MemoryTypeIndex) -> Result<Self, Error> {
+        if !shared_device.video_features().external_memory() {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "No external memory extension for {EXTERNAL_HANDLE_TYPE:?}."
+            ));
+        }
+
         let native_device = shared_device.native();
 
-        let mut todo_bad = ImportMemoryFdInfoKHR::default()
-            .handle_type(ExternalMemoryHandleTypeFlags::OPAQUE_WIN32) // TODO
-            .fd(external as _);
+        let mut export_info = ExportMemoryAllocateInfo::default().handle_types(EXTERNAL_HANDLE_TYPE);
 
         let info = MemoryAllocateInfo::default()
             .allocation_size(size)
-            .memory_type_index(3) // TODO!!
-            .push_next(&mut todo_bad);
+            .memory_type_index(type_index.0)
+            .push_next(&mut export_info);
 
-        unsafe {
-            let device_memory = native_device.allocate_memory(&info, None)?;
+        let device_memory = unsafe { native_device.allocate_memory(&info, None)? };
+
+        Ok(Self {
+            shared_instance: shared_device.instance(),
+            shared_device,
+            device_memory,
+            mapping: Mutex::new(()),
+            mapped: None,
+            owner: Owner::Crate,
+            handle_types: EXTERNAL_HANDLE_TYPE,
+            size,
+            type_index,
+        }
+        .tracked())
+    }
+
+    #[cfg(unix)]
+    pub fn import_fd(
+        shared_device: Arc<DeviceShared>,
+        fd: std::os::fd::OwnedFd,
+        size: u64,
+        type_index: MemoryTypeIndex,
+    ) -> Result<Self, Error> {
+        if !shared_device.video_features().external_memory_fd() {
+            return Err(error!(Variant::FeatureNotSupported, "VK_KHR_external_memory_fd is not available."));
+        }
+
+        // SAFETY: Should be safe as the device is valid, and the fd owned.
+        let device_memory = unsafe { import(&shared_device.native(), fd, size, type_index)? };
+
+        Ok(Self {
+            shared_instance: shared_device.instance(),
+            shared_device,
+            device_memory,
+            mapping: Mutex::new(()),
+            mapped: None,
+            owner: Owner::Crate,
+            handle_types: ExternalMemoryHandleTypeFlags::OPAQUE_FD,
+            size,
+            type_index,
+        }
+        .tracked())
+    }
 
-            Ok(Self {
-                shared_instance: shared_device.instance(),
-                shared_device,
-                device_memory,
-                // size,
-                // type_index: MemoryTypeIndex(0), // TODO
-            })
+    pub fn import_handle(
+        shared_device: Arc<DeviceShared>,
+        handle: ExternalHandle,
+        size: u64,
+        type_index: MemoryTypeIndex,
+    ) -> Result<Self, Error> {
+        if !shared_device.video_features().external_memory() {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "No external memory extension for {EXTERNAL_HANDLE_TYPE:?}."
+            ));
         }
+
+        // SAFETY: Should be safe as the device is valid, and the handle owned.
+        let device_memory = unsafe { import(&shared_device.native(), handle, size, type_index)? };
+
+        Ok(Self {
+            shared_instance: shared_device.instance(),
+            shared_device,
+            device_memory,
+            mapping: Mutex::new(()),
+            mapped: None,
+            owner: Owner::Crate,
+            handle_types: EXTERNAL_HANDLE_TYPE,
+            size,
+            type_index,
+        }
+        .tracked())
+    }
+
+    pub unsafe fn import_win32_handle(
+        shared_device: Arc<DeviceShared>,
+        handle: HANDLE,
+        handle_type: ExternalMemoryHandleTypeFlags,
+        native_image: ash::vk::Image,
+    ) -> Result<Self, Error> {
+        let importable = ExternalMemoryHandleTypeFlags::OPAQUE_WIN32
+            | ExternalMemoryHandleTypeFlags::OPAQUE_WIN32_KMT
+            | ExternalMemoryHandleTypeFlags::D3D11_TEXTURE
+            | ExternalMemoryHandleTypeFlags::D3D11_TEXTURE_KMT
+            | ExternalMemoryHandleTypeFlags::D3D12_RESOURCE;
+
+        if !importable.contains(handle_type) || handle_type.as_raw().count_ones() != 1 {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "Can't import {handle_type:?} as Windows handle."
+            ));
+        }
+
+        let functions = shared_device
+            .external_memory_win32()
+            .ok_or_else(|| error!(Variant::FeatureNotSupported, "VK_KHR_external_memory_win32 is not available."))?;
+
+        let native_device = shared_device.native();
+        let requirements = native_device.get_image_memory_requirements(native_image);
+
+        // Opaque handles can't be queried, for D3D resources the driver knows which memory types they live in.
+        let memory_type_bits = match handle_type {
+            ExternalMemoryHandleTypeFlags::OPAQUE_WIN32 | ExternalMemoryHandleTypeFlags::OPAQUE_WIN32_KMT => requirements.memory_type_bits,
+            _ => {
+                let mut properties = MemoryWin32HandlePropertiesKHR::default();
+                functions.get_memory_win32_handle_properties(handle_type, handle, &mut properties)?;
+                requirements.memory_type_bits & properties.memory_type_bits
+            }
+        };
+
+        if memory_type_bits == 0 {
+            return Err(error!(Variant::HeapNotFound, "Image and handle share no memory type."));
+        }
+
+        let type_index = MemoryTypeIndex(memory_type_bits.trailing_zeros());
+
+        // D3D resources are always imported for a single image.
+        let mut import_info = ImportMemoryWin32HandleInfoKHR::default().handle_type(handle_type).handle(handle);
+        let mut dedicated_info = MemoryDedicatedAllocateInfo::default().image(native_image);
+
+        let info = MemoryAllocateInfo::default()
+            .allocation_size(requirements.size)
+            .memory_type_index(type_index.0)
+            .push_next(&mut import_info)
+            .push_next(&mut dedicated_info);
+
+        let device_memory = native_device.allocate_memory(&info, None)?;
+
+        Ok(Self {
+            shared_instance: shared_device.instance(),
+            shared_device,
+            device_memory,
+            mapping: Mutex::new(()),
+            mapped: None,
+            owner: Owner::Crate,
+            handle_types: handle_type,
+            size: requirements.size,
+            type_index,
+        }
+        .tracked())
+    }
+
+    #[cfg(feature = "android")]
+    pub unsafe fn import_android_hardware_buffer(
+        shared_device: Arc<DeviceShared>,
+        buffer: *mut AHardwareBuffer,
+        native_image: ash::vk::Image,
+    ) -> Result<Self, Error> {
+        let mut format_properties = AndroidHardwareBufferFormatPropertiesANDROID::default();
+        let properties = android_hardware_buffer_properties(&shared_device, buffer, &mut format_properties)?;
+        let native_device = shared_device.native();
+
+        // Any type the buffer allows will do, its memory already exists.
+        if properties.memory_type_bits == 0 {
+            return Err(error!(Variant::HeapNotFound, "Hardware buffer allows no memory type."));
+        }
+
+        let type_index = MemoryTypeIndex(properties.memory_type_bits.trailing_zeros());
+        let mut import_info = ImportAndroidHardwareBufferInfoANDROID::default().buffer(buffer);
+        let mut dedicated_info = MemoryDedicatedAllocateInfo::default().image(native_image);
+
+        let info = MemoryAllocateInfo::default()
+            .allocation_size(properties.allocation_size)
+            .memory_type_index(type_index.0)
+            .push_next(&mut import_info)
+            .push_next(&mut dedicated_info);
+
+        let device_memory = native_device.allocate_memory(&info, None)?;
+
+        Ok(Self {
+            shared_instance: shared_device.instance(),
+            shared_device,
+            device_memory,
+            mapping: Mutex::new(()),
+            mapped: None,
+            owner: Owner::Crate,
+            handle_types: ExternalMemoryHandleTypeFlags::ANDROID_HARDWARE_BUFFER_ANDROID,
+            size: properties.allocation_size,
+            type_index,
+        }
+        .tracked())
+    }
+
+    /// Exports a new handle to the memory, which must have been created exportable.
+    pub fn export_handle(&self) -> Result<ExternalHandle, Error> {
+        if !self.handle_types.contains(EXTERNAL_HANDLE_TYPE) {
+            return Err(error!(Variant::FeatureNotSupported, "Memory was not allocated exportable."));
+        }
+
+        // SAFETY: Should be safe as the memory is valid and exportable.
+        unsafe { export(&self.shared_device, self.device_memory) }
+    }
+
+    pub(crate) fn handle_types(&self) -> ExternalMemoryHandleTypeFlags {
+        self.handle_types
+    }
+
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub(crate) fn type_index(&self) -> MemoryTypeIndex {
+        self.type_index
+    }
+
+    /// Fails unless resources with `memory_type_bits` (from their memory requirements) may be bound to this memory.
+    pub(crate) fn check_type(&self, memory_type_bits: u32) -> Result<(), Error> {
+        if memory_type_bits & (1 << self.type_index.0) == 0 {
+            return Err(error!(
+                Variant::IncompatibleMemoryType,
+                "Memory type {} is not one of the permitted types {memory_type_bits:#b}.", self.type_index.0
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// If host writes and device writes are visible to each other without flushing or invalidating mapped ranges.
+    pub(crate) fn host_coherent(&self) -> bool {
+        self.shared_device
+            .physical_device()
+            .heap_infos()
+            .type_flags(self.type_index)
+            .contains(MemoryPropertyFlags::HOST_COHERENT)
+    }
+
+    /// Range to flush or invalidate for `size` bytes at `offset`, widened to whole `nonCoherentAtomSize` atoms as Vulkan requires.
+    pub(crate) fn mapped_range(&self, offset: u64, size: u64) -> MappedMemoryRange<'static> {
+        let atom_size = self.shared_device.physical_device().non_coherent_atom_size().max(1);
+        let start = offset - offset % atom_size;
+        let end = (offset + size).next_multiple_of(atom_size);
+
+        // Rounding up may pass our end, where only `WHOLE_SIZE` (to the end of the mapping, i.e., the memory) is valid.
+        let size = if end > self.size { WHOLE_SIZE } else { end - start };
+
+        MappedMemoryRange::default().memory(self.device_memory).offset(start).size(size)
     }
 
     #[allow(unused)]
@@ -73,15 +447,148 @@ impl AllocationShared {
     pub(crate) fn native(&self) -> DeviceMemory {
         self.device_memory
     }
+
+    /// Runs `f` with a pointer to the start of the memory, mapping it for as long as `f` runs unless it's mapped anyway.
+    pub(crate) fn map<R>(&self, f: impl FnOnce(*mut u8) -> Result<R, Error>) -> Result<R, Error> {
+        let _mapping = self.mapping.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(mapped) = &self.mapped {
+            return f(mapped.0.as_ptr());
+        }
+
+        let native_device = self.shared_device.native();
+
+        unsafe {
+            let mapped_pointer = native_device.map_memory(self.device_memory, 0, WHOLE_SIZE, MemoryMapFlags::empty())?;
+            let rval = f(mapped_pointer.cast());
+
+            native_device.unmap_memory(self.device_memory);
+
+            rval
+        }
+    }
+}
+
+/// Size and memory types of `buffer`, and its format in `format_properties`.
+#[cfg(feature = "android")]
+pub(crate) unsafe fn android_hardware_buffer_properties<'a>(
+    shared_device: &DeviceShared,
+    buffer: *const AHardwareBuffer,
+    format_properties: &'a mut AndroidHardwareBufferFormatPropertiesANDROID<'_>,
+) -> Result<AndroidHardwareBufferPropertiesANDROID<'a>, Error> {
+    let functions = shared_device.android_hardware_buffer().ok_or_else(|| {
+        error!(
+            Variant::FeatureNotSupported,
+            "VK_ANDROID_external_memory_android_hardware_buffer is not available."
+        )
+    })?;
+
+    let mut properties = AndroidHardwareBufferPropertiesANDROID::default().push_next(format_properties);
+
+    functions.get_android_hardware_buffer_properties(buffer, &mut properties)?;
+
+    Ok(properties)
+}
+
+/// Imports `handle` as new memory, Vulkan owns the file descriptor once that succeeded.
+#[cfg(unix)]
+unsafe fn import(
+    native_device: &ash::Device,
+    handle: ExternalHandle,
+    size: u64,
+    type_index: MemoryTypeIndex,
+) -> Result<DeviceMemory, Error> {
+    use std::os::fd::{FromRawFd, IntoRawFd};
+
+    let fd = handle.into_raw_fd();
+    let mut import_info = ImportMemoryFdInfoKHR::default().handle_type(EXTERNAL_HANDLE_TYPE).fd(fd);
+
+    let info = MemoryAllocateInfo::default()
+        .allocation_size(size)
+        .memory_type_index(type_index.0)
+        .push_next(&mut import_info);
+
+    native_device.allocate_memory(&info, None).map_err(|e| {
+        drop(ExternalHandle::from_raw_fd(fd));
+        e.into()
+    })
+}
+
+/// Imports `handle` as new memory, Vulkan never owns Windows handles, so it's closed once dropped.
+#[cfg(windows)]
+unsafe fn import(
+    native_device: &ash::Device,
+    handle: ExternalHandle,
+    size: u64,
+    type_index: MemoryTypeIndex,
+) -> Result<DeviceMemory, Error> {
+    use std::os::windows::io::AsRawHandle;
+
+    let mut import_info = ImportMemoryWin32HandleInfoKHR::default()
+        .handle_type(EXTERNAL_HANDLE_TYPE)
+        .handle(handle.as_raw_handle() as HANDLE);
+
+    let info = MemoryAllocateInfo::default()
+        .allocation_size(size)
+        .memory_type_index(type_index.0)
+        .push_next(&mut import_info);
+
+    Ok(native_device.allocate_memory(&info, None)?)
+}
+
+/// Exports `memory` as a new file descriptor we then own.
+#[cfg(unix)]
+unsafe fn export(shared_device: &DeviceShared, memory: DeviceMemory) -> Result<ExternalHandle, Error> {
+    use std::os::fd::FromRawFd;
+
+    let functions = shared_device
+        .external_memory_fd()
+        .ok_or_else(|| error!(Variant::FeatureNotSupported, "VK_KHR_external_memory_fd is not available."))?;
+
+    let info = MemoryGetFdInfoKHR::default().memory(memory).handle_type(EXTERNAL_HANDLE_TYPE);
+    let fd = functions.get_memory_fd(&info)?;
+
+    Ok(ExternalHandle::from_raw_fd(fd))
+}
+
+/// Exports `memory` as a new `HANDLE` we then own.
+#[cfg(windows)]
+unsafe fn export(shared_device: &DeviceShared, memory: DeviceMemory) -> Result<ExternalHandle, Error> {
+    use std::os::windows::io::FromRawHandle;
+
+    let functions = shared_device
+        .external_memory_win32()
+        .ok_or_else(|| error!(Variant::FeatureNotSupported, "VK_KHR_external_memory_win32 is not available."))?;
+
+    let info = MemoryGetWin32HandleInfoKHR::default()
+        .memory(memory)
+        .handle_type(EXTERNAL_HANDLE_TYPE);
+    let handle = functions.get_memory_win32_handle(&info)?;
+
+    Ok(ExternalHandle::from_raw_handle(handle as _))
 }
 
 impl Drop for AllocationShared {
     fn drop(&mut self) {
+        match &mut self.owner {
+            Owner::Crate => {}
+            Owner::Foreign => return,
+            Owner::Allocator(free) => {
+                if let Some(free) = free.take() {
+                    free();
+                }
+                return;
+            }
+        }
+
         let native_device = self.shared_device.native();
+        let heap_index = self.shared_device.physical_device().heap_infos().heap_index(self.type_index);
 
         unsafe {
             native_device.free_memory(self.device_memory, None);
         }
+
+        self.shared_device.counters().freed(heap_index, self.size);
     }
 }
 
@@ -99,14 +606,171 @@ impl Allocation {
         })
     }
 
-    pub fn new_external(device: &Device, external: *mut c_void, size: u64) -> Result<Self, Error> {
-        let allocation_shared = AllocationShared::new_external(device.shared(), external, size)?;
+    /// Allocates memory for a resource with `requirements`, e.g., an image, of a permitted type with all `flags`.
+    ///
+    /// The allocation is exactly as large as needed, and binding at offset 0 satisfies any alignment:
+    ///
+    /// ```rust,ignore
+    /// let allocation = Allocation::for_requirements(&device, &image.memory_requirement(), MemoryPropertyFlags::DEVICE_LOCAL)?;
+    /// let image = image.bind(&allocation)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Variant::HeapNotFound`] if no permitted memory type has all `flags`.
+    pub fn for_requirements(device: &Device, requirements: &MemoryRequirements, flags: MemoryPropertyFlags) -> Result<Self, Error> {
+        let allocation_shared = AllocationShared::for_requirements(device.shared(), requirements, flags)?;
 
         Ok(Self {
             shared: Arc::new(allocation_shared),
         })
     }
 
+    /// Allocates memory that can be shared with other APIs or processes via [`export_handle`](Self::export_handle).
+    ///
+    /// Images and buffers bound to it must be created for [`EXTERNAL_HANDLE_TYPE`], see [`ImageInfo::external_memory`](crate::resources::ImageInfo::external_memory)
+    /// and [`Buffer::external`](crate::resources::Buffer::external). Use [`PhysicalDevice::external_image_properties`](crate::PhysicalDevice::external_image_properties)
+    /// to check they can be exported. Needs [`VideoFeatures::external_memory`](crate::VideoFeatures::external_memory).
+    pub fn new_exportable(device: &Device, size: u64, type_index: MemoryTypeIndex) -> Result<Self, Error> {
+        let allocation_shared = AllocationShared::new_exportable(device.shared(), size, type_index)?;
+
+        Ok(Self {
+            shared: Arc::new(allocation_shared),
+        })
+    }
+
+    /// Wraps memory allocated by someone else, e.g., a block of `gpu-allocator`, so resources in it can be used with this crate.
+    ///
+    /// If `owned`, the memory is freed once this (and everything bound to it) is dropped, otherwise that's up to its owner.
+    ///
+    /// # Safety
+    ///
+    /// The memory must have been allocated from `device` with `size` and `type_index`, must not be mapped while this crate
+    /// maps it, and must outlive this and everything bound to it.
+    pub unsafe fn from_raw(device: &Device, memory: DeviceMemory, size: u64, type_index: MemoryTypeIndex, owned: bool) -> Self {
+        // SAFETY: Forwarded to our caller.
+        let allocation_shared = unsafe { AllocationShared::from_native(device.shared(), memory, size, type_index, owned) };
+
+        Self {
+            shared: Arc::new(allocation_shared),
+        }
+    }
+
+    /// Wraps memory another allocator sub-allocated, e.g., to implement an [`Allocator`](crate::Allocator) on top of `vk-mem`.
+    ///
+    /// `size` must cover the memory up to the end of the sub-allocation. If the allocator keeps the memory mapped, `mapped`
+    /// must point to its start (not to the sub-allocation), so we use that mapping instead of mapping it again. `free` runs
+    /// once this (and everything bound to it) is dropped, to hand the sub-allocation back.
+    ///
+    /// # Safety
+    ///
+    /// The memory must have been allocated from `device` with `type_index`, be at least `size` bytes, and must outlive
+    /// this and everything bound to it. `mapped` must stay valid until `free` ran.
+    pub unsafe fn from_allocator(
+        device: &Device,
+        memory: DeviceMemory,
+        size: u64,
+        type_index: MemoryTypeIndex,
+        mapped: Option<NonNull<u8>>,
+        free: impl FnOnce() + Send + Sync + 'static,
+    ) -> Self {
+        // SAFETY: Forwarded to our caller.
+        let allocation_shared =
+            unsafe { AllocationShared::from_allocator(device.shared(), memory, size, type_index, mapped, Box::new(free)) };
+
+        Self {
+            shared: Arc::new(allocation_shared),
+        }
+    }
+
+    /// Imports memory exported as opaque POSIX file descriptor, e.g., by a renderer on another Vulkan instance.
+    ///
+    /// `size` and `type_index` have to match the memory requirements of the resource it's bound to, usually an image
+    /// created with [`ImageInfo::external_memory`](crate::resources::ImageInfo::external_memory). On success, Vulkan
+    /// takes over `fd`, otherwise it's closed. Needs [`VideoFeatures::external_memory_fd`](crate::VideoFeatures::external_memory_fd).
+    #[cfg(unix)]
+    pub fn import_fd(device: &Device, fd: std::os::fd::OwnedFd, size: u64, type_index: MemoryTypeIndex) -> Result<Self, Error> {
+        let allocation_shared = AllocationShared::import_fd(device.shared(), fd, size, type_index)?;
+
+        Ok(Self {
+            shared: Arc::new(allocation_shared),
+        })
+    }
+
+    /// Imports memory another process exported via [`export_handle`](Self::export_handle).
+    ///
+    /// `size` and `type_index` have to be the ones of the exporting allocation, see [`ExportedFrame`](crate::ExportedFrame), and resources
+    /// bound to it must be created for [`EXTERNAL_HANDLE_TYPE`]. Needs [`VideoFeatures::external_memory`](crate::VideoFeatures::external_memory).
+    pub fn import_handle(device: &Device, handle: ExternalHandle, size: u64, type_index: MemoryTypeIndex) -> Result<Self, Error> {
+        let allocation_shared = AllocationShared::import_handle(device.shared(), handle, size, type_index)?;
+
+        Ok(Self {
+            shared: Arc::new(allocation_shared),
+        })
+    }
+
+    /// Imports memory shared by another API on Windows as the memory of `image`, which then has to be bound to it.
+    ///
+    /// Supports `D3D11_TEXTURE` and `D3D12_RESOURCE` (plus their KMT and opaque variants), e.g., a shared `ID3D11Texture2D`.
+    /// The image must be created with [`ImageInfo::external_memory`](crate::resources::ImageInfo::external_memory) set to `handle_type`,
+    /// and D3D11 access synchronized with [`SubmitInfo::acquire_keyed_mutex`](crate::SubmitInfo::acquire_keyed_mutex), D3D12 access with
+    /// a [`Semaphore`](crate::Semaphore) imported from its fence. Needs [`VideoFeatures::external_memory_win32`](crate::VideoFeatures::external_memory_win32).
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be a valid handle of `handle_type`. Vulkan does not take ownership, so the caller still has to close it.
+    pub unsafe fn import_win32_handle(
+        device: &Device,
+        handle: HANDLE,
+        handle_type: ExternalMemoryHandleTypeFlags,
+        image: &Image,
+    ) -> Result<Self, Error> {
+        let allocation_shared = AllocationShared::import_win32_handle(device.shared(), handle, handle_type, image.native())?;
+
+        Ok(Self {
+            shared: Arc::new(allocation_shared),
+        })
+    }
+
+    /// Imports an `AHardwareBuffer`, e.g., a camera frame, as the memory of `image`, which then has to be bound to it.
+    ///
+    /// The image must match the buffer, i.e., be created with [`Image::android_hardware_buffer_format`](crate::resources::Image::android_hardware_buffer_format)
+    /// and [`ImageInfo::external_memory`](crate::resources::ImageInfo::external_memory) set to `ANDROID_HARDWARE_BUFFER_ANDROID`.
+    /// Needs [`VideoFeatures::android_hardware_buffer`](crate::VideoFeatures::android_hardware_buffer).
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be a valid `AHardwareBuffer`. Vulkan acquires its own reference, so the caller may release theirs afterwards.
+    #[cfg(feature = "android")]
+    pub unsafe fn import_android_hardware_buffer(device: &Device, buffer: *mut AHardwareBuffer, image: &Image) -> Result<Self, Error> {
+        let allocation_shared = AllocationShared::import_android_hardware_buffer(device.shared(), buffer, image.native())?;
+
+        Ok(Self {
+            shared: Arc::new(allocation_shared),
+        })
+    }
+
+    /// Exports a new OS handle to this memory, e.g., for another Vulkan instance to import.
+    ///
+    /// Only works for memory created with [`new_exportable`](Self::new_exportable). Each call returns a new handle.
+    pub fn export_handle(&self) -> Result<ExternalHandle, Error> {
+        self.shared.export_handle()
+    }
+
+    /// Size in bytes, e.g., to import an exported handle elsewhere.
+    pub fn size(&self) -> u64 {
+        self.shared.size()
+    }
+
+    /// Memory type the allocation lives in.
+    pub fn type_index(&self) -> MemoryTypeIndex {
+        self.shared.type_index()
+    }
+
+    pub(crate) fn from_shared(shared: Arc<AllocationShared>) -> Self {
+        Self { shared }
+    }
+
     pub(crate) fn shared(&self) -> Arc<AllocationShared> {
         self.shared.clone()
     }
@@ -114,6 +778,11 @@ impl Allocation {
     pub(crate) fn native(&self) -> DeviceMemory {
         self.shared.native()
     }
+
+    /// The raw `VkDeviceMemory`, freed once this (and everything bound to it) is dropped, unless wrapped via [`Self::from_raw`].
+    pub fn raw(&self) -> DeviceMemory {
+        self.shared.native()
+    }
 }
 
 #[cfg(test)]
@@ -124,6 +793,7 @@ mod test {
     use crate::error::{Error, Variant};
     use crate::instance::{Instance, InstanceInfo};
     use crate::physicaldevice::PhysicalDevice;
+    use crate::resources::{Buffer, BufferInfo};
 
     #[test]
     #[cfg(not(miri))]
@@ -141,4 +811,38 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn export_handle() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let device_local = physical_device
+            .heap_infos()
+            .any_device_local()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+
+        if !device.video_features().external_memory() {
+            return Ok(());
+        }
+
+        let exportable = Allocation::new_exportable(&device, 16 * 1024, device_local)?;
+        let regular = Allocation::new(&device, 16 * 1024, device_local)?;
+
+        _ = exportable.export_handle()?;
+        _ = Buffer::external(&exportable, &BufferInfo::new().size(1024))?;
+
+        // The exported fd is moved into the import, and owned by Vulkan afterwards.
+        #[cfg(unix)]
+        if device.video_features().external_memory_fd() {
+            _ = Allocation::import_fd(&device, exportable.export_handle()?, 16 * 1024, device_local)?;
+        }
+
+        assert!(regular.export_handle().is_err());
+        assert!(Buffer::external(&regular, &BufferInfo::new().size(1024)).is_err());
+
+        Ok(())
+    }
 }
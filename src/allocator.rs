@@ -0,0 +1,270 @@
+use crate::allocation::{Allocation, AllocationShared};
+use crate::arena::Arena;
+use crate::device::{Device, DeviceShared};
+use crate::error::Error;
+use crate::resources::MemoryRequirements;
+use ash::vk::MemoryPropertyFlags;
+use std::sync::Arc;
+
+/// Decides where buffers, images and video sessions live in memory.
+///
+/// Resources are bound through an allocator via [`Buffer::allocate`](crate::resources::Buffer::allocate),
+/// [`Image::allocate`](crate::resources::Image::allocate) and [`VideoSessionInfo::allocator`](crate::video::VideoSessionInfo::allocator).
+/// Implement this to place them in memory your application manages anyway, e.g., shared with a renderer:
+///
+/// ```rust,ignore
+/// struct MyAllocator { /* ... */ }
+///
+/// impl Allocator for MyAllocator {
+///     fn allocate(&self, requirements: &MemoryRequirements, flags: MemoryPropertyFlags) -> Result<(Allocation, u64), Error> {
+///         let (memory, offset, size, type_index) = self.my_allocate(requirements, flags);
+///         let allocation = unsafe { Allocation::from_allocator(&self.device, memory, offset + size, type_index, None, move || ...) };
+///
+///         Ok((allocation, offset))
+///     }
+/// }
+/// ```
+pub trait Allocator: Send + Sync {
+    /// Reserves memory for a resource with `requirements`, of a permitted type with all `flags`, and returns the
+    /// allocation and offset to bind the resource at.
+    fn allocate(&self, requirements: &MemoryRequirements, flags: MemoryPropertyFlags) -> Result<(Allocation, u64), Error>;
+}
+
+/// Gives each resource its own allocation, as [`Allocation::for_requirements`] does.
+///
+/// This is what video sessions use unless given another allocator.
+pub struct DedicatedAllocator {
+    shared_device: Arc<DeviceShared>,
+}
+
+impl DedicatedAllocator {
+    pub fn new(device: &Device) -> Self {
+        Self {
+            shared_device: device.shared(),
+        }
+    }
+}
+
+impl Allocator for DedicatedAllocator {
+    fn allocate(&self, requirements: &MemoryRequirements, flags: MemoryPropertyFlags) -> Result<(Allocation, u64), Error> {
+        let allocation_shared = AllocationShared::for_requirements(self.shared_device.clone(), requirements, flags)?;
+
+        Ok((Allocation::from_shared(Arc::new(allocation_shared)), 0))
+    }
+}
+
+impl Allocator for Arena {
+    fn allocate(&self, requirements: &MemoryRequirements, flags: MemoryPropertyFlags) -> Result<(Allocation, u64), Error> {
+        Arena::allocate(self, requirements, flags)
+    }
+}
+
+#[cfg(feature = "gpu-allocator")]
+pub use gpu::GpuAllocator;
+
+#[cfg(feature = "gpu-allocator")]
+mod gpu {
+    use crate::allocation::{Allocation, AllocationShared};
+    use crate::allocator::Allocator;
+    use crate::device::{Device, DeviceShared};
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::resources::MemoryRequirements;
+    use ash::vk::MemoryPropertyFlags;
+    use gpu_allocator::vulkan::{AllocationCreateDesc, AllocationScheme, AllocatorCreateDesc};
+    use gpu_allocator::{AllocationError, AllocationSizes, AllocatorDebugSettings, MemoryLocation};
+    use std::ptr::NonNull;
+    use std::sync::{Arc, Mutex, PoisonError};
+
+    /// Allocates through [`gpu-allocator`](https://crates.io/crates/gpu-allocator), e.g., one a renderer uses as well.
+    ///
+    /// Host visible memory stays mapped by `gpu-allocator`, uploads and downloads use that mapping.
+    ///
+    /// ```rust,ignore
+    /// let allocator = Arc::new(GpuAllocator::new(&device)?);
+    /// let buffer = Buffer::allocate(&device, allocator.as_ref(), &BufferInfo::new().size(1024), MemoryPropertyFlags::HOST_VISIBLE)?;
+    /// let session = VideoSession::new(&device, &inspector, &VideoSessionInfo::new().allocator(allocator))?;
+    /// ```
+    pub struct GpuAllocator {
+        shared_device: Arc<DeviceShared>,
+        allocator: Arc<Mutex<gpu_allocator::vulkan::Allocator>>,
+    }
+
+    impl GpuAllocator {
+        /// Creates a new `gpu-allocator` for `device`.
+        pub fn new(device: &Device) -> Result<Self, Error> {
+            let shared_device = device.shared();
+            let desc = AllocatorCreateDesc {
+                instance: shared_device.instance().native(),
+                device: shared_device.native(),
+                physical_device: shared_device.physical_device().native(),
+                debug_settings: AllocatorDebugSettings::default(),
+                buffer_device_address: false,
+                allocation_sizes: AllocationSizes::default(),
+            };
+
+            let allocator = gpu_allocator::vulkan::Allocator::new(&desc).map_err(convert)?;
+
+            Ok(Self {
+                shared_device,
+                allocator: Arc::new(Mutex::new(allocator)),
+            })
+        }
+
+        /// Allocates through an existing `allocator`, which must have been created for `device`.
+        pub fn from_allocator(device: &Device, allocator: Arc<Mutex<gpu_allocator::vulkan::Allocator>>) -> Self {
+            Self {
+                shared_device: device.shared(),
+                allocator,
+            }
+        }
+
+        /// The wrapped `gpu-allocator`, e.g., to allocate your own resources from it.
+        pub fn allocator(&self) -> Arc<Mutex<gpu_allocator::vulkan::Allocator>> {
+            self.allocator.clone()
+        }
+    }
+
+    impl Allocator for GpuAllocator {
+        fn allocate(&self, requirements: &MemoryRequirements, flags: MemoryPropertyFlags) -> Result<(Allocation, u64), Error> {
+            let location = if flags.contains(MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_CACHED) {
+                MemoryLocation::GpuToCpu
+            } else if flags.contains(MemoryPropertyFlags::HOST_VISIBLE) {
+                MemoryLocation::CpuToGpu
+            } else {
+                MemoryLocation::GpuOnly
+            };
+
+            // We don't know if images are linear, so we keep every resource apart by the granularity instead.
+            let granularity = self.shared_device.physical_device().buffer_image_granularity().max(1);
+            let native_requirements = ash::vk::MemoryRequirements::default()
+                .size(requirements.size().next_multiple_of(granularity))
+                .alignment(requirements.alignment().max(granularity))
+                .memory_type_bits(requirements.memory_type_bits());
+
+            let desc = AllocationCreateDesc {
+                name: "vulkan_video",
+                requirements: native_requirements,
+                location,
+                linear: true,
+                allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+            };
+
+            let allocation = self
+                .allocator
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .allocate(&desc)
+                .map_err(convert)?;
+
+            // The location only states preferences, other types might lack some of the flags. It also doesn't tell us
+            // the type, but picks the first permitted one with the properties it wants, so it should be this one.
+            let properties = allocation.memory_properties();
+            let type_index = self
+                .shared_device
+                .physical_device()
+                .heap_infos()
+                .find_type(requirements.memory_type_bits(), properties, MemoryPropertyFlags::empty())
+                .filter(|_| properties.contains(flags));
+
+            let Some(type_index) = type_index else {
+                _ = self.allocator.lock().unwrap_or_else(PoisonError::into_inner).free(allocation);
+                return Err(error!(Variant::HeapNotFound, "No memory type with {flags:?}, got {properties:?}."));
+            };
+
+            let offset = allocation.offset();
+            let size = offset + allocation.size();
+            let memory = unsafe { allocation.memory() };
+            // SAFETY: Blocks are mapped as a whole, the pointer to the sub-allocation is `offset` bytes into it.
+            let mapped = allocation
+                .mapped_ptr()
+                .map(|x| unsafe { NonNull::new_unchecked(x.as_ptr().cast::<u8>().sub(offset as usize)) });
+            let allocator = self.allocator.clone();
+            let free = move || {
+                _ = allocator.lock().unwrap_or_else(PoisonError::into_inner).free(allocation);
+            };
+
+            // SAFETY: The memory stays allocated until we hand it back, which happens once nothing is bound to it anymore.
+            let allocation_shared =
+                unsafe { AllocationShared::from_allocator(self.shared_device.clone(), memory, size, type_index, mapped, Box::new(free)) };
+            let allocation = Allocation::from_shared(Arc::new(allocation_shared));
+
+            Ok((allocation, offset))
+        }
+    }
+
+    fn convert(e: AllocationError) -> Error {
+        match e {
+            AllocationError::OutOfMemory => error!(Variant::Vulkan(ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY), "gpu-allocator: {e}"),
+            AllocationError::NoCompatibleMemoryTypeFound => error!(Variant::HeapNotFound, "gpu-allocator: {e}"),
+            _ => error!(Variant::Vulkan(ash::vk::Result::ERROR_UNKNOWN), "gpu-allocator: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocator::{Allocator, DedicatedAllocator};
+    use crate::arena::Arena;
+    use crate::device::Device;
+    use crate::error::Error;
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::resources::{Buffer, BufferInfo};
+    use crate::video::h264::H264StreamInspector;
+    use crate::video::{VideoSession, VideoSessionInfo};
+    use ash::vk::MemoryPropertyFlags;
+    use std::sync::Arc;
+
+    fn upload_download(device: &Device, allocator: &dyn Allocator) -> Result<(), Error> {
+        let buffer = Buffer::allocate(device, allocator, &BufferInfo::new().size(1024), MemoryPropertyFlags::HOST_VISIBLE)?;
+        let mut data = vec![0; 1024];
+
+        buffer.upload(&[7; 1024])?;
+        buffer.download_into(&mut data)?;
+
+        assert_eq!(data, [7; 1024]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn allocate_through() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let arena = Arc::new(Arena::new(&device, 16 * 1024 * 1024));
+
+        upload_download(&device, &DedicatedAllocator::new(&device))?;
+        upload_download(&device, arena.as_ref())?;
+
+        let count = arena.allocation_count();
+        let info = VideoSessionInfo::new().allocator(arena.clone());
+        _ = VideoSession::new(&device, &H264StreamInspector::new(), &info)?;
+
+        assert!(arena.allocation_count() > count);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(not(miri), feature = "gpu-allocator"))]
+    fn gpu_allocator() -> Result<(), Error> {
+        use crate::allocator::GpuAllocator;
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let allocator = Arc::new(GpuAllocator::new(&device)?);
+
+        upload_download(&device, allocator.as_ref())?;
+
+        let info = VideoSessionInfo::new().allocator(allocator);
+        _ = VideoSession::new(&device, &H264StreamInspector::new(), &info)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+use crate::allocation::{Allocation, AllocationShared};
+use crate::device::{Device, DeviceShared};
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::resources::{Buffer, BufferInfo, BufferShared, Image, MemoryRequirements};
+use ash::vk::MemoryPropertyFlags;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// A large allocation resources are placed in one after another.
+struct Block {
+    allocation: Arc<AllocationShared>,
+    used: u64,
+}
+
+/// Places many buffers and images in a few large [`Allocation`]s, one (or more) per memory type.
+///
+/// Drivers limit how many allocations may exist at once (often to 4096), which decoding several streams with their
+/// own buffers and images hits quickly. Resources are never moved or freed individually, a block is freed once the arena
+/// and all resources in it are dropped. This suits resources living as long as a decoder, not ones created per frame:
+///
+/// ```rust,ignore
+/// let arena = Arena::new(&device, 64 * 1024 * 1024);
+///
+/// let bitstream = arena.buffer(&BufferInfo::new().size(1024 * 1024), MemoryPropertyFlags::HOST_VISIBLE)?;
+/// let image = arena.bind(Image::new(&device, &image_info)?, MemoryPropertyFlags::DEVICE_LOCAL)?;
+/// ```
+pub struct Arena {
+    shared_device: Arc<DeviceShared>,
+    block_size: u64,
+    blocks: Mutex<Vec<Block>>,
+}
+
+impl Arena {
+    /// Creates an empty arena, allocating blocks of `block_size` bytes (or larger, for larger resources) as needed.
+    pub fn new(device: &Device, block_size: u64) -> Self {
+        Self {
+            shared_device: device.shared(),
+            block_size,
+            blocks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Creates a buffer as [`Buffer::new`] would, in memory with all `flags`, the offset of `info` is ignored.
+    pub fn buffer(&self, info: &BufferInfo, flags: MemoryPropertyFlags) -> Result<Buffer, Error> {
+        let requirements = BufferShared::memory_requirements(&self.shared_device, info);
+        let (allocation, offset) = self.allocate(&requirements, flags)?;
+
+        Buffer::new(&allocation, &info.clone().offset(offset))
+    }
+
+    /// Binds `image` to memory with all `flags`, like [`Image::bind`].
+    pub fn bind(&self, image: Image, flags: MemoryPropertyFlags) -> Result<Image, Error> {
+        image.allocate(self, flags)
+    }
+
+    /// Reserves memory for a resource with `requirements`, returns the allocation and offset to bind it at.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Variant::HeapNotFound`] if no permitted memory type has all `flags`.
+    pub fn allocate(&self, requirements: &MemoryRequirements, flags: MemoryPropertyFlags) -> Result<(Allocation, u64), Error> {
+        let shared_physical_device = self.shared_device.physical_device();
+        let type_index = shared_physical_device
+            .heap_infos()
+            .find_type(requirements.memory_type_bits(), flags, MemoryPropertyFlags::empty())
+            .ok_or_else(|| {
+                error!(
+                    Variant::HeapNotFound,
+                    "No memory type with {flags:?} in {:#b}.",
+                    requirements.memory_type_bits()
+                )
+            })?;
+
+        // Also keeps linear and optimal resources apart, as we don't know which is which.
+        let alignment = requirements
+            .alignment()
+            .max(shared_physical_device.buffer_image_granularity())
+            .max(1);
+        let mut blocks = self.blocks.lock().unwrap_or_else(PoisonError::into_inner);
+
+        for block in blocks
+            .iter_mut()
+            .filter(|x| x.allocation.type_index().index() == type_index.index())
+        {
+            let offset = block.used.next_multiple_of(alignment);
+
+            if offset + requirements.size() <= block.allocation.size() {
+                block.used = offset + requirements.size();
+                return Ok((Allocation::from_shared(block.allocation.clone()), offset));
+            }
+        }
+
+        let size = self.block_size.max(requirements.size());
+        let allocation = Arc::new(AllocationShared::new(self.shared_device.clone(), size, type_index)?);
+
+        blocks.push(Block {
+            allocation: allocation.clone(),
+            used: requirements.size(),
+        });
+
+        Ok((Allocation::from_shared(allocation), 0))
+    }
+
+    /// Number of blocks allocated so far, i.e., how many device allocations this arena holds.
+    pub fn allocation_count(&self) -> usize {
+        self.blocks.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    /// Bytes of all blocks in use by resources, including padding for alignment.
+    pub fn used(&self) -> u64 {
+        self.blocks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|x| x.used)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::arena::Arena;
+    use crate::device::Device;
+    use crate::error::Error;
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::resources::{BufferInfo, Image, ImageInfo};
+    use ash::vk::{Extent3D, Format, ImageTiling, ImageType, ImageUsageFlags, MemoryPropertyFlags, SampleCountFlags};
+
+    #[test]
+    #[cfg(not(miri))]
+    fn sub_allocate() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let arena = Arena::new(&device, 16 * 1024 * 1024);
+        let image_info = ImageInfo::new()
+            .format(Format::R8G8B8A8_UNORM)
+            .samples(SampleCountFlags::TYPE_1)
+            .usage(ImageUsageFlags::TRANSFER_SRC | ImageUsageFlags::TRANSFER_DST)
+            .mip_levels(1)
+            .array_layers(1)
+            .image_type(ImageType::TYPE_2D)
+            .tiling(ImageTiling::OPTIMAL)
+            .extent(Extent3D::default().width(64).height(64).depth(1));
+
+        let buffer_a = arena.buffer(&BufferInfo::new().size(1024), MemoryPropertyFlags::HOST_VISIBLE)?;
+        let buffer_b = arena.buffer(&BufferInfo::new().size(1024), MemoryPropertyFlags::HOST_VISIBLE)?;
+        let image = arena.bind(Image::new(&device, &image_info)?, MemoryPropertyFlags::DEVICE_LOCAL)?;
+
+        buffer_a.upload(&[1; 1024])?;
+        buffer_b.upload(&[2; 1024])?;
+
+        let mut data = vec![0; 1024];
+        buffer_a.download_into(&mut data)?;
+
+        assert_eq!(data, [1; 1024]);
+        assert!(arena.used() >= 2048 + image.memory_requirement().size());
+        assert!(arena.allocation_count() <= 2);
+
+        // Too large for a block, gets its own.
+        let count = arena.allocation_count();
+        _ = arena.buffer(&BufferInfo::new().size(32 * 1024 * 1024), MemoryPropertyFlags::DEVICE_LOCAL)?;
+
+        assert_eq!(arena.allocation_count(), count + 1);
+
+        Ok(())
+    }
+}
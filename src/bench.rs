@@ -0,0 +1,260 @@
+//! Measures what the current device can do, e.g., to compare GPUs for transcoding.
+//!
+//! All measurements use the same operations as regular code, so they reflect what applications built on this crate
+//! will see:
+//!
+//! ```rust,ignore
+//! let decode = bench::decode_throughput(&device, h264_data)?;
+//! let copy = bench::copy_bandwidth(&device, 64 * 1024 * 1024, 10)?;
+//! let dispatch = bench::dispatch_latency(&device, 100)?;
+//!
+//! println!("{:.1} fps, {:.1} GB/s, {:?}", decode.frames_per_second(), copy.bytes_per_second() / 1e9, dispatch.median());
+//! ```
+use crate::allocation::Allocation;
+use crate::commandbuffer::CommandBuffer;
+use crate::device::Device;
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::ops::{ColorMatrix, ConvertNv12ToRgba, CopyBuffer2Buffer, WriteTimestamp};
+use crate::queue::Queue;
+use crate::resources::{Buffer, BufferInfo, BufferShared, QueryPool};
+use crate::video::h264::H264Decoder;
+use ash::vk::{Extent2D, MemoryPropertyFlags};
+use std::time::{Duration, Instant};
+
+/// Durations of repeated runs of the same measurement.
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    samples: Vec<Duration>,
+}
+
+impl Timings {
+    fn new(mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        Self { samples }
+    }
+
+    /// All samples, sorted from fastest to slowest.
+    pub fn samples(&self) -> &[Duration] {
+        &self.samples
+    }
+
+    pub fn min(&self) -> Duration {
+        self.samples.first().copied().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples.last().copied().unwrap_or_default()
+    }
+
+    pub fn median(&self) -> Duration {
+        self.samples.get(self.samples.len() / 2).copied().unwrap_or_default()
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.samples.len() {
+            0 => Duration::ZERO,
+            n => self.samples.iter().sum::<Duration>() / n as u32,
+        }
+    }
+}
+
+/// Result of [`decode_throughput`].
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeThroughput {
+    frames: u64,
+    elapsed: Duration,
+}
+
+impl DecodeThroughput {
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Wall clock time for decoding (and reading back) all frames.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Result of [`copy_bandwidth`].
+#[derive(Debug, Clone)]
+pub struct CopyBandwidth {
+    bytes: u64,
+    timings: Timings,
+}
+
+impl CopyBandwidth {
+    /// Bytes copied per run.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// GPU time of each run.
+    pub fn timings(&self) -> &Timings {
+        &self.timings
+    }
+
+    /// Bytes per second, from the median run.
+    pub fn bytes_per_second(&self) -> f64 {
+        self.bytes as f64 / self.timings.median().as_secs_f64()
+    }
+}
+
+/// Decodes the H.264 `bitstream` as fast as possible, including reading frames back to the host.
+pub fn decode_throughput(device: &Device, bitstream: &[u8]) -> Result<DecodeThroughput, Error> {
+    let mut decoder = H264Decoder::new_for_stream(device, bitstream)?;
+    let started = Instant::now();
+    let mut frames = 0;
+
+    for frame in decoder.frames(bitstream) {
+        frame?;
+        frames += 1;
+    }
+
+    Ok(DecodeThroughput {
+        frames,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// A device local buffer of `size` bytes, in its own allocation.
+fn device_local_buffer(device: &Device, size: u64) -> Result<Buffer, Error> {
+    let info = BufferInfo::new().size(size);
+    let requirements = BufferShared::memory_requirements(&device.shared(), &info);
+    let allocation = Allocation::for_requirements(device, &requirements, MemoryPropertyFlags::DEVICE_LOCAL)?;
+
+    Buffer::new(&allocation, &info)
+}
+
+/// Copies `size` bytes between device local buffers `runs` times, timed on the GPU.
+///
+/// Fails with [`Variant::ParameterMismatch`] if `size` or `runs` is 0.
+pub fn copy_bandwidth(device: &Device, size: u64, runs: u32) -> Result<CopyBandwidth, Error> {
+    if size == 0 || runs == 0 {
+        return Err(error!(
+            Variant::ParameterMismatch,
+            "Need at least 1 byte and 1 run, got {size} and {runs}."
+        ));
+    }
+
+    let queue_family = device
+        .shared()
+        .physical_device()
+        .queue_family_infos()
+        .any_compute()
+        .ok_or_else(|| error!(Variant::QueueNotFound))?;
+
+    let queue = Queue::new(device, queue_family, 0)?;
+    let command_buffer = CommandBuffer::new(device, queue_family)?;
+    let queries = QueryPool::new(device, 2)?;
+    let source = device_local_buffer(device, size)?;
+    let destination = device_local_buffer(device, size)?;
+    let copy = CopyBuffer2Buffer::new(&source, &destination, size);
+
+    queue.record(&command_buffer, |x| {
+        x.run(&WriteTimestamp::new(&queries, 0))?;
+        x.run(&copy)?;
+        x.run(&WriteTimestamp::new(&queries, 1))
+    })?;
+
+    let mut samples = Vec::with_capacity(runs as usize);
+
+    for _ in 0..runs {
+        queue.replay(&command_buffer)?.wait(u64::MAX)?;
+        samples.push(queries.elapsed(0, 1)?.unwrap_or_default());
+    }
+
+    Ok(CopyBandwidth {
+        bytes: size,
+        timings: Timings::new(samples),
+    })
+}
+
+/// Measures the wall clock time from submitting a tiny compute dispatch until the host sees it completed, `runs` times.
+///
+/// Fails with [`Variant::ParameterMismatch`] if `runs` is 0.
+pub fn dispatch_latency(device: &Device, runs: u32) -> Result<Timings, Error> {
+    if runs == 0 {
+        return Err(error!(Variant::ParameterMismatch, "Need at least 1 run."));
+    }
+
+    let queue_family = device
+        .shared()
+        .physical_device()
+        .queue_family_infos()
+        .any_compute()
+        .ok_or_else(|| error!(Variant::QueueNotFound))?;
+
+    let extent = Extent2D { width: 16, height: 16 };
+    let nv12_size = 16 * 16 * 3 / 2;
+    let rgba_size = 16 * 16 * 4;
+
+    let queue = Queue::new(device, queue_family, 0)?;
+    let command_buffer = CommandBuffer::new(device, queue_family)?;
+    let nv12 = device_local_buffer(device, nv12_size)?;
+    let rgba = device_local_buffer(device, rgba_size)?;
+    let convert = ConvertNv12ToRgba::new(device, &nv12, &rgba, extent, ColorMatrix::Bt709)?;
+
+    queue.record(&command_buffer, |x| x.run(&convert))?;
+
+    let mut samples = Vec::with_capacity(runs as usize);
+
+    for _ in 0..runs {
+        let started = Instant::now();
+        queue.replay(&command_buffer)?.wait(u64::MAX)?;
+        samples.push(started.elapsed());
+    }
+
+    Ok(Timings::new(samples))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bench::{copy_bandwidth, decode_throughput, dispatch_latency, Timings};
+    use crate::device::Device;
+    use crate::error::Error;
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use std::time::Duration;
+
+    #[test]
+    fn timings() {
+        let timings = Timings::new(vec![Duration::from_millis(3), Duration::from_millis(1), Duration::from_millis(2)]);
+
+        assert_eq!(timings.min(), Duration::from_millis(1));
+        assert_eq!(timings.median(), Duration::from_millis(2));
+        assert_eq!(timings.mean(), Duration::from_millis(2));
+        assert_eq!(timings.max(), Duration::from_millis(3));
+        assert_eq!(Timings::default().mean(), Duration::ZERO);
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn measure() -> Result<(), Error> {
+        let h264_data = include_bytes!("../tests/videos/multi_512x512.h264");
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+
+        let decode = decode_throughput(&device, h264_data)?;
+        let copy = copy_bandwidth(&device, 1024 * 1024, 4)?;
+        let dispatch = dispatch_latency(&device, 4)?;
+
+        assert!(decode.frames() > 0);
+        assert_eq!(copy.timings().samples().len(), 4);
+        assert!(copy.bytes_per_second() > 0.0);
+        assert!(dispatch.min() <= dispatch.max());
+        assert!(copy_bandwidth(&device, 0, 4).is_err());
+        assert!(copy_bandwidth(&device, 1024, 0).is_err());
+        assert!(dispatch_latency(&device, 0).is_err());
+
+        Ok(())
+    }
+}
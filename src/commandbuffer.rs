@@ -1,17 +1,28 @@
 use crate::device::{Device, DeviceShared};
 use crate::error;
 use crate::error::{Error, Variant};
-use ash::vk::{CommandBufferAllocateInfo, CommandBufferLevel, CommandPoolCreateFlags, CommandPoolCreateInfo};
-use std::sync::Arc;
+use ash::vk::{
+    CommandBufferAllocateInfo, CommandBufferLevel, CommandBufferUsageFlags, CommandPoolCreateFlags, CommandPoolCreateInfo,
+    CommandPoolResetFlags, CommandPoolTrimFlags, Fence,
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
 
-#[allow(unused)]
-pub(crate) struct CommandBufferShared {
+/// Marks command buffers with nothing (valid) recorded.
+const NOT_RECORDED: u64 = u64::MAX;
+
+pub(crate) struct CommandPoolShared {
     shared_device: Arc<DeviceShared>,
     native_command_pool: ash::vk::CommandPool,
-    native_command_buffer: ash::vk::CommandBuffer,
+    /// Held while recording into (or allocating, freeing) any of its buffers, as pools must not be used from multiple threads at once.
+    recording: Mutex<()>,
+    /// Incremented on reset, which invalidates everything recorded before.
+    generation: AtomicU64,
+    /// Buffers allocated from the pool, which must not be pending when it's reset.
+    buffers: Mutex<Vec<Weak<CommandBufferShared>>>,
 }
 
-impl CommandBufferShared {
+impl CommandPoolShared {
     pub fn new(shared_device: Arc<DeviceShared>, queue_family_index: u32) -> Result<Self, Error> {
         let native_device = shared_device.native();
 
@@ -22,41 +33,247 @@ impl CommandBufferShared {
         unsafe {
             let native_command_pool = native_device.create_command_pool(&command_pool_create_info, None)?;
 
-            let command_buffer_alloc_info = CommandBufferAllocateInfo::default()
-                .command_pool(native_command_pool)
-                .command_buffer_count(1)
-                .level(CommandBufferLevel::PRIMARY);
-
-            let native_command_buffer = native_device
-                .allocate_command_buffers(&command_buffer_alloc_info)?
-                .pop()
-                .ok_or_else(|| error!(Variant::NoCommandBuffer))?;
-
             Ok(Self {
                 shared_device,
                 native_command_pool,
-                native_command_buffer,
+                recording: Mutex::new(()),
+                generation: AtomicU64::new(0),
+                buffers: Mutex::new(Vec::new()),
             })
         }
     }
 
+    pub(crate) fn lock_recording(&self) -> MutexGuard<'_, ()> {
+        self.recording.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Notes `buffer` was allocated from the pool, forgetting those dropped since.
+    fn register(&self, buffer: &Arc<CommandBufferShared>) {
+        let mut buffers = self.buffers.lock().unwrap_or_else(PoisonError::into_inner);
+
+        buffers.retain(|x| x.strong_count() > 0);
+        buffers.push(Arc::downgrade(buffer));
+    }
+
+    /// Resets all buffers of the pool, after waiting for their pending submissions.
+    pub fn reset(&self) -> Result<(), Error> {
+        let native_device = self.shared_device.native();
+        let buffers = self
+            .buffers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect::<Vec<_>>();
+
+        // Held so none of them is submitted again before the reset.
+        let _busy = buffers.iter().map(|x| x.lock()).collect::<Vec<_>>();
+
+        // Resetting the pool resets its buffers, which is invalid while they are pending.
+        for buffer in &buffers {
+            buffer.wait_until_executed()?;
+        }
+
+        let _recording = self.lock_recording();
+
+        unsafe {
+            native_device.reset_command_pool(self.native_command_pool, CommandPoolResetFlags::empty())?;
+        }
+
+        self.generation.fetch_add(1, Ordering::AcqRel);
+
+        Ok(())
+    }
+
+    pub fn trim(&self) {
+        let native_device = self.shared_device.native();
+        let _recording = self.lock_recording();
+
+        unsafe {
+            native_device.trim_command_pool(self.native_command_pool, CommandPoolTrimFlags::empty());
+        }
+    }
+}
+
+impl Drop for CommandPoolShared {
+    fn drop(&mut self) {
+        let device = self.shared_device.native();
+
+        unsafe {
+            device.destroy_command_pool(self.native_command_pool, None);
+        }
+    }
+}
+
+#[allow(unused)]
+pub(crate) struct CommandBufferShared {
+    shared_device: Arc<DeviceShared>,
+    shared_pool: Arc<CommandPoolShared>,
+    native_command_buffer: ash::vk::CommandBuffer,
+    usage: CommandBufferUsageFlags,
+    /// Held while recording or submitting the buffer, or resetting its pool.
+    busy: Mutex<()>,
+    /// Pool generation the commands were completely recorded in, if they can be submitted (again).
+    recorded: AtomicU64,
+    /// Signaled once the last submission executed, cleared before the fence is reused.
+    in_flight: Mutex<Option<Fence>>,
+}
+
+impl CommandBufferShared {
+    pub fn new(shared_device: Arc<DeviceShared>, queue_family_index: u32, usage: CommandBufferUsageFlags) -> Result<Arc<Self>, Error> {
+        let shared_pool = CommandPoolShared::new(shared_device, queue_family_index)?;
+
+        Self::new_in(Arc::new(shared_pool), usage)
+    }
+
+    pub fn new_in(shared_pool: Arc<CommandPoolShared>, usage: CommandBufferUsageFlags) -> Result<Arc<Self>, Error> {
+        let shared_device = shared_pool.shared_device.clone();
+        let native_device = shared_device.native();
+
+        let command_buffer_alloc_info = CommandBufferAllocateInfo::default()
+            .command_pool(shared_pool.native_command_pool)
+            .command_buffer_count(1)
+            .level(CommandBufferLevel::PRIMARY);
+
+        let native_command_buffer = unsafe {
+            let _recording = shared_pool.lock_recording();
+
+            native_device
+                .allocate_command_buffers(&command_buffer_alloc_info)?
+                .pop()
+                .ok_or_else(|| error!(Variant::NoCommandBuffer))?
+        };
+
+        let shared = Arc::new(Self {
+            shared_device,
+            shared_pool,
+            native_command_buffer,
+            usage,
+            busy: Mutex::new(()),
+            recorded: AtomicU64::new(NOT_RECORDED),
+            in_flight: Mutex::new(None),
+        });
+
+        shared.shared_pool.register(&shared);
+
+        Ok(shared)
+    }
+
     pub(crate) fn native(&self) -> ash::vk::CommandBuffer {
         self.native_command_buffer
     }
+
+    pub(crate) fn usage(&self) -> CommandBufferUsageFlags {
+        self.usage
+    }
+
+    /// Locks this buffer only, e.g., while waiting for its previous submission, so others of the pool can still be used.
+    pub(crate) fn lock(&self) -> MutexGuard<'_, ()> {
+        self.busy.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the whole pool, needed while recording commands.
+    pub(crate) fn lock_recording(&self) -> MutexGuard<'_, ()> {
+        self.shared_pool.lock_recording()
+    }
+
+    pub(crate) fn is_recorded(&self) -> bool {
+        self.recorded.load(Ordering::Acquire) == self.shared_pool.generation.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn set_recorded(&self, recorded: bool) {
+        let generation = match recorded {
+            true => self.shared_pool.generation.load(Ordering::Acquire),
+            false => NOT_RECORDED,
+        };
+
+        self.recorded.store(generation, Ordering::Release);
+    }
+
+    /// Notes the submission signaling `fence` executes the commands.
+    pub(crate) fn set_in_flight(&self, fence: Fence) {
+        *self.in_flight.lock().unwrap_or_else(PoisonError::into_inner) = Some(fence);
+    }
+
+    /// Forgets `fence` once its submission completed, before it's reused.
+    pub(crate) fn clear_in_flight(&self, fence: Fence) {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if *in_flight == Some(fence) {
+            *in_flight = None;
+        }
+    }
+
+    /// Blocks until the last submission executed, as pending command buffers must not be reset.
+    pub(crate) fn wait_until_executed(&self) -> Result<(), Error> {
+        // Held while waiting, so the fence isn't reused meanwhile.
+        let in_flight = self.in_flight.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let Some(fence) = *in_flight else {
+            return Ok(());
+        };
+
+        // SAFETY: Should be safe as the fence isn't released while we hold the lock.
+        let result = unsafe { self.shared_device.native().wait_for_fences(&[fence], true, u64::MAX) };
+
+        self.shared_device.check_lost(result.map_err(Error::from))
+    }
 }
 
 impl Drop for CommandBufferShared {
     fn drop(&mut self) {
         let device = self.shared_device.native();
+        let _recording = self.shared_pool.lock_recording();
 
         unsafe {
-            device.free_command_buffers(self.native_command_pool, &[self.native_command_buffer]);
-            device.destroy_command_pool(self.native_command_pool, None);
+            device.free_command_buffers(self.shared_pool.native_command_pool, &[self.native_command_buffer]);
         }
     }
 }
 
+/// Allocates [`CommandBuffer`]s of one queue family, e.g., many of them for pipelined decoding.
+///
+/// Recording into buffers of the same pool is serialized, use one pool per thread to record in parallel.
+pub struct CommandPool {
+    shared: Arc<CommandPoolShared>,
+}
+
+impl CommandPool {
+    pub fn new(device: &Device, queue_family_index: u32) -> Result<Self, Error> {
+        let shared = CommandPoolShared::new(device.shared(), queue_family_index)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    /// A new command buffer, recorded for repeated submission.
+    pub fn allocate(&self) -> Result<CommandBuffer, Error> {
+        self.allocate_with_usage(CommandBufferUsageFlags::empty())
+    }
+
+    /// A new command buffer, recorded with `usage`, e.g., [`CommandBufferUsageFlags::ONE_TIME_SUBMIT`].
+    pub fn allocate_with_usage(&self, usage: CommandBufferUsageFlags) -> Result<CommandBuffer, Error> {
+        let shared = CommandBufferShared::new_in(self.shared.clone(), usage)?;
+
+        Ok(CommandBuffer { shared })
+    }
+
+    /// Resets all command buffers of this pool at once, which then have to be recorded again.
+    ///
+    /// Blocks until pending submissions of them completed, as they must not be reset while executing.
+    pub fn reset(&self) -> Result<(), Error> {
+        self.shared.reset()
+    }
+
+    /// Returns unused memory of the pool to the driver, e.g., after freeing many command buffers.
+    pub fn trim(&self) {
+        self.shared.trim()
+    }
+}
+
 /// Stores commands related to a specific queue family.
+///
+/// Recording is serialized internally, so a command buffer can be shared between threads. Recording it again waits for
+/// previous submissions to complete, so back-to-back submissions should alternate between command buffers instead.
 #[allow(unused)]
 pub struct CommandBuffer {
     shared: Arc<CommandBufferShared>,
@@ -64,9 +281,17 @@ pub struct CommandBuffer {
 
 impl CommandBuffer {
     pub fn new(device: &Device, queue_family_index: u32) -> Result<Self, Error> {
-        let shared = CommandBufferShared::new(device.shared(), queue_family_index)?;
+        Self::new_with_usage(device, queue_family_index, CommandBufferUsageFlags::empty())
+    }
 
-        Ok(Self { shared: Arc::new(shared) })
+    /// Creates a command buffer (in a pool of its own) recorded with `usage`.
+    ///
+    /// With [`CommandBufferUsageFlags::ONE_TIME_SUBMIT`] drivers may optimize for being submitted once, which then
+    /// rules out [`Queue::replay`](crate::Queue::replay).
+    pub fn new_with_usage(device: &Device, queue_family_index: u32, usage: CommandBufferUsageFlags) -> Result<Self, Error> {
+        let shared = CommandBufferShared::new(device.shared(), queue_family_index, usage)?;
+
+        Ok(Self { shared })
     }
 
     #[allow(unused)]
@@ -77,15 +302,22 @@ impl CommandBuffer {
     pub(crate) fn shared(&self) -> Arc<CommandBufferShared> {
         self.shared.clone()
     }
+
+    /// The raw `VkCommandBuffer`, only record into it while it's not in use by a submission.
+    pub fn raw(&self) -> ash::vk::CommandBuffer {
+        self.shared.native()
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::commandbuffer::CommandBuffer;
+    use crate::commandbuffer::{CommandBuffer, CommandPool};
     use crate::device::Device;
     use crate::error::Error;
     use crate::instance::{Instance, InstanceInfo};
     use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::Queue;
+    use ash::vk::CommandBufferUsageFlags;
 
     #[test]
     #[cfg(not(miri))]
@@ -99,4 +331,36 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn pool_of_many() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, 0, 0)?;
+        let pool = CommandPool::new(&device, 0)?;
+        let command_buffers = (0..8).map(|_| pool.allocate()).collect::<Result<Vec<_>, _>>()?;
+        let one_time = pool.allocate_with_usage(CommandBufferUsageFlags::ONE_TIME_SUBMIT)?;
+
+        for command_buffer in &command_buffers {
+            queue.build_and_submit(command_buffer, |_| Ok(()))?;
+        }
+
+        queue.build_and_submit(&one_time, |_| Ok(()))?;
+
+        assert!(queue.replay(&command_buffers[0])?.wait(u64::MAX)?);
+        assert!(queue.replay(&one_time).is_err());
+
+        // Resetting the pool waits for pending submissions, and invalidates everything recorded in it.
+        let pending = queue.submit(&command_buffers[1], |_| Ok(()))?;
+        pool.reset()?;
+        assert!(pending.is_complete()?);
+        pool.trim();
+
+        assert!(queue.replay(&command_buffers[0]).is_err());
+
+        Ok(())
+    }
 }
@@ -1,60 +1,337 @@
+use crate::allocation::EXTERNAL_HANDLE_TYPE;
 use crate::error;
 use crate::error::{Error, Variant};
 use crate::instance::InstanceShared;
 use crate::physicaldevice::{PhysicalDevice, PhysicalDeviceShared};
-use ash::vk::{DeviceCreateInfo, DeviceQueueCreateInfo, PhysicalDeviceFeatures2, PhysicalDeviceSynchronization2Features};
-use std::sync::Arc;
+use crate::stats::{DeviceCounters, DeviceStats};
+use ash::vk::{
+    DebugUtilsObjectNameInfoEXT, DeviceCreateInfo, DeviceQueueCreateInfo, ExternalMemoryHandleTypeFlags, Handle, PhysicalDeviceFeatures2,
+    PhysicalDeviceSynchronization2Features, PhysicalDeviceTimelineSemaphoreFeatures, PhysicalDeviceVideoMaintenance1FeaturesKHR, TRUE,
+};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Locks guarding each `VkQueue` of a device by `(family, index)`.
+type QueueLocks = HashMap<(u32, u32), Arc<Mutex<()>>>;
+
+/// Optional video features enabled on a [`Device`], depending on what the device supports.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct VideoFeatures {
+    video_maintenance1: bool,
+    encode_h264: bool,
+    external_memory_fd: bool,
+    external_memory_win32: bool,
+    android_hardware_buffer: bool,
+    external_semaphore_fd: bool,
+    external_semaphore_win32: bool,
+    win32_keyed_mutex: bool,
+    timeline_semaphore: bool,
+    push_descriptor: bool,
+}
+
+impl VideoFeatures {
+    /// If `VK_KHR_video_maintenance1` is enabled.
+    pub fn video_maintenance1(&self) -> bool {
+        self.video_maintenance1
+    }
+
+    /// If `VK_KHR_video_encode_queue` and `VK_KHR_video_encode_h264` are enabled, i.e., H.264 can be encoded.
+    pub fn encode_h264(&self) -> bool {
+        self.encode_h264
+    }
+
+    /// If `VK_KHR_external_memory_fd` is enabled, i.e., memory can be imported via [`Allocation::import_fd`](crate::Allocation::import_fd).
+    pub fn external_memory_fd(&self) -> bool {
+        self.external_memory_fd
+    }
+
+    /// If `VK_KHR_external_memory_win32` is enabled, i.e., memory can be exported as Windows `HANDLE`.
+    pub fn external_memory_win32(&self) -> bool {
+        self.external_memory_win32
+    }
+
+    /// If `VK_ANDROID_external_memory_android_hardware_buffer` is enabled, which needs the `android` feature.
+    pub fn android_hardware_buffer(&self) -> bool {
+        self.android_hardware_buffer
+    }
+
+    /// If `VK_KHR_external_semaphore_fd` is enabled, i.e., semaphores can be shared as file descriptors.
+    pub fn external_semaphore_fd(&self) -> bool {
+        self.external_semaphore_fd
+    }
+
+    /// If `VK_KHR_external_semaphore_win32` is enabled, i.e., D3D12 fences can be imported via [`Semaphore::import_win32_handle`](crate::Semaphore::import_win32_handle).
+    pub fn external_semaphore_win32(&self) -> bool {
+        self.external_semaphore_win32
+    }
+
+    /// If `VK_KHR_win32_keyed_mutex` is enabled, i.e., submissions can acquire and release D3D11 keyed mutexes, see [`SubmitInfo`](crate::SubmitInfo).
+    pub fn win32_keyed_mutex(&self) -> bool {
+        self.win32_keyed_mutex
+    }
+
+    /// If timeline semaphores are enabled, which [`Semaphore`](crate::Semaphore) needs.
+    pub fn timeline_semaphore(&self) -> bool {
+        self.timeline_semaphore
+    }
+
+    /// If memory can be exported as [`ExternalHandle`](crate::ExternalHandle) on this platform, see [`Allocation::export_handle`](crate::Allocation::export_handle).
+    pub fn external_memory(&self) -> bool {
+        match EXTERNAL_HANDLE_TYPE {
+            ExternalMemoryHandleTypeFlags::OPAQUE_WIN32 => self.external_memory_win32,
+            _ => self.external_memory_fd,
+        }
+    }
+
+    /// If semaphores can be exported as [`ExternalHandle`](crate::ExternalHandle) on this platform, see [`Semaphore::export_handle`](crate::Semaphore::export_handle).
+    pub fn external_semaphore(&self) -> bool {
+        match EXTERNAL_HANDLE_TYPE {
+            ExternalMemoryHandleTypeFlags::OPAQUE_WIN32 => self.external_semaphore_win32,
+            _ => self.external_semaphore_fd,
+        }
+    }
+
+    /// If `VK_KHR_push_descriptor` is enabled, i.e., [`Compute`](crate::ops::Compute) pushes its parameters instead of allocating descriptor sets.
+    pub fn push_descriptor(&self) -> bool {
+        self.push_descriptor
+    }
+
+    /// If queries are recorded as part of video operations, instead of around them.
+    pub fn inline_queries(&self) -> bool {
+        self.video_maintenance1
+    }
+
+    /// If video buffers can be created without knowing the stream profile, see [`Buffer::new_video_decode_any`](crate::resources::Buffer::new_video_decode_any).
+    pub fn profile_independent_resources(&self) -> bool {
+        self.video_maintenance1
+    }
+}
 
 #[allow(unused)]
 pub(crate) struct DeviceShared {
     native_device: ash::Device,
     shared_physical_device: Arc<PhysicalDeviceShared>,
+    owned: bool,
+    video_features: VideoFeatures,
+    push_descriptor: Option<ash::khr::push_descriptor::Device>,
+    external_memory_fd: Option<ash::khr::external_memory_fd::Device>,
+    external_memory_win32: Option<ash::khr::external_memory_win32::Device>,
+    android_hardware_buffer: Option<ash::android::external_memory_android_hardware_buffer::Device>,
+    external_semaphore_fd: Option<ash::khr::external_semaphore_fd::Device>,
+    external_semaphore_win32: Option<ash::khr::external_semaphore_win32::Device>,
+    debug_utils: Option<ash::ext::debug_utils::Device>,
+    /// Numbers default object names, e.g., `vulkan_video::Buffer#3`.
+    object_count: AtomicU64,
+    /// Queue families the device was created with, and the priorities of their queues, empty for devices created elsewhere.
+    queues: Vec<(u32, Vec<f32>)>,
+    /// Set once any call reported `VK_ERROR_DEVICE_LOST`, after which the device is unusable.
+    lost: AtomicBool,
+    counters: DeviceCounters,
+    /// Held while using a `VkQueue` by `(family, index)`, shared by all [`Queue`](crate::Queue)s wrapping the same queue.
+    queue_locks: Mutex<QueueLocks>,
 }
 
 impl DeviceShared {
-    pub(crate) fn new_with_families(shared_physical_device: Arc<PhysicalDeviceShared>, queue_families: &[u32]) -> Result<Self, Error> {
-        let native_instance = shared_physical_device.instance().native();
+    pub(crate) fn new_with_queues(shared_physical_device: Arc<PhysicalDeviceShared>, queues: &[(u32, &[f32])]) -> Result<Self, Error> {
+        for (family, priorities) in queues {
+            let available = shared_physical_device.queue_family_infos().queue_count(*family);
+
+            if priorities.is_empty() || priorities.len() > available as usize {
+                return Err(error!(
+                    Variant::QueueNotFound,
+                    "Queue family {family} has {available} queues, {} requested.",
+                    priorities.len()
+                ));
+            }
+
+            if let Some(priority) = priorities.iter().find(|x| !(0.0..=1.0).contains(*x)) {
+                return Err(error!(
+                    Variant::ParameterMismatch,
+                    "Queue priority {priority} is not within 0.0 and 1.0."
+                ));
+            }
+        }
 
-        // SAFETY: Should be safe as native instance is valid.
-        let mut physical_devices = unsafe { native_instance.enumerate_physical_devices()? };
-        let native_physical_device = physical_devices.pop().ok_or_else(|| error!(Variant::NoVideoDevice))?;
+        let native_instance = shared_physical_device.instance().native();
+        let native_physical_device = shared_physical_device.native();
 
         // TODO: ... MAKE THIS PUBLIC AND
         // SAFETY: Should be safe as native instance and physical device are valid.
         // let (queue_family_index, queue_index) =
         //     unsafe { video_decode_queue(native_instance.clone(), native_physical_device).ok_or_else(|| error::NoVideoDevice)? };
 
-        let device_extensions = [
-            c"VK_KHR_video_queue".as_ptr().cast(),
-            c"VK_KHR_video_decode_queue".as_ptr().cast(),
-            c"VK_KHR_video_decode_h264".as_ptr().cast(),
-        ];
+        let mut device_extensions = vec![c"VK_KHR_video_queue", c"VK_KHR_video_decode_queue", c"VK_KHR_video_decode_h264"];
+
+        // SAFETY: Should be safe as native instance and physical device are valid.
+        let available_extensions = unsafe { native_instance.enumerate_device_extension_properties(native_physical_device)? };
+        let has_extension = |name: &CStr| available_extensions.iter().any(|x| x.extension_name_as_c_str() == Ok(name));
+
+        // H.265 is optional, not all devices that can decode H.264 can also decode H.265.
+        if has_extension(c"VK_KHR_video_decode_h265") {
+            device_extensions.push(c"VK_KHR_video_decode_h265");
+        }
+
+        // Encoding is optional, and needs both the generic and the codec specific extension.
+        if has_extension(c"VK_KHR_video_encode_queue") && has_extension(c"VK_KHR_video_encode_h264") {
+            device_extensions.push(c"VK_KHR_video_encode_queue");
+            device_extensions.push(c"VK_KHR_video_encode_h264");
+        }
+
+        // Optional, allows decoding into memory shared with other APIs or Vulkan instances.
+        for name in [
+            c"VK_KHR_external_memory_fd",
+            c"VK_KHR_external_memory_win32",
+            c"VK_KHR_external_semaphore_fd",
+            // D3D interop, to share textures with D3D11 (synchronized by keyed mutexes) or D3D12 (synchronized by fences).
+            c"VK_KHR_external_semaphore_win32",
+            c"VK_KHR_win32_keyed_mutex",
+            // Optional, saves allocating descriptor sets for compute dispatches.
+            c"VK_KHR_push_descriptor",
+            // Optional, reports how much memory is left, see `HeapInfos::budget`.
+            c"VK_EXT_memory_budget",
+        ] {
+            if has_extension(name) {
+                device_extensions.push(name);
+            }
+        }
+
+        // Hardware buffers are owned by a foreign queue family (e.g., the camera) whenever we don't use them.
+        if cfg!(feature = "android")
+            && has_extension(c"VK_ANDROID_external_memory_android_hardware_buffer")
+            && has_extension(c"VK_EXT_queue_family_foreign")
+        {
+            device_extensions.push(c"VK_ANDROID_external_memory_android_hardware_buffer");
+            device_extensions.push(c"VK_EXT_queue_family_foreign");
+        }
+
+        // Optional as well, enables inline queries (and more) if present.
+        let mut maintenance1_features = PhysicalDeviceVideoMaintenance1FeaturesKHR::default();
+
+        if has_extension(c"VK_KHR_video_maintenance1") {
+            let mut features = PhysicalDeviceFeatures2::default().push_next(&mut maintenance1_features);
+
+            // SAFETY: Should be safe as native instance and physical device are valid.
+            unsafe { native_instance.get_physical_device_features2(native_physical_device, &mut features) };
+        }
+
+        let video_maintenance1 = maintenance1_features.video_maintenance1 == TRUE;
+        let timeline_semaphore = timeline_semaphore_supported(&shared_physical_device);
+
+        if video_maintenance1 {
+            device_extensions.push(c"VK_KHR_video_maintenance1");
+        }
 
         let mut create_infos = Vec::new();
 
-        for family in queue_families {
+        for (family, priorities) in queues {
             let create_info = DeviceQueueCreateInfo::default()
                 .queue_family_index(*family)
-                .queue_priorities(&[1.0]);
+                .queue_priorities(priorities);
 
             create_infos.push(create_info);
         }
 
         let mut sync_features = PhysicalDeviceSynchronization2Features::default().synchronization2(true);
-        let mut device_features = PhysicalDeviceFeatures2::default().push_next(&mut sync_features);
+        let mut maintenance1_features = PhysicalDeviceVideoMaintenance1FeaturesKHR::default().video_maintenance1(video_maintenance1);
+        let mut timeline_features = PhysicalDeviceTimelineSemaphoreFeatures::default().timeline_semaphore(timeline_semaphore);
+        let mut device_features = PhysicalDeviceFeatures2::default()
+            .push_next(&mut sync_features)
+            .push_next(&mut timeline_features);
 
+        if video_maintenance1 {
+            device_features = device_features.push_next(&mut maintenance1_features);
+        }
+
+        let extension_names = device_extensions.iter().map(|x| x.as_ptr()).collect::<Vec<_>>();
         let create_info = DeviceCreateInfo::default()
             .queue_create_infos(&create_infos)
             .push_next(&mut device_features)
-            .enabled_extension_names(device_extensions.as_slice());
+            .enabled_extension_names(&extension_names);
 
-        unsafe {
-            let native_device = native_instance.create_device(native_physical_device, &create_info, None)?;
+        // SAFETY: Should be safe as native instance and physical device are valid.
+        let native_device = unsafe { native_instance.create_device(native_physical_device, &create_info, None)? };
+
+        // SAFETY: Should be safe as we just created the device, with exactly these extensions and features.
+        let mut device = unsafe { Self::from_native(shared_physical_device, native_device, &device_extensions, true) };
+        device.queues = queues.iter().map(|(family, priorities)| (*family, priorities.to_vec())).collect();
+
+        Ok(device)
+    }
 
-            Ok(Self {
-                native_device,
-                shared_physical_device,
-            })
+    /// Wraps a device created with `extensions`, and destroys it on drop if `owned`.
+    ///
+    /// # Safety
+    ///
+    /// The device must have been created on `shared_physical_device`, with `extensions`, synchronization2, timeline semaphores
+    /// if supported, and the video maintenance 1 feature if its extension is listed.
+    pub(crate) unsafe fn from_native(
+        shared_physical_device: Arc<PhysicalDeviceShared>,
+        native_device: ash::Device,
+        extensions: &[&CStr],
+        owned: bool,
+    ) -> Self {
+        let native_instance = shared_physical_device.instance().native();
+        let enabled = |name: &CStr| extensions.contains(&name);
+
+        let video_maintenance1 = enabled(c"VK_KHR_video_maintenance1");
+        let encode_h264 = enabled(c"VK_KHR_video_encode_queue") && enabled(c"VK_KHR_video_encode_h264");
+        let external_memory_fd = enabled(c"VK_KHR_external_memory_fd");
+        let external_memory_win32 = enabled(c"VK_KHR_external_memory_win32");
+        let external_semaphore_fd = enabled(c"VK_KHR_external_semaphore_fd");
+        let external_semaphore_win32 = enabled(c"VK_KHR_external_semaphore_win32");
+        let win32_keyed_mutex = enabled(c"VK_KHR_win32_keyed_mutex");
+        let push_descriptor = enabled(c"VK_KHR_push_descriptor");
+        let android_hardware_buffer = cfg!(feature = "android")
+            && enabled(c"VK_ANDROID_external_memory_android_hardware_buffer")
+            && enabled(c"VK_EXT_queue_family_foreign");
+        let timeline_semaphore = timeline_semaphore_supported(&shared_physical_device);
+
+        let push_descriptor_device = push_descriptor.then(|| ash::khr::push_descriptor::Device::new(&native_instance, &native_device));
+        let external_memory_fd_device =
+            external_memory_fd.then(|| ash::khr::external_memory_fd::Device::new(&native_instance, &native_device));
+        let external_memory_win32_device =
+            external_memory_win32.then(|| ash::khr::external_memory_win32::Device::new(&native_instance, &native_device));
+        let android_hardware_buffer_device = android_hardware_buffer
+            .then(|| ash::android::external_memory_android_hardware_buffer::Device::new(&native_instance, &native_device));
+        let external_semaphore_fd_device =
+            external_semaphore_fd.then(|| ash::khr::external_semaphore_fd::Device::new(&native_instance, &native_device));
+        let external_semaphore_win32_device =
+            external_semaphore_win32.then(|| ash::khr::external_semaphore_win32::Device::new(&native_instance, &native_device));
+        let debug_utils_device = shared_physical_device
+            .instance()
+            .debug_utils()
+            .then(|| ash::ext::debug_utils::Device::new(&native_instance, &native_device));
+
+        Self {
+            native_device,
+            shared_physical_device,
+            owned,
+            video_features: VideoFeatures {
+                video_maintenance1,
+                encode_h264,
+                external_memory_fd,
+                external_memory_win32,
+                android_hardware_buffer,
+                external_semaphore_fd,
+                external_semaphore_win32,
+                win32_keyed_mutex,
+                timeline_semaphore,
+                push_descriptor,
+            },
+            push_descriptor: push_descriptor_device,
+            external_memory_fd: external_memory_fd_device,
+            external_memory_win32: external_memory_win32_device,
+            android_hardware_buffer: android_hardware_buffer_device,
+            external_semaphore_fd: external_semaphore_fd_device,
+            external_semaphore_win32: external_semaphore_win32_device,
+            debug_utils: debug_utils_device,
+            object_count: AtomicU64::new(0),
+            queues: Vec::new(),
+            lost: AtomicBool::new(false),
+            counters: DeviceCounters::default(),
+            queue_locks: Mutex::new(HashMap::new()),
         }
     }
 
@@ -64,6 +341,13 @@ impl DeviceShared {
         Self::new_with_families(shared_physical_device, &infos)
     }
 
+    /// One queue of priority 1.0 per family.
+    pub(crate) fn new_with_families(shared_physical_device: Arc<PhysicalDeviceShared>, queue_families: &[u32]) -> Result<Self, Error> {
+        let queues = queue_families.iter().map(|x| (*x, [1.0].as_slice())).collect::<Vec<_>>();
+
+        Self::new_with_queues(shared_physical_device, &queues)
+    }
+
     #[allow(unused)]
     pub(crate) fn physical_device(&self) -> Arc<PhysicalDeviceShared> {
         self.shared_physical_device.clone()
@@ -76,10 +360,147 @@ impl DeviceShared {
     pub(crate) fn native(&self) -> ash::Device {
         self.native_device.clone()
     }
+
+    pub(crate) fn video_features(&self) -> VideoFeatures {
+        self.video_features
+    }
+
+    /// Functions of `VK_KHR_push_descriptor`, if enabled.
+    pub(crate) fn push_descriptor(&self) -> Option<&ash::khr::push_descriptor::Device> {
+        self.push_descriptor.as_ref()
+    }
+
+    /// Functions of `VK_EXT_debug_utils`, if enabled on the instance.
+    pub(crate) fn debug_utils(&self) -> Option<&ash::ext::debug_utils::Device> {
+        self.debug_utils.as_ref()
+    }
+
+    /// Runs `f`, attaching validation messages it caused to its error.
+    pub(crate) fn validated<T>(&self, f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        let instance = self.instance();
+        let position = instance.validation().position();
+
+        self.check_lost(f())
+            .map_err(|e| e.with_validation(instance.validation().since(position)))
+    }
+
+    /// Passes `result` through, remembering if it says the device was lost.
+    pub(crate) fn check_lost<T>(&self, result: Result<T, Error>) -> Result<T, Error> {
+        if let Err(e) = &result {
+            if e.is_device_lost() {
+                self.lost.store(true, Ordering::Release);
+            }
+        }
+
+        result
+    }
+
+    pub(crate) fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn counters(&self) -> &DeviceCounters {
+        &self.counters
+    }
+
+    pub(crate) fn queues(&self) -> &[(u32, Vec<f32>)] {
+        &self.queues
+    }
+
+    /// The lock to hold while submitting to queue `index` of `family`, as Vulkan requires queue access to be externally
+    /// synchronized, even if several of our queues wrap the same `VkQueue`.
+    pub(crate) fn queue_lock(&self, family: u32, index: u32) -> Arc<Mutex<()>> {
+        let mut queue_locks = self.queue_locks.lock().unwrap_or_else(PoisonError::into_inner);
+
+        queue_locks.entry((family, index)).or_default().clone()
+    }
+
+    /// Queues created in `family`, `None` for devices created elsewhere.
+    pub(crate) fn queue_count(&self, family: u32) -> Option<u32> {
+        if !self.owned {
+            return None;
+        }
+
+        let count = self.queues.iter().find(|x| x.0 == family).map_or(0, |x| x.1.len());
+
+        Some(count as u32)
+    }
+
+    /// Names `handle` for validation messages and debugging tools, does nothing without `VK_EXT_debug_utils`.
+    pub(crate) fn set_object_name<H: Handle>(&self, handle: H, name: &str) -> Result<(), Error> {
+        let name = CString::new(name)?;
+
+        let Some(debug_utils) = self.debug_utils() else {
+            return Ok(());
+        };
+
+        let info = DebugUtilsObjectNameInfoEXT::default().object_handle(handle).object_name(&name);
+
+        // SAFETY: Should be safe as the handle was created from this device.
+        unsafe { Ok(debug_utils.set_debug_utils_object_name(&info)?) }
+    }
+
+    /// Names a newly created `handle` of type `kind` like `vulkan_video::Buffer#3`.
+    ///
+    /// Names are only a debugging aid, so failures are ignored.
+    pub(crate) fn set_default_name<H: Handle>(&self, handle: H, kind: &str) {
+        if self.debug_utils.is_none() {
+            return;
+        }
+
+        let number = self.object_count.fetch_add(1, Ordering::Relaxed);
+
+        _ = self.set_object_name(handle, &format!("vulkan_video::{kind}#{number}"));
+    }
+
+    /// Functions of `VK_KHR_external_memory_fd`, if enabled.
+    #[cfg_attr(not(unix), allow(unused))]
+    pub(crate) fn external_memory_fd(&self) -> Option<&ash::khr::external_memory_fd::Device> {
+        self.external_memory_fd.as_ref()
+    }
+
+    /// Functions of `VK_KHR_external_memory_win32`, if enabled.
+    #[cfg_attr(not(windows), allow(unused))]
+    pub(crate) fn external_memory_win32(&self) -> Option<&ash::khr::external_memory_win32::Device> {
+        self.external_memory_win32.as_ref()
+    }
+
+    /// Functions of `VK_KHR_external_semaphore_fd`, if enabled.
+    #[cfg_attr(not(unix), allow(unused))]
+    pub(crate) fn external_semaphore_fd(&self) -> Option<&ash::khr::external_semaphore_fd::Device> {
+        self.external_semaphore_fd.as_ref()
+    }
+
+    /// Functions of `VK_KHR_external_semaphore_win32`, if enabled.
+    pub(crate) fn external_semaphore_win32(&self) -> Option<&ash::khr::external_semaphore_win32::Device> {
+        self.external_semaphore_win32.as_ref()
+    }
+
+    /// Functions of `VK_ANDROID_external_memory_android_hardware_buffer`, if enabled.
+    #[cfg(feature = "android")]
+    pub(crate) fn android_hardware_buffer(&self) -> Option<&ash::android::external_memory_android_hardware_buffer::Device> {
+        self.android_hardware_buffer.as_ref()
+    }
+}
+
+/// Timeline semaphores are core since Vulkan 1.2, but still optional for devices to support.
+fn timeline_semaphore_supported(shared_physical_device: &PhysicalDeviceShared) -> bool {
+    let native_instance = shared_physical_device.instance().native();
+    let mut timeline_features = PhysicalDeviceTimelineSemaphoreFeatures::default();
+    let mut features = PhysicalDeviceFeatures2::default().push_next(&mut timeline_features);
+
+    // SAFETY: Should be safe as native instance and physical device are valid.
+    unsafe { native_instance.get_physical_device_features2(shared_physical_device.native(), &mut features) };
+
+    timeline_features.timeline_semaphore == TRUE
 }
 
 impl Drop for DeviceShared {
     fn drop(&mut self) {
+        if !self.owned {
+            return;
+        }
+
         unsafe {
             self.native_device.destroy_device(None);
         }
@@ -100,6 +521,29 @@ impl Device {
         })
     }
 
+    /// Creates a device with several queues per family, each entry being a family and the priorities of its queues.
+    ///
+    /// Priorities are within 0.0 and 1.0, higher priority queues may get more GPU time, e.g., for a live decode while a
+    /// background transcode runs on a low priority queue of the same family:
+    ///
+    /// ```rust,ignore
+    /// let device = Device::new_with_queues(&physical_device, &[(decode, &[1.0, 0.1]), (compute, &[1.0])])?;
+    ///
+    /// let live = Queue::new(&device, decode, 0)?;
+    /// let background = Queue::new(&device, decode, 1)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Variant::QueueNotFound`] if a family doesn't have as many queues as requested.
+    pub fn new_with_queues(physical_device: &PhysicalDevice, queues: &[(u32, &[f32])]) -> Result<Self, Error> {
+        let device_shared = DeviceShared::new_with_queues(physical_device.shared(), queues)?;
+
+        Ok(Self {
+            shared: Arc::new(device_shared),
+        })
+    }
+
     pub fn new(physical_device: &PhysicalDevice) -> Result<Self, Error> {
         let device_shared = DeviceShared::new(physical_device.shared())?;
 
@@ -108,9 +552,97 @@ impl Device {
         })
     }
 
+    /// Wraps a device created by someone else, e.g., a game engine, to decode on the same device.
+    ///
+    /// Video features are derived from the enabled `extensions`. The device is not destroyed when this (and everything
+    /// created from it) is dropped, that's up to its owner.
+    ///
+    /// # Safety
+    ///
+    /// The device must have been created on `physical_device` with exactly `extensions` enabled, which must include
+    /// `VK_KHR_video_queue`, `VK_KHR_video_decode_queue` and the codec extensions used. It must also have enabled
+    /// `synchronization2`, `timelineSemaphore` if supported, and `videoMaintenance1` if `VK_KHR_video_maintenance1` is listed.
+    /// Queues used with this crate must have been created with the device, and the device must outlive this and everything created from it.
+    pub unsafe fn from_raw(physical_device: &PhysicalDevice, device: ash::vk::Device, extensions: &[&CStr]) -> Self {
+        let shared_physical_device = physical_device.shared();
+        let native_instance = shared_physical_device.instance().native();
+
+        // SAFETY: Forwarded to our caller.
+        let device_shared = unsafe {
+            let native_device = ash::Device::load(native_instance.fp_v1_0(), device);
+            DeviceShared::from_native(shared_physical_device, native_device, extensions, false)
+        };
+
+        Self {
+            shared: Arc::new(device_shared),
+        }
+    }
+
+    /// If a call on this device failed with `VK_ERROR_DEVICE_LOST`, e.g., after a driver reset.
+    ///
+    /// A lost device stays lost, use [`Self::recreate`] to continue with a new one.
+    pub fn is_lost(&self) -> bool {
+        self.shared.is_lost()
+    }
+
+    /// Creates a new device like this one, on the same physical device with the same queue families.
+    ///
+    /// This is how to recover from a lost device. Everything created from the old device, e.g., queues, images or a
+    /// [`H264Decoder`](crate::video::h264::H264Decoder), has to be recreated from the new one, see
+    /// [`H264Decoder::recover`](crate::video::h264::H264Decoder::recover).
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Variant::FeatureNotSupported`] for devices wrapped via [`Self::from_raw`], which only their owner can recreate.
+    pub fn recreate(&self) -> Result<Self, Error> {
+        if !self.shared.owned {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "Devices created elsewhere can't be recreated."
+            ));
+        }
+
+        let queues = self
+            .shared
+            .queues()
+            .iter()
+            .map(|(family, priorities)| (*family, priorities.as_slice()));
+        let device_shared = DeviceShared::new_with_queues(self.shared.physical_device(), &queues.collect::<Vec<_>>())?;
+
+        Ok(Self {
+            shared: Arc::new(device_shared),
+        })
+    }
+
+    /// Queues created in `family`, i.e., valid indices for [`Queue::new`](crate::Queue::new).
+    ///
+    /// Returns `None` for devices wrapped via [`Self::from_raw`], whose queues we don't know.
+    pub fn queue_count(&self, family: u32) -> Option<u32> {
+        self.shared.queue_count(family)
+    }
+
+    /// Resources currently held by this device, e.g., for monitoring.
+    pub fn stats(&self) -> DeviceStats {
+        let heap_count = self.shared.physical_device().heap_infos().heap_count();
+
+        self.shared.counters().stats(heap_count)
+    }
+
+    /// Optional video features this device was created with.
+    pub fn video_features(&self) -> VideoFeatures {
+        self.shared.video_features()
+    }
+
     pub(crate) fn shared(&self) -> Arc<DeviceShared> {
         self.shared.clone()
     }
+
+    /// The `ash` device with its core functions loaded, to mix in your own Vulkan calls.
+    ///
+    /// Don't destroy it, and don't use resources of this crate from your calls while the crate might use them as well.
+    pub fn raw(&self) -> ash::Device {
+        self.shared.native()
+    }
 }
 
 #[cfg(test)]
@@ -119,6 +651,32 @@ mod test {
     use crate::error::Error;
     use crate::instance::{Instance, InstanceInfo};
     use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::Queue;
+    use crate::{error, Variant};
+
+    #[test]
+    #[cfg(not(miri))]
+    fn queue_priorities() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let compute = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+
+        let device = Device::new_with_queues(&physical_device, &[(compute, &[1.0])])?;
+        let too_many = Device::new_with_queues(&physical_device, &[(compute, &[1.0; 1024])]);
+        let invalid_priority = Device::new_with_queues(&physical_device, &[(compute, &[2.0])]);
+
+        assert_eq!(device.queue_count(compute), Some(1));
+        assert!(Queue::new(&device, compute, 0).is_ok());
+        assert!(Queue::new(&device, compute, 1).is_err_and(|e| matches!(e.variant(), Variant::QueueNotFound)));
+        assert!(too_many.is_err_and(|e| matches!(e.variant(), Variant::QueueNotFound)));
+        assert!(invalid_priority.is_err_and(|e| matches!(e.variant(), Variant::ParameterMismatch)));
+
+        Ok(())
+    }
 
     #[test]
     #[cfg(not(miri))]
@@ -128,7 +686,34 @@ mod test {
         let physical_device = PhysicalDevice::new_any(&instance)?;
 
         _ = physical_device.queue_family_infos();
-        _ = Device::new(&physical_device)?;
+        let device = Device::new(&physical_device)?;
+        let features = device.video_features();
+
+        assert_eq!(features.inline_queries(), features.video_maintenance1());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn wrap_device() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let owner = Device::new(&physical_device)?;
+        let native_device = owner.shared().native();
+        let extensions = [c"VK_KHR_video_queue", c"VK_KHR_video_decode_queue", c"VK_KHR_video_decode_h264"];
+
+        let device = unsafe { Device::from_raw(&physical_device, native_device.handle(), &extensions) };
+
+        assert_eq!(device.raw().handle(), native_device.handle());
+        assert!(!device.video_features().encode_h264());
+        assert!(!device.video_features().push_descriptor());
+
+        // Dropping the wrapper must leave the device intact for its owner.
+        drop(device);
+
+        unsafe { native_device.device_wait_idle()? };
 
         Ok(())
     }
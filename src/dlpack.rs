@@ -0,0 +1,329 @@
+//! Describes frames as [DLPack](https://dmlc.github.io/dlpack/latest/) tensors, e.g., to hand them to PyTorch without copies.
+//!
+//! Vulkan memory can't be read by CUDA (and friends) directly. Instead, the memory holding a frame is exported, imported
+//! into the other API, and the tensor then points to wherever it got mapped there:
+//!
+//! ```rust,ignore
+//! let allocation = Allocation::new_exportable(&device, size, device_local)?;
+//! let rgba = Buffer::external(&allocation, &BufferInfo::new().size(size))?;
+//! // ... decode and convert into `rgba` ...
+//!
+//! let mut tensor = Tensor::new(&allocation, 0, TensorLayout::rgba8(extent))?;
+//! let cuda_pointer = import_into_cuda(tensor.take_handle(), allocation.size());
+//! let managed = tensor.into_managed(cuda_pointer, DLDevice::cuda(0));
+//!
+//! // Wrap `managed` into a "dltensor" capsule, e.g., for `torch.from_dlpack`.
+//! ```
+use crate::allocation::{Allocation, AllocationShared, ExternalHandle};
+use crate::error;
+use crate::error::{Error, Variant};
+use ash::vk::Extent2D;
+use std::ffi::c_void;
+use std::sync::Arc;
+
+/// Where a tensor lives, `DLDevice` in `dlpack.h`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DLDevice {
+    pub device_type: i32,
+    pub device_id: i32,
+}
+
+impl DLDevice {
+    pub const CUDA: i32 = 2;
+    pub const VULKAN: i32 = 7;
+
+    pub fn cuda(device_id: i32) -> Self {
+        Self {
+            device_type: Self::CUDA,
+            device_id,
+        }
+    }
+
+    pub fn vulkan(device_id: i32) -> Self {
+        Self {
+            device_type: Self::VULKAN,
+            device_id,
+        }
+    }
+}
+
+/// Element type of a tensor, `DLDataType` in `dlpack.h`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DLDataType {
+    pub code: u8,
+    pub bits: u8,
+    pub lanes: u16,
+}
+
+impl DLDataType {
+    pub const UINT8: Self = Self {
+        code: 1,
+        bits: 8,
+        lanes: 1,
+    };
+    pub const UINT16: Self = Self {
+        code: 1,
+        bits: 16,
+        lanes: 1,
+    };
+    pub const FLOAT32: Self = Self {
+        code: 2,
+        bits: 32,
+        lanes: 1,
+    };
+}
+
+/// A tensor, `DLTensor` in `dlpack.h`. Shape and strides are in elements, not bytes.
+#[repr(C)]
+#[derive(Debug)]
+pub struct DLTensor {
+    pub data: *mut c_void,
+    pub device: DLDevice,
+    pub ndim: i32,
+    pub dtype: DLDataType,
+    pub shape: *mut i64,
+    pub strides: *mut i64,
+    pub byte_offset: u64,
+}
+
+/// A tensor with a way to release it, `DLManagedTensor` in `dlpack.h`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct DLManagedTensor {
+    pub dl_tensor: DLTensor,
+    pub manager_ctx: *mut c_void,
+    pub deleter: Option<unsafe extern "C" fn(*mut DLManagedTensor)>,
+}
+
+/// Shape, strides and element type of a frame in memory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TensorLayout {
+    shape: Vec<i64>,
+    strides: Vec<i64>,
+    dtype: DLDataType,
+}
+
+impl TensorLayout {
+    pub fn new(shape: Vec<i64>, strides: Vec<i64>, dtype: DLDataType) -> Result<Self, Error> {
+        if shape.len() != strides.len() || shape.iter().chain(&strides).any(|x| *x < 0) {
+            return Err(error!(
+                Variant::ParameterMismatch,
+                "Shape {shape:?} and strides {strides:?} must be non-negative, and of same length."
+            ));
+        }
+
+        Ok(Self { shape, strides, dtype })
+    }
+
+    /// Tightly packed RGBA8 frames as `[height, width, 4]`, e.g., from [`ConvertNv12ToRgba`](crate::ops::ConvertNv12ToRgba).
+    pub fn rgba8(extent: Extent2D) -> Self {
+        let (width, height) = (extent.width as i64, extent.height as i64);
+
+        Self {
+            shape: vec![height, width, 4],
+            strides: vec![width * 4, 4, 1],
+            dtype: DLDataType::UINT8,
+        }
+    }
+
+    /// A tightly packed 8 bit plane as `[height, width]`, e.g., NV12 luma.
+    pub fn plane8(extent: Extent2D) -> Self {
+        let (width, height) = (extent.width as i64, extent.height as i64);
+
+        Self {
+            shape: vec![height, width],
+            strides: vec![width, 1],
+            dtype: DLDataType::UINT8,
+        }
+    }
+
+    /// A tightly packed 16 bit plane as `[height, width]`, e.g., P010 luma.
+    pub fn plane16(extent: Extent2D) -> Self {
+        Self {
+            dtype: DLDataType::UINT16,
+            ..Self::plane8(extent)
+        }
+    }
+
+    pub fn shape(&self) -> &[i64] {
+        &self.shape
+    }
+
+    pub fn strides(&self) -> &[i64] {
+        &self.strides
+    }
+
+    pub fn dtype(&self) -> DLDataType {
+        self.dtype
+    }
+
+    /// Bytes from the first to the end of the last element.
+    pub fn size(&self) -> u64 {
+        if self.shape.contains(&0) {
+            return 0;
+        }
+
+        let last = self
+            .shape
+            .iter()
+            .zip(&self.strides)
+            .map(|(n, stride)| (n - 1) * stride)
+            .sum::<i64>();
+        let element = self.dtype.bits as u64 / 8 * self.dtype.lanes as u64;
+
+        (last as u64 + 1) * element
+    }
+}
+
+/// A frame in exportable memory, to be described as DLPack tensor once imported elsewhere.
+pub struct Tensor {
+    /// Kept alive until the consumer deletes the tensor.
+    _allocation: Arc<AllocationShared>,
+    handle: Option<ExternalHandle>,
+    offset: u64,
+    layout: TensorLayout,
+}
+
+impl Tensor {
+    /// Exports `allocation`, which holds a frame with `layout` at `offset`, see [`Allocation::new_exportable`].
+    pub fn new(allocation: &Allocation, offset: u64, layout: TensorLayout) -> Result<Self, Error> {
+        if offset + layout.size() > allocation.size() {
+            return Err(error!(
+                Variant::BufferTooSmall,
+                "Tensor of {} bytes at {offset} exceeds allocation of {} bytes.",
+                layout.size(),
+                allocation.size()
+            ));
+        }
+
+        Ok(Self {
+            _allocation: allocation.shared(),
+            handle: Some(allocation.export_handle()?),
+            offset,
+            layout,
+        })
+    }
+
+    /// The exported handle, to import the memory into another API. Only returns it once.
+    pub fn take_handle(&mut self) -> Option<ExternalHandle> {
+        self.handle.take()
+    }
+
+    pub fn layout(&self) -> &TensorLayout {
+        &self.layout
+    }
+
+    /// Describes the tensor, `data` being where the imported memory starts on `device`.
+    ///
+    /// The allocation stays alive until the consumer calls the tensor's `deleter`, without it, it leaks.
+    pub fn into_managed(self, data: *mut c_void, device: DLDevice) -> *mut DLManagedTensor {
+        let mut shape = self.layout.shape.clone();
+        let mut strides = self.layout.strides.clone();
+
+        let dl_tensor = DLTensor {
+            data,
+            device,
+            ndim: shape.len() as i32,
+            dtype: self.layout.dtype,
+            shape: shape.as_mut_ptr(),
+            strides: strides.as_mut_ptr(),
+            byte_offset: self.offset,
+        };
+
+        let managed = Box::new(Managed {
+            managed: DLManagedTensor {
+                dl_tensor,
+                manager_ctx: std::ptr::null_mut(),
+                deleter: Some(delete_managed),
+            },
+            _shape: shape,
+            _strides: strides,
+            _tensor: self,
+        });
+
+        Box::into_raw(managed).cast()
+    }
+}
+
+/// Owns everything a [`DLManagedTensor`] points to, which comes first so pointers to either are the same.
+#[repr(C)]
+struct Managed {
+    managed: DLManagedTensor,
+    _shape: Vec<i64>,
+    _strides: Vec<i64>,
+    _tensor: Tensor,
+}
+
+unsafe extern "C" fn delete_managed(managed: *mut DLManagedTensor) {
+    if !managed.is_null() {
+        // SAFETY: Should be safe as all tensors we hand out were created by `into_managed`.
+        drop(unsafe { Box::from_raw(managed.cast::<Managed>()) });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::device::Device;
+    use crate::dlpack::{DLDataType, DLDevice, Tensor, TensorLayout};
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use ash::vk::Extent2D;
+
+    #[test]
+    fn layouts() -> Result<(), Error> {
+        let extent = Extent2D::default().width(64).height(32);
+
+        assert_eq!(TensorLayout::rgba8(extent).size(), 64 * 32 * 4);
+        assert_eq!(TensorLayout::plane8(extent).size(), 64 * 32);
+        assert_eq!(TensorLayout::plane16(extent).size(), 64 * 32 * 2);
+
+        // Rows padded to 128 bytes, the last one isn't.
+        let padded = TensorLayout::new(vec![32, 64], vec![128, 1], DLDataType::UINT8)?;
+        assert_eq!(padded.size(), 31 * 128 + 64);
+
+        assert!(TensorLayout::new(vec![32, 64], vec![1], DLDataType::UINT8).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn export_managed() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let device_local = physical_device
+            .heap_infos()
+            .any_device_local()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+
+        if !device.video_features().external_memory() {
+            return Ok(());
+        }
+
+        let extent = Extent2D::default().width(64).height(32);
+        let allocation = Allocation::new_exportable(&device, 64 * 32 * 4, device_local)?;
+
+        assert!(Tensor::new(&allocation, 4, TensorLayout::rgba8(extent)).is_err());
+
+        let mut tensor = Tensor::new(&allocation, 0, TensorLayout::rgba8(extent))?;
+        assert!(tensor.take_handle().is_some());
+        assert!(tensor.take_handle().is_none());
+
+        let managed = tensor.into_managed(std::ptr::null_mut(), DLDevice::vulkan(0));
+
+        unsafe {
+            assert_eq!(*(*managed).dl_tensor.shape.add(2), 4);
+            assert_eq!((*managed).dl_tensor.ndim, 3);
+            ((*managed).deleter.unwrap())(managed);
+        }
+
+        Ok(())
+    }
+}
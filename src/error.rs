@@ -1,3 +1,4 @@
+use crate::validation::ValidationMessage;
 use ash::vk::CStrTooLargeForStaticArray;
 use ash::LoadingError;
 use std::backtrace::Backtrace;
@@ -9,6 +10,7 @@ pub enum Variant {
     Nul(NulError),
     CStrTooLargeForStaticArray(CStrTooLargeForStaticArray),
     Loading(LoadingError),
+    Io(std::io::Error),
     Vulkan(ash::vk::Result),
     NoVideoDevice,
     NoComputePipeline,
@@ -16,23 +18,67 @@ pub enum Variant {
     HeapNotFound,
     QueueNotFound,
     ImageAlreadyBound,
+    InvalidBitstream,
+    BufferTooSmall,
+    NoFreeDpbSlot,
+    ParameterSetChanged,
+    UnsupportedProfile,
+    FeatureNotSupported,
+    PipelineStopped,
+    ImageNotMappable,
+    InvalidUpdateSize,
+    InvalidFillRange,
+    InvalidCopyRegion,
+    QueryOutOfRange,
+    InvalidSpirv,
+    ParameterMismatch,
+    InvalidExtent,
+    EntryPointNotFound,
+    IncompatibleMemoryType,
 }
 
 pub struct Error {
-    message: Option<String>,
+    message: Option<Box<str>>,
     variant: Variant,
     backtrace: Backtrace,
+    /// Boxed slices (and message) keep `Result<_, Error>` small.
+    validation: Option<Box<[ValidationMessage]>>,
 }
 
 impl Error {
     #[track_caller]
     pub fn new(message: Option<String>, variant: Variant) -> Self {
         Self {
-            message,
+            message: message.map(String::into_boxed_str),
             variant,
             backtrace: Backtrace::capture(),
+            validation: None,
         }
     }
+
+    pub fn variant(&self) -> &Variant {
+        &self.variant
+    }
+
+    /// If the device was lost, e.g., after a driver reset, see [`Device::recreate`](crate::Device::recreate).
+    pub fn is_device_lost(&self) -> bool {
+        matches!(self.variant, Variant::Vulkan(ash::vk::Result::ERROR_DEVICE_LOST))
+    }
+
+    /// Validation warnings and errors reported while the failing call ran, if validation is enabled.
+    pub fn validation_messages(&self) -> &[ValidationMessage] {
+        self.validation.as_deref().unwrap_or_default()
+    }
+
+    pub(crate) fn with_validation(mut self, validation: Vec<ValidationMessage>) -> Self {
+        if !validation.is_empty() {
+            let mut messages = self.validation.take().map(Vec::from).unwrap_or_default();
+            messages.extend(validation);
+            self.validation = Some(messages.into_boxed_slice());
+        }
+
+        self
+    }
 }
 
 impl std::fmt::Debug for Error {
@@ -42,6 +88,10 @@ impl std::fmt::Debug for Error {
             None => writeln!(f, "{:?}", self.variant)?,
         }
 
+        for message in self.validation_messages() {
+            writeln!(f, "Validation: {}", message.message())?;
+        }
+
         writeln!(f, "Backtrace:\n{}", self.backtrace)
     }
 }
@@ -56,6 +106,10 @@ impl Display for Error {
             None => writeln!(f, "{:?}", self.variant),
         }?;
 
+        for message in self.validation_messages() {
+            writeln!(f, "Validation: {}", message.message())?;
+        }
+
         // Use the stable `Display` implementation of `Backtrace`
         writeln!(f, "Backtrace:\n{}", self.backtrace)
     }
@@ -68,6 +122,7 @@ impl From<ash::vk::Result> for Error {
             message: None,
             variant: Variant::Vulkan(e),
             backtrace: Backtrace::capture(),
+            validation: None,
         }
     }
 }
@@ -79,6 +134,7 @@ impl From<NulError> for Error {
             message: None,
             variant: Variant::Nul(e),
             backtrace: Backtrace::capture(),
+            validation: None,
         }
     }
 }
@@ -90,6 +146,19 @@ impl From<LoadingError> for Error {
             message: None,
             variant: Variant::Loading(e),
             backtrace: Backtrace::capture(),
+            validation: None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    #[track_caller]
+    fn from(e: std::io::Error) -> Self {
+        Self {
+            message: None,
+            variant: Variant::Io(e),
+            backtrace: Backtrace::capture(),
+            validation: None,
         }
     }
 }
@@ -101,6 +170,7 @@ impl From<CStrTooLargeForStaticArray> for Error {
             message: None,
             variant: Variant::CStrTooLargeForStaticArray(e),
             backtrace: Backtrace::capture(),
+            validation: None,
         }
     }
 }
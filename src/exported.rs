@@ -0,0 +1,331 @@
+use crate::allocation::{Allocation, ExternalHandle, MemoryTypeIndex, EXTERNAL_HANDLE_TYPE};
+use crate::device::Device;
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::resources::{Image, ImageInfo};
+use crate::semaphore::Semaphore;
+use ash::vk::{Extent3D, Format, ImageLayout, ImageTiling, ImageType, ImageUsageFlags, SampleCountFlags};
+
+/// Everything another process needs to import an [`ExportedFrame`], besides the handles themselves.
+///
+/// Sent as bytes, see [`to_bytes`](Self::to_bytes), next to the handles (e.g., via `SCM_RIGHTS`, or `DuplicateHandle` on Windows).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportedFrameInfo {
+    device_uuid: [u8; 16],
+    driver_uuid: [u8; 16],
+    format: Format,
+    extent: Extent3D,
+    image_type: ImageType,
+    tiling: ImageTiling,
+    usage: ImageUsageFlags,
+    samples: SampleCountFlags,
+    mip_levels: u32,
+    array_layers: u32,
+    layout: ImageLayout,
+    allocation_size: u64,
+    offset: u64,
+    type_index: u32,
+    ready_value: u64,
+}
+
+impl ExportedFrameInfo {
+    /// Bytes [`to_bytes`](Self::to_bytes) produces.
+    pub const SIZE: usize = 32 + 4 * 12 + 8 * 3;
+
+    /// Describes the image, how to create it on the importing side.
+    pub fn image_info(&self) -> ImageInfo {
+        ImageInfo::new()
+            .format(self.format)
+            .extent(self.extent)
+            .image_type(self.image_type)
+            .tiling(self.tiling)
+            .usage(self.usage)
+            .samples(self.samples)
+            .mip_levels(self.mip_levels)
+            .array_layers(self.array_layers)
+            .layout(ImageLayout::UNDEFINED)
+            .external_memory(EXTERNAL_HANDLE_TYPE)
+            .external_layout(self.layout)
+    }
+
+    /// Layout the image is in once the semaphore reached [`ready_value`](Self::ready_value).
+    pub fn layout(&self) -> ImageLayout {
+        self.layout
+    }
+
+    /// Offset of the image into the exported memory.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Semaphore value at which the frame is ready to be read.
+    ///
+    /// By convention, importers signal `ready_value + 1` once they're done, so the exporter can reuse the frame.
+    pub fn ready_value(&self) -> u64 {
+        self.ready_value
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let words = [
+            self.format.as_raw() as u32,
+            self.extent.width,
+            self.extent.height,
+            self.extent.depth,
+            self.image_type.as_raw() as u32,
+            self.tiling.as_raw() as u32,
+            self.usage.as_raw(),
+            self.samples.as_raw(),
+            self.mip_levels,
+            self.array_layers,
+            self.layout.as_raw() as u32,
+            self.type_index,
+        ];
+
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+
+        bytes.extend_from_slice(&self.device_uuid);
+        bytes.extend_from_slice(&self.driver_uuid);
+        bytes.extend(words.iter().flat_map(|x| x.to_le_bytes()));
+        bytes.extend_from_slice(&self.allocation_size.to_le_bytes());
+        bytes.extend_from_slice(&self.offset.to_le_bytes());
+        bytes.extend_from_slice(&self.ready_value.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != Self::SIZE {
+            return Err(error!(
+                Variant::ParameterMismatch,
+                "Frame info must be {} bytes, got {}.",
+                Self::SIZE,
+                bytes.len()
+            ));
+        }
+
+        let (uuids, rest) = bytes.split_at(32);
+        let (words, longs) = rest.split_at(4 * 12);
+        let word = |i: usize| u32::from_le_bytes([words[i * 4], words[i * 4 + 1], words[i * 4 + 2], words[i * 4 + 3]]);
+        let long = |i: usize| u64::from_le_bytes(longs[i * 8..i * 8 + 8].try_into().unwrap_or_default());
+
+        Ok(Self {
+            device_uuid: uuids[..16].try_into().unwrap_or_default(),
+            driver_uuid: uuids[16..].try_into().unwrap_or_default(),
+            format: Format::from_raw(word(0) as i32),
+            extent: Extent3D::default().width(word(1)).height(word(2)).depth(word(3)),
+            image_type: ImageType::from_raw(word(4) as i32),
+            tiling: ImageTiling::from_raw(word(5) as i32),
+            usage: ImageUsageFlags::from_raw(word(6)),
+            samples: SampleCountFlags::from_raw(word(7)),
+            mip_levels: word(8),
+            array_layers: word(9),
+            layout: ImageLayout::from_raw(word(10) as i32),
+            type_index: word(11),
+            allocation_size: long(0),
+            offset: long(1),
+            ready_value: long(2),
+        })
+    }
+}
+
+/// A frame (e.g., decode output) shared with another process, which then reads it without copies.
+///
+/// The exporting process decodes into an image bound to exportable memory, and signals an exportable semaphore
+/// once the frame is ready:
+///
+/// ```rust,ignore
+/// let frame = ExportedFrame::new(&image, ImageLayout::VIDEO_DECODE_DPB_KHR, &semaphore, 1)?;
+/// let (info, memory, semaphore) = frame.into_parts();
+/// send(&socket, &info.to_bytes(), [memory, semaphore])?;
+///
+/// // In the other process.
+/// let (bytes, [memory, semaphore]) = receive(&socket)?;
+/// let frame = ExportedFrame::from_parts(ExportedFrameInfo::from_bytes(&bytes)?, memory, semaphore);
+/// let (image, semaphore) = frame.import(&device)?;
+/// ```
+pub struct ExportedFrame {
+    info: ExportedFrameInfo,
+    memory: ExternalHandle,
+    semaphore: ExternalHandle,
+}
+
+impl ExportedFrame {
+    /// Exports `image`, which is ready and in `layout` once `semaphore` reached `ready_value`.
+    ///
+    /// The image needs [`EXTERNAL_HANDLE_TYPE`] in [`ImageInfo::external_memory`] and must be bound to exportable memory,
+    /// the semaphore must be exportable, see [`Allocation::new_exportable`] and [`Semaphore::new_exportable`]. The frame
+    /// is usually exported before the work producing it ran, hence the layout is passed rather than taken from the image.
+    pub fn new(image: &Image, layout: ImageLayout, semaphore: &Semaphore, ready_value: u64) -> Result<Self, Error> {
+        let image_info = image.info();
+        let (shared_allocation, offset) = image
+            .shared()
+            .binding()
+            .ok_or_else(|| error!(Variant::ParameterMismatch, "Image has no memory bound."))?;
+
+        if !image_info.get_external_memory().contains(EXTERNAL_HANDLE_TYPE) {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "Image was not created for {EXTERNAL_HANDLE_TYPE:?}."
+            ));
+        }
+
+        let shared_physical_device = image.device().physical_device();
+
+        let info = ExportedFrameInfo {
+            device_uuid: shared_physical_device.device_uuid(),
+            driver_uuid: shared_physical_device.driver_uuid(),
+            format: image_info.get_format(),
+            extent: image_info.get_extent(),
+            image_type: image_info.get_image_type(),
+            tiling: image_info.get_tiling(),
+            usage: image_info.get_usage(),
+            samples: image_info.get_samples(),
+            mip_levels: image_info.get_mip_levels(),
+            array_layers: image_info.get_array_layers(),
+            layout,
+            allocation_size: shared_allocation.size(),
+            offset,
+            type_index: shared_allocation.type_index().index(),
+            ready_value,
+        };
+
+        Ok(Self {
+            info,
+            memory: shared_allocation.export_handle()?,
+            semaphore: semaphore.export_handle()?,
+        })
+    }
+
+    /// Reassembles a frame received from another process.
+    pub fn from_parts(info: ExportedFrameInfo, memory: ExternalHandle, semaphore: ExternalHandle) -> Self {
+        Self { info, memory, semaphore }
+    }
+
+    /// The info, memory and semaphore handle, to send them to another process.
+    pub fn into_parts(self) -> (ExportedFrameInfo, ExternalHandle, ExternalHandle) {
+        (self.info, self.memory, self.semaphore)
+    }
+
+    pub fn info(&self) -> &ExportedFrameInfo {
+        &self.info
+    }
+
+    /// Imports the frame on `device`, which must be the same physical device (and driver) it was exported from.
+    ///
+    /// Wait for [`ExportedFrameInfo::ready_value`] on the returned semaphore before reading the image.
+    pub fn import(self, device: &Device) -> Result<(Image, Semaphore), Error> {
+        let shared_physical_device = device.shared().physical_device();
+
+        if shared_physical_device.device_uuid() != self.info.device_uuid || shared_physical_device.driver_uuid() != self.info.driver_uuid {
+            return Err(error!(
+                Variant::ParameterMismatch,
+                "Frame was exported from another device or driver."
+            ));
+        }
+
+        let type_index = MemoryTypeIndex::new(self.info.type_index);
+        let allocation = Allocation::import_handle(device, self.memory, self.info.allocation_size, type_index)?;
+        let image = Image::new(device, &self.info.image_info())?.bind_at(&allocation, self.info.offset)?;
+        let semaphore = Semaphore::import_handle(device, self.semaphore)?;
+
+        Ok((image, semaphore))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::exported::{ExportedFrame, ExportedFrameInfo};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::resources::{Image, ImageInfo};
+    use crate::semaphore::Semaphore;
+    use crate::EXTERNAL_HANDLE_TYPE;
+    use ash::vk::{Extent3D, Format, ImageLayout, ImageTiling, ImageType, ImageUsageFlags, MemoryPropertyFlags, SampleCountFlags};
+
+    #[test]
+    fn info_roundtrip() -> Result<(), Error> {
+        let info = ExportedFrameInfo {
+            device_uuid: [1; 16],
+            driver_uuid: [2; 16],
+            format: Format::G8_B8R8_2PLANE_420_UNORM,
+            extent: Extent3D::default().width(1920).height(1080).depth(1),
+            image_type: ImageType::TYPE_2D,
+            tiling: ImageTiling::OPTIMAL,
+            usage: ImageUsageFlags::SAMPLED | ImageUsageFlags::VIDEO_DECODE_DST_KHR,
+            samples: SampleCountFlags::TYPE_1,
+            mip_levels: 1,
+            array_layers: 1,
+            layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            allocation_size: 1 << 33,
+            offset: 1 << 16,
+            type_index: 3,
+            ready_value: 42,
+        };
+
+        let bytes = info.to_bytes();
+
+        assert_eq!(bytes.len(), ExportedFrameInfo::SIZE);
+        assert_eq!(ExportedFrameInfo::from_bytes(&bytes)?, info);
+        assert!(ExportedFrameInfo::from_bytes(&bytes[1..]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn export_import() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+
+        if !device.video_features().external_memory() || !device.video_features().external_semaphore() {
+            return Ok(());
+        }
+
+        let info = ImageInfo::new()
+            .format(Format::R8G8B8A8_UNORM)
+            .samples(SampleCountFlags::TYPE_1)
+            .usage(ImageUsageFlags::TRANSFER_SRC | ImageUsageFlags::TRANSFER_DST)
+            .mip_levels(1)
+            .array_layers(1)
+            .image_type(ImageType::TYPE_2D)
+            .tiling(ImageTiling::OPTIMAL)
+            .extent(Extent3D::default().width(64).height(64).depth(1))
+            .external_memory(EXTERNAL_HANDLE_TYPE);
+        let image = Image::new(&device, &info)?;
+        let requirements = image.memory_requirement();
+        let offset = requirements.alignment().max(1);
+        let type_index = physical_device
+            .heap_infos()
+            .find_type(
+                requirements.memory_type_bits(),
+                MemoryPropertyFlags::DEVICE_LOCAL,
+                MemoryPropertyFlags::empty(),
+            )
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new_exportable(&device, offset + requirements.size(), type_index)?;
+        let image = image.bind_at(&allocation, offset)?;
+        let semaphore = Semaphore::new_exportable(&device, 0)?;
+        let layout = ImageLayout::TRANSFER_SRC_OPTIMAL;
+
+        let (info, memory, exported_semaphore) = ExportedFrame::new(&image, layout, &semaphore, 1)?.into_parts();
+        let info = ExportedFrameInfo::from_bytes(&info.to_bytes())?;
+        let (imported, imported_semaphore) = ExportedFrame::from_parts(info.clone(), memory, exported_semaphore).import(&device)?;
+
+        semaphore.signal(1)?;
+        imported_semaphore.wait(1, 1_000_000_000)?;
+
+        assert_eq!(info.offset(), offset);
+        assert_eq!(info.layout(), layout);
+        assert_eq!(imported.info().get_extent(), image.info().get_extent());
+        assert!(ExportedFrame::new(&image, layout, &Semaphore::new(&device, 0)?, 1).is_err());
+        assert!(ExportedFrame::new(&Image::new(&device, &image.info())?, layout, &semaphore, 1).is_err());
+        assert!(Allocation::new(&device, 1024, requirements.any_heap())?.export_handle().is_err());
+
+        Ok(())
+    }
+}
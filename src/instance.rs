@@ -1,17 +1,26 @@
 use crate::error::Error;
+use crate::validation::{messenger_callback, ValidationCallback, ValidationLog, ValidationMessage};
 use ash::vk;
-use ash::vk::{ApplicationInfo, InstanceCreateFlags, InstanceCreateInfo};
-use std::ffi::CString;
+use ash::vk::{
+    ApplicationInfo, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT, DebugUtilsMessengerCreateInfoEXT,
+    DebugUtilsMessengerEXT, InstanceCreateFlags, InstanceCreateInfo, ValidationFeatureEnableEXT, ValidationFeaturesEXT,
+};
+use std::ffi::{CStr, CString};
+use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
 
+const VALIDATION_LAYER: &CStr = c"VK_LAYER_KHRONOS_validation";
+
 /// Stores information (e.g., app name, version) about the current instance.
-#[derive(Debug)]
 pub struct InstanceInfo {
     app_name: CString,
     engine_name: CString,
     engine_version: u32,
     app_version: u32,
     validation: bool,
+    gpu_assisted_validation: bool,
+    sync_validation: bool,
+    validation_callback: Option<ValidationCallback>,
 }
 
 impl InstanceInfo {
@@ -22,6 +31,9 @@ impl InstanceInfo {
             engine_version: 0,
             app_version: 0,
             validation: false,
+            gpu_assisted_validation: false,
+            sync_validation: false,
+            validation_callback: None,
         }
     }
 
@@ -45,16 +57,54 @@ impl InstanceInfo {
         self
     }
 
-    /// Enables the Vulkan validation layer.
-    ///
-    /// # Errors
+    /// Enables the Vulkan validation layer, which needs the Vulkan SDK installed.
     ///
-    /// Enabling this can cause initialization failures if the validation layers are not present.
-    /// You probably need the Vulkan SDK installed.
+    /// If the layer is missing the instance is created without it, and a warning is reported via
+    /// [`Self::validation_callback`] and [`Instance::take_validation_messages`]. Check
+    /// [`Instance::validation_available`] to know beforehand.
     pub fn validation(mut self, validation: bool) -> Self {
         self.validation = validation;
         self
     }
+
+    /// Enables GPU-assisted validation, which instruments shaders to find, e.g., out of bounds accesses.
+    ///
+    /// Only has an effect together with [`Self::validation`], and slows down pipeline creation and execution considerably.
+    pub fn gpu_assisted_validation(mut self, gpu_assisted_validation: bool) -> Self {
+        self.gpu_assisted_validation = gpu_assisted_validation;
+        self
+    }
+
+    /// Enables synchronization validation, which reports missing or wrong barriers and semaphores.
+    ///
+    /// Only has an effect together with [`Self::validation`].
+    pub fn sync_validation(mut self, sync_validation: bool) -> Self {
+        self.sync_validation = sync_validation;
+        self
+    }
+
+    /// Called with every validation warning or error, instead of printing it.
+    ///
+    /// Messages are also attached to errors of the failing call, see [`Error::validation_messages`].
+    pub fn validation_callback(mut self, callback: impl Fn(&ValidationMessage) + Send + Sync + 'static) -> Self {
+        self.validation_callback = Some(Arc::new(callback));
+        self
+    }
+}
+
+impl Debug for InstanceInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InstanceInfo")
+            .field("app_name", &self.app_name)
+            .field("engine_name", &self.engine_name)
+            .field("engine_version", &self.engine_version)
+            .field("app_version", &self.app_version)
+            .field("validation", &self.validation)
+            .field("gpu_assisted_validation", &self.gpu_assisted_validation)
+            .field("sync_validation", &self.sync_validation)
+            .field("validation_callback", &self.validation_callback.is_some())
+            .finish()
+    }
 }
 
 impl Default for InstanceInfo {
@@ -67,14 +117,18 @@ impl Default for InstanceInfo {
 pub(crate) struct InstanceShared {
     instance: ash::Instance,
     entry: ash::Entry,
+    owned: bool,
+    debug_utils: bool,
+    /// Boxed, as the messenger holds a pointer to it.
+    validation: Box<ValidationLog>,
+    messenger: Option<(ash::ext::debug_utils::Instance, DebugUtilsMessengerEXT)>,
 }
 
 impl InstanceShared {
     pub fn new(info: &InstanceInfo) -> Result<Self, Error> {
         let vulkan_version = vk::make_api_version(0, 1, 3, 0);
-        let debug_layers = [c"VK_LAYER_KHRONOS_validation".as_ptr().cast()];
-        let enabled_layers = if info.validation { debug_layers.as_slice() } else { &[] };
-        let instance_extensions = [c"VK_KHR_portability_enumeration".as_ptr().cast()];
+        let debug_layers = [VALIDATION_LAYER.as_ptr()];
+        let mut instance_extensions = vec![c"VK_KHR_portability_enumeration".as_ptr()];
 
         let app_info = ApplicationInfo::default()
             .application_name(&info.app_name)
@@ -83,19 +137,133 @@ impl InstanceShared {
             .engine_version(info.engine_version)
             .api_version(vulkan_version);
 
-        let instance_create_info = InstanceCreateInfo::default()
+        let entry = unsafe { ash::Entry::load()? };
+        let available = unsafe { entry.enumerate_instance_extension_properties(None)? };
+        let debug_utils = available.iter().any(|x| x.extension_name_as_c_str() == Ok(c"VK_EXT_debug_utils"));
+
+        if debug_utils {
+            instance_extensions.push(c"VK_EXT_debug_utils".as_ptr());
+        }
+
+        let validation = Box::new(ValidationLog::new(info.validation_callback.clone()));
+        let validation_enabled = info.validation && validation_layer_present(&entry);
+
+        if info.validation && !validation_enabled {
+            validation.warn(
+                "validation-layer-missing",
+                "Validation requested, but `VK_LAYER_KHRONOS_validation` is not installed.",
+            );
+        }
+
+        let enabled_layers = if validation_enabled { debug_layers.as_slice() } else { &[] };
+
+        let mut validation_features = Vec::new();
+
+        if info.gpu_assisted_validation {
+            validation_features.push(ValidationFeatureEnableEXT::GPU_ASSISTED);
+            validation_features.push(ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT);
+        }
+
+        if info.sync_validation {
+            validation_features.push(ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION);
+        }
+
+        // Provided by the layer itself, not the driver.
+        let layer_extensions = match validation_enabled {
+            true => unsafe { entry.enumerate_instance_extension_properties(Some(VALIDATION_LAYER))? },
+            false => Vec::new(),
+        };
+        let features_available = layer_extensions
+            .iter()
+            .any(|x| x.extension_name_as_c_str() == Ok(c"VK_EXT_validation_features"));
+        let features_enabled = validation_enabled && features_available && !validation_features.is_empty();
+
+        if validation_enabled && !features_available && !validation_features.is_empty() {
+            validation.warn(
+                "validation-features-missing",
+                "GPU-assisted or sync validation requested, but `VK_EXT_validation_features` is not available.",
+            );
+        }
+
+        if features_enabled {
+            instance_extensions.push(c"VK_EXT_validation_features".as_ptr());
+        }
+
+        let mut validation_features_info = ValidationFeaturesEXT::default().enabled_validation_features(&validation_features);
+
+        let mut instance_create_info = InstanceCreateInfo::default()
             .application_info(&app_info)
             .enabled_layer_names(enabled_layers)
             .enabled_extension_names(&instance_extensions)
             .flags(InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR);
 
+        if features_enabled {
+            instance_create_info = instance_create_info.push_next(&mut validation_features_info);
+        }
+
         unsafe {
-            let entry = ash::Entry::load()?;
             let instance = entry.create_instance(&instance_create_info, None)?;
-            Ok(Self { instance, entry })
+
+            let messenger = match validation_enabled && debug_utils {
+                true => {
+                    let messenger_info = DebugUtilsMessengerCreateInfoEXT::default()
+                        .message_severity(DebugUtilsMessageSeverityFlagsEXT::WARNING | DebugUtilsMessageSeverityFlagsEXT::ERROR)
+                        .message_type(
+                            DebugUtilsMessageTypeFlagsEXT::GENERAL
+                                | DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                                | DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+                        )
+                        .pfn_user_callback(Some(messenger_callback))
+                        .user_data(std::ptr::from_ref(validation.as_ref()).cast_mut().cast());
+
+                    let loader = ash::ext::debug_utils::Instance::new(&entry, &instance);
+
+                    match loader.create_debug_utils_messenger(&messenger_info, None) {
+                        Ok(messenger) => Some((loader, messenger)),
+                        Err(e) => {
+                            instance.destroy_instance(None);
+                            return Err(e.into());
+                        }
+                    }
+                }
+                false => None,
+            };
+
+            Ok(Self {
+                instance,
+                entry,
+                owned: true,
+                debug_utils,
+                validation,
+                messenger,
+            })
         }
     }
 
+    /// Wraps an instance created elsewhere, which we won't destroy.
+    ///
+    /// We don't know its extensions, so debug utils stay off.
+    pub fn from_native(entry: ash::Entry, instance: ash::Instance) -> Self {
+        Self {
+            instance,
+            entry,
+            owned: false,
+            debug_utils: false,
+            validation: Box::new(ValidationLog::new(None)),
+            messenger: None,
+        }
+    }
+
+    /// Validation messages, empty unless validation was enabled via [`InstanceInfo::validation`] (or the layer was missing).
+    pub(crate) fn validation(&self) -> &ValidationLog {
+        &self.validation
+    }
+
+    /// If `VK_EXT_debug_utils` is enabled.
+    pub fn debug_utils(&self) -> bool {
+        self.debug_utils
+    }
+
     pub fn native(&self) -> ash::Instance {
         self.instance.clone()
     }
@@ -105,9 +273,25 @@ impl InstanceShared {
     }
 }
 
+/// If the validation layer (usually part of the Vulkan SDK) is installed.
+fn validation_layer_present(entry: &ash::Entry) -> bool {
+    // SAFETY: Should be safe as the entry is loaded.
+    let layers = unsafe { entry.enumerate_instance_layer_properties() }.unwrap_or_default();
+
+    layers.iter().any(|x| x.layer_name_as_c_str() == Ok(VALIDATION_LAYER))
+}
+
 impl Drop for InstanceShared {
     fn drop(&mut self) {
+        if !self.owned {
+            return;
+        }
+
         unsafe {
+            if let Some((loader, messenger)) = self.messenger.take() {
+                loader.destroy_debug_utils_messenger(messenger, None);
+            }
+
             self.instance.destroy_instance(None);
         }
     }
@@ -125,15 +309,62 @@ impl Instance {
         })
     }
 
+    /// If the validation layer is installed, i.e., [`InstanceInfo::validation`] will have an effect.
+    pub fn validation_available() -> bool {
+        // SAFETY: Loading the library is as safe as it gets with Vulkan.
+        match unsafe { ash::Entry::load() } {
+            Ok(entry) => validation_layer_present(&entry),
+            Err(_) => false,
+        }
+    }
+
+    /// Wraps an instance created by someone else, e.g., a game engine, to decode within the same instance.
+    ///
+    /// The instance is not destroyed when this (and everything created from it) is dropped, that's up to its owner.
+    ///
+    /// # Safety
+    ///
+    /// The instance must have been created from `entry`, with Vulkan 1.3 or later, and must outlive this
+    /// and everything created from it.
+    pub unsafe fn from_ash(entry: ash::Entry, instance: ash::Instance) -> Self {
+        Self {
+            shared: Arc::new(InstanceShared::from_native(entry, instance)),
+        }
+    }
+
     pub(crate) fn shared(&self) -> Arc<InstanceShared> {
         self.shared.clone()
     }
+
+    /// The `ash` instance, to mix in your own Vulkan calls.
+    ///
+    /// Don't destroy it, it's owned by this (unless wrapped via [`Self::from_ash`]).
+    pub fn raw(&self) -> ash::Instance {
+        self.shared.native()
+    }
+
+    /// If `VK_EXT_debug_utils` is enabled, i.e., recorded operations are labeled for tools like RenderDoc.
+    pub fn debug_utils(&self) -> bool {
+        self.shared.debug_utils()
+    }
+
+    /// Removes and returns the validation warnings and errors reported so far (up to the last few hundred).
+    pub fn take_validation_messages(&self) -> Vec<ValidationMessage> {
+        self.shared.validation().take()
+    }
+
+    /// The `ash` entry this instance was created from.
+    pub fn raw_entry(&self) -> ash::Entry {
+        self.shared.native_entry()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use crate::error::Error;
     use crate::instance::{Instance, InstanceInfo, InstanceShared};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
 
     #[test]
     #[cfg(not(miri))]
@@ -154,4 +385,65 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn wrap_instance() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let owner = InstanceShared::new(&instance_info)?;
+
+        // Dropping the wrapper must leave the instance intact for its owner.
+        let instance = unsafe { Instance::from_ash(owner.native_entry(), owner.native()) };
+        drop(instance);
+
+        _ = unsafe { owner.native().enumerate_physical_devices()? };
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn validation_callback() -> Result<(), Error> {
+        let reported = Arc::new(AtomicUsize::new(0));
+        let reported_in = reported.clone();
+        let instance_info = InstanceInfo::new()
+            .app_name("MyApp")?
+            .validation(true)
+            .validation_callback(move |_| _ = reported_in.fetch_add(1, Ordering::Relaxed));
+        let instance = Instance::new(&instance_info)?;
+
+        // Everything reported must be both passed to the callback and kept for errors.
+        assert_eq!(instance.take_validation_messages().len(), reported.load(Ordering::Relaxed));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn validation_fallback() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.validation(true);
+        let instance = Instance::new(&instance_info)?;
+
+        // Without the SDK we still get an instance, but are told why nothing gets validated.
+        if !Instance::validation_available() {
+            let messages = instance.take_validation_messages();
+            assert!(messages.iter().any(|x| x.id_name() == "validation-layer-missing"));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn validation_features() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new()
+            .app_name("MyApp")?
+            .validation(true)
+            .gpu_assisted_validation(true)
+            .sync_validation(true);
+
+        _ = Instance::new(&instance_info)?;
+
+        Ok(())
+    }
 }
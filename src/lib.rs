@@ -26,8 +26,8 @@
 //! - **I'm getting weird errors**
 //!
 //!     We **STRONGLY** recommend you install the [Vulkan SDK](https://vulkan.lunarg.com/) and
-//!     make sure the validation layer is available. In fact, some of the test cases
-//!     require it and will error otherwise.
+//!     make sure the validation layer is available (see `Instance::validation_available`). Without
+//!     it validation is silently skipped, apart from a warning in `Instance::take_validation_messages`.
 //!
 //!     Apart from that, this needs much more work to initialize on various GPUs, help and PRs
 //!     would be greatly appreciated.   
@@ -49,6 +49,16 @@
 //!
 //!     Support for Vulkan (Vulkan video in particular) on CIs is super flaky. Suggestions how to improve this are welcome!
 //!
+//! - **Can I use this from multiple threads?**
+//!
+//!     Yes, devices, queues, resources and sessions are `Send + Sync`. Where Vulkan requires external synchronization (e.g., submitting to a queue, recording a command buffer, mapping memory) we lock internally, also across several `Queue`s for the same Vulkan queue.
+//!     What you still have to ensure is that the GPU is done with a resource before you reuse it, e.g., await a `Submission` before reading a buffer it writes.
+//!
+//! - **Can I mix in my own Vulkan calls?**
+//!
+//!     Yes, most types have a `raw()` method returning their `ash` handle (or loaded functions), and `Instance::from_ash` or `Device::from_raw` wrap ones you created yourself.
+//!     Handles stay owned by whoever created them, so don't destroy ours, and we won't destroy yours.
+//!
 //! - **What's your UB policy?**
 //!
 //!     All Rust code in here should be safe and must never cause undefined behavior (UB). If you find anything that could cause UB, please file an issue.
@@ -72,22 +82,83 @@
 //! [docs.rs-url]: https://docs.rs/vulkan_video/
 //!
 mod allocation;
+mod allocator;
+mod arena;
+#[cfg(feature = "bench")]
+pub mod bench;
 pub(crate) mod commandbuffer;
 mod device;
+#[cfg(feature = "dlpack")]
+pub mod dlpack;
 mod error;
+mod exported;
 mod instance;
 
 pub mod ops;
 mod physicaldevice;
 mod queue;
+#[cfg(feature = "renderdoc")]
+pub mod renderdoc;
 pub mod resources;
+mod semaphore;
 pub mod shader;
+mod stats;
+mod tracking;
+mod validation;
 pub mod video;
 
-pub use allocation::Allocation;
-pub use commandbuffer::CommandBuffer;
-pub use device::Device;
+pub use allocation::{Allocation, ExternalHandle, EXTERNAL_HANDLE_TYPE};
+#[cfg(feature = "gpu-allocator")]
+pub use allocator::GpuAllocator;
+pub use allocator::{Allocator, DedicatedAllocator};
+pub use arena::Arena;
+pub use commandbuffer::{CommandBuffer, CommandPool};
+pub use device::{Device, VideoFeatures};
 pub use error::{Error, Variant};
+pub use exported::{ExportedFrame, ExportedFrameInfo};
 pub use instance::{Instance, InstanceInfo};
-pub use physicaldevice::{HeapInfos, PhysicalDevice, QueueFamilyInfos};
-pub use queue::Queue;
+pub use physicaldevice::{HeapBudget, HeapInfos, PhysicalDevice, QueueFamilyInfo, QueueFamilyInfos};
+pub use queue::{Queue, Submission, SubmitHandle, SubmitInfo};
+pub use semaphore::{Semaphore, EXTERNAL_SEMAPHORE_HANDLE_TYPE};
+pub use stats::{DecoderStats, DeviceStats};
+pub use validation::{ValidationCallback, ValidationMessage};
+
+#[cfg(test)]
+mod test {
+    use crate::ops::{CopyImage2Buffer, DecodeH264, FillBuffer};
+    use crate::resources::{Buffer, Image, ImageView};
+    use crate::video::h264::{H264Decoder, H264StreamInspector};
+    use crate::video::{Frame, FramePool, PooledImage, VideoSession, VideoSessionParameters};
+    use crate::{Allocation, CommandBuffer, CommandPool, Device, Instance, PhysicalDevice, Queue, Semaphore, SubmitHandle};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn types_are_send_sync() {
+        assert_send_sync::<Instance>();
+        assert_send_sync::<PhysicalDevice>();
+        assert_send_sync::<Device>();
+        assert_send_sync::<Queue>();
+        assert_send_sync::<SubmitHandle>();
+        assert_send_sync::<Semaphore>();
+        assert_send_sync::<CommandBuffer>();
+        assert_send_sync::<CommandPool>();
+        assert_send_sync::<Allocation>();
+        assert_send_sync::<Buffer>();
+        assert_send_sync::<Image>();
+        assert_send_sync::<ImageView>();
+        assert_send_sync::<FramePool>();
+        assert_send_sync::<PooledImage>();
+        assert_send_sync::<VideoSession>();
+        assert_send_sync::<VideoSessionParameters>();
+        assert_send_sync::<CopyImage2Buffer>();
+        assert_send_sync::<FillBuffer>();
+        assert_send_sync::<DecodeH264>();
+        assert_send_sync::<Frame>();
+        assert_send_sync::<H264StreamInspector>();
+        assert_send_sync::<H264Decoder>();
+
+        #[cfg(feature = "renderdoc")]
+        assert_send_sync::<crate::renderdoc::RenderDoc>();
+    }
+}
@@ -0,0 +1,268 @@
+use crate::device::{Device, DeviceShared};
+use crate::error::Error;
+use crate::ops::AddToCommandBuffer;
+use crate::queue::CommandBuilder;
+use crate::resources::{Buffer, BufferShared, Image, ImageShared};
+use ash::vk::{
+    AccessFlags2, BufferMemoryBarrier2, DependencyInfoKHR, ImageAspectFlags, ImageLayout, ImageMemoryBarrier2, ImageSubresourceRange,
+    MemoryBarrier2, PipelineStageFlags2, QUEUE_FAMILY_IGNORED, REMAINING_ARRAY_LAYERS, REMAINING_MIP_LEVELS, WHOLE_SIZE,
+};
+use std::sync::Arc;
+
+/// Accesses modifying memory.
+const WRITE_ACCESS: AccessFlags2 = AccessFlags2::from_raw(
+    AccessFlags2::SHADER_WRITE.as_raw()
+        | AccessFlags2::SHADER_STORAGE_WRITE.as_raw()
+        | AccessFlags2::TRANSFER_WRITE.as_raw()
+        | AccessFlags2::HOST_WRITE.as_raw()
+        | AccessFlags2::MEMORY_WRITE.as_raw()
+        | AccessFlags2::VIDEO_DECODE_WRITE_KHR.as_raw()
+        | AccessFlags2::VIDEO_ENCODE_WRITE_KHR.as_raw(),
+);
+
+/// Pipeline stages and the memory accesses they perform, one side of a [`Barrier`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access {
+    stages: PipelineStageFlags2,
+    access: AccessFlags2,
+}
+
+impl Access {
+    /// Execution only, no memory access.
+    pub const NONE: Self = Self::new(PipelineStageFlags2::NONE, AccessFlags2::NONE);
+    pub const HOST_READ: Self = Self::new(PipelineStageFlags2::HOST, AccessFlags2::HOST_READ);
+    pub const HOST_WRITE: Self = Self::new(PipelineStageFlags2::HOST, AccessFlags2::HOST_WRITE);
+    pub const TRANSFER_READ: Self = Self::new(PipelineStageFlags2::TRANSFER, AccessFlags2::TRANSFER_READ);
+    pub const TRANSFER_WRITE: Self = Self::new(PipelineStageFlags2::TRANSFER, AccessFlags2::TRANSFER_WRITE);
+    pub const COMPUTE_READ: Self = Self::new(PipelineStageFlags2::COMPUTE_SHADER, AccessFlags2::SHADER_READ);
+    pub const COMPUTE_WRITE: Self = Self::new(PipelineStageFlags2::COMPUTE_SHADER, AccessFlags2::SHADER_WRITE);
+    pub const VIDEO_DECODE_READ: Self = Self::new(PipelineStageFlags2::VIDEO_DECODE_KHR, AccessFlags2::VIDEO_DECODE_READ_KHR);
+    pub const VIDEO_DECODE_WRITE: Self = Self::new(PipelineStageFlags2::VIDEO_DECODE_KHR, AccessFlags2::VIDEO_DECODE_WRITE_KHR);
+    pub const VIDEO_ENCODE_READ: Self = Self::new(PipelineStageFlags2::VIDEO_ENCODE_KHR, AccessFlags2::VIDEO_ENCODE_READ_KHR);
+    pub const VIDEO_ENCODE_WRITE: Self = Self::new(PipelineStageFlags2::VIDEO_ENCODE_KHR, AccessFlags2::VIDEO_ENCODE_WRITE_KHR);
+
+    pub const fn new(stages: PipelineStageFlags2, access: AccessFlags2) -> Self {
+        Self { stages, access }
+    }
+
+    /// Both accesses, e.g., `Access::TRANSFER_READ.and(Access::COMPUTE_READ)`.
+    pub fn and(self, other: Self) -> Self {
+        Self::new(self.stages | other.stages, self.access | other.access)
+    }
+
+    /// If this includes all stages and accesses of `other`.
+    pub fn contains(&self, other: Self) -> bool {
+        self.stages.contains(other.stages) && self.access.contains(other.access)
+    }
+
+    /// If any of these accesses modify memory.
+    pub fn is_write(&self) -> bool {
+        self.access.intersects(WRITE_ACCESS)
+    }
+
+    pub fn stages(&self) -> PipelineStageFlags2 {
+        self.stages
+    }
+
+    pub fn access(&self) -> AccessFlags2 {
+        self.access
+    }
+}
+
+/// Makes operations wait for earlier ones, e.g., a compute shader for the copy producing its input.
+///
+/// Operations already wait for earlier ones in the same command buffer accessing the same resources. This records
+/// exactly the dependencies given on top, e.g., to move an image into a layout some outside consumer expects:
+///
+/// ```rust,ignore
+/// let barrier = Barrier::new(&device)
+///     .buffer(&buffer, Access::TRANSFER_WRITE, Access::COMPUTE_READ)
+///     .image(&image, Access::VIDEO_DECODE_WRITE, Access::TRANSFER_READ, ImageLayout::GENERAL, ImageLayout::GENERAL);
+///
+/// queue.build_and_submit(&command_buffer, |x| {
+///     copy.run_in(x)?;
+///     barrier.run_in(x)?;
+///     compute.run_in(x)
+/// })?;
+/// ```
+pub struct Barrier {
+    shared_device: Arc<DeviceShared>,
+    memory: Vec<(Access, Access)>,
+    buffers: Vec<(Arc<BufferShared>, Access, Access)>,
+    images: Vec<(Arc<ImageShared>, Access, Access, ImageLayout, ImageLayout)>,
+}
+
+impl Barrier {
+    pub fn new(device: &Device) -> Self {
+        Self {
+            shared_device: device.shared(),
+            memory: Vec::new(),
+            buffers: Vec::new(),
+            images: Vec::new(),
+        }
+    }
+
+    /// Makes `dst` wait for `src`, for all memory.
+    pub fn memory(mut self, src: Access, dst: Access) -> Self {
+        self.memory.push((src, dst));
+        self
+    }
+
+    /// Makes `dst` wait for `src`, for `buffer` only.
+    pub fn buffer(mut self, buffer: &Buffer, src: Access, dst: Access) -> Self {
+        self.buffers.push((buffer.shared(), src, dst));
+        self
+    }
+
+    /// Makes `dst` wait for `src`, for all layers of `image`, which is also transitioned from `old_layout` into `new_layout`.
+    ///
+    /// With `old_layout` being `UNDEFINED` the contents of the image may be discarded.
+    pub fn image(mut self, image: &Image, src: Access, dst: Access, old_layout: ImageLayout, new_layout: ImageLayout) -> Self {
+        self.images.push((image.shared(), src, dst, old_layout, new_layout));
+        self
+    }
+}
+
+impl AddToCommandBuffer for Barrier {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        let native_device = self.shared_device.native();
+        let native_command_buffer = builder.native_command_buffer();
+
+        let memory_barriers = self
+            .memory
+            .iter()
+            .map(|(src, dst)| {
+                MemoryBarrier2::default()
+                    .src_stage_mask(src.stages)
+                    .src_access_mask(src.access)
+                    .dst_stage_mask(dst.stages)
+                    .dst_access_mask(dst.access)
+            })
+            .collect::<Vec<_>>();
+
+        let buffer_barriers = self
+            .buffers
+            .iter()
+            .map(|(buffer, src, dst)| {
+                BufferMemoryBarrier2::default()
+                    .src_stage_mask(src.stages)
+                    .src_access_mask(src.access)
+                    .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+                    .dst_stage_mask(dst.stages)
+                    .dst_access_mask(dst.access)
+                    .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+                    .buffer(buffer.native())
+                    .size(WHOLE_SIZE)
+            })
+            .collect::<Vec<_>>();
+
+        let image_barriers = self
+            .images
+            .iter()
+            .map(|(image, src, dst, old_layout, new_layout)| {
+                // For multi-planar formats `COLOR` covers all planes.
+                let ssr = ImageSubresourceRange::default()
+                    .aspect_mask(ImageAspectFlags::COLOR)
+                    .level_count(REMAINING_MIP_LEVELS)
+                    .layer_count(REMAINING_ARRAY_LAYERS);
+
+                ImageMemoryBarrier2::default()
+                    .src_stage_mask(src.stages)
+                    .src_access_mask(src.access)
+                    .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+                    .old_layout(*old_layout)
+                    .dst_stage_mask(dst.stages)
+                    .dst_access_mask(dst.access)
+                    .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+                    .new_layout(*new_layout)
+                    .image(image.native())
+                    .subresource_range(ssr)
+            })
+            .collect::<Vec<_>>();
+
+        let dependency_info = DependencyInfoKHR::default()
+            .memory_barriers(&memory_barriers)
+            .buffer_memory_barriers(&buffer_barriers)
+            .image_memory_barriers(&image_barriers);
+
+        unsafe {
+            native_device.cmd_pipeline_barrier2(native_command_buffer, &dependency_info);
+        }
+
+        // Let the automatic tracking know where we left things.
+        for (buffer, _, dst) in &self.buffers {
+            builder.assume_buffer(buffer, *dst);
+        }
+
+        for (image, _, dst, _, new_layout) in &self.images {
+            builder.assume_image(image, 0, image.info().get_array_layers(), *dst, *new_layout);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::commandbuffer::CommandBuffer;
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::ops::{Access, AddToCommandBuffer, Barrier, CopyBuffer2Buffer, FillBuffer};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::Queue;
+    use crate::resources::{Buffer, BufferInfo};
+
+    #[test]
+    #[cfg(not(miri))]
+    fn barrier_between_fill_and_copy() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 2 * 1024, host_visible)?;
+
+        let buffer_src = Buffer::new(&allocation, &BufferInfo::new().size(1024))?;
+        let buffer_dst = Buffer::new(&allocation, &BufferInfo::new().size(1024).offset(1024))?;
+
+        let fill_buffer = FillBuffer::new(&buffer_src, 0x11223344);
+        let copy_buffer = CopyBuffer2Buffer::new(&buffer_src, &buffer_dst, 1024);
+        let barrier_fill = Barrier::new(&device).buffer(&buffer_src, Access::TRANSFER_WRITE, Access::TRANSFER_READ);
+        let barrier_host = Barrier::new(&device).memory(Access::TRANSFER_WRITE, Access::HOST_READ);
+
+        queue.build_and_submit(&command_buffer, |x| {
+            fill_buffer.run_in(x)?;
+            barrier_fill.run_in(x)?;
+            copy_buffer.run_in(x)?;
+            barrier_host.run_in(x)?;
+            Ok(())
+        })?;
+
+        let mut data = vec![0; 1024];
+        buffer_dst.download_into(&mut data)?;
+
+        assert_eq!(data[0..4], 0x11223344u32.to_ne_bytes());
+
+        Ok(())
+    }
+
+    #[test]
+    fn access_and() {
+        let access = Access::TRANSFER_READ.and(Access::COMPUTE_READ);
+
+        assert_eq!(access.stages(), Access::TRANSFER_READ.stages() | Access::COMPUTE_READ.stages());
+        assert_eq!(access.access(), Access::TRANSFER_READ.access() | Access::COMPUTE_READ.access());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::device::Device;
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::ops::kernels::{check_size, kernel, Spirv, ThreeBuffers, LINE};
+use crate::ops::{AddToCommandBuffer, Compute};
+use crate::queue::CommandBuilder;
+use crate::resources::{Buffer, BufferShared};
+use std::sync::Arc;
+
+static SQUARED_ERROR: &Spirv<[u8]> = &Spirv(*include_bytes!("shaders/compiled/squared_error.spv"));
+
+/// Measures how much two 8 bit frames (or planes) differ as PSNR, e.g., to check encode quality.
+///
+/// Compares `size` bytes of both buffers, so images have to be copied into buffers first.
+/// The GPU writes partial sums into `sums`, which has to be host visible to read the result.
+///
+/// ```rust,ignore
+/// let sums = Buffer::new(&host_visible, &BufferInfo::new().size(ComparePsnr::sums_size(size)))?;
+/// let compare = ComparePsnr::new(&device, &decoded, &reference, &sums, size)?;
+///
+/// queue.build_and_submit(&command_buffer, |x| compare.run_in(x))?;
+/// println!("{} dB", compare.psnr()?);
+/// ```
+pub struct ComparePsnr {
+    compute: Compute<ThreeBuffers>,
+    sums: Arc<BufferShared>,
+    groups: u32,
+    size: u64,
+}
+
+impl ComparePsnr {
+    /// Bytes the `sums` buffer needs to compare `size` bytes.
+    pub fn sums_size(size: u64) -> u64 {
+        (size / 4).div_ceil(LINE as u64) * 4
+    }
+
+    pub fn new(device: &Device, a: &Buffer, b: &Buffer, sums: &Buffer, size: u64) -> Result<Self, Error> {
+        if size == 0 || !size.is_multiple_of(4) || size / 4 > u32::MAX as u64 {
+            return Err(error!(
+                Variant::InvalidExtent,
+                "Size {size} must be a non-zero multiple of 4 bytes."
+            ));
+        }
+
+        check_size(a, size, "a")?;
+        check_size(b, size, "b")?;
+        check_size(sums, Self::sums_size(size), "sums")?;
+
+        let words = (size / 4) as u32;
+        let groups = words.div_ceil(LINE);
+        let compute = kernel(device, SQUARED_ERROR, &[a, b, sums], &[words], (groups, 1, 1))?;
+
+        Ok(Self {
+            compute,
+            sums: sums.shared(),
+            groups,
+            size,
+        })
+    }
+
+    /// Mean squared error per byte, once the comparison ran.
+    pub fn mse(&self) -> Result<f64, Error> {
+        let mut data = vec![0u8; self.groups as usize * 4];
+
+        self.sums.download_into(&mut data)?;
+
+        let sum = data
+            .chunks_exact(4)
+            .map(|x| u32::from_ne_bytes([x[0], x[1], x[2], x[3]]) as u64)
+            .sum::<u64>();
+
+        Ok(sum as f64 / self.size as f64)
+    }
+
+    /// Peak signal-to-noise ratio in dB, once the comparison ran, infinite for identical data.
+    pub fn psnr(&self) -> Result<f64, Error> {
+        Ok(psnr(self.mse()?))
+    }
+}
+
+impl AddToCommandBuffer for ComparePsnr {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        self.compute.run_in(builder)
+    }
+}
+
+fn psnr(mse: f64) -> f64 {
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::commandbuffer::CommandBuffer;
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::ops::compare::psnr;
+    use crate::ops::{AddToCommandBuffer, ComparePsnr};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::Queue;
+    use crate::resources::{Buffer, BufferInfo};
+
+    #[test]
+    fn psnr_from_mse() {
+        assert_eq!(ComparePsnr::sums_size(4), 4);
+        assert_eq!(ComparePsnr::sums_size(1024), 16);
+        assert_eq!(ComparePsnr::sums_size(1028), 20);
+        assert_eq!(psnr(0.0), f64::INFINITY);
+        assert!((psnr(1.0) - 48.13).abs() < 0.01);
+    }
+
+    #[test]
+    #[cfg(feature = "reflection")]
+    fn kernel_matches_parameters() -> Result<(), Error> {
+        use crate::ops::compare::SQUARED_ERROR;
+        use crate::ops::kernels::ThreeBuffers;
+        use crate::shader::{validate_bindings, ShaderParameterSet};
+
+        validate_bindings(&SQUARED_ERROR.0, "main", &ThreeBuffers::descriptor_types())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn compare_psnr() -> Result<(), Error> {
+        const SIZE: u64 = 64 * 1024;
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 3 * SIZE, host_visible)?;
+        let a = Buffer::new(&allocation, &BufferInfo::new().size(SIZE).offset(0))?;
+        let b = Buffer::new(&allocation, &BufferInfo::new().size(SIZE).offset(SIZE))?;
+        let sums = Buffer::new(&allocation, &BufferInfo::new().size(ComparePsnr::sums_size(SIZE)).offset(2 * SIZE))?;
+
+        // Every byte off by 2 (in both directions), so the MSE is 4.
+        let data = (0..SIZE).map(|x| (x % 200) as u8 + 10).collect::<Vec<_>>();
+        let noisy = data
+            .iter()
+            .enumerate()
+            .map(|(i, x)| if i % 2 == 0 { x + 2 } else { x - 2 })
+            .collect::<Vec<_>>();
+
+        a.upload(&data)?;
+        b.upload(&noisy)?;
+
+        let same = ComparePsnr::new(&device, &a, &a, &sums, SIZE)?;
+        let different = ComparePsnr::new(&device, &a, &b, &sums, SIZE)?;
+        assert!(ComparePsnr::new(&device, &a, &b, &sums, 6).is_err());
+
+        queue.build_and_submit(&command_buffer, |x| same.run_in(x))?;
+        assert_eq!(same.psnr()?, f64::INFINITY);
+
+        queue.build_and_submit(&command_buffer, |x| different.run_in(x))?;
+        assert_eq!(different.mse()?, 4.0);
+
+        Ok(())
+    }
+}
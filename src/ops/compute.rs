@@ -1,13 +1,10 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, PoisonError};
 
-use ash::vk::{
-    AccessFlags, BufferMemoryBarrier, DependencyFlags, DescriptorBufferInfo, DescriptorImageInfo, DescriptorPool, DescriptorPoolCreateInfo,
-    DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorType, ImageAspectFlags, ImageLayout, ImageMemoryBarrier,
-    ImageSubresourceRange, PipelineBindPoint, PipelineStageFlags, WriteDescriptorSet, QUEUE_FAMILY_IGNORED,
-};
+use ash::vk::{DescriptorSet, Extent3D, ImageLayout, PipelineBindPoint, ShaderStageFlags};
 
-use crate::error::Error;
-use crate::ops::AddToCommandBuffer;
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::ops::{Access, AddToCommandBuffer};
 use crate::queue::CommandBuilder;
 use crate::shader::{ParameterType, Pipeline, PipelineShared, ShaderParameterSet};
 
@@ -15,52 +12,73 @@ use crate::shader::{ParameterType, Pipeline, PipelineShared, ShaderParameterSet}
 pub struct Compute<T> {
     shared_pipeline: Arc<PipelineShared<T>>,
     dispatch_groups: (u32, u32, u32),
-    native_descriptor_pool: DescriptorPool,
-    native_descriptor_sets: Vec<DescriptorSet>,
-    params: T,
+    /// `None` when descriptors are pushed instead.
+    native_descriptor_set: Option<DescriptorSet>,
+    /// Held while recording, as descriptor set updates must be externally synchronized.
+    recording: Mutex<()>,
+    parameters: Vec<ParameterType>,
+    push_constants: Vec<u8>,
 }
 
 impl<T: ShaderParameterSet> Compute<T> {
     #[allow(unused)]
     fn new(pipeline: &Pipeline<T>, params: T, dispatch_groups: (u32, u32, u32)) -> Result<Self, Error> {
-        let shared_pipeline = pipeline.shared();
-        let shared_parameters = shared_pipeline.parameters();
-        let native_device = shared_pipeline.device().native();
-        let native_descriptor_set_layout = shared_parameters.native_layout();
-        let native_descriptor_set_layouts = &[native_descriptor_set_layout];
+        Self::new_with_parameters(pipeline, params.parameter_types(), dispatch_groups)
+    }
 
-        let descriptor_pool_storage = DescriptorPoolSize::default().descriptor_count(3).ty(DescriptorType::STORAGE_BUFFER);
-        let descriptor_pool_image = DescriptorPoolSize::default().descriptor_count(3).ty(DescriptorType::STORAGE_IMAGE);
+    /// Dispatches enough groups of `local_size` (the shader's workgroup size) to cover `image_extent`.
+    ///
+    /// Each side is rounded up, so the shader should skip invocations outside the image.
+    pub fn for_image(pipeline: &Pipeline<T>, params: T, image_extent: Extent3D, local_size: (u32, u32, u32)) -> Result<Self, Error> {
+        Self::new(pipeline, params, dispatch_groups(image_extent, local_size)?)
+    }
 
-        let descriptor_pool_sizes = &[descriptor_pool_storage, descriptor_pool_image];
-        let descriptor_pool_create_info = DescriptorPoolCreateInfo::default().pool_sizes(descriptor_pool_sizes).max_sets(1);
+    /// Creates a dispatch with `parameters` matching `T`, without borrowing them as `T` would.
+    pub(crate) fn new_with_parameters(
+        pipeline: &Pipeline<T>,
+        parameters: Vec<ParameterType>,
+        dispatch_groups: (u32, u32, u32),
+    ) -> Result<Self, Error> {
+        let shared_pipeline = pipeline.shared();
+        let native_descriptor_set = match shared_pipeline.parameters().push_descriptors() {
+            true => None,
+            false => Some(shared_pipeline.descriptors().allocate()?),
+        };
+
+        Ok(Self {
+            shared_pipeline,
+            dispatch_groups,
+            native_descriptor_set,
+            recording: Mutex::new(()),
+            parameters,
+            push_constants: Vec::new(),
+        })
+    }
 
-        unsafe {
-            let descriptor_pool = native_device.create_descriptor_pool(&descriptor_pool_create_info, None)?;
+    /// Passes `data` as push constants, for pipelines created with room for them.
+    pub(crate) fn push_constants(mut self, data: &[u8]) -> Self {
+        self.push_constants = data.to_vec();
+        self
+    }
+}
 
-            let descriptor_set_alloc_info = DescriptorSetAllocateInfo::default()
-                .descriptor_pool(descriptor_pool)
-                .set_layouts(native_descriptor_set_layouts);
+/// Workgroups of `local_size` needed to cover `extent`.
+fn dispatch_groups(extent: Extent3D, local_size: (u32, u32, u32)) -> Result<(u32, u32, u32), Error> {
+    if local_size.0 == 0 || local_size.1 == 0 || local_size.2 == 0 {
+        return Err(error!(Variant::InvalidExtent, "Workgroup size {local_size:?} must not be empty."));
+    }
 
-            let descriptor_sets = native_device.allocate_descriptor_sets(&descriptor_set_alloc_info)?;
+    let x = extent.width.div_ceil(local_size.0);
+    let y = extent.height.div_ceil(local_size.1);
+    let z = extent.depth.div_ceil(local_size.2);
 
-            Ok(Self {
-                shared_pipeline: pipeline.shared(),
-                dispatch_groups,
-                native_descriptor_pool: descriptor_pool,
-                native_descriptor_sets: descriptor_sets,
-                params,
-            })
-        }
-    }
+    Ok((x, y, z))
 }
 
 impl<T> Drop for Compute<T> {
     fn drop(&mut self) {
-        unsafe {
-            let native_device = self.shared_pipeline.device().native();
-
-            native_device.destroy_descriptor_pool(self.native_descriptor_pool, None);
+        if let Some(set) = self.native_descriptor_set {
+            self.shared_pipeline.descriptors().release(set);
         }
     }
 }
@@ -72,85 +90,26 @@ impl<T: ShaderParameterSet> AddToCommandBuffer for Compute<T> {
         let native_pipeline = self.shared_pipeline.native();
         let native_layout = self.shared_pipeline.layout();
 
-        let mut acquire_image = Vec::new();
-        let mut acquire_buffer = Vec::new();
-        let mut release_buffer = Vec::new();
-        let release_image = Vec::new();
+        let _recording = self.recording.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // Shaders may read and write all their parameters.
+        let access = Access::COMPUTE_READ.and(Access::COMPUTE_WRITE);
 
         unsafe {
-            let descriptor_set = self.native_descriptor_sets[0];
             let bind_point = PipelineBindPoint::COMPUTE;
 
-            for (i, param) in self.params.parameter_types().iter().enumerate() {
+            if let Some(descriptor_set) = self.native_descriptor_set {
+                self.shared_pipeline.parameters().write(descriptor_set, &self.parameters);
+            }
+
+            for param in &self.parameters {
                 match param {
-                    ParameterType::Buffer { native, size } => {
-                        let mut write_descriptor_sets = Vec::new();
-
-                        let descriptor_buffer_info = DescriptorBufferInfo::default().buffer(*native).range(*size);
-                        let descriptor_buffer_infos = [descriptor_buffer_info];
-
-                        let write_descriptor_set = WriteDescriptorSet::default()
-                            .dst_binding(i as u32)
-                            .dst_set(descriptor_set)
-                            .descriptor_type(DescriptorType::STORAGE_BUFFER)
-                            .buffer_info(&descriptor_buffer_infos);
-
-                        write_descriptor_sets.push(write_descriptor_set);
-
-                        let barrier_acquire = BufferMemoryBarrier::default()
-                            .size(*size)
-                            .buffer(*native)
-                            .src_access_mask(AccessFlags::MEMORY_READ | AccessFlags::MEMORY_WRITE)
-                            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
-                            .dst_access_mask(AccessFlags::MEMORY_READ | AccessFlags::MEMORY_WRITE)
-                            .dst_queue_family_index(builder.queue_family_index());
-
-                        let barrier_release = BufferMemoryBarrier::default()
-                            .size(*size)
-                            .buffer(*native)
-                            .src_access_mask(AccessFlags::MEMORY_READ | AccessFlags::MEMORY_WRITE)
-                            .src_queue_family_index(builder.queue_family_index())
-                            .dst_access_mask(AccessFlags::MEMORY_READ | AccessFlags::MEMORY_WRITE)
-                            .dst_queue_family_index(QUEUE_FAMILY_IGNORED);
-
-                        acquire_buffer.push(barrier_acquire);
-                        release_buffer.push(barrier_release);
-
-                        native_device.update_descriptor_sets(&write_descriptor_sets, &[]);
+                    ParameterType::Buffer(buffer) => {
+                        builder.access_buffer(buffer, access);
                     }
-                    ParameterType::ImageView { native_view, native_image } => {
-                        let mut write_descriptor_sets = Vec::new();
-
-                        let descriptor_image_info = DescriptorImageInfo::default()
-                            .image_view(*native_view)
-                            .image_layout(ImageLayout::GENERAL);
-
-                        let descriptor_image_infos = [descriptor_image_info];
-
-                        let write_descriptor_set = WriteDescriptorSet::default()
-                            .dst_binding(i as u32)
-                            .dst_set(descriptor_set)
-                            .descriptor_type(DescriptorType::STORAGE_IMAGE)
-                            .image_info(&descriptor_image_infos);
-
-                        write_descriptor_sets.push(write_descriptor_set);
-
-                        native_device.update_descriptor_sets(&write_descriptor_sets, &[]);
-
-                        let ssr = ImageSubresourceRange::default()
-                            .aspect_mask(ImageAspectFlags::COLOR)
-                            .level_count(1)
-                            .layer_count(1);
-
-                        let barrier = ImageMemoryBarrier::default()
-                            .old_layout(ImageLayout::UNDEFINED)
-                            .new_layout(ImageLayout::GENERAL)
-                            .image(*native_image)
-                            .subresource_range(ssr)
-                            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
-                            .dst_queue_family_index(QUEUE_FAMILY_IGNORED);
-
-                        acquire_image.push(barrier);
+                    ParameterType::ImageView(view) => {
+                        let ssr = view.subresource_range();
+                        builder.access_image(&view.image(), ssr.base_array_layer, ssr.layer_count, access, ImageLayout::GENERAL);
                     }
                 }
             }
@@ -159,34 +118,29 @@ impl<T: ShaderParameterSet> AddToCommandBuffer for Compute<T> {
             let y = self.dispatch_groups.1;
             let z = self.dispatch_groups.2;
 
+            builder.record_barriers();
+
             native_device.cmd_bind_pipeline(native_command_buffer, PipelineBindPoint::COMPUTE, native_pipeline);
-            native_device.cmd_bind_descriptor_sets(
-                native_command_buffer,
-                bind_point,
-                native_layout,
-                0,
-                &self.native_descriptor_sets,
-                &[],
-            );
-            native_device.cmd_pipeline_barrier(
-                native_command_buffer,
-                PipelineStageFlags::ALL_COMMANDS,
-                PipelineStageFlags::COMPUTE_SHADER,
-                DependencyFlags::empty(),
-                &[],
-                &acquire_buffer,
-                &acquire_image,
-            );
+
+            match self.native_descriptor_set {
+                Some(set) => native_device.cmd_bind_descriptor_sets(native_command_buffer, bind_point, native_layout, 0, &[set], &[]),
+                None => self
+                    .shared_pipeline
+                    .parameters()
+                    .push(native_command_buffer, native_layout, &self.parameters),
+            }
+
+            if !self.push_constants.is_empty() {
+                native_device.cmd_push_constants(
+                    native_command_buffer,
+                    native_layout,
+                    ShaderStageFlags::COMPUTE,
+                    0,
+                    &self.push_constants,
+                );
+            }
+
             native_device.cmd_dispatch(native_command_buffer, x, y, z);
-            native_device.cmd_pipeline_barrier(
-                native_command_buffer,
-                PipelineStageFlags::ALL_COMMANDS,
-                PipelineStageFlags::HOST,
-                DependencyFlags::empty(),
-                &[],
-                &release_buffer,
-                &release_image,
-            );
 
             Ok(())
         }
@@ -205,7 +159,7 @@ mod test {
     use crate::error;
     use crate::error::{Error, Variant};
     use crate::instance::{Instance, InstanceInfo};
-    use crate::ops::compute::Compute;
+    use crate::ops::compute::{dispatch_groups, Compute};
     use crate::ops::copyi2b::CopyImage2Buffer;
     use crate::ops::AddToCommandBuffer;
     use crate::physicaldevice::PhysicalDevice;
@@ -213,6 +167,16 @@ mod test {
     use crate::resources::{Buffer, BufferInfo, Image, ImageInfo, ImageView, ImageViewInfo};
     use crate::shader::{Parameters, Pipeline, Shader};
 
+    #[test]
+    fn dispatch_groups_round_up() {
+        let extent = |width, height| Extent3D::default().width(width).height(height).depth(1);
+
+        assert_eq!(dispatch_groups(extent(1920, 1080), (16, 16, 1)).ok(), Some((120, 68, 1)));
+        assert_eq!(dispatch_groups(extent(64, 48), (16, 16, 1)).ok(), Some((4, 3, 1)));
+        assert_eq!(dispatch_groups(extent(1, 1), (64, 1, 1)).ok(), Some((1, 1, 1)));
+        assert!(dispatch_groups(extent(64, 48), (16, 0, 1)).is_err());
+    }
+
     #[test]
     #[cfg(not(miri))]
     #[allow(clippy::erasing_op)]
@@ -262,6 +226,49 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    #[cfg(not(miri))]
+    fn compute_per_frame() -> Result<(), Error> {
+        let shader_code = include_bytes!("../../tests/shaders/compiled/hello_world.spv");
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 1024, host_visible)?;
+        let buffer = Buffer::new(&allocation, &BufferInfo::new().size(1024))?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let parameters = Parameters::new(&device)?;
+        let shader = Shader::new(&device, shader_code, "main", &parameters)?;
+        let pipeline = Pipeline::new(&device, &shader)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+
+        // More than fit into one descriptor pool, alive at the same time.
+        let computes = (0..40)
+            .map(|_| Compute::new(&pipeline, (&buffer, &buffer, &buffer), (1, 1, 1)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        drop(computes);
+
+        // Per frame, reusing the sets of the ones just dropped.
+        for _ in 0..40 {
+            let compute = Compute::new(&pipeline, (&buffer, &buffer, &buffer), (1, 1, 1))?;
+
+            queue.build_and_submit(&command_buffer, |x| compute.run_in(x))?;
+        }
+
+        Ok(())
+    }
+
     #[test]
     #[cfg(not(miri))]
     fn submit_compute_images() -> Result<(), Error> {
@@ -313,7 +320,8 @@ mod test {
         let buffer_info = BufferInfo::new().size(512 * 512 * 4);
         let buffer = Buffer::new(&allocation_host_visible, &buffer_info)?;
 
-        let compute = Compute::new(&pipeline, (&image_view,), (16, 16, 1))?;
+        // Matches `local_size` of `image_color.glsl`.
+        let compute = Compute::for_image(&pipeline, (&image_view,), image_info.get_extent(), (32, 32, 1))?;
         let copy = CopyImage2Buffer::new(&image, &buffer, ImageAspectFlags::COLOR);
 
         // TODO: SOMETHING HERE GOES WRONG
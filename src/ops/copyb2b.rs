@@ -1,15 +1,31 @@
-use crate::error::Error;
-use crate::ops::AddToCommandBuffer;
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::ops::{Access, AddToCommandBuffer};
 use crate::queue::CommandBuilder;
 use crate::resources::{Buffer, BufferShared};
 use ash::vk::BufferCopy;
 use std::sync::Arc;
 
 /// Performs a buffer-to-buffer copy operation.
+///
+/// Copies the first `size` bytes by default, several regions can be copied at once, e.g., to pack payloads into one
+/// bitstream buffer:
+///
+/// ```rust,ignore
+/// let regions = [
+///     BufferCopy::default().src_offset(0).dst_offset(0).size(100),
+///     BufferCopy::default().src_offset(1024).dst_offset(100).size(50),
+/// ];
+/// let copy = CopyBuffer2Buffer::new(&staging, &bitstream, 0).regions(&regions);
+/// ```
+///
+/// Regions must not be empty. When copying within one buffer, no region may read what any of them writes.
 pub struct CopyBuffer2Buffer {
     source: Arc<BufferShared>,
     destination: Arc<BufferShared>,
-    size: u64,
+    src_offset: u64,
+    dst_offset: u64,
+    regions: Vec<BufferCopy>,
 }
 
 impl CopyBuffer2Buffer {
@@ -17,9 +33,29 @@ impl CopyBuffer2Buffer {
         Self {
             source: source.shared(),
             destination: destination.shared(),
-            size,
+            src_offset: 0,
+            dst_offset: 0,
+            regions: vec![BufferCopy::default().size(size)],
         }
     }
+
+    /// Reads at `offset` bytes into the source, added to the offsets of all regions.
+    pub fn src_offset(mut self, offset: u64) -> Self {
+        self.src_offset = offset;
+        self
+    }
+
+    /// Writes at `offset` bytes into the destination, added to the offsets of all regions.
+    pub fn dst_offset(mut self, offset: u64) -> Self {
+        self.dst_offset = offset;
+        self
+    }
+
+    /// Copies `regions` instead of the first `size` bytes.
+    pub fn regions(mut self, regions: &[BufferCopy]) -> Self {
+        self.regions = regions.to_vec();
+        self
+    }
 }
 
 impl AddToCommandBuffer for CopyBuffer2Buffer {
@@ -29,8 +65,46 @@ impl AddToCommandBuffer for CopyBuffer2Buffer {
         let native_source = self.source.native();
         let native_destination = self.destination.native();
 
-        let region = BufferCopy::default().size(self.size);
-        let regions = [region];
+        if self.regions.is_empty() {
+            return Err(error!(Variant::InvalidCopyRegion, "No regions to copy."));
+        }
+
+        let mut regions = Vec::with_capacity(self.regions.len());
+
+        for x in &self.regions {
+            if x.size == 0 {
+                return Err(error!(Variant::InvalidCopyRegion, "Copy region {x:?} is empty."));
+            }
+
+            let src_end = x.src_offset.checked_add(self.src_offset).and_then(|y| y.checked_add(x.size));
+            let dst_end = x.dst_offset.checked_add(self.dst_offset).and_then(|y| y.checked_add(x.size));
+
+            if src_end.is_none_or(|y| y > self.source.size()) || dst_end.is_none_or(|y| y > self.destination.size()) {
+                return Err(error!(Variant::BufferTooSmall, "Copy region {x:?} exceeds buffers."));
+            }
+
+            // Can't overflow, as the ends didn't.
+            regions.push(
+                x.src_offset(x.src_offset + self.src_offset)
+                    .dst_offset(x.dst_offset + self.dst_offset),
+            );
+        }
+
+        if self.source.native() == self.destination.native() {
+            let overlapping = regions.iter().any(|a| {
+                regions
+                    .iter()
+                    .any(|b| a.src_offset < b.dst_offset + b.size && b.dst_offset < a.src_offset + a.size)
+            });
+
+            if overlapping {
+                return Err(error!(Variant::InvalidCopyRegion, "Copy regions overlap within the same buffer."));
+            }
+        }
+
+        builder.access_buffer(&self.source, Access::TRANSFER_READ);
+        builder.access_buffer(&self.destination, Access::TRANSFER_WRITE);
+        builder.record_barriers();
 
         unsafe {
             native_device.cmd_copy_buffer(native_command_buffer, native_source, native_destination, &regions);
@@ -51,6 +125,7 @@ mod test {
     use crate::queue::Queue;
     use crate::resources::{Buffer, BufferInfo};
     use crate::{error, Variant};
+    use ash::vk::BufferCopy;
 
     #[test]
     #[cfg(not(miri))]
@@ -96,4 +171,77 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn copy_buffer_regions() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 2 * 1024, host_visible)?;
+
+        let buffer_info_src = BufferInfo::new().size(1024);
+        let buffer_info_dst = BufferInfo::new().size(1024).offset(1024);
+
+        let buffer_src = Buffer::new(&allocation, &buffer_info_src)?;
+        let buffer_dst = Buffer::new(&allocation, &buffer_info_dst)?;
+
+        buffer_src.upload(&(0..=255).collect::<Vec<u8>>())?;
+
+        let regions = [
+            BufferCopy::default().src_offset(0).dst_offset(0).size(4),
+            BufferCopy::default().src_offset(100).dst_offset(4).size(2),
+        ];
+        let fill_buffer = FillBuffer::new(&buffer_dst, 0);
+        let copy_buffer = CopyBuffer2Buffer::new(&buffer_src, &buffer_dst, 0)
+            .src_offset(10)
+            .dst_offset(16)
+            .regions(&regions);
+        let copy_too_large = CopyBuffer2Buffer::new(&buffer_src, &buffer_dst, 1024).dst_offset(4);
+        let copy_overflow = CopyBuffer2Buffer::new(&buffer_src, &buffer_dst, 4).src_offset(u64::MAX);
+        let copy_nothing = CopyBuffer2Buffer::new(&buffer_src, &buffer_dst, 4).regions(&[]);
+        let copy_empty = CopyBuffer2Buffer::new(&buffer_src, &buffer_dst, 0);
+        let copy_within = CopyBuffer2Buffer::new(&buffer_dst, &buffer_dst, 8).dst_offset(512);
+        let copy_overlapping = CopyBuffer2Buffer::new(&buffer_dst, &buffer_dst, 8).dst_offset(4);
+
+        queue.build_and_submit(&command_buffer, |x| {
+            fill_buffer.run_in(x)?;
+            copy_buffer.run_in(x)?;
+            copy_within.run_in(x)?;
+            assert!(copy_too_large
+                .run_in(x)
+                .is_err_and(|e| matches!(e.variant(), Variant::BufferTooSmall)));
+            assert!(copy_overflow
+                .run_in(x)
+                .is_err_and(|e| matches!(e.variant(), Variant::BufferTooSmall)));
+            assert!(copy_nothing
+                .run_in(x)
+                .is_err_and(|e| matches!(e.variant(), Variant::InvalidCopyRegion)));
+            assert!(copy_empty
+                .run_in(x)
+                .is_err_and(|e| matches!(e.variant(), Variant::InvalidCopyRegion)));
+            assert!(copy_overlapping
+                .run_in(x)
+                .is_err_and(|e| matches!(e.variant(), Variant::InvalidCopyRegion)));
+            Ok(())
+        })?;
+
+        let mut data = vec![0; 1024];
+        buffer_dst.download_into(&mut data)?;
+
+        assert_eq!(data[14..24], [0, 0, 10, 11, 12, 13, 110, 111, 0, 0]);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,133 @@
+use crate::error::Error;
+use crate::ops::{Access, AddToCommandBuffer};
+use crate::queue::CommandBuilder;
+use crate::resources::{plane_extent, Buffer, BufferShared, Image, ImageShared};
+use ash::vk::{BufferImageCopy, ImageAspectFlags, ImageLayout, ImageSubresourceLayers};
+use std::sync::Arc;
+
+/// Performs a buffer-to-image copy operation, e.g., to upload a plane of a frame to encode.
+pub struct CopyBuffer2Image {
+    buffer: Arc<BufferShared>,
+    image: Arc<ImageShared>,
+    aspect_mask: ImageAspectFlags,
+    array_layer: u32,
+}
+
+impl CopyBuffer2Image {
+    pub fn new(buffer: &Buffer, image: &Image, aspect_mask: ImageAspectFlags) -> Self {
+        Self {
+            buffer: buffer.shared(),
+            image: image.shared(),
+            aspect_mask,
+            array_layer: 0,
+        }
+    }
+
+    /// Copies into array layer `array_layer` of the image instead of the first one.
+    pub fn array_layer(mut self, array_layer: u32) -> Self {
+        self.array_layer = array_layer;
+        self
+    }
+}
+
+impl AddToCommandBuffer for CopyBuffer2Image {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        let native_device = self.image.device().native();
+        let native_command_buffer = builder.native_command_buffer();
+        let native_image = self.image.native();
+        let native_buffer = self.buffer.native();
+
+        let image_info = self.image.info();
+        let extent = plane_extent(image_info.get_format(), self.aspect_mask, image_info.get_extent());
+
+        let srl = ImageSubresourceLayers::default()
+            .aspect_mask(self.aspect_mask)
+            .base_array_layer(self.array_layer)
+            .layer_count(1);
+
+        let copy = BufferImageCopy::default().image_extent(extent).image_subresource(srl);
+
+        builder.access_buffer(&self.buffer, Access::TRANSFER_READ);
+        builder.access_image(&self.image, self.array_layer, 1, Access::TRANSFER_WRITE, ImageLayout::GENERAL);
+        builder.record_barriers();
+
+        unsafe {
+            native_device.cmd_copy_buffer_to_image(native_command_buffer, native_buffer, native_image, ImageLayout::GENERAL, &[copy]);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::commandbuffer::CommandBuffer;
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::ops::{AddToCommandBuffer, CopyBuffer2Image, CopyImage2Buffer};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::Queue;
+    use crate::resources::{Buffer, BufferInfo, Image, ImageInfo};
+    use ash::vk::{
+        Extent3D, Format, ImageAspectFlags, ImageLayout, ImageTiling, ImageType, ImageUsageFlags, MemoryPropertyFlags, SampleCountFlags,
+    };
+
+    #[test]
+    #[cfg(not(miri))]
+    fn copy_buffer_to_image_and_back() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let image_info = ImageInfo::new()
+            .format(Format::R8_UNORM)
+            .samples(SampleCountFlags::TYPE_1)
+            .usage(ImageUsageFlags::TRANSFER_SRC | ImageUsageFlags::TRANSFER_DST)
+            .mip_levels(1)
+            .array_layers(1)
+            .image_type(ImageType::TYPE_2D)
+            .tiling(ImageTiling::OPTIMAL)
+            .layout(ImageLayout::UNDEFINED)
+            .extent(Extent3D::default().width(64).height(64).depth(1));
+        let image = Image::new(&device, &image_info)?;
+        let allocation_image = Allocation::for_requirements(&device, &image.memory_requirement(), MemoryPropertyFlags::DEVICE_LOCAL)?;
+        let image = image.bind(&allocation_image)?;
+        let allocation = Allocation::new(&device, 2 * 64 * 64, host_visible)?;
+        let buffer_in = Buffer::new(&allocation, &BufferInfo::new().size(64 * 64))?;
+        let buffer_out = Buffer::new(&allocation, &BufferInfo::new().size(64 * 64).offset(64 * 64))?;
+
+        buffer_in.upload(&[7u8; 64 * 64])?;
+
+        let buffer2image = CopyBuffer2Image::new(&buffer_in, &image, ImageAspectFlags::COLOR);
+        let image2buffer = CopyImage2Buffer::new(&image, &buffer_out, ImageAspectFlags::COLOR);
+
+        queue.build_and_submit(&command_buffer, |x| {
+            buffer2image.run_in(x)?;
+            Ok(())
+        })?;
+
+        queue.build_and_submit(&command_buffer, |x| {
+            image2buffer.run_in(x)?;
+            Ok(())
+        })?;
+
+        let mut data_out = [0u8; 64 * 64];
+        buffer_out.download_into(&mut data_out)?;
+
+        assert_eq!(data_out[64 * 64 - 1], 7);
+
+        Ok(())
+    }
+}
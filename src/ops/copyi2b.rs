@@ -1,16 +1,17 @@
 use crate::error::Error;
-use crate::ops::AddToCommandBuffer;
+use crate::ops::{Access, AddToCommandBuffer};
 use crate::queue::CommandBuilder;
-use crate::resources::{Buffer, BufferShared, Image, ImageShared};
-use ash::vk::{BufferImageCopy, ImageAspectFlags, ImageLayout, ImageSubresourceLayers};
-use std::rc::Rc;
+use crate::resources::{plane_extent, Buffer, BufferShared, Image, ImageShared};
+use ash::vk::{BufferImageCopy, Extent3D, ImageAspectFlags, ImageLayout, ImageSubresourceLayers, Offset3D, Rect2D};
 use std::sync::Arc;
 
 /// Performs an image-to-buffer copy operation.
 pub struct CopyImage2Buffer {
-    image: Rc<ImageShared>,
+    image: Arc<ImageShared>,
     buffer: Arc<BufferShared>,
     aspect_mask: ImageAspectFlags,
+    array_layer: u32,
+    region: Option<Rect2D>,
 }
 
 impl CopyImage2Buffer {
@@ -19,8 +20,24 @@ impl CopyImage2Buffer {
             image: image.shared(),
             buffer: buffer.shared(),
             aspect_mask,
+            array_layer: 0,
+            region: None,
         }
     }
+
+    /// Copies from array layer `array_layer` of the image instead of the first one.
+    pub fn array_layer(mut self, array_layer: u32) -> Self {
+        self.array_layer = array_layer;
+        self
+    }
+
+    /// Copies only `region` of the image, e.g., the cropped area of a decoded frame, tightly packed into the buffer.
+    ///
+    /// The region is given in luma samples, and scaled down for subsampled chroma planes.
+    pub fn region(mut self, region: Rect2D) -> Self {
+        self.region = Some(region);
+        self
+    }
 }
 
 impl AddToCommandBuffer for CopyImage2Buffer {
@@ -31,13 +48,39 @@ impl AddToCommandBuffer for CopyImage2Buffer {
         let native_buffer = self.buffer.native();
 
         let image_info = self.image.info();
+        let format = image_info.get_format();
+        let (offset, extent) = match self.region {
+            Some(region) => {
+                // Offsets scale like extents, chroma offsets are always even for subsampled formats.
+                let offset = Extent3D::default()
+                    .width(region.offset.x as u32)
+                    .height(region.offset.y as u32)
+                    .depth(1);
+                let offset = plane_extent(format, self.aspect_mask, offset);
+                let extent = Extent3D::default().width(region.extent.width).height(region.extent.height).depth(1);
+
+                (
+                    Offset3D::default().x(offset.width as i32).y(offset.height as i32),
+                    plane_extent(format, self.aspect_mask, extent),
+                )
+            }
+            None => (Offset3D::default(), plane_extent(format, self.aspect_mask, image_info.get_extent())),
+        };
 
-        let srl = ImageSubresourceLayers::default().aspect_mask(self.aspect_mask).layer_count(1);
+        let srl = ImageSubresourceLayers::default()
+            .aspect_mask(self.aspect_mask)
+            .base_array_layer(self.array_layer)
+            .layer_count(1);
 
         let copy = BufferImageCopy::default()
-            .image_extent(image_info.get_extent())
+            .image_offset(offset)
+            .image_extent(extent)
             .image_subresource(srl);
 
+        builder.access_image(&self.image, self.array_layer, 1, Access::TRANSFER_READ, ImageLayout::GENERAL);
+        builder.access_buffer(&self.buffer, Access::TRANSFER_WRITE);
+        builder.record_barriers();
+
         unsafe {
             native_device.cmd_copy_image_to_buffer(native_command_buffer, native_image, ImageLayout::GENERAL, native_buffer, &[copy]);
             Ok(())
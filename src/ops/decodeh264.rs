@@ -1,26 +1,26 @@
 use crate::error::Error;
-use crate::ops::AddToCommandBuffer;
+use crate::ops::{Access, AddToCommandBuffer};
 use crate::queue::CommandBuilder;
-use crate::resources::{Buffer, BufferShared, ImageView, ImageViewShared};
-use crate::video::{VideoSessionParameters, VideoSessionParametersShared};
-use ash::vk::native::{
-    StdVideoDecodeH264PictureInfo, StdVideoDecodeH264PictureInfoFlags, StdVideoDecodeH264ReferenceInfo,
-    StdVideoDecodeH264ReferenceInfoFlags,
+use crate::resources::{Buffer, BufferShared, ImageShared, ImageView, ImageViewShared};
+use crate::video::h264::SliceHeader;
+use crate::video::{
+    DpbSlot, DpbSlotManager, ReferenceSlots, VideoQueryPool, VideoQueryPoolShared, VideoSessionParameters, VideoSessionParametersShared,
 };
+use ash::vk::native::{StdVideoDecodeH264PictureInfo, StdVideoDecodeH264PictureInfoFlags};
 use ash::vk::{
-    AccessFlags2, BufferMemoryBarrier2, DependencyInfoKHR, Extent2D, ImageAspectFlags, ImageLayout, ImageMemoryBarrier2,
-    ImageSubresourceRange, PipelineStageFlags2, VideoBeginCodingInfoKHR, VideoCodingControlFlagsKHR, VideoCodingControlInfoKHR,
-    VideoDecodeCapabilityFlagsKHR, VideoDecodeH264DpbSlotInfoKHR, VideoDecodeH264PictureInfoKHR, VideoDecodeInfoKHR, VideoEndCodingInfoKHR,
-    VideoPictureResourceInfoKHR, VideoReferenceSlotInfoKHR, QUEUE_FAMILY_IGNORED,
+    AccessFlags2, BufferMemoryBarrier2, DependencyInfoKHR, Extent2D, ImageLayout, ImageMemoryBarrier2, ImageSubresourceRange,
+    MemoryBarrier2, Offset2D, PipelineStageFlags2, QueryControlFlags, VideoBeginCodingInfoKHR, VideoCodingControlFlagsKHR,
+    VideoCodingControlInfoKHR, VideoDecodeCapabilityFlagsKHR, VideoDecodeH264PictureInfoKHR, VideoDecodeH264PictureLayoutFlagsKHR,
+    VideoDecodeInfoKHR, VideoEndCodingInfoKHR, VideoInlineQueryInfoKHR, VideoPictureResourceInfoKHR, VideoSessionCreateFlagsKHR,
+    QUEUE_FAMILY_IGNORED, WHOLE_SIZE,
 };
-use std::rc::Rc;
 use std::sync::Arc;
 
 /// Specifies which part of a buffer to decode.
 #[derive(Copy, Clone)]
 pub struct DecodeInfo {
-    offset: u64,
-    size: u64,
+    pub(crate) offset: u64,
+    pub(crate) size: u64,
 }
 
 impl DecodeInfo {
@@ -29,13 +29,24 @@ impl DecodeInfo {
     }
 }
 
+/// DPB state to decode against, see [`DecodeH264::dpb`].
+struct Dpb {
+    manager: DpbSlotManager,
+    setup: DpbSlot,
+    views: Vec<Arc<ImageViewShared>>,
+}
+
 /// Decode a H.264 video frame.
 pub struct DecodeH264 {
     shared_parameters: Arc<VideoSessionParametersShared>,
     shared_buffer: Arc<BufferShared>,
-    shared_image_view: Rc<ImageViewShared>,
-    shared_ref_view: Rc<ImageViewShared>,
+    shared_image_view: Arc<ImageViewShared>,
+    shared_ref_view: Arc<ImageViewShared>,
     decode_info: DecodeInfo,
+    slice_offsets: Vec<u32>,
+    dpb: Option<Dpb>,
+    header: Option<SliceHeader>,
+    query: Option<(Arc<VideoQueryPoolShared>, u32)>,
 }
 
 impl DecodeH264 {
@@ -52,181 +63,536 @@ impl DecodeH264 {
             shared_image_view: target_view.shared(),
             shared_ref_view: ref_view.shared(),
             decode_info: *decode_info,
+            slice_offsets: vec![0],
+            dpb: None,
+            header: None,
+            query: None,
         }
     }
+
+    /// Offsets of the start codes of all slices of the picture, relative to the offset of the [`DecodeInfo`].
+    ///
+    /// Without this, the range is decoded as a single slice starting at its first byte.
+    pub fn slice_offsets(mut self, slice_offsets: &[u32]) -> Self {
+        self.slice_offsets = slice_offsets.to_vec();
+        self
+    }
+
+    /// Decodes into `setup` while referencing all active references of `dpb`.
+    ///
+    /// `dpb_views` hold the picture of each DPB slot, indexed by slot index. Without this, the picture is decoded
+    /// into slot 0 without any references (i.e., only intra frames will decode properly).
+    pub fn dpb(mut self, dpb: &DpbSlotManager, setup: DpbSlot, dpb_views: &[ImageView]) -> Self {
+        self.dpb = Some(Dpb {
+            manager: dpb.clone(),
+            setup,
+            views: dpb_views.iter().map(|x| x.shared()).collect(),
+        });
+        self
+    }
+
+    /// Takes parameter set ids, reference and field flags from the first slice header of the picture.
+    ///
+    /// Without this, the picture is decoded as a reference frame using SPS and PPS 0.
+    pub(crate) fn slice_header(mut self, header: &SliceHeader) -> Self {
+        self.header = Some(header.clone());
+        self
+    }
+
+    /// Records the result status of this decode into query `index` of `queries`.
+    ///
+    /// If the device supports `VK_KHR_video_maintenance1` the query is recorded inline with the decode.
+    pub fn query(mut self, queries: &VideoQueryPool, index: u32) -> Self {
+        self.query = Some((queries.shared(), index));
+        self
+    }
 }
 
-impl AddToCommandBuffer for DecodeH264 {
-    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
-        let shared_video_session = self.shared_parameters.video_session();
+/// A picture about to be decoded, with everything the structures recorded for it point to.
+struct Picture {
+    manager: DpbSlotManager,
+    setup: DpbSlot,
+    dpb_views: Vec<Arc<ImageViewShared>>,
+    reference_slots: ReferenceSlots<'static>,
+    resource_dst: VideoPictureResourceInfoKHR<'static>,
+    std: StdVideoDecodeH264PictureInfo,
+    second_field: bool,
+}
 
-        let native_buffer_h264 = self.shared_buffer.native();
-        let native_device = shared_video_session.device().native();
-        let native_queue_fns = shared_video_session.queue_fns();
-        let native_decode_fns = shared_video_session.decode_fns();
-        let native_command_buffer = builder.native_command_buffer();
+/// An image (or array layer) accessed by a decode, also used by [`DecodeH265`](crate::ops::DecodeH265).
+#[derive(Clone)]
+pub(super) struct Transition {
+    image: Arc<ImageShared>,
+    ssr: ImageSubresourceRange,
+    /// Layout before decoding, `UNDEFINED` if the contents can be discarded.
+    old_layout: ImageLayout,
+    /// Layout while decoding.
+    decode_layout: ImageLayout,
+    /// Layout after decoding, see [`ImageInfo::external_layout`](crate::resources::ImageInfo::external_layout).
+    external_layout: ImageLayout,
+    /// Family owning the image outside of decodes, if it has to be transferred.
+    owner: Option<u32>,
+}
+
+impl DecodeH264 {
+    fn picture(&self) -> Result<Picture, Error> {
+        let shared_video_session = self.shared_parameters.video_session();
         let native_view_dst = self.shared_image_view.native();
-        let native_view_ref = self.shared_ref_view.native();
-        let native_image_dst = self.shared_image_view.image().native();
-        let native_image_ref = self.shared_ref_view.image().native();
-        let native_video_session = shared_video_session.native();
-        let native_video_session_parameters = self.shared_parameters.native();
 
         let image_info = self.shared_image_view.image().info();
         let image_extent = image_info.get_extent();
         let extent = Extent2D::default().width(image_extent.width).height(image_extent.height);
 
-        let picture_resource_dst = VideoPictureResourceInfoKHR::default()
-            .coded_extent(extent)
-            .image_view_binding(native_view_dst);
-
-        let picture_resource_ref = VideoPictureResourceInfoKHR::default()
-            .coded_extent(extent)
-            .image_view_binding(native_view_ref);
+        let dpb_and_output_coincide = shared_video_session
+            .decode_capabilities()
+            .flags()
+            .contains(VideoDecodeCapabilityFlagsKHR::DPB_AND_OUTPUT_COINCIDE);
 
-        let mut f = StdVideoDecodeH264ReferenceInfoFlags {
-            _bitfield_align_1: [],
-            _bitfield_1: Default::default(),
-            __bindgen_padding_0: Default::default(),
+        let (manager, setup, dpb_views) = match &self.dpb {
+            Some(dpb) => (dpb.manager.clone(), dpb.setup, dpb.views.clone()),
+            None if dpb_and_output_coincide => (DpbSlotManager::new(1, 0), DpbSlot::default(), vec![self.shared_image_view.clone()]),
+            None => (DpbSlotManager::new(1, 0), DpbSlot::default(), vec![self.shared_ref_view.clone()]),
         };
-        f.set_used_for_long_term_reference(1);
 
-        let s = StdVideoDecodeH264ReferenceInfo {
-            flags: f,
-            FrameNum: 0,
-            reserved: 0,
-            PicOrderCnt: [0, 0],
+        let mut picture_resources = dpb_views
+            .iter()
+            .map(|x| {
+                VideoPictureResourceInfoKHR::default()
+                    .coded_extent(extent)
+                    .image_view_binding(x.native())
+            })
+            .collect::<Vec<_>>();
+
+        // With interleaved lines fields use the entire frame, with separate planes each field has its own region.
+        let field_resource = |resource: VideoPictureResourceInfoKHR<'static>| match self.header.as_ref() {
+            Some(header)
+                if header.field_pic_flag
+                    && shared_video_session.h264_picture_layout() == VideoDecodeH264PictureLayoutFlagsKHR::INTERLACED_SEPARATE_PLANES =>
+            {
+                let granularity = shared_video_session.decode_capabilities().field_offset_granularity();
+                let field_height = extent.height.div_ceil(2);
+                let offset_y = match header.bottom_field_flag {
+                    true => field_height.next_multiple_of(granularity.y.max(1) as u32) as i32,
+                    false => 0,
+                };
+
+                resource
+                    .coded_offset(Offset2D { x: 0, y: offset_y })
+                    .coded_extent(Extent2D::default().width(extent.width).height(field_height))
+            }
+            _ => resource,
         };
 
-        let mut video_decode_h264_dpb_slot_info = VideoDecodeH264DpbSlotInfoKHR::default().std_reference_info(&s);
+        if let Some(resource) = picture_resources.get_mut(setup.index() as usize) {
+            *resource = field_resource(*resource);
+        }
 
-        let picture_resource_choice = if self
-            .shared_parameters
-            .video_session()
-            .decode_capabilities()
-            .flags()
-            .contains(VideoDecodeCapabilityFlagsKHR::DPB_AND_OUTPUT_COINCIDE)
-        {
-            &picture_resource_dst
+        let reference_slots = manager.reference_slots(&setup, &picture_resources)?;
+
+        let resource_dst = if dpb_and_output_coincide {
+            picture_resources[setup.index() as usize]
         } else {
-            &picture_resource_ref
+            field_resource(
+                VideoPictureResourceInfoKHR::default()
+                    .coded_extent(extent)
+                    .image_view_binding(native_view_dst),
+            )
         };
 
-        let video_reference_slot = VideoReferenceSlotInfoKHR::default()
-            .push_next(&mut video_decode_h264_dpb_slot_info)
-            .slot_index(0)
-            .picture_resource(picture_resource_choice);
-
-        let begin_coding_info = VideoBeginCodingInfoKHR::default()
-            .video_session(native_video_session)
-            .video_session_parameters(native_video_session_parameters);
-
-        let end_coding_info = VideoEndCodingInfoKHR::default();
-
         let mut stdflags = StdVideoDecodeH264PictureInfoFlags {
             _bitfield_align_1: Default::default(),
             _bitfield_1: Default::default(),
             __bindgen_padding_0: Default::default(),
         };
 
-        stdflags.set_is_intra(1);
+        stdflags.set_is_intra(reference_slots.references().is_empty() as u32);
         stdflags.set_is_reference(1);
 
+        let second_field = self.header.as_ref().is_some_and(|x| x.field_pic_flag) && setup.fields() == [true, true];
+
+        if let Some(header) = &self.header {
+            stdflags.set_is_reference((header.nal_ref_idc != 0) as u32);
+            stdflags.set_IdrPicFlag(header.idr as u32);
+            stdflags.set_field_pic_flag(header.field_pic_flag as u32);
+            stdflags.set_bottom_field_flag(header.bottom_field_flag as u32);
+            stdflags.set_complementary_field_pair(second_field as u32);
+        }
+
         let std = StdVideoDecodeH264PictureInfo {
             flags: stdflags,
-            seq_parameter_set_id: 0,
-            pic_parameter_set_id: 0,
+            seq_parameter_set_id: self.header.as_ref().map_or(0, |x| x.seq_parameter_set_id),
+            pic_parameter_set_id: self.header.as_ref().map_or(0, |x| x.pic_parameter_set_id),
             reserved1: 0,
             reserved2: 0,
-            frame_num: 0,
-            idr_pic_id: 0,
-            PicOrderCnt: [0, 0], // TODO: ???
+            frame_num: setup.frame_num() as u16,
+            idr_pic_id: self.header.as_ref().map_or(0, |x| x.idr_pic_id as u16),
+            PicOrderCnt: setup.pic_order_cnt(),
         };
 
-        let video_coding_control = VideoCodingControlInfoKHR::default().flags(VideoCodingControlFlagsKHR::RESET);
-        let mut video_decode_info_h264 = VideoDecodeH264PictureInfoKHR::default().std_picture_info(&std).slice_offsets(&[0]);
+        Ok(Picture {
+            manager,
+            setup,
+            dpb_views,
+            reference_slots,
+            resource_dst,
+            std,
+            second_field,
+        })
+    }
+
+    /// The images `picture` is decoded into or references.
+    fn transitions(&self, picture: &Picture) -> Vec<Transition> {
+        let setup = picture.setup;
+
+        // The target (and the setup slot) is overwritten, unless it's the second field of a pair, which must keep the first one.
+        let overwritten = !picture.second_field;
+        let transition_setup = Transition::new(
+            &picture.dpb_views[setup.index() as usize],
+            overwritten,
+            ImageLayout::VIDEO_DECODE_DPB_KHR,
+        );
+        let transition_dst = Transition::new(&self.shared_image_view, overwritten, ImageLayout::VIDEO_DECODE_DST_KHR);
+
+        // A distinct output picture must be in the decode destination layout, DPB pictures in the DPB layout.
+        let mut images = vec![transition_setup.clone()];
+
+        if !transition_setup.same_layer(&transition_dst) {
+            images.push(transition_dst);
+        }
+
+        for reference in picture.manager.references().iter().filter(|x| x.index() != setup.index()) {
+            let view = &picture.dpb_views[reference.index() as usize];
+            images.push(Transition::new(view, false, ImageLayout::VIDEO_DECODE_DPB_KHR));
+        }
+
+        images
+    }
+
+    /// Records decoding `picture`, within a video coding scope.
+    fn record(&self, builder: &mut CommandBuilder, picture: &Picture) {
+        let shared_video_session = self.shared_parameters.video_session();
 
-        let video_decode_info = VideoDecodeInfoKHR::default()
+        let native_device = shared_video_session.device().native();
+        let native_queue_fns = shared_video_session.queue_fns();
+        let native_decode_fns = shared_video_session.decode_fns();
+        let native_command_buffer = builder.native_command_buffer();
+        let reference_slots = &picture.reference_slots;
+
+        let video_coding_control = VideoCodingControlInfoKHR::default().flags(VideoCodingControlFlagsKHR::RESET);
+        let mut video_decode_info_h264 = VideoDecodeH264PictureInfoKHR::default()
+            .std_picture_info(&picture.std)
+            .slice_offsets(&self.slice_offsets);
+
+        let inline_queries = shared_video_session
+            .info()
+            .get_flags()
+            .contains(VideoSessionCreateFlagsKHR::INLINE_QUERIES);
+        let mut video_inline_query = self.query.as_ref().map(|(queries, index)| {
+            VideoInlineQueryInfoKHR::default()
+                .query_pool(queries.native())
+                .first_query(*index)
+                .query_count(1)
+        });
+
+        let mut video_decode_info = VideoDecodeInfoKHR::default()
             .push_next(&mut video_decode_info_h264)
-            .src_buffer(native_buffer_h264)
+            .src_buffer(self.shared_buffer.native())
             .src_buffer_offset(self.decode_info.offset)
             .src_buffer_range(self.decode_info.size)
-            // .src_buffer_range(2736)
-            .dst_picture_resource(picture_resource_dst)
-            .setup_reference_slot(&video_reference_slot);
+            .dst_picture_resource(picture.resource_dst)
+            .setup_reference_slot(reference_slots.setup())
+            .reference_slots(reference_slots.references());
+
+        if let Some(video_inline_query) = video_inline_query.as_mut().filter(|_| inline_queries) {
+            video_decode_info = video_decode_info.push_next(video_inline_query);
+        }
 
         unsafe {
-            let ssr = ImageSubresourceRange::default()
-                .aspect_mask(ImageAspectFlags::COLOR)
-                .level_count(1)
-                .layer_count(1);
-
-            let image_barrier_dst = ImageMemoryBarrier2::default()
-                .src_stage_mask(PipelineStageFlags2::NONE)
-                .src_access_mask(AccessFlags2::NONE)
-                .src_queue_family_index(QUEUE_FAMILY_IGNORED)
-                .old_layout(ImageLayout::UNDEFINED)
-                .dst_stage_mask(PipelineStageFlags2::VIDEO_DECODE_KHR)
-                .dst_access_mask(AccessFlags2::VIDEO_DECODE_WRITE_KHR)
-                .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
-                .new_layout(ImageLayout::VIDEO_DECODE_DPB_KHR)
-                .image(native_image_dst)
-                .subresource_range(ssr);
+            // Resetting deactivates all DPB slots, so only do that if we don't reference anything.
+            if reference_slots.references().is_empty() {
+                (native_queue_fns.cmd_control_video_coding_khr)(native_command_buffer, &video_coding_control);
+            }
+
+            match &self.query {
+                Some((queries, index)) if !inline_queries => {
+                    native_device.cmd_begin_query(native_command_buffer, queries.native(), *index, QueryControlFlags::empty());
+                    (native_decode_fns.cmd_decode_video_khr)(native_command_buffer, &video_decode_info);
+                    native_device.cmd_end_query(native_command_buffer, queries.native(), *index);
+                }
+                _ => (native_decode_fns.cmd_decode_video_khr)(native_command_buffer, &video_decode_info),
+            }
+        }
+    }
+}
 
-            let image_release_dst = ImageMemoryBarrier2::default()
-                .src_stage_mask(PipelineStageFlags2::VIDEO_DECODE_KHR)
-                .src_access_mask(AccessFlags2::VIDEO_DECODE_WRITE_KHR)
-                .src_queue_family_index(QUEUE_FAMILY_IGNORED)
-                .old_layout(ImageLayout::VIDEO_DECODE_DPB_KHR)
-                .dst_stage_mask(PipelineStageFlags2::BOTTOM_OF_PIPE)
-                .dst_access_mask(AccessFlags2::NONE_KHR)
-                .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
-                .new_layout(ImageLayout::GENERAL)
-                .image(native_image_dst)
-                .subresource_range(ssr);
+/// Records `decodes`, which must share their session parameters, within a single video coding scope.
+fn record_scope(decodes: &[DecodeH264], builder: &mut CommandBuilder) -> Result<(), Error> {
+    let Some(first) = decodes.first() else {
+        return Ok(());
+    };
+
+    let shared_video_session = first.shared_parameters.video_session();
+
+    let native_device = shared_video_session.device().native();
+    let native_queue_fns = shared_video_session.queue_fns();
+    let native_command_buffer = builder.native_command_buffer();
+
+    let pictures = decodes.iter().map(|x| x.picture()).collect::<Result<Vec<_>, _>>()?;
+
+    // Every image is transitioned once, into the layout of the first decode accessing it.
+    let mut images = Vec::<Transition>::new();
+
+    for (decode, picture) in decodes.iter().zip(&pictures) {
+        for image in decode.transitions(picture) {
+            if images.iter().all(|x| !x.same_layer(&image)) {
+                images.push(image);
+            }
+        }
+    }
 
-            let buffer_barrier = BufferMemoryBarrier2::default()
+    // Likewise, each DPB slot is bound once, as active if the first decode using it references it.
+    let mut begin_coding_slots = Vec::new();
+    let mut bound_slots = Vec::new();
+
+    for picture in &pictures {
+        let slots = picture.reference_slots.begin_coding_slots();
+
+        for (slot, index) in slots.into_iter().zip(picture.reference_slots.slot_indices()) {
+            if !bound_slots.contains(index) {
+                bound_slots.push(*index);
+                begin_coding_slots.push(slot);
+            }
+        }
+    }
+
+    let mut buffers = decodes.iter().map(|x| x.shared_buffer.native()).collect::<Vec<_>>();
+    buffers.dedup();
+
+    let queue_family_index = builder.queue_family_index();
+    let image_barriers = images.iter().map(|x| x.acquire(queue_family_index)).collect::<Vec<_>>();
+    let image_barriers_release = images.iter().map(|x| x.release(queue_family_index)).collect::<Vec<_>>();
+
+    let buffer_barriers = buffers
+        .iter()
+        .map(|x| {
+            BufferMemoryBarrier2::default()
                 .src_stage_mask(PipelineStageFlags2::HOST)
                 .src_access_mask(AccessFlags2::HOST_WRITE)
                 .src_queue_family_index(QUEUE_FAMILY_IGNORED)
                 .dst_stage_mask(PipelineStageFlags2::VIDEO_DECODE_KHR)
                 .dst_access_mask(AccessFlags2::VIDEO_DECODE_READ_KHR)
                 .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
-                .buffer(native_buffer_h264)
-                .size(256 * 16);
-
-            let buffer_barrier_release = BufferMemoryBarrier2::default()
+                .buffer(*x)
+                .size(WHOLE_SIZE)
+        })
+        .collect::<Vec<_>>();
+
+    let buffer_barriers_release = buffers
+        .iter()
+        .map(|x| {
+            BufferMemoryBarrier2::default()
                 .src_stage_mask(PipelineStageFlags2::VIDEO_DECODE_KHR)
                 .src_access_mask(AccessFlags2::VIDEO_DECODE_READ_KHR)
                 .src_queue_family_index(QUEUE_FAMILY_IGNORED)
                 .dst_stage_mask(PipelineStageFlags2::TOP_OF_PIPE)
                 .dst_access_mask(AccessFlags2::NONE)
                 .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
-                .buffer(native_buffer_h264)
-                .size(256 * 16);
+                .buffer(*x)
+                .size(WHOLE_SIZE)
+        })
+        .collect::<Vec<_>>();
+
+    // Later pictures reference what earlier ones wrote.
+    let memory_barriers_between = [MemoryBarrier2::default()
+        .src_stage_mask(PipelineStageFlags2::VIDEO_DECODE_KHR)
+        .src_access_mask(AccessFlags2::VIDEO_DECODE_WRITE_KHR)
+        .dst_stage_mask(PipelineStageFlags2::VIDEO_DECODE_KHR)
+        .dst_access_mask(AccessFlags2::VIDEO_DECODE_READ_KHR | AccessFlags2::VIDEO_DECODE_WRITE_KHR)];
+
+    let dependency_info = DependencyInfoKHR::default()
+        .buffer_memory_barriers(&buffer_barriers)
+        .image_memory_barriers(&image_barriers);
+
+    let dependency_info_between = DependencyInfoKHR::default().memory_barriers(&memory_barriers_between);
+
+    let dependency_info_release = DependencyInfoKHR::default()
+        .buffer_memory_barriers(&buffer_barriers_release)
+        .image_memory_barriers(&image_barriers_release);
+
+    let begin_coding_info = VideoBeginCodingInfoKHR::default()
+        .video_session(shared_video_session.native())
+        .video_session_parameters(first.shared_parameters.native())
+        .reference_slots(&begin_coding_slots);
+
+    let end_coding_info = VideoEndCodingInfoKHR::default();
+
+    // Bitstreams might have been written by earlier commands, e.g., copies packing them.
+    for decode in decodes {
+        builder.access_buffer(&decode.shared_buffer, Access::VIDEO_DECODE_READ);
+    }
 
-            let buffer_barriers = &[buffer_barrier];
-            let buffer_barriers_release = &[buffer_barrier_release];
-            let image_barriers = &[image_barrier_dst];
-            let image_barriers_release = &[image_release_dst];
+    builder.record_barriers();
 
-            let dependency_info = DependencyInfoKHR::default()
-                .buffer_memory_barriers(buffer_barriers)
-                .image_memory_barriers(image_barriers);
+    unsafe {
+        native_device.cmd_pipeline_barrier2(native_command_buffer, &dependency_info);
 
-            let dependency_info_release = DependencyInfoKHR::default()
-                .buffer_memory_barriers(buffer_barriers_release)
-                .image_memory_barriers(image_barriers_release);
+        // Queries must be reset before use, and outside of a video coding scope.
+        for (queries, index) in decodes.iter().filter_map(|x| x.query.as_ref()) {
+            native_device.cmd_reset_query_pool(native_command_buffer, queries.native(), *index, 1);
+        }
 
-            native_device.cmd_pipeline_barrier2(native_command_buffer, &dependency_info);
-            (native_queue_fns.cmd_begin_video_coding_khr)(native_command_buffer, &begin_coding_info);
-            (native_queue_fns.cmd_control_video_coding_khr)(native_command_buffer, &video_coding_control);
-            (native_decode_fns.cmd_decode_video_khr)(native_command_buffer, &video_decode_info);
-            (native_queue_fns.cmd_end_video_coding_khr)(native_command_buffer, &end_coding_info);
-            native_device.cmd_pipeline_barrier2(native_command_buffer, &dependency_info_release);
+        (native_queue_fns.cmd_begin_video_coding_khr)(native_command_buffer, &begin_coding_info);
 
-            Ok(())
+        for (i, (decode, picture)) in decodes.iter().zip(&pictures).enumerate() {
+            if i > 0 {
+                native_device.cmd_pipeline_barrier2(native_command_buffer, &dependency_info_between);
+            }
+
+            decode.record(builder, picture);
+        }
+
+        (native_queue_fns.cmd_end_video_coding_khr)(native_command_buffer, &end_coding_info);
+        native_device.cmd_pipeline_barrier2(native_command_buffer, &dependency_info_release);
+    }
+
+    for x in &images {
+        x.assume_released(builder);
+    }
+
+    Ok(())
+}
+
+impl Transition {
+    /// The image (or array layer) behind `view`, as barriers apply to images and several DPB slots may share one.
+    ///
+    /// Images rest in their external layout between decodes, and might be owned by another queue family. Unless
+    /// `overwritten` their contents are kept. Images of other families are always acquired with their contents though,
+    /// so the owner's release barrier has a matching acquire. Otherwise images are in whatever layout earlier commands
+    /// left them.
+    pub(super) fn new(view: &ImageViewShared, overwritten: bool, decode_layout: ImageLayout) -> Self {
+        let image = view.image();
+        let info = image.info();
+        let owner = info.get_queue_family_index();
+        let ssr = view.subresource_range();
+        let old_layout = match (overwritten, owner) {
+            (true, None) => ImageLayout::UNDEFINED,
+            (false, None) => image.layout(ssr.base_array_layer),
+            (_, Some(_)) => info.get_external_layout(),
+        };
+
+        Self {
+            image,
+            ssr,
+            old_layout,
+            decode_layout,
+            external_layout: info.get_external_layout(),
+            owner,
+        }
+    }
+
+    pub(super) fn same_layer(&self, other: &Self) -> bool {
+        (self.image.native(), self.ssr.base_array_layer) == (other.image.native(), other.ssr.base_array_layer)
+    }
+
+    /// The families to transfer the image between, from its owner to `queue_family_index` if they differ.
+    fn families(&self, queue_family_index: u32) -> (u32, u32) {
+        match self.owner {
+            Some(owner) if owner != queue_family_index => (owner, queue_family_index),
+            _ => (QUEUE_FAMILY_IGNORED, QUEUE_FAMILY_IGNORED),
+        }
+    }
+
+    pub(super) fn acquire(&self, queue_family_index: u32) -> ImageMemoryBarrier2<'static> {
+        let (src_family, dst_family) = self.families(queue_family_index);
+
+        // Waits for earlier decodes, e.g., of a previous scope recorded into the same command buffer.
+        ImageMemoryBarrier2::default()
+            .src_stage_mask(PipelineStageFlags2::VIDEO_DECODE_KHR)
+            .src_access_mask(AccessFlags2::VIDEO_DECODE_WRITE_KHR)
+            .src_queue_family_index(src_family)
+            .old_layout(self.old_layout)
+            .dst_stage_mask(PipelineStageFlags2::VIDEO_DECODE_KHR)
+            .dst_access_mask(AccessFlags2::VIDEO_DECODE_READ_KHR | AccessFlags2::VIDEO_DECODE_WRITE_KHR)
+            .dst_queue_family_index(dst_family)
+            .new_layout(self.decode_layout)
+            .image(self.image.native())
+            .subresource_range(self.ssr)
+    }
+
+    pub(super) fn release(&self, queue_family_index: u32) -> ImageMemoryBarrier2<'static> {
+        let (dst_family, src_family) = self.families(queue_family_index);
+
+        ImageMemoryBarrier2::default()
+            .src_stage_mask(PipelineStageFlags2::VIDEO_DECODE_KHR)
+            .src_access_mask(AccessFlags2::VIDEO_DECODE_WRITE_KHR)
+            .src_queue_family_index(src_family)
+            .old_layout(self.decode_layout)
+            .dst_stage_mask(PipelineStageFlags2::BOTTOM_OF_PIPE)
+            .dst_access_mask(AccessFlags2::NONE_KHR)
+            .dst_queue_family_index(dst_family)
+            .new_layout(self.external_layout)
+            .image(self.image.native())
+            .subresource_range(self.ssr)
+    }
+
+    /// Lets `builder` know the image is in its external layout after [`Self::release`].
+    pub(super) fn assume_released(&self, builder: &mut CommandBuilder) {
+        builder.assume_image(
+            &self.image,
+            self.ssr.base_array_layer,
+            self.ssr.layer_count,
+            Access::VIDEO_DECODE_WRITE,
+            self.external_layout,
+        );
+    }
+}
+
+impl AddToCommandBuffer for DecodeH264 {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        record_scope(std::slice::from_ref(self), builder)
+    }
+}
+
+/// Decode several H.264 pictures, e.g., a whole GOP, recorded into a single command buffer.
+///
+/// Consecutive decodes sharing session parameters are recorded within a single video coding scope, with barriers
+/// between them so later pictures can reference earlier ones. Build the decodes as for one-by-one decoding, i.e.,
+/// mark each picture in the [`DpbSlotManager`] before building the next decode:
+///
+/// ```rust,ignore
+/// let mut decodes = Vec::new();
+///
+/// for (frame_num, decode_info) in decode_infos.iter().enumerate() {
+///     let slot = dpb.next_slot(frame_num as u32, poc, frame_num == 0)?;
+///     decodes.push(DecodeH264::new(&buffer, &parameters, &dpb_views[slot.index() as usize], &dpb_views[0], decode_info).dpb(&dpb, slot, &dpb_views));
+///     dpb.mark_reference(slot)?;
+/// }
+///
+/// queue.build_and_submit(&command_buffer, |x| DecodeH264Batch::new(decodes).run_in(x))?;
+/// ```
+///
+/// Unless DPB and output coincide, each decode needs a distinct target view, as all of them run before any output
+/// can be copied.
+pub struct DecodeH264Batch {
+    decodes: Vec<DecodeH264>,
+}
+
+impl DecodeH264Batch {
+    pub fn new(decodes: Vec<DecodeH264>) -> Self {
+        Self { decodes }
+    }
+}
+
+impl AddToCommandBuffer for DecodeH264Batch {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        for decodes in self
+            .decodes
+            .chunk_by(|x, y| Arc::ptr_eq(&x.shared_parameters, &y.shared_parameters))
+        {
+            record_scope(decodes, builder)?;
         }
+
+        Ok(())
     }
 }
 
@@ -239,14 +605,15 @@ mod test {
     use crate::error::{Error, Variant};
     use crate::instance::{Instance, InstanceInfo};
     use crate::ops::decodeh264::DecodeInfo;
-    use crate::ops::{AddToCommandBuffer, CopyImage2Buffer, DecodeH264};
+    use crate::ops::{AddToCommandBuffer, CopyImage2Buffer, DecodeH264, DecodeH264Batch};
     use crate::physicaldevice::PhysicalDevice;
     use crate::queue::Queue;
     use crate::resources::{Buffer, BufferInfo, Image, ImageInfo, ImageView, ImageViewInfo};
     use crate::video::h264::H264StreamInspector;
-    use crate::video::{VideoSession, VideoSessionParameters};
+    use crate::video::{access_units, nal_units, DpbSlotManager, VideoQueryPool, VideoSession, VideoSessionInfo, VideoSessionParameters};
     use ash::vk::{
-        Extent3D, Format, ImageAspectFlags, ImageLayout, ImageTiling, ImageType, ImageUsageFlags, ImageViewType, SampleCountFlags,
+        Extent3D, Format, ImageAspectFlags, ImageLayout, ImageTiling, ImageType, ImageUsageFlags, ImageViewType, MemoryPropertyFlags,
+        QueryResultStatusKHR, SampleCountFlags, VideoDecodeCapabilityFlagsKHR,
     };
 
     #[test]
@@ -254,7 +621,12 @@ mod test {
     fn decode_h264() -> Result<(), Error> {
         let h264_data = include_bytes!("../../tests/videos/multi_512x512.h264");
 
-        let stream_inspector = H264StreamInspector::new();
+        let mut stream_inspector = H264StreamInspector::new();
+
+        for nal in nal_units(h264_data) {
+            stream_inspector.feed_nal(nal)?;
+        }
+
         let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
         let instance = Instance::new(&instance_info)?;
         let physical_device = PhysicalDevice::new_any(&instance)?;
@@ -277,9 +649,8 @@ mod test {
 
         let image_dst = Image::new_video_target(&device, &image_dst_info, &stream_inspector)?;
         let image_ref = Image::new_video_target(&device, &image_dst_info, &stream_inspector)?;
-        let heap_image = image_dst.memory_requirement().any_heap();
-        let allocation_image_dst = Allocation::new(&device, 512 * 512 * 4, heap_image)?;
-        let allocation_image_ref = Allocation::new(&device, 512 * 512 * 4, heap_image)?;
+        let allocation_image_dst = Allocation::for_requirements(&device, &image_dst.memory_requirement(), MemoryPropertyFlags::empty())?;
+        let allocation_image_ref = Allocation::for_requirements(&device, &image_ref.memory_requirement(), MemoryPropertyFlags::empty())?;
         let image_dst = image_dst.bind(&allocation_image_dst)?;
         let image_ref = image_ref.bind(&allocation_image_ref)?;
 
@@ -324,9 +695,10 @@ mod test {
         let buffer_info_output = BufferInfo::new().size(512 * 512 * 4);
         let buffer_output = Buffer::new(&allocation_output, &buffer_info_output)?;
 
-        let video_session = VideoSession::new(&device, &stream_inspector)?;
+        let video_session = VideoSession::new(&device, &stream_inspector, &VideoSessionInfo::new())?;
         let video_session_parameters = VideoSessionParameters::new(&video_session, &stream_inspector)?;
         let decode_info = DecodeInfo::new(0, 16 * 256);
+        let queries = VideoQueryPool::new(&device, &stream_inspector, 1)?;
 
         let decode = DecodeH264::new(
             &buffer_h264,
@@ -334,7 +706,8 @@ mod test {
             &image_view_dst,
             &image_view_ref,
             &decode_info,
-        );
+        )
+        .query(&queries, 0);
         let copy = CopyImage2Buffer::new(&image_dst, &buffer_output, ImageAspectFlags::PLANE_0);
 
         queue.build_and_submit(&command_buffer, |x| {
@@ -356,6 +729,236 @@ mod test {
         assert_eq!(data_out[1], 108);
         assert_eq!(data_out[2], 108);
         assert_eq!(data_out[3], 108);
+        assert_eq!(queries.results()?, [QueryResultStatusKHR::COMPLETE]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn decode_h264_linear() -> Result<(), Error> {
+        let h264_data = include_bytes!("../../tests/videos/multi_512x512.h264");
+
+        let mut stream_inspector = H264StreamInspector::new();
+
+        for nal in nal_units(h264_data) {
+            stream_inspector.feed_nal(nal)?;
+        }
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let video_session = VideoSession::new(&device, &stream_inspector, &VideoSessionInfo::new())?;
+        let video_session_parameters = VideoSessionParameters::new(&video_session, &stream_inspector)?;
+        let dpb_and_output_coincide = video_session
+            .shared()
+            .decode_capabilities()
+            .flags()
+            .contains(VideoDecodeCapabilityFlagsKHR::DPB_AND_OUTPUT_COINCIDE);
+
+        // The linear image is decoded into directly, so it must not double as reference.
+        if !video_session.supports_linear_output() || dpb_and_output_coincide {
+            return Ok(());
+        }
+
+        let image_info = ImageInfo::new()
+            .format(Format::G8_B8R8_2PLANE_420_UNORM)
+            .samples(SampleCountFlags::TYPE_1)
+            .mip_levels(1)
+            .array_layers(1)
+            .image_type(ImageType::TYPE_2D)
+            .layout(ImageLayout::UNDEFINED)
+            .extent(Extent3D::default().width(512).height(512).depth(1));
+        let image_dst_info = image_info
+            .clone()
+            .usage(ImageUsageFlags::VIDEO_DECODE_DST_KHR)
+            .tiling(ImageTiling::LINEAR);
+        let image_ref_info = image_info.usage(ImageUsageFlags::VIDEO_DECODE_DPB_KHR).tiling(ImageTiling::OPTIMAL);
+
+        let image_dst = Image::new_video_target(&device, &image_dst_info, &stream_inspector)?;
+        let image_ref = Image::new_video_target(&device, &image_ref_info, &stream_inspector)?;
+        let requirements_dst = image_dst.memory_requirement();
+        let requirements_ref = image_ref.memory_requirement();
+        let heap_dst = physical_device
+            .heap_infos()
+            .any_host_visible_for(&requirements_dst)
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation_image_dst = Allocation::new(&device, requirements_dst.size(), heap_dst)?;
+        let allocation_image_ref = Allocation::new(&device, requirements_ref.size(), requirements_ref.any_heap())?;
+        let image_dst = image_dst.bind(&allocation_image_dst)?;
+        let image_ref = image_ref.bind(&allocation_image_ref)?;
+
+        let image_view_info = ImageViewInfo::new()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .format(Format::G8_B8R8_2PLANE_420_UNORM)
+            .image_view_type(ImageViewType::TYPE_2D)
+            .layer_count(1)
+            .level_count(1);
+        let image_view_dst = ImageView::new(&image_dst, &image_view_info)?;
+        let image_view_ref = ImageView::new(&image_ref, &image_view_info)?;
+        let queue_video_decode = physical_device
+            .queue_family_infos()
+            .any_decode()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue = Queue::new(&device, queue_video_decode, 0)?;
+        let command_buffer = CommandBuffer::new(&device, queue_video_decode)?;
+
+        let memory_host = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation_h264 = Allocation::new(&device, 1024 * 1024 * 4 + 256, memory_host)?;
+        let buffer_info_h264 = BufferInfo::new().size(1024 * 1024 * 4);
+        let buffer_h264 = Buffer::new_video_decode(&allocation_h264, &buffer_info_h264, &stream_inspector)?;
+
+        buffer_h264.upload(&h264_data[0..])?;
+
+        let decode_info = DecodeInfo::new(0, 16 * 256);
+        let decode = DecodeH264::new(
+            &buffer_h264,
+            &video_session_parameters,
+            &image_view_dst,
+            &image_view_ref,
+            &decode_info,
+        );
+
+        queue.build_and_submit(&command_buffer, |x| {
+            decode.run_in(x)?;
+            Ok(())
+        })?;
+
+        // No copy needed, luma rows start `row_pitch` bytes apart.
+        let first_pixels = image_dst.map(|x| x.plane(ImageAspectFlags::PLANE_0).map(|luma| luma[..4].to_vec()))?;
+        let row_pitch = image_dst.map(|x| x.row_pitch(ImageAspectFlags::PLANE_0))?;
+
+        assert_eq!(first_pixels, Some(vec![108, 108, 108, 108]));
+        assert!(row_pitch >= Some(512));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn decode_h264_batch() -> Result<(), Error> {
+        let h264_data = include_bytes!("../../tests/videos/multi_512x512.h264");
+
+        let mut stream_inspector = H264StreamInspector::new();
+
+        for nal in nal_units(h264_data) {
+            stream_inspector.feed_nal(nal)?;
+        }
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let image_info = ImageInfo::new()
+            .format(Format::G8_B8R8_2PLANE_420_UNORM)
+            .samples(SampleCountFlags::TYPE_1)
+            .usage(ImageUsageFlags::TRANSFER_SRC | ImageUsageFlags::VIDEO_DECODE_DST_KHR | ImageUsageFlags::VIDEO_DECODE_DPB_KHR)
+            .mip_levels(1)
+            .array_layers(1)
+            .image_type(ImageType::TYPE_2D)
+            .tiling(ImageTiling::OPTIMAL)
+            .layout(ImageLayout::UNDEFINED)
+            .extent(Extent3D::default().width(512).height(512).depth(1));
+        let image_view_info = ImageViewInfo::new()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .format(Format::G8_B8R8_2PLANE_420_UNORM)
+            .image_view_type(ImageViewType::TYPE_2D)
+            .layer_count(1)
+            .level_count(1);
+
+        let mut images = Vec::new();
+        let mut image_views = Vec::new();
+
+        for _ in 0..4 {
+            let image = Image::new_video_target(&device, &image_info, &stream_inspector)?;
+            let requirements = image.memory_requirement();
+            let allocation = Allocation::new(&device, requirements.size(), requirements.any_heap())?;
+            let image = image.bind(&allocation)?;
+
+            image_views.push(ImageView::new(&image, &image_view_info)?);
+            images.push(image);
+        }
+
+        let queue_video_decode = physical_device
+            .queue_family_infos()
+            .any_decode()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue_compute = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let memory_host = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let queue = Queue::new(&device, queue_video_decode, 0)?;
+        let queue_copy = Queue::new(&device, queue_compute, 0)?;
+        let command_buffer = CommandBuffer::new(&device, queue_video_decode)?;
+        let command_buffer_copy = CommandBuffer::new(&device, queue_compute)?;
+
+        // All access units go into one buffer, each starting at an aligned offset.
+        let mut bitstream = Vec::new();
+        let mut decode_infos = Vec::new();
+        let mut headers = Vec::new();
+
+        for access_unit in access_units(h264_data).take(3) {
+            let offset = bitstream.len();
+
+            bitstream.extend_from_slice(access_unit);
+            bitstream.resize(bitstream.len().next_multiple_of(256), 0);
+
+            decode_infos.push(DecodeInfo::new(offset as u64, (bitstream.len() - offset) as u64));
+            headers.push(
+                nal_units(access_unit)
+                    .find_map(|x| stream_inspector.slice_header(x).ok())
+                    .ok_or_else(|| error!(Variant::InvalidBitstream))?,
+            );
+        }
+
+        let allocation_h264 = Allocation::new(&device, 1024 * 1024 * 4 + 256, memory_host)?;
+        let buffer_h264 = Buffer::new_video_decode(&allocation_h264, &BufferInfo::new().size(1024 * 1024 * 4), &stream_inspector)?;
+        let allocation_output = Allocation::new(&device, 512 * 512, memory_host)?;
+        let buffer_output = Buffer::new(&allocation_output, &BufferInfo::new().size(512 * 512))?;
+
+        buffer_h264.upload(&bitstream)?;
+
+        let video_session = VideoSession::new(&device, &stream_inspector, &VideoSessionInfo::new())?;
+        let video_session_parameters = VideoSessionParameters::new(&video_session, &stream_inspector)?;
+        let mut dpb = DpbSlotManager::new(4, 3);
+        let mut decodes = Vec::new();
+        let mut slots = Vec::new();
+
+        for (i, (decode_info, header)) in decode_infos.iter().zip(&headers).enumerate() {
+            let pic_order_cnt = 2 * i as i32;
+            let slot = dpb.next_slot(header.frame_num, [pic_order_cnt; 2], header.idr)?;
+            let view = &image_views[slot.index() as usize];
+            let decode = DecodeH264::new(&buffer_h264, &video_session_parameters, view, view, decode_info)
+                .dpb(&dpb, slot, &image_views)
+                .slice_header(header);
+
+            let max_frame_num = stream_inspector
+                .sps(header.seq_parameter_set_id)
+                .map_or(16, |x| 1 << x.log2_max_frame_num());
+
+            dpb.mark_decoded(slot, header, max_frame_num)?;
+            decodes.push(decode);
+            slots.push(slot);
+        }
+
+        let batch = DecodeH264Batch::new(decodes);
+        let copy = CopyImage2Buffer::new(&images[slots[0].index() as usize], &buffer_output, ImageAspectFlags::PLANE_0);
+
+        queue.build_and_submit(&command_buffer, |x| batch.run_in(x))?;
+        queue_copy.build_and_submit(&command_buffer_copy, |x| copy.run_in(x))?;
+
+        let mut data_out = vec![0u8; 512 * 512];
+        buffer_output.download_into(&mut data_out)?;
+
+        assert_eq!(data_out[0], 108);
 
         Ok(())
     }
@@ -0,0 +1,411 @@
+use crate::error::Error;
+use crate::ops::decodeh264::Transition;
+use crate::ops::{Access, AddToCommandBuffer, DecodeInfo};
+use crate::queue::CommandBuilder;
+use crate::resources::{Buffer, BufferShared, ImageView, ImageViewShared};
+use crate::video::h265::{H265DpbSlot, H265DpbSlotManager, SliceSegmentHeader};
+use crate::video::{VideoSessionParameters, VideoSessionParametersShared};
+use ash::vk::native::{StdVideoDecodeH265PictureInfo, StdVideoDecodeH265PictureInfoFlags};
+use ash::vk::{
+    AccessFlags2, BufferMemoryBarrier2, DependencyInfoKHR, Extent2D, ImageLayout, PipelineStageFlags2, VideoBeginCodingInfoKHR,
+    VideoCodingControlFlagsKHR, VideoCodingControlInfoKHR, VideoDecodeCapabilityFlagsKHR, VideoDecodeH265PictureInfoKHR,
+    VideoDecodeInfoKHR, VideoEndCodingInfoKHR, VideoPictureResourceInfoKHR, QUEUE_FAMILY_IGNORED, WHOLE_SIZE,
+};
+use std::sync::Arc;
+
+/// DPB state to decode against, see [`DecodeH265::dpb`].
+struct Dpb {
+    manager: H265DpbSlotManager,
+    setup: H265DpbSlot,
+    views: Vec<Arc<ImageViewShared>>,
+}
+
+/// Decode a H.265 video frame.
+pub struct DecodeH265 {
+    shared_parameters: Arc<VideoSessionParametersShared>,
+    shared_buffer: Arc<BufferShared>,
+    shared_image_view: Arc<ImageViewShared>,
+    shared_ref_view: Arc<ImageViewShared>,
+    decode_info: DecodeInfo,
+    slice_segment_offsets: Vec<u32>,
+    dpb: Option<Dpb>,
+    header: Option<SliceSegmentHeader>,
+}
+
+impl DecodeH265 {
+    pub fn new(
+        buffer: &Buffer,
+        video_session_parameters: &VideoSessionParameters,
+        target_view: &ImageView,
+        ref_view: &ImageView,
+        decode_info: &DecodeInfo,
+    ) -> Self {
+        Self {
+            shared_parameters: video_session_parameters.shared(),
+            shared_buffer: buffer.shared(),
+            shared_image_view: target_view.shared(),
+            shared_ref_view: ref_view.shared(),
+            decode_info: *decode_info,
+            slice_segment_offsets: vec![0],
+            dpb: None,
+            header: None,
+        }
+    }
+
+    /// Offsets of the start codes of all slice segments of the picture, relative to the offset of the [`DecodeInfo`].
+    ///
+    /// Without this, the range is decoded as a single slice segment starting at its first byte.
+    pub fn slice_segment_offsets(mut self, slice_segment_offsets: &[u32]) -> Self {
+        self.slice_segment_offsets = slice_segment_offsets.to_vec();
+        self
+    }
+
+    /// Decodes into `setup` while referencing all references of `dpb`, as given by the reference picture set of the picture.
+    ///
+    /// `dpb_views` hold the picture of each DPB slot, indexed by slot index. Without this, the picture is decoded
+    /// into slot 0 without any references (i.e., only intra frames will decode properly).
+    pub fn dpb(mut self, dpb: &H265DpbSlotManager, setup: H265DpbSlot, dpb_views: &[ImageView]) -> Self {
+        self.dpb = Some(Dpb {
+            manager: dpb.clone(),
+            setup,
+            views: dpb_views.iter().map(|x| x.shared()).collect(),
+        });
+        self
+    }
+
+    /// Takes parameter set ids, picture type and the short-term RPS from the first slice segment header of the picture.
+    ///
+    /// Without this, the picture is decoded as an IDR picture using VPS, SPS and PPS 0.
+    pub fn slice_segment_header(mut self, header: &SliceSegmentHeader) -> Self {
+        self.header = Some(header.clone());
+        self
+    }
+}
+
+impl AddToCommandBuffer for DecodeH265 {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        let shared_video_session = self.shared_parameters.video_session();
+
+        let native_buffer_h265 = self.shared_buffer.native();
+        let native_device = shared_video_session.device().native();
+        let native_queue_fns = shared_video_session.queue_fns();
+        let native_decode_fns = shared_video_session.decode_fns();
+        let native_command_buffer = builder.native_command_buffer();
+        let native_video_session = shared_video_session.native();
+        let native_video_session_parameters = self.shared_parameters.native();
+
+        let image_info = self.shared_image_view.image().info();
+        let image_extent = image_info.get_extent();
+        let extent = Extent2D::default().width(image_extent.width).height(image_extent.height);
+
+        let dpb_and_output_coincide = shared_video_session
+            .decode_capabilities()
+            .flags()
+            .contains(VideoDecodeCapabilityFlagsKHR::DPB_AND_OUTPUT_COINCIDE);
+
+        let (manager, setup, dpb_views) = match &self.dpb {
+            Some(dpb) => (dpb.manager.clone(), dpb.setup, dpb.views.clone()),
+            None if dpb_and_output_coincide => (
+                H265DpbSlotManager::new(1),
+                H265DpbSlot::default(),
+                vec![self.shared_image_view.clone()],
+            ),
+            None => (
+                H265DpbSlotManager::new(1),
+                H265DpbSlot::default(),
+                vec![self.shared_ref_view.clone()],
+            ),
+        };
+
+        let picture_resources = dpb_views
+            .iter()
+            .map(|x| {
+                VideoPictureResourceInfoKHR::default()
+                    .coded_extent(extent)
+                    .image_view_binding(x.native())
+            })
+            .collect::<Vec<_>>();
+
+        let reference_slots = manager.reference_slots(&setup, &picture_resources)?;
+
+        let picture_resource_dst = if dpb_and_output_coincide {
+            picture_resources[setup.index() as usize]
+        } else {
+            VideoPictureResourceInfoKHR::default()
+                .coded_extent(extent)
+                .image_view_binding(self.shared_image_view.native())
+        };
+
+        let header = self.header.as_ref();
+        let ref_pic_sets = manager.ref_pic_sets();
+
+        let mut stdflags = StdVideoDecodeH265PictureInfoFlags {
+            _bitfield_align_1: Default::default(),
+            _bitfield_1: Default::default(),
+            __bindgen_padding_0: Default::default(),
+        };
+
+        stdflags.set_IrapPicFlag(header.map_or(1, |x| x.is_irap() as u32));
+        stdflags.set_IdrPicFlag(header.map_or(1, |x| x.is_idr() as u32));
+        stdflags.set_IsReference(header.map_or(1, |x| !x.is_sub_layer_non_reference() as u32));
+        stdflags.set_short_term_ref_pic_set_sps_flag(header.map_or(0, |x| x.short_term_ref_pic_set_sps_flag as u32));
+
+        let std = StdVideoDecodeH265PictureInfo {
+            flags: stdflags,
+            sps_video_parameter_set_id: header.map_or(0, |x| x.video_parameter_set_id),
+            pps_seq_parameter_set_id: header.map_or(0, |x| x.seq_parameter_set_id),
+            pps_pic_parameter_set_id: header.map_or(0, |x| x.slice_pic_parameter_set_id),
+            NumDeltaPocsOfRefRpsIdx: header.map_or(0, |x| x.num_delta_pocs_of_ref_rps_idx),
+            PicOrderCntVal: setup.pic_order_cnt(),
+            NumBitsForSTRefPicSetInSlice: header.map_or(0, |x| x.num_bits_for_st_ref_pic_set_in_slice),
+            reserved: 0,
+            RefPicSetStCurrBefore: ref_pic_sets.st_curr_before,
+            RefPicSetStCurrAfter: ref_pic_sets.st_curr_after,
+            RefPicSetLtCurr: ref_pic_sets.lt_curr,
+        };
+
+        // Barriers apply to the (array layers of) images behind views, several DPB slots may share one image.
+        let transition_setup = Transition::new(&dpb_views[setup.index() as usize], true, ImageLayout::VIDEO_DECODE_DPB_KHR);
+        let transition_dst = Transition::new(&self.shared_image_view, true, ImageLayout::VIDEO_DECODE_DST_KHR);
+        let mut images = vec![transition_setup.clone()];
+
+        if !transition_setup.same_layer(&transition_dst) {
+            images.push(transition_dst);
+        }
+
+        for reference in manager.references().iter().filter(|x| x.index() != setup.index()) {
+            let view = &dpb_views[reference.index() as usize];
+            let transition = Transition::new(view, false, ImageLayout::VIDEO_DECODE_DPB_KHR);
+
+            if images.iter().all(|x| !x.same_layer(&transition)) {
+                images.push(transition);
+            }
+        }
+
+        let queue_family_index = builder.queue_family_index();
+        let image_barriers = images.iter().map(|x| x.acquire(queue_family_index)).collect::<Vec<_>>();
+        let image_barriers_release = images.iter().map(|x| x.release(queue_family_index)).collect::<Vec<_>>();
+
+        let buffer_barriers = [BufferMemoryBarrier2::default()
+            .src_stage_mask(PipelineStageFlags2::HOST)
+            .src_access_mask(AccessFlags2::HOST_WRITE)
+            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .dst_stage_mask(PipelineStageFlags2::VIDEO_DECODE_KHR)
+            .dst_access_mask(AccessFlags2::VIDEO_DECODE_READ_KHR)
+            .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .buffer(native_buffer_h265)
+            .size(WHOLE_SIZE)];
+
+        let buffer_barriers_release = [BufferMemoryBarrier2::default()
+            .src_stage_mask(PipelineStageFlags2::VIDEO_DECODE_KHR)
+            .src_access_mask(AccessFlags2::VIDEO_DECODE_READ_KHR)
+            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .dst_stage_mask(PipelineStageFlags2::TOP_OF_PIPE)
+            .dst_access_mask(AccessFlags2::NONE)
+            .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .buffer(native_buffer_h265)
+            .size(WHOLE_SIZE)];
+
+        let dependency_info = DependencyInfoKHR::default()
+            .buffer_memory_barriers(&buffer_barriers)
+            .image_memory_barriers(&image_barriers);
+
+        let dependency_info_release = DependencyInfoKHR::default()
+            .buffer_memory_barriers(&buffer_barriers_release)
+            .image_memory_barriers(&image_barriers_release);
+
+        let begin_coding_slots = reference_slots.begin_coding_slots();
+        let begin_coding_info = VideoBeginCodingInfoKHR::default()
+            .video_session(native_video_session)
+            .video_session_parameters(native_video_session_parameters)
+            .reference_slots(&begin_coding_slots);
+
+        let end_coding_info = VideoEndCodingInfoKHR::default();
+
+        let video_coding_control = VideoCodingControlInfoKHR::default().flags(VideoCodingControlFlagsKHR::RESET);
+        let mut video_decode_info_h265 = VideoDecodeH265PictureInfoKHR::default()
+            .std_picture_info(&std)
+            .slice_segment_offsets(&self.slice_segment_offsets);
+
+        let video_decode_info = VideoDecodeInfoKHR::default()
+            .push_next(&mut video_decode_info_h265)
+            .src_buffer(native_buffer_h265)
+            .src_buffer_offset(self.decode_info.offset)
+            .src_buffer_range(self.decode_info.size)
+            .dst_picture_resource(picture_resource_dst)
+            .setup_reference_slot(reference_slots.setup())
+            .reference_slots(reference_slots.references());
+
+        // The bitstream might have been written by earlier commands.
+        builder.access_buffer(&self.shared_buffer, Access::VIDEO_DECODE_READ);
+        builder.record_barriers();
+
+        unsafe {
+            native_device.cmd_pipeline_barrier2(native_command_buffer, &dependency_info);
+            (native_queue_fns.cmd_begin_video_coding_khr)(native_command_buffer, &begin_coding_info);
+
+            // Resetting deactivates all DPB slots, so only do that if we don't reference anything.
+            if reference_slots.references().is_empty() {
+                (native_queue_fns.cmd_control_video_coding_khr)(native_command_buffer, &video_coding_control);
+            }
+
+            (native_decode_fns.cmd_decode_video_khr)(native_command_buffer, &video_decode_info);
+            (native_queue_fns.cmd_end_video_coding_khr)(native_command_buffer, &end_coding_info);
+            native_device.cmd_pipeline_barrier2(native_command_buffer, &dependency_info_release);
+        }
+
+        for x in &images {
+            x.assume_released(builder);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::commandbuffer::CommandBuffer;
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::ops::{AddToCommandBuffer, CopyImage2Buffer, DecodeH265, DecodeInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::Queue;
+    use crate::resources::{Buffer, BufferInfo, Image, ImageInfo, ImageView, ImageViewInfo};
+    use crate::video::h265::{H265DpbSlotManager, H265StreamInspector};
+    use crate::video::{nal_units, VideoSession, VideoSessionInfo, VideoSessionParameters};
+    use ash::vk::{
+        Extent3D, Format, ImageAspectFlags, ImageLayout, ImageTiling, ImageType, ImageUsageFlags, ImageViewType, MemoryPropertyFlags,
+        SampleCountFlags, VideoDecodeCapabilityFlagsKHR,
+    };
+
+    #[test]
+    #[cfg(not(miri))]
+    fn decode_h265() -> Result<(), Error> {
+        let h265_data = include_bytes!("../../tests/videos/single_64x48.h265");
+
+        let mut stream_inspector = H265StreamInspector::new();
+
+        for nal in nal_units(h265_data) {
+            stream_inspector.feed_nal(nal)?;
+        }
+
+        // The last NAL is the IDR slice, everything before are parameter sets.
+        let slice = nal_units(h265_data).last().ok_or_else(|| error!(Variant::InvalidBitstream))?;
+        let header = stream_inspector.slice_segment_header(slice)?;
+        let mut dpb = H265DpbSlotManager::new(1);
+        let setup = dpb.next_slot_for(&header)?;
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let image_dst_info = ImageInfo::new()
+            .format(Format::G8_B8R8_2PLANE_420_UNORM)
+            .samples(SampleCountFlags::TYPE_1)
+            .usage(
+                ImageUsageFlags::TRANSFER_SRC
+                    | ImageUsageFlags::TRANSFER_DST
+                    | ImageUsageFlags::VIDEO_DECODE_DST_KHR
+                    | ImageUsageFlags::VIDEO_DECODE_DPB_KHR,
+            )
+            .mip_levels(1)
+            .array_layers(1)
+            .image_type(ImageType::TYPE_2D)
+            .tiling(ImageTiling::OPTIMAL)
+            .layout(ImageLayout::UNDEFINED)
+            .extent(Extent3D::default().width(64).height(48).depth(1));
+
+        let image_dst = Image::new_video_target(&device, &image_dst_info, &stream_inspector)?;
+        let image_ref = Image::new_video_target(&device, &image_dst_info, &stream_inspector)?;
+        let allocation_image_dst = Allocation::for_requirements(&device, &image_dst.memory_requirement(), MemoryPropertyFlags::empty())?;
+        let allocation_image_ref = Allocation::for_requirements(&device, &image_ref.memory_requirement(), MemoryPropertyFlags::empty())?;
+        let image_dst = image_dst.bind(&allocation_image_dst)?;
+        let image_ref = image_ref.bind(&allocation_image_ref)?;
+
+        let image_view_dst_info = ImageViewInfo::new()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .format(Format::G8_B8R8_2PLANE_420_UNORM)
+            .image_view_type(ImageViewType::TYPE_2D)
+            .layer_count(1)
+            .level_count(1);
+        let image_view_dst = ImageView::new(&image_dst, &image_view_dst_info)?;
+        let image_view_ref = ImageView::new(&image_ref, &image_view_dst_info)?;
+        let queue_video_decode = physical_device
+            .queue_family_infos()
+            .any_decode()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue_compute = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue = Queue::new(&device, queue_video_decode, 0)?;
+        let queue_copy = Queue::new(&device, queue_compute, 0)?;
+        let command_buffer = CommandBuffer::new(&device, queue_video_decode)?;
+        let command_buffer_copy = CommandBuffer::new(&device, queue_compute)?;
+
+        let memory_host = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+
+        let allocation_h265 = Allocation::new(&device, 1024 * 1024 + 256, memory_host)?;
+        let buffer_info_h265 = BufferInfo::new().size(1024 * 1024);
+        let buffer_h265 = Buffer::new_video_decode(&allocation_h265, &buffer_info_h265, &stream_inspector)?;
+
+        buffer_h265.upload(slice)?;
+
+        let allocation_output = Allocation::new(&device, 64 * 48 * 4, memory_host)?;
+        let buffer_info_output = BufferInfo::new().size(64 * 48 * 4);
+        let buffer_output = Buffer::new(&allocation_output, &buffer_info_output)?;
+
+        let video_session = VideoSession::new(&device, &stream_inspector, &VideoSessionInfo::new())?;
+        let video_session_parameters = VideoSessionParameters::new_h265(&video_session, &stream_inspector)?;
+        let decode_info = DecodeInfo::new(0, 256);
+
+        let dpb_and_output_coincide = video_session
+            .shared()
+            .decode_capabilities()
+            .flags()
+            .contains(VideoDecodeCapabilityFlagsKHR::DPB_AND_OUTPUT_COINCIDE);
+        let dpb_view = match dpb_and_output_coincide {
+            true => &image_view_dst,
+            false => &image_view_ref,
+        };
+
+        let decode = DecodeH265::new(
+            &buffer_h265,
+            &video_session_parameters,
+            &image_view_dst,
+            &image_view_ref,
+            &decode_info,
+        )
+        .slice_segment_offsets(&[0])
+        .dpb(&dpb, setup, std::slice::from_ref(dpb_view))
+        .slice_segment_header(&header);
+        let copy = CopyImage2Buffer::new(&image_dst, &buffer_output, ImageAspectFlags::PLANE_0);
+
+        queue.build_and_submit(&command_buffer, |x| {
+            decode.run_in(x)?;
+            Ok(())
+        })?;
+
+        queue_copy.build_and_submit(&command_buffer_copy, |x| {
+            copy.run_in(x)?;
+            Ok(())
+        })?;
+
+        let mut data_out = [0u8; 64 * 48 * 4];
+        buffer_output.download_into(&mut data_out)?;
+
+        // The test clip is a horizontal luma gradient.
+        assert!(data_out[60] > data_out[0]);
+
+        dpb.mark_reference(setup)?;
+        assert_eq!(dpb.references().len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,739 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::ops::{Access, AddToCommandBuffer};
+use crate::queue::CommandBuilder;
+use crate::resources::{Buffer, BufferShared, ImageView, ImageViewShared};
+use crate::video::{
+    DpbSlot, FrameType, RateControlInfo, VideoEncodeSessionParameters, VideoEncodeSessionParametersShared, VideoQueryPool,
+    VideoQueryPoolShared,
+};
+use ash::vk::native::{
+    StdVideoEncodeH264PictureInfo, StdVideoEncodeH264PictureInfoFlags, StdVideoEncodeH264ReferenceListsInfo,
+    StdVideoEncodeH264ReferenceListsInfoFlags, StdVideoEncodeH264SliceHeader, StdVideoEncodeH264SliceHeaderFlags,
+    StdVideoH264CabacInitIdc_STD_VIDEO_H264_CABAC_INIT_IDC_0,
+    StdVideoH264DisableDeblockingFilterIdc_STD_VIDEO_H264_DISABLE_DEBLOCKING_FILTER_IDC_DISABLED, StdVideoH264PictureType,
+    StdVideoH264PictureType_STD_VIDEO_H264_PICTURE_TYPE_B, StdVideoH264PictureType_STD_VIDEO_H264_PICTURE_TYPE_I,
+    StdVideoH264PictureType_STD_VIDEO_H264_PICTURE_TYPE_IDR, StdVideoH264PictureType_STD_VIDEO_H264_PICTURE_TYPE_P, StdVideoH264SliceType,
+    StdVideoH264SliceType_STD_VIDEO_H264_SLICE_TYPE_B, StdVideoH264SliceType_STD_VIDEO_H264_SLICE_TYPE_I,
+    StdVideoH264SliceType_STD_VIDEO_H264_SLICE_TYPE_P,
+};
+use ash::vk::{
+    AccessFlags2, BufferMemoryBarrier2, DependencyInfoKHR, Extent2D, ImageLayout, ImageMemoryBarrier2, PipelineStageFlags2,
+    QueryControlFlags, VideoBeginCodingInfoKHR, VideoCodingControlFlagsKHR, VideoCodingControlInfoKHR, VideoEncodeH264DpbSlotInfoKHR,
+    VideoEncodeH264NaluSliceInfoKHR, VideoEncodeH264PictureInfoKHR, VideoEncodeInfoKHR, VideoEncodeQualityLevelInfoKHR,
+    VideoEndCodingInfoKHR, VideoInlineQueryInfoKHR, VideoPictureResourceInfoKHR, VideoReferenceSlotInfoKHR, VideoSessionCreateFlagsKHR,
+    QUEUE_FAMILY_IGNORED, WHOLE_SIZE,
+};
+use std::sync::Arc;
+
+/// Marks unused entries of `RefPicList0` and `RefPicList1`.
+const NO_REFERENCE_PICTURE: u8 = 0xFF;
+
+/// Specifies which part of a buffer to write the encoded bitstream to.
+#[derive(Copy, Clone)]
+pub struct EncodeInfo {
+    pub(crate) offset: u64,
+    pub(crate) size: u64,
+}
+
+impl EncodeInfo {
+    pub fn new(offset: u64, size: u64) -> Self {
+        EncodeInfo { offset, size }
+    }
+}
+
+/// DPB state to encode against, see [`EncodeH264::dpb`].
+struct Dpb {
+    setup: DpbSlot,
+    references_l0: Vec<DpbSlot>,
+    references_l1: Vec<DpbSlot>,
+    views: Vec<Arc<ImageViewShared>>,
+}
+
+/// Encode a H.264 video frame.
+///
+/// By default the frame is encoded as a single-slice IDR picture, reconstructed into `setup_view`. The source image
+/// is expected in `GENERAL` layout, e.g., after uploading it with [`CopyBuffer2Image`](crate::ops::CopyBuffer2Image).
+pub struct EncodeH264 {
+    shared_parameters: Arc<VideoEncodeSessionParametersShared>,
+    shared_buffer: Arc<BufferShared>,
+    shared_src_view: Arc<ImageViewShared>,
+    shared_setup_view: Arc<ImageViewShared>,
+    encode_info: EncodeInfo,
+    rate_control: Option<RateControlInfo>,
+    idr_pic_id: u16,
+    frame_type: FrameType,
+    dpb: Option<Dpb>,
+    query: Option<(Arc<VideoQueryPoolShared>, u32)>,
+}
+
+impl EncodeH264 {
+    pub fn new(
+        buffer: &Buffer,
+        video_session_parameters: &VideoEncodeSessionParameters,
+        src_view: &ImageView,
+        setup_view: &ImageView,
+        encode_info: &EncodeInfo,
+    ) -> Self {
+        Self {
+            shared_parameters: video_session_parameters.shared(),
+            shared_buffer: buffer.shared(),
+            shared_src_view: src_view.shared(),
+            shared_setup_view: setup_view.shared(),
+            encode_info: *encode_info,
+            rate_control: None,
+            idr_pic_id: 0,
+            frame_type: FrameType::Idr,
+            dpb: None,
+            query: None,
+        }
+    }
+
+    /// Switches the session to `rate_control` before encoding.
+    ///
+    /// Rate control is session state, following encodes keep using it until changed again. Without this, the
+    /// implementation's default rate control is used.
+    pub fn rate_control(mut self, rate_control: &RateControlInfo) -> Self {
+        self.rate_control = Some(rate_control.clone());
+        self
+    }
+
+    /// Sets the `idr_pic_id` of the picture, consecutive IDR pictures must have different ids.
+    pub fn idr_pic_id(mut self, idr_pic_id: u16) -> Self {
+        self.idr_pic_id = idr_pic_id;
+        self
+    }
+
+    /// Encodes the picture as `frame_type`, e.g., as decided by a [`Gop`](crate::video::Gop).
+    ///
+    /// P- and B-frames need references, see [`dpb`](Self::dpb). B-frames are never used as reference, so they
+    /// are not reconstructed into a DPB slot.
+    pub fn frame_type(mut self, frame_type: FrameType) -> Self {
+        self.frame_type = frame_type;
+        self
+    }
+
+    /// Encodes into `setup` while predicting from `references_l0` and `references_l1`, closest first.
+    ///
+    /// `setup` also provides `frame_num` and picture order count of the encoded picture. `dpb_views` hold the picture
+    /// of each DPB slot, indexed by slot index. Without this, the picture is encoded into slot 0 without references.
+    pub fn dpb(mut self, setup: DpbSlot, references_l0: &[DpbSlot], references_l1: &[DpbSlot], dpb_views: &[ImageView]) -> Self {
+        self.dpb = Some(Dpb {
+            setup,
+            references_l0: references_l0.to_vec(),
+            references_l1: references_l1.to_vec(),
+            views: dpb_views.iter().map(|x| x.shared()).collect(),
+        });
+        self
+    }
+
+    /// Records offset and size of the encoded bitstream into query `index` of `queries`.
+    ///
+    /// `queries` must be created with [`VideoQueryPool::new_encode_feedback`]. If the device supports
+    /// `VK_KHR_video_maintenance1` the query is recorded inline with the encode.
+    pub fn query(mut self, queries: &VideoQueryPool, index: u32) -> Self {
+        self.query = Some((queries.shared(), index));
+        self
+    }
+}
+
+impl AddToCommandBuffer for EncodeH264 {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        let shared_video_session = self.shared_parameters.video_session();
+        let encode_capabilities = shared_video_session.encode_capabilities();
+
+        let native_buffer = self.shared_buffer.native();
+        let native_device = shared_video_session.device().native();
+        let native_queue_fns = shared_video_session.queue_fns();
+        let native_encode_fns = shared_video_session.encode_fns();
+        let native_command_buffer = builder.native_command_buffer();
+        let native_video_session = shared_video_session.native();
+        let native_video_session_parameters = self.shared_parameters.native();
+
+        let image_extent = self.shared_src_view.image().info().get_extent();
+        let extent = Extent2D::default().width(image_extent.width).height(image_extent.height);
+
+        if let Some(rate_control) = &self.rate_control {
+            rate_control.validate(encode_capabilities)?;
+        }
+
+        let (setup, references_l0, references_l1, dpb_views) = match &self.dpb {
+            Some(dpb) => (dpb.setup, dpb.references_l0.clone(), dpb.references_l1.clone(), dpb.views.clone()),
+            None => (DpbSlot::default(), Vec::new(), Vec::new(), vec![self.shared_setup_view.clone()]),
+        };
+
+        let (max_l0, max_l1) = match self.frame_type {
+            FrameType::Idr | FrameType::I => (0, 0),
+            FrameType::P => (encode_capabilities.max_p_references(), 0),
+            FrameType::B => (encode_capabilities.max_b_references(), encode_capabilities.max_l1_references()),
+        };
+
+        if references_l0.len() > max_l0 as usize || references_l1.len() > max_l1 as usize {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "{:?} pictures support {max_l0} L0 and {max_l1} L1 references, got {} and {}.",
+                self.frame_type,
+                references_l0.len(),
+                references_l1.len()
+            ));
+        }
+
+        if self.frame_type != FrameType::Idr && self.frame_type != FrameType::I && references_l0.is_empty() {
+            return Err(error!(
+                Variant::NoFreeDpbSlot,
+                "{:?} pictures need at least one L0 reference.", self.frame_type
+            ));
+        }
+
+        let is_reference = self.frame_type != FrameType::B;
+
+        let dpb_view = |slot: &DpbSlot| {
+            dpb_views
+                .get(slot.index() as usize)
+                .cloned()
+                .ok_or_else(|| error!(Variant::NoFreeDpbSlot, "No picture resource for DPB slot."))
+        };
+
+        let mut state = shared_video_session.state();
+
+        // Beginning to code has to restate the current rate control, changing it is a separate control command.
+        let rate_control_current = state.rate_control.clone();
+        let rate_control_new = self.rate_control.clone().filter(|x| Some(x) != rate_control_current.as_ref());
+        let rate_control = rate_control_new
+            .as_ref()
+            .or(rate_control_current.as_ref())
+            .cloned()
+            .unwrap_or_default();
+
+        let layers_current = rate_control_current.as_ref().map(|x| x.native_layers()).unwrap_or_default();
+        let layers_new = rate_control_new.as_ref().map(|x| x.native_layers()).unwrap_or_default();
+        let mut native_rate_control_current = rate_control_current.as_ref().map(|x| x.native(&layers_current));
+        let mut native_rate_control_new = rate_control_new.as_ref().map(|x| x.native(&layers_new));
+
+        // Slots referenced by this picture (each only once), followed by the setup slot.
+        let mut slots = Vec::new();
+
+        for slot in references_l0.iter().chain(references_l1.iter()) {
+            if slots.iter().all(|x: &DpbSlot| x.index() != slot.index()) {
+                slots.push(*slot);
+            }
+        }
+
+        slots.push(setup);
+
+        let views = slots.iter().map(dpb_view).collect::<Result<Vec<_>, _>>()?;
+        let std_reference_infos = slots.iter().map(|x| x.std_encode_reference_info()).collect::<Vec<_>>();
+        let picture_resources = views
+            .iter()
+            .map(|x| {
+                VideoPictureResourceInfoKHR::default()
+                    .coded_extent(extent)
+                    .image_view_binding(x.native())
+            })
+            .collect::<Vec<_>>();
+        let mut dpb_slot_infos = std_reference_infos
+            .iter()
+            .map(|x| VideoEncodeH264DpbSlotInfoKHR::default().std_reference_info(x))
+            .collect::<Vec<_>>();
+        let reference_slots = dpb_slot_infos
+            .iter_mut()
+            .zip(picture_resources.iter())
+            .zip(slots.iter())
+            .map(|((dpb_slot_info, picture_resource), slot)| {
+                VideoReferenceSlotInfoKHR::default()
+                    .slot_index(slot.index() as i32)
+                    .picture_resource(picture_resource)
+                    .push_next(dpb_slot_info)
+            })
+            .collect::<Vec<_>>();
+
+        let (setup_slot, reference_slots) = reference_slots.split_last().expect("Always has setup slot");
+
+        // The setup slot is activated by this encode, so it's not yet active when beginning to code.
+        let mut begin_coding_slots = reference_slots.to_vec();
+
+        if is_reference {
+            begin_coding_slots.push(
+                VideoReferenceSlotInfoKHR::default()
+                    .slot_index(-1)
+                    .picture_resource(&picture_resources[slots.len() - 1]),
+            );
+        }
+
+        let mut begin_coding_info = VideoBeginCodingInfoKHR::default()
+            .video_session(native_video_session)
+            .video_session_parameters(native_video_session_parameters)
+            .reference_slots(&begin_coding_slots);
+
+        if let Some(x) = native_rate_control_current.as_mut() {
+            begin_coding_info = begin_coding_info.push_next(x);
+        }
+
+        let end_coding_info = VideoEndCodingInfoKHR::default();
+
+        let mut control_flags = VideoCodingControlFlagsKHR::empty();
+
+        let mut video_coding_control = VideoCodingControlInfoKHR::default();
+        let mut quality_level_info =
+            VideoEncodeQualityLevelInfoKHR::default().quality_level(shared_video_session.info().get_quality_level());
+
+        // The quality level is fixed for the lifetime of a session, so it's set once with the initial reset.
+        if !state.initialized {
+            control_flags |= VideoCodingControlFlagsKHR::RESET | VideoCodingControlFlagsKHR::ENCODE_QUALITY_LEVEL;
+            video_coding_control = video_coding_control.push_next(&mut quality_level_info);
+        }
+
+        if let Some(x) = native_rate_control_new.as_mut() {
+            control_flags |= VideoCodingControlFlagsKHR::ENCODE_RATE_CONTROL;
+            video_coding_control = video_coding_control.push_next(x);
+        }
+
+        video_coding_control = video_coding_control.flags(control_flags);
+
+        let mut ref_pic_list0 = [NO_REFERENCE_PICTURE; 32];
+        let mut ref_pic_list1 = [NO_REFERENCE_PICTURE; 32];
+
+        for (entry, slot) in ref_pic_list0.iter_mut().zip(references_l0.iter()) {
+            *entry = slot.index() as u8;
+        }
+
+        for (entry, slot) in ref_pic_list1.iter_mut().zip(references_l1.iter()) {
+            *entry = slot.index() as u8;
+        }
+
+        // The default lists order references like the `Gop` does, so no modifications are needed.
+        let std_reference_lists = StdVideoEncodeH264ReferenceListsInfo {
+            flags: StdVideoEncodeH264ReferenceListsInfoFlags {
+                _bitfield_align_1: [],
+                _bitfield_1: Default::default(),
+            },
+            num_ref_idx_l0_active_minus1: references_l0.len().saturating_sub(1) as u8,
+            num_ref_idx_l1_active_minus1: references_l1.len().saturating_sub(1) as u8,
+            RefPicList0: ref_pic_list0,
+            RefPicList1: ref_pic_list1,
+            refList0ModOpCount: 0,
+            refList1ModOpCount: 0,
+            refPicMarkingOpCount: 0,
+            reserved1: [0; 7],
+            pRefList0ModOperations: std::ptr::null(),
+            pRefList1ModOperations: std::ptr::null(),
+            pRefPicMarkingOperations: std::ptr::null(),
+        };
+
+        let mut picture_flags = StdVideoEncodeH264PictureInfoFlags {
+            _bitfield_align_1: Default::default(),
+            _bitfield_1: Default::default(),
+        };
+
+        picture_flags.set_IdrPicFlag((self.frame_type == FrameType::Idr) as u32);
+        picture_flags.set_is_reference(is_reference as u32);
+
+        let std_picture_info = StdVideoEncodeH264PictureInfo {
+            flags: picture_flags,
+            seq_parameter_set_id: 0,
+            pic_parameter_set_id: 0,
+            idr_pic_id: self.idr_pic_id,
+            primary_pic_type: std_picture_type(self.frame_type),
+            frame_num: setup.frame_num(),
+            PicOrderCnt: setup.pic_order_cnt()[0],
+            temporal_id: 0,
+            reserved1: [0; 3],
+            pRefLists: &std_reference_lists,
+        };
+
+        let mut slice_header_flags = StdVideoEncodeH264SliceHeaderFlags {
+            _bitfield_align_1: Default::default(),
+            _bitfield_1: Default::default(),
+        };
+
+        // The PPS defaults to a single active reference per list.
+        slice_header_flags.set_num_ref_idx_active_override_flag((references_l0.len() > 1 || references_l1.len() > 1) as u32);
+        slice_header_flags.set_direct_spatial_mv_pred_flag((self.frame_type == FrameType::B) as u32);
+
+        let std_slice_header = StdVideoEncodeH264SliceHeader {
+            flags: slice_header_flags,
+            first_mb_in_slice: 0,
+            slice_type: std_slice_type(self.frame_type),
+            slice_alpha_c0_offset_div2: 0,
+            slice_beta_offset_div2: 0,
+            slice_qp_delta: 0,
+            reserved1: 0,
+            cabac_init_idc: StdVideoH264CabacInitIdc_STD_VIDEO_H264_CABAC_INIT_IDC_0,
+            disable_deblocking_filter_idc: StdVideoH264DisableDeblockingFilterIdc_STD_VIDEO_H264_DISABLE_DEBLOCKING_FILTER_IDC_DISABLED,
+            pWeightTable: std::ptr::null(),
+        };
+
+        let nalu_slice_entries = [VideoEncodeH264NaluSliceInfoKHR::default()
+            .constant_qp(rate_control.constant_qp())
+            .std_slice_header(&std_slice_header)];
+
+        let mut video_encode_info_h264 = VideoEncodeH264PictureInfoKHR::default()
+            .nalu_slice_entries(&nalu_slice_entries)
+            .std_picture_info(&std_picture_info);
+
+        let picture_resource_src = VideoPictureResourceInfoKHR::default()
+            .coded_extent(extent)
+            .image_view_binding(self.shared_src_view.native());
+
+        let mut video_encode_info = VideoEncodeInfoKHR::default()
+            .push_next(&mut video_encode_info_h264)
+            .dst_buffer(native_buffer)
+            .dst_buffer_offset(self.encode_info.offset)
+            .dst_buffer_range(self.encode_info.size)
+            .src_picture_resource(picture_resource_src)
+            .reference_slots(reference_slots);
+
+        if is_reference {
+            video_encode_info = video_encode_info.setup_reference_slot(setup_slot);
+        }
+
+        let inline_queries = shared_video_session
+            .info()
+            .get_flags()
+            .contains(VideoSessionCreateFlagsKHR::INLINE_QUERIES);
+        let mut video_inline_query = self.query.as_ref().map(|(queries, index)| {
+            VideoInlineQueryInfoKHR::default()
+                .query_pool(queries.native())
+                .first_query(*index)
+                .query_count(1)
+        });
+
+        if let Some(video_inline_query) = video_inline_query.as_mut().filter(|_| inline_queries) {
+            video_encode_info = video_encode_info.push_next(video_inline_query);
+        }
+
+        // The source (and the bitstream buffer) might have been written by earlier commands, e.g., an upload.
+        let ssr_src = self.shared_src_view.subresource_range();
+        let image_src = self.shared_src_view.image();
+
+        builder.access_image(
+            &image_src,
+            ssr_src.base_array_layer,
+            ssr_src.layer_count,
+            Access::VIDEO_ENCODE_READ,
+            ImageLayout::GENERAL,
+        );
+        builder.access_buffer(&self.shared_buffer, Access::VIDEO_ENCODE_WRITE);
+        builder.record_barriers();
+
+        unsafe {
+            let barrier = |(view, old_layout, new_layout): (&ImageViewShared, _, _)| {
+                ImageMemoryBarrier2::default()
+                    .src_stage_mask(PipelineStageFlags2::NONE)
+                    .src_access_mask(AccessFlags2::NONE)
+                    .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+                    .old_layout(old_layout)
+                    .dst_stage_mask(PipelineStageFlags2::VIDEO_ENCODE_KHR)
+                    .dst_access_mask(AccessFlags2::VIDEO_ENCODE_READ_KHR | AccessFlags2::VIDEO_ENCODE_WRITE_KHR)
+                    .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+                    .new_layout(new_layout)
+                    .image(view.image().native())
+                    .subresource_range(view.subresource_range())
+            };
+
+            let release = |(view, _, old_layout): (&ImageViewShared, _, _)| {
+                ImageMemoryBarrier2::default()
+                    .src_stage_mask(PipelineStageFlags2::VIDEO_ENCODE_KHR)
+                    .src_access_mask(AccessFlags2::VIDEO_ENCODE_WRITE_KHR)
+                    .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+                    .old_layout(old_layout)
+                    .dst_stage_mask(PipelineStageFlags2::BOTTOM_OF_PIPE)
+                    .dst_access_mask(AccessFlags2::NONE_KHR)
+                    .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+                    .new_layout(ImageLayout::GENERAL)
+                    .image(view.image().native())
+                    .subresource_range(view.subresource_range())
+            };
+
+            // The source holds the frame to encode, references hold earlier pictures, the setup picture is overwritten.
+            let mut images = vec![(&*self.shared_src_view, ImageLayout::GENERAL, ImageLayout::VIDEO_ENCODE_SRC_KHR)];
+
+            for view in &views[..views.len() - 1] {
+                images.push((view, ImageLayout::GENERAL, ImageLayout::VIDEO_ENCODE_DPB_KHR));
+            }
+
+            if is_reference {
+                images.push((&views[views.len() - 1], ImageLayout::UNDEFINED, ImageLayout::VIDEO_ENCODE_DPB_KHR));
+            }
+
+            let image_barriers = images.iter().copied().map(barrier).collect::<Vec<_>>();
+            let image_barriers_release = images.iter().copied().map(release).collect::<Vec<_>>();
+
+            let buffer_barrier = BufferMemoryBarrier2::default()
+                .src_stage_mask(PipelineStageFlags2::NONE)
+                .src_access_mask(AccessFlags2::NONE)
+                .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+                .dst_stage_mask(PipelineStageFlags2::VIDEO_ENCODE_KHR)
+                .dst_access_mask(AccessFlags2::VIDEO_ENCODE_WRITE_KHR)
+                .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+                .buffer(native_buffer)
+                .size(WHOLE_SIZE);
+
+            let buffer_barrier_release = BufferMemoryBarrier2::default()
+                .src_stage_mask(PipelineStageFlags2::VIDEO_ENCODE_KHR)
+                .src_access_mask(AccessFlags2::VIDEO_ENCODE_WRITE_KHR)
+                .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+                .dst_stage_mask(PipelineStageFlags2::HOST)
+                .dst_access_mask(AccessFlags2::HOST_READ)
+                .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+                .buffer(native_buffer)
+                .size(WHOLE_SIZE);
+
+            let buffer_barriers = &[buffer_barrier];
+            let buffer_barriers_release = &[buffer_barrier_release];
+
+            let dependency_info = DependencyInfoKHR::default()
+                .buffer_memory_barriers(buffer_barriers)
+                .image_memory_barriers(&image_barriers);
+
+            let dependency_info_release = DependencyInfoKHR::default()
+                .buffer_memory_barriers(buffer_barriers_release)
+                .image_memory_barriers(&image_barriers_release);
+
+            native_device.cmd_pipeline_barrier2(native_command_buffer, &dependency_info);
+
+            // Queries must be reset before use, and outside of a video coding scope.
+            if let Some((queries, index)) = &self.query {
+                native_device.cmd_reset_query_pool(native_command_buffer, queries.native(), *index, 1);
+            }
+
+            (native_queue_fns.cmd_begin_video_coding_khr)(native_command_buffer, &begin_coding_info);
+
+            if !control_flags.is_empty() {
+                (native_queue_fns.cmd_control_video_coding_khr)(native_command_buffer, &video_coding_control);
+            }
+
+            match &self.query {
+                Some((queries, index)) if !inline_queries => {
+                    native_device.cmd_begin_query(native_command_buffer, queries.native(), *index, QueryControlFlags::empty());
+                    (native_encode_fns.cmd_encode_video_khr)(native_command_buffer, &video_encode_info);
+                    native_device.cmd_end_query(native_command_buffer, queries.native(), *index);
+                }
+                _ => (native_encode_fns.cmd_encode_video_khr)(native_command_buffer, &video_encode_info),
+            }
+
+            (native_queue_fns.cmd_end_video_coding_khr)(native_command_buffer, &end_coding_info);
+            native_device.cmd_pipeline_barrier2(native_command_buffer, &dependency_info_release);
+
+            for (view, ..) in images {
+                let ssr = view.subresource_range();
+                builder.assume_image(
+                    &view.image(),
+                    ssr.base_array_layer,
+                    ssr.layer_count,
+                    Access::VIDEO_ENCODE_WRITE,
+                    ImageLayout::GENERAL,
+                );
+            }
+        }
+
+        state.initialized = true;
+
+        if rate_control_new.is_some() {
+            state.rate_control = rate_control_new;
+        }
+
+        Ok(())
+    }
+}
+
+fn std_picture_type(frame_type: FrameType) -> StdVideoH264PictureType {
+    match frame_type {
+        FrameType::Idr => StdVideoH264PictureType_STD_VIDEO_H264_PICTURE_TYPE_IDR,
+        FrameType::I => StdVideoH264PictureType_STD_VIDEO_H264_PICTURE_TYPE_I,
+        FrameType::P => StdVideoH264PictureType_STD_VIDEO_H264_PICTURE_TYPE_P,
+        FrameType::B => StdVideoH264PictureType_STD_VIDEO_H264_PICTURE_TYPE_B,
+    }
+}
+
+fn std_slice_type(frame_type: FrameType) -> StdVideoH264SliceType {
+    match frame_type {
+        FrameType::Idr | FrameType::I => StdVideoH264SliceType_STD_VIDEO_H264_SLICE_TYPE_I,
+        FrameType::P => StdVideoH264SliceType_STD_VIDEO_H264_SLICE_TYPE_P,
+        FrameType::B => StdVideoH264SliceType_STD_VIDEO_H264_SLICE_TYPE_B,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::commandbuffer::CommandBuffer;
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::ops::{AddToCommandBuffer, CopyBuffer2Image, EncodeH264, EncodeInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::Queue;
+    use crate::resources::{Buffer, BufferInfo, Image, ImageInfo, ImageView, ImageViewInfo};
+    use crate::video::h264::H264EncodeInfo;
+    use crate::video::{
+        DpbSlotManager, FrameType, Gop, GopConfig, RateControlInfo, VideoEncodeSession, VideoEncodeSessionParameters, VideoQueryPool,
+        VideoSessionInfo,
+    };
+    use ash::vk::{
+        Extent2D, Extent3D, ImageAspectFlags, ImageLayout, ImageTiling, ImageType, ImageUsageFlags, ImageViewType, MemoryPropertyFlags,
+        QueryResultStatusKHR, SampleCountFlags,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    #[cfg(not(miri))]
+    fn encode_h264() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+
+        if !device.video_features().encode_h264() {
+            return Ok(());
+        }
+
+        let queue_family_encode = physical_device
+            .queue_family_infos()
+            .any_encode()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue_family_compute = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let memory_host = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+
+        let gop_config = GopConfig::new().idr_period(4);
+        let dpb_slots = gop_config.num_ref_frames() + 1;
+
+        let encode_info = H264EncodeInfo::new()
+            .extent(Extent2D { width: 256, height: 256 })
+            .max_num_ref_frames(gop_config.num_ref_frames());
+        let video_session_info = VideoSessionInfo::new()
+            .max_dpb_slots(dpb_slots)
+            .max_active_reference_pictures(gop_config.num_ref_frames());
+        let video_session = VideoEncodeSession::new(&device, &encode_info, &video_session_info)?;
+        let video_session_parameters = VideoEncodeSessionParameters::new(&video_session, &encode_info)?;
+        let format = video_session.info().get_picture_format().unwrap_or_default();
+        let format_dpb = video_session.info().get_reference_picture_format().unwrap_or_default();
+
+        let image_info = ImageInfo::new()
+            .samples(SampleCountFlags::TYPE_1)
+            .mip_levels(1)
+            .array_layers(1)
+            .image_type(ImageType::TYPE_2D)
+            .tiling(ImageTiling::OPTIMAL)
+            .layout(ImageLayout::UNDEFINED)
+            .extent(Extent3D::default().width(256).height(256).depth(1));
+        let image_info_src = image_info
+            .clone()
+            .format(format)
+            .usage(ImageUsageFlags::TRANSFER_DST | ImageUsageFlags::VIDEO_ENCODE_SRC_KHR);
+        let image_info_dpb = image_info
+            .format(format_dpb)
+            .usage(ImageUsageFlags::VIDEO_ENCODE_DPB_KHR)
+            .array_layers(dpb_slots);
+
+        let image_src = Image::new_video_target(&device, &image_info_src, &encode_info)?;
+        let image_dpb = Image::new_video_target(&device, &image_info_dpb, &encode_info)?;
+        let allocation_src = Allocation::for_requirements(&device, &image_src.memory_requirement(), MemoryPropertyFlags::DEVICE_LOCAL)?;
+        let allocation_dpb = Allocation::for_requirements(&device, &image_dpb.memory_requirement(), MemoryPropertyFlags::DEVICE_LOCAL)?;
+        let image_src = image_src.bind(&allocation_src)?;
+        let image_dpb = image_dpb.bind(&allocation_dpb)?;
+
+        let image_view_info = ImageViewInfo::new()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .image_view_type(ImageViewType::TYPE_2D)
+            .layer_count(1)
+            .level_count(1);
+        let image_view_src = ImageView::new(&image_src, &image_view_info.clone().format(format))?;
+        let image_views_dpb = (0..dpb_slots)
+            .map(|i| ImageView::new(&image_dpb, &image_view_info.clone().format(format_dpb).base_array_layer(i)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Mid-gray luma and chroma.
+        let allocation_planes = Allocation::new(&device, 256 * 256 * 2, memory_host)?;
+        let buffer_luma = Buffer::new(&allocation_planes, &BufferInfo::new().size(256 * 256))?;
+        let buffer_chroma = Buffer::new(&allocation_planes, &BufferInfo::new().size(256 * 128).offset(256 * 256))?;
+        buffer_luma.upload(&[128u8; 256 * 256])?;
+        buffer_chroma.upload(&[128u8; 256 * 128])?;
+
+        let allocation_bitstream = Allocation::new(&device, 1024 * 1024 + 256, memory_host)?;
+        let buffer_bitstream = Buffer::new_video_encode(&allocation_bitstream, &BufferInfo::new().size(1024 * 1024), &encode_info)?;
+
+        let queue_encode = Queue::new(&device, queue_family_encode, 0)?;
+        let queue_copy = Queue::new(&device, queue_family_compute, 0)?;
+        let command_buffer_encode = CommandBuffer::new(&device, queue_family_encode)?;
+        let command_buffer_copy = CommandBuffer::new(&device, queue_family_compute)?;
+
+        let upload_luma = CopyBuffer2Image::new(&buffer_luma, &image_src, ImageAspectFlags::PLANE_0);
+        let upload_chroma = CopyBuffer2Image::new(&buffer_chroma, &image_src, ImageAspectFlags::PLANE_1);
+
+        queue_copy.build_and_submit(&command_buffer_copy, |x| {
+            upload_luma.run_in(x)?;
+            upload_chroma.run_in(x)?;
+            Ok(())
+        })?;
+
+        let queries = VideoQueryPool::new_encode_feedback(&device, &encode_info, 1)?;
+
+        let mut gop = Gop::new(&gop_config);
+        let mut dpb = DpbSlotManager::new(dpb_slots, gop_config.num_ref_frames());
+        let mut slots = HashMap::new();
+        let mut frame_num = 0;
+        let mut idr_index = 0;
+
+        // Encodes the same (gray) frame a few times, switching rate control halfway.
+        for i in 0..6 {
+            let rate_control = match i < 3 {
+                true => RateControlInfo::cqp(30),
+                false => RateControlInfo::new(),
+            };
+
+            for frame in gop.push() {
+                if frame.frame_type() == FrameType::Idr {
+                    frame_num = 0;
+                    idr_index = frame.index();
+                }
+
+                let pic_order_cnt = 2 * (frame.index() - idr_index) as i32;
+                let setup = dpb.next_slot(frame_num, [pic_order_cnt; 2], frame.frame_type() == FrameType::Idr)?;
+                let references_l0 = frame.references_l0().iter().map(|x| slots[x]).collect::<Vec<_>>();
+
+                let encode = EncodeH264::new(
+                    &buffer_bitstream,
+                    &video_session_parameters,
+                    &image_view_src,
+                    &image_views_dpb[0],
+                    &EncodeInfo::new(0, 1024 * 1024),
+                )
+                .rate_control(&rate_control)
+                .idr_pic_id(idr_index as u16)
+                .frame_type(frame.frame_type())
+                .dpb(setup, &references_l0, &[], &image_views_dpb)
+                .query(&queries, 0);
+
+                queue_encode.build_and_submit(&command_buffer_encode, |x| {
+                    encode.run_in(x)?;
+                    Ok(())
+                })?;
+
+                let feedback = queries.encode_feedback()?[0];
+
+                assert_eq!(feedback.status(), QueryResultStatusKHR::COMPLETE);
+                assert!(feedback.bytes_written() > 0);
+
+                dpb.mark_reference(setup)?;
+                slots.insert(frame.index(), setup);
+                frame_num += 1;
+            }
+        }
+
+        // Encoded slices start with a start code.
+        let mut data_out = [0u8; 4];
+        buffer_bitstream.download_into(&mut data_out)?;
+
+        assert_eq!(data_out, [0, 0, 0, 1]);
+
+        Ok(())
+    }
+}
@@ -1,15 +1,20 @@
-use crate::error::Error;
-use crate::ops::AddToCommandBuffer;
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::ops::{Access, AddToCommandBuffer};
 use crate::queue::CommandBuilder;
 use crate::resources::{Buffer, BufferShared};
-use ash::vk;
-use ash::vk::{DependencyFlags, PipelineStageFlags, WHOLE_SIZE};
+use ash::vk::WHOLE_SIZE;
 use std::sync::Arc;
 
-/// Fills a buffer with a fixed value.
+/// Fills a buffer (or a range of it) with a fixed value.
+///
+/// Offset and size must be multiples of 4 bytes, as the value is written as `u32`. Without a size, everything up to the
+/// last multiple of 4 bytes before the end of the buffer is filled.
 pub struct FillBuffer {
     buffer: Arc<BufferShared>,
     value: u32,
+    offset: u64,
+    size: Option<u64>,
 }
 
 impl FillBuffer {
@@ -17,8 +22,33 @@ impl FillBuffer {
         Self {
             buffer: buffer.shared(),
             value,
+            offset: 0,
+            size: None,
         }
     }
+
+    /// Fills every byte with `value`.
+    pub fn new_u8(buffer: &Buffer, value: u8) -> Self {
+        Self::new(buffer, u32::from_ne_bytes([value; 4]))
+    }
+
+    /// Fills every 2 bytes with `value`, in native byte order.
+    pub fn new_u16(buffer: &Buffer, value: u16) -> Self {
+        let [a, b] = value.to_ne_bytes();
+        Self::new(buffer, u32::from_ne_bytes([a, b, a, b]))
+    }
+
+    /// Starts filling at `offset` bytes into the buffer.
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Fills `size` bytes instead of everything up to the end of the buffer, must not be 0.
+    pub fn size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
 }
 
 impl AddToCommandBuffer for FillBuffer {
@@ -26,29 +56,30 @@ impl AddToCommandBuffer for FillBuffer {
         let native_device = self.buffer.device().native();
         let native_buffer = self.buffer.native();
         let native_command_buffer = builder.native_command_buffer();
+        let size = self.size.unwrap_or(WHOLE_SIZE);
+
+        if !self.offset.is_multiple_of(4) || self.size.is_some_and(|x| x == 0 || !x.is_multiple_of(4)) {
+            return Err(error!(
+                Variant::InvalidFillRange,
+                "Offset {} and size {size} must be multiples of 4 bytes, and size must not be 0.", self.offset
+            ));
+        }
 
-        // TODO: Do we want to keep these barriers as part of these operations (but then we'd sort
-        // of have to divine what the subsequent operations are). Or do we want barriers to be
-        // explicit operations (but then people might forget using them or won't use them correctly)?
-        let buffer_barrier_after = vk::BufferMemoryBarrier::default()
-            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
-            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
-            .buffer(native_buffer)
-            .size(self.buffer.size())
-            .offset(0);
+        let in_bounds = match self.size {
+            Some(size) => self.offset.checked_add(size).is_some_and(|x| x <= self.buffer.size()),
+            None => self.offset < self.buffer.size(),
+        };
+
+        if !in_bounds {
+            return Err(error!(Variant::BufferTooSmall, "Fill range exceeds buffer."));
+        }
+
+        // Waits for earlier accesses, later ones wait for us in turn.
+        builder.access_buffer(&self.buffer, Access::TRANSFER_WRITE);
+        builder.record_barriers();
 
         unsafe {
-            native_device.cmd_fill_buffer(native_command_buffer, native_buffer, 0, WHOLE_SIZE, self.value);
-
-            native_device.cmd_pipeline_barrier(
-                native_command_buffer,
-                PipelineStageFlags::TRANSFER,
-                PipelineStageFlags::TRANSFER,
-                DependencyFlags::empty(),
-                &[],
-                &[buffer_barrier_after],
-                &[], // No image-level memory barriers
-            );
+            native_device.cmd_fill_buffer(native_command_buffer, native_buffer, self.offset, size, self.value);
 
             Ok(())
         }
@@ -107,4 +138,97 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn fill_buffer_range() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 1024, host_visible)?;
+
+        let buffer_info = BufferInfo::new().size(1024);
+        let buffer = Buffer::new(&allocation, &buffer_info)?;
+
+        let fill_zero = FillBuffer::new(&buffer, 0);
+        let fill_u8 = FillBuffer::new_u8(&buffer, 0xAB).offset(4).size(8);
+        let fill_u16 = FillBuffer::new_u16(&buffer, 0x1234).offset(512);
+        let fill_unaligned = FillBuffer::new(&buffer, 0).offset(2);
+        let fill_empty = FillBuffer::new(&buffer, 0).size(0);
+
+        queue.build_and_submit(&command_buffer, |x| {
+            fill_zero.run_in(x)?;
+            fill_u8.run_in(x)?;
+            fill_u16.run_in(x)?;
+            assert!(fill_unaligned.run_in(x).is_err());
+            assert!(fill_empty
+                .run_in(x)
+                .is_err_and(|e| matches!(e.variant(), Variant::InvalidFillRange)));
+            Ok(())
+        })?;
+
+        let mut data = vec![0; 1024];
+        buffer.download_into(&mut data)?;
+
+        assert_eq!(data[0..4], [0; 4]);
+        assert_eq!(data[4..12], [0xAB; 8]);
+        assert_eq!(data[12..512], [0; 500]);
+        assert!(data[512..].chunks(2).all(|x| u16::from_ne_bytes([x[0], x[1]]) == 0x1234));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn fill_to_unaligned_end() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 1024, host_visible)?;
+
+        let buffer_info = BufferInfo::new().size(1022);
+        let buffer = Buffer::new(&allocation, &buffer_info)?;
+
+        let fill_zero = FillBuffer::new(&buffer, 0).size(1020);
+        let fill_rest = FillBuffer::new_u8(&buffer, 0xAB).offset(8);
+        let fill_overflow = FillBuffer::new(&buffer, 0).offset(4).size(u64::MAX - 3);
+
+        queue.build_and_submit(&command_buffer, |x| {
+            fill_zero.run_in(x)?;
+            fill_rest.run_in(x)?;
+            assert!(fill_overflow.run_in(x).is_err());
+            Ok(())
+        })?;
+
+        let mut data = vec![0; 1020];
+        buffer.download_into(&mut data)?;
+
+        // Filling stops before the last 2 bytes, which don't make up a whole `u32`.
+        assert_eq!(data[0..8], [0; 8]);
+        assert_eq!(data[8..1020], [0xAB; 1012]);
+
+        Ok(())
+    }
 }
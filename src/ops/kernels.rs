@@ -0,0 +1,814 @@
+//! Ready-made compute kernels for common frame processing, see `shaders/` for their sources.
+//!
+//! All kernels work on tightly packed buffers, e.g., as produced by [`CopyImage2Buffer`](crate::ops::CopyImage2Buffer).
+use crate::device::Device;
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::ops::{AddToCommandBuffer, Compute};
+use crate::queue::CommandBuilder;
+use crate::resources::Buffer;
+use crate::shader::{Parameters, Pipeline, Shader, ShaderParameter, ShaderParameterSet};
+use ash::vk::{Extent2D, Offset2D, Rect2D};
+use h264_reader::nal::sps::{ChromaFormat, FrameMbsFlags, SeqParameterSet};
+
+/// SPIR-V must be 4-byte aligned, which `include_bytes!` alone does not guarantee.
+#[repr(C, align(4))]
+pub(super) struct Spirv<T: ?Sized>(pub(super) T);
+
+static YUV_TO_RGB: &Spirv<[u8]> = &Spirv(*include_bytes!("shaders/compiled/yuv_to_rgb.spv"));
+static RGBA_TO_NV12: &Spirv<[u8]> = &Spirv(*include_bytes!("shaders/compiled/rgba_to_nv12.spv"));
+static SCALE_RGBA: &Spirv<[u8]> = &Spirv(*include_bytes!("shaders/compiled/scale_rgba.spv"));
+static SPLIT_CHROMA: &Spirv<[u8]> = &Spirv(*include_bytes!("shaders/compiled/split_chroma.spv"));
+static MERGE_CHROMA: &Spirv<[u8]> = &Spirv(*include_bytes!("shaders/compiled/merge_chroma.spv"));
+
+/// Workgroup size of the 2D kernels.
+pub(super) const TILE: u32 = 16;
+
+/// Workgroup size of the 1D kernels.
+pub(super) const LINE: u32 = 64;
+
+pub(super) type TwoBuffers = (&'static Buffer, &'static Buffer);
+pub(super) type ThreeBuffers = (&'static Buffer, &'static Buffer, &'static Buffer);
+
+/// How luma and chroma relate to RGB.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMatrix {
+    /// SD content, ITU-R BT.601.
+    #[default]
+    Bt601,
+    /// HD content, ITU-R BT.709.
+    Bt709,
+    /// UHD and HDR content, ITU-R BT.2020 non-constant luminance.
+    Bt2020,
+}
+
+impl ColorMatrix {
+    /// The matrix for `matrix_coefficients` as signaled in the VUI (ITU-T H.273), if supported.
+    pub fn from_matrix_coefficients(matrix_coefficients: u8) -> Option<Self> {
+        match matrix_coefficients {
+            1 => Some(Self::Bt709),
+            5 | 6 => Some(Self::Bt601),
+            9 => Some(Self::Bt2020),
+            _ => None,
+        }
+    }
+
+    /// Weights of red and blue in luma, as push constants.
+    fn push_constants(self) -> [u32; 2] {
+        let (kr, kb) = match self {
+            Self::Bt601 => (0.299f32, 0.114f32),
+            Self::Bt709 => (0.2126, 0.0722),
+            Self::Bt2020 => (0.2627, 0.0593),
+        };
+
+        [kr.to_bits(), kb.to_bits()]
+    }
+}
+
+/// Which code values are black and white.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorRange {
+    /// Luma from 16 to 235 (at 8 bit), what most video uses.
+    #[default]
+    Limited,
+    /// All code values, e.g., from cameras or JPEG.
+    Full,
+}
+
+/// Matrix and range of YUV content, see [`YuvToRgb`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ColorSpace {
+    matrix: ColorMatrix,
+    range: ColorRange,
+}
+
+impl ColorSpace {
+    pub fn new(matrix: ColorMatrix, range: ColorRange) -> Self {
+        Self { matrix, range }
+    }
+
+    /// Reads the color space from the VUI of `sps`.
+    ///
+    /// Without (supported) matrix coefficients, HD content is assumed to be BT.709 and everything else BT.601.
+    pub fn from_h264(sps: &SeqParameterSet) -> Self {
+        let signal_type = sps.vui_parameters.as_ref().and_then(|x| x.video_signal_type.as_ref());
+        let signaled = signal_type
+            .and_then(|x| x.colour_description.as_ref())
+            .and_then(|x| ColorMatrix::from_matrix_coefficients(x.matrix_coefficients));
+        let height = sps.pixel_dimensions().map(|(_, height)| height).unwrap_or_default();
+
+        let matrix = match signaled {
+            Some(matrix) => matrix,
+            None if height >= 720 => ColorMatrix::Bt709,
+            None => ColorMatrix::Bt601,
+        };
+
+        let range = match signal_type.is_some_and(|x| x.video_full_range_flag) {
+            true => ColorRange::Full,
+            false => ColorRange::Limited,
+        };
+
+        Self { matrix, range }
+    }
+
+    pub fn matrix(&self) -> ColorMatrix {
+        self.matrix
+    }
+
+    pub fn range(&self) -> ColorRange {
+        self.range
+    }
+}
+
+/// Layout of a YUV 4:2:0 frame, luma plane followed by interleaved chroma, tightly packed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum YuvFormat {
+    /// 8 bit per sample, e.g., from `G8_B8R8_2PLANE_420_UNORM` images.
+    #[default]
+    Nv12,
+    /// 10 bit in the upper bits of 16 bit samples, e.g., from `G10X6_B10X6R10X6_2PLANE_420_UNORM_3PACK16` images.
+    P010,
+}
+
+impl YuvFormat {
+    /// Size of a frame in bytes.
+    pub(super) fn frame_size(self, extent: Extent2D) -> u64 {
+        let bytes_per_sample = match self {
+            Self::Nv12 => 1,
+            Self::P010 => 2,
+        };
+
+        extent.width as u64 * extent.height as u64 * 3 / 2 * bytes_per_sample
+    }
+}
+
+/// Builds a dispatch of `spirv` over `buffers`, with `constants` pushed.
+pub(super) fn kernel<T: ShaderParameterSet>(
+    device: &Device,
+    spirv: &Spirv<[u8]>,
+    buffers: &[&Buffer],
+    constants: &[u32],
+    dispatch_groups: (u32, u32, u32),
+) -> Result<Compute<T>, Error> {
+    let push_constants = constants.iter().flat_map(|x| x.to_ne_bytes()).collect::<Vec<_>>();
+    let parameters = Parameters::<T>::new(device)?;
+    let shader = Shader::new(device, &spirv.0, "main", &parameters)?;
+    let pipeline = Pipeline::new_with_push_constants(device, &shader, push_constants.len() as u32)?;
+    let parameters = buffers.iter().map(|x| x.parameter_type()).collect();
+
+    Ok(Compute::new_with_parameters(&pipeline, parameters, dispatch_groups)?.push_constants(&push_constants))
+}
+
+pub(super) fn check_size(buffer: &Buffer, required: u64, name: &str) -> Result<(), Error> {
+    if buffer.size() < required {
+        return Err(error!(
+            Variant::BufferTooSmall,
+            "Buffer `{name}` has {} bytes, but needs {required}.",
+            buffer.size()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks `extent` is non-empty and both sides are multiples of the given values.
+pub(super) fn check_extent(extent: Extent2D, multiple_x: u32, multiple_y: u32) -> Result<(), Error> {
+    if extent.width == 0 || extent.height == 0 || !extent.width.is_multiple_of(multiple_x) || !extent.height.is_multiple_of(multiple_y) {
+        return Err(error!(
+            Variant::InvalidExtent,
+            "Extent {}x{} must be non-empty and a multiple of {multiple_x}x{multiple_y}.", extent.width, extent.height
+        ));
+    }
+
+    Ok(())
+}
+
+/// Size of an RGBA8 frame in bytes.
+pub(super) fn rgba_size(extent: Extent2D) -> u64 {
+    extent.width as u64 * extent.height as u64 * 4
+}
+
+/// Converts a YUV frame (NV12 or P010) to RGBA8, e.g., after decoding.
+///
+/// Width must be a multiple of 4, height a multiple of 2.
+///
+/// ```rust,ignore
+/// let color_space = ColorSpace::from_h264(&sps);
+/// let convert = YuvToRgb::new(&device, &yuv, &rgba, extent, YuvFormat::Nv12, color_space)?;
+/// ```
+pub struct YuvToRgb {
+    compute: Compute<TwoBuffers>,
+}
+
+impl YuvToRgb {
+    pub fn new(
+        device: &Device,
+        yuv: &Buffer,
+        rgba: &Buffer,
+        extent: Extent2D,
+        format: YuvFormat,
+        color_space: ColorSpace,
+    ) -> Result<Self, Error> {
+        check_extent(extent, 4, 2)?;
+        check_size(yuv, format.frame_size(extent), "yuv")?;
+        check_size(rgba, rgba_size(extent), "rgba")?;
+
+        let [kr, kb] = color_space.matrix.push_constants();
+        let p010 = (format == YuvFormat::P010) as u32;
+        let full_range = (color_space.range == ColorRange::Full) as u32;
+
+        let constants = [extent.width, extent.height, p010, full_range, kr, kb];
+        let groups = (extent.width.div_ceil(TILE), extent.height.div_ceil(TILE), 1);
+        let compute = kernel(device, YUV_TO_RGB, &[yuv, rgba], &constants, groups)?;
+
+        Ok(Self { compute })
+    }
+}
+
+impl AddToCommandBuffer for YuvToRgb {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        self.compute.run_in(builder)
+    }
+}
+
+/// Converts a limited range NV12 frame to RGBA8, shorthand for the most common [`YuvToRgb`].
+pub struct ConvertNv12ToRgba {
+    yuv_to_rgb: YuvToRgb,
+}
+
+impl ConvertNv12ToRgba {
+    pub fn new(device: &Device, nv12: &Buffer, rgba: &Buffer, extent: Extent2D, matrix: ColorMatrix) -> Result<Self, Error> {
+        let color_space = ColorSpace::new(matrix, ColorRange::Limited);
+        let yuv_to_rgb = YuvToRgb::new(device, nv12, rgba, extent, YuvFormat::Nv12, color_space)?;
+
+        Ok(Self { yuv_to_rgb })
+    }
+}
+
+impl AddToCommandBuffer for ConvertNv12ToRgba {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        self.yuv_to_rgb.run_in(builder)
+    }
+}
+
+/// Converts an RGBA8 frame to NV12, e.g., before encoding.
+///
+/// Chroma is averaged over 2x2 pixels. Width must be a multiple of 4, height a multiple of 2.
+pub struct ConvertRgbaToNv12 {
+    compute: Compute<TwoBuffers>,
+}
+
+impl ConvertRgbaToNv12 {
+    pub fn new(device: &Device, rgba: &Buffer, nv12: &Buffer, extent: Extent2D, matrix: ColorMatrix) -> Result<Self, Error> {
+        check_extent(extent, 4, 2)?;
+        check_size(rgba, rgba_size(extent), "rgba")?;
+        check_size(nv12, YuvFormat::Nv12.frame_size(extent), "nv12")?;
+
+        // One invocation per 4x2 block.
+        let [kr, kb] = matrix.push_constants();
+        let constants = [extent.width, extent.height, kr, kb];
+        let groups = ((extent.width / 4).div_ceil(TILE), (extent.height / 2).div_ceil(TILE), 1);
+        let compute = kernel(device, RGBA_TO_NV12, &[rgba, nv12], &constants, groups)?;
+
+        Ok(Self { compute })
+    }
+}
+
+impl AddToCommandBuffer for ConvertRgbaToNv12 {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        self.compute.run_in(builder)
+    }
+}
+
+/// Scales an RGBA8 frame to another size, with bilinear filtering.
+pub struct ScaleRgba {
+    compute: Compute<TwoBuffers>,
+}
+
+impl ScaleRgba {
+    pub fn new(device: &Device, src: &Buffer, src_extent: Extent2D, dst: &Buffer, dst_extent: Extent2D) -> Result<Self, Error> {
+        let crop = Rect2D::default().extent(src_extent);
+        let view = Rect2D::default().extent(dst_extent);
+        let compute = scale_kernel(device, src, src_extent, crop, dst, dst_extent, view, 0)?;
+
+        Ok(Self { compute })
+    }
+}
+
+impl AddToCommandBuffer for ScaleRgba {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        self.compute.run_in(builder)
+    }
+}
+
+/// Crops a region of an RGBA8 frame and scales it to another frame, with bilinear filtering.
+///
+/// ```rust,ignore
+/// // Drops the padding rows of a 1920x1088 decoded frame, keeping 16:9 in a 4:3 output.
+/// let crop = CropRgba::h264_rect(&sps)?;
+/// let op = CropRgba::new_letterboxed(&device, &src, src_extent, crop, &dst, dst_extent, [0, 0, 0, 255])?;
+/// ```
+pub struct CropRgba {
+    compute: Compute<TwoBuffers>,
+}
+
+impl CropRgba {
+    /// Stretches `crop` of `src` over all of `dst`.
+    pub fn new(
+        device: &Device,
+        src: &Buffer,
+        src_extent: Extent2D,
+        crop: Rect2D,
+        dst: &Buffer,
+        dst_extent: Extent2D,
+    ) -> Result<Self, Error> {
+        let view = Rect2D::default().extent(dst_extent);
+        let compute = scale_kernel(device, src, src_extent, crop, dst, dst_extent, view, 0)?;
+
+        Ok(Self { compute })
+    }
+
+    /// Scales `crop` of `src` to fit `dst` keeping its aspect ratio, centered, filling bars with `fill` (RGBA).
+    pub fn new_letterboxed(
+        device: &Device,
+        src: &Buffer,
+        src_extent: Extent2D,
+        crop: Rect2D,
+        dst: &Buffer,
+        dst_extent: Extent2D,
+        fill: [u8; 4],
+    ) -> Result<Self, Error> {
+        let view = letterbox(crop.extent, dst_extent);
+        let compute = scale_kernel(device, src, src_extent, crop, dst, dst_extent, view, u32::from_le_bytes(fill))?;
+
+        Ok(Self { compute })
+    }
+
+    /// The visible region of frames decoded with `sps`, as given by its frame cropping.
+    pub fn h264_rect(sps: &SeqParameterSet) -> Result<Rect2D, Error> {
+        let (width, height) = sps.pixel_dimensions().map_err(|e| error!(Variant::InvalidBitstream, "{e:?}"))?;
+        let crop = sps.frame_cropping.clone().unwrap_or_default();
+
+        // Offsets are in chroma samples, and in field pairs for interlaced content.
+        let step_x = match sps.chroma_info.chroma_format {
+            ChromaFormat::YUV420 | ChromaFormat::YUV422 => 2,
+            _ => 1,
+        };
+        let step_y = match (sps.chroma_info.chroma_format, &sps.frame_mbs_flags) {
+            (ChromaFormat::YUV420, FrameMbsFlags::Frames) => 2,
+            (ChromaFormat::YUV420, FrameMbsFlags::Fields { .. }) => 4,
+            (_, FrameMbsFlags::Frames) => 1,
+            (_, FrameMbsFlags::Fields { .. }) => 2,
+        };
+
+        let offset = Offset2D::default()
+            .x((crop.left_offset * step_x) as i32)
+            .y((crop.top_offset * step_y) as i32);
+        let extent = Extent2D::default().width(width).height(height);
+
+        Ok(Rect2D::default().offset(offset).extent(extent))
+    }
+}
+
+impl AddToCommandBuffer for CropRgba {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        self.compute.run_in(builder)
+    }
+}
+
+/// Scales `crop` of `src` into `view` of `dst`, filling the rest of `dst` with `fill`.
+#[allow(clippy::too_many_arguments)]
+fn scale_kernel(
+    device: &Device,
+    src: &Buffer,
+    src_extent: Extent2D,
+    crop: Rect2D,
+    dst: &Buffer,
+    dst_extent: Extent2D,
+    view: Rect2D,
+    fill: u32,
+) -> Result<Compute<TwoBuffers>, Error> {
+    check_extent(src_extent, 1, 1)?;
+    check_extent(dst_extent, 1, 1)?;
+    check_extent(crop.extent, 1, 1)?;
+    check_size(src, rgba_size(src_extent), "src")?;
+    check_size(dst, rgba_size(dst_extent), "dst")?;
+
+    let fits = |rect: Rect2D, extent: Extent2D| {
+        rect.offset.x >= 0
+            && rect.offset.y >= 0
+            && rect.offset.x as u64 + rect.extent.width as u64 <= extent.width as u64
+            && rect.offset.y as u64 + rect.extent.height as u64 <= extent.height as u64
+    };
+
+    if !fits(crop, src_extent) || !fits(view, dst_extent) {
+        return Err(error!(
+            Variant::InvalidExtent,
+            "Rect {crop:?} exceeds source or {view:?} destination."
+        ));
+    }
+
+    let constants = [
+        src_extent.width,
+        crop.offset.x as u32,
+        crop.offset.y as u32,
+        crop.extent.width,
+        crop.extent.height,
+        dst_extent.width,
+        dst_extent.height,
+        view.offset.x as u32,
+        view.offset.y as u32,
+        view.extent.width,
+        view.extent.height,
+        fill,
+    ];
+    let groups = (dst_extent.width.div_ceil(TILE), dst_extent.height.div_ceil(TILE), 1);
+
+    kernel(device, SCALE_RGBA, &[src, dst], &constants, groups)
+}
+
+/// The largest centered rect in `dst` with the aspect ratio of `src`.
+fn letterbox(src: Extent2D, dst: Extent2D) -> Rect2D {
+    // Compares `src.width / src.height` against `dst.width / dst.height` without rounding.
+    let (width, height) = match src.width as u64 * dst.height as u64 >= dst.width as u64 * src.height as u64 {
+        true => (dst.width, (dst.width as u64 * src.height as u64 / src.width.max(1) as u64) as u32),
+        false => ((dst.height as u64 * src.width as u64 / src.height.max(1) as u64) as u32, dst.height),
+    };
+
+    let offset = Offset2D::default()
+        .x(((dst.width - width) / 2) as i32)
+        .y(((dst.height - height) / 2) as i32);
+
+    Rect2D::default()
+        .offset(offset)
+        .extent(Extent2D::default().width(width.max(1)).height(height.max(1)))
+}
+
+/// Splits interleaved chroma (the second NV12 plane) into separate U and V planes, as in I420.
+///
+/// The `extent` is that of the chroma planes, half the frame in each direction; its area must be a multiple of 4.
+pub struct SplitChroma {
+    compute: Compute<ThreeBuffers>,
+}
+
+impl SplitChroma {
+    pub fn new(device: &Device, uv: &Buffer, u: &Buffer, v: &Buffer, extent: Extent2D) -> Result<Self, Error> {
+        let words = chroma_words(extent)?;
+        let samples = words as u64 * 4;
+
+        check_size(uv, 2 * samples, "uv")?;
+        check_size(u, samples, "u")?;
+        check_size(v, samples, "v")?;
+
+        let compute = kernel(device, SPLIT_CHROMA, &[uv, u, v], &[words], (words.div_ceil(LINE), 1, 1))?;
+
+        Ok(Self { compute })
+    }
+}
+
+impl AddToCommandBuffer for SplitChroma {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        self.compute.run_in(builder)
+    }
+}
+
+/// Interleaves separate U and V planes into NV12 chroma, the inverse of [`SplitChroma`].
+pub struct MergeChroma {
+    compute: Compute<ThreeBuffers>,
+}
+
+impl MergeChroma {
+    pub fn new(device: &Device, u: &Buffer, v: &Buffer, uv: &Buffer, extent: Extent2D) -> Result<Self, Error> {
+        let words = chroma_words(extent)?;
+        let samples = words as u64 * 4;
+
+        check_size(u, samples, "u")?;
+        check_size(v, samples, "v")?;
+        check_size(uv, 2 * samples, "uv")?;
+
+        let compute = kernel(device, MERGE_CHROMA, &[u, v, uv], &[words], (words.div_ceil(LINE), 1, 1))?;
+
+        Ok(Self { compute })
+    }
+}
+
+impl AddToCommandBuffer for MergeChroma {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        self.compute.run_in(builder)
+    }
+}
+
+/// Number of 4-byte words in one chroma plane of `extent`.
+fn chroma_words(extent: Extent2D) -> Result<u32, Error> {
+    check_extent(extent, 1, 1)?;
+
+    let samples = extent.width as u64 * extent.height as u64;
+
+    if !samples.is_multiple_of(4) || samples / 4 > u32::MAX as u64 {
+        return Err(error!(
+            Variant::InvalidExtent,
+            "Chroma extent {}x{} must cover a multiple of 4 samples.", extent.width, extent.height
+        ));
+    }
+
+    Ok((samples / 4) as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::commandbuffer::CommandBuffer;
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::ops::kernels::{check_extent, chroma_words, letterbox};
+    use crate::ops::{
+        AddToCommandBuffer, ColorMatrix, ColorRange, ColorSpace, ConvertNv12ToRgba, ConvertRgbaToNv12, CropRgba, MergeChroma, ScaleRgba,
+        SplitChroma, YuvFormat, YuvToRgb,
+    };
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::Queue;
+    use crate::resources::{Buffer, BufferInfo};
+    use ash::vk::{Extent2D, Offset2D, Rect2D};
+
+    #[test]
+    fn extents() {
+        assert!(check_extent(Extent2D::default().width(64).height(32), 4, 2).is_ok());
+        assert!(check_extent(Extent2D::default().width(62).height(32), 4, 2).is_err());
+        assert!(check_extent(Extent2D::default().width(0).height(32), 1, 1).is_err());
+        assert_eq!(chroma_words(Extent2D::default().width(8).height(2)).ok(), Some(4));
+        assert!(chroma_words(Extent2D::default().width(3).height(1)).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "reflection")]
+    fn kernels_match_parameters() -> Result<(), Error> {
+        use crate::ops::kernels::{ThreeBuffers, TwoBuffers, MERGE_CHROMA, RGBA_TO_NV12, SCALE_RGBA, SPLIT_CHROMA, YUV_TO_RGB};
+        use crate::shader::{validate_bindings, ShaderParameterSet};
+
+        for spirv in [YUV_TO_RGB, RGBA_TO_NV12, SCALE_RGBA] {
+            validate_bindings(&spirv.0, "main", &TwoBuffers::descriptor_types())?;
+        }
+
+        for spirv in [SPLIT_CHROMA, MERGE_CHROMA] {
+            validate_bindings(&spirv.0, "main", &ThreeBuffers::descriptor_types())?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn letterbox_rects() {
+        let extent = |width, height| Extent2D::default().width(width).height(height);
+        let rect = |x, y, width, height| (x, y, width, height);
+        let flat = |x: ash::vk::Rect2D| rect(x.offset.x, x.offset.y, x.extent.width, x.extent.height);
+
+        assert_eq!(flat(letterbox(extent(1920, 1080), extent(640, 480))), rect(0, 60, 640, 360));
+        assert_eq!(flat(letterbox(extent(640, 480), extent(1920, 1080))), rect(240, 0, 1440, 1080));
+        assert_eq!(flat(letterbox(extent(100, 100), extent(100, 100))), rect(0, 0, 100, 100));
+    }
+
+    #[test]
+    fn color_matrices() {
+        assert_eq!(ColorMatrix::from_matrix_coefficients(1), Some(ColorMatrix::Bt709));
+        assert_eq!(ColorMatrix::from_matrix_coefficients(6), Some(ColorMatrix::Bt601));
+        assert_eq!(ColorMatrix::from_matrix_coefficients(9), Some(ColorMatrix::Bt2020));
+        assert_eq!(ColorMatrix::from_matrix_coefficients(2), None);
+        assert_eq!(ColorSpace::default().range(), ColorRange::Limited);
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn p010_full_range_to_rgb() -> Result<(), Error> {
+        let extent = Extent2D::default().width(64).height(32);
+        let p010_size = 64 * 32 * 3;
+        let rgba_size = 64 * 32 * 4;
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 2 * 8192, host_visible)?;
+        let p010 = Buffer::new(&allocation, &BufferInfo::new().size(p010_size).offset(0))?;
+        let rgba = Buffer::new(&allocation, &BufferInfo::new().size(rgba_size).offset(8192))?;
+
+        // Mid gray, no chroma, 10 bit values in the upper bits.
+        p010.upload(&(512u16 << 6).to_le_bytes().repeat(p010_size as usize / 2))?;
+
+        let color_space = ColorSpace::new(ColorMatrix::Bt2020, ColorRange::Full);
+        let convert = YuvToRgb::new(&device, &p010, &rgba, extent, YuvFormat::P010, color_space)?;
+        assert!(YuvToRgb::new(&device, &rgba, &p010, extent, YuvFormat::P010, color_space).is_err());
+
+        queue.build_and_submit(&command_buffer, |x| convert.run_in(x))?;
+
+        let mut pixels = vec![0u8; rgba_size as usize];
+        rgba.download_into(&mut pixels)?;
+
+        assert!(pixels.chunks(4).all(|x| x == [128, 128, 128, 255]));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn nv12_rgba_roundtrip() -> Result<(), Error> {
+        let extent = Extent2D::default().width(64).height(32);
+        let nv12_size = 64 * 32 * 3 / 2;
+        let rgba_size = 64 * 32 * 4;
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 2 * 16384, host_visible)?;
+        let nv12 = Buffer::new(&allocation, &BufferInfo::new().size(nv12_size).offset(0))?;
+        let rgba = Buffer::new(&allocation, &BufferInfo::new().size(rgba_size).offset(16384))?;
+
+        // Mid gray, no chroma.
+        let mut frame = vec![126u8; nv12_size as usize];
+        frame[64 * 32..].fill(128);
+        nv12.upload(&frame)?;
+
+        let to_rgba = ConvertNv12ToRgba::new(&device, &nv12, &rgba, extent, ColorMatrix::Bt709)?;
+        let to_nv12 = ConvertRgbaToNv12::new(&device, &rgba, &nv12, extent, ColorMatrix::Bt709)?;
+
+        queue.build_and_submit(&command_buffer, |x| to_rgba.run_in(x))?;
+
+        let mut pixels = vec![0u8; rgba_size as usize];
+        rgba.download_into(&mut pixels)?;
+
+        assert!(pixels.chunks(4).all(|x| x[0] == x[1] && x[1] == x[2] && x[3] == 255));
+        assert!(pixels.chunks(4).all(|x| x[0].abs_diff(128) <= 1));
+
+        queue.build_and_submit(&command_buffer, |x| to_nv12.run_in(x))?;
+
+        let mut roundtrip = vec![0u8; nv12_size as usize];
+        nv12.download_into(&mut roundtrip)?;
+
+        assert!(roundtrip.iter().zip(&frame).all(|(a, b)| a.abs_diff(*b) <= 1));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn scale_constant_color() -> Result<(), Error> {
+        let src_extent = Extent2D::default().width(40).height(30);
+        let dst_extent = Extent2D::default().width(17).height(9);
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 2 * 8192, host_visible)?;
+        let src = Buffer::new(&allocation, &BufferInfo::new().size(40 * 30 * 4).offset(0))?;
+        let dst = Buffer::new(&allocation, &BufferInfo::new().size(17 * 9 * 4).offset(8192))?;
+
+        src.upload(&[10, 20, 30, 40].repeat(40 * 30))?;
+
+        let scale = ScaleRgba::new(&device, &src, src_extent, &dst, dst_extent)?;
+        assert!(ScaleRgba::new(&device, &dst, src_extent, &src, dst_extent).is_err());
+
+        queue.build_and_submit(&command_buffer, |x| scale.run_in(x))?;
+
+        let mut pixels = vec![0u8; 17 * 9 * 4];
+        dst.download_into(&mut pixels)?;
+
+        assert!(pixels.chunks(4).all(|x| x == [10, 20, 30, 40]));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn crop_letterboxed() -> Result<(), Error> {
+        let src_extent = Extent2D::default().width(8).height(4);
+        let dst_extent = Extent2D::default().width(16).height(8);
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 2 * 8192, host_visible)?;
+        let src = Buffer::new(&allocation, &BufferInfo::new().size(8 * 4 * 4).offset(0))?;
+        let dst = Buffer::new(&allocation, &BufferInfo::new().size(16 * 8 * 4).offset(8192))?;
+
+        // Left half red, right half green; we only want the green square.
+        let row = [[255, 0, 0, 255].repeat(4), [0, 255, 0, 255].repeat(4)].concat();
+        src.upload(&row.repeat(4))?;
+
+        let crop = Rect2D::default()
+            .offset(Offset2D::default().x(4))
+            .extent(Extent2D::default().width(4).height(4));
+        let outside = crop.offset(Offset2D::default().x(5));
+
+        let op = CropRgba::new_letterboxed(&device, &src, src_extent, crop, &dst, dst_extent, [1, 2, 3, 4])?;
+        assert!(CropRgba::new(&device, &src, src_extent, outside, &dst, dst_extent).is_err());
+
+        queue.build_and_submit(&command_buffer, |x| op.run_in(x))?;
+
+        let mut pixels = vec![0u8; 16 * 8 * 4];
+        dst.download_into(&mut pixels)?;
+
+        for (i, pixel) in pixels.chunks(4).enumerate() {
+            match i % 16 {
+                4..12 => assert_eq!(pixel, [0, 255, 0, 255]),
+                _ => assert_eq!(pixel, [1, 2, 3, 4]),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn split_merge_chroma() -> Result<(), Error> {
+        let extent = Extent2D::default().width(32).height(16);
+        let samples = 32 * 16;
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 4 * 1024, host_visible)?;
+        let uv = Buffer::new(&allocation, &BufferInfo::new().size(2 * samples).offset(0))?;
+        let u = Buffer::new(&allocation, &BufferInfo::new().size(samples).offset(1024))?;
+        let v = Buffer::new(&allocation, &BufferInfo::new().size(samples).offset(2048))?;
+        let merged = Buffer::new(&allocation, &BufferInfo::new().size(2 * samples).offset(3072))?;
+
+        let interleaved = (0..2 * samples).map(|x| (x % 251) as u8).collect::<Vec<_>>();
+        uv.upload(&interleaved)?;
+
+        let split = SplitChroma::new(&device, &uv, &u, &v, extent)?;
+        let merge = MergeChroma::new(&device, &u, &v, &merged, extent)?;
+
+        queue.build_and_submit(&command_buffer, |x| {
+            split.run_in(x)?;
+            merge.run_in(x)
+        })?;
+
+        let mut data_u = vec![0u8; samples as usize];
+        let mut data_v = vec![0u8; samples as usize];
+        let mut data_merged = vec![0u8; 2 * samples as usize];
+        u.download_into(&mut data_u)?;
+        v.download_into(&mut data_v)?;
+        merged.download_into(&mut data_merged)?;
+
+        assert!(interleaved.chunks(2).zip(&data_u).all(|(uv, u)| uv[0] == *u));
+        assert!(interleaved.chunks(2).zip(&data_v).all(|(uv, v)| uv[1] == *v));
+        assert_eq!(data_merged, interleaved);
+
+        Ok(())
+    }
+}
@@ -2,21 +2,137 @@
 use crate::error::Error;
 use crate::queue::CommandBuilder;
 
+mod barrier;
+mod compare;
 mod compute;
 mod copyb2b;
+mod copyb2i;
 mod copyi2b;
 mod decodeh264;
+mod decodeh265;
 mod dummy;
+mod encodeh264;
 mod fill;
+mod kernels;
+mod overlay;
+mod sequence;
+mod timestamp;
+mod tonemap;
+mod update;
 
 /// Something that can be added to a command buffer (e.g., compute, mem copy, or video decode).
+///
+/// Closures are operations too, for one-off raw commands:
+///
+/// ```rust,ignore
+/// let clear = |x: &mut CommandBuilder| unsafe {
+///     x.native_device().cmd_fill_buffer(x.native_command_buffer(), native_buffer, 0, WHOLE_SIZE, 0);
+///     Ok(())
+/// };
+///
+/// queue.build_and_submit(&command_buffer, |x| x.run(&clear))?;
+/// ```
+///
+/// Raw commands are not seen by the automatic barriers, so they have to place their own.
 pub trait AddToCommandBuffer {
     fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error>;
+
+    /// Name of this operation, used to label it in debugging tools if `VK_EXT_debug_utils` is enabled.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+impl<F> AddToCommandBuffer for F
+where
+    F: Fn(&mut CommandBuilder) -> Result<(), Error>,
+{
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        self(builder)
+    }
 }
 
+pub use barrier::{Access, Barrier};
+pub use compare::ComparePsnr;
 pub use compute::Compute;
 pub use copyb2b::CopyBuffer2Buffer;
+pub use copyb2i::CopyBuffer2Image;
 pub use copyi2b::CopyImage2Buffer;
-pub use decodeh264::{DecodeH264, DecodeInfo};
+pub use decodeh264::{DecodeH264, DecodeH264Batch, DecodeInfo};
+pub use decodeh265::DecodeH265;
 pub use dummy::Dummy;
+pub use encodeh264::{EncodeH264, EncodeInfo};
 pub use fill::FillBuffer;
+pub use kernels::{
+    ColorMatrix, ColorRange, ColorSpace, ConvertNv12ToRgba, ConvertRgbaToNv12, CropRgba, MergeChroma, ScaleRgba, SplitChroma, YuvFormat,
+    YuvToRgb,
+};
+pub use overlay::OverlayRgba;
+pub use sequence::Sequence;
+pub use timestamp::WriteTimestamp;
+pub use tonemap::{ToneCurve, ToneMap, ToneMapInfo, TransferFunction};
+pub use update::UpdateBuffer;
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::commandbuffer::CommandBuffer;
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::ops::AddToCommandBuffer;
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::{CommandBuilder, Queue};
+    use crate::resources::{Buffer, BufferInfo};
+    use ash::vk::{AccessFlags2, BufferMemoryBarrier2, DependencyInfoKHR, PipelineStageFlags2, WHOLE_SIZE};
+
+    #[test]
+    #[cfg(not(miri))]
+    fn closure_as_op() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 1024, host_visible)?;
+
+        let buffer_info = BufferInfo::new().size(1024);
+        let buffer = Buffer::new(&allocation, &buffer_info)?;
+        let native_buffer = buffer.shared().native();
+
+        let fill = |x: &mut CommandBuilder| unsafe {
+            let buffer_barriers = [BufferMemoryBarrier2::default()
+                .src_stage_mask(PipelineStageFlags2::TRANSFER)
+                .src_access_mask(AccessFlags2::TRANSFER_WRITE)
+                .dst_stage_mask(PipelineStageFlags2::HOST)
+                .dst_access_mask(AccessFlags2::HOST_READ)
+                .buffer(native_buffer)
+                .size(WHOLE_SIZE)];
+            let dependency_info = DependencyInfoKHR::default().buffer_memory_barriers(&buffer_barriers);
+
+            x.native_device()
+                .cmd_fill_buffer(x.native_command_buffer(), native_buffer, 0, WHOLE_SIZE, 0x11223344);
+            x.native_device().cmd_pipeline_barrier2(x.native_command_buffer(), &dependency_info);
+            Ok(())
+        };
+
+        queue.build_and_submit(&command_buffer, |x| fill.run_in(x))?;
+
+        let mut data = vec![0; 1024];
+        buffer.download_into(&mut data)?;
+
+        assert!(data.chunks(4).all(|x| x == 0x11223344_u32.to_ne_bytes()));
+
+        Ok(())
+    }
+}
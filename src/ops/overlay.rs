@@ -0,0 +1,137 @@
+use crate::device::Device;
+use crate::error::Error;
+use crate::ops::kernels::{check_extent, check_size, kernel, rgba_size, Spirv, TwoBuffers, TILE};
+use crate::ops::{AddToCommandBuffer, Compute};
+use crate::queue::CommandBuilder;
+use crate::resources::Buffer;
+use ash::vk::{Extent2D, Offset2D};
+
+static OVERLAY_RGBA: &Spirv<[u8]> = &Spirv(*include_bytes!("shaders/compiled/overlay_rgba.spv"));
+
+/// Blends an RGBA8 overlay (e.g., a watermark or subtitles) onto an RGBA8 frame, in place.
+///
+/// The overlay has straight (not premultiplied) alpha, which is further scaled by `opacity`.
+/// It is placed with its top left corner at `position`, and parts outside the frame are skipped.
+///
+/// ```rust,ignore
+/// let logo = OverlayRgba::new(&device, &logo, logo_extent, &frame, frame_extent, Offset2D::default().x(16).y(16), 0.8)?;
+///
+/// queue.build_and_submit(&command_buffer, |x| {
+///     convert.run_in(x)?;
+///     logo.run_in(x)
+/// })?;
+/// ```
+pub struct OverlayRgba {
+    compute: Compute<TwoBuffers>,
+}
+
+impl OverlayRgba {
+    /// Prepares blending `overlay` onto `frame`, with `opacity` clamped to `0.0..=1.0`.
+    pub fn new(
+        device: &Device,
+        overlay: &Buffer,
+        overlay_extent: Extent2D,
+        frame: &Buffer,
+        frame_extent: Extent2D,
+        position: Offset2D,
+        opacity: f32,
+    ) -> Result<Self, Error> {
+        check_extent(overlay_extent, 1, 1)?;
+        check_extent(frame_extent, 1, 1)?;
+        check_size(overlay, rgba_size(overlay_extent), "overlay")?;
+        check_size(frame, rgba_size(frame_extent), "frame")?;
+
+        let opacity = if opacity.is_nan() { 0.0 } else { opacity.clamp(0.0, 1.0) };
+        let constants = [
+            overlay_extent.width,
+            overlay_extent.height,
+            frame_extent.width,
+            frame_extent.height,
+            position.x as u32,
+            position.y as u32,
+            opacity.to_bits(),
+        ];
+        let groups = (overlay_extent.width.div_ceil(TILE), overlay_extent.height.div_ceil(TILE), 1);
+        let compute = kernel(device, OVERLAY_RGBA, &[overlay, frame], &constants, groups)?;
+
+        Ok(Self { compute })
+    }
+}
+
+impl AddToCommandBuffer for OverlayRgba {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        self.compute.run_in(builder)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::commandbuffer::CommandBuffer;
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::ops::{AddToCommandBuffer, OverlayRgba};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::Queue;
+    use crate::resources::{Buffer, BufferInfo};
+    use ash::vk::{Extent2D, Offset2D};
+
+    #[test]
+    #[cfg(feature = "reflection")]
+    fn kernel_matches_parameters() -> Result<(), Error> {
+        use crate::ops::kernels::TwoBuffers;
+        use crate::ops::overlay::OVERLAY_RGBA;
+        use crate::shader::{validate_bindings, ShaderParameterSet};
+
+        validate_bindings(&OVERLAY_RGBA.0, "main", &TwoBuffers::descriptor_types())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn overlay_clipped() -> Result<(), Error> {
+        let frame_extent = Extent2D::default().width(8).height(8);
+        let overlay_extent = Extent2D::default().width(4).height(4);
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 2 * 4096, host_visible)?;
+        let overlay = Buffer::new(&allocation, &BufferInfo::new().size(4 * 4 * 4).offset(0))?;
+        let frame = Buffer::new(&allocation, &BufferInfo::new().size(8 * 8 * 4).offset(4096))?;
+
+        overlay.upload(&[255, 255, 255, 255].repeat(4 * 4))?;
+        frame.upload(&[0, 0, 0, 255].repeat(8 * 8))?;
+
+        // Hangs off the top left corner, so only a 2x2 block lands in the frame.
+        let position = Offset2D::default().x(-2).y(-2);
+        let op = OverlayRgba::new(&device, &overlay, overlay_extent, &frame, frame_extent, position, 0.5)?;
+        assert!(OverlayRgba::new(&device, &frame, frame_extent, &overlay, overlay_extent, position, 0.5).is_err());
+
+        queue.build_and_submit(&command_buffer, |x| op.run_in(x))?;
+
+        let mut pixels = vec![0u8; 8 * 8 * 4];
+        frame.download_into(&mut pixels)?;
+
+        for (i, pixel) in pixels.chunks(4).enumerate() {
+            match (i % 8, i / 8) {
+                (0..2, 0..2) => assert!(pixel[..3].iter().all(|x| x.abs_diff(128) <= 1) && pixel[3] == 255),
+                _ => assert_eq!(pixel, [0, 0, 0, 255]),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use crate::error::Error;
+use crate::ops::AddToCommandBuffer;
+use crate::queue::CommandBuilder;
+
+/// Runs several operations in order, so pipelines can be packaged and passed around as one.
+///
+/// Nested operations synchronize with each other as they would when run individually:
+///
+/// ```rust,ignore
+/// let pipeline = Sequence::new()
+///     .then(decode)
+///     .then(convert)
+///     .then(CopyImage2Buffer::new(&image, &buffer, ImageAspectFlags::PLANE_0));
+///
+/// queue.build_and_submit(&command_buffer, |x| x.run(&pipeline))?;
+/// ```
+#[derive(Default)]
+pub struct Sequence {
+    ops: Vec<Box<dyn AddToCommandBuffer>>,
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        Self { ops: Vec::new() }
+    }
+
+    /// Appends `op`, to be run after all operations added before.
+    pub fn then(mut self, op: impl AddToCommandBuffer + 'static) -> Self {
+        self.ops.push(Box::new(op));
+        self
+    }
+
+    /// Appends an already boxed `op`.
+    pub fn then_boxed(mut self, op: Box<dyn AddToCommandBuffer>) -> Self {
+        self.ops.push(op);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+impl From<Vec<Box<dyn AddToCommandBuffer>>> for Sequence {
+    fn from(ops: Vec<Box<dyn AddToCommandBuffer>>) -> Self {
+        Self { ops }
+    }
+}
+
+impl AddToCommandBuffer for Sequence {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        for op in &self.ops {
+            builder.run(op.as_ref())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::commandbuffer::CommandBuffer;
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::ops::{AddToCommandBuffer, CopyBuffer2Buffer, Dummy, FillBuffer, Sequence};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::{CommandBuilder, Queue};
+    use crate::resources::{Buffer, BufferInfo};
+
+    #[test]
+    fn sequence_len() {
+        let sequence = Sequence::new()
+            .then(Dummy::new())
+            .then_boxed(Box::new(Dummy::new()))
+            .then(|_: &mut CommandBuilder| Ok(()));
+
+        assert_eq!(sequence.len(), 3);
+        assert!(Sequence::new().is_empty());
+    }
+
+    #[test]
+    fn op_names() {
+        let boxed: Box<dyn AddToCommandBuffer> = Box::new(Dummy::new());
+
+        assert!(boxed.name().ends_with("::Dummy"));
+        assert!(Sequence::new().name().ends_with("::Sequence"));
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn fill_then_copy() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 2048, host_visible)?;
+
+        let buffer_info_src = BufferInfo::new().size(1024);
+        let buffer_info_dst = BufferInfo::new().size(1024).offset(1024);
+
+        let buffer_src = Buffer::new(&allocation, &buffer_info_src)?;
+        let buffer_dst = Buffer::new(&allocation, &buffer_info_dst)?;
+
+        let sequence = Sequence::new()
+            .then(FillBuffer::new(&buffer_src, 0x11223344))
+            .then(CopyBuffer2Buffer::new(&buffer_src, &buffer_dst, 1024));
+
+        queue.build_and_submit(&command_buffer, |x| x.run(&sequence))?;
+
+        let mut data = vec![0; 1024];
+        buffer_dst.download_into(&mut data)?;
+
+        assert!(data.chunks(4).all(|x| x == 0x11223344_u32.to_ne_bytes()));
+
+        Ok(())
+    }
+}
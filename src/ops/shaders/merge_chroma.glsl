@@ -0,0 +1,34 @@
+#version 450
+
+// Each invocation interleaves 4 samples of both chroma planes, so it writes whole words.
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+layout(std430, set = 0, binding = 0) readonly buffer _u {
+    uint u[];
+};
+
+layout(std430, set = 0, binding = 1) readonly buffer _v {
+    uint v[];
+};
+
+layout(std430, set = 0, binding = 2) buffer _uv {
+    uint uv[];
+};
+
+layout(push_constant) uniform Constants {
+    uint words;
+} constants;
+
+void main() {
+    uint i = gl_GlobalInvocationID.x;
+
+    if (i >= constants.words) {
+        return;
+    }
+
+    uint a = u[i];
+    uint b = v[i];
+
+    uv[2u * i] = (a & 0xFFu) | ((b & 0xFFu) << 8u) | ((a & 0xFF00u) << 8u) | ((b & 0xFF00u) << 16u);
+    uv[2u * i + 1u] = ((a >> 16u) & 0xFFu) | ((b >> 8u) & 0xFF00u) | ((a >> 8u) & 0xFF0000u) | (b & 0xFF000000u);
+}
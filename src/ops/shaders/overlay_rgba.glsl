@@ -0,0 +1,53 @@
+#version 450
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+layout(std430, set = 0, binding = 0) readonly buffer _overlay {
+    uint overlay[];
+};
+
+layout(std430, set = 0, binding = 1) buffer _frame {
+    uint frame[];
+};
+
+// Blends the overlay (straight alpha) over the frame with its top left corner at (x, y), which may lie outside the frame.
+layout(push_constant) uniform Constants {
+    uint overlay_width;
+    uint overlay_height;
+    uint frame_width;
+    uint frame_height;
+    int x;
+    int y;
+    float opacity;
+} constants;
+
+void main() {
+    uint ox = gl_GlobalInvocationID.x;
+    uint oy = gl_GlobalInvocationID.y;
+
+    if (ox >= constants.overlay_width || oy >= constants.overlay_height) {
+        return;
+    }
+
+    int fx = constants.x + int(ox);
+    int fy = constants.y + int(oy);
+
+    if (fx < 0 || fy < 0 || fx >= int(constants.frame_width) || fy >= int(constants.frame_height)) {
+        return;
+    }
+
+    uint index = uint(fy) * constants.frame_width + uint(fx);
+    vec4 src = unpackUnorm4x8(overlay[oy * constants.overlay_width + ox]);
+    vec4 dst = unpackUnorm4x8(frame[index]);
+
+    // Porter-Duff source over destination.
+    float src_alpha = src.a * constants.opacity;
+    float alpha = src_alpha + dst.a * (1.0 - src_alpha);
+    vec3 color = src.rgb * src_alpha + dst.rgb * dst.a * (1.0 - src_alpha);
+
+    if (alpha > 0.0) {
+        color = color / alpha;
+    }
+
+    frame[index] = packUnorm4x8(vec4(color, alpha));
+}
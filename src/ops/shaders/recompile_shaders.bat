@@ -0,0 +1,22 @@
+@echo off
+
+echo.
+echo Compiling kernels ...
+echo.
+
+set args=-fshader-stage=compute -O
+
+glslc %args% .\yuv_to_rgb.glsl -o .\compiled\yuv_to_rgb.spv
+glslc %args% .\rgba_to_nv12.glsl -o .\compiled\rgba_to_nv12.spv
+glslc %args% .\scale_rgba.glsl -o .\compiled\scale_rgba.spv
+glslc %args% .\split_chroma.glsl -o .\compiled\split_chroma.spv
+glslc %args% .\merge_chroma.glsl -o .\compiled\merge_chroma.spv
+glslc %args% .\tone_map.glsl -o .\compiled\tone_map.spv
+glslc %args% .\squared_error.glsl -o .\compiled\squared_error.spv
+glslc %args% .\overlay_rgba.glsl -o .\compiled\overlay_rgba.spv
+
+echo.
+echo Done.
+echo.
+
+pause
@@ -0,0 +1,71 @@
+#version 450
+
+// Each invocation converts 4x2 pixels, so it writes whole words of both planes.
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+layout(std430, set = 0, binding = 0) readonly buffer _rgba {
+    uint rgba[];
+};
+
+// Luma plane followed by interleaved chroma, both tightly packed.
+layout(std430, set = 0, binding = 1) buffer _nv12 {
+    uint nv12[];
+};
+
+layout(push_constant) uniform Constants {
+    uint width;
+    uint height;
+    float kr;
+    float kb;
+} constants;
+
+void main() {
+    uint bx = gl_GlobalInvocationID.x;
+    uint by = gl_GlobalInvocationID.y;
+
+    if (bx * 4u >= constants.width || by * 2u >= constants.height) {
+        return;
+    }
+
+    vec3 k = vec3(constants.kr, 1.0 - constants.kr - constants.kb, constants.kb);
+    uint words_per_row = constants.width / 4u;
+    uint chroma_word = 0u;
+
+    for (uint half_block = 0u; half_block < 2u; half_block++) {
+        vec3 sum = vec3(0.0);
+
+        for (uint dy = 0u; dy < 2u; dy++) {
+            uint y = by * 2u + dy;
+
+            for (uint dx = 0u; dx < 2u; dx++) {
+                uint x = bx * 4u + half_block * 2u + dx;
+                sum += unpackUnorm4x8(rgba[y * constants.width + x]).rgb;
+            }
+        }
+
+        vec3 rgb = sum / 4.0;
+        float l = dot(k, rgb);
+        float cb = (rgb.b - l) / (2.0 * (1.0 - k.b));
+        float cr = (rgb.r - l) / (2.0 * (1.0 - k.r));
+
+        uint cb8 = uint(clamp(128.0 + 224.0 * cb, 0.0, 255.0) + 0.5);
+        uint cr8 = uint(clamp(128.0 + 224.0 * cr, 0.0, 255.0) + 0.5);
+
+        chroma_word |= (cb8 | (cr8 << 8u)) << (half_block * 16u);
+    }
+
+    for (uint dy = 0u; dy < 2u; dy++) {
+        uint y = by * 2u + dy;
+        uint luma_word = 0u;
+
+        for (uint dx = 0u; dx < 4u; dx++) {
+            vec3 rgb = unpackUnorm4x8(rgba[y * constants.width + bx * 4u + dx]).rgb;
+            uint l8 = uint(clamp(16.0 + 219.0 * dot(k, rgb), 0.0, 255.0) + 0.5);
+            luma_word |= l8 << (dx * 8u);
+        }
+
+        nv12[y * words_per_row + bx] = luma_word;
+    }
+
+    nv12[constants.width * constants.height / 4u + by * words_per_row + bx] = chroma_word;
+}
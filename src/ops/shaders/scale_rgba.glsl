@@ -0,0 +1,63 @@
+#version 450
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+layout(std430, set = 0, binding = 0) readonly buffer _src {
+    uint src[];
+};
+
+layout(std430, set = 0, binding = 1) buffer _dst {
+    uint dst[];
+};
+
+// Scales the crop rect of the source into the view rect of the destination, filling everything else.
+layout(push_constant) uniform Constants {
+    uint src_width;
+    uint crop_x;
+    uint crop_y;
+    uint crop_width;
+    uint crop_height;
+    uint dst_width;
+    uint dst_height;
+    uint view_x;
+    uint view_y;
+    uint view_width;
+    uint view_height;
+    uint fill;
+} constants;
+
+vec4 texel(uint x, uint y) {
+    return unpackUnorm4x8(src[y * constants.src_width + x]);
+}
+
+void main() {
+    uint x = gl_GlobalInvocationID.x;
+    uint y = gl_GlobalInvocationID.y;
+
+    if (x >= constants.dst_width || y >= constants.dst_height) {
+        return;
+    }
+
+    uint view_end_x = constants.view_x + constants.view_width;
+    uint view_end_y = constants.view_y + constants.view_height;
+
+    if (x < constants.view_x || y < constants.view_y || x >= view_end_x || y >= view_end_y) {
+        dst[y * constants.dst_width + x] = constants.fill;
+        return;
+    }
+
+    // Sample at pixel centers, clamping at the edges of the crop rect.
+    vec2 crop_origin = vec2(constants.crop_x, constants.crop_y);
+    vec2 crop_last = crop_origin + vec2(constants.crop_width - 1u, constants.crop_height - 1u);
+    vec2 scale = vec2(constants.crop_width, constants.crop_height) / vec2(constants.view_width, constants.view_height);
+    vec2 position = clamp((vec2(x - constants.view_x, y - constants.view_y) + 0.5) * scale - 0.5 + crop_origin, crop_origin, crop_last);
+
+    uvec2 p0 = uvec2(floor(position));
+    uvec2 p1 = min(p0 + 1u, uvec2(crop_last));
+    vec2 f = position - vec2(p0);
+
+    vec4 top = mix(texel(p0.x, p0.y), texel(p1.x, p0.y), f.x);
+    vec4 bottom = mix(texel(p0.x, p1.y), texel(p1.x, p1.y), f.x);
+
+    dst[y * constants.dst_width + x] = packUnorm4x8(mix(top, bottom, f.y));
+}
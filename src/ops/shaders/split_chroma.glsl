@@ -0,0 +1,34 @@
+#version 450
+
+// Each invocation splits 4 interleaved chroma samples, so it writes whole words of both planes.
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+layout(std430, set = 0, binding = 0) readonly buffer _uv {
+    uint uv[];
+};
+
+layout(std430, set = 0, binding = 1) buffer _u {
+    uint u[];
+};
+
+layout(std430, set = 0, binding = 2) buffer _v {
+    uint v[];
+};
+
+layout(push_constant) uniform Constants {
+    uint words;
+} constants;
+
+void main() {
+    uint i = gl_GlobalInvocationID.x;
+
+    if (i >= constants.words) {
+        return;
+    }
+
+    uint a = uv[2u * i];
+    uint b = uv[2u * i + 1u];
+
+    u[i] = (a & 0xFFu) | ((a >> 8u) & 0xFF00u) | ((b & 0xFFu) << 16u) | ((b & 0xFF0000u) << 8u);
+    v[i] = ((a >> 8u) & 0xFFu) | ((a >> 16u) & 0xFF00u) | ((b & 0xFF00u) << 8u) | (b & 0xFF000000u);
+}
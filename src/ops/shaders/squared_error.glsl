@@ -0,0 +1,54 @@
+#version 450
+
+// Each invocation compares 4 bytes, each workgroup writes the sum of its squared differences.
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+layout(std430, set = 0, binding = 0) readonly buffer _a {
+    uint a[];
+};
+
+layout(std430, set = 0, binding = 1) readonly buffer _b {
+    uint b[];
+};
+
+// One sum per workgroup, at most 64 * 4 * 255^2, so it can't overflow.
+layout(std430, set = 0, binding = 2) buffer _sums {
+    uint sums[];
+};
+
+layout(push_constant) uniform Constants {
+    uint words;
+} constants;
+
+shared uint partial[64];
+
+void main() {
+    uint i = gl_GlobalInvocationID.x;
+    uint local = gl_LocalInvocationID.x;
+    uint sum = 0u;
+
+    if (i < constants.words) {
+        uint x = a[i];
+        uint y = b[i];
+
+        for (uint k = 0u; k < 4u; k++) {
+            int d = int((x >> (k * 8u)) & 0xFFu) - int((y >> (k * 8u)) & 0xFFu);
+            sum += uint(d * d);
+        }
+    }
+
+    partial[local] = sum;
+    barrier();
+
+    for (uint stride = 32u; stride > 0u; stride >>= 1u) {
+        if (local < stride) {
+            partial[local] += partial[local + stride];
+        }
+
+        barrier();
+    }
+
+    if (local == 0u) {
+        sums[gl_WorkGroupID.x] = partial[0];
+    }
+}
@@ -0,0 +1,139 @@
+#version 450
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+// P010 frame, luma plane followed by interleaved chroma, 10 bit in the upper bits of 16 bit samples.
+layout(std430, set = 0, binding = 0) readonly buffer _yuv {
+    uint yuv[];
+};
+
+layout(std430, set = 0, binding = 1) buffer _rgba {
+    uint rgba[];
+};
+
+layout(push_constant) uniform Constants {
+    uint width;
+    uint height;
+    uint full_range;
+    // 0 = PQ, 1 = HLG.
+    uint transfer;
+    // 0 = clip, 1 = Reinhard, 2 = Hable.
+    uint curve;
+    // Content (PQ) or display (HLG) peak, and SDR white, in nits.
+    float peak_luminance;
+    float white_luminance;
+} constants;
+
+float sample_at(uint i) {
+    return float(((yuv[i >> 1] >> ((i & 1u) * 16u)) & 0xFFFFu) >> 6u);
+}
+
+// Non-linear BT.2020 RGB to nits.
+vec3 pq_eotf(vec3 e) {
+    float m1 = 0.1593017578125;
+    float m2 = 78.84375;
+    float c1 = 0.8359375;
+    float c2 = 18.8515625;
+    float c3 = 18.6875;
+
+    vec3 p = pow(e, vec3(1.0 / m2));
+
+    return 10000.0 * pow(max(p - c1, vec3(0.0)) / (c2 - c3 * p), vec3(1.0 / m1));
+}
+
+// Non-linear BT.2020 RGB to nits, on a display of `peak_luminance`.
+vec3 hlg_eotf(vec3 e) {
+    float a = 0.17883277;
+    float b = 0.28466892;
+    float c = 0.55991073;
+
+    vec3 low = e * e / 3.0;
+    vec3 high = (exp((e - c) / a) + b) / 12.0;
+    vec3 scene = mix(low, high, step(vec3(0.5), e));
+
+    // OOTF with the system gamma for a 1000 nits display.
+    float luminance = dot(vec3(0.2627, 0.6780, 0.0593), scene);
+
+    return constants.peak_luminance * pow(max(luminance, 1e-6), 0.2) * scene;
+}
+
+vec3 hable(vec3 x) {
+    float a = 0.15;
+    float b = 0.50;
+    float c = 0.10;
+    float d = 0.20;
+    float e = 0.02;
+    float f = 0.30;
+
+    return ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f;
+}
+
+vec3 tone_map(vec3 x) {
+    // Peak relative to SDR white.
+    float peak = max(constants.peak_luminance / constants.white_luminance, 1.0);
+
+    if (constants.curve == 1u) {
+        return x * (1.0 + x / (peak * peak)) / (1.0 + x);
+    }
+
+    if (constants.curve == 2u) {
+        return hable(x) / hable(vec3(peak));
+    }
+
+    return x;
+}
+
+vec3 srgb_oetf(vec3 x) {
+    vec3 low = 12.92 * x;
+    vec3 high = 1.055 * pow(x, vec3(1.0 / 2.4)) - 0.055;
+
+    return mix(low, high, step(vec3(0.0031308), x));
+}
+
+void main() {
+    uint x = gl_GlobalInvocationID.x;
+    uint y = gl_GlobalInvocationID.y;
+
+    if (x >= constants.width || y >= constants.height) {
+        return;
+    }
+
+    uint luma = y * constants.width + x;
+    uint chroma = constants.width * constants.height + (y / 2u) * constants.width + (x / 2u) * 2u;
+
+    float l;
+    float cb;
+    float cr;
+
+    if (constants.full_range != 0u) {
+        l = sample_at(luma) / 1023.0;
+        cb = (sample_at(chroma) - 512.0) / 1023.0;
+        cr = (sample_at(chroma + 1u) - 512.0) / 1023.0;
+    } else {
+        l = (sample_at(luma) - 64.0) / 876.0;
+        cb = (sample_at(chroma) - 512.0) / 896.0;
+        cr = (sample_at(chroma + 1u) - 512.0) / 896.0;
+    }
+
+    // BT.2020 non-constant luminance.
+    float kr = 0.2627;
+    float kb = 0.0593;
+    float r = l + 2.0 * (1.0 - kr) * cr;
+    float b = l + 2.0 * (1.0 - kb) * cb;
+    float g = (l - kr * r - kb * b) / (1.0 - kr - kb);
+    vec3 e = clamp(vec3(r, g, b), 0.0, 1.0);
+
+    vec3 nits = constants.transfer == 1u ? hlg_eotf(e) : pq_eotf(e);
+    vec3 bt2020 = nits / constants.white_luminance;
+
+    // BT.2020 to BT.709 primaries, both linear.
+    vec3 bt709 = vec3(
+        dot(vec3(1.6605, -0.5876, -0.0728), bt2020),
+        dot(vec3(-0.1246, 1.1329, -0.0083), bt2020),
+        dot(vec3(-0.0182, -0.1006, 1.1187), bt2020)
+    );
+
+    vec3 sdr = clamp(tone_map(max(bt709, vec3(0.0))), 0.0, 1.0);
+
+    rgba[luma] = packUnorm4x8(vec4(srgb_oetf(sdr), 1.0));
+}
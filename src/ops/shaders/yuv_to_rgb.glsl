@@ -0,0 +1,68 @@
+#version 450
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+// Luma plane followed by interleaved chroma, both tightly packed, 8 bit (NV12) or 16 bit (P010) per sample.
+layout(std430, set = 0, binding = 0) readonly buffer _yuv {
+    uint yuv[];
+};
+
+layout(std430, set = 0, binding = 1) buffer _rgba {
+    uint rgba[];
+};
+
+layout(push_constant) uniform Constants {
+    uint width;
+    uint height;
+    uint p010;
+    uint full_range;
+    float kr;
+    float kb;
+} constants;
+
+// Sample `i` of the frame, P010 keeps its 10 bits in the upper bits of each 16 bit word.
+float sample_at(uint i) {
+    if (constants.p010 != 0u) {
+        return float(((yuv[i >> 1] >> ((i & 1u) * 16u)) & 0xFFFFu) >> 6u);
+    }
+
+    return float((yuv[i >> 2] >> ((i & 3u) * 8u)) & 0xFFu);
+}
+
+void main() {
+    uint x = gl_GlobalInvocationID.x;
+    uint y = gl_GlobalInvocationID.y;
+
+    if (x >= constants.width || y >= constants.height) {
+        return;
+    }
+
+    uint luma = y * constants.width + x;
+    uint chroma = constants.width * constants.height + (y / 2u) * constants.width + (x / 2u) * 2u;
+
+    // Scale of 8 bit code values, e.g., 4 for 10 bit.
+    float scale = constants.p010 != 0u ? 4.0 : 1.0;
+    float max_value = 256.0 * scale - 1.0;
+
+    float l;
+    float cb;
+    float cr;
+
+    if (constants.full_range != 0u) {
+        l = sample_at(luma) / max_value;
+        cb = (sample_at(chroma) - 128.0 * scale) / max_value;
+        cr = (sample_at(chroma + 1u) - 128.0 * scale) / max_value;
+    } else {
+        l = (sample_at(luma) - 16.0 * scale) / (219.0 * scale);
+        cb = (sample_at(chroma) - 128.0 * scale) / (224.0 * scale);
+        cr = (sample_at(chroma + 1u) - 128.0 * scale) / (224.0 * scale);
+    }
+
+    float kr = constants.kr;
+    float kb = constants.kb;
+    float r = l + 2.0 * (1.0 - kr) * cr;
+    float b = l + 2.0 * (1.0 - kb) * cb;
+    float g = (l - kr * r - kb * b) / (1.0 - kr - kb);
+
+    rgba[luma] = packUnorm4x8(vec4(clamp(vec3(r, g, b), 0.0, 1.0), 1.0));
+}
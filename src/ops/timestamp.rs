@@ -0,0 +1,125 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::ops::AddToCommandBuffer;
+use crate::queue::CommandBuilder;
+use crate::resources::{QueryPool, QueryPoolShared};
+use ash::vk::PipelineStageFlags2;
+use std::sync::Arc;
+
+/// Writes a GPU timestamp into a [`QueryPool`], once all previous commands passed `stage`.
+///
+/// By default that is when they completed, which is what measuring them needs. Fails on queue families without
+/// [`timestamp_valid_bits`](crate::QueueFamilyInfo::timestamp_valid_bits).
+pub struct WriteTimestamp {
+    query_pool: Arc<QueryPoolShared>,
+    query: u32,
+    stage: PipelineStageFlags2,
+}
+
+impl WriteTimestamp {
+    pub fn new(query_pool: &QueryPool, query: u32) -> Self {
+        Self {
+            query_pool: query_pool.shared(),
+            query,
+            stage: PipelineStageFlags2::ALL_COMMANDS,
+        }
+    }
+
+    /// Writes the timestamp once previous commands passed `stage`, instead of completing.
+    pub fn stage(mut self, stage: PipelineStageFlags2) -> Self {
+        self.stage = stage;
+        self
+    }
+}
+
+impl AddToCommandBuffer for WriteTimestamp {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        let native_device = self.query_pool.device().native();
+        let native_command_buffer = builder.native_command_buffer();
+        let native_query_pool = self.query_pool.native();
+        let queue_family_index = builder.queue_family_index();
+        let valid_bits = self
+            .query_pool
+            .device()
+            .physical_device()
+            .queue_family_infos()
+            .timestamp_valid_bits(queue_family_index);
+
+        self.query_pool.check_query(self.query)?;
+
+        if valid_bits == 0 {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "Queue family {queue_family_index} can't write timestamps."
+            ));
+        }
+
+        unsafe {
+            // Queries have to be reset before each use, doing it here spares users a separate operation.
+            native_device.cmd_reset_query_pool(native_command_buffer, native_query_pool, self.query, 1);
+            native_device.cmd_write_timestamp2(native_command_buffer, self.stage, native_query_pool, self.query);
+        }
+
+        self.query_pool.set_recorded(self.query, valid_bits);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::commandbuffer::CommandBuffer;
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::ops::{AddToCommandBuffer, FillBuffer, WriteTimestamp};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::Queue;
+    use crate::resources::{Buffer, BufferInfo, QueryPool};
+
+    #[test]
+    #[cfg(not(miri))]
+    fn time_fill_buffer() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 1024, host_visible)?;
+
+        let buffer_info = BufferInfo::new().size(1024);
+        let buffer = Buffer::new(&allocation, &buffer_info)?;
+        let queries = QueryPool::new(&device, 2)?;
+
+        assert_eq!(queries.ticks()?, vec![None, None]);
+
+        let fill_buffer = FillBuffer::new(&buffer, 0x11223344);
+        let start = WriteTimestamp::new(&queries, 0);
+        let end = WriteTimestamp::new(&queries, 1);
+        let out_of_range = WriteTimestamp::new(&queries, 2);
+
+        queue.build_and_submit(&command_buffer, |x| {
+            start.run_in(x)?;
+            fill_buffer.run_in(x)?;
+            end.run_in(x)?;
+            assert!(out_of_range.run_in(x).is_err());
+            Ok(())
+        })?;
+
+        assert!(queries.elapsed(0, 1)?.is_some());
+        assert!(queries.elapsed(0, 2).is_err());
+
+        Ok(())
+    }
+}
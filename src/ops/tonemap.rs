@@ -0,0 +1,257 @@
+use crate::device::Device;
+use crate::error::Error;
+use crate::ops::kernels::{check_extent, check_size, kernel, rgba_size, Spirv, TwoBuffers, TILE};
+use crate::ops::{AddToCommandBuffer, ColorRange, Compute, YuvFormat};
+use crate::queue::CommandBuilder;
+use crate::resources::Buffer;
+use ash::vk::Extent2D;
+
+static TONE_MAP: &Spirv<[u8]> = &Spirv(*include_bytes!("shaders/compiled/tone_map.spv"));
+
+/// How HDR content encodes light.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TransferFunction {
+    /// SMPTE ST 2084, absolute luminance up to 10000 nits.
+    #[default]
+    Pq,
+    /// ARIB STD-B67, relative to the display's peak.
+    Hlg,
+}
+
+impl TransferFunction {
+    /// The function for `transfer_characteristics` as signaled in the VUI (ITU-T H.273), if HDR.
+    pub fn from_transfer_characteristics(transfer_characteristics: u8) -> Option<Self> {
+        match transfer_characteristics {
+            16 => Some(Self::Pq),
+            18 => Some(Self::Hlg),
+            _ => None,
+        }
+    }
+}
+
+/// How luminance above SDR white is compressed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ToneCurve {
+    /// Clips everything brighter than white.
+    Clip,
+    /// Extended Reinhard, reaching white exactly at the peak.
+    #[default]
+    Reinhard,
+    /// Filmic curve from Uncharted 2, more contrast in the highlights.
+    Hable,
+}
+
+/// Configures a [`ToneMap`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneMapInfo {
+    transfer: TransferFunction,
+    curve: ToneCurve,
+    range: ColorRange,
+    peak_luminance: f32,
+    white_luminance: f32,
+}
+
+impl Default for ToneMapInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ToneMapInfo {
+    pub fn new() -> Self {
+        Self {
+            transfer: TransferFunction::Pq,
+            curve: ToneCurve::Reinhard,
+            range: ColorRange::Limited,
+            peak_luminance: 1000.0,
+            white_luminance: 203.0,
+        }
+    }
+
+    pub fn transfer(mut self, transfer: TransferFunction) -> Self {
+        self.transfer = transfer;
+        self
+    }
+
+    pub fn curve(mut self, curve: ToneCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    pub fn range(mut self, range: ColorRange) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Brightest content in nits for PQ, e.g., from mastering metadata, or the display peak HLG is rendered for.
+    pub fn peak_luminance(mut self, nits: f32) -> Self {
+        self.peak_luminance = nits;
+        self
+    }
+
+    /// Luminance in nits mapped to SDR white, 203 as in ITU-R BT.2408 by default.
+    pub fn white_luminance(mut self, nits: f32) -> Self {
+        self.white_luminance = nits;
+        self
+    }
+
+    pub fn get_transfer(&self) -> TransferFunction {
+        self.transfer
+    }
+
+    pub fn get_curve(&self) -> ToneCurve {
+        self.curve
+    }
+
+    pub fn get_range(&self) -> ColorRange {
+        self.range
+    }
+
+    pub fn get_peak_luminance(&self) -> f32 {
+        self.peak_luminance
+    }
+
+    pub fn get_white_luminance(&self) -> f32 {
+        self.white_luminance
+    }
+}
+
+/// Maps a 10 bit HDR frame (P010, BT.2020) to SDR RGBA8 (BT.709, sRGB), e.g., for playback on SDR displays.
+///
+/// Width must be a multiple of 4, height a multiple of 2.
+pub struct ToneMap {
+    compute: Compute<TwoBuffers>,
+}
+
+impl ToneMap {
+    pub fn new(device: &Device, p010: &Buffer, rgba: &Buffer, extent: Extent2D, info: &ToneMapInfo) -> Result<Self, Error> {
+        check_extent(extent, 4, 2)?;
+        check_size(p010, YuvFormat::P010.frame_size(extent), "p010")?;
+        check_size(rgba, rgba_size(extent), "rgba")?;
+
+        let transfer = match info.transfer {
+            TransferFunction::Pq => 0,
+            TransferFunction::Hlg => 1,
+        };
+
+        let curve = match info.curve {
+            ToneCurve::Clip => 0,
+            ToneCurve::Reinhard => 1,
+            ToneCurve::Hable => 2,
+        };
+
+        let constants = [
+            extent.width,
+            extent.height,
+            (info.range == ColorRange::Full) as u32,
+            transfer,
+            curve,
+            info.peak_luminance.to_bits(),
+            info.white_luminance.to_bits(),
+        ];
+        let groups = (extent.width.div_ceil(TILE), extent.height.div_ceil(TILE), 1);
+        let compute = kernel(device, TONE_MAP, &[p010, rgba], &constants, groups)?;
+
+        Ok(Self { compute })
+    }
+}
+
+impl AddToCommandBuffer for ToneMap {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        self.compute.run_in(builder)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::commandbuffer::CommandBuffer;
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::ops::{AddToCommandBuffer, ColorRange, ToneCurve, ToneMap, ToneMapInfo, TransferFunction};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::Queue;
+    use crate::resources::{Buffer, BufferInfo};
+    use ash::vk::Extent2D;
+
+    /// PQ code value of `nits`, at 10 bit full range.
+    fn pq(nits: f32) -> u16 {
+        let (m1, m2, c1, c2, c3) = (0.159_301_76, 78.84375, 0.8359375, 18.851_563, 18.6875);
+        let y = (nits / 10000.0).powf(m1);
+
+        (((c1 + c2 * y) / (1.0 + c3 * y)).powf(m2) * 1023.0).round() as u16
+    }
+
+    #[test]
+    fn transfer_functions() {
+        assert_eq!(TransferFunction::from_transfer_characteristics(16), Some(TransferFunction::Pq));
+        assert_eq!(TransferFunction::from_transfer_characteristics(18), Some(TransferFunction::Hlg));
+        assert_eq!(TransferFunction::from_transfer_characteristics(1), None);
+        assert_eq!(pq(0.0), 0);
+        assert_eq!(pq(10000.0), 1023);
+    }
+
+    #[test]
+    #[cfg(feature = "reflection")]
+    fn kernel_matches_parameters() -> Result<(), Error> {
+        use crate::ops::kernels::TwoBuffers;
+        use crate::ops::tonemap::TONE_MAP;
+        use crate::shader::{validate_bindings, ShaderParameterSet};
+
+        validate_bindings(&TONE_MAP.0, "main", &TwoBuffers::descriptor_types())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn tone_map_pq() -> Result<(), Error> {
+        let extent = Extent2D::default().width(64).height(32);
+        let p010_size = 64 * 32 * 3;
+        let rgba_size = 64 * 32 * 4;
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 3 * 8192, host_visible)?;
+        let p010 = Buffer::new(&allocation, &BufferInfo::new().size(p010_size).offset(0))?;
+        let clipped = Buffer::new(&allocation, &BufferInfo::new().size(rgba_size).offset(8192))?;
+        let reinhard = Buffer::new(&allocation, &BufferInfo::new().size(rgba_size).offset(16384))?;
+
+        // Gray at SDR white, no chroma.
+        let mut frame = (pq(203.0) << 6).to_le_bytes().repeat(64 * 32);
+        frame.extend((512u16 << 6).to_le_bytes().repeat(64 * 16));
+        p010.upload(&frame)?;
+
+        let info = ToneMapInfo::new().range(ColorRange::Full);
+        let clip = ToneMap::new(&device, &p010, &clipped, extent, &info.curve(ToneCurve::Clip))?;
+        let compress = ToneMap::new(&device, &p010, &reinhard, extent, &info.curve(ToneCurve::Reinhard))?;
+
+        queue.build_and_submit(&command_buffer, |x| {
+            clip.run_in(x)?;
+            compress.run_in(x)
+        })?;
+
+        let mut pixels = vec![0u8; rgba_size as usize];
+
+        clipped.download_into(&mut pixels)?;
+        assert!(pixels.chunks(4).all(|x| x[0] >= 250 && x[0] == x[1] && x[1] == x[2]));
+
+        // Compressed to leave room for highlights.
+        reinhard.download_into(&mut pixels)?;
+        assert!(pixels.chunks(4).all(|x| x[0] > 128 && x[0] < 250));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::ops::{Access, AddToCommandBuffer};
+use crate::queue::CommandBuilder;
+use crate::resources::{Buffer, BufferShared};
+use std::sync::Arc;
+
+/// Largest update `vkCmdUpdateBuffer` accepts, in bytes.
+const MAX_UPDATE_SIZE: usize = 65536;
+
+/// Writes a small block of data into a buffer, stored inline in the command buffer.
+///
+/// Unlike [`Buffer::upload`] this needs no host visible memory, e.g., to update per-frame constants of a
+/// [`Compute`](crate::ops::Compute) operation in device local memory. Data and offset must be multiples of 4 bytes,
+/// and data must not be empty and at most 64 KiB.
+pub struct UpdateBuffer {
+    buffer: Arc<BufferShared>,
+    data: Vec<u8>,
+    offset: u64,
+}
+
+impl UpdateBuffer {
+    pub fn new(buffer: &Buffer, data: &[u8]) -> Self {
+        Self {
+            buffer: buffer.shared(),
+            data: data.to_vec(),
+            offset: 0,
+        }
+    }
+
+    /// Writes the data at `offset` bytes into the buffer instead of its start.
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+impl AddToCommandBuffer for UpdateBuffer {
+    fn run_in(&self, builder: &mut CommandBuilder) -> Result<(), Error> {
+        let native_device = self.buffer.device().native();
+        let native_buffer = self.buffer.native();
+        let native_command_buffer = builder.native_command_buffer();
+        let size = self.data.len() as u64;
+
+        if self.data.is_empty() || self.data.len() > MAX_UPDATE_SIZE || !self.data.len().is_multiple_of(4) || !self.offset.is_multiple_of(4)
+        {
+            return Err(error!(
+                Variant::InvalidUpdateSize,
+                "Updates must be non-empty multiples of 4 bytes (and at most {MAX_UPDATE_SIZE}), got {size} bytes at offset {}.",
+                self.offset
+            ));
+        }
+
+        let in_bounds = self.offset.checked_add(size).is_some_and(|x| x <= self.buffer.size());
+
+        if !in_bounds {
+            return Err(error!(Variant::BufferTooSmall, "Update does not fit into buffer."));
+        }
+
+        builder.access_buffer(&self.buffer, Access::TRANSFER_WRITE);
+        builder.record_barriers();
+
+        unsafe {
+            native_device.cmd_update_buffer(native_command_buffer, native_buffer, self.offset, &self.data);
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::allocation::Allocation;
+    use crate::commandbuffer::CommandBuffer;
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::ops::{AddToCommandBuffer, UpdateBuffer};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::queue::Queue;
+    use crate::resources::{Buffer, BufferInfo};
+
+    #[test]
+    #[cfg(not(miri))]
+    fn update_buffer() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let compute_queue = physical_device
+            .queue_family_infos()
+            .any_compute()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, compute_queue, 0)?;
+        let command_buffer = CommandBuffer::new(&device, compute_queue)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 1024, host_visible)?;
+
+        let buffer_info = BufferInfo::new().size(1024);
+        let buffer = Buffer::new(&allocation, &buffer_info)?;
+
+        let update = UpdateBuffer::new(&buffer, &[1, 2, 3, 4, 5, 6, 7, 8]).offset(16);
+        let update_unaligned = UpdateBuffer::new(&buffer, &[1, 2, 3]);
+        let update_empty = UpdateBuffer::new(&buffer, &[]);
+        let update_overflowing = UpdateBuffer::new(&buffer, &[1, 2, 3, 4]).offset(u64::MAX - 3);
+
+        queue.build_and_submit(&command_buffer, |x| {
+            update.run_in(x)?;
+            assert!(update_unaligned.run_in(x).is_err());
+            assert!(update_empty
+                .run_in(x)
+                .is_err_and(|e| matches!(e.variant(), Variant::InvalidUpdateSize)));
+            assert!(update_overflowing
+                .run_in(x)
+                .is_err_and(|e| matches!(e.variant(), Variant::BufferTooSmall)));
+            Ok(())
+        })?;
+
+        let mut data = vec![0; 1024];
+        buffer.download_into(&mut data)?;
+
+        assert_eq!(data[16..24], [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        Ok(())
+    }
+}
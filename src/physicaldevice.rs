@@ -2,20 +2,83 @@ use crate::allocation::MemoryTypeIndex;
 use crate::error;
 use crate::error::{Error, Variant};
 use crate::instance::{Instance, InstanceShared};
-use ash::vk::{MemoryPropertyFlags, PhysicalDeviceMemoryProperties, QueueFlags};
+use crate::resources::{ImageInfo, MemoryRequirements};
+use ash::vk::{
+    BufferUsageFlags, Extent3D, ExternalBufferProperties, ExternalImageFormatProperties, ExternalMemoryHandleTypeFlags,
+    ExternalMemoryProperties, ImageFormatProperties2, MemoryHeap, MemoryHeapFlags, MemoryPropertyFlags, PhysicalDeviceExternalBufferInfo,
+    PhysicalDeviceExternalImageFormatInfo, PhysicalDeviceIDProperties, PhysicalDeviceImageFormatInfo2,
+    PhysicalDeviceMemoryBudgetPropertiesEXT, PhysicalDeviceMemoryProperties, PhysicalDeviceMemoryProperties2, PhysicalDeviceProperties2,
+    PhysicalDeviceType, QueueFamilyProperties, QueueFamilyProperties2, QueueFamilyVideoPropertiesKHR, QueueFlags,
+    VideoCodecOperationFlagsKHR,
+};
+use std::ffi::CStr;
 use std::sync::Arc;
 
+/// Properties of a single queue family, see [`QueueFamilyInfos::iter`].
+#[derive(Debug, Clone, Copy)]
+pub struct QueueFamilyInfo {
+    index: u32,
+    properties: QueueFamilyProperties,
+    codec_operations: VideoCodecOperationFlagsKHR,
+}
+
+impl QueueFamilyInfo {
+    /// The family index, e.g., for [`Queue::new`](crate::Queue::new).
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn flags(&self) -> QueueFlags {
+        self.properties.queue_flags
+    }
+
+    /// Queues devices may create in this family.
+    pub fn queue_count(&self) -> u32 {
+        self.properties.queue_count
+    }
+
+    /// Meaningful bits of timestamps written on this family, zero if it can't write timestamps.
+    pub fn timestamp_valid_bits(&self) -> u32 {
+        self.properties.timestamp_valid_bits
+    }
+
+    /// Granularity of image copies on this family, `(0, 0, 0)` if only whole mip levels can be copied.
+    pub fn min_image_transfer_granularity(&self) -> Extent3D {
+        self.properties.min_image_transfer_granularity
+    }
+
+    /// Video codec operations this family supports, see [`QueueFamilyInfos::codec_operations`].
+    pub fn codec_operations(&self) -> VideoCodecOperationFlagsKHR {
+        self.codec_operations
+    }
+}
+
 /// Provides logical information about vulkan queue families.
 pub struct QueueFamilyInfos {
     queue_compute: Option<u32>,
     queue_decode: Option<u32>,
+    queue_encode: Option<u32>,
+    queue_transfer: Option<u32>,
+    queue_graphics: Option<u32>,
     available_queues: Vec<u32>,
+    properties: Vec<QueueFamilyProperties>,
+    codec_operations: Vec<VideoCodecOperationFlagsKHR>,
 }
 
 impl QueueFamilyInfos {
     unsafe fn new(instance: ash::Instance, physical_device: ash::vk::PhysicalDevice) -> Self {
         unsafe {
-            let queue_family_properties = instance.get_physical_device_queue_family_properties(physical_device);
+            let count = instance.get_physical_device_queue_family_properties2_len(physical_device);
+            let mut video_properties = vec![QueueFamilyVideoPropertiesKHR::default(); count];
+            let mut properties = video_properties
+                .iter_mut()
+                .map(|x| QueueFamilyProperties2::default().push_next(x))
+                .collect::<Vec<_>>();
+
+            instance.get_physical_device_queue_family_properties2(physical_device, &mut properties);
+
+            let queue_family_properties = properties.iter().map(|x| x.queue_family_properties).collect::<Vec<_>>();
+            let codec_operations = video_properties.iter().map(|x| x.video_codec_operations).collect();
 
             let queue_compute = queue_family_properties
                 .iter()
@@ -29,20 +92,52 @@ impl QueueFamilyInfos {
                 .find(|x| x.1.queue_flags.contains(QueueFlags::VIDEO_DECODE_KHR))
                 .map(|x| x.0 as u32);
 
-            let mut available_queues = Vec::with_capacity(2);
+            let queue_encode = queue_family_properties
+                .iter()
+                .enumerate()
+                .find(|x| x.1.queue_flags.contains(QueueFlags::VIDEO_ENCODE_KHR))
+                .map(|x| x.0 as u32);
 
-            if let Some(x) = queue_compute {
-                available_queues.push(x)
-            }
+            // Prefer dedicated transfer families, they usually map to DMA engines running alongside everything else.
+            let queue_transfer = queue_family_properties
+                .iter()
+                .enumerate()
+                .find(|x| x.1.queue_flags & (QueueFlags::TRANSFER | QueueFlags::GRAPHICS | QueueFlags::COMPUTE) == QueueFlags::TRANSFER)
+                .or_else(|| {
+                    // Graphics and compute families support transfers even if they don't say so.
+                    queue_family_properties.iter().enumerate().find(|x| {
+                        x.1.queue_flags
+                            .intersects(QueueFlags::TRANSFER | QueueFlags::GRAPHICS | QueueFlags::COMPUTE)
+                    })
+                })
+                .map(|x| x.0 as u32);
+
+            let queue_graphics = queue_family_properties
+                .iter()
+                .enumerate()
+                .find(|x| x.1.queue_flags.contains(QueueFlags::GRAPHICS))
+                .map(|x| x.0 as u32);
 
-            if let Some(x) = queue_decode {
-                available_queues.push(x)
+            let mut available_queues = Vec::with_capacity(5);
+
+            for x in [queue_compute, queue_decode, queue_encode, queue_transfer, queue_graphics]
+                .into_iter()
+                .flatten()
+            {
+                if !available_queues.contains(&x) {
+                    available_queues.push(x);
+                }
             }
 
             Self {
                 queue_compute,
                 queue_decode,
+                queue_encode,
+                queue_transfer,
+                queue_graphics,
                 available_queues,
+                properties: queue_family_properties,
+                codec_operations,
             }
         }
     }
@@ -57,11 +152,86 @@ impl QueueFamilyInfos {
     pub fn any_decode(&self) -> Option<u32> {
         self.queue_decode
     }
+
+    pub fn any_encode(&self) -> Option<u32> {
+        self.queue_encode
+    }
+
+    /// A family for copies, a dedicated transfer family if there is one, e.g., to read back frames while decoding the next.
+    pub fn any_transfer(&self) -> Option<u32> {
+        self.queue_transfer
+    }
+
+    pub fn any_graphics(&self) -> Option<u32> {
+        self.queue_graphics
+    }
+
+    /// All queue families of the device, e.g., to pick families by criteria the `any_*` methods don't know about.
+    pub fn iter(&self) -> impl Iterator<Item = QueueFamilyInfo> + '_ {
+        self.properties
+            .iter()
+            .zip(&self.codec_operations)
+            .enumerate()
+            .map(|(index, (properties, codec_operations))| QueueFamilyInfo {
+                index: index as u32,
+                properties: *properties,
+                codec_operations: *codec_operations,
+            })
+    }
+
+    /// Queues `family` has, zero if it doesn't exist.
+    pub(crate) fn queue_count(&self, family: u32) -> u32 {
+        self.properties.get(family as usize).map_or(0, |x| x.queue_count)
+    }
+
+    /// Meaningful bits of timestamps written on `family`, zero if it can't write them (or doesn't exist).
+    pub(crate) fn timestamp_valid_bits(&self, family: u32) -> u32 {
+        self.properties.get(family as usize).map_or(0, |x| x.timestamp_valid_bits)
+    }
+
+    /// Video codec operations queues of `family` support, empty for families without video support (or that don't exist).
+    pub fn codec_operations(&self, family: u32) -> VideoCodecOperationFlagsKHR {
+        self.codec_operations.get(family as usize).copied().unwrap_or_default()
+    }
+
+    /// The first queue family supporting `codec`, e.g., [`VideoCodecOperationFlagsKHR::DECODE_AV1`].
+    ///
+    /// Unlike [`Self::any_decode`] this won't return a family that can decode video, just not this codec.
+    pub fn family_for(&self, codec: VideoCodecOperationFlagsKHR) -> Option<u32> {
+        self.codec_operations.iter().position(|x| x.contains(codec)).map(|x| x as u32)
+    }
+}
+
+/// Memory use of a heap, see [`HeapInfos::budget`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeapBudget {
+    usage: u64,
+    budget: u64,
+}
+
+impl HeapBudget {
+    /// Bytes currently allocated from the heap by this process.
+    pub fn usage(&self) -> u64 {
+        self.usage
+    }
+
+    /// Bytes this process can allocate from the heap without problems, including [`Self::usage`].
+    pub fn budget(&self) -> u64 {
+        self.budget
+    }
+
+    /// Bytes left before exceeding the budget.
+    pub fn available(&self) -> u64 {
+        self.budget.saturating_sub(self.usage)
+    }
 }
 
 /// Provides logical information about Vulkan memory heaps.
 pub struct HeapInfos {
     memory_properties: PhysicalDeviceMemoryProperties,
+    native_instance: ash::Instance,
+    native_physical_device: ash::vk::PhysicalDevice,
+    memory_budget: bool,
 }
 
 impl HeapInfos {
@@ -69,32 +239,124 @@ impl HeapInfos {
         unsafe {
             let memory_properties = instance.get_physical_device_memory_properties(physical_device);
 
-            Self { memory_properties }
+            // Without a list of extensions we can't tell, so we won't ask for budgets either.
+            let memory_budget = instance
+                .enumerate_device_extension_properties(physical_device)
+                .unwrap_or_default()
+                .iter()
+                .any(|x| x.extension_name_as_c_str() == Ok(c"VK_EXT_memory_budget"));
+
+            Self {
+                memory_properties,
+                native_instance: instance,
+                native_physical_device: physical_device,
+                memory_budget,
+            }
         }
     }
 
-    pub fn any_host_visible(&self) -> Option<MemoryTypeIndex> {
-        for i in 0..self.memory_properties.memory_type_count as usize {
-            let memory_type = self.memory_properties.memory_types[i];
+    /// Heap memory of `type_index` is allocated from.
+    pub(crate) fn heap_index(&self, type_index: MemoryTypeIndex) -> u32 {
+        self.memory_properties.memory_types[type_index.index() as usize].heap_index
+    }
 
-            if memory_type.property_flags.contains(MemoryPropertyFlags::HOST_VISIBLE) {
-                return Some(MemoryTypeIndex::new(i as u32));
-            }
-        }
+    /// Properties of memory type `type_index`, e.g., if it's [`MemoryPropertyFlags::HOST_COHERENT`], empty if it doesn't exist.
+    pub fn type_flags(&self, type_index: MemoryTypeIndex) -> MemoryPropertyFlags {
+        self.memory_properties.memory_types[..self.memory_properties.memory_type_count as usize]
+            .get(type_index.index() as usize)
+            .map_or(MemoryPropertyFlags::empty(), |x| x.property_flags)
+    }
 
-        None
+    pub fn heap_count(&self) -> u32 {
+        self.memory_properties.memory_heap_count
     }
 
-    pub fn any_device_local(&self) -> Option<MemoryTypeIndex> {
-        for i in 0..self.memory_properties.memory_type_count as usize {
-            let memory_type = self.memory_properties.memory_types[i];
+    /// Size of heap `heap` in bytes, zero if it doesn't exist.
+    pub fn heap_size(&self, heap: u32) -> u64 {
+        self.heap(heap).map_or(0, |x| x.size)
+    }
 
-            if memory_type.property_flags.contains(MemoryPropertyFlags::DEVICE_LOCAL) {
-                return Some(MemoryTypeIndex::new(i as u32));
-            }
+    /// Flags of heap `heap`, e.g., [`MemoryHeapFlags::DEVICE_LOCAL`], empty if it doesn't exist.
+    pub fn heap_flags(&self, heap: u32) -> MemoryHeapFlags {
+        self.heap(heap).map_or(MemoryHeapFlags::empty(), |x| x.flags)
+    }
+
+    /// If [`Self::budget`] is available, i.e., if the device supports `VK_EXT_memory_budget`.
+    pub fn has_budget(&self) -> bool {
+        self.memory_budget
+    }
+
+    /// Current usage and budget of heap `heap`, across all processes, `None` without `VK_EXT_memory_budget`.
+    ///
+    /// Budgets change as other applications allocate memory, so this asks the driver on each call. Allocating more than
+    /// [`HeapBudget::available`] will likely fail, or degrade performance by paging memory out.
+    pub fn budget(&self, heap: u32) -> Option<HeapBudget> {
+        if !self.memory_budget || heap >= self.heap_count() {
+            return None;
         }
 
-        None
+        let mut budget_properties = PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let mut properties = PhysicalDeviceMemoryProperties2::default().push_next(&mut budget_properties);
+
+        // SAFETY: Should be safe as native instance and physical device are valid while we are, and the extension is supported.
+        unsafe {
+            self.native_instance
+                .get_physical_device_memory_properties2(self.native_physical_device, &mut properties)
+        };
+
+        Some(HeapBudget {
+            usage: budget_properties.heap_usage[heap as usize],
+            budget: budget_properties.heap_budget[heap as usize],
+        })
+    }
+
+    fn heap(&self, heap: u32) -> Option<MemoryHeap> {
+        (heap < self.heap_count()).then(|| self.memory_properties.memory_heaps[heap as usize])
+    }
+
+    /// A memory type permitted by `memory_type_bits` with all `required` flags, and as many `preferred` flags as possible.
+    ///
+    /// Use [`MemoryRequirements::memory_type_bits`] for the memory a resource can be bound to, or `u32::MAX` for any
+    /// type. Among equally good types the first is returned, which Vulkan orders by performance.
+    pub fn find_type(
+        &self,
+        memory_type_bits: u32,
+        required: MemoryPropertyFlags,
+        preferred: MemoryPropertyFlags,
+    ) -> Option<MemoryTypeIndex> {
+        let memory_types = &self.memory_properties.memory_types[..self.memory_properties.memory_type_count as usize];
+
+        memory_types
+            .iter()
+            .enumerate()
+            .filter(|(i, x)| memory_type_bits & (1 << i) != 0 && x.property_flags.contains(required))
+            .min_by_key(|(_, x)| ((x.property_flags & preferred) ^ preferred).as_raw().count_ones())
+            .map(|(i, _)| MemoryTypeIndex::new(i as u32))
+    }
+
+    pub fn any_host_visible(&self) -> Option<MemoryTypeIndex> {
+        self.find_type(u32::MAX, MemoryPropertyFlags::HOST_VISIBLE, MemoryPropertyFlags::empty())
+    }
+
+    /// A host visible memory type suitable for a resource with `requirements`, e.g., a linear image to be mapped.
+    pub fn any_host_visible_for(&self, requirements: &MemoryRequirements) -> Option<MemoryTypeIndex> {
+        self.find_type(
+            requirements.memory_type_bits(),
+            MemoryPropertyFlags::HOST_VISIBLE,
+            MemoryPropertyFlags::empty(),
+        )
+    }
+
+    /// A host visible memory type, preferably host cached, e.g., for buffers the host reads back.
+    ///
+    /// Reading uncached (write-combined) memory from the host is very slow on many devices, but if there is no cached
+    /// type this returns an uncached one rather than none.
+    pub fn any_host_cached(&self) -> Option<MemoryTypeIndex> {
+        self.find_type(u32::MAX, MemoryPropertyFlags::HOST_VISIBLE, MemoryPropertyFlags::HOST_CACHED)
+    }
+
+    pub fn any_device_local(&self) -> Option<MemoryTypeIndex> {
+        self.find_type(u32::MAX, MemoryPropertyFlags::DEVICE_LOCAL, MemoryPropertyFlags::empty())
     }
 }
 
@@ -103,25 +365,75 @@ pub(crate) struct PhysicalDeviceShared {
     shared_instance: Arc<InstanceShared>,
     queue_family_infos: QueueFamilyInfos,
     heap_infos: HeapInfos,
+    device_type: PhysicalDeviceType,
+    buffer_image_granularity: u64,
+    non_coherent_atom_size: u64,
+    timestamp_period: f32,
+    device_uuid: [u8; 16],
+    driver_uuid: [u8; 16],
 }
 
 impl PhysicalDeviceShared {
     pub fn new_any(shared_instance: Arc<InstanceShared>) -> Result<Self, Error> {
         let native_instance = shared_instance.native();
 
+        // SAFETY: Should be safe as native instance is valid.
+        let mut physical_devices = unsafe { native_instance.enumerate_physical_devices()? };
+        let native_physical_device = physical_devices.pop().ok_or_else(|| error!(Variant::NoVideoDevice))?;
+
+        // SAFETY: Should be safe as the physical device was just enumerated from this instance.
+        Ok(unsafe { Self::from_native(shared_instance, native_physical_device) })
+    }
+
+    /// The device best suited for `codec`, preferring discrete over integrated over other GPUs, in enumeration order otherwise.
+    pub fn new_best_for(shared_instance: Arc<InstanceShared>, codec: VideoCodecOperationFlagsKHR) -> Result<Self, Error> {
+        let native_instance = shared_instance.native();
+
+        // SAFETY: Should be safe as native instance is valid.
+        let physical_devices = unsafe { native_instance.enumerate_physical_devices()? };
+        let mut candidates = Vec::with_capacity(physical_devices.len());
+
+        for native_physical_device in physical_devices {
+            // SAFETY: Should be safe as the physical device was just enumerated from this instance.
+            let candidate = unsafe { Self::from_native(shared_instance.clone(), native_physical_device) };
+
+            if candidate.supports_codec(codec)? {
+                candidates.push(candidate);
+            }
+        }
+
+        candidates
+            .into_iter()
+            .min_by_key(|x| device_type_rank(x.device_type))
+            .ok_or_else(|| error!(Variant::NoVideoDevice, "No device supports {codec:?}."))
+    }
+
+    /// # Safety
+    ///
+    /// The physical device must have been enumerated from `shared_instance`.
+    pub unsafe fn from_native(shared_instance: Arc<InstanceShared>, native_physical_device: ash::vk::PhysicalDevice) -> Self {
+        let native_instance = shared_instance.native();
+
         unsafe {
-            // SAFETY: Should be safe as native instance is valid.
-            let mut physical_devices = native_instance.enumerate_physical_devices()?;
-            let native_physical_device = physical_devices.pop().ok_or_else(|| error!(Variant::NoVideoDevice))?;
             let queue_family_infos = QueueFamilyInfos::new(native_instance.clone(), native_physical_device);
             let heap_infos = HeapInfos::new(native_instance.clone(), native_physical_device);
+            let mut id_properties = PhysicalDeviceIDProperties::default();
+            let mut properties = PhysicalDeviceProperties2::default().push_next(&mut id_properties);
 
-            Ok(Self {
+            native_instance.get_physical_device_properties2(native_physical_device, &mut properties);
+
+            Self {
                 native_physical_device,
                 shared_instance,
                 queue_family_infos,
                 heap_infos,
-            })
+                device_type: properties.properties.device_type,
+                buffer_image_granularity: properties.properties.limits.buffer_image_granularity,
+                non_coherent_atom_size: properties.properties.limits.non_coherent_atom_size,
+                timestamp_period: properties.properties.limits.timestamp_period,
+                device_uuid: id_properties.device_uuid,
+                driver_uuid: id_properties.driver_uuid,
+            }
         }
     }
 
@@ -140,6 +452,126 @@ impl PhysicalDeviceShared {
     pub fn heap_infos(&self) -> &HeapInfos {
         &self.heap_infos
     }
+
+    pub fn device_type(&self) -> PhysicalDeviceType {
+        self.device_type
+    }
+
+    pub fn timestamp_period(&self) -> f32 {
+        self.timestamp_period
+    }
+
+    /// Distance linear and optimal resources in the same memory must keep to not alias.
+    pub fn buffer_image_granularity(&self) -> u64 {
+        self.buffer_image_granularity
+    }
+
+    /// Granularity of ranges of non-coherent memory to be flushed or invalidated.
+    pub fn non_coherent_atom_size(&self) -> u64 {
+        self.non_coherent_atom_size
+    }
+
+    /// If the device has the extension and a queue family for `codec`, i.e., if we can create video sessions for it.
+    pub fn supports_codec(&self, codec: VideoCodecOperationFlagsKHR) -> Result<bool, Error> {
+        let Some(extension) = codec_extension(codec) else {
+            return Ok(false);
+        };
+
+        let queue_family = self.queue_family_infos.family_for(codec);
+
+        // SAFETY: Should be safe as native instance and physical device are valid.
+        let available_extensions = unsafe {
+            self.shared_instance
+                .native()
+                .enumerate_device_extension_properties(self.native_physical_device)?
+        };
+
+        let has_extension = available_extensions.iter().any(|x| x.extension_name_as_c_str() == Ok(extension));
+
+        Ok(has_extension && queue_family.is_some())
+    }
+
+    pub fn device_uuid(&self) -> [u8; 16] {
+        self.device_uuid
+    }
+
+    pub fn driver_uuid(&self) -> [u8; 16] {
+        self.driver_uuid
+    }
+
+    pub fn external_buffer_properties(
+        &self,
+        usage: BufferUsageFlags,
+        handle_type: ExternalMemoryHandleTypeFlags,
+    ) -> ExternalMemoryProperties {
+        let native_instance = self.shared_instance.native();
+        let info = PhysicalDeviceExternalBufferInfo::default().usage(usage).handle_type(handle_type);
+        let mut properties = ExternalBufferProperties::default();
+
+        // SAFETY: Should be safe as native instance and physical device are valid.
+        unsafe { native_instance.get_physical_device_external_buffer_properties(self.native_physical_device, &info, &mut properties) };
+
+        properties.external_memory_properties
+    }
+
+    pub fn external_image_properties(
+        &self,
+        image_info: &ImageInfo,
+        handle_type: ExternalMemoryHandleTypeFlags,
+    ) -> Result<ExternalMemoryProperties, Error> {
+        let native_instance = self.shared_instance.native();
+        let mut external_info = PhysicalDeviceExternalImageFormatInfo::default().handle_type(handle_type);
+        let info = PhysicalDeviceImageFormatInfo2::default()
+            .format(image_info.get_format())
+            .ty(image_info.get_image_type())
+            .tiling(image_info.get_tiling())
+            .usage(image_info.get_usage())
+            .push_next(&mut external_info);
+
+        let mut external_properties = ExternalImageFormatProperties::default();
+        let mut properties = ImageFormatProperties2::default().push_next(&mut external_properties);
+
+        // SAFETY: Should be safe as native instance and physical device are valid.
+        let result =
+            unsafe { native_instance.get_physical_device_image_format_properties2(self.native_physical_device, &info, &mut properties) };
+
+        // Unsupported combinations are not an error, they just can't be shared.
+        match result {
+            Ok(()) => Ok(external_properties.external_memory_properties),
+            Err(ash::vk::Result::ERROR_FORMAT_NOT_SUPPORTED) => Ok(ExternalMemoryProperties::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// All decode operations we know of.
+const VIDEO_DECODE_OPERATIONS: VideoCodecOperationFlagsKHR = VideoCodecOperationFlagsKHR::from_raw(
+    VideoCodecOperationFlagsKHR::DECODE_H264.as_raw()
+        | VideoCodecOperationFlagsKHR::DECODE_H265.as_raw()
+        | VideoCodecOperationFlagsKHR::DECODE_AV1.as_raw(),
+);
+
+/// Device extension needed for `codec`, `None` for codecs we don't know.
+fn codec_extension(codec: VideoCodecOperationFlagsKHR) -> Option<&'static CStr> {
+    match codec {
+        VideoCodecOperationFlagsKHR::DECODE_H264 => Some(c"VK_KHR_video_decode_h264"),
+        VideoCodecOperationFlagsKHR::DECODE_H265 => Some(c"VK_KHR_video_decode_h265"),
+        VideoCodecOperationFlagsKHR::DECODE_AV1 => Some(c"VK_KHR_video_decode_av1"),
+        VideoCodecOperationFlagsKHR::ENCODE_H264 => Some(c"VK_KHR_video_encode_h264"),
+        VideoCodecOperationFlagsKHR::ENCODE_H265 => Some(c"VK_KHR_video_encode_h265"),
+        _ => None,
+    }
+}
+
+/// Lower is better, dedicated hardware usually has the more capable (and less contended) video engines.
+fn device_type_rank(device_type: PhysicalDeviceType) -> u8 {
+    match device_type {
+        PhysicalDeviceType::DISCRETE_GPU => 0,
+        PhysicalDeviceType::INTEGRATED_GPU => 1,
+        PhysicalDeviceType::VIRTUAL_GPU => 2,
+        PhysicalDeviceType::CPU => 4,
+        _ => 3,
+    }
 }
 
 /// Some GPU in your system.
@@ -154,23 +586,209 @@ impl PhysicalDevice {
         Ok(Self { shared: Arc::new(shared) })
     }
 
+    /// Wraps a physical device enumerated from `instance`, e.g., the one an existing device was created on, see [`Device::from_raw`](crate::Device::from_raw).
+    ///
+    /// # Safety
+    ///
+    /// The physical device must have been enumerated from `instance`.
+    pub unsafe fn from_raw(instance: &Instance, physical_device: ash::vk::PhysicalDevice) -> Self {
+        // SAFETY: Forwarded to our caller.
+        let shared = unsafe { PhysicalDeviceShared::from_native(instance.shared(), physical_device) };
+
+        Self { shared: Arc::new(shared) }
+    }
+
+    /// The device best suited to decode `codec`, e.g., [`VideoCodecOperationFlagsKHR::DECODE_H264`].
+    ///
+    /// Only considers devices that support `codec` at all, and of those prefers discrete GPUs, then integrated ones, then
+    /// anything else. Unlike [`Self::new_any`] this won't pick a device that can't decode the stream.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Variant::NoVideoDevice`] if no device supports `codec`.
+    pub fn best_for_decode(instance: &Instance, codec: VideoCodecOperationFlagsKHR) -> Result<Self, Error> {
+        if !codec.intersects(VIDEO_DECODE_OPERATIONS) {
+            return Err(error!(Variant::FeatureNotSupported, "{codec:?} is not a decode operation."));
+        }
+
+        let shared = PhysicalDeviceShared::new_best_for(instance.shared(), codec)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    /// Like [`Self::best_for_decode`], but for encode operations, e.g., [`VideoCodecOperationFlagsKHR::ENCODE_H264`].
+    pub fn best_for_encode(instance: &Instance, codec: VideoCodecOperationFlagsKHR) -> Result<Self, Error> {
+        if codec.intersects(VIDEO_DECODE_OPERATIONS) {
+            return Err(error!(Variant::FeatureNotSupported, "{codec:?} is not an encode operation."));
+        }
+
+        let shared = PhysicalDeviceShared::new_best_for(instance.shared(), codec)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
     pub(crate) fn shared(&self) -> Arc<PhysicalDeviceShared> {
         self.shared.clone()
     }
 
+    /// The raw `VkPhysicalDevice`.
+    pub fn raw(&self) -> ash::vk::PhysicalDevice {
+        self.shared.native()
+    }
+
     pub fn queue_family_infos(&self) -> &QueueFamilyInfos {
         self.shared.queue_family_infos()
     }
     pub fn heap_infos(&self) -> &HeapInfos {
         self.shared.heap_infos()
     }
+
+    /// Nanoseconds per GPU timestamp tick.
+    pub fn timestamp_period(&self) -> f32 {
+        self.shared.timestamp_period()
+    }
+
+    /// If this is a discrete, integrated, ... GPU.
+    pub fn device_type(&self) -> PhysicalDeviceType {
+        self.shared.device_type()
+    }
+
+    /// If `codec` can be decoded or encoded, e.g., [`VideoCodecOperationFlagsKHR::DECODE_H265`].
+    pub fn supports_codec(&self, codec: VideoCodecOperationFlagsKHR) -> Result<bool, Error> {
+        self.shared.supports_codec(codec)
+    }
+
+    /// Identifies the device across processes and APIs, memory can only be shared between users of the same device.
+    pub fn device_uuid(&self) -> [u8; 16] {
+        self.shared.device_uuid()
+    }
+
+    /// Identifies the driver, memory can only be shared between users of the same driver (version).
+    pub fn driver_uuid(&self) -> [u8; 16] {
+        self.shared.driver_uuid()
+    }
+
+    /// If, and how, buffers with `usage` can be shared via `handle_type`, e.g., [`EXTERNAL_HANDLE_TYPE`](crate::EXTERNAL_HANDLE_TYPE).
+    ///
+    /// `external_memory_features` says whether they're exportable or importable, `compatible_handle_types` which other handle types
+    /// they can be created with at the same time.
+    pub fn external_buffer_properties(
+        &self,
+        usage: BufferUsageFlags,
+        handle_type: ExternalMemoryHandleTypeFlags,
+    ) -> ExternalMemoryProperties {
+        self.shared.external_buffer_properties(usage, handle_type)
+    }
+
+    /// If, and how, images described by `info` can be shared via `handle_type`, empty if not at all.
+    ///
+    /// Images must not be created with [`ImageInfo::external_memory`] handle types lacking both `EXPORTABLE` and `IMPORTABLE`.
+    pub fn external_image_properties(
+        &self,
+        info: &ImageInfo,
+        handle_type: ExternalMemoryHandleTypeFlags,
+    ) -> Result<ExternalMemoryProperties, Error> {
+        self.shared.external_image_properties(info, handle_type)
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::allocation::MemoryTypeIndex;
     use crate::error::Error;
     use crate::instance::{Instance, InstanceInfo};
-    use crate::physicaldevice::PhysicalDevice;
+    use crate::physicaldevice::{device_type_rank, HeapBudget, PhysicalDevice};
+    use crate::Variant;
+    use ash::vk::{MemoryPropertyFlags, PhysicalDeviceType, QueueFlags, VideoCodecOperationFlagsKHR};
+
+    #[test]
+    fn prefer_discrete() {
+        let mut types = [
+            PhysicalDeviceType::CPU,
+            PhysicalDeviceType::INTEGRATED_GPU,
+            PhysicalDeviceType::DISCRETE_GPU,
+        ];
+
+        types.sort_by_key(|x| device_type_rank(*x));
+
+        assert_eq!(types[0], PhysicalDeviceType::DISCRETE_GPU);
+        assert_eq!(types[2], PhysicalDeviceType::CPU);
+    }
+
+    #[test]
+    fn heap_budget() {
+        let budget = HeapBudget { usage: 300, budget: 1000 };
+        let exceeded = HeapBudget { usage: 1200, budget: 1000 };
+
+        assert_eq!(budget.available(), 700);
+        assert_eq!(exceeded.available(), 0);
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn heap_sizes() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let heap_infos = physical_device.heap_infos();
+
+        assert!(heap_infos.heap_count() > 0);
+        assert!((0..heap_infos.heap_count()).all(|x| heap_infos.heap_size(x) > 0));
+        assert_eq!(heap_infos.heap_size(heap_infos.heap_count()), 0);
+        assert_eq!(heap_infos.budget(0).is_some(), heap_infos.has_budget());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn find_type() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let heap_infos = physical_device.heap_infos();
+        let host_visible = MemoryPropertyFlags::HOST_VISIBLE;
+        let preferred = MemoryPropertyFlags::HOST_CACHED;
+
+        assert!(heap_infos.find_type(0, MemoryPropertyFlags::empty(), preferred).is_none());
+
+        assert!(heap_infos.type_flags(MemoryTypeIndex::new(u32::MAX)).is_empty());
+
+        if let Some(x) = heap_infos.any_host_cached() {
+            let cached_available = heap_infos
+                .find_type(u32::MAX, host_visible | preferred, MemoryPropertyFlags::empty())
+                .is_some();
+
+            assert!(heap_infos.type_flags(x).contains(host_visible));
+            assert_eq!(heap_infos.type_flags(x).contains(preferred), cached_available);
+        }
+
+        if let Some(x) = heap_infos.find_type(u32::MAX, host_visible, preferred) {
+            assert!(heap_infos.type_flags(x).contains(host_visible));
+            assert!(heap_infos
+                .find_type(1 << x.index(), host_visible, MemoryPropertyFlags::empty())
+                .is_some());
+            assert!(heap_infos
+                .find_type(!(1 << x.index()), host_visible, preferred)
+                .is_none_or(|y| y.index() != x.index()));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn best_for_decode() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::best_for_decode(&instance, VideoCodecOperationFlagsKHR::DECODE_H264)?;
+        let encode = PhysicalDevice::best_for_decode(&instance, VideoCodecOperationFlagsKHR::ENCODE_H264);
+
+        assert!(physical_device.supports_codec(VideoCodecOperationFlagsKHR::DECODE_H264)?);
+        assert!(encode.is_err_and(|e| matches!(e.variant(), Variant::FeatureNotSupported)));
+
+        Ok(())
+    }
 
     #[test]
     #[cfg(not(miri))]
@@ -190,7 +808,34 @@ mod test {
         let instance = Instance::new(&instance_info)?;
         let physical_device = PhysicalDevice::new_any(&instance)?;
 
-        _ = physical_device.queue_family_infos();
+        let queue_family_infos = physical_device.queue_family_infos();
+
+        if let Some(family) = queue_family_infos.family_for(VideoCodecOperationFlagsKHR::DECODE_H264) {
+            assert!(queue_family_infos
+                .codec_operations(family)
+                .contains(VideoCodecOperationFlagsKHR::DECODE_H264));
+        }
+
+        assert!(queue_family_infos.codec_operations(u32::MAX).is_empty());
+
+        for family in [queue_family_infos.any_transfer(), queue_family_infos.any_graphics()]
+            .into_iter()
+            .flatten()
+        {
+            assert!(queue_family_infos.available().contains(&family));
+        }
+
+        for (i, family) in queue_family_infos.iter().enumerate() {
+            assert_eq!(family.index(), i as u32);
+            assert!(family.queue_count() > 0);
+            assert_eq!(family.codec_operations(), queue_family_infos.codec_operations(family.index()));
+        }
+
+        if let Some(compute) = queue_family_infos.any_compute() {
+            assert!(queue_family_infos
+                .iter()
+                .any(|x| x.index() == compute && x.flags().contains(QueueFlags::COMPUTE)));
+        }
 
         Ok(())
     }
@@ -1,16 +1,99 @@
+use std::any::Any;
+use std::ffi::CString;
+use std::future::Future;
 use std::marker::PhantomData;
-use std::sync::Arc;
+use std::pin::Pin;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
 
-use ash::vk::{CommandBufferBeginInfo, CommandBufferResetFlags, FenceCreateFlags, FenceCreateInfo, SubmitInfo};
+use ash::vk::{
+    CommandBufferBeginInfo, CommandBufferResetFlags, CommandBufferUsageFlags, DebugUtilsLabelEXT, Fence, FenceCreateFlags, FenceCreateInfo,
+    ImageLayout, PipelineStageFlags, TimelineSemaphoreSubmitInfo, Win32KeyedMutexAcquireReleaseInfoKHR,
+};
 
+use crate::allocation::{Allocation, AllocationShared};
 use crate::commandbuffer::{CommandBuffer, CommandBufferShared};
 use crate::device::{Device, DeviceShared};
-use crate::error::Error;
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::ops::{Access, AddToCommandBuffer};
+#[cfg(feature = "renderdoc")]
+use crate::renderdoc::RenderDoc;
+use crate::resources::{BufferShared, ImageShared};
+use crate::semaphore::{Semaphore, SemaphoreShared};
+use crate::tracking::ResourceStates;
 
+/// Synchronization of a submission with other submissions or APIs, see [`Queue::build_and_submit_with`].
+///
+/// ```rust,ignore
+/// // Decode once D3D12 is done with the texture, and let it know when we are.
+/// let info = SubmitInfo::new().wait(&fence, 1).signal(&fence, 2);
+/// queue.build_and_submit_with(&command_buffer, &info, |x| decode.run_in(x))?;
+/// ```
+///
+/// Submissions on different queues can be chained the same way, without waiting on the CPU in between:
+///
+/// ```rust,ignore
+/// let decoded = SubmitInfo::new().signal(&semaphore, frame * 2 + 1);
+/// let converted = SubmitInfo::new().wait_at(&semaphore, frame * 2 + 1, PipelineStageFlags::COMPUTE_SHADER).signal(&semaphore, frame * 2 + 2);
+///
+/// let _decode = decode_queue.submit_async_with(&decode_commands, &decoded, |x| decode.run_in(x))?;
+/// let convert = compute_queue.submit_async_with(&compute_commands, &converted, |x| convert.run_in(x))?;
+/// convert.await?;
+/// ```
+#[derive(Clone, Default)]
+pub struct SubmitInfo {
+    waits: Vec<(Arc<SemaphoreShared>, u64, PipelineStageFlags)>,
+    signals: Vec<(Arc<SemaphoreShared>, u64)>,
+    acquire_keys: Vec<(Arc<AllocationShared>, u64, u32)>,
+    release_keys: Vec<(Arc<AllocationShared>, u64)>,
+}
+
+impl SubmitInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits until `semaphore` reached `value` before executing.
+    pub fn wait(self, semaphore: &Semaphore, value: u64) -> Self {
+        self.wait_at(semaphore, value, PipelineStageFlags::ALL_COMMANDS)
+    }
+
+    /// Like [`Self::wait`], but only `stages` wait, earlier stages of the submission may already execute.
+    pub fn wait_at(mut self, semaphore: &Semaphore, value: u64, stages: PipelineStageFlags) -> Self {
+        self.waits.push((semaphore.shared(), value, stages));
+        self
+    }
+
+    /// Sets `semaphore` to `value` once executed.
+    pub fn signal(mut self, semaphore: &Semaphore, value: u64) -> Self {
+        self.signals.push((semaphore.shared(), value));
+        self
+    }
+
+    /// Acquires the D3D11 keyed mutex of imported memory with `key` before executing, waiting at most `timeout_ms`.
+    ///
+    /// Needs [`VideoFeatures::win32_keyed_mutex`](crate::VideoFeatures::win32_keyed_mutex).
+    pub fn acquire_keyed_mutex(mut self, allocation: &Allocation, key: u64, timeout_ms: u32) -> Self {
+        self.acquire_keys.push((allocation.shared(), key, timeout_ms));
+        self
+    }
+
+    /// Releases the D3D11 keyed mutex of imported memory with `key` once executed.
+    pub fn release_keyed_mutex(mut self, allocation: &Allocation, key: u64) -> Self {
+        self.release_keys.push((allocation.shared(), key));
+        self
+    }
+}
+
+/// Records operations into a command buffer, placing the barriers between them.
 pub struct CommandBuilder<'a> {
     _lt: PhantomData<&'a ()>,
+    shared_device: Arc<DeviceShared>,
     native_command_buffer: ash::vk::CommandBuffer,
     queue_family_index: u32,
+    states: ResourceStates,
 }
 
 impl<'a> CommandBuilder<'a> {
@@ -18,71 +101,575 @@ impl<'a> CommandBuilder<'a> {
         self.native_command_buffer
     }
 
+    /// The device to record raw commands with, e.g., from a closure operation.
+    pub fn native_device(&self) -> ash::Device {
+        self.shared_device.native()
+    }
+
     pub fn queue_family_index(&self) -> u32 {
         self.queue_family_index
     }
+
+    /// Records `op`, labeled with its [`name`](AddToCommandBuffer::name) if `VK_EXT_debug_utils` is enabled.
+    ///
+    /// Prefer this over calling [`AddToCommandBuffer::run_in`] directly, so captures in tools like RenderDoc show
+    /// which operation recorded what.
+    pub fn run<T: AddToCommandBuffer + ?Sized>(&mut self, op: &T) -> Result<(), Error> {
+        let Some(debug_utils) = self.shared_device.debug_utils().cloned() else {
+            return op.run_in(self);
+        };
+
+        let name = op.name();
+        let name = name.split('<').next().unwrap_or_default();
+        let name = name.rsplit("::").next().unwrap_or_default();
+        let name = CString::new(name)?;
+        let label = DebugUtilsLabelEXT::default().label_name(&name);
+
+        // SAFETY: Should be safe as the command buffer is recording, and labels are balanced even on errors.
+        unsafe {
+            debug_utils.cmd_begin_debug_utils_label(self.native_command_buffer, &label);
+            let rval = op.run_in(self);
+            debug_utils.cmd_end_debug_utils_label(self.native_command_buffer);
+            rval
+        }
+    }
+
+    /// Declares `access` to `buffer` by the next command, see [`Self::record_barriers`].
+    pub(crate) fn access_buffer(&mut self, buffer: &BufferShared, access: Access) {
+        self.states.access_buffer(buffer, access);
+    }
+
+    /// Declares `access` to array layers of `image` by the next command, which needs them in `layout`.
+    pub(crate) fn access_image(
+        &mut self,
+        image: &ImageShared,
+        base_array_layer: u32,
+        layer_count: u32,
+        access: Access,
+        layout: ImageLayout,
+    ) {
+        self.states.access_image(image, base_array_layer, layer_count, access, layout);
+    }
+
+    /// Notes `access` to `buffer` by a command placing its own barriers.
+    pub(crate) fn assume_buffer(&mut self, buffer: &BufferShared, access: Access) {
+        self.states.assume_buffer(buffer, access);
+    }
+
+    /// Notes `access` to array layers of `image` by a command placing its own barriers, which left them in `layout`.
+    pub(crate) fn assume_image(
+        &mut self,
+        image: &ImageShared,
+        base_array_layer: u32,
+        layer_count: u32,
+        access: Access,
+        layout: ImageLayout,
+    ) {
+        self.states.assume_image(image, base_array_layer, layer_count, access, layout);
+    }
+
+    /// Records the barriers the accesses declared since the last call need.
+    pub(crate) fn record_barriers(&mut self) {
+        self.states
+            .record_barriers(&self.shared_device.native(), self.native_command_buffer);
+    }
+}
+
+/// Fences of completed submissions, reused as some drivers are slow to create them.
+struct FencePool {
+    shared_device: Arc<DeviceShared>,
+    fences: Mutex<Vec<Fence>>,
+}
+
+impl FencePool {
+    fn new(shared_device: Arc<DeviceShared>) -> Self {
+        Self {
+            shared_device,
+            fences: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// An unsignaled fence, created if none is left.
+    fn acquire(&self) -> Result<Fence, Error> {
+        let fence = self.fences.lock().unwrap_or_else(PoisonError::into_inner).pop();
+
+        match fence {
+            Some(fence) => Ok(fence),
+            None => {
+                let native_device = self.shared_device.native();
+                let fence_info = FenceCreateInfo::default().flags(FenceCreateFlags::default());
+
+                // SAFETY: Should be safe as the device is valid.
+                Ok(unsafe { native_device.create_fence(&fence_info, None)? })
+            }
+        }
+    }
+
+    /// Takes `fence` back once its submission completed (or never happened).
+    fn release(&self, fence: Fence) {
+        let native_device = self.shared_device.native();
+
+        // SAFETY: Should be safe as no pending submission uses the fence anymore.
+        unsafe {
+            match native_device.reset_fences(&[fence]) {
+                Ok(()) => self.fences.lock().unwrap_or_else(PoisonError::into_inner).push(fence),
+                Err(_) => native_device.destroy_fence(fence, None),
+            }
+        }
+    }
+}
+
+impl Drop for FencePool {
+    fn drop(&mut self) {
+        let native_device = self.shared_device.native();
+        let fences = self.fences.get_mut().unwrap_or_else(PoisonError::into_inner);
+
+        for fence in fences.drain(..) {
+            unsafe {
+                native_device.destroy_fence(fence, None);
+            }
+        }
+    }
 }
 
 struct QueueShared {
     shared_device: Arc<DeviceShared>,
     native_queue: ash::vk::Queue,
     queue_family_index: u32,
+    /// Held while using `native_queue`, as Vulkan requires queue access to be externally synchronized. Shared with
+    /// all other queues wrapping the same `VkQueue`.
+    submission: Arc<Mutex<()>>,
+    fences: Arc<FencePool>,
+    /// Hands async submissions to the thread waiting for them, started on first use.
+    waiter: Mutex<Option<Sender<PendingSubmission>>>,
 }
 
 impl QueueShared {
     fn new(shared_device: Arc<DeviceShared>, queue_family_index: u32, index: u32) -> Result<Self, Error> {
         let native_device = shared_device.native();
 
+        if let Some(count) = shared_device.queue_count(queue_family_index).filter(|x| index >= *x) {
+            return Err(error!(
+                Variant::QueueNotFound,
+                "Queue family {queue_family_index} has {count} queues on this device, can't get queue {index}."
+            ));
+        }
+
         unsafe {
             let native_queue = native_device.get_device_queue(queue_family_index, index);
 
             Ok(Self {
+                fences: Arc::new(FencePool::new(shared_device.clone())),
+                submission: shared_device.queue_lock(queue_family_index, index),
                 shared_device,
                 native_queue,
                 queue_family_index,
+                waiter: Mutex::new(None),
             })
         }
     }
 
+    fn native(&self) -> ash::vk::Queue {
+        self.native_queue
+    }
+
     pub fn build_and_submit(
         &self,
         command_buffer: Arc<CommandBufferShared>,
+        info: &SubmitInfo,
+        f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let handle = self.submit(command_buffer, info, f)?;
+
+        // Can't time out without timeout.
+        handle.wait(u64::MAX)?;
+
+        Ok(())
+    }
+
+    pub fn submit(
+        &self,
+        command_buffer: Arc<CommandBufferShared>,
+        info: &SubmitInfo,
+        f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>,
+    ) -> Result<SubmitHandle, Error> {
+        let fence = self.record_and_submit(&command_buffer, info, f)?;
+
+        Ok(SubmitHandle {
+            fences: self.fences.clone(),
+            fence,
+            command_buffer,
+            resources: Vec::new(),
+        })
+    }
+
+    pub fn replay(&self, command_buffer: Arc<CommandBufferShared>, info: &SubmitInfo) -> Result<SubmitHandle, Error> {
+        let fence = self.submit_recorded(&command_buffer, info)?;
+
+        Ok(SubmitHandle {
+            fences: self.fences.clone(),
+            fence,
+            command_buffer,
+            resources: Vec::new(),
+        })
+    }
+
+    pub fn submit_async(
+        &self,
+        command_buffer: Arc<CommandBufferShared>,
+        info: &SubmitInfo,
+        f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>,
+    ) -> Result<Submission, Error> {
+        let fence = self.record_and_submit(&command_buffer, info, f)?;
+        let state = Arc::new((Mutex::new(SubmissionState::default()), Condvar::new()));
+        let pending = PendingSubmission {
+            fence,
+            command_buffer,
+            state: state.clone(),
+        };
+
+        self.wait_async(pending);
+
+        Ok(Submission { state })
+    }
+
+    /// Completes `pending` once executed, on the thread waiting for all async submissions of this queue.
+    ///
+    /// Vulkan has no way to be notified about fences, so one thread per queue waits for them.
+    fn wait_async(&self, pending: PendingSubmission) {
+        let mut waiter = self.waiter.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let sender = match &*waiter {
+            Some(sender) => sender.clone(),
+            None => {
+                let (sender, receiver) = channel::<PendingSubmission>();
+                let fences = self.fences.clone();
+
+                // Ends once the queue is gone, after completing what was submitted until then.
+                let spawned = std::thread::Builder::new()
+                    .name("vulkan_video fence".to_string())
+                    .spawn(move || wait_for_submissions(&receiver, &fences));
+
+                if spawned.is_err() {
+                    return pending.complete(&self.fences);
+                }
+
+                waiter.insert(sender).clone()
+            }
+        };
+
+        // Only fails if the thread is gone, in which case we wait right here.
+        if let Err(e) = sender.send(pending) {
+            *waiter = None;
+            e.0.complete(&self.fences);
+        }
+    }
+
+    /// Records `f` into `command_buffer` and submits it, returning the fence signalled on completion.
+    fn record_and_submit(
+        &self,
+        command_buffer: &CommandBufferShared,
+        info: &SubmitInfo,
+        f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>,
+    ) -> Result<Fence, Error> {
+        self.record(command_buffer, f)?;
+        self.submit_recorded(command_buffer, info)
+    }
+
+    /// Records `f` into `command_buffer`, replacing whatever was recorded before.
+    pub fn record(
+        &self,
+        command_buffer: &CommandBufferShared,
+        f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.shared_device.validated(|| self.record_commands(command_buffer, f))
+    }
+
+    fn record_commands(
+        &self,
+        command_buffer: &CommandBufferShared,
         f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>,
     ) -> Result<(), Error> {
         let native_device = self.shared_device.native();
         let native_command_buffer = command_buffer.native();
-        let native_queue = self.native_queue;
-
-        let begin_info = CommandBufferBeginInfo::default();
-        let command_buffers = [native_command_buffer];
-        let submit_info = SubmitInfo::default().command_buffers(&command_buffers);
-        let fence_info = FenceCreateInfo::default().flags(FenceCreateFlags::default());
+        let begin_info = CommandBufferBeginInfo::default().flags(command_buffer.usage());
 
+        let _busy = command_buffer.lock();
         let mut queue_live = CommandBuilder {
             _lt: Default::default(),
+            shared_device: self.shared_device.clone(),
             native_command_buffer,
             queue_family_index: self.queue_family_index,
+            states: ResourceStates::default(),
         };
 
-        unsafe {
-            let fence = native_device.create_fence(&fence_info, None)?;
+        // Resetting a pending command buffer is invalid, so we wait for earlier submissions of it. Only then we lock
+        // the pool, so other buffers of it can be recorded meanwhile.
+        command_buffer.wait_until_executed()?;
+        let _recording = command_buffer.lock_recording();
+        command_buffer.set_recorded(false);
 
+        unsafe {
             native_device.reset_command_buffer(native_command_buffer, CommandBufferResetFlags::empty())?;
             native_device.begin_command_buffer(native_command_buffer, &begin_info)?;
             f(&mut queue_live)?;
+            queue_live.states.record_host_barrier(&native_device, native_command_buffer);
             native_device.end_command_buffer(native_command_buffer)?;
-            // TODO - nevermind, this still about 1 in 5 times fails on this line ... (DEVICE LOST)
-            native_device.queue_submit(native_queue, &[submit_info], fence)?;
-            native_device.wait_for_fences(&[fence], true, u64::MAX)?;
-            native_device.destroy_fence(fence, None);
-            native_device.queue_wait_idle(native_queue)?;
+        }
+
+        command_buffer.set_recorded(true);
 
-            Ok(())
+        Ok(())
+    }
+
+    /// Submits what was last recorded into `command_buffer`, returning the fence signalled on completion.
+    fn submit_recorded(&self, command_buffer: &CommandBufferShared, info: &SubmitInfo) -> Result<Fence, Error> {
+        self.shared_device.validated(|| self.submit_commands(command_buffer, info))
+    }
+
+    fn submit_commands(&self, command_buffer: &CommandBufferShared, info: &SubmitInfo) -> Result<Fence, Error> {
+        let native_device = self.shared_device.native();
+        let native_command_buffer = command_buffer.native();
+        let native_queue = self.native_queue;
+
+        let keyed_mutex = !info.acquire_keys.is_empty() || !info.release_keys.is_empty();
+
+        if keyed_mutex && !self.shared_device.video_features().win32_keyed_mutex() {
+            return Err(error!(Variant::FeatureNotSupported, "VK_KHR_win32_keyed_mutex is not available."));
         }
+
+        let wait_semaphores = info.waits.iter().map(|x| x.0.native()).collect::<Vec<_>>();
+        let wait_values = info.waits.iter().map(|x| x.1).collect::<Vec<_>>();
+        let wait_stages = info.waits.iter().map(|x| x.2).collect::<Vec<_>>();
+        let signal_semaphores = info.signals.iter().map(|x| x.0.native()).collect::<Vec<_>>();
+        let signal_values = info.signals.iter().map(|x| x.1).collect::<Vec<_>>();
+        let acquire_memory = info.acquire_keys.iter().map(|x| x.0.native()).collect::<Vec<_>>();
+        let acquire_keys = info.acquire_keys.iter().map(|x| x.1).collect::<Vec<_>>();
+        let acquire_timeouts = info.acquire_keys.iter().map(|x| x.2).collect::<Vec<_>>();
+        let release_memory = info.release_keys.iter().map(|x| x.0.native()).collect::<Vec<_>>();
+        let release_keys = info.release_keys.iter().map(|x| x.1).collect::<Vec<_>>();
+
+        let mut timeline_info = TimelineSemaphoreSubmitInfo::default()
+            .wait_semaphore_values(&wait_values)
+            .signal_semaphore_values(&signal_values);
+        let mut keyed_mutex_info = Win32KeyedMutexAcquireReleaseInfoKHR::default()
+            .acquire_syncs(&acquire_memory)
+            .acquire_keys(&acquire_keys)
+            .acquire_timeouts(&acquire_timeouts)
+            .release_syncs(&release_memory)
+            .release_keys(&release_keys);
+
+        let command_buffers = [native_command_buffer];
+        let mut submit_info = ash::vk::SubmitInfo::default()
+            .command_buffers(&command_buffers)
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .signal_semaphores(&signal_semaphores)
+            .push_next(&mut timeline_info);
+
+        if keyed_mutex {
+            submit_info = submit_info.push_next(&mut keyed_mutex_info);
+        }
+
+        // Held so the command buffer can't be recorded again meanwhile, other buffers of its pool can.
+        let _busy = command_buffer.lock();
+
+        if !command_buffer.is_recorded() {
+            return Err(error!(Variant::NoCommandBuffer, "Nothing was recorded into the command buffer."));
+        }
+
+        // Replays must not overlap unless the command buffer was recorded for that.
+        if !command_buffer.usage().contains(CommandBufferUsageFlags::SIMULTANEOUS_USE) {
+            command_buffer.wait_until_executed()?;
+        }
+
+        let fence = self.fences.acquire()?;
+        let _submission = self.submission.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // TODO - nevermind, this still about 1 in 5 times fails on this line ... (DEVICE LOST)
+        // SAFETY: Should be safe as the command buffer was recorded, and everything else is valid.
+        if let Err(e) = unsafe { native_device.queue_submit(native_queue, &[submit_info], fence) } {
+            self.fences.release(fence);
+            return Err(e.into());
+        }
+
+        command_buffer.set_in_flight(fence);
+
+        // Such command buffers become invalid once executed.
+        if command_buffer.usage().contains(CommandBufferUsageFlags::ONE_TIME_SUBMIT) {
+            command_buffer.set_recorded(false);
+        }
+
+        Ok(fence)
+    }
+}
+
+/// How long the thread waiting for async submissions blocks before picking up new ones, in nanoseconds.
+const WAIT_INTERVAL: u64 = 1_000_000;
+
+/// Completes async submissions received through `receiver`, until the queue is gone and all of them completed.
+fn wait_for_submissions(receiver: &Receiver<PendingSubmission>, fences: &FencePool) {
+    let native_device = fences.shared_device.native();
+    let mut pending = Vec::<PendingSubmission>::new();
+
+    loop {
+        // Only blocks on the channel if there is nothing to wait for.
+        match pending.is_empty() {
+            true => match receiver.recv() {
+                Ok(x) => pending.push(x),
+                Err(_) => return,
+            },
+            false => pending.extend(receiver.try_iter()),
+        }
+
+        let native_fences = pending.iter().map(|x| x.fence).collect::<Vec<_>>();
+
+        // Waits for any of them, as later submissions can complete first, e.g., if an earlier one waits for a semaphore.
+        // Errors (i.e., a lost device) are reported by completing the submissions below.
+        // SAFETY: Should be safe as the fences are valid until released.
+        _ = unsafe { native_device.wait_for_fences(&native_fences, false, WAIT_INTERVAL) };
+
+        // SAFETY: Should be safe as the fences are valid until released.
+        let (executed, waiting) = pending
+            .into_iter()
+            .partition::<Vec<_>, _>(|x| !matches!(unsafe { native_device.get_fence_status(x.fence) }, Ok(false)));
+
+        pending = waiting;
+        executed.into_iter().for_each(|x| x.complete(fences));
+    }
+}
+
+/// An async submission waited for by the thread of its queue, which owns the command buffer, so it isn't reset while
+/// the GPU still executes it, even if the [`Submission`] is dropped early.
+struct PendingSubmission {
+    fence: Fence,
+    command_buffer: Arc<CommandBufferShared>,
+    state: Arc<(Mutex<SubmissionState>, Condvar)>,
+}
+
+impl PendingSubmission {
+    /// Blocks until executed, then releases the fence and notifies the [`Submission`].
+    fn complete(self, fences: &FencePool) {
+        let native_device = fences.shared_device.native();
+
+        // SAFETY: Should be safe as the fence is valid until released.
+        let result = unsafe { native_device.wait_for_fences(&[self.fence], true, u64::MAX) };
+        self.command_buffer.clear_in_flight(self.fence);
+        fences.release(self.fence);
+        drop(self.command_buffer);
+
+        let (state, completed) = &*self.state;
+        let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+
+        state.result = Some(fences.shared_device.check_lost(result.map_err(Error::from)));
+        completed.notify_all();
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Default)]
+struct SubmissionState {
+    result: Option<Result<(), Error>>,
+    waker: Option<Waker>,
+}
+
+/// Completes once the GPU finished executing a submission, see [`Queue::submit_async`].
+pub struct Submission {
+    /// Result and waker, with a condition variable notified on completion.
+    state: Arc<(Mutex<SubmissionState>, Condvar)>,
+}
+
+impl Submission {
+    /// Blocks until the submission completed, for when it can't be awaited.
+    pub(crate) fn wait(&self) -> Result<(), Error> {
+        let (state, completed) = &*self.state;
+        let state = state.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut state = completed
+            .wait_while(state, |x| x.result.is_none())
+            .unwrap_or_else(PoisonError::into_inner);
+
+        state.result.take().unwrap_or(Ok(()))
+    }
+}
+
+impl Future for Submission {
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.0.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A submission in flight, returned by [`Queue::submit`], which can be polled or waited for without blocking up front.
+///
+/// Dropping the handle waits for the submission to complete, so resources it uses (and those passed to
+/// [`keep_alive`](Self::keep_alive)) are never released while the GPU still needs them.
+pub struct SubmitHandle {
+    fences: Arc<FencePool>,
+    fence: Fence,
+    /// Must not be reset while the GPU still executes it.
+    command_buffer: Arc<CommandBufferShared>,
+    resources: Vec<Box<dyn Any + Send + Sync>>,
+}
+
+impl SubmitHandle {
+    /// Waits at most `timeout` nanoseconds for the submission, returns if it completed.
+    pub fn wait(&self, timeout: u64) -> Result<bool, Error> {
+        let native_device = self.fences.shared_device.native();
+
+        // SAFETY: Should be safe as the fence is valid until we're dropped.
+        let result = match unsafe { native_device.wait_for_fences(&[self.fence], true, timeout) } {
+            Ok(()) => Ok(true),
+            Err(ash::vk::Result::TIMEOUT) => Ok(false),
+            Err(e) => Err(e.into()),
+        };
+
+        self.fences.shared_device.check_lost(result)
+    }
+
+    /// If the submission completed, without blocking.
+    pub fn is_complete(&self) -> Result<bool, Error> {
+        let native_device = self.fences.shared_device.native();
+
+        // SAFETY: Should be safe as the fence is valid until we're dropped.
+        let result = unsafe { native_device.get_fence_status(self.fence) };
+
+        self.fences.shared_device.check_lost(result.map_err(Error::from))
+    }
+
+    /// Keeps `resource` (e.g., a [`Buffer`](crate::resources::Buffer) the submission reads) alive until the submission completed.
+    pub fn keep_alive(mut self, resource: impl Any + Send + Sync) -> Self {
+        self.resources.push(Box::new(resource));
+        self
+    }
+}
+
+impl Drop for SubmitHandle {
+    fn drop(&mut self) {
+        let native_device = self.fences.shared_device.native();
+
+        // If waiting fails the device is lost, and nothing executes anymore either way.
+        _ = unsafe { native_device.wait_for_fences(&[self.fence], true, u64::MAX) };
+
+        self.command_buffer.clear_in_flight(self.fence);
+        self.fences.release(self.fence);
     }
 }
 
 /// GPU execution unit to run your command buffers.
+///
+/// Submissions are serialized internally, so a queue can be shared between threads.
 pub struct Queue {
     shared: Arc<QueueShared>,
 }
@@ -94,22 +681,153 @@ impl Queue {
         Ok(Self { shared: Arc::new(shared) })
     }
 
+    /// The raw `VkQueue`.
+    ///
+    /// Vulkan requires submissions to a queue to be externally synchronized, don't submit to it while this crate might.
+    pub fn raw(&self) -> ash::vk::Queue {
+        self.shared.native()
+    }
+
     pub fn build_and_submit(
         &self,
         command_buffer: &CommandBuffer,
         f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>,
     ) -> Result<(), Error> {
-        self.shared.build_and_submit(command_buffer.shared(), f)
+        self.shared.build_and_submit(command_buffer.shared(), &SubmitInfo::default(), f)
+    }
+
+    /// Like [`Self::build_and_submit`], but waits for and signals semaphores (or keyed mutexes) as given by `info`.
+    pub fn build_and_submit_with(
+        &self,
+        command_buffer: &CommandBuffer,
+        info: &SubmitInfo,
+        f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.shared.build_and_submit(command_buffer.shared(), info, f)
+    }
+
+    /// Like [`Self::build_and_submit`], but captures the submission with RenderDoc, e.g., to debug a flaky operation.
+    #[cfg(feature = "renderdoc")]
+    pub fn build_and_submit_captured(
+        &self,
+        renderdoc: &RenderDoc,
+        command_buffer: &CommandBuffer,
+        f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let shared_instance = self.shared.shared_device.instance();
+
+        renderdoc.start(&shared_instance);
+        let result = self.shared.build_and_submit(command_buffer.shared(), &SubmitInfo::default(), f);
+        renderdoc.end(&shared_instance);
+
+        result
+    }
+
+    /// Like [`Self::build_and_submit`], but returns right after submission, waiting is up to the returned [`SubmitHandle`].
+    ///
+    /// Recording `command_buffer` again waits for the submission to complete, back-to-back submissions should alternate
+    /// between command buffers instead.
+    pub fn submit(
+        &self,
+        command_buffer: &CommandBuffer,
+        f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>,
+    ) -> Result<SubmitHandle, Error> {
+        self.shared.submit(command_buffer.shared(), &SubmitInfo::default(), f)
+    }
+
+    /// Like [`Self::submit`], but waits for and signals semaphores (or keyed mutexes) as given by `info`.
+    pub fn submit_with(
+        &self,
+        command_buffer: &CommandBuffer,
+        info: &SubmitInfo,
+        f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>,
+    ) -> Result<SubmitHandle, Error> {
+        self.shared.submit(command_buffer.shared(), info, f)
+    }
+
+    /// Records `f` into `command_buffer` without submitting it, to [`replay`](Self::replay) it as often as needed.
+    ///
+    /// Resources are tracked as if recorded commands executed once, so they must leave images in the layouts they found
+    /// them in, e.g., by ending with the same ops they started with. Otherwise replays would transition images from stale layouts.
+    pub fn record(&self, command_buffer: &CommandBuffer, f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>) -> Result<(), Error> {
+        self.shared.record(&command_buffer.shared(), f)
+    }
+
+    /// Submits what was last recorded into `command_buffer` again, without recording it anew.
+    ///
+    /// Works for command buffers recorded via [`Self::record`], as well as for those of earlier submissions. Waits for a
+    /// previous submission of it to complete first, unless recorded with [`CommandBufferUsageFlags::SIMULTANEOUS_USE`].
+    pub fn replay(&self, command_buffer: &CommandBuffer) -> Result<SubmitHandle, Error> {
+        self.shared.replay(command_buffer.shared(), &SubmitInfo::default())
+    }
+
+    /// Like [`Self::replay`], but waits for and signals semaphores (or keyed mutexes) as given by `info`.
+    pub fn replay_with(&self, command_buffer: &CommandBuffer, info: &SubmitInfo) -> Result<SubmitHandle, Error> {
+        self.shared.replay(command_buffer.shared(), info)
+    }
+
+    /// Like [`Self::build_and_submit`], but returns right after submission instead of blocking until the GPU is done.
+    ///
+    /// Await the returned [`Submission`] before reading any results, recording `command_buffer` again would block until
+    /// it completed. Completion is awaited on a background thread (one per queue), so this works with any async runtime.
+    pub fn submit_async(
+        &self,
+        command_buffer: &CommandBuffer,
+        f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>,
+    ) -> Result<Submission, Error> {
+        self.shared.submit_async(command_buffer.shared(), &SubmitInfo::default(), f)
+    }
+
+    /// Like [`Self::submit_async`], but waits for and signals semaphores (or keyed mutexes) as given by `info`.
+    pub fn submit_async_with(
+        &self,
+        command_buffer: &CommandBuffer,
+        info: &SubmitInfo,
+        f: impl FnOnce(&mut CommandBuilder) -> Result<(), Error>,
+    ) -> Result<Submission, Error> {
+        self.shared.submit_async(command_buffer.shared(), info, f)
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::commandbuffer::CommandBuffer;
     use crate::device::Device;
-    use crate::error::Error;
+    use crate::error;
+    use crate::error::{Error, Variant};
     use crate::instance::{Instance, InstanceInfo};
     use crate::physicaldevice::PhysicalDevice;
-    use crate::queue::Queue;
+    use crate::queue::{Queue, SubmitInfo};
+    use crate::semaphore::Semaphore;
+    use ash::vk::PipelineStageFlags;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::Thread;
+
+    /// Minimal executor, polling `future` on the current thread until it completes.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct ThreadWaker(Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut context = Context::from_waker(&waker);
+        let mut future = pin!(future);
+
+        loop {
+            if let Poll::Ready(x) = future.as_mut().poll(&mut context) {
+                return x;
+            }
+
+            std::thread::park();
+        }
+    }
 
     #[test]
     #[cfg(not(miri))]
@@ -123,4 +841,184 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn same_queue_from_threads() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+
+        // Both wrap the same `VkQueue`, so they must share its lock.
+        let queues = [Queue::new(&device, 0, 0)?, Queue::new(&device, 0, 0)?];
+
+        std::thread::scope(|s| {
+            let threads = queues
+                .iter()
+                .map(|queue| {
+                    s.spawn(|| -> Result<(), Error> {
+                        let command_buffer = CommandBuffer::new(&device, 0)?;
+
+                        for _ in 0..100 {
+                            queue.build_and_submit(&command_buffer, |_| Ok(()))?;
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            threads.into_iter().try_for_each(|x| x.join().expect("Submitting thread panicked."))
+        })
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn submit_async() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, 0, 0)?;
+        let command_buffer = CommandBuffer::new(&device, 0)?;
+
+        let submission = queue.submit_async(&command_buffer, |_| Ok(()))?;
+
+        block_on(submission)?;
+        block_on(queue.submit_async(&command_buffer, |_| Ok(()))?)?;
+
+        // All submissions of a queue are waited for by the same thread, and can complete in any order.
+        let others = [CommandBuffer::new(&device, 0)?, CommandBuffer::new(&device, 0)?];
+        let submissions = others
+            .iter()
+            .map(|x| queue.submit_async(x, |_| Ok(())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for submission in submissions.into_iter().rev() {
+            block_on(submission)?;
+        }
+
+        // The command buffer must outlive the submission, even if neither is around anymore.
+        let submission = queue.submit_async(&command_buffer, |_| Ok(()))?;
+        drop(command_buffer);
+        drop(submission);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn submit_handle() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, 0, 0)?;
+        let command_buffers = [CommandBuffer::new(&device, 0)?, CommandBuffer::new(&device, 0)?];
+
+        let first = queue.submit(&command_buffers[0], |_| Ok(()))?.keep_alive(vec![0u8; 16]);
+        let second = queue.submit(&command_buffers[1], |_| Ok(()))?;
+
+        assert!(first.wait(u64::MAX)?);
+        assert!(first.is_complete()?);
+        drop(second);
+        drop(first);
+
+        // Fences come from the pool now, and must be reset.
+        let third = queue.submit(&command_buffers[0], |_| Ok(()))?;
+        assert!(third.wait(u64::MAX)?);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn record_pending() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, 0, 0)?;
+        let command_buffer = CommandBuffer::new(&device, 0)?;
+
+        // Recording again waits for the first submission, instead of resetting the command buffer under it.
+        let first = queue.submit(&command_buffer, |_| Ok(()))?;
+        let second = queue.submit(&command_buffer, |_| Ok(()))?;
+
+        assert!(first.is_complete()?);
+        assert!(second.wait(u64::MAX)?);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn record_and_replay() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let queue = Queue::new(&device, 0, 0)?;
+        let command_buffer = CommandBuffer::new(&device, 0)?;
+
+        assert!(queue.replay(&command_buffer).is_err());
+
+        queue.record(&command_buffer, |_| Ok(()))?;
+
+        for _ in 0..3 {
+            assert!(queue.replay(&command_buffer)?.wait(u64::MAX)?);
+        }
+
+        // Without simultaneous use, replays wait for the previous one instead of overlapping.
+        let first = queue.replay(&command_buffer)?;
+        let second = queue.replay(&command_buffer)?;
+
+        assert!(first.is_complete()?);
+        assert!(second.wait(u64::MAX)?);
+        drop(second);
+        drop(first);
+
+        // Failed recordings leave nothing to replay.
+        assert!(queue.record(&command_buffer, |_| Err(error!(Variant::NoCommandBuffer))).is_err());
+        assert!(queue.replay(&command_buffer).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn chain_across_queues() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let queue_family_infos = physical_device.queue_family_infos();
+        let compute_family = queue_family_infos.any_compute().ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let decode_family = queue_family_infos.any_decode().ok_or_else(|| error!(Variant::QueueNotFound))?;
+
+        if !device.video_features().timeline_semaphore() {
+            return Ok(());
+        }
+
+        let decode_queue = Queue::new(&device, decode_family, 0)?;
+        let compute_queue = Queue::new(&device, compute_family, 0)?;
+        let decode_commands = CommandBuffer::new(&device, decode_family)?;
+        let compute_commands = CommandBuffer::new(&device, compute_family)?;
+        let semaphore = Semaphore::new(&device, 0)?;
+
+        // Submitted in reverse, so the compute queue really has to wait on the GPU.
+        let converted = SubmitInfo::new()
+            .wait_at(&semaphore, 1, PipelineStageFlags::COMPUTE_SHADER)
+            .signal(&semaphore, 2);
+        let convert = compute_queue.submit_async_with(&compute_commands, &converted, |_| Ok(()))?;
+        let decode = decode_queue.submit_async_with(&decode_commands, &SubmitInfo::new().signal(&semaphore, 1), |_| Ok(()))?;
+
+        block_on(convert)?;
+        block_on(decode)?;
+
+        assert_eq!(semaphore.value()?, 2);
+
+        Ok(())
+    }
 }
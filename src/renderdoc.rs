@@ -0,0 +1,147 @@
+//! Triggers [RenderDoc](https://renderdoc.org/) captures from code, e.g., around a single flaky submission.
+//!
+//! Captures only work if the application was launched from RenderDoc (or RenderDoc was injected into it), we never
+//! load RenderDoc ourselves:
+//!
+//! ```rust,ignore
+//! let renderdoc = RenderDoc::new()?;
+//!
+//! queue.build_and_submit_captured(&renderdoc, &command_buffer, |x| x.run(&decode))?;
+//! ```
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::instance::{Instance, InstanceShared};
+use ash::vk::Handle;
+use libloading::Library;
+use std::ffi::{c_int, c_void};
+
+/// `eRENDERDOC_API_Version_1_1_2`, the oldest version with everything we need.
+const API_VERSION: c_int = 10102;
+
+type GetApi = unsafe extern "C" fn(version: c_int, api: *mut *mut c_void) -> c_int;
+type StartFrameCapture = unsafe extern "C" fn(device: *const c_void, window: *const c_void);
+type IsFrameCapturing = unsafe extern "C" fn() -> u32;
+type EndFrameCapture = unsafe extern "C" fn(device: *const c_void, window: *const c_void) -> u32;
+
+/// `RENDERDOC_API_1_1_2` in `renderdoc_app.h`, only the functions we call are typed.
+#[repr(C)]
+struct Api {
+    _unused: [*const c_void; 19],
+    start_frame_capture: StartFrameCapture,
+    is_frame_capturing: IsFrameCapturing,
+    end_frame_capture: EndFrameCapture,
+}
+
+/// Connection to the RenderDoc in-application API.
+pub struct RenderDoc {
+    api: *const Api,
+    _library: Library,
+}
+
+// SAFETY: The RenderDoc API may be used from any thread.
+unsafe impl Send for RenderDoc {}
+unsafe impl Sync for RenderDoc {}
+
+impl RenderDoc {
+    /// Connects to RenderDoc, which must already be loaded into this process.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Variant::FeatureNotSupported`] if we don't run under RenderDoc.
+    pub fn new() -> Result<Self, Error> {
+        let library = load().ok_or_else(|| error!(Variant::FeatureNotSupported, "Not running under RenderDoc."))?;
+        let mut api = std::ptr::null_mut();
+
+        // SAFETY: Should be safe as the symbol has this signature in all RenderDoc versions.
+        unsafe {
+            let get_api = library
+                .get::<GetApi>(b"RENDERDOC_GetAPI\0")
+                .map_err(|_| error!(Variant::FeatureNotSupported, "RenderDoc has no `RENDERDOC_GetAPI`."))?;
+
+            if get_api(API_VERSION, &mut api) != 1 || api.is_null() {
+                return Err(error!(Variant::FeatureNotSupported, "RenderDoc does not provide API 1.1.2."));
+            }
+        }
+
+        Ok(Self {
+            api: api.cast(),
+            _library: library,
+        })
+    }
+
+    /// Starts capturing everything submitted on devices of `instance`.
+    pub fn start_capture(&self, instance: &Instance) {
+        self.start(&instance.shared());
+    }
+
+    /// Ends the capture started by [`Self::start_capture`], returns if it was written successfully.
+    pub fn end_capture(&self, instance: &Instance) -> bool {
+        self.end(&instance.shared())
+    }
+
+    /// If a capture is currently in progress.
+    pub fn is_capturing(&self) -> bool {
+        // SAFETY: Should be safe as the API table is valid while the library is loaded.
+        unsafe { ((*self.api).is_frame_capturing)() == 1 }
+    }
+
+    pub(crate) fn start(&self, instance: &InstanceShared) {
+        // SAFETY: Should be safe as the API table is valid while the library is loaded, and the instance is valid.
+        unsafe { ((*self.api).start_frame_capture)(device_pointer(instance), std::ptr::null()) }
+    }
+
+    pub(crate) fn end(&self, instance: &InstanceShared) -> bool {
+        // SAFETY: Should be safe as the API table is valid while the library is loaded, and the instance is valid.
+        unsafe { ((*self.api).end_frame_capture)(device_pointer(instance), std::ptr::null()) == 1 }
+    }
+}
+
+/// `RENDERDOC_DEVICEPOINTER_FROM_VKINSTANCE`, the dispatch table pointer the instance handle points to.
+fn device_pointer(instance: &InstanceShared) -> *const c_void {
+    let handle = instance.native().handle().as_raw() as *const *const c_void;
+
+    // SAFETY: Dispatchable Vulkan handles always point to their dispatch table pointer.
+    unsafe { *handle }
+}
+
+/// Opens the RenderDoc library if (and only if) it's already loaded.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn load() -> Option<Library> {
+    use libloading::os::unix::{Library, RTLD_NOW};
+
+    /// Not exported by `libloading`, same value for glibc, musl and bionic.
+    const RTLD_NOLOAD: c_int = 0x4;
+    let name = if cfg!(target_os = "android") {
+        "libVkLayer_GLES_RenderDoc.so"
+    } else {
+        "librenderdoc.so"
+    };
+
+    // SAFETY: Should be safe as RenderDoc already ran its initialization when it was loaded.
+    unsafe { Library::open(Some(name), RTLD_NOW | RTLD_NOLOAD).ok().map(Into::into) }
+}
+
+/// Opens the RenderDoc library if (and only if) it's already loaded.
+#[cfg(windows)]
+fn load() -> Option<Library> {
+    libloading::os::windows::Library::open_already_loaded("renderdoc.dll")
+        .ok()
+        .map(Into::into)
+}
+
+/// RenderDoc doesn't support other platforms.
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+fn load() -> Option<Library> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use crate::renderdoc::Api;
+
+    #[test]
+    fn api_layout() {
+        // `EndFrameCapture` is the 22nd function of `RENDERDOC_API_1_1_2`.
+        assert_eq!(std::mem::offset_of!(Api, end_frame_capture), 21 * size_of::<usize>());
+    }
+}
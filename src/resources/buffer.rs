@@ -1,13 +1,15 @@
 use crate::allocation::{Allocation, AllocationShared};
-use crate::device::DeviceShared;
-use crate::error::Error;
-use crate::video::h264::H264StreamInspector;
+use crate::allocator::Allocator;
+use crate::device::{Device, DeviceShared};
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::resources::MemoryRequirements;
+use crate::video::StreamInspector;
 use ash::vk;
 use ash::vk::{
-    BufferCreateInfo, BufferUsageFlags, DeviceSize, ExternalMemoryBufferCreateInfo, ExternalMemoryHandleTypeFlags, MappedMemoryRange,
-    MemoryMapFlags, WHOLE_SIZE,
+    BufferCreateFlags, BufferCreateInfo, BufferUsageFlags, DeviceBufferMemoryRequirements, ExternalMemoryBufferCreateInfo,
+    MemoryPropertyFlags, MemoryRequirements2,
 };
-use std::ffi::c_void;
 use std::sync::Arc;
 
 /// Specifies how to crate a [`Buffer`](Buffer).
@@ -43,11 +45,39 @@ impl BufferInfo {
     }
 }
 
+/// Binds `device_buffer` to `shared_allocation`, destroying the buffer if that fails.
+unsafe fn bind_memory(shared_allocation: &AllocationShared, device_buffer: vk::Buffer, offset: u64) -> Result<(), Error> {
+    let native_device = shared_allocation.device().native();
+
+    unsafe {
+        let requirements = native_device.get_buffer_memory_requirements(device_buffer);
+        let result = shared_allocation
+            .check_type(requirements.memory_type_bits)
+            .and_then(|_| Ok(native_device.bind_buffer_memory(device_buffer, shared_allocation.native(), offset)?));
+
+        if result.is_err() {
+            native_device.destroy_buffer(device_buffer, None);
+        }
+
+        result
+    }
+}
+
+/// Usage of regular buffers, see [`Buffer::new`].
+const USAGE: BufferUsageFlags = BufferUsageFlags::from_raw(
+    BufferUsageFlags::STORAGE_BUFFER.as_raw()
+        | BufferUsageFlags::TRANSFER_DST.as_raw()
+        | BufferUsageFlags::TRANSFER_SRC.as_raw()
+        | BufferUsageFlags::UNIFORM_BUFFER.as_raw(),
+);
+
 pub(crate) struct BufferShared {
     shared_device: Arc<DeviceShared>,
     shared_allocation: Arc<AllocationShared>,
     device_buffer: vk::Buffer,
     buffer_info: BufferInfo,
+    /// If we destroy the buffer on drop, not the case for buffers wrapped via [`Buffer::from_raw`].
+    owned: bool,
 }
 
 impl BufferShared {
@@ -55,33 +85,40 @@ impl BufferShared {
         let shared_device = shared_allocation.device();
         let native_device = shared_device.native();
 
-        let usage = BufferUsageFlags::STORAGE_BUFFER
-            | BufferUsageFlags::TRANSFER_DST
-            | BufferUsageFlags::TRANSFER_SRC
-            | BufferUsageFlags::UNIFORM_BUFFER;
-
         unsafe {
-            let buffer_create_info = BufferCreateInfo::default().size(buffer_info.size).usage(usage);
+            let buffer_create_info = BufferCreateInfo::default().size(buffer_info.size).usage(USAGE);
 
             let device_buffer = native_device.create_buffer(&buffer_create_info, None)?;
-            let device_memory = shared_allocation.native();
-            let offset = buffer_info.offset.unwrap_or(0);
-
-            native_device.bind_buffer_memory(device_buffer, device_memory, offset)?;
+            shared_device.set_default_name(device_buffer, "Buffer");
+            bind_memory(&shared_allocation, device_buffer, buffer_info.offset.unwrap_or(0))?;
 
             Ok(Self {
                 shared_device,
                 shared_allocation,
                 device_buffer,
                 buffer_info: buffer_info.clone(),
+                owned: true,
             })
         }
     }
 
+    /// Memory requirements of a regular buffer described by `buffer_info`, without creating it.
+    pub fn memory_requirements(shared_device: &DeviceShared, buffer_info: &BufferInfo) -> MemoryRequirements {
+        let native_device = shared_device.native();
+        let buffer_create_info = BufferCreateInfo::default().size(buffer_info.size).usage(USAGE);
+        let info = DeviceBufferMemoryRequirements::default().create_info(&buffer_create_info);
+        let mut requirements = MemoryRequirements2::default();
+
+        // SAFETY: Should be safe as the device is valid, and Vulkan 1.3 has this in core.
+        unsafe { native_device.get_device_buffer_memory_requirements(&info, &mut requirements) };
+
+        MemoryRequirements::from_native(requirements.memory_requirements)
+    }
+
     pub fn new_video_decode(
         shared_allocation: Arc<AllocationShared>,
         buffer_info: &BufferInfo,
-        stream_inspector: &H264StreamInspector,
+        stream_inspector: &impl StreamInspector,
     ) -> Result<Self, Error> {
         let shared_device = shared_allocation.device();
         let native_device = shared_device.native();
@@ -105,92 +142,200 @@ impl BufferShared {
                 .push_next(profile_infos);
 
             let device_buffer = native_device.create_buffer(&buffer_create_info, None)?;
-            let device_memory = shared_allocation.native();
-            let offset = buffer_info.offset.unwrap_or(0);
-
-            native_device.bind_buffer_memory(device_buffer, device_memory, offset)?;
+            shared_device.set_default_name(device_buffer, "Buffer");
+            bind_memory(&shared_allocation, device_buffer, buffer_info.offset.unwrap_or(0))?;
 
             Ok(Self {
                 shared_device,
                 shared_allocation,
                 device_buffer,
                 buffer_info: buffer_info.clone(),
+                owned: true,
             })
         }
     }
 
-    pub fn external(shared_allocation: Arc<AllocationShared>, _pointer: *mut c_void, buffer_info: &BufferInfo) -> Result<Self, Error> {
+    pub fn new_video_encode(
+        shared_allocation: Arc<AllocationShared>,
+        buffer_info: &BufferInfo,
+        stream_inspector: &impl StreamInspector,
+    ) -> Result<Self, Error> {
         let shared_device = shared_allocation.device();
         let native_device = shared_device.native();
 
         let usage = BufferUsageFlags::STORAGE_BUFFER
             | BufferUsageFlags::TRANSFER_DST
             | BufferUsageFlags::TRANSFER_SRC
-            | BufferUsageFlags::UNIFORM_BUFFER;
+            | BufferUsageFlags::VIDEO_ENCODE_DST_KHR;
 
-        let mut eee = ExternalMemoryBufferCreateInfo::default().handle_types(ExternalMemoryHandleTypeFlags::OPAQUE_WIN32);
+        let mut profiles = stream_inspector.profiles();
 
         unsafe {
-            let buffer_create_info = BufferCreateInfo::default().size(buffer_info.size).usage(usage).push_next(&mut eee);
+            let profile_infos = &mut profiles.as_mut().get_unchecked_mut().list;
 
-            let device_buffer = native_device.create_buffer(&buffer_create_info, None)?;
-            let device_memory = shared_allocation.native();
-            let offset = buffer_info.offset.unwrap_or(0);
+            let buffer_create_info = BufferCreateInfo::default()
+                .size(buffer_info.size)
+                .usage(usage)
+                .push_next(profile_infos);
 
-            native_device.bind_buffer_memory(device_buffer, device_memory, offset)?;
+            let device_buffer = native_device.create_buffer(&buffer_create_info, None)?;
+            shared_device.set_default_name(device_buffer, "Buffer");
+            bind_memory(&shared_allocation, device_buffer, buffer_info.offset.unwrap_or(0))?;
 
             Ok(Self {
                 shared_device,
                 shared_allocation,
                 device_buffer,
                 buffer_info: buffer_info.clone(),
+                owned: true,
             })
         }
     }
 
-    pub fn upload(&self, data: &[u8]) -> Result<(), Error> {
-        let native_device = self.shared_device.native();
-        let device_memory = self.shared_allocation.native();
-        let offset = self.buffer_info.offset.unwrap_or(0);
+    pub fn new_video_decode_any(shared_allocation: Arc<AllocationShared>, buffer_info: &BufferInfo) -> Result<Self, Error> {
+        let shared_device = shared_allocation.device();
+        let native_device = shared_device.native();
+
+        if !shared_device.video_features().profile_independent_resources() {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "Profile independent buffers need `VK_KHR_video_maintenance1`"
+            ));
+        }
+
+        let usage = BufferUsageFlags::STORAGE_BUFFER
+            | BufferUsageFlags::TRANSFER_DST
+            | BufferUsageFlags::TRANSFER_SRC
+            | BufferUsageFlags::VIDEO_DECODE_SRC_KHR
+            | BufferUsageFlags::VIDEO_DECODE_DST_KHR;
 
         unsafe {
-            let mapped_pointer = native_device.map_memory(device_memory, offset, WHOLE_SIZE, MemoryMapFlags::empty())?;
+            let buffer_create_info = BufferCreateInfo::default()
+                .flags(BufferCreateFlags::VIDEO_PROFILE_INDEPENDENT_KHR)
+                .size(buffer_info.size)
+                .usage(usage);
 
-            std::ptr::copy_nonoverlapping::<u8>(data.as_ptr(), mapped_pointer.cast(), data.len());
+            let device_buffer = native_device.create_buffer(&buffer_create_info, None)?;
+            shared_device.set_default_name(device_buffer, "Buffer");
+            bind_memory(&shared_allocation, device_buffer, buffer_info.offset.unwrap_or(0))?;
 
-            let mapped_range = MappedMemoryRange::default().size(WHOLE_SIZE).memory(device_memory).offset(offset);
-            let mapped_range_slice = &[mapped_range];
-            let rval = native_device.flush_mapped_memory_ranges(mapped_range_slice);
+            Ok(Self {
+                shared_device,
+                shared_allocation,
+                device_buffer,
+                buffer_info: buffer_info.clone(),
+                owned: true,
+            })
+        }
+    }
 
-            native_device.unmap_memory(device_memory);
+    pub fn external(shared_allocation: Arc<AllocationShared>, buffer_info: &BufferInfo) -> Result<Self, Error> {
+        let shared_device = shared_allocation.device();
+        let native_device = shared_device.native();
+        let handle_types = shared_allocation.handle_types();
 
-            rval?;
+        if handle_types.is_empty() {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "Allocation is neither exportable nor imported."
+            ));
         }
 
-        Ok(())
+        let usage = BufferUsageFlags::STORAGE_BUFFER
+            | BufferUsageFlags::TRANSFER_DST
+            | BufferUsageFlags::TRANSFER_SRC
+            | BufferUsageFlags::UNIFORM_BUFFER;
+
+        let mut external_memory = ExternalMemoryBufferCreateInfo::default().handle_types(handle_types);
+
+        unsafe {
+            let buffer_create_info = BufferCreateInfo::default()
+                .size(buffer_info.size)
+                .usage(usage)
+                .push_next(&mut external_memory);
+
+            let device_buffer = native_device.create_buffer(&buffer_create_info, None)?;
+            shared_device.set_default_name(device_buffer, "Buffer");
+            bind_memory(&shared_allocation, device_buffer, buffer_info.offset.unwrap_or(0))?;
+
+            Ok(Self {
+                shared_device,
+                shared_allocation,
+                device_buffer,
+                buffer_info: buffer_info.clone(),
+                owned: true,
+            })
+        }
     }
 
-    pub fn download_into(&self, target: &mut [u8]) -> Result<(), Error> {
+    /// # Safety
+    ///
+    /// The buffer must have been created from the device of `shared_allocation`, and be bound to it at the info's offset.
+    pub unsafe fn from_native(
+        shared_allocation: Arc<AllocationShared>,
+        device_buffer: vk::Buffer,
+        buffer_info: &BufferInfo,
+        owned: bool,
+    ) -> Self {
+        Self {
+            shared_device: shared_allocation.device(),
+            shared_allocation,
+            device_buffer,
+            buffer_info: buffer_info.clone(),
+            owned,
+        }
+    }
+
+    pub fn upload(&self, data: &[u8]) -> Result<(), Error> {
         let native_device = self.shared_device.native();
-        let device_memory = self.shared_allocation.native();
         let offset = self.buffer_info.offset.unwrap_or(0);
+        let size = data.len() as u64;
+        let host_coherent = self.shared_allocation.host_coherent();
+
+        if size > self.size() {
+            return Err(error!(
+                Variant::BufferTooSmall,
+                "Uploading {size} bytes into {} bytes.",
+                self.size()
+            ));
+        }
 
-        unsafe {
-            let len_bytes = target.len() as DeviceSize;
-            let flags = MemoryMapFlags::empty();
-            let mapped_pointer = native_device.map_memory(device_memory, offset, len_bytes, flags)?;
+        self.shared_allocation.map(|mapped_pointer| unsafe {
+            std::ptr::copy_nonoverlapping::<u8>(data.as_ptr(), mapped_pointer.add(offset as usize), data.len());
 
-            // // DO I NEED THIS HERE?
-            // let mapped_range = MappedMemoryRange::default().size(len_bytes).memory(device_memory);
-            // let mapped_range_slice = &[mapped_range];
-            // let rval = native_device.flush_mapped_memory_ranges(mapped_range_slice);
+            // Makes our writes visible to the device, unless the memory is host coherent anyway.
+            if !host_coherent {
+                native_device.flush_mapped_memory_ranges(&[self.shared_allocation.mapped_range(offset, size)])?;
+            }
 
-            std::ptr::copy_nonoverlapping::<u8>(mapped_pointer.cast(), target.as_mut_ptr(), len_bytes as usize);
+            Ok(())
+        })
+    }
 
-            native_device.unmap_memory(device_memory);
+    pub fn download_into(&self, target: &mut [u8]) -> Result<(), Error> {
+        let native_device = self.shared_device.native();
+        let offset = self.buffer_info.offset.unwrap_or(0);
+        let size = target.len() as u64;
+        let host_coherent = self.shared_allocation.host_coherent();
+
+        if size > self.size() {
+            return Err(error!(
+                Variant::BufferTooSmall,
+                "Downloading {size} bytes from {} bytes.",
+                self.size()
+            ));
         }
 
-        Ok(())
+        self.shared_allocation.map(|mapped_pointer| unsafe {
+            // Makes device writes visible to us, unless the memory is host coherent anyway.
+            if !host_coherent {
+                native_device.invalidate_mapped_memory_ranges(&[self.shared_allocation.mapped_range(offset, size)])?;
+            }
+
+            std::ptr::copy_nonoverlapping::<u8>(mapped_pointer.add(offset as usize), target.as_mut_ptr(), target.len());
+
+            Ok(())
+        })
     }
 
     pub fn size(&self) -> u64 {
@@ -208,6 +353,10 @@ impl BufferShared {
 
 impl Drop for BufferShared {
     fn drop(&mut self) {
+        if !self.owned {
+            return;
+        }
+
         let device = self.shared_device.native();
 
         unsafe {
@@ -230,7 +379,7 @@ impl Buffer {
         })
     }
 
-    pub fn new_video_decode(allocation: &Allocation, info: &BufferInfo, stream_inspector: &H264StreamInspector) -> Result<Self, Error> {
+    pub fn new_video_decode(allocation: &Allocation, info: &BufferInfo, stream_inspector: &impl StreamInspector) -> Result<Self, Error> {
         let buffer_shared = BufferShared::new_video_decode(allocation.shared(), info, stream_inspector)?;
 
         Ok(Self {
@@ -238,14 +387,58 @@ impl Buffer {
         })
     }
 
-    pub fn external(allocation: &Allocation, pointer: *mut c_void, info: &BufferInfo) -> Result<Self, Error> {
-        let buffer_shared = BufferShared::external(allocation.shared(), pointer, info)?;
+    /// Creates a buffer encoded bitstreams of `stream_inspector`'s profile can be written to.
+    pub fn new_video_encode(allocation: &Allocation, info: &BufferInfo, stream_inspector: &impl StreamInspector) -> Result<Self, Error> {
+        let buffer_shared = BufferShared::new_video_encode(allocation.shared(), info, stream_inspector)?;
+
+        Ok(Self {
+            shared: Arc::new(buffer_shared),
+        })
+    }
+
+    /// Creates a video decode buffer usable with any profile, requires [`VideoFeatures::profile_independent_resources`](crate::VideoFeatures::profile_independent_resources).
+    pub fn new_video_decode_any(allocation: &Allocation, info: &BufferInfo) -> Result<Self, Error> {
+        let buffer_shared = BufferShared::new_video_decode_any(allocation.shared(), info)?;
+
+        Ok(Self {
+            shared: Arc::new(buffer_shared),
+        })
+    }
+
+    /// Creates a buffer in memory that was made exportable, or imported, e.g., via [`Allocation::new_exportable`].
+    pub fn external(allocation: &Allocation, info: &BufferInfo) -> Result<Self, Error> {
+        let buffer_shared = BufferShared::external(allocation.shared(), info)?;
 
         Ok(Self {
             shared: Arc::new(buffer_shared),
         })
     }
 
+    /// Creates a buffer as [`Self::new`] would, in memory with all `flags` reserved by `allocator`, the offset of `info` is ignored.
+    pub fn allocate(device: &Device, allocator: &dyn Allocator, info: &BufferInfo, flags: MemoryPropertyFlags) -> Result<Self, Error> {
+        let requirements = BufferShared::memory_requirements(&device.shared(), info);
+        let (allocation, offset) = allocator.allocate(&requirements, flags)?;
+
+        Self::new(&allocation, &info.clone().offset(offset))
+    }
+
+    /// Wraps a buffer created by someone else, e.g., in memory of `gpu-allocator` wrapped via [`Allocation::from_raw`].
+    ///
+    /// If `owned`, the buffer is destroyed once this is dropped, otherwise that's up to its owner.
+    ///
+    /// # Safety
+    ///
+    /// The buffer must have been created on the device of `allocation` with `info`'s size and usages matching how it's used
+    /// with this crate, be bound to `allocation` at `info`'s offset, and outlive this.
+    pub unsafe fn from_raw(allocation: &Allocation, buffer: vk::Buffer, info: &BufferInfo, owned: bool) -> Self {
+        // SAFETY: Forwarded to our caller.
+        let buffer_shared = unsafe { BufferShared::from_native(allocation.shared(), buffer, info, owned) };
+
+        Self {
+            shared: Arc::new(buffer_shared),
+        }
+    }
+
     pub fn size(&self) -> u64 {
         self.shared.size()
     }
@@ -255,6 +448,18 @@ impl Buffer {
         self.shared.clone()
     }
 
+    /// The raw `VkBuffer`.
+    pub fn raw(&self) -> vk::Buffer {
+        self.shared.native()
+    }
+
+    /// Names this buffer in validation messages and debugging tools, instead of the default `vulkan_video::Buffer#N`.
+    ///
+    /// Does nothing if `VK_EXT_debug_utils` isn't available.
+    pub fn set_name(&self, name: &str) -> Result<(), Error> {
+        self.shared.device().set_object_name(self.raw(), name)
+    }
+
     pub fn upload(&self, data: &[u8]) -> Result<(), Error> {
         self.shared.upload(data)
     }
@@ -295,6 +500,33 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    #[cfg(not(miri))]
+    fn upload_at_offset() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let device = Device::new(&physical_device)?;
+        let allocation = Allocation::new(&device, 16 * 1024, host_visible)?;
+        let buffer = Buffer::new(&allocation, &BufferInfo::new().size(100).offset(256))?;
+        let mut data = [0; 100];
+
+        // Neither offset nor size are multiples of `nonCoherentAtomSize`, which validation would flag unless widened.
+        buffer.upload(&[3; 100])?;
+        buffer.download_into(&mut data)?;
+
+        assert_eq!(data, [3; 100]);
+        assert!(buffer
+            .upload(&[3; 101])
+            .is_err_and(|e| matches!(e.variant(), Variant::BufferTooSmall)));
+
+        Ok(())
+    }
+
     #[test]
     #[cfg(not(miri))]
     fn crate_buffer_video() -> Result<(), Error> {
@@ -315,6 +547,32 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    #[cfg(not(miri))]
+    fn crate_buffer_video_any() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let device_local = physical_device
+            .heap_infos()
+            .any_device_local()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 16 * 1024, device_local)?;
+        let buffer_info = BufferInfo::new().size(1024).alignment(0).offset(0);
+        let buffer = Buffer::new_video_decode_any(&allocation, &buffer_info);
+
+        match device.video_features().profile_independent_resources() {
+            true => _ = buffer?,
+            false => assert!(matches!(
+                buffer.err().as_ref().map(|x| x.variant()),
+                Some(Variant::FeatureNotSupported)
+            )),
+        }
+
+        Ok(())
+    }
+
     #[test]
     #[cfg(not(miri))]
     fn upload_download() -> Result<(), Error> {
@@ -340,4 +598,55 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn wrap_raw() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 16 * 1024, host_visible)?;
+        let buffer_info = BufferInfo::new().size(1024).alignment(0).offset(0);
+        let buffer = Buffer::new(&allocation, &buffer_info)?;
+
+        // Neither wrapper owns its handle, so dropping them must leave `allocation` and `buffer` intact.
+        let wrapped_allocation = unsafe { Allocation::from_raw(&device, allocation.raw(), 16 * 1024, host_visible, false) };
+        let wrapped = unsafe { Buffer::from_raw(&wrapped_allocation, buffer.raw(), &buffer_info, false) };
+        wrapped.upload(&[2; 1024])?;
+        drop(wrapped);
+        drop(wrapped_allocation);
+
+        let mut target = vec![0; 1024];
+        buffer.download_into(&mut target)?;
+
+        assert_eq!(target[1023], 2);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn set_name() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let host_visible = physical_device
+            .heap_infos()
+            .any_host_visible()
+            .ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let allocation = Allocation::new(&device, 1024, host_visible)?;
+        let buffer = Buffer::new(&allocation, &BufferInfo::new().size(1024))?;
+
+        buffer.set_name("frame upload")?;
+
+        assert!(buffer.set_name("nul \0 inside").is_err());
+
+        Ok(())
+    }
 }
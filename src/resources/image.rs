@@ -1,14 +1,47 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, PoisonError};
 
 use crate::allocation::{Allocation, AllocationShared, MemoryTypeIndex};
-use ash::vk::{Extent3D, Format, ImageCreateInfo, ImageLayout, ImageTiling, ImageType, ImageUsageFlags, SampleCountFlags};
+use crate::allocator::Allocator;
+use ash::vk::{
+    Extent3D, ExternalMemoryFeatureFlags, ExternalMemoryHandleTypeFlags, ExternalMemoryImageCreateInfo, Format, ImageAspectFlags,
+    ImageCreateInfo, ImageLayout, ImageSubresource, ImageTiling, ImageType, ImageUsageFlags, MemoryPropertyFlags, SampleCountFlags,
+    SharingMode, SubresourceLayout,
+};
 
 use crate::device::{Device, DeviceShared};
 use crate::error;
 use crate::error::{Error, Variant};
-use crate::video::h264::H264StreamInspector;
+use crate::video::StreamInspector;
+
+/// Extent of the plane(s) in `aspect_mask` of a (possibly multi-planar) image with `format` and `extent`.
+///
+/// Chroma planes of 4:2:0 formats have half the resolution in both dimensions, those of 4:2:2 formats only horizontally.
+pub(crate) fn plane_extent(format: Format, aspect_mask: ImageAspectFlags, extent: Extent3D) -> Extent3D {
+    if !aspect_mask.intersects(ImageAspectFlags::PLANE_1 | ImageAspectFlags::PLANE_2) {
+        return extent;
+    }
+
+    match format {
+        Format::G8_B8R8_2PLANE_420_UNORM
+        | Format::G8_B8_R8_3PLANE_420_UNORM
+        | Format::G10X6_B10X6R10X6_2PLANE_420_UNORM_3PACK16
+        | Format::G12X4_B12X4R12X4_2PLANE_420_UNORM_3PACK16
+        | Format::G16_B16R16_2PLANE_420_UNORM => Extent3D {
+            width: extent.width.div_ceil(2),
+            height: extent.height.div_ceil(2),
+            depth: extent.depth,
+        },
+        Format::G8_B8R8_2PLANE_422_UNORM
+        | Format::G8_B8_R8_3PLANE_422_UNORM
+        | Format::G10X6_B10X6R10X6_2PLANE_422_UNORM_3PACK16
+        | Format::G12X4_B12X4R12X4_2PLANE_422_UNORM_3PACK16
+        | Format::G16_B16R16_2PLANE_422_UNORM => Extent3D {
+            width: extent.width.div_ceil(2),
+            ..extent
+        },
+        _ => extent,
+    }
+}
 
 pub struct MemoryRequirements {
     size: u64,
@@ -17,6 +50,14 @@ pub struct MemoryRequirements {
 }
 
 impl MemoryRequirements {
+    pub(crate) fn from_native(requirements: ash::vk::MemoryRequirements) -> Self {
+        Self {
+            size: requirements.size,
+            alignment: requirements.alignment,
+            memory_type_bits: requirements.memory_type_bits,
+        }
+    }
+
     pub fn size(&self) -> u64 {
         self.size
     }
@@ -25,9 +66,33 @@ impl MemoryRequirements {
         self.alignment
     }
 
+    /// The first permitted memory type, which might be neither host visible nor device local, see [`HeapInfos::find_type`](crate::HeapInfos::find_type).
     pub fn any_heap(&self) -> MemoryTypeIndex {
         MemoryTypeIndex::new(self.memory_type_bits.trailing_zeros())
     }
+
+    /// Bit `i` is set if memory type `i` may back the resource.
+    pub fn memory_type_bits(&self) -> u32 {
+        self.memory_type_bits
+    }
+}
+
+/// The plane aspects of images with `format`, just `COLOR` for formats with a single plane.
+fn plane_aspects(format: Format) -> ImageAspectFlags {
+    match format {
+        Format::G8_B8R8_2PLANE_420_UNORM
+        | Format::G10X6_B10X6R10X6_2PLANE_420_UNORM_3PACK16
+        | Format::G12X4_B12X4R12X4_2PLANE_420_UNORM_3PACK16
+        | Format::G16_B16R16_2PLANE_420_UNORM
+        | Format::G8_B8R8_2PLANE_422_UNORM
+        | Format::G10X6_B10X6R10X6_2PLANE_422_UNORM_3PACK16
+        | Format::G12X4_B12X4R12X4_2PLANE_422_UNORM_3PACK16
+        | Format::G16_B16R16_2PLANE_422_UNORM => ImageAspectFlags::PLANE_0 | ImageAspectFlags::PLANE_1,
+        Format::G8_B8_R8_3PLANE_420_UNORM | Format::G8_B8_R8_3PLANE_422_UNORM | Format::G8_B8_R8_3PLANE_444_UNORM => {
+            ImageAspectFlags::PLANE_0 | ImageAspectFlags::PLANE_1 | ImageAspectFlags::PLANE_2
+        }
+        _ => ImageAspectFlags::COLOR,
+    }
 }
 
 /// Specifies how to crate an [`Image`](Image).
@@ -38,11 +103,14 @@ pub struct ImageInfo {
     usage: ImageUsageFlags,
     mip_levels: u32,
     array_layers: u32,
-    bind_offset: u64,
     image_type: ImageType,
     tiling: ImageTiling,
     extent: Extent3D,
     layout: ImageLayout,
+    external_memory: ExternalMemoryHandleTypeFlags,
+    external_layout: Option<ImageLayout>,
+    queue_family_index: Option<u32>,
+    concurrent_queue_families: Vec<u32>,
 }
 
 impl ImageInfo {
@@ -55,36 +123,64 @@ impl ImageInfo {
         self
     }
 
+    pub fn get_format(&self) -> Format {
+        self.format
+    }
+
     pub fn samples(mut self, samples: SampleCountFlags) -> Self {
         self.samples = samples;
         self
     }
 
+    pub fn get_samples(&self) -> SampleCountFlags {
+        self.samples
+    }
+
     pub fn usage(mut self, usage: ImageUsageFlags) -> Self {
         self.usage = usage;
         self
     }
 
+    pub fn get_usage(&self) -> ImageUsageFlags {
+        self.usage
+    }
+
     pub fn mip_levels(mut self, mip_levels: u32) -> Self {
         self.mip_levels = mip_levels;
         self
     }
 
+    pub fn get_mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
     pub fn array_layers(mut self, array_layers: u32) -> Self {
         self.array_layers = array_layers;
         self
     }
 
+    pub fn get_array_layers(&self) -> u32 {
+        self.array_layers
+    }
+
     pub fn image_type(mut self, image_type: ImageType) -> Self {
         self.image_type = image_type;
         self
     }
 
+    pub fn get_image_type(&self) -> ImageType {
+        self.image_type
+    }
+
     pub fn tiling(mut self, tiling: ImageTiling) -> Self {
         self.tiling = tiling;
         self
     }
 
+    pub fn get_tiling(&self) -> ImageTiling {
+        self.tiling
+    }
+
     pub fn extent(mut self, extent: Extent3D) -> Self {
         self.extent = extent;
         self
@@ -98,18 +194,104 @@ impl ImageInfo {
         self.layout = layout;
         self
     }
+
+    /// Creates the image for memory imported or exported with these handle types, see [`Allocation::import_fd`](crate::Allocation::import_fd)
+    /// and [`Allocation::new_exportable`](crate::Allocation::new_exportable).
+    pub fn external_memory(mut self, handle_types: ExternalMemoryHandleTypeFlags) -> Self {
+        self.external_memory = handle_types;
+        self
+    }
+
+    pub fn get_external_memory(&self) -> ExternalMemoryHandleTypeFlags {
+        self.external_memory
+    }
+
+    /// Layout the image is in whenever it's not used by us, e.g., as agreed with a renderer sharing it.
+    ///
+    /// Decodes transition the image from and back into this layout, `GENERAL` if not set.
+    pub fn external_layout(mut self, layout: ImageLayout) -> Self {
+        self.external_layout = Some(layout);
+        self
+    }
+
+    pub fn get_external_layout(&self) -> ImageLayout {
+        self.external_layout.unwrap_or(ImageLayout::GENERAL)
+    }
+
+    /// Queue family owning the image whenever it's not used by us, e.g., the graphics queue family of a renderer.
+    ///
+    /// Decodes acquire the image from, and release it back to this family. The owner has to record the matching
+    /// release and acquire barriers (with the decode queue family) on its side.
+    pub fn queue_family_index(mut self, queue_family_index: u32) -> Self {
+        self.queue_family_index = Some(queue_family_index);
+        self
+    }
+
+    pub fn get_queue_family_index(&self) -> Option<u32> {
+        self.queue_family_index
+    }
+
+    /// Queue families using the image at the same time, e.g., decoding into it on one and copying from it on another.
+    ///
+    /// Creates the image with concurrent sharing, so none of them has to release or acquire it. With fewer than two
+    /// distinct families the image stays exclusive to the family using it.
+    pub fn concurrent_queue_families(mut self, queue_family_indices: &[u32]) -> Self {
+        let mut queue_family_indices = queue_family_indices.to_vec();
+
+        queue_family_indices.sort_unstable();
+        queue_family_indices.dedup();
+
+        self.concurrent_queue_families = queue_family_indices;
+        self
+    }
+
+    pub fn get_concurrent_queue_families(&self) -> &[u32] {
+        &self.concurrent_queue_families
+    }
+
+    /// Adds the sharing mode of the image to `create_image`.
+    fn with_sharing<'a>(&'a self, create_image: ImageCreateInfo<'a>) -> ImageCreateInfo<'a> {
+        match self.concurrent_queue_families.len() {
+            0 | 1 => create_image,
+            _ => create_image
+                .sharing_mode(SharingMode::CONCURRENT)
+                .queue_family_indices(&self.concurrent_queue_families),
+        }
+    }
 }
 
 pub(crate) struct ImageShared {
     shared_device: Arc<DeviceShared>,
-    shared_allocation: RefCell<Option<Arc<AllocationShared>>>,
+    /// Memory bound to the image, and the offset it was bound at.
+    shared_allocation: Mutex<Option<(Arc<AllocationShared>, u64)>>,
     native_image: ash::vk::Image,
+    /// Layout of each array layer once recorded commands executed, see [`ResourceStates`](crate::tracking::ResourceStates).
+    layouts: Mutex<Vec<ImageLayout>>,
     info: ImageInfo,
+    /// If we destroy the image on drop, not the case for images wrapped via [`Image::from_raw`].
+    owned: bool,
 }
 
 impl ImageShared {
     fn new(shared_device: Arc<DeviceShared>, info: &ImageInfo) -> Result<Self, Error> {
         let native_device = shared_device.native();
+        let shared_physical_device = shared_device.physical_device();
+
+        // Video targets can't be checked the same way, as their format query needs the video profile as well.
+        for bit in 0..u32::BITS {
+            let handle_type = ExternalMemoryHandleTypeFlags::from_raw(1 << bit);
+            let sharable = ExternalMemoryFeatureFlags::EXPORTABLE | ExternalMemoryFeatureFlags::IMPORTABLE;
+
+            if !info.external_memory.contains(handle_type) {
+                continue;
+            }
+
+            let properties = shared_physical_device.external_image_properties(info, handle_type)?;
+
+            if !properties.external_memory_features.intersects(sharable) {
+                return Err(error!(Variant::FeatureNotSupported, "Image can't be shared as {handle_type:?}."));
+            }
+        }
 
         let create_image = ImageCreateInfo::default()
             .format(info.format) // we got this from the videosession struct which listed this as teh format.
@@ -123,19 +305,33 @@ impl ImageShared {
             // .push_next(&mut video_profile_list_info_khr)
             .extent(info.extent);
 
+        let create_image = info.with_sharing(create_image);
+        let mut external_memory = ExternalMemoryImageCreateInfo::default().handle_types(info.external_memory);
+        let create_image = match info.external_memory.is_empty() {
+            true => create_image,
+            false => create_image.push_next(&mut external_memory),
+        };
+
         unsafe {
             let native_image = native_device.create_image(&create_image, None)?;
+            shared_device.set_default_name(native_image, "Image");
 
             Ok(Self {
                 shared_device,
-                shared_allocation: RefCell::new(None),
+                shared_allocation: Mutex::new(None),
                 native_image,
+                layouts: Mutex::new(vec![info.layout; info.array_layers as usize]),
                 info: info.clone(),
+                owned: true,
             })
         }
     }
 
-    fn new_video_target(shared_device: Arc<DeviceShared>, info: &ImageInfo, stream_inspector: &H264StreamInspector) -> Result<Self, Error> {
+    fn new_video_target(
+        shared_device: Arc<DeviceShared>,
+        info: &ImageInfo,
+        stream_inspector: &impl StreamInspector,
+    ) -> Result<Self, Error> {
         let native_device = shared_device.native();
 
         unsafe {
@@ -154,47 +350,109 @@ impl ImageShared {
                 .push_next(&mut profiles_inner.list)
                 .extent(info.extent);
 
+            let create_image = info.with_sharing(create_image);
+            let mut external_memory = ExternalMemoryImageCreateInfo::default().handle_types(info.external_memory);
+            let create_image = match info.external_memory.is_empty() {
+                true => create_image,
+                false => create_image.push_next(&mut external_memory),
+            };
+
             let native_image = native_device.create_image(&create_image, None)?;
+            shared_device.set_default_name(native_image, "Image");
 
             Ok(Self {
                 shared_device,
-                shared_allocation: RefCell::new(None),
+                shared_allocation: Mutex::new(None),
                 native_image,
+                layouts: Mutex::new(vec![info.layout; info.array_layers as usize]),
                 info: info.clone(),
+                owned: true,
             })
         }
     }
 
-    pub fn bind(&self, shared_allocation: Arc<AllocationShared>) -> Result<(), Error> {
+    /// # Safety
+    ///
+    /// The image must have been created from `shared_device` as described by `info`, and currently be in `info`'s layout.
+    unsafe fn from_native(shared_device: Arc<DeviceShared>, native_image: ash::vk::Image, info: &ImageInfo, owned: bool) -> Self {
+        Self {
+            shared_device,
+            shared_allocation: Mutex::new(None),
+            native_image,
+            layouts: Mutex::new(vec![info.layout; info.array_layers as usize]),
+            info: info.clone(),
+            owned,
+        }
+    }
+
+    pub fn bind(&self, shared_allocation: Arc<AllocationShared>, offset: u64) -> Result<(), Error> {
         let native_device = self.shared_device.native();
         let native_image = self.native_image;
         let native_allocation = shared_allocation.native();
+        let requirements = self.memory_requirement();
 
-        if self.shared_allocation.borrow().is_some() {
+        // Held until bound so concurrent calls can't both bind memory.
+        let mut bound_allocation = self.shared_allocation.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if bound_allocation.is_some() {
             return Err(error!(Variant::ImageAlreadyBound));
         }
 
+        shared_allocation.check_type(requirements.memory_type_bits)?;
+
+        if !offset.is_multiple_of(requirements.alignment) || offset + requirements.size > shared_allocation.size() {
+            return Err(error!(
+                Variant::BufferTooSmall,
+                "Image needs {} bytes aligned to {} at offset {offset}, allocation has {}.",
+                requirements.size,
+                requirements.alignment,
+                shared_allocation.size()
+            ));
+        }
+
         unsafe {
-            native_device.bind_image_memory(native_image, native_allocation, self.info.bind_offset)?;
+            native_device.bind_image_memory(native_image, native_allocation, offset)?;
 
-            self.shared_allocation.replace(Some(shared_allocation));
+            *bound_allocation = Some((shared_allocation, offset));
 
             Ok(())
         }
     }
 
-    pub(crate) fn memory_requirement(&self) -> MemoryRequirements {
-        let native_device = self.shared_device.native();
+    pub fn map<R>(&self, f: impl FnOnce(&MappedImage<'_>) -> R) -> Result<R, Error> {
+        if self.info.tiling != ImageTiling::LINEAR {
+            return Err(error!(Variant::ImageNotMappable, "Only images with linear tiling can be mapped."));
+        }
 
-        unsafe {
-            let requirements = native_device.get_image_memory_requirements(self.native_image);
+        let (shared_allocation, offset) = self
+            .binding()
+            .ok_or_else(|| error!(Variant::ImageNotMappable, "Image has no memory bound."))?;
+
+        let native_device = self.shared_device.native();
+        let size = self.memory_requirement().size;
+        let host_coherent = shared_allocation.host_coherent();
 
-            MemoryRequirements {
-                size: requirements.size,
-                alignment: requirements.alignment,
-                memory_type_bits: requirements.memory_type_bits,
+        shared_allocation.map(|mapped_pointer| unsafe {
+            // Makes device writes visible, unless the memory is host coherent anyway.
+            if !host_coherent {
+                native_device.invalidate_mapped_memory_ranges(&[shared_allocation.mapped_range(offset, size)])?;
             }
-        }
+
+            let data = std::slice::from_raw_parts(mapped_pointer.add(offset as usize), size as usize);
+
+            Ok(f(&MappedImage { image: self, data }))
+        })
+    }
+
+    /// Memory bound to the image and the offset into it, if any.
+    pub(crate) fn binding(&self) -> Option<(Arc<AllocationShared>, u64)> {
+        self.shared_allocation.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    pub(crate) fn memory_requirement(&self) -> MemoryRequirements {
+        let native_device = self.shared_device.native();
+
+        unsafe { MemoryRequirements::from_native(native_device.get_image_memory_requirements(self.native_image)) }
     }
 
     pub(crate) fn native(&self) -> ash::vk::Image {
@@ -208,10 +466,28 @@ impl ImageShared {
     pub(crate) fn info(&self) -> ImageInfo {
         self.info.clone()
     }
+
+    pub(crate) fn layout(&self, array_layer: u32) -> ImageLayout {
+        let layouts = self.layouts.lock().unwrap_or_else(PoisonError::into_inner);
+
+        layouts.get(array_layer as usize).copied().unwrap_or(self.info.layout)
+    }
+
+    pub(crate) fn set_layout(&self, array_layer: u32, layout: ImageLayout) {
+        let mut layouts = self.layouts.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(x) = layouts.get_mut(array_layer as usize) {
+            *x = layout;
+        }
+    }
 }
 
 impl Drop for ImageShared {
     fn drop(&mut self) {
+        if !self.owned {
+            return;
+        }
+
         let native_device = self.shared_device.native();
 
         unsafe {
@@ -220,9 +496,45 @@ impl Drop for ImageShared {
     }
 }
 
+/// The memory of a linear image, mapped for the duration of [`Image::map`].
+pub struct MappedImage<'a> {
+    image: &'a ImageShared,
+    data: &'a [u8],
+}
+
+impl MappedImage<'_> {
+    /// Bytes of the plane `aspect` (e.g., `PLANE_0` for luma) in the first array layer, `None` if the format has no
+    /// such plane.
+    ///
+    /// Rows may be padded, they start [`Self::row_pitch`] bytes apart.
+    pub fn plane(&self, aspect: ImageAspectFlags) -> Option<&[u8]> {
+        let layout = self.layout(aspect)?;
+
+        self.data.get(layout.offset as usize..(layout.offset + layout.size) as usize)
+    }
+
+    /// Distance in bytes between the rows of plane `aspect`, `None` if the format has no such plane.
+    pub fn row_pitch(&self, aspect: ImageAspectFlags) -> Option<u64> {
+        self.layout(aspect).map(|x| x.row_pitch)
+    }
+
+    fn layout(&self, aspect: ImageAspectFlags) -> Option<SubresourceLayout> {
+        let native_device = self.image.shared_device.native();
+
+        // Single planes only, querying other aspects is invalid.
+        if aspect.as_raw().count_ones() != 1 || !plane_aspects(self.image.info.format).contains(aspect) {
+            return None;
+        }
+
+        let subresource = ImageSubresource::default().aspect_mask(aspect);
+
+        unsafe { Some(native_device.get_image_subresource_layout(self.image.native_image, subresource)) }
+    }
+}
+
 /// A often 2D image, usually stored on the GPU.
 pub struct Image {
-    shared: Rc<ImageShared>,
+    shared: Arc<ImageShared>,
 }
 
 impl Image {
@@ -230,28 +542,94 @@ impl Image {
         let shared_device = ImageShared::new(device.shared(), info)?;
 
         Ok(Self {
-            shared: Rc::new(shared_device),
+            shared: Arc::new(shared_device),
         })
     }
 
-    pub fn new_video_target(device: &Device, info: &ImageInfo, stream_inspector: &H264StreamInspector) -> Result<Self, Error> {
+    pub fn new_video_target(device: &Device, info: &ImageInfo, stream_inspector: &impl StreamInspector) -> Result<Self, Error> {
         let shared_device = ImageShared::new_video_target(device.shared(), info, stream_inspector)?;
 
         Ok(Self {
-            shared: Rc::new(shared_device),
+            shared: Arc::new(shared_device),
         })
     }
 
+    /// Wraps an image created by someone else, e.g., a swapchain image, to use it as source or target of ops.
+    ///
+    /// Images that already have memory bound (like swapchain images) must not be bound again. If `owned`, the image is destroyed
+    /// once this is dropped, otherwise that's up to its owner.
+    ///
+    /// # Safety
+    ///
+    /// The image must have been created on `device` as described by `info`, must currently be in `info`'s layout, and must
+    /// outlive this.
+    pub unsafe fn from_raw(device: &Device, image: ash::vk::Image, info: &ImageInfo, owned: bool) -> Self {
+        // SAFETY: Forwarded to our caller.
+        let shared = unsafe { ImageShared::from_native(device.shared(), image, info, owned) };
+
+        Self { shared: Arc::new(shared) }
+    }
+
+    /// Vulkan format of an `AHardwareBuffer`, to create an image for [`Allocation::import_android_hardware_buffer`](crate::Allocation::import_android_hardware_buffer).
+    ///
+    /// Buffers without Vulkan equivalent (e.g., vendor specific camera formats) are not supported.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be a valid `AHardwareBuffer`.
+    #[cfg(feature = "android")]
+    pub unsafe fn android_hardware_buffer_format(device: &Device, buffer: *const ash::vk::AHardwareBuffer) -> Result<Format, Error> {
+        let mut format_properties = ash::vk::AndroidHardwareBufferFormatPropertiesANDROID::default();
+
+        _ = crate::allocation::android_hardware_buffer_properties(&device.shared(), buffer, &mut format_properties)?;
+
+        match format_properties.format {
+            Format::UNDEFINED => Err(error!(
+                Variant::FeatureNotSupported,
+                "Hardware buffer has external format {}.", format_properties.external_format
+            )),
+            format => Ok(format),
+        }
+    }
+
     pub fn bind(self, allocation: &Allocation) -> Result<Self, Error> {
-        self.shared.bind(allocation.shared())?;
+        self.shared.bind(allocation.shared(), 0)?;
         Ok(self)
     }
 
+    /// Like [`Self::bind`], but at `offset` into `allocation`, e.g., to place several images in one allocation.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Variant::BufferTooSmall`] if `offset` isn't aligned as [`Self::memory_requirement`] demands, or the
+    /// image doesn't fit behind it.
+    pub fn bind_at(self, allocation: &Allocation, offset: u64) -> Result<Self, Error> {
+        self.shared.bind(allocation.shared(), offset)?;
+        Ok(self)
+    }
+
+    /// Binds this image to memory with all `flags` reserved by `allocator`.
+    pub fn allocate(self, allocator: &dyn Allocator, flags: MemoryPropertyFlags) -> Result<Self, Error> {
+        let (allocation, offset) = allocator.allocate(&self.memory_requirement(), flags)?;
+
+        self.bind_at(&allocation, offset)
+    }
+
     pub fn memory_requirement(&self) -> MemoryRequirements {
         self.shared.memory_requirement()
     }
 
-    pub(crate) fn shared(&self) -> Rc<ImageShared> {
+    /// Maps the memory of this image and calls `f` with its planes, e.g., to read decoded pictures without copying
+    /// them into a [`Buffer`](crate::resources::Buffer) first.
+    ///
+    /// The image must have [`ImageTiling::LINEAR`] and be bound to host visible memory, and no GPU work may access it
+    /// meanwhile. See [`VideoSession::supports_linear_output`](crate::video::VideoSession::supports_linear_output)
+    /// for whether decodes can output such images.
+    pub fn map<R>(&self, f: impl FnOnce(&MappedImage<'_>) -> R) -> Result<R, Error> {
+        self.shared.map(f)
+    }
+
+    pub(crate) fn shared(&self) -> Arc<ImageShared> {
         self.shared.clone()
     }
 
@@ -260,6 +638,20 @@ impl Image {
         self.shared.native()
     }
 
+    /// The raw `VkImage`.
+    ///
+    /// Layout transitions you record yourself aren't tracked, transition back to the layout you found it in.
+    pub fn raw(&self) -> ash::vk::Image {
+        self.shared.native()
+    }
+
+    /// Names this image in validation messages and debugging tools, instead of the default `vulkan_video::Image#N`.
+    ///
+    /// Does nothing if `VK_EXT_debug_utils` isn't available.
+    pub fn set_name(&self, name: &str) -> Result<(), Error> {
+        self.shared.device().set_object_name(self.raw(), name)
+    }
+
     #[allow(unused)]
     pub(crate) fn device(&self) -> Arc<DeviceShared> {
         self.shared.shared_device.clone()
@@ -273,7 +665,10 @@ impl Image {
 #[cfg(test)]
 mod test {
     use crate::allocation::Allocation;
-    use ash::vk::{Extent3D, Format, ImageTiling, ImageType, ImageUsageFlags, SampleCountFlags};
+    use ash::vk::{
+        Extent3D, ExternalMemoryHandleTypeFlags, Format, ImageLayout, ImageTiling, ImageType, ImageUsageFlags, MemoryPropertyFlags,
+        SampleCountFlags,
+    };
 
     use crate::device::Device;
     use crate::error::Error;
@@ -298,11 +693,42 @@ mod test {
             .tiling(ImageTiling::OPTIMAL)
             .extent(Extent3D::default().width(512).height(512).depth(1));
         let image = Image::new(&device, &info)?;
-        let heap_index = image.memory_requirement().any_heap();
-        let allocation = Allocation::new(&device, 1024 * 1024, heap_index)?;
+        let allocation = Allocation::for_requirements(&device, &image.memory_requirement(), MemoryPropertyFlags::DEVICE_LOCAL)?;
 
         _ = image.bind(&allocation)?;
 
         Ok(())
     }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn crate_external_image() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+
+        if !device.video_features().external_memory_fd() {
+            return Ok(());
+        }
+
+        let info = ImageInfo::new()
+            .format(Format::G8_B8R8_2PLANE_420_UNORM)
+            .samples(SampleCountFlags::TYPE_1)
+            .usage(ImageUsageFlags::TRANSFER_SRC | ImageUsageFlags::TRANSFER_DST)
+            .mip_levels(1)
+            .array_layers(1)
+            .image_type(ImageType::TYPE_2D)
+            .tiling(ImageTiling::OPTIMAL)
+            .extent(Extent3D::default().width(512).height(512).depth(1))
+            .external_memory(ExternalMemoryHandleTypeFlags::OPAQUE_FD)
+            .external_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .queue_family_index(0);
+        let image = Image::new(&device, &info)?;
+
+        assert_eq!(image.info().get_external_layout(), ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        assert_eq!(image.info().get_queue_family_index(), Some(0));
+
+        Ok(())
+    }
 }
@@ -1,4 +1,3 @@
-use std::rc::Rc;
 use std::sync::Arc;
 
 use ash::vk::{Format, ImageAspectFlags, ImageSubresourceRange, ImageViewCreateInfo, ImageViewType};
@@ -14,6 +13,7 @@ pub struct ImageViewInfo {
     format: Format,
     image_view_type: ImageViewType,
     aspect_mask: ImageAspectFlags,
+    base_array_layer: u32,
     layer_count: u32,
     level_count: u32,
 }
@@ -38,6 +38,12 @@ impl ImageViewInfo {
         self
     }
 
+    /// First array layer of the image visible through this view.
+    pub fn base_array_layer(mut self, base_array_layer: u32) -> Self {
+        self.base_array_layer = base_array_layer;
+        self
+    }
+
     pub fn layer_count(mut self, layer_count: u32) -> Self {
         self.layer_count = layer_count;
         self
@@ -50,13 +56,14 @@ impl ImageViewInfo {
 }
 
 pub(crate) struct ImageViewShared {
-    shared_image: Rc<ImageShared>,
+    shared_image: Arc<ImageShared>,
     shared_device: Arc<DeviceShared>,
     native_view: ash::vk::ImageView,
+    subresource_range: ImageSubresourceRange,
 }
 
 impl ImageViewShared {
-    pub fn new(shared_image: Rc<ImageShared>, info: &ImageViewInfo) -> Result<Self, Error> {
+    pub fn new(shared_image: Arc<ImageShared>, info: &ImageViewInfo) -> Result<Self, Error> {
         let shared_device = shared_image.device();
 
         let native_image = shared_image.native();
@@ -64,6 +71,7 @@ impl ImageViewShared {
 
         let srr = ImageSubresourceRange::default()
             .aspect_mask(info.aspect_mask)
+            .base_array_layer(info.base_array_layer)
             .layer_count(info.layer_count)
             .level_count(info.level_count);
 
@@ -75,11 +83,13 @@ impl ImageViewShared {
 
         unsafe {
             let native_view = native_device.create_image_view(&create_image_view, None)?;
+            shared_device.set_default_name(native_view, "ImageView");
 
             Ok(ImageViewShared {
                 shared_device,
                 shared_image,
                 native_view,
+                subresource_range: srr,
             })
         }
     }
@@ -88,9 +98,14 @@ impl ImageViewShared {
         self.native_view
     }
 
-    pub(crate) fn image(&self) -> Rc<ImageShared> {
+    pub(crate) fn image(&self) -> Arc<ImageShared> {
         self.shared_image.clone()
     }
+
+    /// Part of the image this view covers.
+    pub(crate) fn subresource_range(&self) -> ImageSubresourceRange {
+        self.subresource_range
+    }
 }
 
 impl Drop for ImageViewShared {
@@ -105,7 +120,7 @@ impl Drop for ImageViewShared {
 
 /// View of an [`Image`](Image).
 pub struct ImageView {
-    shared_view: Rc<ImageViewShared>,
+    shared_view: Arc<ImageViewShared>,
 }
 
 impl ImageView {
@@ -113,18 +128,32 @@ impl ImageView {
         let shared_view = ImageViewShared::new(image.shared(), info)?;
 
         Ok(Self {
-            shared_view: Rc::new(shared_view),
+            shared_view: Arc::new(shared_view),
         })
     }
 
-    pub(crate) fn shared(&self) -> Rc<ImageViewShared> {
+    pub(crate) fn shared(&self) -> Arc<ImageViewShared> {
         self.shared_view.clone()
     }
 
+    /// The raw `VkImageView`, owned by this view.
+    pub fn raw(&self) -> ash::vk::ImageView {
+        self.shared_view.native()
+    }
+
+    /// Names this view in validation messages and debugging tools, instead of the default `vulkan_video::ImageView#N`.
+    ///
+    /// Does nothing if `VK_EXT_debug_utils` isn't available.
+    pub fn set_name(&self, name: &str) -> Result<(), Error> {
+        self.shared_view.image().device().set_object_name(self.raw(), name)
+    }
+
+    #[allow(unused)]
     pub(crate) fn native(&self) -> ash::vk::ImageView {
         self.shared_view.native()
     }
 
+    #[allow(unused)]
     pub(crate) fn native_image(&self) -> ash::vk::Image {
         self.shared_view.shared_image.native()
     }
@@ -133,7 +162,9 @@ impl ImageView {
 #[cfg(test)]
 mod test {
     use crate::allocation::Allocation;
-    use ash::vk::{Extent3D, Format, ImageAspectFlags, ImageTiling, ImageType, ImageUsageFlags, ImageViewType, SampleCountFlags};
+    use ash::vk::{
+        Extent3D, Format, ImageAspectFlags, ImageTiling, ImageType, ImageUsageFlags, ImageViewType, MemoryPropertyFlags, SampleCountFlags,
+    };
 
     use crate::device::Device;
     use crate::error::Error;
@@ -159,8 +190,7 @@ mod test {
             .extent(Extent3D::default().width(512).height(512).depth(1));
 
         let image = Image::new(&device, &image_info)?;
-        let heap_type = image.memory_requirement().any_heap();
-        let allocation = Allocation::new(&device, 1024 * 1024, heap_type)?;
+        let allocation = Allocation::for_requirements(&device, &image.memory_requirement(), MemoryPropertyFlags::DEVICE_LOCAL)?;
 
         let image = image.bind(&allocation)?;
 
@@ -175,4 +205,44 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn create_image_view_per_layer() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let image_info = ImageInfo::new()
+            .format(Format::R8_UNORM)
+            .samples(SampleCountFlags::TYPE_1)
+            .usage(ImageUsageFlags::TRANSFER_SRC | ImageUsageFlags::TRANSFER_DST | ImageUsageFlags::SAMPLED)
+            .mip_levels(1)
+            .array_layers(4)
+            .image_type(ImageType::TYPE_2D)
+            .tiling(ImageTiling::OPTIMAL)
+            .extent(Extent3D::default().width(512).height(512).depth(1));
+
+        let image = Image::new(&device, &image_info)?;
+        let requirements = image.memory_requirement();
+        let allocation = Allocation::new(&device, requirements.size(), requirements.any_heap())?;
+
+        let image = image.bind(&allocation)?;
+
+        for layer in 0..4 {
+            let image_view_info = ImageViewInfo::new()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .format(Format::R8_UNORM)
+                .image_view_type(ImageViewType::TYPE_2D)
+                .base_array_layer(layer)
+                .layer_count(1)
+                .level_count(1);
+
+            let view = ImageView::new(&image, &image_view_info)?;
+
+            assert_eq!(view.shared().subresource_range().base_array_layer, layer);
+        }
+
+        Ok(())
+    }
 }
@@ -3,11 +3,14 @@
 mod buffer;
 mod image;
 mod imageview;
+mod querypool;
 
 pub use buffer::{Buffer, BufferInfo};
-pub use image::{Image, ImageInfo};
+pub use image::{Image, ImageInfo, MappedImage, MemoryRequirements};
 pub use imageview::{ImageView, ImageViewInfo};
+pub use querypool::QueryPool;
 
 pub(crate) use buffer::BufferShared;
-pub(crate) use image::ImageShared;
+pub(crate) use image::{plane_extent, ImageShared};
 pub(crate) use imageview::ImageViewShared;
+pub(crate) use querypool::QueryPoolShared;
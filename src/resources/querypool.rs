@@ -0,0 +1,192 @@
+use crate::device::{Device, DeviceShared};
+use crate::error;
+use crate::error::{Error, Variant};
+use ash::vk::{QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+pub(crate) struct QueryPoolShared {
+    shared_device: Arc<DeviceShared>,
+    native_query_pool: ash::vk::QueryPool,
+    count: u32,
+    /// Valid bits of queries reset and written by some command buffer, reading others is not allowed.
+    recorded: Mutex<Vec<Option<u32>>>,
+}
+
+impl QueryPoolShared {
+    pub fn new_timestamps(shared_device: Arc<DeviceShared>, count: u32) -> Result<Self, Error> {
+        let native_device = shared_device.native();
+
+        let create_info = QueryPoolCreateInfo::default().query_type(QueryType::TIMESTAMP).query_count(count);
+
+        unsafe {
+            let native_query_pool = native_device.create_query_pool(&create_info, None)?;
+
+            Ok(Self {
+                shared_device,
+                native_query_pool,
+                count,
+                recorded: Mutex::new(vec![None; count as usize]),
+            })
+        }
+    }
+
+    pub(crate) fn native(&self) -> ash::vk::QueryPool {
+        self.native_query_pool
+    }
+
+    pub(crate) fn device(&self) -> Arc<DeviceShared> {
+        self.shared_device.clone()
+    }
+
+    pub(crate) fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Fails if `query` is not part of this pool.
+    pub(crate) fn check_query(&self, query: u32) -> Result<(), Error> {
+        if query >= self.count {
+            return Err(error!(Variant::QueryOutOfRange, "Query {query} not in pool of {}.", self.count));
+        }
+
+        Ok(())
+    }
+
+    /// Notes `query` was reset and written in a command buffer, by a queue family with `valid_bits` timestamp bits.
+    pub(crate) fn set_recorded(&self, query: u32, valid_bits: u32) {
+        let mut recorded = self.recorded.lock().unwrap_or_else(PoisonError::into_inner);
+
+        recorded[query as usize] = Some(valid_bits);
+    }
+
+    /// Timestamp bits the queue family writing `query` reported, `None` if not written.
+    pub(crate) fn valid_bits(&self, query: u32) -> Option<u32> {
+        self.recorded.lock().unwrap_or_else(PoisonError::into_inner)[query as usize]
+    }
+
+    pub fn ticks(&self) -> Result<Vec<Option<u64>>, Error> {
+        let native_device = self.shared_device.native();
+        let recorded = self.recorded.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let flags = QueryResultFlags::TYPE_64 | QueryResultFlags::WITH_AVAILABILITY;
+        let mut ticks = vec![None; self.count as usize];
+
+        for (query, _) in recorded.iter().enumerate().filter(|x| x.1.is_some()) {
+            let mut result = [[0_u64; 2]];
+
+            unsafe {
+                // Queries not being available yet is fine, we report them as `None`.
+                match native_device.get_query_pool_results(self.native_query_pool, query as u32, &mut result, flags) {
+                    Ok(()) | Err(ash::vk::Result::NOT_READY) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            let [value, available] = result[0];
+            ticks[query] = (available != 0).then_some(value);
+        }
+
+        Ok(ticks)
+    }
+}
+
+impl Drop for QueryPoolShared {
+    fn drop(&mut self) {
+        let native_device = self.shared_device.native();
+
+        unsafe {
+            native_device.destroy_query_pool(self.native_query_pool, None);
+        }
+    }
+}
+
+/// GPU timestamps, written by [`WriteTimestamp`](crate::ops::WriteTimestamp) to measure how long operations take.
+///
+/// ```rust,ignore
+/// let queries = QueryPool::new(&device, 2)?;
+///
+/// queue.build_and_submit(&command_buffer, |x| {
+///     WriteTimestamp::new(&queries, 0).run_in(x)?;
+///     decode.run_in(x)?;
+///     WriteTimestamp::new(&queries, 1).run_in(x)
+/// })?;
+///
+/// let elapsed = queries.elapsed(0, 1)?;
+/// ```
+pub struct QueryPool {
+    shared: Arc<QueryPoolShared>,
+    timestamp_period: f32,
+}
+
+impl QueryPool {
+    /// Creates a pool of `count` timestamp queries.
+    pub fn new(device: &Device, count: u32) -> Result<Self, Error> {
+        let shared_device = device.shared();
+        let timestamp_period = shared_device.physical_device().timestamp_period();
+        let shared = QueryPoolShared::new_timestamps(shared_device, count)?;
+
+        Ok(Self {
+            shared: Arc::new(shared),
+            timestamp_period,
+        })
+    }
+
+    pub(crate) fn shared(&self) -> Arc<QueryPoolShared> {
+        self.shared.clone()
+    }
+
+    /// The raw `VkQueryPool`.
+    pub fn raw(&self) -> ash::vk::QueryPool {
+        self.shared.native()
+    }
+
+    pub fn count(&self) -> u32 {
+        self.shared.count()
+    }
+
+    /// Raw timestamps of all queries in device ticks, `None` for the ones not written yet.
+    pub fn ticks(&self) -> Result<Vec<Option<u64>>, Error> {
+        self.shared.ticks()
+    }
+
+    /// Time between the timestamps written to queries `start` and `end`, `None` if either was not written yet.
+    ///
+    /// Timestamps only have [`timestamp_valid_bits`](crate::QueueFamilyInfo::timestamp_valid_bits), so the counter
+    /// wrapping once between both is accounted for.
+    pub fn elapsed(&self, start: u32, end: u32) -> Result<Option<Duration>, Error> {
+        self.shared.check_query(start)?;
+        self.shared.check_query(end)?;
+
+        let ticks = self.ticks()?;
+        let valid_bits = self.shared.valid_bits(start).zip(self.shared.valid_bits(end));
+
+        let Some(((start, end), (start_bits, end_bits))) = ticks[start as usize].zip(ticks[end as usize]).zip(valid_bits) else {
+            return Ok(None);
+        };
+
+        let nanos = elapsed_ticks(start, end, start_bits.min(end_bits)) as f64 * self.timestamp_period as f64;
+
+        Ok(Some(Duration::from_nanos(nanos as u64)))
+    }
+}
+
+/// Ticks from `start` to `end`, of which only the lower `valid_bits` are meaningful, e.g., after the counter wrapped.
+fn elapsed_ticks(start: u64, end: u64, valid_bits: u32) -> u64 {
+    let mask = u64::MAX.checked_shr(64 - valid_bits.min(64)).unwrap_or(0);
+
+    (end & mask).wrapping_sub(start & mask) & mask
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resources::querypool::elapsed_ticks;
+
+    #[test]
+    fn elapsed_ticks_wrap() {
+        assert_eq!(elapsed_ticks(10, 25, 64), 15);
+        assert_eq!(elapsed_ticks(u64::MAX - 4, 5, 64), 10);
+        assert_eq!(elapsed_ticks((1 << 36) - 4, 6, 36), 10);
+
+        // Bits above the valid ones are ignored.
+        assert_eq!(elapsed_ticks(0xff00_0000_0000_0010, 0x0000_0000_0000_0020, 36), 0x10);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::allocation::ExternalHandle;
+use crate::device::{Device, DeviceShared};
+use crate::error;
+use crate::error::{Error, Variant};
+#[cfg(windows)]
+use ash::vk::SemaphoreGetWin32HandleInfoKHR;
+use ash::vk::{
+    ExportSemaphoreCreateInfo, ExternalSemaphoreHandleTypeFlags, ImportSemaphoreWin32HandleInfoKHR, SemaphoreCreateInfo,
+    SemaphoreSignalInfo, SemaphoreType, SemaphoreTypeCreateInfo, SemaphoreWaitInfo, HANDLE,
+};
+#[cfg(unix)]
+use ash::vk::{ImportSemaphoreFdInfoKHR, SemaphoreGetFdInfoKHR};
+use std::sync::Arc;
+
+/// Handle type semaphores are exported as, see [`EXTERNAL_HANDLE_TYPE`](crate::EXTERNAL_HANDLE_TYPE).
+#[cfg(unix)]
+pub const EXTERNAL_SEMAPHORE_HANDLE_TYPE: ExternalSemaphoreHandleTypeFlags = ExternalSemaphoreHandleTypeFlags::OPAQUE_FD;
+
+/// Handle type semaphores are exported as, see [`EXTERNAL_HANDLE_TYPE`](crate::EXTERNAL_HANDLE_TYPE).
+#[cfg(windows)]
+pub const EXTERNAL_SEMAPHORE_HANDLE_TYPE: ExternalSemaphoreHandleTypeFlags = ExternalSemaphoreHandleTypeFlags::OPAQUE_WIN32;
+
+pub(crate) struct SemaphoreShared {
+    shared_device: Arc<DeviceShared>,
+    native_semaphore: ash::vk::Semaphore,
+    exportable: bool,
+}
+
+impl SemaphoreShared {
+    pub fn new(shared_device: Arc<DeviceShared>, initial_value: u64) -> Result<Self, Error> {
+        Self::new_with_handle_types(shared_device, initial_value, ExternalSemaphoreHandleTypeFlags::empty())
+    }
+
+    pub fn new_exportable(shared_device: Arc<DeviceShared>, initial_value: u64) -> Result<Self, Error> {
+        if !shared_device.video_features().external_semaphore() {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "No external semaphore extension for {EXTERNAL_SEMAPHORE_HANDLE_TYPE:?}."
+            ));
+        }
+
+        Self::new_with_handle_types(shared_device, initial_value, EXTERNAL_SEMAPHORE_HANDLE_TYPE)
+    }
+
+    fn new_with_handle_types(
+        shared_device: Arc<DeviceShared>,
+        initial_value: u64,
+        handle_types: ExternalSemaphoreHandleTypeFlags,
+    ) -> Result<Self, Error> {
+        if !shared_device.video_features().timeline_semaphore() {
+            return Err(error!(Variant::FeatureNotSupported, "Timeline semaphores are not available."));
+        }
+
+        let native_device = shared_device.native();
+        let mut type_info = SemaphoreTypeCreateInfo::default()
+            .semaphore_type(SemaphoreType::TIMELINE)
+            .initial_value(initial_value);
+        let mut export_info = ExportSemaphoreCreateInfo::default().handle_types(handle_types);
+        let info = SemaphoreCreateInfo::default().push_next(&mut type_info);
+        let info = match handle_types.is_empty() {
+            true => info,
+            false => info.push_next(&mut export_info),
+        };
+
+        let native_semaphore = unsafe { native_device.create_semaphore(&info, None)? };
+
+        Ok(Self {
+            shared_device,
+            native_semaphore,
+            exportable: !handle_types.is_empty(),
+        })
+    }
+
+    pub fn import_handle(shared_device: Arc<DeviceShared>, handle: ExternalHandle) -> Result<Self, Error> {
+        if !shared_device.video_features().external_semaphore() {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "No external semaphore extension for {EXTERNAL_SEMAPHORE_HANDLE_TYPE:?}."
+            ));
+        }
+
+        let semaphore = Self::new(shared_device, 0)?;
+
+        // SAFETY: Should be safe as the semaphore is valid, and the handle owned.
+        unsafe { import(&semaphore.shared_device, semaphore.native_semaphore, handle)? };
+
+        Ok(semaphore)
+    }
+
+    pub fn export_handle(&self) -> Result<ExternalHandle, Error> {
+        if !self.exportable {
+            return Err(error!(Variant::FeatureNotSupported, "Semaphore was not created exportable."));
+        }
+
+        // SAFETY: Should be safe as the semaphore is valid and exportable.
+        unsafe { export(&self.shared_device, self.native_semaphore) }
+    }
+
+    pub unsafe fn import_win32_handle(
+        shared_device: Arc<DeviceShared>,
+        handle: HANDLE,
+        handle_type: ExternalSemaphoreHandleTypeFlags,
+    ) -> Result<Self, Error> {
+        let functions = shared_device
+            .external_semaphore_win32()
+            .ok_or_else(|| error!(Variant::FeatureNotSupported, "VK_KHR_external_semaphore_win32 is not available."))?
+            .clone();
+
+        // Imports replace the payload of an existing semaphore, which must have the right type already.
+        let semaphore = Self::new(shared_device, 0)?;
+        let info = ImportSemaphoreWin32HandleInfoKHR::default()
+            .semaphore(semaphore.native_semaphore)
+            .handle_type(handle_type)
+            .handle(handle);
+
+        functions.import_semaphore_win32_handle(&info)?;
+
+        Ok(semaphore)
+    }
+
+    pub(crate) fn native(&self) -> ash::vk::Semaphore {
+        self.native_semaphore
+    }
+
+    pub fn value(&self) -> Result<u64, Error> {
+        let native_device = self.shared_device.native();
+
+        Ok(unsafe { native_device.get_semaphore_counter_value(self.native_semaphore)? })
+    }
+
+    pub fn wait(&self, value: u64, timeout_ns: u64) -> Result<(), Error> {
+        let native_device = self.shared_device.native();
+        let semaphores = [self.native_semaphore];
+        let values = [value];
+        let info = SemaphoreWaitInfo::default().semaphores(&semaphores).values(&values);
+
+        unsafe { native_device.wait_semaphores(&info, timeout_ns)? };
+
+        Ok(())
+    }
+
+    pub fn signal(&self, value: u64) -> Result<(), Error> {
+        let native_device = self.shared_device.native();
+        let info = SemaphoreSignalInfo::default().semaphore(self.native_semaphore).value(value);
+
+        unsafe { native_device.signal_semaphore(&info)? };
+
+        Ok(())
+    }
+}
+
+/// Exports `semaphore` as a new file descriptor we then own.
+#[cfg(unix)]
+unsafe fn export(shared_device: &DeviceShared, semaphore: ash::vk::Semaphore) -> Result<ExternalHandle, Error> {
+    use std::os::fd::FromRawFd;
+
+    let functions = shared_device
+        .external_semaphore_fd()
+        .ok_or_else(|| error!(Variant::FeatureNotSupported, "VK_KHR_external_semaphore_fd is not available."))?;
+
+    let info = SemaphoreGetFdInfoKHR::default()
+        .semaphore(semaphore)
+        .handle_type(EXTERNAL_SEMAPHORE_HANDLE_TYPE);
+    let fd = functions.get_semaphore_fd(&info)?;
+
+    Ok(ExternalHandle::from_raw_fd(fd))
+}
+
+/// Exports `semaphore` as a new `HANDLE` we then own.
+#[cfg(windows)]
+unsafe fn export(shared_device: &DeviceShared, semaphore: ash::vk::Semaphore) -> Result<ExternalHandle, Error> {
+    use std::os::windows::io::FromRawHandle;
+
+    let functions = shared_device
+        .external_semaphore_win32()
+        .ok_or_else(|| error!(Variant::FeatureNotSupported, "VK_KHR_external_semaphore_win32 is not available."))?;
+
+    let info = SemaphoreGetWin32HandleInfoKHR::default()
+        .semaphore(semaphore)
+        .handle_type(EXTERNAL_SEMAPHORE_HANDLE_TYPE);
+    let handle = functions.get_semaphore_win32_handle(&info)?;
+
+    Ok(ExternalHandle::from_raw_handle(handle as _))
+}
+
+/// Imports `handle` into `semaphore`, Vulkan owns the file descriptor once that succeeded.
+#[cfg(unix)]
+unsafe fn import(shared_device: &DeviceShared, semaphore: ash::vk::Semaphore, handle: ExternalHandle) -> Result<(), Error> {
+    use std::os::fd::{FromRawFd, IntoRawFd};
+
+    let functions = shared_device
+        .external_semaphore_fd()
+        .ok_or_else(|| error!(Variant::FeatureNotSupported, "VK_KHR_external_semaphore_fd is not available."))?;
+
+    let fd = handle.into_raw_fd();
+    let info = ImportSemaphoreFdInfoKHR::default()
+        .semaphore(semaphore)
+        .handle_type(EXTERNAL_SEMAPHORE_HANDLE_TYPE)
+        .fd(fd);
+
+    functions.import_semaphore_fd(&info).map_err(|e| {
+        drop(ExternalHandle::from_raw_fd(fd));
+        e.into()
+    })
+}
+
+/// Imports `handle` into `semaphore`, Vulkan never owns Windows handles, so it's closed once dropped.
+#[cfg(windows)]
+unsafe fn import(shared_device: &DeviceShared, semaphore: ash::vk::Semaphore, handle: ExternalHandle) -> Result<(), Error> {
+    use std::os::windows::io::AsRawHandle;
+
+    let functions = shared_device
+        .external_semaphore_win32()
+        .ok_or_else(|| error!(Variant::FeatureNotSupported, "VK_KHR_external_semaphore_win32 is not available."))?;
+
+    let info = ImportSemaphoreWin32HandleInfoKHR::default()
+        .semaphore(semaphore)
+        .handle_type(EXTERNAL_SEMAPHORE_HANDLE_TYPE)
+        .handle(handle.as_raw_handle() as HANDLE);
+
+    Ok(functions.import_semaphore_win32_handle(&info)?)
+}
+
+impl Drop for SemaphoreShared {
+    fn drop(&mut self) {
+        let native_device = self.shared_device.native();
+
+        unsafe {
+            native_device.destroy_semaphore(self.native_semaphore, None);
+        }
+    }
+}
+
+/// A timeline semaphore, to order submissions against each other or against other APIs sharing it.
+///
+/// Submissions wait for, or signal, a value of the semaphore's counter, see [`SubmitInfo`](crate::SubmitInfo).
+pub struct Semaphore {
+    shared: Arc<SemaphoreShared>,
+}
+
+impl Semaphore {
+    /// Creates a semaphore with its counter at `initial_value`, needs [`VideoFeatures::timeline_semaphore`](crate::VideoFeatures::timeline_semaphore).
+    pub fn new(device: &Device, initial_value: u64) -> Result<Self, Error> {
+        let shared = SemaphoreShared::new(device.shared(), initial_value)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    /// Creates a semaphore that can be shared with other processes via [`export_handle`](Self::export_handle).
+    ///
+    /// Needs [`VideoFeatures::external_semaphore`](crate::VideoFeatures::external_semaphore).
+    pub fn new_exportable(device: &Device, initial_value: u64) -> Result<Self, Error> {
+        let shared = SemaphoreShared::new_exportable(device.shared(), initial_value)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    /// Imports a semaphore another process exported via [`export_handle`](Self::export_handle).
+    pub fn import_handle(device: &Device, handle: ExternalHandle) -> Result<Self, Error> {
+        let shared = SemaphoreShared::import_handle(device.shared(), handle)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    /// Exports a new OS handle to this semaphore. Only works for semaphores created with [`new_exportable`](Self::new_exportable).
+    pub fn export_handle(&self) -> Result<ExternalHandle, Error> {
+        self.shared.export_handle()
+    }
+
+    /// Imports a semaphore shared by another API, e.g., an `ID3D12Fence` as `D3D12_FENCE`.
+    ///
+    /// Needs [`VideoFeatures::external_semaphore_win32`](crate::VideoFeatures::external_semaphore_win32).
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be a valid handle of `handle_type`. Vulkan does not take ownership, so the caller still has to close it.
+    pub unsafe fn import_win32_handle(
+        device: &Device,
+        handle: HANDLE,
+        handle_type: ExternalSemaphoreHandleTypeFlags,
+    ) -> Result<Self, Error> {
+        let shared = SemaphoreShared::import_win32_handle(device.shared(), handle, handle_type)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    /// Current value of the counter.
+    pub fn value(&self) -> Result<u64, Error> {
+        self.shared.value()
+    }
+
+    /// Blocks until the counter reached `value`, or fails with `TIMEOUT` after `timeout_ns`.
+    pub fn wait(&self, value: u64, timeout_ns: u64) -> Result<(), Error> {
+        self.shared.wait(value, timeout_ns)
+    }
+
+    /// Sets the counter to `value` from the host, which must be larger than its current value.
+    pub fn signal(&self, value: u64) -> Result<(), Error> {
+        self.shared.signal(value)
+    }
+
+    pub(crate) fn shared(&self) -> Arc<SemaphoreShared> {
+        self.shared.clone()
+    }
+
+    /// The raw `VkSemaphore`, e.g., to wait for it in your own submissions.
+    pub fn raw(&self) -> ash::vk::Semaphore {
+        self.shared.native()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::device::Device;
+    use crate::error::Error;
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::semaphore::Semaphore;
+
+    #[test]
+    #[cfg(not(miri))]
+    fn host_signal_wait() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let semaphore = Semaphore::new(&device, 1)?;
+
+        assert_eq!(semaphore.value()?, 1);
+
+        semaphore.signal(5)?;
+        semaphore.wait(5, 0)?;
+
+        assert_eq!(semaphore.value()?, 5);
+        assert!(semaphore.wait(6, 0).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use crate::device::{Device, DeviceShared};
+use crate::error::Error;
+use ash::vk::PipelineCacheCreateInfo;
+use std::sync::Arc;
+
+pub(crate) struct PipelineCacheShared {
+    shared_device: Arc<DeviceShared>,
+    native_cache: ash::vk::PipelineCache,
+}
+
+impl PipelineCacheShared {
+    pub fn new(shared_device: Arc<DeviceShared>, data: &[u8]) -> Result<Self, Error> {
+        let native_device = shared_device.native();
+        let create_info = PipelineCacheCreateInfo::default().initial_data(data);
+
+        unsafe {
+            let native_cache = native_device.create_pipeline_cache(&create_info, None)?;
+
+            Ok(Self {
+                shared_device,
+                native_cache,
+            })
+        }
+    }
+
+    pub(crate) fn native(&self) -> ash::vk::PipelineCache {
+        self.native_cache
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>, Error> {
+        let native_device = self.shared_device.native();
+
+        unsafe { Ok(native_device.get_pipeline_cache_data(self.native_cache)?) }
+    }
+}
+
+impl Drop for PipelineCacheShared {
+    fn drop(&mut self) {
+        let native_device = self.shared_device.native();
+
+        unsafe {
+            native_device.destroy_pipeline_cache(self.native_cache, None);
+        }
+    }
+}
+
+/// Speeds up creating [`Pipeline`](crate::shader::Pipeline)s, also across runs when persisted.
+///
+/// ```rust,ignore
+/// let data = std::fs::read("pipelines.bin").unwrap_or_default();
+/// let cache = PipelineCache::load(&device, &data)?;
+/// let pipeline = Pipeline::new_with_cache(&device, &shader, &cache, &SpecializationConstants::new())?;
+///
+/// std::fs::write("pipelines.bin", cache.serialize()?)?;
+/// ```
+pub struct PipelineCache {
+    shared: Arc<PipelineCacheShared>,
+}
+
+impl PipelineCache {
+    /// Creates an empty cache.
+    pub fn new(device: &Device) -> Result<Self, Error> {
+        Self::load(device, &[])
+    }
+
+    /// Creates a cache from data returned by [`Self::serialize`].
+    ///
+    /// Data from another device or driver version is ignored, resulting in an empty cache.
+    pub fn load(device: &Device, data: &[u8]) -> Result<Self, Error> {
+        let shared = PipelineCacheShared::new(device.shared(), data)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    pub(crate) fn shared(&self) -> Arc<PipelineCacheShared> {
+        self.shared.clone()
+    }
+
+    /// The raw `VkPipelineCache`, e.g., to create your own pipelines with it.
+    pub fn raw(&self) -> ash::vk::PipelineCache {
+        self.shared.native()
+    }
+
+    /// Returns the cache contents, e.g., to store them on disk.
+    pub fn serialize(&self) -> Result<Vec<u8>, Error> {
+        self.shared.serialize()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::device::Device;
+    use crate::error::Error;
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::resources::Buffer;
+    use crate::shader::{Parameters, Pipeline, PipelineCache, Shader, SpecializationConstants};
+
+    #[test]
+    #[cfg(not(miri))]
+    fn serialize_and_load() -> Result<(), Error> {
+        let shader_code = include_bytes!("../../tests/shaders/compiled/hello_world.spv");
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let parameters = Parameters::<(&Buffer, &Buffer, &Buffer)>::new(&device)?;
+        let shader = Shader::new(&device, shader_code, "main", &parameters)?;
+        let constants = SpecializationConstants::new();
+
+        let cache = PipelineCache::new(&device)?;
+        _ = Pipeline::new_with_cache(&device, &shader, &cache, &constants)?;
+        let data = cache.serialize()?;
+
+        let cache = PipelineCache::load(&device, &data)?;
+        _ = Pipeline::new_with_cache(&device, &shader, &cache, &constants)?;
+
+        // Garbage is ignored, not an error.
+        _ = PipelineCache::load(&device, &[1, 2, 3, 4])?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use crate::device::DeviceShared;
+use crate::error::Error;
+use ash::vk::{
+    DescriptorPool, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout,
+    DescriptorType,
+};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Descriptor sets allocated per pool, more pools are created as needed.
+const SETS_PER_POOL: u32 = 16;
+
+struct Pools {
+    pools: Vec<DescriptorPool>,
+    /// Sets still available in the last pool.
+    remaining: u32,
+    /// Sets handed back, ready for reuse.
+    free: Vec<DescriptorSet>,
+}
+
+/// Hands out descriptor sets of one layout, recycling them instead of creating a pool for each.
+pub(crate) struct DescriptorAllocator {
+    shared_device: Arc<DeviceShared>,
+    native_layout: DescriptorSetLayout,
+    pool_sizes: Vec<DescriptorPoolSize>,
+    pools: Mutex<Pools>,
+}
+
+impl DescriptorAllocator {
+    /// Creates an allocator for sets of `native_layout`, which has bindings of `descriptor_types`.
+    ///
+    /// The layout must outlive the allocator.
+    pub fn new(shared_device: Arc<DeviceShared>, native_layout: DescriptorSetLayout, descriptor_types: &[DescriptorType]) -> Self {
+        Self {
+            shared_device,
+            native_layout,
+            pool_sizes: pool_sizes(descriptor_types, SETS_PER_POOL),
+            pools: Mutex::new(Pools {
+                pools: Vec::new(),
+                remaining: 0,
+                free: Vec::new(),
+            }),
+        }
+    }
+
+    /// Returns a set, which should be given back via [`Self::release`] once no command buffer uses it anymore.
+    pub fn allocate(&self) -> Result<DescriptorSet, Error> {
+        let native_device = self.shared_device.native();
+        let mut pools = self.pools.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(set) = pools.free.pop() {
+            return Ok(set);
+        }
+
+        unsafe {
+            if pools.remaining == 0 {
+                let create_info = DescriptorPoolCreateInfo::default()
+                    .pool_sizes(&self.pool_sizes)
+                    .max_sets(SETS_PER_POOL);
+
+                let pool = native_device.create_descriptor_pool(&create_info, None)?;
+
+                pools.pools.push(pool);
+                pools.remaining = SETS_PER_POOL;
+            }
+
+            let layouts = [self.native_layout];
+            let allocate_info = DescriptorSetAllocateInfo::default()
+                .descriptor_pool(*pools.pools.last().expect("Pool was just created."))
+                .set_layouts(&layouts);
+
+            let set = native_device.allocate_descriptor_sets(&allocate_info)?[0];
+
+            pools.remaining -= 1;
+
+            Ok(set)
+        }
+    }
+
+    /// Gives `set` back for reuse.
+    pub fn release(&self, set: DescriptorSet) {
+        let mut pools = self.pools.lock().unwrap_or_else(PoisonError::into_inner);
+
+        pools.free.push(set);
+    }
+}
+
+impl Drop for DescriptorAllocator {
+    fn drop(&mut self) {
+        let native_device = self.shared_device.native();
+        let pools = self.pools.get_mut().unwrap_or_else(PoisonError::into_inner);
+
+        unsafe {
+            for pool in pools.pools.drain(..) {
+                native_device.destroy_descriptor_pool(pool, None);
+            }
+        }
+    }
+}
+
+/// Pool sizes to fit `sets` sets with bindings of `descriptor_types` each.
+fn pool_sizes(descriptor_types: &[DescriptorType], sets: u32) -> Vec<DescriptorPoolSize> {
+    let mut pool_sizes = Vec::<DescriptorPoolSize>::new();
+
+    for t in descriptor_types {
+        match pool_sizes.iter_mut().find(|x| x.ty == *t) {
+            Some(x) => x.descriptor_count += sets,
+            None => pool_sizes.push(DescriptorPoolSize::default().ty(*t).descriptor_count(sets)),
+        }
+    }
+
+    pool_sizes
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shader::descriptors::pool_sizes;
+    use ash::vk::DescriptorType;
+
+    #[test]
+    fn pool_sizes_per_type() {
+        let types = [
+            DescriptorType::STORAGE_BUFFER,
+            DescriptorType::STORAGE_IMAGE,
+            DescriptorType::STORAGE_BUFFER,
+        ];
+        let sizes = pool_sizes(&types, 4);
+
+        assert_eq!(sizes.len(), 2);
+        assert_eq!((sizes[0].ty, sizes[0].descriptor_count), (DescriptorType::STORAGE_BUFFER, 8));
+        assert_eq!((sizes[1].ty, sizes[1].descriptor_count), (DescriptorType::STORAGE_IMAGE, 4));
+        assert!(pool_sizes(&[], 4).is_empty());
+    }
+}
@@ -2,14 +2,31 @@
 
 #![allow(unused_imports)]
 
+mod cache;
+mod descriptors;
 mod parameters;
 mod pipeline;
+#[cfg(feature = "reflection")]
+mod reflection;
+#[cfg(feature = "rust-gpu")]
+mod rustgpu;
 mod shader;
+mod specialization;
 
+pub use cache::PipelineCache;
 pub use parameters::Parameters;
 pub use pipeline::Pipeline;
+#[cfg(feature = "reflection")]
+pub use reflection::{reflect_bindings, reflect_compute_entry_points, ReflectedBinding};
+#[cfg(feature = "rust-gpu")]
+pub use rustgpu::RustGpuModule;
 pub use shader::Shader;
+pub use specialization::SpecializationConstants;
 
+pub(crate) use cache::PipelineCacheShared;
+pub(crate) use descriptors::DescriptorAllocator;
 pub(crate) use parameters::{ParameterType, ParametersShared, ShaderParameter, ShaderParameterSet};
 pub(crate) use pipeline::PipelineShared;
+#[cfg(feature = "reflection")]
+pub(crate) use reflection::validate_bindings;
 pub(crate) use shader::ShaderShared;
@@ -1,21 +1,22 @@
 use std::marker::PhantomData;
 use std::sync::Arc;
 
-use ash::vk::{DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, ShaderStageFlags};
+use ash::vk::{
+    CommandBuffer, DescriptorBufferInfo, DescriptorImageInfo, DescriptorSet, DescriptorSetLayout, DescriptorSetLayoutBinding,
+    DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo, DescriptorType, DescriptorUpdateTemplate,
+    DescriptorUpdateTemplateCreateInfo, DescriptorUpdateTemplateEntry, DescriptorUpdateTemplateType, ImageLayout, PipelineBindPoint,
+    PipelineLayout, ShaderStageFlags, WriteDescriptorSet,
+};
 
 use crate::device::{Device, DeviceShared};
 use crate::error::Error;
-use crate::resources::{Buffer, ImageView};
+use crate::resources::{Buffer, BufferShared, ImageView, ImageViewShared};
 
+/// A shader parameter, with the resource so commands can synchronize with other accesses to it.
+#[allow(private_interfaces)]
 pub enum ParameterType {
-    Buffer {
-        native: ash::vk::Buffer,
-        size: u64,
-    },
-    ImageView {
-        native_view: ash::vk::ImageView,
-        native_image: ash::vk::Image,
-    },
+    Buffer(Arc<BufferShared>),
+    ImageView(Arc<ImageViewShared>),
 }
 
 pub trait ShaderParameter {
@@ -25,10 +26,7 @@ pub trait ShaderParameter {
 }
 impl ShaderParameter for Buffer {
     fn parameter_type(&self) -> ParameterType {
-        ParameterType::Buffer {
-            native: self.shared().native(),
-            size: self.size(),
-        }
+        ParameterType::Buffer(self.shared())
     }
 
     fn descrtiptor_type() -> DescriptorType {
@@ -38,10 +36,7 @@ impl ShaderParameter for Buffer {
 
 impl ShaderParameter for ImageView {
     fn parameter_type(&self) -> ParameterType {
-        let native_image = self.native_image();
-        let native_view = self.native();
-
-        ParameterType::ImageView { native_view, native_image }
+        ParameterType::ImageView(self.shared())
     }
 
     fn descrtiptor_type() -> DescriptorType {
@@ -78,6 +73,20 @@ where
     }
 }
 
+impl<T0, T1> ShaderParameterSet for (&T0, &T1)
+where
+    T0: ShaderParameter,
+    T1: ShaderParameter,
+{
+    fn parameter_types(&self) -> Vec<ParameterType> {
+        vec![self.0.parameter_type(), self.1.parameter_type()]
+    }
+
+    fn descriptor_types() -> Vec<DescriptorType> {
+        vec![T0::descrtiptor_type(), T1::descrtiptor_type()]
+    }
+}
+
 impl<T0, T1, T2> ShaderParameterSet for (&T0, &T1, &T2)
 where
     T0: ShaderParameter,
@@ -93,9 +102,21 @@ where
     }
 }
 
+/// One binding's worth of data for a descriptor update template.
+#[repr(C)]
+#[derive(Clone, Copy)]
+union DescriptorData {
+    buffer: DescriptorBufferInfo,
+    image: DescriptorImageInfo,
+}
+
 pub(crate) struct ParametersShared<T> {
     shared_device: Arc<DeviceShared>,
     descriptor_set_layout: DescriptorSetLayout,
+    /// `None` without parameters, as templates need at least one entry, or when pushing descriptors.
+    update_template: Option<DescriptorUpdateTemplate>,
+    /// If parameters are pushed into command buffers, instead of written to allocated sets.
+    push_descriptors: bool,
     _phantom: PhantomData<T>,
 }
 
@@ -116,14 +137,53 @@ impl<T: ShaderParameterSet> ParametersShared<T> {
             bindings.push(binding);
         }
 
-        let create_info = DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+        // Push descriptor layouts can't be allocated from, so it's either one or the other.
+        let push_descriptors = shared_device.push_descriptor().is_some();
+        let flags = match push_descriptors {
+            true => DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR,
+            false => DescriptorSetLayoutCreateFlags::empty(),
+        };
+
+        let create_info = DescriptorSetLayoutCreateInfo::default().bindings(&bindings).flags(flags);
+
+        // Writes all bindings in one call, from an array with one `DescriptorData` per binding.
+        let template_entries = descriptor_types
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                DescriptorUpdateTemplateEntry::default()
+                    .dst_binding(i as u32)
+                    .descriptor_count(1)
+                    .descriptor_type(*t)
+                    .offset(i * size_of::<DescriptorData>())
+                    .stride(size_of::<DescriptorData>())
+            })
+            .collect::<Vec<_>>();
 
         unsafe {
             let descriptor_set_layout = native_device.create_descriptor_set_layout(&create_info, None)?;
 
+            let template_info = DescriptorUpdateTemplateCreateInfo::default()
+                .descriptor_update_entries(&template_entries)
+                .template_type(DescriptorUpdateTemplateType::DESCRIPTOR_SET)
+                .descriptor_set_layout(descriptor_set_layout);
+
+            let update_template = match template_entries.is_empty() || push_descriptors {
+                true => None,
+                false => match native_device.create_descriptor_update_template(&template_info, None) {
+                    Ok(x) => Some(x),
+                    Err(e) => {
+                        native_device.destroy_descriptor_set_layout(descriptor_set_layout, None);
+                        return Err(e.into());
+                    }
+                },
+            };
+
             Ok(Self {
                 shared_device,
                 descriptor_set_layout,
+                update_template,
+                push_descriptors,
                 _phantom: Default::default(),
             })
         }
@@ -132,14 +192,97 @@ impl<T: ShaderParameterSet> ParametersShared<T> {
     pub fn native_layout(&self) -> DescriptorSetLayout {
         self.descriptor_set_layout
     }
+
+    /// If [`Self::push`] must be used instead of allocating and writing sets.
+    pub(crate) fn push_descriptors(&self) -> bool {
+        self.push_descriptors
+    }
+
+    /// Points the bindings of `set` to `parameters`, which must match `T`.
+    pub(crate) fn write(&self, set: DescriptorSet, parameters: &[ParameterType]) {
+        let native_device = self.shared_device.native();
+
+        let Some(update_template) = self.update_template else {
+            return;
+        };
+
+        let data = parameters
+            .iter()
+            .map(|x| match x {
+                ParameterType::Buffer(buffer) => DescriptorData {
+                    buffer: DescriptorBufferInfo::default().buffer(buffer.native()).range(buffer.size()),
+                },
+                ParameterType::ImageView(view) => DescriptorData {
+                    image: DescriptorImageInfo::default()
+                        .image_view(view.native())
+                        .image_layout(ImageLayout::GENERAL),
+                },
+            })
+            .collect::<Vec<_>>();
+
+        unsafe {
+            native_device.update_descriptor_set_with_template(set, update_template, data.as_ptr().cast());
+        }
+    }
+
+    /// Pushes `parameters`, which must match `T`, as set 0 of `layout` into `command_buffer`.
+    pub(crate) fn push(&self, command_buffer: CommandBuffer, layout: PipelineLayout, parameters: &[ParameterType]) {
+        let Some(push_descriptor) = self.shared_device.push_descriptor() else {
+            return;
+        };
+
+        let buffer_infos = parameters
+            .iter()
+            .map(|x| match x {
+                ParameterType::Buffer(buffer) => DescriptorBufferInfo::default().buffer(buffer.native()).range(buffer.size()),
+                ParameterType::ImageView(_) => DescriptorBufferInfo::default(),
+            })
+            .collect::<Vec<_>>();
+
+        let image_infos = parameters
+            .iter()
+            .map(|x| match x {
+                ParameterType::Buffer(_) => DescriptorImageInfo::default(),
+                ParameterType::ImageView(view) => DescriptorImageInfo::default()
+                    .image_view(view.native())
+                    .image_layout(ImageLayout::GENERAL),
+            })
+            .collect::<Vec<_>>();
+
+        let writes = parameters
+            .iter()
+            .zip(T::descriptor_types())
+            .enumerate()
+            .map(|(i, (x, t))| {
+                let write = WriteDescriptorSet::default().dst_binding(i as u32).descriptor_type(t);
+
+                match x {
+                    ParameterType::Buffer(_) => write.buffer_info(std::slice::from_ref(&buffer_infos[i])),
+                    ParameterType::ImageView(_) => write.image_info(std::slice::from_ref(&image_infos[i])),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if writes.is_empty() {
+            return;
+        }
+
+        unsafe {
+            push_descriptor.cmd_push_descriptor_set(command_buffer, PipelineBindPoint::COMPUTE, layout, 0, &writes);
+        }
+    }
 }
 
 impl<T> Drop for ParametersShared<T> {
     fn drop(&mut self) {
         unsafe {
-            self.shared_device
-                .native()
-                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            let native_device = self.shared_device.native();
+
+            if let Some(update_template) = self.update_template {
+                native_device.destroy_descriptor_update_template(update_template, None);
+            }
+
+            native_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
         }
     }
 }
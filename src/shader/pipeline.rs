@@ -1,11 +1,13 @@
 use crate::device::{Device, DeviceShared};
 use crate::error;
 use crate::error::{Error, Variant};
+use crate::shader::descriptors::DescriptorAllocator;
 use crate::shader::parameters::ParametersShared;
 use crate::shader::shader::{Shader, ShaderShared};
-use crate::shader::ShaderParameterSet;
+use crate::shader::{PipelineCache, PipelineCacheShared, ShaderParameterSet, SpecializationConstants};
 use ash::vk::{
-    ComputePipelineCreateInfo, PipelineCache, PipelineLayout, PipelineLayoutCreateInfo, PipelineShaderStageCreateInfo, ShaderStageFlags,
+    ComputePipelineCreateInfo, PipelineLayout, PipelineLayoutCreateInfo, PipelineShaderStageCreateInfo, PushConstantRange,
+    ShaderStageFlags, SpecializationInfo,
 };
 use std::sync::Arc;
 
@@ -16,28 +18,43 @@ pub(crate) struct PipelineShared<T> {
     shared_parameters: Arc<ParametersShared<T>>,
     native_layout: PipelineLayout,
     native_pipeline: ash::vk::Pipeline,
+    descriptors: DescriptorAllocator,
 }
 
 impl<T: ShaderParameterSet> PipelineShared<T> {
-    pub(crate) fn new(shared_device: Arc<DeviceShared>, shared_shader: Arc<ShaderShared<T>>) -> Result<Self, Error> {
+    pub(crate) fn new(
+        shared_device: Arc<DeviceShared>,
+        shared_shader: Arc<ShaderShared<T>>,
+        shared_cache: Option<Arc<PipelineCacheShared>>,
+        constants: &SpecializationConstants,
+        push_constants_size: u32,
+    ) -> Result<Self, Error> {
         let native_device = shared_device.native();
         let shared_parameters = shared_shader.parameters();
 
-        // TODO!!!
-        // let push_constant = PushConstantRange::default()
-        //     .offset(0)
-        //     .size(4)
-        //     .stage_flags(ShaderStageFlags::COMPUTE);
-        //
-        // let push_constants = [push_constant];
+        let push_constant = PushConstantRange::default()
+            .offset(0)
+            .size(push_constants_size)
+            .stage_flags(ShaderStageFlags::COMPUTE);
+
+        let push_constants = [push_constant];
         let layouts = [shared_parameters.native_layout()];
 
-        let pipeline_layout = PipelineLayoutCreateInfo::default().set_layouts(&layouts);
+        let pipeline_layout = match push_constants_size {
+            0 => PipelineLayoutCreateInfo::default().set_layouts(&layouts),
+            _ => PipelineLayoutCreateInfo::default()
+                .set_layouts(&layouts)
+                .push_constant_ranges(&push_constants),
+        };
+
+        let (map_entries, data) = constants.map_and_data();
+        let specialization_info = SpecializationInfo::default().map_entries(&map_entries).data(&data);
 
         let pipeline_shader_stage = PipelineShaderStageCreateInfo::default()
             .stage(ShaderStageFlags::COMPUTE)
             .module(shared_shader.native())
-            .name(shared_shader.entry_point());
+            .name(shared_shader.entry_point())
+            .specialization_info(&specialization_info);
 
         unsafe {
             let native_layout = native_device.create_pipeline_layout(&pipeline_layout, None)?;
@@ -47,8 +64,9 @@ impl<T: ShaderParameterSet> PipelineShared<T> {
                 .layout(native_layout);
 
             let pipeline_infos = [pipeline_info];
+            let native_cache = shared_cache.as_ref().map(|x| x.native()).unwrap_or_default();
 
-            let native_pipeline = match native_device.create_compute_pipelines(PipelineCache::null(), &pipeline_infos, None) {
+            let native_pipeline = match native_device.create_compute_pipelines(native_cache, &pipeline_infos, None) {
                 Ok(mut pipelines) => pipelines.pop().ok_or_else(|| error!(Variant::NoComputePipeline))?,
                 Err((_, e)) => {
                     native_device.destroy_pipeline_layout(native_layout, None);
@@ -56,12 +74,17 @@ impl<T: ShaderParameterSet> PipelineShared<T> {
                 }
             };
 
+            shared_device.set_default_name(native_pipeline, "Pipeline");
+
+            let descriptors = DescriptorAllocator::new(shared_device.clone(), shared_parameters.native_layout(), &T::descriptor_types());
+
             Ok(Self {
                 shared_device,
                 shared_shader,
                 shared_parameters,
                 native_layout,
                 native_pipeline,
+                descriptors,
             })
         }
     }
@@ -83,6 +106,11 @@ impl<T> PipelineShared<T> {
     pub(crate) fn device(&self) -> Arc<DeviceShared> {
         self.shared_device.clone()
     }
+
+    /// Descriptor sets for invocations of this pipeline.
+    pub(crate) fn descriptors(&self) -> &DescriptorAllocator {
+        &self.descriptors
+    }
 }
 
 impl<T> Drop for PipelineShared<T> {
@@ -104,7 +132,31 @@ pub struct Pipeline<T: ShaderParameterSet> {
 
 impl<T: ShaderParameterSet> Pipeline<T> {
     pub fn new(device: &Device, shader: &Shader<T>) -> Result<Self, Error> {
-        let shared = PipelineShared::new(device.shared(), shader.shared())?;
+        Self::new_with_constants(device, shader, &SpecializationConstants::new())
+    }
+
+    /// Creates a pipeline with the shader's specialization constants set to `constants`.
+    pub fn new_with_constants(device: &Device, shader: &Shader<T>, constants: &SpecializationConstants) -> Result<Self, Error> {
+        let shared = PipelineShared::new(device.shared(), shader.shared(), None, constants, 0)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    /// Creates a pipeline like [`Self::new_with_constants`], reusing and filling `cache`.
+    pub fn new_with_cache(
+        device: &Device,
+        shader: &Shader<T>,
+        cache: &PipelineCache,
+        constants: &SpecializationConstants,
+    ) -> Result<Self, Error> {
+        let shared = PipelineShared::new(device.shared(), shader.shared(), Some(cache.shared()), constants, 0)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    /// Creates a pipeline whose shader takes `size` bytes of push constants, starting at offset 0.
+    pub(crate) fn new_with_push_constants(device: &Device, shader: &Shader<T>, size: u32) -> Result<Self, Error> {
+        let shared = PipelineShared::new(device.shared(), shader.shared(), None, &SpecializationConstants::new(), size)?;
 
         Ok(Self { shared: Arc::new(shared) })
     }
@@ -114,6 +166,18 @@ impl<T: ShaderParameterSet> Pipeline<T> {
         self.shared.clone()
     }
 
+    /// The raw `VkPipeline`.
+    pub fn raw(&self) -> ash::vk::Pipeline {
+        self.shared.native()
+    }
+
+    /// Names this pipeline in validation messages and debugging tools, instead of the default `vulkan_video::Pipeline#N`.
+    ///
+    /// Does nothing if `VK_EXT_debug_utils` isn't available.
+    pub fn set_name(&self, name: &str) -> Result<(), Error> {
+        self.shared.device().set_object_name(self.raw(), name)
+    }
+
     #[allow(unused)]
     pub(crate) fn layout(&self) -> ash::vk::PipelineLayout {
         self.shared.layout()
@@ -127,7 +191,7 @@ mod test {
     use crate::instance::{Instance, InstanceInfo};
     use crate::physicaldevice::PhysicalDevice;
     use crate::resources::Buffer;
-    use crate::shader::{Parameters, Pipeline, Shader};
+    use crate::shader::{Parameters, Pipeline, Shader, SpecializationConstants};
 
     #[test]
     #[cfg(not(miri))]
@@ -145,4 +209,24 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn create_pipeline_with_constants() -> Result<(), Error> {
+        let shader_code = include_bytes!("../../tests/shaders/compiled/hello_world.spv");
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let parameters = Parameters::<(&Buffer, &Buffer, &Buffer)>::new(&device)?;
+        let shader = Shader::new(&device, shader_code, "main", &parameters)?;
+
+        // The shader doesn't declare these, which Vulkan ignores.
+        let constants = SpecializationConstants::new().u32(0, 2).bool(1, true);
+
+        _ = Pipeline::new_with_constants(&device, &shader, &constants)?;
+
+        Ok(())
+    }
 }
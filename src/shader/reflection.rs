@@ -0,0 +1,434 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use ash::vk::DescriptorType;
+use rspirv::dr::{Instruction, Module, Operand};
+use rspirv::spirv::{Decoration, Dim, ExecutionModel, Op, StorageClass, Word};
+use std::collections::{HashMap, HashSet};
+
+/// A descriptor binding declared by a shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReflectedBinding {
+    variable: Word,
+    set: u32,
+    binding: u32,
+    descriptor_type: DescriptorType,
+    count: u32,
+}
+
+impl ReflectedBinding {
+    pub fn set(&self) -> u32 {
+        self.set
+    }
+
+    pub fn binding(&self) -> u32 {
+        self.binding
+    }
+
+    pub fn descriptor_type(&self) -> DescriptorType {
+        self.descriptor_type
+    }
+
+    /// Number of descriptors, e.g., for arrays of images, `0` for runtime sized arrays.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+fn load(spirv_code: &[u8]) -> Result<Module, Error> {
+    rspirv::dr::load_bytes(spirv_code).map_err(|e| error!(Variant::InvalidSpirv, "{e}"))
+}
+
+/// Returns the descriptor bindings `spirv_code` declares, ordered by set and binding.
+pub fn reflect_bindings(spirv_code: &[u8]) -> Result<Vec<ReflectedBinding>, Error> {
+    bindings(&load(spirv_code)?)
+}
+
+/// Returns the names of the compute entry points in `spirv_code`, in declaration order.
+pub fn reflect_compute_entry_points(spirv_code: &[u8]) -> Result<Vec<String>, Error> {
+    let module = load(spirv_code)?;
+
+    let names = module
+        .entry_points
+        .iter()
+        .filter(|x| matches!(x.operands.first(), Some(Operand::ExecutionModel(ExecutionModel::GLCompute))))
+        .filter_map(|x| match x.operands.get(2) {
+            Some(Operand::LiteralString(name)) => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+
+    Ok(names)
+}
+
+fn bindings(module: &Module) -> Result<Vec<ReflectedBinding>, Error> {
+    let mut sets = HashMap::new();
+    let mut bindings = HashMap::new();
+    let mut buffer_blocks = Vec::new();
+
+    for x in module.annotations.iter().filter(|x| x.class.opcode == Op::Decorate) {
+        match (x.operands.first(), x.operands.get(1), x.operands.get(2)) {
+            (Some(Operand::IdRef(id)), Some(Operand::Decoration(Decoration::DescriptorSet)), Some(Operand::LiteralInt32(set))) => {
+                sets.insert(*id, *set);
+            }
+            (Some(Operand::IdRef(id)), Some(Operand::Decoration(Decoration::Binding)), Some(Operand::LiteralInt32(binding))) => {
+                bindings.insert(*id, *binding);
+            }
+            (Some(Operand::IdRef(id)), Some(Operand::Decoration(Decoration::BufferBlock)), _) => {
+                buffer_blocks.push(*id);
+            }
+            _ => {}
+        }
+    }
+
+    let globals = module
+        .types_global_values
+        .iter()
+        .filter_map(|x| Some((x.result_id?, x)))
+        .collect::<HashMap<Word, &Instruction>>();
+
+    let mut reflected = Vec::new();
+
+    for variable in module.types_global_values.iter().filter(|x| x.class.opcode == Op::Variable) {
+        let (Some(id), Some(pointer)) = (variable.result_id, variable.result_type) else {
+            continue;
+        };
+
+        let Some(&binding) = bindings.get(&id) else {
+            continue;
+        };
+
+        let storage_class = match variable.operands.first() {
+            Some(Operand::StorageClass(x)) => *x,
+            _ => return Err(error!(Variant::InvalidSpirv, "Variable {id} without storage class.")),
+        };
+
+        let (count, element) = array_element(&globals, pointee(&globals, pointer)?)?;
+        let descriptor_type = descriptor_type(&buffer_blocks, storage_class, element)?;
+
+        reflected.push(ReflectedBinding {
+            variable: id,
+            set: sets.get(&id).copied().unwrap_or(0),
+            binding,
+            descriptor_type,
+            count,
+        });
+    }
+
+    reflected.sort_by_key(|x| (x.set, x.binding));
+
+    Ok(reflected)
+}
+
+/// Checks the bindings `entry_point` of `spirv_code` uses are exactly the ones of `descriptor_types`, all in set 0.
+///
+/// Modules can have several entry points with different bindings, e.g., when built with `rust-gpu`.
+pub(crate) fn validate_bindings(spirv_code: &[u8], entry_point: &str, descriptor_types: &[DescriptorType]) -> Result<(), Error> {
+    let module = load(spirv_code)?;
+    let used = used_ids(&module, entry_point)?;
+    let reflected = bindings(&module)?
+        .into_iter()
+        .filter(|x| used.contains(&x.variable))
+        .collect::<Vec<_>>();
+
+    for x in &reflected {
+        match descriptor_types.get(x.binding as usize) {
+            _ if x.set != 0 => {
+                return Err(error!(
+                    Variant::ParameterMismatch,
+                    "Shader uses set {}, only set 0 is supported.", x.set
+                ))
+            }
+            None => return Err(error!(Variant::ParameterMismatch, "Shader binding {} has no parameter.", x.binding)),
+            Some(t) if *t != x.descriptor_type || x.count != 1 => {
+                return Err(error!(
+                    Variant::ParameterMismatch,
+                    "Shader binding {} is {} {:?}, parameter is {t:?}.", x.binding, x.count, x.descriptor_type
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+
+    if let Some(unused) = (0..descriptor_types.len() as u32).find(|i| !reflected.iter().any(|x| x.binding == *i)) {
+        return Err(error!(Variant::ParameterMismatch, "Parameter {unused} has no shader binding."));
+    }
+
+    Ok(())
+}
+
+/// Ids referenced by `entry_point`, its interface and all functions it calls.
+fn used_ids(module: &Module, entry_point: &str) -> Result<HashSet<Word>, Error> {
+    let entry = module
+        .entry_points
+        .iter()
+        .find(|x| matches!(x.operands.get(2), Some(Operand::LiteralString(name)) if name == entry_point))
+        .ok_or_else(|| error!(Variant::EntryPointNotFound, "No entry point `{entry_point}` in shader."))?;
+
+    let functions = module
+        .functions
+        .iter()
+        .filter_map(|x| Some((x.def.as_ref()?.result_id?, x)))
+        .collect::<HashMap<_, _>>();
+
+    let mut used = entry.operands.iter().skip(3).filter_map(|x| x.id_ref_any()).collect::<HashSet<_>>();
+    let mut visited = HashSet::new();
+    let mut pending = vec![id_operand(entry, 1)?];
+
+    while let Some(id) = pending.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+
+        let function = functions
+            .get(&id)
+            .ok_or_else(|| error!(Variant::InvalidSpirv, "Function {id} not found."))?;
+
+        for instruction in function.blocks.iter().flat_map(|x| &x.instructions) {
+            if instruction.class.opcode == Op::FunctionCall {
+                pending.push(id_operand(instruction, 0)?);
+            }
+
+            used.extend(instruction.operands.iter().filter_map(|x| x.id_ref_any()));
+        }
+    }
+
+    Ok(used)
+}
+
+fn id_operand(instruction: &Instruction, index: usize) -> Result<Word, Error> {
+    match instruction.operands.get(index) {
+        Some(Operand::IdRef(id)) => Ok(*id),
+        _ => Err(error!(
+            Variant::InvalidSpirv,
+            "Expected id as operand {index} of {:?}.", instruction.class.opcode
+        )),
+    }
+}
+
+fn global<'a>(globals: &HashMap<Word, &'a Instruction>, id: Word) -> Result<&'a Instruction, Error> {
+    globals
+        .get(&id)
+        .copied()
+        .ok_or_else(|| error!(Variant::InvalidSpirv, "Type {id} not found."))
+}
+
+/// The type a pointer type points to.
+fn pointee<'a>(globals: &HashMap<Word, &'a Instruction>, pointer: Word) -> Result<&'a Instruction, Error> {
+    let pointer = global(globals, pointer)?;
+
+    global(globals, id_operand(pointer, 1)?)
+}
+
+/// Element count and type of arrays, `1` and `ty` itself for everything else.
+fn array_element<'a>(globals: &HashMap<Word, &'a Instruction>, ty: &'a Instruction) -> Result<(u32, &'a Instruction), Error> {
+    match ty.class.opcode {
+        Op::TypeArray => {
+            let element = global(globals, id_operand(ty, 0)?)?;
+            let length = global(globals, id_operand(ty, 1)?)?;
+
+            match length.operands.first() {
+                Some(Operand::LiteralInt32(count)) => Ok((*count, element)),
+                _ => Err(error!(Variant::InvalidSpirv, "Array length is not a constant.")),
+            }
+        }
+        Op::TypeRuntimeArray => Ok((0, global(globals, id_operand(ty, 0)?)?)),
+        _ => Ok((1, ty)),
+    }
+}
+
+fn descriptor_type(buffer_blocks: &[Word], storage_class: StorageClass, ty: &Instruction) -> Result<DescriptorType, Error> {
+    let is_buffer_block = ty.result_id.is_some_and(|x| buffer_blocks.contains(&x));
+
+    let descriptor_type = match (ty.class.opcode, storage_class) {
+        (Op::TypeStruct, StorageClass::StorageBuffer) => DescriptorType::STORAGE_BUFFER,
+        (Op::TypeStruct, StorageClass::Uniform) if is_buffer_block => DescriptorType::STORAGE_BUFFER,
+        (Op::TypeStruct, StorageClass::Uniform) => DescriptorType::UNIFORM_BUFFER,
+        (Op::TypeSampler, _) => DescriptorType::SAMPLER,
+        (Op::TypeSampledImage, _) => DescriptorType::COMBINED_IMAGE_SAMPLER,
+        (Op::TypeImage, _) => {
+            let dim = ty.operands.get(1);
+            let sampled = ty.operands.get(5);
+
+            match (dim, sampled) {
+                (Some(Operand::Dim(Dim::DimBuffer)), Some(Operand::LiteralInt32(2))) => DescriptorType::STORAGE_TEXEL_BUFFER,
+                (Some(Operand::Dim(Dim::DimBuffer)), _) => DescriptorType::UNIFORM_TEXEL_BUFFER,
+                (Some(Operand::Dim(Dim::DimSubpassData)), _) => DescriptorType::INPUT_ATTACHMENT,
+                (_, Some(Operand::LiteralInt32(2))) => DescriptorType::STORAGE_IMAGE,
+                _ => DescriptorType::SAMPLED_IMAGE,
+            }
+        }
+        (Op::TypeAccelerationStructureKHR, _) => DescriptorType::ACCELERATION_STRUCTURE_KHR,
+        (op, _) => return Err(error!(Variant::InvalidSpirv, "Unsupported descriptor type {op:?}.")),
+    };
+
+    Ok(descriptor_type)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shader::reflection::{reflect_bindings, reflect_compute_entry_points, validate_bindings};
+    use ash::vk::DescriptorType;
+    use rspirv::binary::Assemble;
+    use rspirv::dr::{Builder, Operand};
+    use rspirv::spirv::{
+        AddressingModel, Capability, Decoration, Dim, ExecutionModel, FunctionControl, ImageFormat, MemoryModel, StorageClass, Word,
+    };
+
+    fn assemble(builder: Builder) -> Vec<u8> {
+        builder.module().assemble().iter().flat_map(|x| x.to_ne_bytes()).collect()
+    }
+
+    fn builder() -> Builder {
+        let mut builder = Builder::new();
+
+        builder.capability(Capability::Shader);
+        builder.memory_model(AddressingModel::Logical, MemoryModel::GLSL450);
+        builder
+    }
+
+    /// Adds a compute entry point `name`, listing `interface` as used, calling `callee` if given.
+    fn entry_point(builder: &mut Builder, name: &str, interface: &[Word], callee: Option<Word>) {
+        let void = builder.type_void();
+        let function_type = builder.type_function(void, []);
+        let function = builder.begin_function(void, None, FunctionControl::NONE, function_type).unwrap();
+
+        builder.begin_block(None).unwrap();
+
+        if let Some(callee) = callee {
+            builder.function_call(void, None, callee, []).unwrap();
+        }
+
+        builder.ret().unwrap();
+        builder.end_function().unwrap();
+        builder.entry_point(ExecutionModel::GLCompute, function, name, interface);
+    }
+
+    /// Assembles a module with the variables `f` declares, all used by entry point `main`.
+    fn shader(f: impl FnOnce(&mut Builder) -> Vec<Word>) -> Vec<u8> {
+        let mut builder = builder();
+        let variables = f(&mut builder);
+
+        entry_point(&mut builder, "main", &variables, None);
+        assemble(builder)
+    }
+
+    /// Declares a variable of type `ty` in `storage_class` at `binding` of set 0.
+    fn variable(builder: &mut Builder, binding: u32, storage_class: StorageClass, ty: Word) -> Word {
+        let pointer = builder.type_pointer(None, storage_class, ty);
+        let variable = builder.variable(pointer, None, storage_class, None);
+
+        builder.decorate(variable, Decoration::DescriptorSet, [Operand::LiteralInt32(0)]);
+        builder.decorate(variable, Decoration::Binding, [Operand::LiteralInt32(binding)]);
+        variable
+    }
+
+    /// Declares a GLSL `buffer` block, as `glslc` does for Vulkan 1.0.
+    fn buffer(builder: &mut Builder, binding: u32) -> Word {
+        let uint = builder.type_int(32, 0);
+        let data = builder.type_runtime_array(uint);
+        let block = builder.type_struct([data]);
+
+        builder.decorate(block, Decoration::BufferBlock, []);
+        variable(builder, binding, StorageClass::Uniform, block)
+    }
+
+    /// Like `hello_world.glsl`, three buffers.
+    fn hello_world() -> Vec<u8> {
+        shader(|x| (0..3).map(|i| buffer(x, i)).collect())
+    }
+
+    #[test]
+    fn reflect_buffers() {
+        let bindings = reflect_bindings(&hello_world()).unwrap();
+
+        assert_eq!(bindings.len(), 3);
+
+        for (i, x) in bindings.iter().enumerate() {
+            assert_eq!(x.set(), 0);
+            assert_eq!(x.binding(), i as u32);
+            assert_eq!(x.descriptor_type(), DescriptorType::STORAGE_BUFFER);
+            assert_eq!(x.count(), 1);
+        }
+    }
+
+    #[test]
+    fn reflect_images_and_uniforms() {
+        let shader_code = shader(|x| {
+            let float = x.type_float(32);
+            let uint = x.type_int(32, 0);
+            let four = x.constant_u32(uint, 4);
+            let storage_image = x.type_image(float, Dim::Dim2D, 0, 0, 0, 2, ImageFormat::Rgba8, None);
+            let storage_images = x.type_array(storage_image, four);
+            let sampled_image = x.type_image(float, Dim::Dim2D, 0, 0, 0, 1, ImageFormat::Unknown, None);
+            let combined = x.type_sampled_image(sampled_image);
+            let block = x.type_struct([float]);
+
+            x.decorate(block, Decoration::Block, []);
+
+            vec![
+                variable(x, 2, StorageClass::UniformConstant, storage_images),
+                variable(x, 1, StorageClass::UniformConstant, combined),
+                variable(x, 0, StorageClass::Uniform, block),
+            ]
+        });
+
+        let bindings = reflect_bindings(&shader_code).unwrap();
+        let types = bindings
+            .iter()
+            .map(|x| (x.binding(), x.descriptor_type(), x.count()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            types,
+            vec![
+                (0, DescriptorType::UNIFORM_BUFFER, 1),
+                (1, DescriptorType::COMBINED_IMAGE_SAMPLER, 1),
+                (2, DescriptorType::STORAGE_IMAGE, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_parameters() {
+        let shader_code = hello_world();
+        let buffer = DescriptorType::STORAGE_BUFFER;
+        let image = DescriptorType::STORAGE_IMAGE;
+
+        assert!(validate_bindings(&shader_code, "main", &[buffer, buffer, buffer]).is_ok());
+        assert!(validate_bindings(&shader_code, "main", &[buffer, buffer]).is_err());
+        assert!(validate_bindings(&shader_code, "main", &[buffer, buffer, buffer, buffer]).is_err());
+        assert!(validate_bindings(&shader_code, "main", &[buffer, image, buffer]).is_err());
+        assert!(validate_bindings(&shader_code, "other", &[buffer, buffer, buffer]).is_err());
+        assert!(validate_bindings(&[1, 2, 3, 4], "main", &[]).is_err());
+    }
+
+    #[test]
+    fn validate_per_entry_point() {
+        let mut builder = builder();
+        let first = buffer(&mut builder, 0);
+        let second = buffer(&mut builder, 1);
+
+        // `add` reaches `second` only through a function call, as in pre SPIR-V 1.4 modules.
+        let void = builder.type_void();
+        let uint = builder.type_int(32, 0);
+        let data = builder.type_runtime_array(uint);
+        let block = builder.type_struct([data]);
+        let function_type = builder.type_function(void, []);
+        let helper = builder.begin_function(void, None, FunctionControl::NONE, function_type).unwrap();
+        builder.begin_block(None).unwrap();
+        builder.load(block, None, second, None, []).unwrap();
+        builder.ret().unwrap();
+        builder.end_function().unwrap();
+
+        entry_point(&mut builder, "clear", &[first], None);
+        entry_point(&mut builder, "add", &[first], Some(helper));
+
+        let shader_code = assemble(builder);
+        let buffer = DescriptorType::STORAGE_BUFFER;
+
+        assert_eq!(reflect_compute_entry_points(&shader_code).unwrap(), vec!["clear", "add"]);
+        assert!(validate_bindings(&shader_code, "clear", &[buffer]).is_ok());
+        assert!(validate_bindings(&shader_code, "clear", &[buffer, buffer]).is_err());
+        assert!(validate_bindings(&shader_code, "add", &[buffer, buffer]).is_ok());
+    }
+}
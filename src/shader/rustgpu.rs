@@ -0,0 +1,122 @@
+use crate::device::Device;
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::shader::reflection::reflect_compute_entry_points;
+use crate::shader::{Parameters, Shader, ShaderParameterSet};
+use std::path::Path;
+
+/// A SPIR-V module built with [rust-gpu](https://github.com/Rust-GPU/rust-gpu), e.g., via `spirv-builder`.
+///
+/// Such modules usually hold all entry points of a shader crate, each with its own bindings.
+///
+/// ```rust,ignore
+/// let module = RustGpuModule::from_file(env!("kernels.spv"))?;
+/// let parameters = Parameters::<(&Buffer, &Buffer, &Buffer)>::new(&device)?;
+/// let shader = module.shader(&device, "add", &parameters)?;
+/// ```
+pub struct RustGpuModule {
+    /// Words, as Vulkan wants SPIR-V 4-byte aligned.
+    code: Vec<u32>,
+    entry_points: Vec<String>,
+}
+
+impl RustGpuModule {
+    /// Loads `spirv_code`, which must have at least one compute entry point.
+    pub fn new(spirv_code: &[u8]) -> Result<Self, Error> {
+        if !spirv_code.len().is_multiple_of(4) {
+            return Err(error!(Variant::InvalidSpirv, "Length {} is not a multiple of 4.", spirv_code.len()));
+        }
+
+        let entry_points = reflect_compute_entry_points(spirv_code)?;
+
+        if entry_points.is_empty() {
+            return Err(error!(Variant::EntryPointNotFound, "Module has no compute entry points."));
+        }
+
+        let code = spirv_code
+            .chunks_exact(4)
+            .map(|x| u32::from_ne_bytes([x[0], x[1], x[2], x[3]]))
+            .collect();
+
+        Ok(Self { code, entry_points })
+    }
+
+    /// Loads the module at `path`, e.g., as reported by `spirv-builder`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::new(&std::fs::read(path)?)
+    }
+
+    /// Names of the compute entry points, in declaration order.
+    pub fn entry_points(&self) -> &[String] {
+        &self.entry_points
+    }
+
+    /// Creates a shader for `entry_point`, checking it uses exactly the bindings of `T`.
+    pub fn shader<T: ShaderParameterSet>(
+        &self,
+        device: &Device,
+        entry_point: &str,
+        parameters: &Parameters<T>,
+    ) -> Result<Shader<T>, Error> {
+        if !self.entry_points.iter().any(|x| x == entry_point) {
+            return Err(error!(
+                Variant::EntryPointNotFound,
+                "No entry point `{entry_point}`, module has {:?}.", self.entry_points
+            ));
+        }
+
+        Shader::new(device, self.code(), entry_point, parameters)
+    }
+
+    /// The module as bytes.
+    pub fn code(&self) -> &[u8] {
+        // SAFETY: Any `u32` is 4 valid bytes, and `u8` needs no alignment.
+        unsafe { std::slice::from_raw_parts(self.code.as_ptr().cast(), self.code.len() * 4) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shader::rustgpu::RustGpuModule;
+    use rspirv::binary::Assemble;
+    use rspirv::dr::Builder;
+    use rspirv::spirv::{AddressingModel, Capability, ExecutionModel, FunctionControl, MemoryModel};
+
+    /// A module with compute entry points `names`, as rust-gpu emits for a crate with several kernels.
+    fn module(names: &[&str]) -> Vec<u8> {
+        let mut builder = Builder::new();
+
+        builder.capability(Capability::Shader);
+        builder.memory_model(AddressingModel::Logical, MemoryModel::Vulkan);
+
+        for name in names {
+            let void = builder.type_void();
+            let function_type = builder.type_function(void, []);
+            let function = builder.begin_function(void, None, FunctionControl::NONE, function_type).unwrap();
+
+            builder.begin_block(None).unwrap();
+            builder.ret().unwrap();
+            builder.end_function().unwrap();
+            builder.entry_point(ExecutionModel::GLCompute, function, *name, []);
+        }
+
+        builder.module().assemble().iter().flat_map(|x| x.to_ne_bytes()).collect()
+    }
+
+    #[test]
+    fn discover_entry_points() {
+        let spirv_code = module(&["add", "clear"]);
+        let module = RustGpuModule::new(&spirv_code).unwrap();
+
+        assert_eq!(module.entry_points(), ["add", "clear"]);
+        assert_eq!(module.code(), spirv_code);
+        assert_eq!(module.code().as_ptr() as usize % 4, 0);
+    }
+
+    #[test]
+    fn reject_invalid_modules() {
+        assert!(RustGpuModule::new(&module(&[])).is_err());
+        assert!(RustGpuModule::new(&module(&["add"])[1..]).is_err());
+        assert!(RustGpuModule::new(&[1, 2, 3, 4]).is_err());
+    }
+}
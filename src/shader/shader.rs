@@ -21,6 +21,9 @@ impl<T: ShaderParameterSet> ShaderShared<T> {
         entry_point: &str,
         shared_parameters: Arc<ParametersShared<T>>,
     ) -> Result<Self, Error> {
+        #[cfg(feature = "reflection")]
+        crate::shader::reflection::validate_bindings(spirv_code, entry_point, &T::descriptor_types())?;
+
         let entry_point = CString::new(entry_point)?;
 
         let mut create_info = ShaderModuleCreateInfo::default();
@@ -66,6 +69,7 @@ pub struct Shader<T: ShaderParameterSet> {
 }
 
 impl<T: ShaderParameterSet> Shader<T> {
+    /// Loads `spirv_code`, with the `reflection` feature also checking it declares exactly the bindings of `T`.
     pub fn new(device: &Device, spirv_code: &[u8], entry_point: &str, parameters: &Parameters<T>) -> Result<Self, Error> {
         let shared = ShaderShared::<T>::new(device.shared(), spirv_code, entry_point, parameters.shared())?;
 
@@ -76,6 +80,11 @@ impl<T: ShaderParameterSet> Shader<T> {
         self.shared.clone()
     }
 
+    /// The raw `VkShaderModule`.
+    pub fn raw(&self) -> ShaderModule {
+        self.shared.native()
+    }
+
     #[allow(unused)]
     pub fn entry_point(&self) -> &CStr {
         self.shared.entry_point()
@@ -106,7 +115,7 @@ mod test {
         let instance = Instance::new(&instance_info)?;
         let physical_device = PhysicalDevice::new_any(&instance)?;
         let device = Device::new(&physical_device)?;
-        let parameters = Parameters::<(&Buffer,)>::new(&device)?;
+        let parameters = Parameters::<(&Buffer, &Buffer, &Buffer)>::new(&device)?;
 
         _ = Shader::new(&device, shader_code, "main", &parameters)?;
 
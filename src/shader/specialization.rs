@@ -0,0 +1,92 @@
+use ash::vk::SpecializationMapEntry;
+
+/// Values for a shader's specialization constants, e.g., `layout(constant_id = 0) const uint PLANES = 2;`.
+///
+/// Lets one shader serve several variants, which are then compiled into a [`Pipeline`](crate::shader::Pipeline) each:
+///
+/// ```rust,ignore
+/// let constants = SpecializationConstants::new().u32(0, 2).bool(1, true);
+/// let pipeline = Pipeline::new_with_constants(&device, &shader, &constants)?;
+/// ```
+///
+/// Setting a constant id twice keeps the last value, ids the shader doesn't declare are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct SpecializationConstants {
+    constants: Vec<(u32, [u8; 4])>,
+}
+
+impl SpecializationConstants {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn u32(self, constant_id: u32, value: u32) -> Self {
+        self.set(constant_id, value.to_ne_bytes())
+    }
+
+    pub fn i32(self, constant_id: u32, value: i32) -> Self {
+        self.set(constant_id, value.to_ne_bytes())
+    }
+
+    pub fn f32(self, constant_id: u32, value: f32) -> Self {
+        self.set(constant_id, value.to_ne_bytes())
+    }
+
+    /// Sets a `bool` constant, which shaders store as 32 bit value.
+    pub fn bool(self, constant_id: u32, value: bool) -> Self {
+        self.set(constant_id, u32::from(value).to_ne_bytes())
+    }
+
+    fn set(mut self, constant_id: u32, data: [u8; 4]) -> Self {
+        self.constants.retain(|x| x.0 != constant_id);
+        self.constants.push((constant_id, data));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.constants.is_empty()
+    }
+
+    /// Map entries and the data they point into, as needed for a `SpecializationInfo`.
+    pub(crate) fn map_and_data(&self) -> (Vec<SpecializationMapEntry>, Vec<u8>) {
+        let mut entries = Vec::with_capacity(self.constants.len());
+        let mut data = Vec::with_capacity(self.constants.len() * 4);
+
+        for (constant_id, value) in &self.constants {
+            let entry = SpecializationMapEntry::default()
+                .constant_id(*constant_id)
+                .offset(data.len() as u32)
+                .size(value.len());
+
+            entries.push(entry);
+            data.extend_from_slice(value);
+        }
+
+        (entries, data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shader::SpecializationConstants;
+
+    #[test]
+    fn map_and_data() {
+        let constants = SpecializationConstants::new().u32(0, 1).f32(3, 1.0).u32(0, 2).bool(1, true);
+        let (entries, data) = constants.map_and_data();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(data.len(), 12);
+
+        for (entry, (id, value)) in entries
+            .iter()
+            .zip([(3, 1.0_f32.to_ne_bytes()), (0, 2_u32.to_ne_bytes()), (1, 1_u32.to_ne_bytes())])
+        {
+            let offset = entry.offset as usize;
+
+            assert_eq!(entry.constant_id, id);
+            assert_eq!(entry.size, 4);
+            assert_eq!(data[offset..offset + 4], value);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use ash::vk::MAX_MEMORY_HEAPS;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Resources held by a [`Device`](crate::Device), see [`Device::stats`](crate::Device::stats).
+#[derive(Debug, Clone, Default)]
+pub struct DeviceStats {
+    allocated_by_heap: Vec<u64>,
+}
+
+impl DeviceStats {
+    /// Bytes currently allocated via [`Allocation`](crate::Allocation)s, indexed by memory heap.
+    pub fn allocated_by_heap(&self) -> &[u64] {
+        &self.allocated_by_heap
+    }
+
+    /// Bytes currently allocated on all heaps.
+    pub fn allocated(&self) -> u64 {
+        self.allocated_by_heap.iter().sum()
+    }
+}
+
+/// Counters behind [`DeviceStats`], updated as allocations come and go.
+#[derive(Debug, Default)]
+pub(crate) struct DeviceCounters {
+    allocated_by_heap: [AtomicU64; MAX_MEMORY_HEAPS],
+}
+
+impl DeviceCounters {
+    pub fn allocated(&self, heap_index: u32, size: u64) {
+        self.allocated_by_heap[heap_index as usize].fetch_add(size, Ordering::Relaxed);
+    }
+
+    pub fn freed(&self, heap_index: u32, size: u64) {
+        self.allocated_by_heap[heap_index as usize].fetch_sub(size, Ordering::Relaxed);
+    }
+
+    /// Current values for the first `heap_count` heaps.
+    pub fn stats(&self, heap_count: u32) -> DeviceStats {
+        DeviceStats {
+            allocated_by_heap: self.allocated_by_heap[..heap_count as usize]
+                .iter()
+                .map(|x| x.load(Ordering::Relaxed))
+                .collect(),
+        }
+    }
+}
+
+/// Work done by a decoder, e.g., [`H264Decoder::stats`](crate::video::h264::H264Decoder::stats).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DecoderStats {
+    pub(crate) frames_decoded: u64,
+    pub(crate) bytes_consumed: u64,
+    pub(crate) decode_time: Duration,
+    pub(crate) dpb_occupancy: u32,
+    pub(crate) dpb_slots: u32,
+}
+
+impl DecoderStats {
+    /// Pictures decoded so far, counting each field of field-coded streams.
+    pub fn frames_decoded(&self) -> u64 {
+        self.frames_decoded
+    }
+
+    /// Bitstream bytes passed to the decoder so far, including access units skipped.
+    pub fn bytes_consumed(&self) -> u64 {
+        self.bytes_consumed
+    }
+
+    /// Time spent decoding (and copying) pictures on the GPU, including waiting for it.
+    pub fn decode_time(&self) -> Duration {
+        self.decode_time
+    }
+
+    /// Average [`Self::decode_time`] per picture, zero before the first.
+    pub fn average_decode_time(&self) -> Duration {
+        match self.frames_decoded {
+            0 => Duration::ZERO,
+            n => self.decode_time.div_f64(n as f64),
+        }
+    }
+
+    /// DPB slots currently holding reference pictures.
+    pub fn dpb_occupancy(&self) -> u32 {
+        self.dpb_occupancy
+    }
+
+    /// DPB slots of the video session.
+    pub fn dpb_slots(&self) -> u32 {
+        self.dpb_slots
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::{DecoderStats, DeviceCounters};
+    use std::time::Duration;
+
+    #[test]
+    fn count_allocations() {
+        let counters = DeviceCounters::default();
+
+        counters.allocated(0, 1024);
+        counters.allocated(1, 512);
+        counters.allocated(1, 512);
+        counters.freed(1, 512);
+
+        let stats = counters.stats(2);
+
+        assert_eq!(stats.allocated_by_heap(), &[1024, 512]);
+        assert_eq!(stats.allocated(), 1536);
+    }
+
+    #[test]
+    fn average_decode_time() {
+        let stats = DecoderStats {
+            frames_decoded: 4,
+            decode_time: Duration::from_millis(10),
+            ..Default::default()
+        };
+
+        assert_eq!(stats.average_decode_time(), Duration::from_micros(2500));
+        assert_eq!(DecoderStats::default().average_decode_time(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::ops::Access;
+use crate::resources::{BufferShared, ImageShared};
+use ash::vk::{
+    BufferMemoryBarrier2, CommandBuffer, DependencyInfoKHR, ImageAspectFlags, ImageLayout, ImageMemoryBarrier2, ImageSubresourceRange,
+    MemoryBarrier2, QUEUE_FAMILY_IGNORED, REMAINING_MIP_LEVELS, WHOLE_SIZE,
+};
+use std::collections::HashMap;
+
+/// How commands recorded so far accessed a resource (or an array layer of an image).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Accesses {
+    /// The last write, `Access::NONE` if there was none.
+    write: Access,
+    /// Accesses since the last write, which already waited for it.
+    reads: Access,
+}
+
+impl Default for Accesses {
+    fn default() -> Self {
+        Self {
+            write: Access::NONE,
+            reads: Access::NONE,
+        }
+    }
+}
+
+impl Accesses {
+    /// Notes `access`, returning what it has to wait for, if anything.
+    fn access(&mut self, access: Access) -> Option<Access> {
+        if access.is_write() {
+            // Writes wait for the last write, and for reads not to see what we're about to write.
+            let src = self.write.and(self.reads);
+            *self = Self {
+                write: access,
+                reads: Access::NONE,
+            };
+
+            return (!src.stages().is_empty()).then_some(src);
+        }
+
+        if self.write.stages().is_empty() || self.reads.contains(access) {
+            self.reads = self.reads.and(access);
+            return None;
+        }
+
+        self.reads = self.reads.and(access);
+        Some(self.write)
+    }
+
+    /// Notes a layout transition right before `access`, returning what it has to wait for.
+    ///
+    /// Transitions write the whole resource, so unlike reads they always wait for all accesses before them, and
+    /// accesses after them have to wait for the transition, which the barrier placed before `access`.
+    fn transition(&mut self, access: Access) -> Access {
+        let src = self.write.and(self.reads);
+        *self = Self {
+            write: access,
+            reads: if access.is_write() { Access::NONE } else { access },
+        };
+
+        src
+    }
+
+    /// Notes `access`, which already waited for everything it had to.
+    fn assume(&mut self, access: Access) {
+        match access.is_write() {
+            true => {
+                *self = Self {
+                    write: access,
+                    reads: Access::NONE,
+                }
+            }
+            false => self.reads = self.reads.and(access),
+        }
+    }
+}
+
+/// Tracks how commands in a command buffer access resources, to place the barriers subsequent commands need.
+///
+/// Operations declare their accesses before recording their commands, and then record the resulting barriers. Image
+/// layouts are kept in the images themselves, as they outlive command buffers.
+#[derive(Default)]
+pub(crate) struct ResourceStates {
+    buffers: HashMap<ash::vk::Buffer, Accesses>,
+    images: HashMap<(ash::vk::Image, u32), Accesses>,
+    buffer_barriers: Vec<BufferMemoryBarrier2<'static>>,
+    image_barriers: Vec<ImageMemoryBarrier2<'static>>,
+}
+
+impl ResourceStates {
+    /// Declares `access` to `buffer`.
+    pub fn access_buffer(&mut self, buffer: &BufferShared, access: Access) {
+        let native_buffer = buffer.native();
+
+        if let Some(src) = self.buffers.entry(native_buffer).or_default().access(access) {
+            let barrier = BufferMemoryBarrier2::default()
+                .src_stage_mask(src.stages())
+                .src_access_mask(src.access())
+                .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+                .dst_stage_mask(access.stages())
+                .dst_access_mask(access.access())
+                .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+                .buffer(native_buffer)
+                .size(WHOLE_SIZE);
+
+            self.buffer_barriers.push(barrier);
+        }
+    }
+
+    /// Declares `access` to `layer_count` array layers of `image` starting at `base_array_layer`, which must be in `layout` for it.
+    pub fn access_image(&mut self, image: &ImageShared, base_array_layer: u32, layer_count: u32, access: Access, layout: ImageLayout) {
+        let native_image = image.native();
+
+        for layer in base_array_layer..base_array_layer + layer_count {
+            let old_layout = image.layout(layer);
+            let accesses = self.images.entry((native_image, layer)).or_default();
+
+            let src = match old_layout == layout {
+                true => match accesses.access(access) {
+                    Some(src) => src,
+                    None => continue,
+                },
+                false => accesses.transition(access),
+            };
+
+            // For multi-planar formats `COLOR` covers all planes.
+            let ssr = ImageSubresourceRange::default()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .base_array_layer(layer)
+                .layer_count(1)
+                .level_count(REMAINING_MIP_LEVELS);
+
+            let barrier = ImageMemoryBarrier2::default()
+                .src_stage_mask(src.stages())
+                .src_access_mask(src.access())
+                .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+                .old_layout(old_layout)
+                .dst_stage_mask(access.stages())
+                .dst_access_mask(access.access())
+                .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+                .new_layout(layout)
+                .image(native_image)
+                .subresource_range(ssr);
+
+            self.image_barriers.push(barrier);
+            image.set_layout(layer, layout);
+        }
+    }
+
+    /// Notes `access` to `buffer` by a command placing its own barriers.
+    pub fn assume_buffer(&mut self, buffer: &BufferShared, access: Access) {
+        self.buffers.entry(buffer.native()).or_default().assume(access);
+    }
+
+    /// Notes `access` to array layers of `image` by a command placing its own barriers, which left them in `layout`.
+    pub fn assume_image(&mut self, image: &ImageShared, base_array_layer: u32, layer_count: u32, access: Access, layout: ImageLayout) {
+        for layer in base_array_layer..base_array_layer + layer_count {
+            self.images.entry((image.native(), layer)).or_default().assume(access);
+            image.set_layout(layer, layout);
+        }
+    }
+
+    /// Records the barriers needed for all accesses declared since the last call.
+    pub fn record_barriers(&mut self, native_device: &ash::Device, native_command_buffer: CommandBuffer) {
+        if self.buffer_barriers.is_empty() && self.image_barriers.is_empty() {
+            return;
+        }
+
+        let dependency_info = DependencyInfoKHR::default()
+            .buffer_memory_barriers(&self.buffer_barriers)
+            .image_memory_barriers(&self.image_barriers);
+
+        unsafe {
+            native_device.cmd_pipeline_barrier2(native_command_buffer, &dependency_info);
+        }
+
+        self.buffer_barriers.clear();
+        self.image_barriers.clear();
+    }
+
+    /// Records a barrier making all writes visible to the host, once the command buffer completed.
+    pub fn record_host_barrier(&mut self, native_device: &ash::Device, native_command_buffer: CommandBuffer) {
+        let writes = self
+            .buffers
+            .values()
+            .chain(self.images.values())
+            .fold(Access::NONE, |writes, x| writes.and(x.write));
+
+        if writes.stages().is_empty() {
+            return;
+        }
+
+        let memory_barriers = [MemoryBarrier2::default()
+            .src_stage_mask(writes.stages())
+            .src_access_mask(writes.access())
+            .dst_stage_mask(Access::HOST_READ.stages())
+            .dst_access_mask(Access::HOST_READ.access())];
+
+        let dependency_info = DependencyInfoKHR::default().memory_barriers(&memory_barriers);
+
+        unsafe {
+            native_device.cmd_pipeline_barrier2(native_command_buffer, &dependency_info);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ops::Access;
+    use crate::tracking::Accesses;
+
+    #[test]
+    fn reads_wait_for_writes_once() {
+        let mut accesses = Accesses::default();
+
+        assert_eq!(accesses.access(Access::TRANSFER_READ), None);
+        assert_eq!(accesses.access(Access::TRANSFER_WRITE), Some(Access::TRANSFER_READ));
+        assert_eq!(accesses.access(Access::COMPUTE_READ), Some(Access::TRANSFER_WRITE));
+        assert_eq!(accesses.access(Access::COMPUTE_READ), None);
+        assert_eq!(accesses.access(Access::TRANSFER_READ), Some(Access::TRANSFER_WRITE));
+    }
+
+    #[test]
+    fn writes_wait_for_reads_and_writes() {
+        let mut accesses = Accesses::default();
+
+        assert_eq!(accesses.access(Access::TRANSFER_WRITE), None);
+        assert_eq!(accesses.access(Access::COMPUTE_READ), Some(Access::TRANSFER_WRITE));
+        assert_eq!(
+            accesses.access(Access::VIDEO_DECODE_WRITE),
+            Some(Access::TRANSFER_WRITE.and(Access::COMPUTE_READ))
+        );
+        assert_eq!(accesses.access(Access::TRANSFER_WRITE), Some(Access::VIDEO_DECODE_WRITE));
+    }
+
+    #[test]
+    fn transitions_wait_for_reads() {
+        let mut accesses = Accesses::default();
+
+        // E.g., a copy from an image in `TRANSFER_SRC_OPTIMAL`, then a shader reading it in `GENERAL`.
+        assert_eq!(accesses.access(Access::TRANSFER_READ), None);
+        assert_eq!(accesses.transition(Access::COMPUTE_READ), Access::TRANSFER_READ);
+        assert_eq!(accesses.access(Access::COMPUTE_READ), None);
+        assert_eq!(accesses.access(Access::TRANSFER_READ), Some(Access::COMPUTE_READ));
+        assert_eq!(
+            accesses.transition(Access::TRANSFER_WRITE),
+            Access::COMPUTE_READ.and(Access::TRANSFER_READ)
+        );
+        assert_eq!(accesses.access(Access::COMPUTE_READ), Some(Access::TRANSFER_WRITE));
+    }
+}
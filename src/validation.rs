@@ -0,0 +1,186 @@
+use ash::vk::{Bool32, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT, DebugUtilsMessengerCallbackDataEXT, FALSE};
+use std::collections::VecDeque;
+use std::ffi::{c_void, CStr};
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::ThreadId;
+
+/// How many messages we keep around to attach to errors.
+const MAX_MESSAGES: usize = 256;
+
+/// Called with each validation warning or error, see [`InstanceInfo::validation_callback`](crate::InstanceInfo::validation_callback).
+pub type ValidationCallback = Arc<dyn Fn(&ValidationMessage) + Send + Sync>;
+
+/// A warning or error reported by the validation layer.
+#[derive(Debug, Clone)]
+pub struct ValidationMessage {
+    severity: DebugUtilsMessageSeverityFlagsEXT,
+    id_name: String,
+    message: String,
+}
+
+impl ValidationMessage {
+    pub fn severity(&self) -> DebugUtilsMessageSeverityFlagsEXT {
+        self.severity
+    }
+
+    /// If this is an error, not just a warning.
+    pub fn is_error(&self) -> bool {
+        self.severity.contains(DebugUtilsMessageSeverityFlagsEXT::ERROR)
+    }
+
+    /// The identifier of the violated rule, e.g., `SYNC-HAZARD-WRITE-AFTER-READ`.
+    pub fn id_name(&self) -> &str {
+        &self.id_name
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// A message, numbered so callers can ask for those since a certain point, and the thread that caused it.
+type LoggedMessage = (u64, ThreadId, ValidationMessage);
+
+/// Messages of the validation layer, reported on the thread that made the offending call.
+pub(crate) struct ValidationLog {
+    /// Number of the next message, and recent messages.
+    messages: Mutex<(u64, VecDeque<LoggedMessage>)>,
+    callback: Option<ValidationCallback>,
+}
+
+impl ValidationLog {
+    pub fn new(callback: Option<ValidationCallback>) -> Self {
+        Self {
+            messages: Mutex::new((0, VecDeque::new())),
+            callback,
+        }
+    }
+
+    fn push(&self, message: ValidationMessage) {
+        if let Some(callback) = &self.callback {
+            callback(&message);
+        }
+
+        let mut messages = self.messages.lock().unwrap_or_else(PoisonError::into_inner);
+        let (next, queue) = &mut *messages;
+
+        if queue.len() == MAX_MESSAGES {
+            queue.pop_front();
+        }
+
+        queue.push_back((*next, std::thread::current().id(), message));
+        *next += 1;
+    }
+
+    /// Reports a warning of our own, e.g., if validation was requested but isn't available.
+    pub fn warn(&self, id_name: &str, message: &str) {
+        self.push(ValidationMessage {
+            severity: DebugUtilsMessageSeverityFlagsEXT::WARNING,
+            id_name: id_name.to_string(),
+            message: message.to_string(),
+        });
+    }
+
+    /// Marks the current point, see [`Self::since`].
+    pub fn position(&self) -> u64 {
+        self.messages.lock().unwrap_or_else(PoisonError::into_inner).0
+    }
+
+    /// Messages reported on the current thread since `position`.
+    pub fn since(&self, position: u64) -> Vec<ValidationMessage> {
+        let messages = self.messages.lock().unwrap_or_else(PoisonError::into_inner);
+        let thread = std::thread::current().id();
+
+        messages
+            .1
+            .iter()
+            .filter(|(number, id, _)| *number >= position && *id == thread)
+            .map(|(_, _, message)| message.clone())
+            .collect()
+    }
+
+    /// Removes and returns all messages, of all threads.
+    pub fn take(&self) -> Vec<ValidationMessage> {
+        let mut messages = self.messages.lock().unwrap_or_else(PoisonError::into_inner);
+
+        messages.1.drain(..).map(|(_, _, message)| message).collect()
+    }
+}
+
+impl Debug for ValidationLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValidationLog").finish_non_exhaustive()
+    }
+}
+
+/// Messenger callback, `user_data` points to the instance's [`ValidationLog`].
+pub(crate) unsafe extern "system" fn messenger_callback(
+    severity: DebugUtilsMessageSeverityFlagsEXT,
+    _types: DebugUtilsMessageTypeFlagsEXT,
+    data: *const DebugUtilsMessengerCallbackDataEXT<'_>,
+    user_data: *mut c_void,
+) -> Bool32 {
+    // SAFETY: The log outlives the messenger, and the layer passes valid data (with possibly null strings).
+    unsafe {
+        let (Some(log), Some(data)) = (user_data.cast::<ValidationLog>().as_ref(), data.as_ref()) else {
+            return FALSE;
+        };
+
+        let to_string = |x: *const std::ffi::c_char| match x.is_null() {
+            true => String::new(),
+            false => CStr::from_ptr(x).to_string_lossy().into_owned(),
+        };
+
+        log.push(ValidationMessage {
+            severity,
+            id_name: to_string(data.p_message_id_name),
+            message: to_string(data.p_message),
+        });
+    }
+
+    // Must not abort the call that triggered the message.
+    FALSE
+}
+
+#[cfg(test)]
+mod test {
+    use crate::validation::{ValidationLog, ValidationMessage};
+    use ash::vk::DebugUtilsMessageSeverityFlagsEXT;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn message(text: &str) -> ValidationMessage {
+        ValidationMessage {
+            severity: DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            id_name: "VUID-test".to_string(),
+            message: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn messages_since() {
+        let called = Arc::new(AtomicUsize::new(0));
+        let called_in = called.clone();
+        let log = ValidationLog::new(Some(Arc::new(move |_: &ValidationMessage| {
+            called_in.fetch_add(1, Ordering::Relaxed);
+        })));
+
+        log.push(message("before"));
+        let position = log.position();
+        log.push(message("during"));
+
+        std::thread::scope(|s| {
+            s.spawn(|| log.push(message("other thread")));
+        });
+
+        let since = log.since(position);
+
+        assert_eq!(since.len(), 1);
+        assert_eq!(since[0].message(), "during");
+        assert!(since[0].is_error());
+        assert_eq!(called.load(Ordering::Relaxed), 3);
+        assert_eq!(log.take().len(), 3);
+        assert!(log.take().is_empty());
+    }
+}
@@ -0,0 +1,311 @@
+use crate::error;
+use crate::error::{Error, Variant};
+
+/// Strips the Annex B start code (if any) from a NAL unit.
+pub(crate) fn strip_start_code(nal: &[u8]) -> &[u8] {
+    let zeros = nal.iter().take_while(|x| **x == 0).count();
+
+    match nal.get(zeros) {
+        Some(1) if zeros >= 2 => &nal[zeros + 1..],
+        _ => nal,
+    }
+}
+
+/// Converts a NAL payload into its RBSP, i.e., removes all emulation prevention bytes (`00 00 03`).
+pub(crate) fn rbsp(nal_payload: &[u8]) -> Vec<u8> {
+    let mut rval = Vec::with_capacity(nal_payload.len());
+    let mut count_0 = 0;
+
+    for byte in nal_payload {
+        if count_0 >= 2 && *byte == 3 {
+            count_0 = 0;
+            continue;
+        }
+
+        count_0 = if *byte == 0 { count_0 + 1 } else { 0 };
+        rval.push(*byte);
+    }
+
+    rval
+}
+
+/// Reads bits and Exp-Golomb codes from a RBSP.
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    /// If `data` is a NAL payload, whose emulation prevention bytes are skipped while reading.
+    escaped: bool,
+    /// Zero bytes read directly before `position`, to spot emulation prevention bytes.
+    zeros: usize,
+    /// Emulation prevention bytes skipped so far.
+    skipped: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            escaped: false,
+            zeros: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Reads the RBSP of `nal_payload` like [`rbsp`] would return it, without copying the payload, e.g., to parse
+    /// the header of a large slice.
+    pub fn new_escaped(nal_payload: &'a [u8]) -> Self {
+        Self {
+            escaped: true,
+            ..Self::new(nal_payload)
+        }
+    }
+
+    /// Bits left in the data, for escaped data including emulation prevention bytes.
+    pub fn bits_left(&self) -> usize {
+        (self.data.len() * 8).saturating_sub(self.position)
+    }
+
+    /// Bits of the RBSP read so far, i.e., without emulation prevention bytes.
+    pub fn bits_read(&self) -> usize {
+        self.position - self.skipped * 8
+    }
+
+    pub fn flag(&mut self) -> Result<bool, Error> {
+        if self.escaped && self.position.is_multiple_of(8) && self.zeros >= 2 && self.data.get(self.position / 8) == Some(&3) {
+            self.position += 8;
+            self.zeros = 0;
+            self.skipped += 1;
+        }
+
+        let byte = *self
+            .data
+            .get(self.position / 8)
+            .ok_or_else(|| error!(Variant::InvalidBitstream, "Unexpected end of bitstream."))?;
+
+        let bit = (byte >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+
+        if self.escaped && self.position.is_multiple_of(8) {
+            self.zeros = if byte == 0 { self.zeros + 1 } else { 0 };
+        }
+
+        Ok(bit == 1)
+    }
+
+    /// Reads `n` bits, `n` must be 32 or less.
+    pub fn u(&mut self, n: u32) -> Result<u32, Error> {
+        debug_assert!(n <= 32);
+
+        let mut rval = 0u64;
+
+        for _ in 0..n {
+            rval = (rval << 1) | self.flag()? as u64;
+        }
+
+        Ok(rval as u32)
+    }
+
+    pub fn skip(&mut self, n: usize) -> Result<(), Error> {
+        // Emulation prevention bytes can only be told apart byte by byte.
+        if self.escaped {
+            for _ in 0..n {
+                self.flag()?;
+            }
+
+            return Ok(());
+        }
+
+        if n > self.bits_left() {
+            return Err(error!(Variant::InvalidBitstream, "Unexpected end of bitstream."));
+        }
+
+        self.position += n;
+        Ok(())
+    }
+
+    /// Reads an unsigned Exp-Golomb code.
+    pub fn ue(&mut self) -> Result<u32, Error> {
+        let mut leading_zeros = 0;
+
+        while !self.flag()? {
+            leading_zeros += 1;
+
+            if leading_zeros > 31 {
+                return Err(error!(Variant::InvalidBitstream, "Exp-Golomb code too long."));
+            }
+        }
+
+        let rest = self.u(leading_zeros)? as u64;
+
+        Ok(((1u64 << leading_zeros) - 1 + rest) as u32)
+    }
+
+    /// Reads a signed Exp-Golomb code.
+    pub fn se(&mut self) -> Result<i32, Error> {
+        let k = self.ue()? as i64;
+        let value = if k % 2 == 1 { (k + 1) / 2 } else { -(k / 2) };
+
+        Ok(value as i32)
+    }
+}
+
+/// Inserts emulation prevention bytes into a RBSP, i.e., the inverse of [`rbsp`].
+pub(crate) fn emulation_prevention(rbsp: &[u8]) -> Vec<u8> {
+    let mut rval = Vec::with_capacity(rbsp.len() + rbsp.len() / 64);
+    let mut count_0 = 0;
+
+    for byte in rbsp {
+        if count_0 >= 2 && *byte <= 3 {
+            rval.push(3);
+            count_0 = 0;
+        }
+
+        count_0 = if *byte == 0 { count_0 + 1 } else { 0 };
+        rval.push(*byte);
+    }
+
+    rval
+}
+
+/// Writes bits and Exp-Golomb codes into a RBSP.
+#[derive(Default)]
+pub(crate) struct BitWriter {
+    data: Vec<u8>,
+    position: usize,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn flag(&mut self, value: bool) {
+        if self.position.is_multiple_of(8) {
+            self.data.push(0);
+        }
+
+        if value {
+            let last = self.data.len() - 1;
+            self.data[last] |= 1 << (7 - self.position % 8);
+        }
+
+        self.position += 1;
+    }
+
+    /// Writes the lowest `n` bits of `value`, `n` must be 32 or less.
+    pub fn u(&mut self, n: u32, value: u32) {
+        debug_assert!(n <= 32);
+
+        for i in (0..n).rev() {
+            self.flag((value >> i) & 1 == 1);
+        }
+    }
+
+    /// Writes an unsigned Exp-Golomb code.
+    pub fn ue(&mut self, value: u32) {
+        let value = value as u64 + 1;
+        let bits = 64 - value.leading_zeros();
+
+        self.u(bits - 1, 0);
+
+        for i in (0..bits).rev() {
+            self.flag((value >> i) & 1 == 1);
+        }
+    }
+
+    /// Writes a signed Exp-Golomb code.
+    pub fn se(&mut self, value: i32) {
+        let value = value as i64;
+        let k = if value > 0 { 2 * value - 1 } else { -2 * value };
+
+        self.ue(k as u32);
+    }
+
+    /// Appends `rbsp_trailing_bits()` and returns the RBSP.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.flag(true);
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{emulation_prevention, rbsp, strip_start_code, BitReader, BitWriter};
+    use crate::error::Error;
+
+    #[test]
+    fn strips_emulation_prevention() {
+        assert_eq!(rbsp(&[0, 0, 3, 1]), &[0, 0, 1]);
+        assert_eq!(rbsp(&[0, 0, 3, 0, 0, 3]), &[0, 0, 0, 0]);
+        assert_eq!(rbsp(&[0, 3, 0]), &[0, 3, 0]);
+        assert_eq!(strip_start_code(&[0, 0, 0, 1, 0x40]), &[0x40]);
+        assert_eq!(strip_start_code(&[0, 0, 1, 0x40]), &[0x40]);
+        assert_eq!(strip_start_code(&[0x40]), &[0x40]);
+        assert_eq!(emulation_prevention(&[0, 0, 1]), &[0, 0, 3, 1]);
+        assert_eq!(emulation_prevention(&[0, 0, 0, 0]), &[0, 0, 3, 0, 0]);
+        assert_eq!(emulation_prevention(&[0, 0, 4]), &[0, 0, 4]);
+    }
+
+    #[test]
+    fn reads_exp_golomb() -> Result<(), Error> {
+        // 1 | 010 | 011 | 00100 | 00101
+        let data = [0b1010_0110, 0b0100_0010, 0b1000_0000];
+        let mut reader = BitReader::new(&data);
+
+        assert_eq!(reader.ue()?, 0);
+        assert_eq!(reader.ue()?, 1);
+        assert_eq!(reader.ue()?, 2);
+        assert_eq!(reader.se()?, 2);
+        assert_eq!(reader.se()?, -2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn writes_exp_golomb() -> Result<(), Error> {
+        let mut writer = BitWriter::new();
+
+        writer.ue(0);
+        writer.ue(1);
+        writer.ue(2);
+        writer.se(2);
+        writer.se(-2);
+        writer.u(3, 0b101);
+
+        let data = writer.finish();
+        let mut reader = BitReader::new(&data);
+
+        assert_eq!(data, [0b1010_0110, 0b0100_0010, 0b1101_1000]);
+        assert_eq!(reader.ue()?, 0);
+        assert_eq!(reader.ue()?, 1);
+        assert_eq!(reader.ue()?, 2);
+        assert_eq!(reader.se()?, 2);
+        assert_eq!(reader.se()?, -2);
+        assert_eq!(reader.u(3)?, 0b101);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reads_escaped() -> Result<(), Error> {
+        let payload = [0xff, 0, 0, 3, 1, 0, 0, 3, 0, 0, 3, 0x80];
+        let expected = rbsp(&payload);
+        let mut reader = BitReader::new_escaped(&payload);
+
+        for byte in &expected {
+            assert_eq!(reader.u(8)?, *byte as u32);
+        }
+
+        assert!(reader.flag().is_err());
+
+        // Skipping must not miss emulation prevention bytes either.
+        let mut reader = BitReader::new_escaped(&payload);
+
+        reader.skip(8 * 8)?;
+        assert_eq!(reader.u(8)?, 0x80);
+        assert_eq!(reader.bits_read(), expected.len() * 8);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,793 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::video::h264::{MemoryManagementControlOperation, SliceHeader};
+use ash::vk::native::{
+    StdVideoDecodeH264ReferenceInfo, StdVideoDecodeH264ReferenceInfoFlags, StdVideoEncodeH264ReferenceInfo,
+    StdVideoEncodeH264ReferenceInfoFlags, StdVideoH264PictureType_STD_VIDEO_H264_PICTURE_TYPE_IDR,
+    StdVideoH264PictureType_STD_VIDEO_H264_PICTURE_TYPE_P,
+};
+use ash::vk::{VideoDecodeH264DpbSlotInfoKHR, VideoPictureResourceInfoKHR, VideoReferenceSlotInfoKHR};
+
+/// A reference picture living in one of the DPB slots of a video session.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct DpbSlot {
+    index: u32,
+    frame_num: u32,
+    pic_order_cnt: [i32; 2],
+    long_term: bool,
+    long_term_frame_idx: u32,
+    /// Which fields of a field-coded picture the slot holds, both `false` for frames.
+    fields: [bool; 2],
+    idr: bool,
+}
+
+impl DpbSlot {
+    /// Index of the DPB slot this picture is stored in.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn frame_num(&self) -> u32 {
+        self.frame_num
+    }
+
+    /// Top and bottom field order count.
+    pub fn pic_order_cnt(&self) -> [i32; 2] {
+        self.pic_order_cnt
+    }
+
+    pub fn long_term(&self) -> bool {
+        self.long_term
+    }
+
+    /// `LongTermFrameIdx` of long-term references, 0 otherwise.
+    pub fn long_term_frame_idx(&self) -> u32 {
+        self.long_term_frame_idx
+    }
+
+    /// If the slot holds a top and / or bottom field instead of a frame.
+    pub fn fields(&self) -> [bool; 2] {
+        self.fields
+    }
+
+    /// If only one field of a field pair has been decoded so far.
+    fn is_single_field(&self) -> bool {
+        self.fields[0] != self.fields[1]
+    }
+
+    /// Top and bottom field held by the slot, both for frames.
+    fn held_fields(&self) -> [bool; 2] {
+        match self.is_single_field() {
+            true => self.fields,
+            false => [true; 2],
+        }
+    }
+
+    /// Stops `fields` of the slot being used for reference, returns if a field is left.
+    fn unmark_fields(&mut self, fields: [bool; 2]) -> bool {
+        if !fields.contains(&true) {
+            return true;
+        }
+
+        let held = self.held_fields();
+        self.fields = [held[0] && !fields[0], held[1] && !fields[1]];
+        self.fields.contains(&true)
+    }
+
+    /// `PicNum` (8.2.4.1) of a short-term reference, relative to the picture currently decoded.
+    fn pic_num(&self, current_frame_num: u32, max_frame_num: u32) -> i64 {
+        match self.frame_num > current_frame_num {
+            true => self.frame_num as i64 - max_frame_num as i64,
+            false => self.frame_num as i64,
+        }
+    }
+
+    pub(crate) fn std_reference_info(&self) -> StdVideoDecodeH264ReferenceInfo {
+        let mut flags = StdVideoDecodeH264ReferenceInfoFlags {
+            _bitfield_align_1: [],
+            _bitfield_1: Default::default(),
+            __bindgen_padding_0: Default::default(),
+        };
+
+        flags.set_used_for_long_term_reference(self.long_term as u32);
+        flags.set_top_field_flag(self.fields[0] as u32);
+        flags.set_bottom_field_flag(self.fields[1] as u32);
+
+        // Long-term references are identified by their `LongTermFrameIdx` instead.
+        let frame_num = match self.long_term {
+            true => self.long_term_frame_idx,
+            false => self.frame_num,
+        };
+
+        StdVideoDecodeH264ReferenceInfo {
+            flags,
+            FrameNum: frame_num as u16,
+            reserved: 0,
+            PicOrderCnt: self.pic_order_cnt,
+        }
+    }
+
+    /// Reference info for encoding, all non-IDR references are reported as P pictures.
+    pub(crate) fn std_encode_reference_info(&self) -> StdVideoEncodeH264ReferenceInfo {
+        let mut flags = StdVideoEncodeH264ReferenceInfoFlags {
+            _bitfield_align_1: [],
+            _bitfield_1: Default::default(),
+        };
+
+        flags.set_used_for_long_term_reference(self.long_term as u32);
+
+        let primary_pic_type = match self.idr {
+            true => StdVideoH264PictureType_STD_VIDEO_H264_PICTURE_TYPE_IDR,
+            false => StdVideoH264PictureType_STD_VIDEO_H264_PICTURE_TYPE_P,
+        };
+
+        StdVideoEncodeH264ReferenceInfo {
+            flags,
+            primary_pic_type,
+            FrameNum: self.frame_num,
+            PicOrderCnt: self.pic_order_cnt[0],
+            long_term_pic_num: 0,
+            long_term_frame_idx: self.long_term_frame_idx as u16,
+            temporal_id: 0,
+        }
+    }
+}
+
+/// Tracks which DPB slots hold reference pictures, and which slot the next picture goes into.
+///
+/// References are retired with the H.264 sliding window process (8.2.5.3): once `max_num_ref_frames`
+/// are held, marking another picture as reference evicts the oldest short-term reference. Streams using
+/// memory management control operations are handled when decoding through [`H264Decoder`](crate::video::h264::H264Decoder).
+///
+/// A typical decode loop looks like this:
+///
+/// ```rust,ignore
+/// let slot = dpb.next_slot(frame_num, poc, is_idr)?;
+/// let decode = DecodeH264::new(..).dpb(&dpb, slot, &dpb_views);
+/// // submit decode ...
+/// dpb.mark_reference(slot);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DpbSlotManager {
+    max_slots: u32,
+    max_num_ref_frames: u32,
+    /// `MaxLongTermFrameIdx`, `None` means "no long-term frame indices".
+    max_long_term_frame_idx: Option<u32>,
+    /// Active references, in decoding order.
+    references: Vec<DpbSlot>,
+    /// First field of a field pair still waiting for its second field.
+    first_field: Option<DpbSlot>,
+}
+
+impl DpbSlotManager {
+    /// Creates a new manager for a session with `max_slots` DPB slots, holding at most `max_num_ref_frames` references.
+    ///
+    /// At least one slot more than references is needed, so the picture being decoded has a place to go.
+    pub fn new(max_slots: u32, max_num_ref_frames: u32) -> Self {
+        Self {
+            max_slots,
+            max_num_ref_frames: max_num_ref_frames.max(1).min(max_slots.saturating_sub(1)),
+            max_long_term_frame_idx: None,
+            references: Vec::new(),
+            first_field: None,
+        }
+    }
+
+    /// Returns the slot the next picture should be decoded into.
+    ///
+    /// IDR pictures clear all existing references. The slot does not become a reference until
+    /// [`mark_reference`](Self::mark_reference) is called.
+    pub fn next_slot(&mut self, frame_num: u32, pic_order_cnt: [i32; 2], idr: bool) -> Result<DpbSlot, Error> {
+        self.first_field = None;
+
+        if idr {
+            self.references.clear();
+        }
+
+        let index = (0..self.max_slots)
+            .find(|x| self.references.iter().all(|r| r.index != *x))
+            .ok_or_else(|| error!(Variant::NoFreeDpbSlot))?;
+
+        Ok(DpbSlot {
+            index,
+            frame_num,
+            pic_order_cnt,
+            long_term: false,
+            long_term_frame_idx: 0,
+            fields: [false; 2],
+            idr,
+        })
+    }
+
+    /// Returns the slot the picture `header` belongs to should be decoded into, handling field pictures.
+    ///
+    /// The second field of a field pair goes into the slot of its first field. For fields only the order count
+    /// of the decoded field is used from `pic_order_cnt`.
+    pub(crate) fn next_slot_for(&mut self, header: &SliceHeader, pic_order_cnt: [i32; 2]) -> Result<DpbSlot, Error> {
+        if !header.field_pic_flag {
+            return self.next_slot(header.frame_num, pic_order_cnt, header.idr);
+        }
+
+        let parity = header.bottom_field_flag as usize;
+
+        // A second field follows its first field directly, with the same `frame_num` and opposite parity, and is never IDR.
+        if let Some(mut slot) = self.first_field.take() {
+            if slot.frame_num == header.frame_num && !slot.fields[parity] && !header.idr {
+                slot.fields[parity] = true;
+                slot.pic_order_cnt[parity] = pic_order_cnt[parity];
+                return Ok(slot);
+            }
+        }
+
+        let mut slot = self.next_slot(header.frame_num, [0, 0], header.idr)?;
+        slot.fields[parity] = true;
+        slot.pic_order_cnt[parity] = pic_order_cnt[parity];
+        self.first_field = Some(slot);
+
+        Ok(slot)
+    }
+
+    /// Marks a decoded picture as short-term reference, evicting the oldest short-term reference if needed.
+    pub fn mark_reference(&mut self, slot: DpbSlot) -> Result<(), Error> {
+        self.update_first_field(slot);
+        self.references.retain(|x| x.index != slot.index);
+
+        if self.references.len() >= self.max_num_ref_frames as usize {
+            let oldest = self
+                .references
+                .iter()
+                .position(|x| !x.long_term)
+                .ok_or_else(|| error!(Variant::NoFreeDpbSlot, "All references are long-term, sliding window can't evict."))?;
+
+            self.references.remove(oldest);
+        }
+
+        self.references.push(slot);
+
+        Ok(())
+    }
+
+    /// Marks a decoded picture according to its slice header (8.2.5), i.e., applying `dec_ref_pic_marking()`.
+    ///
+    /// Non-reference pictures are ignored. `max_frame_num` is `MaxFrameNum` of the active SPS. In field pictures,
+    /// memory management control operations address single fields through `PicNum` and `LongTermPicNum` (8.2.4.1).
+    /// Fields can stop being references on their own, but are only marked long-term together with their frame.
+    pub(crate) fn mark_decoded(&mut self, mut slot: DpbSlot, header: &SliceHeader, max_frame_num: u32) -> Result<(), Error> {
+        let Some(marking) = &header.dec_ref_pic_marking else {
+            return Ok(());
+        };
+
+        // The second field of a reference field pair joins its first field (8.2.5.1).
+        if self.references.iter().any(|x| x.index == slot.index && x.is_single_field()) && !slot.is_single_field() {
+            for reference in self.references.iter_mut().filter(|x| x.index == slot.index) {
+                reference.fields = slot.fields;
+                reference.pic_order_cnt = slot.pic_order_cnt;
+            }
+
+            return Ok(());
+        }
+
+        self.update_first_field(slot);
+
+        if header.idr {
+            self.references.clear();
+            self.max_long_term_frame_idx = marking.long_term_reference_flag.then_some(0);
+
+            slot.long_term = marking.long_term_reference_flag;
+            slot.long_term_frame_idx = 0;
+            self.references.push(slot);
+
+            return Ok(());
+        }
+
+        if marking.operations.is_empty() {
+            return self.mark_reference(slot);
+        }
+
+        let current_frame_num = header.frame_num;
+        let parity = header.bottom_field_flag as usize;
+
+        // For field pictures `CurrPicNum` is `2 * frame_num + 1`, and fields of the current parity have odd, those of
+        // the other parity even numbers (8.2.4.1). Both return the fields the number names, both for frames.
+        let short_term_fields = |x: &DpbSlot, difference_of_pic_nums_minus1: u32| {
+            let pic_num = x.pic_num(current_frame_num, max_frame_num);
+            let held = x.held_fields();
+            let mut named = [false; 2];
+
+            match header.field_pic_flag {
+                true => {
+                    let pic_num_x = 2 * current_frame_num as i64 + 1 - (difference_of_pic_nums_minus1 as i64 + 1);
+                    named[parity] = held[parity] && 2 * pic_num + 1 == pic_num_x;
+                    named[1 - parity] = held[1 - parity] && 2 * pic_num == pic_num_x;
+                }
+                false => named = [pic_num == current_frame_num as i64 - (difference_of_pic_nums_minus1 as i64 + 1); 2],
+            }
+
+            named
+        };
+
+        let long_term_fields = |x: &DpbSlot, long_term_pic_num: u32| {
+            let long_term_frame_idx = x.long_term_frame_idx as u64;
+            let held = x.held_fields();
+            let mut named = [false; 2];
+
+            match header.field_pic_flag {
+                true => {
+                    named[parity] = held[parity] && 2 * long_term_frame_idx + 1 == long_term_pic_num as u64;
+                    named[1 - parity] = held[1 - parity] && 2 * long_term_frame_idx == long_term_pic_num as u64;
+                }
+                false => named = [long_term_frame_idx == long_term_pic_num as u64; 2],
+            }
+
+            named.map(|named| named && x.long_term)
+        };
+
+        slot.long_term = false;
+
+        for operation in &marking.operations {
+            match *operation {
+                MemoryManagementControlOperation::ShortTermUnused {
+                    difference_of_pic_nums_minus1,
+                } => {
+                    // Of a field pair only the named field stops being a reference.
+                    self.references.retain_mut(|x| {
+                        let named = short_term_fields(x, difference_of_pic_nums_minus1);
+                        x.long_term || x.unmark_fields(named)
+                    });
+                }
+                MemoryManagementControlOperation::LongTermUnused { long_term_pic_num } => {
+                    self.references.retain_mut(|x| {
+                        let named = long_term_fields(x, long_term_pic_num);
+                        x.unmark_fields(named)
+                    });
+                }
+                MemoryManagementControlOperation::ShortTermToLongTerm {
+                    difference_of_pic_nums_minus1,
+                    long_term_frame_idx,
+                } => {
+                    // The pair of the named field keeps its index if its other field already got it (8.2.5.4.3).
+                    let pair_of_named =
+                        |x: &DpbSlot| header.field_pic_flag && short_term_fields(x, difference_of_pic_nums_minus1).contains(&true);
+
+                    self.check_long_term_frame_idx(long_term_frame_idx)?;
+                    self.references
+                        .retain(|x| !x.long_term || x.long_term_frame_idx != long_term_frame_idx || pair_of_named(x));
+
+                    for reference in &mut self.references {
+                        if !reference.long_term && short_term_fields(reference, difference_of_pic_nums_minus1).contains(&true) {
+                            reference.long_term = true;
+                            reference.long_term_frame_idx = long_term_frame_idx;
+                        }
+                    }
+                }
+                MemoryManagementControlOperation::MaxLongTermFrameIdx {
+                    max_long_term_frame_idx_plus1,
+                } => {
+                    self.max_long_term_frame_idx = max_long_term_frame_idx_plus1.checked_sub(1);
+                    self.references
+                        .retain(|x| !x.long_term || max_long_term_frame_idx_plus1 > x.long_term_frame_idx);
+                }
+                MemoryManagementControlOperation::AllUnused => {
+                    self.references.clear();
+                    self.max_long_term_frame_idx = None;
+
+                    // The picture is treated as if it had `frame_num` 0, with its POC rebased to 0 (8.2.1).
+                    let temp_pic_order_cnt = slot.pic_order_cnt[0].min(slot.pic_order_cnt[1]);
+                    slot.frame_num = 0;
+                    slot.pic_order_cnt = slot.pic_order_cnt.map(|x| x - temp_pic_order_cnt);
+                }
+                MemoryManagementControlOperation::CurrentToLongTerm { long_term_frame_idx } => {
+                    self.check_long_term_frame_idx(long_term_frame_idx)?;
+                    self.references
+                        .retain(|x| !x.long_term || x.long_term_frame_idx != long_term_frame_idx);
+
+                    slot.long_term = true;
+                    slot.long_term_frame_idx = long_term_frame_idx;
+                }
+            }
+        }
+
+        self.references.retain(|x| x.index != slot.index);
+        self.references.push(slot);
+
+        Ok(())
+    }
+
+    /// Keeps a pending first field in sync with how it was marked.
+    fn update_first_field(&mut self, slot: DpbSlot) {
+        if let Some(first_field) = &mut self.first_field {
+            if first_field.index == slot.index {
+                *first_field = slot;
+            }
+        }
+    }
+
+    fn check_long_term_frame_idx(&self, long_term_frame_idx: u32) -> Result<(), Error> {
+        match self.max_long_term_frame_idx {
+            Some(x) if long_term_frame_idx <= x => Ok(()),
+            _ => Err(error!(Variant::InvalidBitstream, "LongTermFrameIdx exceeds MaxLongTermFrameIdx.")),
+        }
+    }
+
+    /// Removes all references, e.g., when seeking.
+    pub fn reset(&mut self) {
+        self.references.clear();
+        self.first_field = None;
+        self.max_long_term_frame_idx = None;
+    }
+
+    /// All active references, in decoding order.
+    pub fn references(&self) -> &[DpbSlot] {
+        &self.references
+    }
+
+    pub fn max_slots(&self) -> u32 {
+        self.max_slots
+    }
+
+    /// Produces the reference slot infos for decoding into `setup` while referencing all active references.
+    ///
+    /// `resources` are the picture resources for each DPB slot, indexed by slot index.
+    pub(crate) fn reference_slots<'a>(
+        &self,
+        setup: &DpbSlot,
+        resources: &[VideoPictureResourceInfoKHR<'a>],
+    ) -> Result<ReferenceSlots<'a>, Error> {
+        // The second field of a pair may reference the first one, which lives in the setup slot.
+        let references = self
+            .references
+            .iter()
+            .filter(|x| x.index != setup.index || (x.is_single_field() && !setup.is_single_field()))
+            .copied()
+            .collect::<Vec<_>>();
+        let resource = |slot: &DpbSlot| {
+            resources
+                .get(slot.index as usize)
+                .copied()
+                .ok_or_else(|| error!(Variant::NoFreeDpbSlot, "No picture resource for DPB slot."))
+        };
+
+        let mut std_infos = Vec::with_capacity(references.len() + 1);
+        let mut picture_resources = Vec::with_capacity(references.len() + 1);
+
+        for slot in references.iter().chain(std::iter::once(setup)) {
+            std_infos.push(slot.std_reference_info());
+            picture_resources.push(resource(slot)?);
+        }
+
+        let mut rval = ReferenceSlots {
+            std_infos,
+            dpb_infos: Vec::with_capacity(references.len() + 1),
+            picture_resources,
+            slots: Vec::with_capacity(references.len() + 1),
+            slot_indices: references
+                .iter()
+                .map(|x| x.index as i32)
+                .chain(std::iter::once(setup.index as i32))
+                .collect(),
+        };
+
+        // From here on the vectors above must not be resized anymore, as the infos below point into them.
+        for std_info in &rval.std_infos {
+            rval.dpb_infos.push(VideoDecodeH264DpbSlotInfoKHR {
+                p_std_reference_info: std_info,
+                ..Default::default()
+            });
+        }
+
+        for (i, index) in rval.slot_indices.iter().enumerate() {
+            let slot = VideoReferenceSlotInfoKHR {
+                p_next: (&rval.dpb_infos[i] as *const VideoDecodeH264DpbSlotInfoKHR).cast(),
+                slot_index: *index,
+                p_picture_resource: &rval.picture_resources[i],
+                ..Default::default()
+            };
+
+            rval.slots.push(slot);
+        }
+
+        Ok(rval)
+    }
+}
+
+/// Reference slot infos for a single decode operation, and everything they point to.
+///
+/// All pointees live on the heap, so moving this struct is fine, but the vectors must not be modified.
+pub(crate) struct ReferenceSlots<'a> {
+    std_infos: Vec<StdVideoDecodeH264ReferenceInfo>,
+    dpb_infos: Vec<VideoDecodeH264DpbSlotInfoKHR<'a>>,
+    picture_resources: Vec<VideoPictureResourceInfoKHR<'a>>,
+    slots: Vec<VideoReferenceSlotInfoKHR<'a>>,
+    slot_indices: Vec<i32>,
+}
+
+impl<'a> ReferenceSlots<'a> {
+    /// Slots referenced by the decode operation (i.e., without the setup slot).
+    pub(crate) fn references(&self) -> &[VideoReferenceSlotInfoKHR<'a>] {
+        &self.slots[..self.slots.len() - 1]
+    }
+
+    /// The slot the decoded picture is written to.
+    pub(crate) fn setup(&self) -> &VideoReferenceSlotInfoKHR<'a> {
+        &self.slots[self.slots.len() - 1]
+    }
+
+    /// DPB slot indices of [`Self::references`] followed by the setup slot.
+    pub(crate) fn slot_indices(&self) -> &[i32] {
+        &self.slot_indices
+    }
+
+    /// Slots to bind when beginning video coding, the setup slot is marked as not yet active.
+    pub(crate) fn begin_coding_slots(&self) -> Vec<VideoReferenceSlotInfoKHR<'a>> {
+        let mut rval = self.slots.clone();
+
+        if let Some(setup) = rval.last_mut() {
+            setup.slot_index = -1;
+        }
+
+        rval
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::Error;
+    use crate::video::h264::{DecRefPicMarking, MemoryManagementControlOperation, SliceHeader};
+    use crate::video::DpbSlotManager;
+
+    #[test]
+    fn sliding_window_evicts_oldest() -> Result<(), Error> {
+        let mut dpb = DpbSlotManager::new(4, 2);
+
+        let a = dpb.next_slot(0, [0, 0], true)?;
+        dpb.mark_reference(a)?;
+        let b = dpb.next_slot(1, [2, 2], false)?;
+        dpb.mark_reference(b)?;
+        let c = dpb.next_slot(2, [4, 4], false)?;
+
+        assert_eq!((a.index(), b.index(), c.index()), (0, 1, 2));
+        assert_eq!(dpb.references().len(), 2);
+
+        dpb.mark_reference(c)?;
+
+        let frame_nums = dpb.references().iter().map(|x| x.frame_num()).collect::<Vec<_>>();
+        assert_eq!(frame_nums, [1, 2]);
+
+        // Slot 0 was freed and can be reused.
+        assert_eq!(dpb.next_slot(3, [6, 6], false)?.index(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_management_control_operations() -> Result<(), Error> {
+        let mut dpb = DpbSlotManager::new(17, 16);
+        let marking = |frame_num, operations| SliceHeader {
+            nal_ref_idc: 1,
+            idr: frame_num == 0,
+            frame_num,
+            dec_ref_pic_marking: Some(DecRefPicMarking {
+                operations,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        for frame_num in 0..4 {
+            let slot = dpb.next_slot(frame_num, [0, 0], frame_num == 0)?;
+            dpb.mark_decoded(slot, &marking(frame_num, vec![]), 16)?;
+        }
+
+        // Frame 4 drops frame 2 (PicNum 4 - 2), moves frame 1 to long-term index 0, and becomes long-term index 1 itself.
+        let slot = dpb.next_slot(4, [8, 8], false)?;
+        let operations = vec![
+            MemoryManagementControlOperation::MaxLongTermFrameIdx {
+                max_long_term_frame_idx_plus1: 2,
+            },
+            MemoryManagementControlOperation::ShortTermUnused {
+                difference_of_pic_nums_minus1: 1,
+            },
+            MemoryManagementControlOperation::ShortTermToLongTerm {
+                difference_of_pic_nums_minus1: 2,
+                long_term_frame_idx: 0,
+            },
+            MemoryManagementControlOperation::CurrentToLongTerm { long_term_frame_idx: 1 },
+        ];
+        dpb.mark_decoded(slot, &marking(4, operations), 16)?;
+
+        let references = dpb.references().iter().map(|x| (x.frame_num(), x.long_term())).collect::<Vec<_>>();
+        assert_eq!(references, [(0, false), (1, true), (3, false), (4, true)]);
+        assert_eq!(dpb.references()[3].std_reference_info().FrameNum, 1);
+
+        // MMCO 5 clears everything, the picture itself continues as frame 0 with POC rebased to 0.
+        let slot = dpb.next_slot(5, [10, 12], false)?;
+        dpb.mark_decoded(slot, &marking(5, vec![MemoryManagementControlOperation::AllUnused]), 16)?;
+
+        assert_eq!(dpb.references().len(), 1);
+        assert_eq!(dpb.references()[0].frame_num(), 0);
+        assert_eq!(dpb.references()[0].pic_order_cnt(), [0, 2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn long_term_pic_nums() -> Result<(), Error> {
+        let mut dpb = DpbSlotManager::new(17, 16);
+        let marking = |frame_num, field_pic_flag, operations| SliceHeader {
+            nal_ref_idc: 1,
+            idr: frame_num == 0,
+            frame_num,
+            field_pic_flag,
+            dec_ref_pic_marking: Some(DecRefPicMarking {
+                operations,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        for frame_num in 0..3 {
+            let slot = dpb.next_slot(frame_num, [0, 0], frame_num == 0)?;
+            dpb.mark_decoded(slot, &marking(frame_num, false, vec![]), 16)?;
+        }
+
+        // Frame 3 moves frame 2 to long-term index 0 and frame 1 to long-term index 1.
+        let slot = dpb.next_slot(3, [6, 6], false)?;
+        let operations = vec![
+            MemoryManagementControlOperation::MaxLongTermFrameIdx {
+                max_long_term_frame_idx_plus1: 2,
+            },
+            MemoryManagementControlOperation::ShortTermToLongTerm {
+                difference_of_pic_nums_minus1: 0,
+                long_term_frame_idx: 0,
+            },
+            MemoryManagementControlOperation::ShortTermToLongTerm {
+                difference_of_pic_nums_minus1: 1,
+                long_term_frame_idx: 1,
+            },
+        ];
+        dpb.mark_decoded(slot, &marking(3, false, operations), 16)?;
+
+        // Indices beyond `MaxLongTermFrameIdx` are invalid.
+        let slot = dpb.next_slot(4, [8, 8], false)?;
+        let operations = vec![MemoryManagementControlOperation::ShortTermToLongTerm {
+            difference_of_pic_nums_minus1: 0,
+            long_term_frame_idx: 2,
+        }];
+        assert!(dpb.clone().mark_decoded(slot, &marking(4, false, operations), 16).is_err());
+
+        // For a top field, `LongTermPicNum` 3 is the top field of long-term index 1, i.e., frame 1 keeps its bottom field.
+        let header = marking(
+            4,
+            true,
+            vec![MemoryManagementControlOperation::LongTermUnused { long_term_pic_num: 3 }],
+        );
+        let slot = dpb.next_slot_for(&header, [8, 0])?;
+        dpb.mark_decoded(slot, &header, 16)?;
+
+        let references = dpb.references().iter().map(|x| (x.frame_num(), x.long_term())).collect::<Vec<_>>();
+        assert_eq!(references, [(0, false), (1, true), (2, true), (3, false), (4, false)]);
+        assert_eq!(dpb.references()[1].fields(), [false, true]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn field_pic_nums() -> Result<(), Error> {
+        let mut dpb = DpbSlotManager::new(17, 16);
+        let field = |frame_num, bottom_field_flag: bool, operations| SliceHeader {
+            nal_ref_idc: 1,
+            idr: frame_num == 0 && !bottom_field_flag,
+            frame_num,
+            field_pic_flag: true,
+            bottom_field_flag,
+            dec_ref_pic_marking: Some(DecRefPicMarking {
+                operations,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        for frame_num in 0..3 {
+            for bottom_field_flag in [false, true] {
+                let header = field(frame_num, bottom_field_flag, vec![]);
+                let slot = dpb.next_slot_for(&header, [0, 0])?;
+                dpb.mark_decoded(slot, &header, 16)?;
+            }
+        }
+
+        // For the top field of frame 3 `CurrPicNum` is 7, so `PicNum` 2 is the bottom field of frame 1, and 5 and 4
+        // are the top and bottom field of frame 2, which both go to long-term index 0.
+        let operations = vec![
+            MemoryManagementControlOperation::MaxLongTermFrameIdx {
+                max_long_term_frame_idx_plus1: 1,
+            },
+            MemoryManagementControlOperation::ShortTermUnused {
+                difference_of_pic_nums_minus1: 4,
+            },
+            MemoryManagementControlOperation::ShortTermToLongTerm {
+                difference_of_pic_nums_minus1: 1,
+                long_term_frame_idx: 0,
+            },
+            MemoryManagementControlOperation::ShortTermToLongTerm {
+                difference_of_pic_nums_minus1: 2,
+                long_term_frame_idx: 0,
+            },
+        ];
+        let header = field(3, false, operations);
+        let slot = dpb.next_slot_for(&header, [12, 0])?;
+        dpb.mark_decoded(slot, &header, 16)?;
+
+        let references = dpb
+            .references()
+            .iter()
+            .map(|x| (x.frame_num(), x.fields(), x.long_term()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            references,
+            [
+                (0, [true, true], false),
+                (1, [true, false], false),
+                (2, [true, true], true),
+                (3, [true, false], false)
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn field_pairs_share_slot() -> Result<(), Error> {
+        let mut dpb = DpbSlotManager::new(17, 16);
+        let field = |frame_num, idr, bottom_field_flag| SliceHeader {
+            nal_ref_idc: 1,
+            idr,
+            frame_num,
+            field_pic_flag: true,
+            bottom_field_flag,
+            dec_ref_pic_marking: Some(DecRefPicMarking::default()),
+            ..Default::default()
+        };
+
+        // IDR top field, followed by its (non-IDR) bottom field.
+        let top = dpb.next_slot_for(&field(0, true, false), [0, 0])?;
+        dpb.mark_decoded(top, &field(0, true, false), 16)?;
+
+        let bottom = dpb.next_slot_for(&field(0, false, true), [0, 1])?;
+        dpb.mark_decoded(bottom, &field(0, false, true), 16)?;
+
+        assert_eq!(top.index(), bottom.index());
+        assert_eq!(top.fields(), [true, false]);
+        assert_eq!(bottom.fields(), [true, true]);
+        assert_eq!(dpb.references().len(), 1);
+        assert_eq!(dpb.references()[0].pic_order_cnt(), [0, 1]);
+
+        let std = dpb.references()[0].std_reference_info();
+        assert_eq!((std.flags.top_field_flag(), std.flags.bottom_field_flag()), (1, 1));
+
+        // Next frame starts a new pair in a new slot.
+        let next = dpb.next_slot_for(&field(1, false, false), [4, 0])?;
+        assert_eq!(next.index(), 1);
+        assert_eq!(next.pic_order_cnt(), [4, 0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn idr_clears_references() -> Result<(), Error> {
+        let mut dpb = DpbSlotManager::new(17, 16);
+
+        for i in 0..5 {
+            let slot = dpb.next_slot(i, [0, 0], i == 0)?;
+            dpb.mark_reference(slot)?;
+        }
+
+        assert_eq!(dpb.references().len(), 5);
+
+        let idr = dpb.next_slot(0, [0, 0], true)?;
+
+        assert_eq!(idr.index(), 0);
+        assert!(dpb.references().is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,396 @@
+use crate::allocation::Allocation;
+use crate::device::{Device, DeviceShared};
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::video::session::{bind_session_memory, select_format, video_formats};
+use crate::video::{QualityLevelProperties, RateControlInfo, StreamInspector, VideoSessionInfo};
+use ash::khr::{
+    video_encode_queue::{DeviceFn as KhrVideoEncodeQueueDeviceFn, InstanceFn as KhrVideoEncodeQueueInstanceFn},
+    video_queue::{DeviceFn as KhrVideoQueueDeviceFn, InstanceFn as KhrVideoQueueInstanceFn},
+};
+use ash::vk::{
+    self, ExtensionProperties, ImageUsageFlags, PhysicalDeviceVideoEncodeQualityLevelInfoKHR, VideoCapabilitiesKHR,
+    VideoCodecOperationFlagsKHR, VideoEncodeCapabilitiesKHR, VideoEncodeH264CapabilitiesKHR, VideoEncodeH264QualityLevelPropertiesKHR,
+    VideoEncodeQualityLevelPropertiesKHR, VideoEncodeRateControlModeFlagsKHR, VideoSessionCreateFlagsKHR, VideoSessionCreateInfoKHR,
+    VideoSessionKHR,
+};
+use std::ops::RangeInclusive;
+use std::ptr::null;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// What an encode session supports.
+#[derive(Debug, Clone)]
+pub(crate) struct VideoEncodeCapabilities {
+    rate_control_modes: VideoEncodeRateControlModeFlagsKHR,
+    max_bitrate: u64,
+    qp: RangeInclusive<i32>,
+    /// Maximum number of L0 references of P- and B-pictures, and of L1 references.
+    max_references: [u32; 3],
+}
+
+impl VideoEncodeCapabilities {
+    pub(crate) fn new(rate_control_modes: VideoEncodeRateControlModeFlagsKHR, max_bitrate: u64, qp: RangeInclusive<i32>) -> Self {
+        Self {
+            rate_control_modes,
+            max_bitrate,
+            qp,
+            max_references: [0; 3],
+        }
+    }
+
+    /// Sets the maximum number of L0 references of P- and B-pictures, and of L1 references.
+    pub(crate) fn max_references(mut self, p: u32, b: u32, l1: u32) -> Self {
+        self.max_references = [p, b, l1];
+        self
+    }
+
+    pub(crate) fn rate_control_modes(&self) -> VideoEncodeRateControlModeFlagsKHR {
+        self.rate_control_modes
+    }
+
+    pub(crate) fn max_bitrate(&self) -> u64 {
+        self.max_bitrate
+    }
+
+    pub(crate) fn min_qp(&self) -> i32 {
+        *self.qp.start()
+    }
+
+    pub(crate) fn max_qp(&self) -> i32 {
+        *self.qp.end()
+    }
+
+    pub(crate) fn max_p_references(&self) -> u32 {
+        self.max_references[0]
+    }
+
+    pub(crate) fn max_b_references(&self) -> u32 {
+        self.max_references[1]
+    }
+
+    pub(crate) fn max_l1_references(&self) -> u32 {
+        self.max_references[2]
+    }
+}
+
+/// Session state as of the last recorded encode, as beginning to code must restate the current rate control.
+#[derive(Default)]
+pub(crate) struct VideoEncodeState {
+    /// If the session was reset, which has to happen before the first encode.
+    pub initialized: bool,
+    /// Rate control last set, `None` if still the implementation's default.
+    pub rate_control: Option<RateControlInfo>,
+}
+
+pub(crate) struct VideoEncodeSessionShared {
+    shared_device: Arc<DeviceShared>,
+    native_queue_fns: KhrVideoQueueDeviceFn,
+    native_encode_queue_fns: KhrVideoEncodeQueueDeviceFn,
+    native_session: VideoSessionKHR,
+    /// Memory bound to the session, only freed after the session was destroyed in `drop`.
+    #[allow(unused)]
+    allocations: Vec<Allocation>,
+    encode_capabilities: VideoEncodeCapabilities,
+    quality_levels: Vec<QualityLevelProperties>,
+    info: VideoSessionInfo,
+    state: Mutex<VideoEncodeState>,
+}
+
+impl VideoEncodeSessionShared {
+    pub fn new(device: &Device, stream_inspector: &impl StreamInspector, info: &VideoSessionInfo) -> Result<Self, Error> {
+        let shared_device = device.shared();
+        let shared_instance = shared_device.instance();
+
+        let native_device = shared_device.native();
+        let native_instance = shared_instance.native();
+        let native_entry = shared_instance.native_entry();
+
+        let profiles = stream_inspector.profiles();
+        let codec_operation = profiles.info.video_codec_operation;
+
+        if !shared_device.video_features().encode_h264() {
+            return Err(error!(Variant::FeatureNotSupported, "Device cannot encode H.264."));
+        }
+
+        if codec_operation != VideoCodecOperationFlagsKHR::ENCODE_H264 {
+            return Err(error!(
+                Variant::UnsupportedProfile,
+                "Encode sessions need an encode profile, got {codec_operation:?}."
+            ));
+        }
+
+        let extension_version = vk::make_api_version(0, 1, 0, 0);
+        let extensions_names = ExtensionProperties::default()
+            .spec_version(extension_version)
+            .extension_name(c"VK_STD_vulkan_video_codec_h264_encode")?;
+
+        let queue_family_index = shared_device
+            .physical_device()
+            .queue_family_infos()
+            .any_encode()
+            .ok_or_else(|| error!(Variant::QueueNotFound))?;
+
+        unsafe {
+            let load = |x: &std::ffi::CStr| {
+                native_entry
+                    .get_instance_proc_addr(native_instance.handle(), x.as_ptr().cast())
+                    .expect("Must have function pointer") as *const _
+            };
+
+            let queue_fns = KhrVideoQueueDeviceFn::load(load);
+            let encode_queue_fns = KhrVideoEncodeQueueDeviceFn::load(load);
+            let video_instance_fn = KhrVideoQueueInstanceFn::load(load);
+            let encode_instance_fn = KhrVideoEncodeQueueInstanceFn::load(load);
+
+            let get_physical_device_video_format_properties_khr = video_instance_fn.get_physical_device_video_format_properties_khr;
+            let get_physical_device_video_capabilities = video_instance_fn.get_physical_device_video_capabilities_khr;
+            let create_video_session = queue_fns.create_video_session_khr;
+
+            let video_profile = profiles.info;
+            let physical_device = shared_device.physical_device().native();
+
+            let mut video_encode_h264_capabilities = VideoEncodeH264CapabilitiesKHR::default();
+            let mut video_encode_capabilities = VideoEncodeCapabilitiesKHR::default();
+            let mut video_capabilities = VideoCapabilitiesKHR::default()
+                .push_next(&mut video_encode_capabilities)
+                .push_next(&mut video_encode_h264_capabilities);
+
+            (get_physical_device_video_capabilities)(physical_device, &video_profile, &mut video_capabilities)
+                .result()
+                .map_err(|e| match e {
+                    vk::Result::ERROR_VIDEO_PROFILE_OPERATION_NOT_SUPPORTED_KHR
+                    | vk::Result::ERROR_VIDEO_PROFILE_FORMAT_NOT_SUPPORTED_KHR
+                    | vk::Result::ERROR_VIDEO_PROFILE_CODEC_NOT_SUPPORTED_KHR => error!(
+                        Variant::UnsupportedProfile,
+                        "Device cannot encode with profile_idc {} ({e}).", profiles.info_h264_encode.std_profile_idc
+                    ),
+                    e => e.into(),
+                })?;
+
+            let encode_capabilities = VideoEncodeCapabilities::new(
+                video_encode_capabilities.rate_control_modes,
+                video_encode_capabilities.max_bitrate,
+                video_encode_h264_capabilities.min_qp..=video_encode_h264_capabilities.max_qp,
+            )
+            .max_references(
+                video_encode_h264_capabilities.max_p_picture_l0_reference_count,
+                video_encode_h264_capabilities.max_b_picture_l0_reference_count,
+                video_encode_h264_capabilities.max_l1_reference_count,
+            );
+
+            let get_quality_level_properties = encode_instance_fn.get_physical_device_video_encode_quality_level_properties_khr;
+            let mut quality_levels = Vec::with_capacity(video_encode_capabilities.max_quality_levels as usize);
+
+            for quality_level in 0..video_encode_capabilities.max_quality_levels {
+                let quality_level_info = PhysicalDeviceVideoEncodeQualityLevelInfoKHR::default()
+                    .video_profile(&video_profile)
+                    .quality_level(quality_level);
+                let mut properties_h264 = VideoEncodeH264QualityLevelPropertiesKHR::default();
+                let mut properties = VideoEncodeQualityLevelPropertiesKHR::default().push_next(&mut properties_h264);
+
+                (get_quality_level_properties)(physical_device, &quality_level_info, &mut properties).result()?;
+
+                let rate_control_mode = properties.preferred_rate_control_mode;
+                quality_levels.push(QualityLevelProperties::new(quality_level, rate_control_mode, &properties_h264));
+            }
+
+            if info.get_quality_level() >= video_encode_capabilities.max_quality_levels {
+                return Err(error!(
+                    Variant::FeatureNotSupported,
+                    "Quality level {} not supported, device has {} levels.",
+                    info.get_quality_level(),
+                    video_encode_capabilities.max_quality_levels
+                ));
+            }
+
+            let formats_src = video_formats(
+                get_physical_device_video_format_properties_khr,
+                physical_device,
+                &video_profile,
+                ImageUsageFlags::VIDEO_ENCODE_SRC_KHR,
+            )?;
+            let formats_dpb = video_formats(
+                get_physical_device_video_format_properties_khr,
+                physical_device,
+                &video_profile,
+                ImageUsageFlags::VIDEO_ENCODE_DPB_KHR,
+            )?;
+
+            let picture_format = select_format(info.get_picture_format(), profiles.format(), &formats_src)?;
+            let reference_picture_format = select_format(info.get_reference_picture_format(), profiles.format(), &formats_dpb)?;
+            let mut info = info
+                .clone()
+                .picture_format(picture_format)
+                .reference_picture_format(reference_picture_format);
+
+            // With `VK_KHR_video_maintenance1` queries can be recorded as part of encode operations.
+            if shared_device.video_features().inline_queries() {
+                let flags = info.get_flags() | VideoSessionCreateFlagsKHR::INLINE_QUERIES;
+                info = info.flags(flags);
+            }
+
+            let video_session_create_info = VideoSessionCreateInfoKHR::default()
+                .queue_family_index(queue_family_index)
+                .flags(info.get_flags())
+                .video_profile(&profiles.info)
+                .picture_format(picture_format)
+                .max_coded_extent(info.get_max_coded_extent())
+                .reference_picture_format(reference_picture_format)
+                .max_dpb_slots(info.get_max_dpb_slots())
+                .max_active_reference_pictures(info.get_max_active_reference_pictures())
+                .std_header_version(&extensions_names);
+
+            let mut native_session = VideoSessionKHR::default();
+
+            create_video_session(native_device.handle(), &video_session_create_info, null(), &mut native_session).result()?;
+            shared_device.set_default_name(native_session, "VideoEncodeSession");
+
+            let allocations = bind_session_memory(device, info.get_allocator().as_deref(), &queue_fns, native_session)?;
+
+            Ok(Self {
+                shared_device,
+                native_queue_fns: queue_fns,
+                native_encode_queue_fns: encode_queue_fns,
+                native_session,
+                allocations,
+                encode_capabilities,
+                quality_levels,
+                info,
+                state: Mutex::new(VideoEncodeState::default()),
+            })
+        }
+    }
+
+    pub(crate) fn native(&self) -> VideoSessionKHR {
+        self.native_session
+    }
+
+    pub(crate) fn queue_fns(&self) -> KhrVideoQueueDeviceFn {
+        self.native_queue_fns.clone()
+    }
+
+    pub(crate) fn encode_fns(&self) -> KhrVideoEncodeQueueDeviceFn {
+        self.native_encode_queue_fns.clone()
+    }
+
+    pub(crate) fn device(&self) -> Arc<DeviceShared> {
+        self.shared_device.clone()
+    }
+
+    pub(crate) fn encode_capabilities(&self) -> &VideoEncodeCapabilities {
+        &self.encode_capabilities
+    }
+
+    pub(crate) fn quality_levels(&self) -> &[QualityLevelProperties] {
+        &self.quality_levels
+    }
+
+    pub(crate) fn info(&self) -> &VideoSessionInfo {
+        &self.info
+    }
+
+    /// The state is only modified once an encode was fully recorded, so it's consistent even if poisoned.
+    pub(crate) fn state(&self) -> MutexGuard<'_, VideoEncodeState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for VideoEncodeSessionShared {
+    fn drop(&mut self) {
+        let native_device = self.shared_device.native();
+        let destroy_video_session_khr = self.native_queue_fns.destroy_video_session_khr;
+
+        unsafe {
+            destroy_video_session_khr(native_device.handle(), self.native_session, null());
+        }
+    }
+}
+
+/// Vulkan-internal state needed for encode ops.
+pub struct VideoEncodeSession {
+    shared: Arc<VideoEncodeSessionShared>,
+}
+
+impl VideoEncodeSession {
+    /// Creates a session encoding streams described by `stream_inspector`, e.g., a [`H264EncodeInfo`](crate::video::h264::H264EncodeInfo).
+    pub fn new(device: &Device, stream_inspector: &impl StreamInspector, info: &VideoSessionInfo) -> Result<Self, Error> {
+        let shared = VideoEncodeSessionShared::new(device, stream_inspector, info)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    pub(crate) fn shared(&self) -> Arc<VideoEncodeSessionShared> {
+        self.shared.clone()
+    }
+
+    /// The raw `VkVideoSessionKHR`.
+    pub fn raw(&self) -> VideoSessionKHR {
+        self.shared.native()
+    }
+
+    /// Names this session in validation messages and debugging tools, instead of the default `vulkan_video::VideoEncodeSession#N`.
+    ///
+    /// Does nothing if `VK_EXT_debug_utils` isn't available.
+    pub fn set_name(&self, name: &str) -> Result<(), Error> {
+        self.shared.device().set_object_name(self.raw(), name)
+    }
+
+    /// The loaded `VK_KHR_video_queue` functions.
+    pub fn raw_queue_fns(&self) -> KhrVideoQueueDeviceFn {
+        self.shared.queue_fns()
+    }
+
+    /// The loaded `VK_KHR_video_encode_queue` functions.
+    pub fn raw_encode_fns(&self) -> KhrVideoEncodeQueueDeviceFn {
+        self.shared.encode_fns()
+    }
+
+    /// The info this session was created with, picture and reference formats are those actually used.
+    pub fn info(&self) -> VideoSessionInfo {
+        self.shared.info().clone()
+    }
+
+    /// Properties of all quality levels the device supports for this session's profile, indexed by level.
+    pub fn quality_levels(&self) -> Vec<QualityLevelProperties> {
+        self.shared.quality_levels().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::device::Device;
+    use crate::error::{Error, Variant};
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::video::h264::{H264EncodeInfo, H264StreamInspector};
+    use crate::video::{VideoEncodeSession, VideoSessionInfo};
+
+    #[test]
+    #[cfg(not(miri))]
+    fn create_encode_session() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+
+        if !device.video_features().encode_h264() {
+            return Ok(());
+        }
+
+        let session = VideoEncodeSession::new(&device, &H264EncodeInfo::new(), &VideoSessionInfo::new())?;
+        let quality_levels = session.quality_levels();
+
+        assert!(!quality_levels.is_empty());
+
+        // Levels past the last one supported are rejected.
+        let info = VideoSessionInfo::new().quality_level(quality_levels.len() as u32);
+        let session = VideoEncodeSession::new(&device, &H264EncodeInfo::new(), &info);
+        assert!(session.is_err_and(|e| matches!(e.variant(), Variant::FeatureNotSupported)));
+
+        // Decode profiles can't be used to encode.
+        let session = VideoEncodeSession::new(&device, &H264StreamInspector::new(), &VideoSessionInfo::new());
+        assert!(session.is_err_and(|e| matches!(e.variant(), Variant::UnsupportedProfile)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use crate::error::Error;
+use crate::video::encodesession::{VideoEncodeSession, VideoEncodeSessionShared};
+use crate::video::h264::H264EncodeInfo;
+use ash::vk::{
+    VideoEncodeH264SessionParametersAddInfoKHR, VideoEncodeH264SessionParametersCreateInfoKHR, VideoEncodeQualityLevelInfoKHR,
+    VideoSessionParametersCreateInfoKHR, VideoSessionParametersKHR,
+};
+use std::ptr::null;
+use std::sync::Arc;
+
+pub(crate) struct VideoEncodeSessionParametersShared {
+    shared_session: Arc<VideoEncodeSessionShared>,
+    native_parameters: VideoSessionParametersKHR,
+}
+
+impl VideoEncodeSessionParametersShared {
+    pub fn new(shared_session: Arc<VideoEncodeSessionShared>, encode_info: &H264EncodeInfo) -> Result<Self, Error> {
+        let native_session = shared_session.native();
+        let native_device = shared_session.device().native();
+        let native_queue_fns = shared_session.queue_fns();
+
+        let sps = [encode_info.std_sps()?];
+        let pps = [encode_info.std_pps()];
+
+        let add_info = VideoEncodeH264SessionParametersAddInfoKHR::default()
+            .std_sp_ss(&sps)
+            .std_pp_ss(&pps);
+
+        let mut video_encode_h264_session_parameters_create_info = VideoEncodeH264SessionParametersCreateInfoKHR::default()
+            .max_std_sps_count(1)
+            .max_std_pps_count(1)
+            .parameters_add_info(&add_info);
+
+        // Parameters can only be used with the quality level they were created for.
+        let mut quality_level_info = VideoEncodeQualityLevelInfoKHR::default().quality_level(shared_session.info().get_quality_level());
+
+        let session_create_info = VideoSessionParametersCreateInfoKHR::default()
+            .video_session(native_session)
+            .push_next(&mut video_encode_h264_session_parameters_create_info)
+            .push_next(&mut quality_level_info);
+
+        unsafe {
+            let mut native_parameters = VideoSessionParametersKHR::null();
+            let create_video_session_parameters = native_queue_fns.create_video_session_parameters_khr;
+
+            create_video_session_parameters(native_device.handle(), &session_create_info, null(), &mut native_parameters).result()?;
+
+            Ok(Self {
+                shared_session,
+                native_parameters,
+            })
+        }
+    }
+
+    pub(crate) fn native(&self) -> VideoSessionParametersKHR {
+        self.native_parameters
+    }
+
+    pub(crate) fn video_session(&self) -> Arc<VideoEncodeSessionShared> {
+        self.shared_session.clone()
+    }
+}
+
+impl Drop for VideoEncodeSessionParametersShared {
+    fn drop(&mut self) {
+        let queue_fns = self.shared_session.queue_fns();
+        let native_device = self.shared_session.device().native();
+
+        let destroy_video_session_parameters_khr = queue_fns.destroy_video_session_parameters_khr;
+
+        unsafe {
+            destroy_video_session_parameters_khr(native_device.handle(), self.native_parameters, null());
+        }
+    }
+}
+
+/// The parameter sets (SPS and PPS) a [`VideoEncodeSession`] encodes with.
+pub struct VideoEncodeSessionParameters {
+    shared: Arc<VideoEncodeSessionParametersShared>,
+}
+
+impl VideoEncodeSessionParameters {
+    /// Creates parameters with the SPS and PPS `encode_info` describes.
+    pub fn new(session: &VideoEncodeSession, encode_info: &H264EncodeInfo) -> Result<Self, Error> {
+        let shared = VideoEncodeSessionParametersShared::new(session.shared(), encode_info)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    pub(crate) fn shared(&self) -> Arc<VideoEncodeSessionParametersShared> {
+        self.shared.clone()
+    }
+
+    /// The raw `VkVideoSessionParametersKHR`.
+    pub fn raw(&self) -> VideoSessionParametersKHR {
+        self.shared.native()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::device::Device;
+    use crate::error::Error;
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::video::h264::H264EncodeInfo;
+    use crate::video::{VideoEncodeSession, VideoEncodeSessionParameters, VideoSessionInfo};
+    use ash::vk::Extent2D;
+
+    #[test]
+    #[cfg(not(miri))]
+    fn create_encode_session_parameters() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+
+        if !device.video_features().encode_h264() {
+            return Ok(());
+        }
+
+        let encode_info = H264EncodeInfo::new().extent(Extent2D { width: 500, height: 300 });
+        let session = VideoEncodeSession::new(&device, &encode_info, &VideoSessionInfo::new())?;
+
+        _ = VideoEncodeSessionParameters::new(&session, &encode_info)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+use crate::resources::plane_extent;
+use ash::vk::{Extent3D, Format, ImageAspectFlags};
+
+/// How samples of a frame map to colors, as signalled in the stream's VUI.
+///
+/// Values are code points of ITU-T H.273, e.g., 1 for BT.709 primaries, transfer characteristics and matrix. Streams
+/// without this information report 2 (unspecified) and limited range.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ColorDescription {
+    color_primaries: u8,
+    transfer_characteristics: u8,
+    matrix_coefficients: u8,
+    full_range: bool,
+}
+
+impl Default for ColorDescription {
+    fn default() -> Self {
+        Self::new(2, 2, 2, false)
+    }
+}
+
+impl ColorDescription {
+    pub(crate) fn new(color_primaries: u8, transfer_characteristics: u8, matrix_coefficients: u8, full_range: bool) -> Self {
+        Self {
+            color_primaries,
+            transfer_characteristics,
+            matrix_coefficients,
+            full_range,
+        }
+    }
+
+    pub fn color_primaries(&self) -> u8 {
+        self.color_primaries
+    }
+
+    pub fn transfer_characteristics(&self) -> u8 {
+        self.transfer_characteristics
+    }
+
+    /// Matrix to convert to RGB with, e.g., 1 for BT.709 or 6 for BT.601.
+    pub fn matrix_coefficients(&self) -> u8 {
+        self.matrix_coefficients
+    }
+
+    /// If samples use the full range (e.g., 0..=255 for 8 bits) instead of the limited one (16..=235 for luma).
+    pub fn full_range(&self) -> bool {
+        self.full_range
+    }
+}
+
+/// A decoded video frame in NV12 layout, i.e., the full-resolution luma plane followed by the interleaved, half-resolution chroma plane.
+///
+/// For 4:2:2 and 4:4:4 content the chroma plane only has half horizontal resolution, or full resolution, respectively.
+///
+/// For bit depths above 8 (P010 / P012) every sample takes two little-endian bytes, with the value in the most significant bits.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    format: Format,
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    data: Vec<u8>,
+    timestamp: Option<i64>,
+    color_description: ColorDescription,
+}
+
+impl Frame {
+    pub(crate) fn new(format: Format, width: u32, height: u32, bit_depth: u8, data: Vec<u8>) -> Self {
+        Self {
+            format,
+            width,
+            height,
+            bit_depth,
+            data,
+            timestamp: None,
+            color_description: ColorDescription::default(),
+        }
+    }
+
+    pub(crate) fn with_color_description(mut self, color_description: ColorDescription) -> Self {
+        self.color_description = color_description;
+        self
+    }
+
+    pub(crate) fn with_timestamp(mut self, timestamp: Option<i64>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Format of the decoded images, which determines the chroma subsampling of [`Self::chroma`].
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+
+    /// Timestamp passed along with the access unit this frame was decoded from, see
+    /// [`H264Decoder::decode_with_timestamp`](crate::video::h264::H264Decoder::decode_with_timestamp).
+    pub fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
+
+    /// How to interpret the samples, e.g., for converting to RGB.
+    pub fn color_description(&self) -> ColorDescription {
+        self.color_description
+    }
+
+    /// Number of bytes per luma or chroma sample, 1 or 2.
+    pub fn bytes_per_sample(&self) -> usize {
+        self.bit_depth.div_ceil(8) as usize
+    }
+
+    /// All planes, back to back.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn luma(&self) -> &[u8] {
+        &self.data[..self.luma_size()]
+    }
+
+    /// Interleaved `CbCr` samples.
+    pub fn chroma(&self) -> &[u8] {
+        &self.data[self.luma_size()..]
+    }
+
+    /// Bytes per row of the luma plane, rows are tightly packed.
+    pub fn luma_stride(&self) -> usize {
+        self.width as usize * self.bytes_per_sample()
+    }
+
+    /// Width of the chroma plane in `CbCr` pairs.
+    pub fn chroma_width(&self) -> u32 {
+        self.chroma_extent().width
+    }
+
+    /// Height of the chroma plane in rows.
+    pub fn chroma_height(&self) -> u32 {
+        self.chroma_extent().height
+    }
+
+    /// Bytes per row of the chroma plane, rows are tightly packed.
+    pub fn chroma_stride(&self) -> usize {
+        self.chroma_width() as usize * 2 * self.bytes_per_sample()
+    }
+
+    /// Copies the frame into a single buffer as NV12 (or P010 / P012), i.e., as [`Self::data`].
+    pub fn to_vec_nv12(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    /// Copies the frame into a single buffer as I420, i.e., the luma plane followed by separate `Cb` and `Cr` planes.
+    ///
+    /// For 4:2:2 and 4:4:4 content the chroma planes have the resolution of [`Self::chroma`].
+    pub fn to_vec_i420(&self) -> Vec<u8> {
+        let sample = self.bytes_per_sample();
+        let chroma = self.chroma();
+        let mut rval = Vec::with_capacity(self.data.len());
+
+        rval.extend_from_slice(self.luma());
+
+        for offset in [0, sample] {
+            for pair in chroma.chunks_exact(2 * sample) {
+                rval.extend_from_slice(&pair[offset..offset + sample]);
+            }
+        }
+
+        rval
+    }
+
+    fn luma_size(&self) -> usize {
+        self.width as usize * self.height as usize * self.bytes_per_sample()
+    }
+
+    fn chroma_extent(&self) -> Extent3D {
+        let extent = Extent3D::default().width(self.width).height(self.height).depth(1);
+
+        plane_extent(self.format, ImageAspectFlags::PLANE_1, extent)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::video::Frame;
+    use ash::vk::Format;
+
+    #[test]
+    fn plane_layout() {
+        let data = [1, 1, 1, 1, 1, 1, 1, 1, 2, 3, 4, 5];
+        let frame = Frame::new(Format::G8_B8R8_2PLANE_420_UNORM, 4, 2, 8, data.to_vec());
+
+        assert_eq!(frame.luma_stride(), 4);
+        assert_eq!(frame.chroma_width(), 2);
+        assert_eq!(frame.chroma_height(), 1);
+        assert_eq!(frame.chroma_stride(), 4);
+        assert_eq!(frame.to_vec_nv12(), data);
+        assert_eq!(frame.to_vec_i420(), [1, 1, 1, 1, 1, 1, 1, 1, 2, 4, 3, 5]);
+    }
+
+    #[test]
+    fn plane_layout_16_bit() {
+        let data = [0, 1, 0, 1, 0, 1, 0, 1, 2, 3, 4, 5];
+        let frame = Frame::new(Format::G10X6_B10X6R10X6_2PLANE_420_UNORM_3PACK16, 2, 2, 10, data.to_vec());
+
+        assert_eq!(frame.luma_stride(), 4);
+        assert_eq!(frame.chroma_stride(), 4);
+        assert_eq!(frame.chroma(), [2, 3, 4, 5]);
+        assert_eq!(frame.to_vec_i420(), data);
+    }
+}
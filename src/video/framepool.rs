@@ -0,0 +1,161 @@
+use crate::allocation::Allocation;
+use crate::device::Device;
+use crate::error::Error;
+use crate::resources::{Image, ImageInfo, ImageView, ImageViewInfo};
+use crate::video::StreamInspector;
+use ash::vk::MemoryPropertyFlags;
+use std::sync::{Arc, Mutex, PoisonError};
+
+struct FramePoolShared {
+    images: Vec<(Image, ImageView)>,
+    /// Indices of images not handed out.
+    free: Mutex<Vec<usize>>,
+}
+
+/// A fixed set of output images (with their allocations and views) that are recycled instead of created per frame.
+///
+/// Images are handed out as [`PooledImage`], and return to the pool once that is dropped, e.g., after the consumer
+/// displayed the frame decoded into it:
+///
+/// ```rust,ignore
+/// let pool = FramePool::new(&device, &image_info, &image_view_info, &stream_inspector, 4)?;
+///
+/// if let Some(output) = pool.acquire() {
+///     let decode = DecodeH264::new(&buffer, &parameters, output.image_view(), &dpb_view, &decode_info);
+/// }
+/// ```
+pub struct FramePool {
+    shared: Arc<FramePoolShared>,
+}
+
+impl FramePool {
+    /// Creates `count` images for decoding streams described by `stream_inspector`.
+    pub fn new(
+        device: &Device,
+        image_info: &ImageInfo,
+        image_view_info: &ImageViewInfo,
+        stream_inspector: &impl StreamInspector,
+        count: usize,
+    ) -> Result<Self, Error> {
+        let mut images = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let image = Image::new_video_target(device, image_info, stream_inspector)?;
+            let requirements = image.memory_requirement();
+            let allocation = Allocation::for_requirements(device, &requirements, MemoryPropertyFlags::DEVICE_LOCAL)?;
+            let image = image.bind(&allocation)?;
+            let image_view = ImageView::new(&image, image_view_info)?;
+
+            images.push((image, image_view));
+        }
+
+        let shared = FramePoolShared {
+            images,
+            free: Mutex::new((0..count).rev().collect()),
+        };
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    /// Hands out an unused image, or `None` if all are in use.
+    pub fn acquire(&self) -> Option<PooledImage> {
+        let index = self.shared.free.lock().unwrap_or_else(PoisonError::into_inner).pop()?;
+
+        Some(PooledImage {
+            pool: self.shared.clone(),
+            index,
+        })
+    }
+
+    /// Number of images not in use.
+    pub fn available(&self) -> usize {
+        self.shared.free.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    /// Number of images in the pool.
+    pub fn capacity(&self) -> usize {
+        self.shared.images.len()
+    }
+}
+
+/// An image of a [`FramePool`], returned to the pool when dropped.
+pub struct PooledImage {
+    pool: Arc<FramePoolShared>,
+    index: usize,
+}
+
+impl PooledImage {
+    /// Index of the image within its pool, stable across acquisitions.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.pool.images[self.index].0
+    }
+
+    pub fn image_view(&self) -> &ImageView {
+        &self.pool.images[self.index].1
+    }
+}
+
+impl Drop for PooledImage {
+    fn drop(&mut self) {
+        self.pool.free.lock().unwrap_or_else(PoisonError::into_inner).push(self.index);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::device::Device;
+    use crate::error::Error;
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::resources::{ImageInfo, ImageViewInfo};
+    use crate::video::h264::H264StreamInspector;
+    use crate::video::FramePool;
+    use ash::vk::{
+        Extent3D, Format, ImageAspectFlags, ImageLayout, ImageTiling, ImageType, ImageUsageFlags, ImageViewType, SampleCountFlags,
+    };
+
+    #[test]
+    #[cfg(not(miri))]
+    fn recycles_images() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let stream_inspector = H264StreamInspector::new();
+        let image_info = ImageInfo::new()
+            .format(Format::G8_B8R8_2PLANE_420_UNORM)
+            .samples(SampleCountFlags::TYPE_1)
+            .usage(ImageUsageFlags::TRANSFER_SRC | ImageUsageFlags::VIDEO_DECODE_DST_KHR)
+            .mip_levels(1)
+            .array_layers(1)
+            .image_type(ImageType::TYPE_2D)
+            .tiling(ImageTiling::OPTIMAL)
+            .layout(ImageLayout::UNDEFINED)
+            .extent(Extent3D::default().width(512).height(512).depth(1));
+        let image_view_info = ImageViewInfo::new()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .format(Format::G8_B8R8_2PLANE_420_UNORM)
+            .image_view_type(ImageViewType::TYPE_2D)
+            .layer_count(1)
+            .level_count(1);
+
+        let pool = FramePool::new(&device, &image_info, &image_view_info, &stream_inspector, 2)?;
+        let first = pool.acquire();
+        let second = pool.acquire();
+
+        assert!(first.is_some() && second.is_some());
+        assert!(pool.acquire().is_none());
+
+        let index = first.map(|x| x.index());
+
+        assert_eq!(pool.available(), 1);
+        assert_eq!(pool.acquire().map(|x| x.index()), index);
+        assert_eq!(pool.capacity(), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::video::Frame;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64-bit FNV-1a, which unlike `std`'s hashers is stable across Rust versions and platforms.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter()
+        .fold(FNV_OFFSET_BASIS, |hash, x| (hash ^ u64::from(*x)).wrapping_mul(FNV_PRIME))
+}
+
+/// Hashes of the luma and chroma planes of a decoded frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FrameHash {
+    luma: u64,
+    chroma: u64,
+}
+
+impl FrameHash {
+    pub fn new(frame: &Frame) -> Self {
+        Self {
+            luma: fnv1a(frame.luma()),
+            chroma: fnv1a(frame.chroma()),
+        }
+    }
+
+    pub fn luma(&self) -> u64 {
+        self.luma
+    }
+
+    pub fn chroma(&self) -> u64 {
+        self.chroma
+    }
+}
+
+impl Display for FrameHash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x} {:016x}", self.luma, self.chroma)
+    }
+}
+
+impl FromStr for FrameHash {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut hashes = s.split_whitespace().map(|x| u64::from_str_radix(x, 16));
+
+        match (hashes.next(), hashes.next(), hashes.next()) {
+            (Some(Ok(luma)), Some(Ok(chroma)), None) => Ok(Self { luma, chroma }),
+            _ => Err(error!(Variant::InvalidBitstream, "Invalid frame hash `{s}`.")),
+        }
+    }
+}
+
+/// Hashes of all frames of a decoded stream, to compare decoder output against known good (golden) output.
+///
+/// Goldens are stored as text, one [`FrameHash`] per line in display order:
+///
+/// ```rust,ignore
+/// let frames = decode_all(&mut decoder, h264_data)?;
+/// let goldens = Goldens::parse(include_str!("videos/multi_512x512.golden"))?;
+///
+/// assert_eq!(Goldens::from_frames(&frames), goldens);
+/// ```
+///
+/// To create goldens, write `Goldens::from_frames(&frames).to_string()` to a file, after having checked the frames
+/// are correct (e.g., with `Y4mWriter` of the `y4m` feature).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Goldens {
+    hashes: Vec<FrameHash>,
+}
+
+impl Goldens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_frames(frames: &[Frame]) -> Self {
+        Self {
+            hashes: frames.iter().map(FrameHash::new).collect(),
+        }
+    }
+
+    /// Parses goldens written by [`Display`], ignoring empty lines and `#` comments.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let hashes = text
+            .lines()
+            .map(str::trim)
+            .filter(|x| !x.is_empty() && !x.starts_with('#'))
+            .map(FrameHash::from_str)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { hashes })
+    }
+
+    pub fn push(&mut self, frame: &Frame) {
+        self.hashes.push(FrameHash::new(frame));
+    }
+
+    pub fn hashes(&self) -> &[FrameHash] {
+        &self.hashes
+    }
+
+    /// Indices of frames that differ from `other`, including frames only one of both has.
+    pub fn mismatches(&self, other: &Goldens) -> Vec<usize> {
+        let len = self.hashes.len().max(other.hashes.len());
+
+        (0..len).filter(|i| self.hashes.get(*i) != other.hashes.get(*i)).collect()
+    }
+}
+
+impl Display for Goldens {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for hash in &self.hashes {
+            writeln!(f, "{hash}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::fnv1a;
+    use crate::error::{Error, Variant};
+    use crate::video::{Frame, Goldens};
+    use ash::vk::Format;
+
+    fn frame(luma: u8) -> Frame {
+        Frame::new(Format::G8_B8R8_2PLANE_420_UNORM, 2, 2, 8, vec![luma, 1, 1, 1, 2, 3])
+    }
+
+    #[test]
+    fn fnv1a_reference_values() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn round_trip_and_compare() -> Result<(), Error> {
+        let goldens = Goldens::from_frames(&[frame(0), frame(1)]);
+        let parsed = Goldens::parse(&format!("# multi_512x512.h264\n{goldens}"))?;
+
+        assert_eq!(parsed, goldens);
+        assert_eq!(goldens.mismatches(&Goldens::from_frames(&[frame(0), frame(2), frame(3)])), [1, 2]);
+        assert_ne!(goldens.hashes()[0].luma(), goldens.hashes()[1].luma());
+        assert_eq!(goldens.hashes()[0].chroma(), goldens.hashes()[1].chroma());
+        assert!(Goldens::parse("not a hash").is_err_and(|e| matches!(e.variant(), Variant::InvalidBitstream)));
+
+        Ok(())
+    }
+}
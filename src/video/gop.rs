@@ -0,0 +1,323 @@
+/// Type of an encoded picture.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum FrameType {
+    /// Instantaneous decoder refresh, an intra picture that clears all references.
+    #[default]
+    Idr,
+    /// Intra picture.
+    I,
+    /// Picture predicted from earlier pictures.
+    P,
+    /// Picture predicted from earlier and later pictures, never used as reference.
+    B,
+}
+
+/// Specifies the group of pictures (GOP) structure of an encoded stream.
+///
+/// Defaults to an IDR picture every 60 frames, no B-frames, and P-frames predicting from the previous frame only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GopConfig {
+    idr_period: u32,
+    b_frames: u32,
+    max_references: u32,
+}
+
+impl Default for GopConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GopConfig {
+    pub fn new() -> Self {
+        Self {
+            idr_period: 60,
+            b_frames: 0,
+            max_references: 1,
+        }
+    }
+
+    /// Distance between IDR pictures in frames, 0 means only the first frame is an IDR picture.
+    pub fn idr_period(mut self, idr_period: u32) -> Self {
+        self.idr_period = idr_period;
+        self
+    }
+
+    pub fn get_idr_period(&self) -> u32 {
+        self.idr_period
+    }
+
+    /// Number of consecutive B-frames between I- or P-frames.
+    ///
+    /// B-frames are encoded after the following I- or P-frame, so they add latency.
+    pub fn b_frames(mut self, b_frames: u32) -> Self {
+        self.b_frames = b_frames;
+        self
+    }
+
+    pub fn get_b_frames(&self) -> u32 {
+        self.b_frames
+    }
+
+    /// Number of preceding I- or P-frames a P-frame predicts from, B-frames always use the closest one on each side.
+    pub fn max_references(mut self, max_references: u32) -> Self {
+        self.max_references = max_references;
+        self
+    }
+
+    pub fn get_max_references(&self) -> u32 {
+        self.max_references
+    }
+
+    /// Number of reference frames that must be kept, i.e., `max_num_ref_frames` of the stream.
+    pub fn num_ref_frames(&self) -> u32 {
+        let b_references = match self.b_frames {
+            0 => 1,
+            _ => 2,
+        };
+
+        self.max_references.max(b_references)
+    }
+}
+
+/// A picture to encode, as decided by [`Gop`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GopFrame {
+    index: u64,
+    frame_type: FrameType,
+    references_l0: Vec<u64>,
+    references_l1: Vec<u64>,
+}
+
+impl GopFrame {
+    /// Index of the picture in display (i.e., input) order.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    pub fn frame_type(&self) -> FrameType {
+        self.frame_type
+    }
+
+    /// If later pictures may predict from this one.
+    pub fn is_reference(&self) -> bool {
+        self.frame_type != FrameType::B
+    }
+
+    /// Indices of earlier pictures this one predicts from, closest first.
+    pub fn references_l0(&self) -> &[u64] {
+        &self.references_l0
+    }
+
+    /// Indices of later pictures this one predicts from.
+    pub fn references_l1(&self) -> &[u64] {
+        &self.references_l1
+    }
+}
+
+/// Decides frame types and references of pictures according to a [`GopConfig`].
+///
+/// Pictures are pushed in display order, and returned in the order they must be encoded in. As B-frames
+/// reference the following I- or P-frame, they are held back until that one was pushed:
+///
+/// ```rust
+/// use vulkan_video::video::{FrameType, Gop, GopConfig};
+///
+/// let mut gop = Gop::new(&GopConfig::new().b_frames(1));
+///
+/// assert_eq!(gop.push()[0].frame_type(), FrameType::Idr);
+/// assert!(gop.push().is_empty());
+///
+/// let frames = gop.push();
+/// assert_eq!(frames[0].frame_type(), FrameType::P);
+/// assert_eq!(frames[1].frame_type(), FrameType::B);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Gop {
+    config: GopConfig,
+    /// Index of the next picture pushed.
+    next_index: u64,
+    /// Index of the last IDR picture.
+    last_idr: u64,
+    /// Pictures waiting for their following I- or P-frame.
+    pending: Vec<u64>,
+    /// Reference pictures, in encode order.
+    references: Vec<u64>,
+}
+
+impl Gop {
+    pub fn new(config: &GopConfig) -> Self {
+        Self {
+            config: config.clone(),
+            next_index: 0,
+            last_idr: 0,
+            pending: Vec::new(),
+            references: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &GopConfig {
+        &self.config
+    }
+
+    /// Pushes the next picture, returning all pictures that can be encoded now.
+    pub fn push(&mut self) -> Vec<GopFrame> {
+        let index = self.next_index;
+        let idr_period = u64::from(self.config.idr_period);
+
+        self.next_index += 1;
+
+        if index == 0 || (idr_period > 0 && index - self.last_idr >= idr_period) {
+            // Pending pictures can't reference across the IDR picture.
+            let mut frames = self.flush();
+            frames.push(self.anchor(index, FrameType::Idr));
+            return frames;
+        }
+
+        if self.pending.len() < self.config.b_frames as usize {
+            self.pending.push(index);
+            return Vec::new();
+        }
+
+        self.anchor_with_pending(index)
+    }
+
+    /// Returns all pictures held back, e.g., at the end of the stream.
+    ///
+    /// The last of them becomes a P-frame, the others B-frames predicting from it.
+    pub fn flush(&mut self) -> Vec<GopFrame> {
+        match self.pending.pop() {
+            Some(index) => self.anchor_with_pending(index),
+            None => Vec::new(),
+        }
+    }
+
+    /// Encodes `index` as P-frame, followed by the pending B-frames.
+    fn anchor_with_pending(&mut self, index: u64) -> Vec<GopFrame> {
+        let previous = self.references.last().copied();
+        let mut frames = vec![self.anchor(index, FrameType::P)];
+
+        for index in self.pending.drain(..) {
+            frames.push(GopFrame {
+                index,
+                frame_type: FrameType::B,
+                references_l0: previous.into_iter().collect(),
+                references_l1: vec![frames[0].index],
+            });
+        }
+
+        frames
+    }
+
+    /// Creates a reference picture, retiring the oldest reference if too many are held.
+    fn anchor(&mut self, index: u64, frame_type: FrameType) -> GopFrame {
+        if frame_type == FrameType::Idr {
+            self.last_idr = index;
+            self.references.clear();
+        }
+
+        let references_l0 = match frame_type {
+            FrameType::P => self
+                .references
+                .iter()
+                .rev()
+                .take(self.config.max_references.max(1) as usize)
+                .copied()
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        if self.references.len() >= self.config.num_ref_frames() as usize {
+            self.references.remove(0);
+        }
+
+        self.references.push(index);
+
+        GopFrame {
+            index,
+            frame_type,
+            references_l0,
+            references_l1: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::video::{FrameType, Gop, GopConfig};
+
+    /// Frame types and indices of `n` pushed pictures in encode order, including flushed ones.
+    fn plan(config: &GopConfig, n: usize) -> Vec<(FrameType, u64)> {
+        let mut gop = Gop::new(config);
+        let mut frames = (0..n).flat_map(|_| gop.push()).collect::<Vec<_>>();
+
+        frames.extend(gop.flush());
+        frames.iter().map(|x| (x.frame_type(), x.index())).collect()
+    }
+
+    #[test]
+    fn idr_and_p_frames() {
+        let frames = plan(&GopConfig::new().idr_period(3), 7);
+
+        assert_eq!(
+            frames,
+            [
+                (FrameType::Idr, 0),
+                (FrameType::P, 1),
+                (FrameType::P, 2),
+                (FrameType::Idr, 3),
+                (FrameType::P, 4),
+                (FrameType::P, 5),
+                (FrameType::Idr, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn b_frames_follow_anchor() {
+        let frames = plan(&GopConfig::new().idr_period(0).b_frames(2), 6);
+
+        assert_eq!(
+            frames,
+            [
+                (FrameType::Idr, 0),
+                (FrameType::P, 3),
+                (FrameType::B, 1),
+                (FrameType::B, 2),
+                (FrameType::P, 5),
+                (FrameType::B, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn b_frames_before_idr_are_flushed() {
+        let frames = plan(&GopConfig::new().idr_period(4).b_frames(3), 5);
+
+        assert_eq!(
+            frames,
+            [
+                (FrameType::Idr, 0),
+                (FrameType::P, 3),
+                (FrameType::B, 1),
+                (FrameType::B, 2),
+                (FrameType::Idr, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn reference_lists() {
+        let mut gop = Gop::new(&GopConfig::new().idr_period(0).b_frames(1).max_references(2));
+        let frames = (0..5).flat_map(|_| gop.push()).collect::<Vec<_>>();
+
+        // IDR 0, P 2, B 1, P 4, B 3
+        assert_eq!(frames[1].references_l0(), [0]);
+        assert_eq!(frames[2].references_l0(), [0]);
+        assert_eq!(frames[2].references_l1(), [2]);
+        assert_eq!(frames[3].references_l0(), [2, 0]);
+        assert_eq!(frames[4].references_l0(), [2]);
+        assert_eq!(frames[4].references_l1(), [4]);
+        assert!(!frames[4].is_reference());
+    }
+}
@@ -0,0 +1,982 @@
+use crate::allocation::Allocation;
+use crate::commandbuffer::CommandBuffer;
+use crate::device::Device;
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::ops::{AddToCommandBuffer, CopyImage2Buffer, DecodeH264, DecodeInfo};
+use crate::queue::{Queue, Submission, SubmitHandle, SubmitInfo};
+use crate::resources::{plane_extent, Buffer, BufferInfo, Image, ImageInfo, ImageView, ImageViewInfo};
+use crate::semaphore::Semaphore;
+use crate::stats::DecoderStats;
+use crate::video::bitstream::strip_start_code;
+use crate::video::h264::{color_description, crop_rect, max_num_reorder_frames, H264StreamInspector, PicOrderCntState, SliceHeader};
+use crate::video::reorder::ReorderBuffer;
+use crate::video::{
+    access_units, nal_units, nal_units_indexed, ColorDescription, DpbSlot, DpbSlotManager, Frame, VideoSession, VideoSessionInfo,
+    VideoSessionParameters,
+};
+use ash::vk::{
+    Extent2D, Extent3D, Format, ImageAspectFlags, ImageLayout, ImageTiling, ImageType, ImageUsageFlags, ImageViewType, MemoryPropertyFlags,
+    Rect2D, SampleCountFlags, VideoCapabilityFlagsKHR, VideoDecodeCapabilityFlagsKHR,
+};
+use h264_reader::nal::sps::FrameMbsFlags;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Size of the bitstream buffer, i.e., the largest access unit we can decode.
+const BITSTREAM_BUFFER_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Decode ranges have to be a multiple of this.
+const BITSTREAM_SIZE_ALIGNMENT: u64 = 256;
+
+/// Number of DPB slots, must match the video session.
+const DPB_SLOTS: u32 = 17;
+
+/// NAL unit types of coded slices.
+const NAL_UNIT_TYPE_SLICE: u8 = 1;
+const NAL_UNIT_TYPE_SLICE_IDR: u8 = 5;
+
+/// Offsets of the start codes of all slices in the access unit `data`, which also holds any parameter sets, SEI, ...
+fn slice_offsets(data: &[u8]) -> Vec<u32> {
+    nal_units_indexed(data)
+        .filter(|(_, nal)| {
+            let nal_unit_type = strip_start_code(nal).first().map(|x| x & 0x1f);
+            matches!(nal_unit_type, Some(NAL_UNIT_TYPE_SLICE | NAL_UNIT_TYPE_SLICE_IDR))
+        })
+        .map(|(offset, _)| offset as u32)
+        .collect()
+}
+
+/// An access unit submitted for decoding, with everything needed once the GPU is done.
+struct PendingDecode {
+    picture: DecodedPicture,
+    header: SliceHeader,
+    max_frame_num: u32,
+    decode: DecodeH264,
+    copy_luma: CopyImage2Buffer,
+    copy_chroma: CopyImage2Buffer,
+    /// When we started decoding, for [`DecoderStats::decode_time`].
+    started: Instant,
+}
+
+/// Decoder state changed while decoding an access unit, restored if its picture never made it into the DPB.
+///
+/// Otherwise later pictures would reference a picture the GPU never decoded.
+struct DecodeSnapshot {
+    stream_inspector: H264StreamInspector,
+    dpb: DpbSlotManager,
+    pic_order_cnt: PicOrderCntState,
+    stats: DecoderStats,
+}
+
+/// A picture decoded and copied into a [`Readback`], with what's needed to display it.
+pub(crate) struct DecodedPicture {
+    slot: DpbSlot,
+    timestamp: Option<i64>,
+    color_description: ColorDescription,
+    /// Pictures decoded before are displayed first, as this is an IDR picture or has memory_management_control_operation 5 (C.4.4).
+    flush: bool,
+    /// Set for IDR pictures, from their SPS.
+    max_num_reorder_frames: Option<u32>,
+}
+
+/// Host visible buffers decoded pictures are copied into, so they can be read back.
+pub(crate) struct Readback {
+    luma: Buffer,
+    chroma: Buffer,
+    format: Format,
+    /// Area of the decoded images making up the frame, in luma samples.
+    crop: Rect2D,
+    bit_depth: u8,
+}
+
+impl Readback {
+    fn new(device: &Device, format: Format, crop: Rect2D, bit_depth: u8) -> Result<Self, Error> {
+        let shared_physical_device = device.shared().physical_device();
+        let heap_infos = shared_physical_device.heap_infos();
+        // Cached memory, as frames are read back by the host.
+        let memory_host = heap_infos.any_host_cached().ok_or_else(|| error!(Variant::HeapNotFound))?;
+        let bytes_per_sample = u64::from(bit_depth).div_ceil(8);
+
+        let luma_size = crop.extent.width as u64 * crop.extent.height as u64 * bytes_per_sample;
+        let crop_extent = Extent3D::default().width(crop.extent.width).height(crop.extent.height).depth(1);
+        let chroma_extent = plane_extent(format, ImageAspectFlags::PLANE_1, crop_extent);
+        let chroma_size = chroma_extent.width as u64 * chroma_extent.height as u64 * 2 * bytes_per_sample;
+        let allocation_luma = Allocation::new(device, luma_size, memory_host)?;
+        let allocation_chroma = Allocation::new(device, chroma_size, memory_host)?;
+
+        Ok(Self {
+            luma: Buffer::new(&allocation_luma, &BufferInfo::new().size(luma_size))?,
+            chroma: Buffer::new(&allocation_chroma, &BufferInfo::new().size(chroma_size))?,
+            format,
+            crop,
+            bit_depth,
+        })
+    }
+
+    /// Reads back `picture`, which must be the last one copied into these buffers.
+    pub fn download(&self, picture: &DecodedPicture) -> Result<Frame, Error> {
+        let luma_size = self.luma.size() as usize;
+        let mut data = vec![0; luma_size + self.chroma.size() as usize];
+
+        self.luma.download_into(&mut data[..luma_size])?;
+        self.chroma.download_into(&mut data[luma_size..])?;
+
+        let frame = Frame::new(self.format, self.crop.extent.width, self.crop.extent.height, self.bit_depth, data)
+            .with_timestamp(picture.timestamp)
+            .with_color_description(picture.color_description);
+
+        Ok(frame)
+    }
+}
+
+/// Brings decoded pictures into display order, pairing fields into frames.
+pub(crate) struct DisplayOrder {
+    /// Decoded frames waiting for their turn to be displayed.
+    reorder: ReorderBuffer<Frame>,
+    /// First field of a field pair, with its order count, until the second field arrives.
+    first_field: Option<(i32, Frame)>,
+}
+
+impl DisplayOrder {
+    pub fn new(max_num_reorder_frames: u32) -> Self {
+        Self {
+            reorder: ReorderBuffer::new(max_num_reorder_frames),
+            first_field: None,
+        }
+    }
+
+    /// Queues `frame` read back from `picture` for display, returning the frames ready now.
+    ///
+    /// Pictures must be pushed in decode order.
+    pub fn push(&mut self, picture: &DecodedPicture, frame: Frame) -> Vec<Frame> {
+        let mut frames = match picture.flush {
+            true => self.flush(),
+            false => Vec::new(),
+        };
+
+        if let Some(max_num_reorder_frames) = picture.max_num_reorder_frames {
+            self.reorder.set_max_num_reorder_frames(max_num_reorder_frames);
+        }
+
+        let slot = picture.slot;
+        let [top, bottom] = slot.pic_order_cnt();
+
+        match slot.fields() {
+            [true, false] | [false, true] => {
+                let pic_order_cnt = if slot.fields()[0] { top } else { bottom };
+
+                frames.extend(self.flush_first_field());
+                self.first_field = Some((pic_order_cnt, frame));
+            }
+            [true, true] => {
+                // The second field completes the frame of its first field, which supersedes it.
+                let timestamp = match self.first_field.take() {
+                    Some((_, first_field)) => first_field.timestamp(),
+                    None => frame.timestamp(),
+                };
+
+                frames.extend(self.reorder.push(top.min(bottom), frame.with_timestamp(timestamp)));
+            }
+            [false, false] => {
+                frames.extend(self.flush_first_field());
+                frames.extend(self.reorder.push(top.min(bottom), frame));
+            }
+        }
+
+        frames
+    }
+
+    /// Returns all frames held back, in display order.
+    pub fn flush(&mut self) -> Vec<Frame> {
+        // A first field without second field is displayed on its own.
+        let mut frames = self.flush_first_field();
+
+        frames.extend(self.reorder.flush());
+        frames
+    }
+
+    /// Queues an unpaired first field for display.
+    fn flush_first_field(&mut self) -> Vec<Frame> {
+        match self.first_field.take() {
+            Some((pic_order_cnt, frame)) => self.reorder.push(pic_order_cnt, frame),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Decodes a H.264 stream, frame by frame.
+///
+/// Owns everything needed for decoding (session, parameters, DPB images, bitstream and output buffers) so you don't
+/// have to assemble these yourself. Frames are returned in display order, which for streams with B-frames differs
+/// from the order they are decoded in.
+pub struct H264Decoder {
+    stream_inspector: H264StreamInspector,
+    /// Area of the decoded images making up the frame, in luma samples.
+    crop: Rect2D,
+    format: Format,
+    bit_depth: u8,
+    queue_decode: Queue,
+    queue_copy: Queue,
+    command_buffer_decode: CommandBuffer,
+    command_buffer_copy: CommandBuffer,
+    video_session: VideoSession,
+    video_session_parameters: VideoSessionParameters,
+    dpb: DpbSlotManager,
+    pic_order_cnt: PicOrderCntState,
+    display: DisplayOrder,
+    /// Separate output image, `None` if DPB and output coincide.
+    image_dst: Option<Image>,
+    image_view_dst: Option<ImageView>,
+    /// One image per DPB slot, or a single image with one array layer per slot.
+    images_dpb: Vec<Image>,
+    image_views_dpb: Vec<ImageView>,
+    buffer_bitstream: Buffer,
+    readback: Arc<Readback>,
+    /// Set by [`Self::recover`], as only an IDR picture can be decoded without the references lost with the device.
+    awaiting_idr: bool,
+    /// Submission of [`Self::decode_async`], left behind if its future was dropped before the GPU was done.
+    in_flight: Option<Submission>,
+    /// Decode submitted by [`Self::submit_into`], which still reads the bitstream buffer.
+    decoding: Option<SubmitHandle>,
+    stats: DecoderStats,
+}
+
+impl H264Decoder {
+    /// Creates a new decoder for 8-bit 4:2:0 frames of the given size.
+    pub fn new(device: &Device, width: u32, height: u32) -> Result<Self, Error> {
+        Self::with_stream_inspector(device, width, height, H264StreamInspector::new())
+    }
+
+    /// Creates a new decoder for the stream starting with `parameter_sets`, e.g., its first access unit.
+    ///
+    /// Size, bit depth and profile are taken from the first SPS. Frames are cropped as specified there, e.g., a
+    /// 1080p stream has 1088 coded lines but returns frames with 1080 lines.
+    pub fn new_for_stream(device: &Device, parameter_sets: &[u8]) -> Result<Self, Error> {
+        let mut stream_inspector = H264StreamInspector::new();
+
+        for nal in nal_units(parameter_sets) {
+            stream_inspector.feed_nal(nal)?;
+        }
+
+        let sps = stream_inspector
+            .first_sps()
+            .ok_or_else(|| error!(Variant::InvalidBitstream, "Stream does not start with a SPS."))?;
+
+        let width = (sps.pic_width_in_mbs_minus1 + 1) * 16;
+        let height = match sps.frame_mbs_flags {
+            FrameMbsFlags::Frames => (sps.pic_height_in_map_units_minus1 + 1) * 16,
+            FrameMbsFlags::Fields { .. } => (sps.pic_height_in_map_units_minus1 + 1) * 32,
+        };
+
+        Self::with_stream_inspector(device, width, height, stream_inspector)
+    }
+
+    fn with_stream_inspector(device: &Device, width: u32, height: u32, stream_inspector: H264StreamInspector) -> Result<Self, Error> {
+        let shared_physical_device = device.shared().physical_device();
+        let queue_family_infos = shared_physical_device.queue_family_infos();
+        let heap_infos = shared_physical_device.heap_infos();
+
+        let queue_family_decode = queue_family_infos.any_decode().ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let queue_family_copy = queue_family_infos.any_compute().ok_or_else(|| error!(Variant::QueueNotFound))?;
+        let memory_host = heap_infos.any_host_visible().ok_or_else(|| error!(Variant::HeapNotFound))?;
+
+        let video_session_info = VideoSessionInfo::new()
+            .max_coded_extent(Extent2D { width, height })
+            .max_dpb_slots(DPB_SLOTS)
+            .max_active_reference_pictures(DPB_SLOTS - 1);
+        let video_session = VideoSession::new(device, &stream_inspector, &video_session_info)?;
+        let video_session_parameters = VideoSessionParameters::new(&video_session, &stream_inspector)?;
+        let dpb_and_output_coincide = video_session
+            .shared()
+            .decode_capabilities()
+            .flags()
+            .contains(VideoDecodeCapabilityFlagsKHR::DPB_AND_OUTPUT_COINCIDE);
+        let layered_dpb = !video_session
+            .shared()
+            .capability_flags()
+            .contains(VideoCapabilityFlagsKHR::SEPARATE_REFERENCE_IMAGES);
+
+        // The session picked formats supported by the device, so these are always set.
+        let video_session_info = video_session.info();
+        let format = video_session_info.get_picture_format().unwrap_or_default();
+        let format_dpb = video_session_info.get_reference_picture_format().unwrap_or_default();
+        let bit_depth = stream_inspector.first_sps().map_or(8, |x| x.chroma_info.bit_depth_luma_minus8 + 8);
+        let crop = stream_inspector
+            .first_sps()
+            .map_or(Rect2D::default().extent(Extent2D { width, height }), crop_rect);
+        let max_num_reorder_frames = stream_inspector.first_sps().map_or(DPB_SLOTS - 1, max_num_reorder_frames);
+
+        let image_info = ImageInfo::new()
+            .samples(SampleCountFlags::TYPE_1)
+            .mip_levels(1)
+            .array_layers(1)
+            .image_type(ImageType::TYPE_2D)
+            .tiling(ImageTiling::OPTIMAL)
+            .layout(ImageLayout::UNDEFINED)
+            .extent(Extent3D::default().width(width).height(height).depth(1));
+
+        // Output images are decoded into on the decode queue and copied from on the copy queue, which without concurrent
+        // sharing would need their ownership transferred between both families every frame.
+        let image_info_output = image_info
+            .clone()
+            .format(format)
+            .concurrent_queue_families(&[queue_family_decode, queue_family_copy]);
+
+        // Without coinciding DPB and output, DPB images can't be decoded (or copied) into directly, and we need a separate output image.
+        let (image_info_dpb, image_info_dst) = match dpb_and_output_coincide {
+            true => (
+                image_info_output
+                    .usage(ImageUsageFlags::TRANSFER_SRC | ImageUsageFlags::VIDEO_DECODE_DST_KHR | ImageUsageFlags::VIDEO_DECODE_DPB_KHR),
+                None,
+            ),
+            false => (
+                image_info.clone().format(format_dpb).usage(ImageUsageFlags::VIDEO_DECODE_DPB_KHR),
+                Some(image_info_output.usage(ImageUsageFlags::TRANSFER_SRC | ImageUsageFlags::VIDEO_DECODE_DST_KHR)),
+            ),
+        };
+
+        let new_image = |image_info: &ImageInfo| -> Result<Image, Error> {
+            let image = Image::new_video_target(device, image_info, &stream_inspector)?;
+            let requirements = image.memory_requirement();
+            let allocation = Allocation::for_requirements(device, &requirements, MemoryPropertyFlags::DEVICE_LOCAL)?;
+            image.bind(&allocation)
+        };
+
+        let image_view_info = |format| {
+            ImageViewInfo::new()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .format(format)
+                .image_view_type(ImageViewType::TYPE_2D)
+                .layer_count(1)
+                .level_count(1)
+        };
+
+        let image_dst = image_info_dst.as_ref().map(new_image).transpose()?;
+        let image_view_dst = image_dst
+            .as_ref()
+            .map(|x| ImageView::new(x, &image_view_info(format)))
+            .transpose()?;
+
+        // Without separate reference images all DPB slots are array layers of a single image, with one view per slot.
+        let images_dpb = match layered_dpb {
+            true => vec![new_image(&image_info_dpb.clone().array_layers(DPB_SLOTS))?],
+            false => (0..DPB_SLOTS).map(|_| new_image(&image_info_dpb)).collect::<Result<Vec<_>, _>>()?,
+        };
+        let image_views_dpb = (0..DPB_SLOTS)
+            .map(|i| match layered_dpb {
+                true => ImageView::new(&images_dpb[0], &image_view_info(image_info_dpb.get_format()).base_array_layer(i)),
+                false => ImageView::new(&images_dpb[i as usize], &image_view_info(image_info_dpb.get_format())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // TODO: Video buffers seem to need some extra space, see `decode_h264` test.
+        let allocation_bitstream = Allocation::new(device, BITSTREAM_BUFFER_SIZE + 256, memory_host)?;
+        let buffer_info_bitstream = BufferInfo::new().size(BITSTREAM_BUFFER_SIZE);
+        let buffer_bitstream = Buffer::new_video_decode(&allocation_bitstream, &buffer_info_bitstream, &stream_inspector)?;
+        let readback = Readback::new(device, format, crop, bit_depth)?;
+
+        Ok(Self {
+            stream_inspector,
+            crop,
+            format,
+            bit_depth,
+            queue_decode: Queue::new(device, queue_family_decode, 0)?,
+            queue_copy: Queue::new(device, queue_family_copy, 0)?,
+            command_buffer_decode: CommandBuffer::new(device, queue_family_decode)?,
+            command_buffer_copy: CommandBuffer::new(device, queue_family_copy)?,
+            video_session,
+            video_session_parameters,
+            dpb: DpbSlotManager::new(DPB_SLOTS, DPB_SLOTS - 1),
+            pic_order_cnt: PicOrderCntState::default(),
+            display: DisplayOrder::new(max_num_reorder_frames),
+            image_dst,
+            image_view_dst,
+            images_dpb,
+            image_views_dpb,
+            buffer_bitstream,
+            readback: Arc::new(readback),
+            awaiting_idr: false,
+            in_flight: None,
+            decoding: None,
+            stats: DecoderStats::default(),
+        })
+    }
+
+    /// Decodes a single access unit (i.e., all NAL units making up one frame) and returns the frames now ready for
+    /// display, in display order.
+    ///
+    /// Frames are held back while pictures decoded later may still be displayed before them (at most
+    /// `max_num_reorder_frames` of the SPS), call [`Self::flush`] at the end of the stream to get the remaining ones.
+    ///
+    /// In field-coded streams every access unit holds a single field, frames are returned once both fields of a
+    /// pair have been decoded. If the access unit fails to decode, the decoder is left as if it never saw it.
+    pub fn decode(&mut self, data: &[u8]) -> Result<Vec<Frame>, Error> {
+        self.decode_access_unit(data, None)
+    }
+
+    /// Like [`Self::decode`], but the frame decoded from `data` carries `timestamp`, see [`Frame::timestamp`].
+    ///
+    /// The timestamp is opaque to the decoder, e.g., a presentation timestamp or an index into application data. As
+    /// frames are returned in display order, this is how to tell which access unit a frame came from. Frames of
+    /// field pairs carry the timestamp of their first field.
+    pub fn decode_with_timestamp(&mut self, data: &[u8], timestamp: i64) -> Result<Vec<Frame>, Error> {
+        self.decode_access_unit(data, Some(timestamp))
+    }
+
+    fn decode_access_unit(&mut self, data: &[u8], timestamp: Option<i64>) -> Result<Vec<Frame>, Error> {
+        if self.skip_until_idr(data)? {
+            return Ok(Vec::new());
+        }
+
+        let readback = self.readback.clone();
+        let picture = self.decode_into(data, timestamp, &readback)?;
+        let frame = readback.download(&picture)?;
+
+        Ok(self.display.push(&picture, frame))
+    }
+
+    /// Decodes `data` and copies the picture into `readback`, without reading it back yet.
+    pub(crate) fn decode_into(&mut self, data: &[u8], timestamp: Option<i64>, readback: &Readback) -> Result<DecodedPicture, Error> {
+        self.wait_in_flight();
+
+        let snapshot = self.snapshot();
+        let pending = self
+            .decode_and_mark(data, timestamp, readback)
+            .inspect_err(|_| self.restore(snapshot))?;
+
+        // Decode queues usually can't copy, so we have to do that on a compute queue.
+        self.queue_copy.build_and_submit(&self.command_buffer_copy, |x| {
+            x.run(&pending.copy_luma)?;
+            x.run(&pending.copy_chroma)?;
+            Ok(())
+        })?;
+
+        Ok(pending.picture)
+    }
+
+    /// Like [`Self::decode_into`], but returns once submitted, with a handle completing once the picture was copied.
+    ///
+    /// The decode waits for `semaphore` to reach `value` and signals `value + 1`, the copy waits for that and signals
+    /// `value + 2`. Calls with `value` growing by 2 thus order each decode after the previous copy (which might still
+    /// read the image decoded into) on the GPU. On the CPU we only wait for the previous decode, as it reads the
+    /// bitstream buffer we upload to.
+    pub(crate) fn submit_into(
+        &mut self,
+        data: &[u8],
+        timestamp: Option<i64>,
+        readback: &Readback,
+        semaphore: &Semaphore,
+        value: u64,
+    ) -> Result<(DecodedPicture, SubmitHandle), Error> {
+        self.wait_in_flight();
+
+        let decoded = SubmitInfo::new().wait(semaphore, value).signal(semaphore, value + 1);
+        let copied = SubmitInfo::new().wait(semaphore, value + 1).signal(semaphore, value + 2);
+
+        let snapshot = self.snapshot();
+        let pending = self
+            .submit_and_mark(data, timestamp, readback, &decoded, semaphore)
+            .inspect_err(|_| self.restore(snapshot))?;
+
+        let copy = self.queue_copy.submit_with(&self.command_buffer_copy, &copied, |x| {
+            x.run(&pending.copy_luma)?;
+            x.run(&pending.copy_chroma)?;
+            Ok(())
+        })?;
+
+        // The semaphore must outlive all submissions using it.
+        Ok((pending.picture, copy.keep_alive(semaphore.shared())))
+    }
+
+    /// Like [`Self::decode_and_mark`], but only submits the decode, which is kept in [`Self::decoding`].
+    ///
+    /// The picture is marked as decoded right away, as later submissions execute after it anyway.
+    fn submit_and_mark(
+        &mut self,
+        data: &[u8],
+        timestamp: Option<i64>,
+        readback: &Readback,
+        info: &SubmitInfo,
+        semaphore: &Semaphore,
+    ) -> Result<PendingDecode, Error> {
+        let pending = self.begin_decode(data, timestamp, readback)?;
+
+        let decode = self.queue_decode.submit_with(&self.command_buffer_decode, info, |x| {
+            x.run(&pending.decode)?;
+            Ok(())
+        })?;
+
+        self.decoding = Some(decode.keep_alive(semaphore.shared()));
+        self.finish_decode(&pending)?;
+
+        Ok(pending)
+    }
+
+    /// Waits for earlier submissions still in flight, before their bitstream buffer is overwritten.
+    fn wait_in_flight(&mut self) {
+        if let Some(in_flight) = self.in_flight.take() {
+            // Only fails if the device was lost, which the next submission reports anyway.
+            _ = in_flight.wait();
+        }
+
+        // Dropping the handle waits for its submission.
+        self.decoding = None;
+    }
+
+    /// Decodes `data` and marks its picture as decoded, the copy into `readback` is up to the caller.
+    fn decode_and_mark(&mut self, data: &[u8], timestamp: Option<i64>, readback: &Readback) -> Result<PendingDecode, Error> {
+        let pending = self.begin_decode(data, timestamp, readback)?;
+
+        self.queue_decode.build_and_submit(&self.command_buffer_decode, |x| {
+            x.run(&pending.decode)?;
+            Ok(())
+        })?;
+
+        self.finish_decode(&pending)?;
+
+        Ok(pending)
+    }
+
+    /// Like [`Self::decode`], but awaits the GPU instead of blocking the calling thread, see [`Queue::submit_async`].
+    ///
+    /// Dropping the future before it completes loses the access unit, so access units are skipped until the next IDR
+    /// picture, as with [`Self::recover`]. The GPU still finishes the submission, which the next decode waits for.
+    pub async fn decode_async(&mut self, data: &[u8]) -> Result<Vec<Frame>, Error> {
+        if let Some(in_flight) = self.in_flight.as_mut() {
+            // Only fails if the device was lost, which the next submission reports anyway.
+            _ = in_flight.await;
+            self.in_flight = None;
+        }
+
+        self.decoding = None;
+
+        if self.skip_until_idr(data)? {
+            return Ok(Vec::new());
+        }
+
+        let readback = self.readback.clone();
+        let snapshot = self.snapshot();
+
+        // Cleared once the frame was read back, in case we are dropped before.
+        self.awaiting_idr = true;
+
+        let pending = match self.decode_and_mark_async(data, &readback).await {
+            Ok(x) => x,
+            Err(e) => {
+                self.restore(snapshot);
+                self.awaiting_idr = false;
+                return Err(e);
+            }
+        };
+
+        let copy = self.queue_copy.submit_async(&self.command_buffer_copy, |x| {
+            x.run(&pending.copy_luma)?;
+            x.run(&pending.copy_chroma)?;
+            Ok(())
+        })?;
+
+        self.complete(copy).await?;
+
+        let frame = readback.download(&pending.picture)?;
+
+        self.awaiting_idr = false;
+
+        Ok(self.display.push(&pending.picture, frame))
+    }
+
+    /// Like [`Self::decode_and_mark`], but awaits the GPU instead of blocking.
+    async fn decode_and_mark_async(&mut self, data: &[u8], readback: &Readback) -> Result<PendingDecode, Error> {
+        let pending = self.begin_decode(data, None, readback)?;
+
+        let decode = self.queue_decode.submit_async(&self.command_buffer_decode, |x| {
+            x.run(&pending.decode)?;
+            Ok(())
+        })?;
+
+        self.complete(decode).await?;
+        self.finish_decode(&pending)?;
+
+        Ok(pending)
+    }
+
+    /// The state decoding the next access unit changes, see [`DecodeSnapshot`].
+    fn snapshot(&self) -> DecodeSnapshot {
+        DecodeSnapshot {
+            stream_inspector: self.stream_inspector.clone(),
+            dpb: self.dpb.clone(),
+            pic_order_cnt: self.pic_order_cnt.clone(),
+            stats: self.stats,
+        }
+    }
+
+    /// Undoes whatever an access unit that failed to decode changed.
+    fn restore(&mut self, snapshot: DecodeSnapshot) {
+        self.stream_inspector = snapshot.stream_inspector;
+        self.dpb = snapshot.dpb;
+        self.pic_order_cnt = snapshot.pic_order_cnt;
+        self.stats = snapshot.stats;
+    }
+
+    /// Awaits `submission`, keeping it in [`Self::in_flight`] meanwhile.
+    async fn complete(&mut self, submission: Submission) -> Result<(), Error> {
+        let result = self.in_flight.insert(submission).await;
+
+        self.in_flight = None;
+
+        result
+    }
+
+    /// Continues decoding on `device`, e.g., one from [`Device::recreate`] after the previous device was lost.
+    ///
+    /// All GPU resources are created anew on `device`. Parameter sets and frames held back for display are kept, but
+    /// access units are skipped until the next IDR picture, as the references of all others are gone.
+    ///
+    /// ```rust,ignore
+    /// if let Err(e) = decoder.decode(access_unit) {
+    ///     if device.is_lost() {
+    ///         device = device.recreate()?;
+    ///         decoder.recover(&device)?;
+    ///     }
+    /// }
+    /// ```
+    pub fn recover(&mut self, device: &Device) -> Result<(), Error> {
+        let extent = self.video_session.info().get_max_coded_extent();
+        let stream_inspector = self.stream_inspector.clone();
+
+        // Nothing of ours is touched until the new decoder exists, so we can try again if this fails.
+        let mut decoder = Self::with_stream_inspector(device, extent.width, extent.height, stream_inspector)?;
+        std::mem::swap(&mut decoder.display, &mut self.display);
+        decoder.stats = self.stats;
+        decoder.awaiting_idr = true;
+
+        *self = decoder;
+
+        Ok(())
+    }
+
+    /// After [`Self::recover`], if `data` has to be skipped as it holds no IDR picture. Its parameter sets are used nonetheless.
+    fn skip_until_idr(&mut self, data: &[u8]) -> Result<bool, Error> {
+        if !self.awaiting_idr {
+            return Ok(false);
+        }
+
+        let idr = nal_units(data).any(|nal| strip_start_code(nal).first().map(|x| x & 0x1f) == Some(NAL_UNIT_TYPE_SLICE_IDR));
+
+        if idr {
+            self.awaiting_idr = false;
+            return Ok(false);
+        }
+
+        for nal in nal_units(data) {
+            self.stream_inspector.feed_nal(nal)?;
+        }
+
+        self.stats.bytes_consumed += data.len() as u64;
+
+        Ok(true)
+    }
+
+    /// Parses `data`, uploads it and prepares the operations decoding it and copying the picture into `readback`.
+    fn begin_decode(&mut self, data: &[u8], timestamp: Option<i64>, readback: &Readback) -> Result<PendingDecode, Error> {
+        let started = Instant::now();
+        let size = (data.len() as u64).next_multiple_of(BITSTREAM_SIZE_ALIGNMENT);
+
+        if size > BITSTREAM_BUFFER_SIZE {
+            return Err(error!(Variant::BufferTooSmall, "Access unit does not fit into bitstream buffer."));
+        }
+
+        self.stats.bytes_consumed += data.len() as u64;
+
+        for nal in nal_units(data) {
+            self.stream_inspector.feed_nal(nal)?;
+        }
+
+        // Streams may repeat or change parameter sets at any time, the latter needs new session parameters.
+        match self.video_session_parameters.update(&self.stream_inspector) {
+            Err(e) if matches!(e.variant(), Variant::ParameterSetChanged) => {
+                self.video_session_parameters = VideoSessionParameters::new(&self.video_session, &self.stream_inspector)?;
+            }
+            x => x?,
+        }
+
+        let slices = slice_offsets(data);
+        let first_slice = slices
+            .first()
+            .and_then(|x| nal_units(&data[*x as usize..]).next())
+            .ok_or_else(|| error!(Variant::InvalidBitstream, "Access unit contains no slice."))?;
+        let header = self.stream_inspector.slice_header(first_slice)?;
+
+        let sps = self
+            .stream_inspector
+            .sps(header.seq_parameter_set_id)
+            .ok_or_else(|| error!(Variant::InvalidBitstream, "Slice refers to unknown SPS."))?;
+
+        if header.idr {
+            self.dpb = DpbSlotManager::new(DPB_SLOTS, sps.max_num_ref_frames);
+        }
+
+        let max_frame_num = 1 << sps.log2_max_frame_num();
+        let color_description = color_description(sps);
+        let max_num_reorder_frames = header.idr.then(|| max_num_reorder_frames(sps));
+        let pic_order_cnt = self.pic_order_cnt.compute(sps, &header);
+        let setup = self.dpb.next_slot_for(&header, pic_order_cnt)?;
+
+        self.buffer_bitstream.upload(data)?;
+
+        let decode_info = DecodeInfo::new(0, size);
+        // Without distinct output images the picture ends up in its DPB slot, which might be an array layer.
+        let (image_output, image_view_output, array_layer) = match (&self.image_dst, &self.image_view_dst) {
+            (Some(image), Some(view)) => (image, view, 0),
+            _ => match self.images_dpb.len() {
+                1 => (&self.images_dpb[0], &self.image_views_dpb[setup.index() as usize], setup.index()),
+                _ => (
+                    &self.images_dpb[setup.index() as usize],
+                    &self.image_views_dpb[setup.index() as usize],
+                    0,
+                ),
+            },
+        };
+
+        let decode = DecodeH264::new(
+            &self.buffer_bitstream,
+            &self.video_session_parameters,
+            image_view_output,
+            image_view_output,
+            &decode_info,
+        )
+        .slice_offsets(&slices)
+        .dpb(&self.dpb, setup, &self.image_views_dpb)
+        .slice_header(&header);
+
+        let copy_luma = CopyImage2Buffer::new(image_output, &readback.luma, ImageAspectFlags::PLANE_0)
+            .array_layer(array_layer)
+            .region(self.crop);
+        let copy_chroma = CopyImage2Buffer::new(image_output, &readback.chroma, ImageAspectFlags::PLANE_1)
+            .array_layer(array_layer)
+            .region(self.crop);
+
+        let picture = DecodedPicture {
+            slot: setup,
+            timestamp,
+            color_description,
+            flush: header.idr || header.has_mmco5(),
+            max_num_reorder_frames,
+        };
+
+        Ok(PendingDecode {
+            picture,
+            header,
+            max_frame_num,
+            decode,
+            copy_luma,
+            copy_chroma,
+            started,
+        })
+    }
+
+    /// Marks the picture of `pending` as decoded, once the GPU did so.
+    fn finish_decode(&mut self, pending: &PendingDecode) -> Result<(), Error> {
+        self.dpb
+            .mark_decoded(pending.picture.slot, &pending.header, pending.max_frame_num)?;
+
+        self.stats.frames_decoded += 1;
+        self.stats.decode_time += pending.started.elapsed();
+
+        Ok(())
+    }
+
+    /// Decodes all access units of `bitstream`, yielding frames in display order.
+    ///
+    /// Once the bitstream is exhausted the decoder is flushed, so all frames are returned. Decoding stops after the
+    /// first error.
+    ///
+    /// ```rust,ignore
+    /// for frame in decoder.frames(h264_data) {
+    ///     let frame = frame?;
+    /// }
+    /// ```
+    pub fn frames<'a>(&'a mut self, bitstream: &'a [u8]) -> impl Iterator<Item = Result<Frame, Error>> + 'a {
+        let mut access_units = access_units(bitstream);
+        let mut pending = VecDeque::new();
+        let mut done = false;
+
+        std::iter::from_fn(move || loop {
+            if let Some(frame) = pending.pop_front() {
+                return Some(Ok(frame));
+            }
+
+            if done {
+                return None;
+            }
+
+            match access_units.next() {
+                Some(access_unit) => match self.decode(access_unit) {
+                    Ok(frames) => pending.extend(frames),
+                    Err(e) => {
+                        done = true;
+                        return Some(Err(e));
+                    }
+                },
+                None => {
+                    done = true;
+                    pending.extend(self.flush());
+                }
+            }
+        })
+    }
+
+    /// The SPS frame cropping rectangle, i.e., the area of the (macroblock aligned) decoded images returned as frames.
+    pub fn crop_rect(&self) -> Rect2D {
+        self.crop
+    }
+
+    /// Work done so far, e.g., for monitoring.
+    pub fn stats(&self) -> DecoderStats {
+        DecoderStats {
+            dpb_occupancy: self.dpb.references().len() as u32,
+            dpb_slots: self.dpb.max_slots(),
+            ..self.stats
+        }
+    }
+
+    /// Returns all frames held back, in display order, e.g., at the end of the stream.
+    pub fn flush(&mut self) -> Vec<Frame> {
+        self.display.flush()
+    }
+
+    /// Creates another set of buffers pictures can be decoded into, see [`Self::decode_into`].
+    pub(crate) fn new_readback(&self, device: &Device) -> Result<Readback, Error> {
+        Readback::new(device, self.format, self.crop, self.bit_depth)
+    }
+
+    pub(crate) fn display_order(&mut self) -> &mut DisplayOrder {
+        &mut self.display
+    }
+}
+
+impl Drop for H264Decoder {
+    fn drop(&mut self) {
+        // The images and buffers of a dropped `decode_async` (or of `submit_into`) might still be in use.
+        self.wait_in_flight();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::device::Device;
+    use crate::error;
+    use crate::error::Error;
+    use crate::error::Variant;
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::video::access_units;
+    use crate::video::h264::decoder::slice_offsets;
+    use crate::video::h264::H264Decoder;
+
+    #[test]
+    fn slice_offsets_skip_parameter_sets() {
+        let access_unit = [
+            0, 0, 0, 1, 0x09, 0xf0, // AUD
+            0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1e, // SPS
+            0, 0, 0, 1, 0x68, 0xce, // PPS
+            0, 0, 1, 0x65, 0x88, 0x84, // IDR slice
+            0, 0, 1, 0x65, 0x00, 0x21, // IDR slice
+            0, 0, 1, 0x65, 0x00, 0x42, // IDR slice
+        ];
+
+        assert_eq!(slice_offsets(&access_unit), [20, 26, 32]);
+        assert!(slice_offsets(&access_unit[..20]).is_empty());
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn decode_frames() -> Result<(), Error> {
+        let h264_data = include_bytes!("../../../tests/videos/multi_512x512.h264");
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let mut decoder = H264Decoder::new(&device, 512, 512)?;
+
+        let mut frames = Vec::new();
+
+        for (i, access_unit) in access_units(h264_data).enumerate() {
+            frames.extend(decoder.decode_with_timestamp(access_unit, i as i64)?);
+        }
+
+        frames.extend(decoder.flush());
+
+        let frame = frames.first().ok_or_else(|| error!(Variant::InvalidBitstream))?;
+
+        assert_eq!(frames.len(), access_units(h264_data).count());
+        assert_eq!(frame.timestamp(), Some(0));
+        assert_eq!(frame.width(), 512);
+        assert_eq!(frame.luma().len(), 512 * 512);
+        assert_eq!(frame.chroma().len(), 512 * 256);
+        assert_eq!(frame.luma()[0], 108);
+        assert_eq!(decoder.stats().frames_decoded(), frames.len() as u64);
+        assert_eq!(
+            decoder.stats().bytes_consumed(),
+            access_units(h264_data).map(|x| x.len() as u64).sum::<u64>()
+        );
+        assert!(device.stats().allocated() > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn iterate_frames() -> Result<(), Error> {
+        let h264_data = include_bytes!("../../../tests/videos/multi_512x512.h264");
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let mut decoder = H264Decoder::new_for_stream(&device, h264_data)?;
+
+        let frames = decoder.frames(h264_data).collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(frames.len(), access_units(h264_data).count());
+        assert_eq!(frames[0].luma()[0], 108);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn recover() -> Result<(), Error> {
+        let h264_data = include_bytes!("../../../tests/videos/multi_512x512.h264");
+        let access_units = access_units(h264_data).collect::<Vec<_>>();
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let mut decoder = H264Decoder::new(&device, 512, 512)?;
+
+        decoder.decode(access_units[0])?;
+
+        // Pretend the device was lost, the stream only continues with its next IDR picture.
+        let device = device.recreate()?;
+        decoder.recover(&device)?;
+
+        assert!(decoder.decode(access_units[1])?.is_empty());
+
+        let mut frames = decoder.flush();
+
+        for access_unit in &access_units {
+            frames.extend(decoder.decode(access_unit)?);
+        }
+
+        frames.extend(decoder.flush());
+
+        assert_eq!(frames.len(), access_units.len() + 1);
+        assert!(!device.is_lost());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::video::h264::headers::{pps_nal_unit, sps_nal_unit, std_level_idc};
+use crate::video::profile::{chroma_subsampling, component_bit_depth};
+use crate::video::{StreamInspector, VideoProfileInfoBundle};
+use ash::vk::native::{
+    StdVideoH264ChromaFormatIdc_STD_VIDEO_H264_CHROMA_FORMAT_IDC_420, StdVideoH264PictureParameterSet,
+    StdVideoH264PocType_STD_VIDEO_H264_POC_TYPE_0, StdVideoH264PpsFlags, StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_BASELINE,
+    StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_HIGH, StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_MAIN,
+    StdVideoH264SequenceParameterSet, StdVideoH264SpsFlags, StdVideoH264WeightedBipredIdc_STD_VIDEO_H264_WEIGHTED_BIPRED_IDC_DEFAULT,
+};
+use ash::vk::{Extent2D, VideoCodecOperationFlagsKHR, VideoProfileListInfoKHR};
+use std::pin::Pin;
+use std::ptr::{addr_of, null};
+
+/// `log2_max_frame_num_minus4` and `log2_max_pic_order_cnt_lsb_minus4` of generated parameter sets.
+pub(crate) const LOG2_MAX_FRAME_NUM_MINUS4: u8 = 4;
+pub(crate) const LOG2_MAX_PIC_ORDER_CNT_LSB_MINUS4: u8 = 4;
+
+/// `profile_idc` of the High 10 profile, which has no `StdVideoH264ProfileIdc` constant.
+const STD_VIDEO_H264_PROFILE_IDC_HIGH_10: u32 = 110;
+
+/// H.264 profile of an encoded stream.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum H264Profile {
+    /// Constrained Baseline, no B-frames and CAVLC only.
+    Baseline,
+    #[default]
+    Main,
+    High,
+    /// High profile with up to 10 bits per sample.
+    High10,
+}
+
+impl H264Profile {
+    pub(crate) fn std_profile_idc(&self) -> u32 {
+        match self {
+            Self::Baseline => StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_BASELINE,
+            Self::Main => StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_MAIN,
+            Self::High => StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_HIGH,
+            Self::High10 => STD_VIDEO_H264_PROFILE_IDC_HIGH_10,
+        }
+    }
+}
+
+/// Describes the H.264 stream to encode, and generates the parameter sets (SPS and PPS) for it.
+///
+/// Streams are 4:2:0, Main profile, level 4.1 and 8-bit unless configured otherwise. Frame sizes need not be a
+/// multiple of the macroblock size (the SPS crops them).
+///
+/// The parameter sets must precede the first encoded picture in the stream, see [`Self::annex_b_headers`].
+#[derive(Debug, Clone)]
+pub struct H264EncodeInfo {
+    extent: Extent2D,
+    max_num_ref_frames: u32,
+    profile: H264Profile,
+    level_idc: u8,
+    bit_depth: u8,
+}
+
+impl Default for H264EncodeInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl H264EncodeInfo {
+    pub fn new() -> Self {
+        Self {
+            extent: Extent2D { width: 512, height: 512 },
+            max_num_ref_frames: 1,
+            profile: H264Profile::Main,
+            level_idc: 41,
+            bit_depth: 8,
+        }
+    }
+
+    /// Size of the frames to encode.
+    pub fn extent(mut self, extent: Extent2D) -> Self {
+        self.extent = extent;
+        self
+    }
+
+    pub fn get_extent(&self) -> Extent2D {
+        self.extent
+    }
+
+    /// Number of reference frames pictures may predict from, see [`GopConfig::num_ref_frames`](crate::video::GopConfig::num_ref_frames).
+    pub fn max_num_ref_frames(mut self, max_num_ref_frames: u32) -> Self {
+        self.max_num_ref_frames = max_num_ref_frames;
+        self
+    }
+
+    pub fn get_max_num_ref_frames(&self) -> u32 {
+        self.max_num_ref_frames
+    }
+
+    pub fn profile(mut self, profile: H264Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    pub fn get_profile(&self) -> H264Profile {
+        self.profile
+    }
+
+    /// Level as `level_idc`, i.e., ten times the level number, e.g., 51 for level 5.1.
+    pub fn level(mut self, level_idc: u8) -> Self {
+        self.level_idc = level_idc;
+        self
+    }
+
+    pub fn get_level(&self) -> u8 {
+        self.level_idc
+    }
+
+    /// Bits per luma and chroma sample, 8 or 10 (which needs [`H264Profile::High10`]).
+    pub fn bit_depth(mut self, bit_depth: u8) -> Self {
+        self.bit_depth = bit_depth;
+        self
+    }
+
+    pub fn get_bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+
+    /// Frame size rounded up to whole macroblocks, the size of the images to encode from.
+    pub fn get_coded_extent(&self) -> Extent2D {
+        Extent2D {
+            width: self.extent.width.next_multiple_of(16),
+            height: self.extent.height.next_multiple_of(16),
+        }
+    }
+
+    pub fn profiles<'f>(&self) -> Pin<Box<VideoProfileInfoBundle<'f>>> {
+        let mut inner = Box::pin(VideoProfileInfoBundle::default());
+
+        let m = unsafe { inner.as_mut().get_unchecked_mut() };
+
+        m.info_h264_encode.std_profile_idc = self.profile.std_profile_idc();
+
+        m.info.p_next = addr_of!(m.info_h264_encode).cast();
+        m.info.video_codec_operation = VideoCodecOperationFlagsKHR::ENCODE_H264;
+        m.info.chroma_subsampling = chroma_subsampling(1);
+        m.info.luma_bit_depth = component_bit_depth(self.bit_depth);
+        m.info.chroma_bit_depth = component_bit_depth(self.bit_depth);
+
+        m.list = VideoProfileListInfoKHR {
+            p_profiles: addr_of!(m.info),
+            profile_count: 1,
+            ..Default::default()
+        };
+
+        inner
+    }
+
+    /// The SPS and PPS of the stream as Annex B NAL units, to be written before the first encoded picture.
+    pub fn annex_b_headers(&self) -> Result<Vec<u8>, Error> {
+        let mut rval = sps_nal_unit(&self.std_sps()?);
+
+        rval.extend(pps_nal_unit(&self.std_pps()));
+
+        Ok(rval)
+    }
+
+    /// The SPS (with id 0) of the stream.
+    pub(crate) fn std_sps(&self) -> Result<StdVideoH264SequenceParameterSet, Error> {
+        let coded_extent = self.get_coded_extent();
+        let level_idc = std_level_idc(self.level_idc)
+            .ok_or_else(|| error!(Variant::FeatureNotSupported, "Unknown H.264 level_idc {}.", self.level_idc))?;
+
+        match (self.bit_depth, self.profile) {
+            (8, _) | (10, H264Profile::High10) => {}
+            _ => {
+                return Err(error!(
+                    Variant::UnsupportedProfile,
+                    "Bit depth {} not supported in profile {:?}.", self.bit_depth, self.profile
+                ))
+            }
+        }
+
+        if self.extent.width == 0 || self.extent.height == 0 || self.max_num_ref_frames > 16 {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "Invalid extent or number of reference frames."
+            ));
+        }
+
+        let mut flags = StdVideoH264SpsFlags {
+            _bitfield_align_1: [],
+            _bitfield_1: Default::default(),
+            __bindgen_padding_0: 0,
+        };
+
+        // Cropping is in units of 2 luma samples for (progressive) 4:2:0.
+        let crop_right = (coded_extent.width - self.extent.width) / 2;
+        let crop_bottom = (coded_extent.height - self.extent.height) / 2;
+
+        // Constrained Baseline, and streams decodable by Main profile decoders.
+        flags.set_constraint_set0_flag((self.profile == H264Profile::Baseline) as u32);
+        flags.set_constraint_set1_flag(matches!(self.profile, H264Profile::Baseline | H264Profile::Main) as u32);
+        flags.set_frame_mbs_only_flag(1);
+        flags.set_direct_8x8_inference_flag(1);
+        flags.set_frame_cropping_flag((crop_right > 0 || crop_bottom > 0) as u32);
+
+        Ok(StdVideoH264SequenceParameterSet {
+            flags,
+            profile_idc: self.profile.std_profile_idc(),
+            level_idc,
+            chroma_format_idc: StdVideoH264ChromaFormatIdc_STD_VIDEO_H264_CHROMA_FORMAT_IDC_420,
+            seq_parameter_set_id: 0,
+            bit_depth_luma_minus8: self.bit_depth - 8,
+            bit_depth_chroma_minus8: self.bit_depth - 8,
+            log2_max_frame_num_minus4: LOG2_MAX_FRAME_NUM_MINUS4,
+            pic_order_cnt_type: StdVideoH264PocType_STD_VIDEO_H264_POC_TYPE_0,
+            offset_for_non_ref_pic: 0,
+            offset_for_top_to_bottom_field: 0,
+            log2_max_pic_order_cnt_lsb_minus4: LOG2_MAX_PIC_ORDER_CNT_LSB_MINUS4,
+            num_ref_frames_in_pic_order_cnt_cycle: 0,
+            max_num_ref_frames: self.max_num_ref_frames as u8,
+            reserved1: 0,
+            pic_width_in_mbs_minus1: coded_extent.width / 16 - 1,
+            pic_height_in_map_units_minus1: coded_extent.height / 16 - 1,
+            frame_crop_left_offset: 0,
+            frame_crop_right_offset: crop_right,
+            frame_crop_top_offset: 0,
+            frame_crop_bottom_offset: crop_bottom,
+            reserved2: 0,
+            pOffsetForRefFrame: null(),
+            pScalingLists: null(),
+            pSequenceParameterSetVui: null(),
+        })
+    }
+
+    /// The PPS (with id 0, referring to SPS 0) of the stream.
+    pub(crate) fn std_pps(&self) -> StdVideoH264PictureParameterSet {
+        let flags = StdVideoH264PpsFlags {
+            _bitfield_align_1: [],
+            _bitfield_1: Default::default(),
+            __bindgen_padding_0: [0; 3],
+        };
+
+        StdVideoH264PictureParameterSet {
+            flags,
+            seq_parameter_set_id: 0,
+            pic_parameter_set_id: 0,
+            num_ref_idx_l0_default_active_minus1: 0,
+            num_ref_idx_l1_default_active_minus1: 0,
+            weighted_bipred_idc: StdVideoH264WeightedBipredIdc_STD_VIDEO_H264_WEIGHTED_BIPRED_IDC_DEFAULT,
+            pic_init_qp_minus26: 0,
+            pic_init_qs_minus26: 0,
+            chroma_qp_index_offset: 0,
+            second_chroma_qp_index_offset: 0,
+            pScalingLists: null(),
+        }
+    }
+}
+
+impl StreamInspector for H264EncodeInfo {
+    fn profiles<'f>(&self) -> Pin<Box<VideoProfileInfoBundle<'f>>> {
+        self.profiles()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::Error;
+    use crate::video::h264::{H264EncodeInfo, H264Profile, H264StreamInspector};
+    use ash::vk::{Extent2D, Format, VideoCodecOperationFlagsKHR};
+
+    #[test]
+    fn encode_profile() {
+        let info = H264EncodeInfo::new();
+        let profiles = info.profiles();
+
+        assert_eq!(profiles.info.video_codec_operation, VideoCodecOperationFlagsKHR::ENCODE_H264);
+        assert_eq!(profiles.format(), Some(Format::G8_B8R8_2PLANE_420_UNORM));
+    }
+
+    #[test]
+    fn crops_to_extent() {
+        let info = H264EncodeInfo::new().extent(Extent2D { width: 1920, height: 1080 });
+        let sps = info.std_sps().unwrap();
+
+        assert_eq!(info.get_coded_extent(), Extent2D { width: 1920, height: 1088 });
+        assert_eq!(sps.pic_width_in_mbs_minus1, 119);
+        assert_eq!(sps.pic_height_in_map_units_minus1, 67);
+        assert_eq!(sps.flags.frame_cropping_flag(), 1);
+        assert_eq!(sps.frame_crop_bottom_offset, 4);
+    }
+
+    #[test]
+    fn validates_settings() {
+        assert!(H264EncodeInfo::new().level(45).std_sps().is_err());
+        assert!(H264EncodeInfo::new().bit_depth(10).std_sps().is_err());
+        assert!(H264EncodeInfo::new().bit_depth(10).profile(H264Profile::High10).std_sps().is_ok());
+        assert_eq!(H264EncodeInfo::new().level(51).std_sps().unwrap().level_idc, 14);
+    }
+
+    #[test]
+    fn annex_b_headers_round_trip() -> Result<(), Error> {
+        let info = H264EncodeInfo::new()
+            .extent(Extent2D { width: 1280, height: 720 })
+            .profile(H264Profile::High)
+            .level(51)
+            .max_num_ref_frames(2);
+
+        let headers = info.annex_b_headers()?;
+        let pps_start = headers.windows(4).rposition(|x| x == [0, 0, 0, 1]).unwrap();
+        let mut inspector = H264StreamInspector::new();
+
+        inspector.feed_nal(&headers[..pps_start])?;
+        inspector.feed_nal(&headers[pps_start..])?;
+
+        let sps = inspector.first_sps().unwrap();
+        let std = inspector.std_parameter_sets();
+
+        assert_eq!(sps.pixel_dimensions().unwrap(), (1280, 720));
+        assert_eq!(u8::from(sps.profile_idc), 100);
+        assert_eq!(sps.level_idc, 51);
+        assert_eq!(sps.max_num_ref_frames, 2);
+        assert_eq!(std.pps.len(), 1);
+
+        Ok(())
+    }
+}
@@ -1,31 +1,50 @@
-use crate::Error;
-use ash::vk::{
-    VideoChromaSubsamplingFlagsKHR, VideoCodecOperationFlagsKHR, VideoComponentBitDepthFlagsKHR, VideoDecodeH264PictureLayoutFlagsKHR,
-    VideoDecodeH264ProfileInfoKHR, VideoProfileInfoKHR, VideoProfileListInfoKHR,
-};
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::video::bitstream::strip_start_code;
+use crate::video::h264::parameters::{chroma_format_idc, std_profile_idc, StdParameterSets};
+use crate::video::h264::SliceHeader;
+use crate::video::profile::{chroma_subsampling, component_bit_depth};
+use crate::video::{StreamInspector, VideoProfileInfoBundle};
+use ash::vk::native::StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_HIGH;
+use ash::vk::{VideoCodecOperationFlagsKHR, VideoDecodeH264PictureLayoutFlagsKHR, VideoProfileListInfoKHR};
 use h264_reader::annexb::AnnexBReader;
-use h264_reader::nal::pps::PicParameterSet;
-use h264_reader::nal::sps::SeqParameterSet;
+use h264_reader::nal::pps::{ParamSetId, PicParameterSet};
+use h264_reader::nal::sps::{FrameMbsFlags, SeqParameterSet};
 use h264_reader::nal::{Nal, NalHeader, NalHeaderError, RefNal, UnitType};
 use h264_reader::push::{NalFragmentHandler, NalInterest};
 use h264_reader::Context;
-use std::marker::PhantomPinned;
+use std::collections::BTreeMap;
 use std::pin::Pin;
 use std::ptr::addr_of;
 
-#[derive(Default)]
-pub struct VideoProfileInfoBundle<'a> {
-    pub(crate) info_h264: VideoDecodeH264ProfileInfoKHR<'a>,
-    pub(crate) info: VideoProfileInfoKHR<'a>,
-    pub(crate) list: VideoProfileListInfoKHR<'a>,
-    _pinned: PhantomPinned,
-}
+const NAL_UNIT_TYPE_SPS: u8 = 7;
+const NAL_UNIT_TYPE_PPS: u8 = 8;
 
 /// Parses H.264 NAL units and returns mata data we need to feed into Vulkan.
 #[derive(Default)]
 pub struct H264StreamInspector {
     h264_context: Context,
     h264_feeding_vec: Vec<u8>,
+    /// Raw SPS and PPS NAL units by `(nal_unit_type, id)`, to tell repeated from changed parameter sets.
+    parameter_set_nals: BTreeMap<(u8, u8), Vec<u8>>,
+}
+
+// `Context` isn't `Clone`, so we copy the parameter sets over one by one.
+impl Clone for H264StreamInspector {
+    fn clone(&self) -> Self {
+        let mut rval = Self::new();
+
+        for sps in self.h264_context.sps() {
+            rval.h264_context.put_seq_param_set(sps.clone());
+        }
+
+        for pps in self.h264_context.pps() {
+            rval.h264_context.put_pic_param_set(pps.clone());
+        }
+
+        rval.parameter_set_nals = self.parameter_set_nals.clone();
+        rval
+    }
 }
 
 pub enum XXX {
@@ -38,29 +57,46 @@ impl H264StreamInspector {
         Self {
             h264_context: Default::default(),
             h264_feeding_vec: Vec::with_capacity(32 * 1024),
+            parameter_set_nals: BTreeMap::new(),
         }
     }
 
-    pub fn feed_nal(&mut self, nal: &[u8]) -> Option<XXX> {
+    pub fn feed_nal(&mut self, nal: &[u8]) -> Result<Option<XXX>, Error> {
         let rval = None;
+        let mut parameter_set = None;
+        let mut parse_error = None;
 
-        // TODO: This is ugly as there does not seem to be a good way to signal errors within this accumulate function.
+        // Errors can't be returned from within this accumulate function, so we keep them for later.
         let mut reader = AnnexBReader::accumulate(|nal: RefNal<'_>| {
-            let nal_unit_type = nal.header().unwrap().nal_unit_type(); // TODO: Remove unwrap(), see above.
+            // Parameter sets can only be parsed once we have all of them.
+            if !nal.is_complete() {
+                return NalInterest::Buffer;
+            }
+
+            let nal_unit_type = match nal.header() {
+                Ok(x) => x.nal_unit_type(),
+                Err(e) => {
+                    parse_error = Some(error!(Variant::InvalidBitstream, "Invalid NAL unit header: {e:?}"));
+                    return NalInterest::Ignore;
+                }
+            };
             let bits = nal.rbsp_bits();
 
             match nal_unit_type {
-                UnitType::SeqParameterSet => {
-                    let sps = SeqParameterSet::from_bits(bits).unwrap(); // TODO: Remove unwrap(), see above.
-
-                    dbg!(&sps.chroma_info);
-
-                    self.h264_context.put_seq_param_set(sps);
-                }
-                UnitType::PicParameterSet => {
-                    // TODO: Remove unwrap(), see above.
-                    let _pps = PicParameterSet::from_bits(&self.h264_context, bits).unwrap();
-                }
+                UnitType::SeqParameterSet => match SeqParameterSet::from_bits(bits) {
+                    Ok(sps) => {
+                        parameter_set = Some((NAL_UNIT_TYPE_SPS, sps.seq_parameter_set_id.id()));
+                        self.h264_context.put_seq_param_set(sps);
+                    }
+                    Err(e) => parse_error = Some(error!(Variant::InvalidBitstream, "Invalid SPS: {e:?}")),
+                },
+                UnitType::PicParameterSet => match PicParameterSet::from_bits(&self.h264_context, bits) {
+                    Ok(pps) => {
+                        parameter_set = Some((NAL_UNIT_TYPE_PPS, pps.pic_parameter_set_id.id()));
+                        self.h264_context.put_pic_param_set(pps);
+                    }
+                    Err(e) => parse_error = Some(error!(Variant::InvalidBitstream, "Invalid PPS: {e:?}")),
+                },
                 _ => {} // _ => NalInterest::Ignore,
             }
 
@@ -71,8 +107,55 @@ impl H264StreamInspector {
         self.h264_feeding_vec.extend_from_slice(nal);
         self.h264_feeding_vec.extend_from_slice(&[0x00, 0x00]); // For whatever reason we need these as well
         reader.push(self.h264_feeding_vec.as_slice());
+        reader.reset(); // Ends the NAL unit, otherwise parsers reading up to its trailing bits fail.
 
-        rval
+        if let Some(e) = parse_error {
+            return Err(e);
+        }
+
+        if let Some(key) = parameter_set {
+            self.parameter_set_nals.insert(key, strip_start_code(nal).to_vec());
+        }
+
+        Ok(rval)
+    }
+
+    /// Raw SPS and PPS NAL units seen so far (latest per id), keyed by `(nal_unit_type, id)`.
+    pub(crate) fn parameter_set_nals(&self) -> &BTreeMap<(u8, u8), Vec<u8>> {
+        &self.parameter_set_nals
+    }
+
+    /// All SPS and PPS seen so far, converted for Vulkan.
+    pub(crate) fn std_parameter_sets(&self) -> StdParameterSets {
+        self.std_parameter_sets_for(|_| true)
+    }
+
+    /// SPS and PPS whose `(nal_unit_type, id)` matches `filter`, converted for Vulkan.
+    pub(crate) fn std_parameter_sets_for(&self, filter: impl Fn((u8, u8)) -> bool) -> StdParameterSets {
+        let sps = self
+            .h264_context
+            .sps()
+            .filter(|x| filter((NAL_UNIT_TYPE_SPS, x.seq_parameter_set_id.id())));
+        let pps = self
+            .h264_context
+            .pps()
+            .filter(|x| filter((NAL_UNIT_TYPE_PPS, x.pic_parameter_set_id.id())));
+
+        StdParameterSets::new(sps, pps)
+    }
+
+    /// Parses the header of a slice NAL unit, using the SPS and PPS seen so far.
+    pub(crate) fn slice_header(&self, nal: &[u8]) -> Result<SliceHeader, Error> {
+        SliceHeader::parse(nal, &self.h264_context)
+    }
+
+    /// The SPS with the lowest id seen so far, which also determines the profile.
+    pub(crate) fn first_sps(&self) -> Option<&SeqParameterSet> {
+        self.h264_context.sps().next()
+    }
+
+    pub(crate) fn sps(&self, id: u8) -> Option<&SeqParameterSet> {
+        self.h264_context.sps_by_id(ParamSetId::from_u32(id as u32).ok()?)
     }
 
     pub fn profiles<'f>(&self) -> Pin<Box<VideoProfileInfoBundle<'f>>> {
@@ -80,14 +163,20 @@ impl H264StreamInspector {
 
         let m = unsafe { inner.as_mut().get_unchecked_mut() };
 
-        m.info_h264.picture_layout = VideoDecodeH264PictureLayoutFlagsKHR::INTERLACED_INTERLEAVED_LINES;
-        m.info_h264.std_profile_idc = 100;
+        // Without any SPS seen yet we assume High, which also covers Baseline (sans FMO / ASO) and Main streams.
+        let sps = self.first_sps();
+
+        m.info_h264.std_profile_idc = sps.map_or(StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_HIGH, std_profile_idc);
+        m.info_h264.picture_layout = match sps.map(|x| &x.frame_mbs_flags) {
+            Some(FrameMbsFlags::Frames) => VideoDecodeH264PictureLayoutFlagsKHR::PROGRESSIVE,
+            _ => VideoDecodeH264PictureLayoutFlagsKHR::INTERLACED_INTERLEAVED_LINES,
+        };
 
         m.info.p_next = addr_of!(m.info_h264).cast();
         m.info.video_codec_operation = VideoCodecOperationFlagsKHR::DECODE_H264;
-        m.info.chroma_subsampling = VideoChromaSubsamplingFlagsKHR::TYPE_420;
-        m.info.luma_bit_depth = VideoComponentBitDepthFlagsKHR::TYPE_8;
-        m.info.chroma_bit_depth = VideoComponentBitDepthFlagsKHR::TYPE_8;
+        m.info.chroma_subsampling = chroma_subsampling(sps.map_or(1, |x| chroma_format_idc(x) as u8));
+        m.info.luma_bit_depth = component_bit_depth(sps.map_or(8, |x| x.chroma_info.bit_depth_luma_minus8 + 8));
+        m.info.chroma_bit_depth = component_bit_depth(sps.map_or(8, |x| x.chroma_info.bit_depth_chroma_minus8 + 8));
 
         m.list = VideoProfileListInfoKHR {
             p_profiles: addr_of!(m.info),
@@ -99,12 +188,18 @@ impl H264StreamInspector {
     }
 }
 
+impl StreamInspector for H264StreamInspector {
+    fn profiles<'f>(&self) -> Pin<Box<VideoProfileInfoBundle<'f>>> {
+        self.profiles()
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::error::Error;
+    use crate::error::{Error, Variant};
     use crate::video::h264::H264StreamInspector;
     use crate::video::nal_units;
-    use ash::vk::VideoCodecOperationFlagsKHR;
+    use ash::vk::{Format, VideoCodecOperationFlagsKHR, VideoComponentBitDepthFlagsKHR, VideoDecodeH264PictureLayoutFlagsKHR};
 
     #[test]
     fn get_profile_info_list() -> Result<(), Error> {
@@ -120,6 +215,24 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn profile_from_sps() -> Result<(), Error> {
+        // High 10 SPS for 16x16 frames.
+        let sps = [0x00, 0x00, 0x01, 0x67, 0x6e, 0x00, 0x0a, 0xa6, 0xcb, 0x4f, 0x20];
+
+        let mut inspector = H264StreamInspector::new();
+        inspector.feed_nal(&sps)?;
+
+        let profiles = inspector.profiles();
+
+        assert_eq!(profiles.info_h264.std_profile_idc, 110);
+        assert_eq!(profiles.info.luma_bit_depth, VideoComponentBitDepthFlagsKHR::TYPE_10);
+        assert_eq!(profiles.info_h264.picture_layout, VideoDecodeH264PictureLayoutFlagsKHR::PROGRESSIVE);
+        assert_eq!(profiles.format(), Some(Format::G10X6_B10X6R10X6_2PLANE_420_UNORM_3PACK16));
+
+        Ok(())
+    }
+
     #[test]
     fn inspect_h264_stream() -> Result<(), Error> {
         let h264_data = include_bytes!("../../../tests/videos/multi_512x512.h264");
@@ -128,9 +241,45 @@ mod test {
 
         // Push a couple NALs. Pushes don't have to match up to Annex B framing.
         for nal in nal_units(h264_data) {
-            inspector.feed_nal(nal);
+            inspector.feed_nal(nal)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_parameter_sets() {
+        // SPS and PPS cut off right after their first byte.
+        let sps = [0x00, 0x00, 0x01, 0x67, 0x6e];
+        let pps = [0x00, 0x00, 0x01, 0x68, 0xde];
+
+        let mut inspector = H264StreamInspector::new();
+
+        assert!(inspector
+            .feed_nal(&sps)
+            .is_err_and(|e| matches!(e.variant(), Variant::InvalidBitstream)));
+        assert!(inspector
+            .feed_nal(&pps)
+            .is_err_and(|e| matches!(e.variant(), Variant::InvalidBitstream)));
+        assert!(inspector.parameter_set_nals().is_empty());
+    }
+
+    #[test]
+    fn clone_keeps_parameter_sets() -> Result<(), Error> {
+        let h264_data = include_bytes!("../../../tests/videos/multi_512x512.h264");
+
+        let mut inspector = H264StreamInspector::new();
+
+        for nal in nal_units(h264_data) {
+            inspector.feed_nal(nal)?;
         }
 
+        let clone = inspector.clone();
+
+        assert_eq!(clone.sps(0), inspector.sps(0));
+        assert_eq!(clone.parameter_set_nals(), inspector.parameter_set_nals());
+        assert_eq!(clone.std_parameter_sets().pps.len(), inspector.std_parameter_sets().pps.len());
+
         Ok(())
     }
 }
@@ -0,0 +1,137 @@
+use crate::video::bitstream::{emulation_prevention, BitWriter};
+use ash::vk::native::{StdVideoH264PictureParameterSet, StdVideoH264PocType_STD_VIDEO_H264_POC_TYPE_0, StdVideoH264SequenceParameterSet};
+
+/// `level_idc` values, indexed by `StdVideoH264LevelIdc`.
+const LEVEL_IDC: [u8; 19] = [10, 11, 12, 13, 20, 21, 22, 30, 31, 32, 40, 41, 42, 50, 51, 52, 60, 61, 62];
+
+/// Profiles whose SPS contain chroma format and bit depth (7.3.2.1.1).
+const HIGH_PROFILE_IDC: [u32; 13] = [100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134, 135];
+
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// NAL unit headers with `nal_ref_idc` 3.
+const NAL_HEADER_SPS: u8 = 0x67;
+const NAL_HEADER_PPS: u8 = 0x68;
+
+/// The `StdVideoH264LevelIdc` of a `level_idc`, e.g., 41 for level 4.1.
+pub(crate) fn std_level_idc(level_idc: u8) -> Option<u32> {
+    LEVEL_IDC.iter().position(|x| *x == level_idc).map(|x| x as u32)
+}
+
+/// Serializes `sps` to an Annex B NAL unit, including start code.
+///
+/// Only what [`H264EncodeInfo`](crate::video::h264::H264EncodeInfo) generates is supported, i.e., no VUI, scaling
+/// lists, or picture order count type 1.
+pub(crate) fn sps_nal_unit(sps: &StdVideoH264SequenceParameterSet) -> Vec<u8> {
+    let flags = &sps.flags;
+    let mut writer = BitWriter::new();
+
+    writer.u(8, sps.profile_idc);
+    writer.flag(flags.constraint_set0_flag() != 0);
+    writer.flag(flags.constraint_set1_flag() != 0);
+    writer.flag(flags.constraint_set2_flag() != 0);
+    writer.flag(flags.constraint_set3_flag() != 0);
+    writer.flag(flags.constraint_set4_flag() != 0);
+    writer.flag(flags.constraint_set5_flag() != 0);
+    writer.u(2, 0);
+    writer.u(8, u32::from(LEVEL_IDC[sps.level_idc as usize]));
+    writer.ue(u32::from(sps.seq_parameter_set_id));
+
+    if HIGH_PROFILE_IDC.contains(&sps.profile_idc) {
+        writer.ue(sps.chroma_format_idc);
+
+        if sps.chroma_format_idc == 3 {
+            writer.flag(flags.separate_colour_plane_flag() != 0);
+        }
+
+        writer.ue(u32::from(sps.bit_depth_luma_minus8));
+        writer.ue(u32::from(sps.bit_depth_chroma_minus8));
+        writer.flag(flags.qpprime_y_zero_transform_bypass_flag() != 0);
+        writer.flag(false); // seq_scaling_matrix_present_flag
+    }
+
+    writer.ue(u32::from(sps.log2_max_frame_num_minus4));
+    writer.ue(sps.pic_order_cnt_type);
+
+    if sps.pic_order_cnt_type == StdVideoH264PocType_STD_VIDEO_H264_POC_TYPE_0 {
+        writer.ue(u32::from(sps.log2_max_pic_order_cnt_lsb_minus4));
+    }
+
+    writer.ue(u32::from(sps.max_num_ref_frames));
+    writer.flag(flags.gaps_in_frame_num_value_allowed_flag() != 0);
+    writer.ue(sps.pic_width_in_mbs_minus1);
+    writer.ue(sps.pic_height_in_map_units_minus1);
+    writer.flag(flags.frame_mbs_only_flag() != 0);
+
+    if flags.frame_mbs_only_flag() == 0 {
+        writer.flag(flags.mb_adaptive_frame_field_flag() != 0);
+    }
+
+    writer.flag(flags.direct_8x8_inference_flag() != 0);
+    writer.flag(flags.frame_cropping_flag() != 0);
+
+    if flags.frame_cropping_flag() != 0 {
+        writer.ue(sps.frame_crop_left_offset);
+        writer.ue(sps.frame_crop_right_offset);
+        writer.ue(sps.frame_crop_top_offset);
+        writer.ue(sps.frame_crop_bottom_offset);
+    }
+
+    writer.flag(false); // vui_parameters_present_flag
+
+    nal_unit(NAL_HEADER_SPS, &writer.finish())
+}
+
+/// Serializes `pps` to an Annex B NAL unit, including start code.
+///
+/// As for [`sps_nal_unit`], scaling lists are not supported.
+pub(crate) fn pps_nal_unit(pps: &StdVideoH264PictureParameterSet) -> Vec<u8> {
+    let flags = &pps.flags;
+    let mut writer = BitWriter::new();
+
+    writer.ue(u32::from(pps.pic_parameter_set_id));
+    writer.ue(u32::from(pps.seq_parameter_set_id));
+    writer.flag(flags.entropy_coding_mode_flag() != 0);
+    writer.flag(flags.bottom_field_pic_order_in_frame_present_flag() != 0);
+    writer.ue(0); // num_slice_groups_minus1
+    writer.ue(u32::from(pps.num_ref_idx_l0_default_active_minus1));
+    writer.ue(u32::from(pps.num_ref_idx_l1_default_active_minus1));
+    writer.flag(flags.weighted_pred_flag() != 0);
+    writer.u(2, pps.weighted_bipred_idc);
+    writer.se(i32::from(pps.pic_init_qp_minus26));
+    writer.se(i32::from(pps.pic_init_qs_minus26));
+    writer.se(i32::from(pps.chroma_qp_index_offset));
+    writer.flag(flags.deblocking_filter_control_present_flag() != 0);
+    writer.flag(flags.constrained_intra_pred_flag() != 0);
+    writer.flag(flags.redundant_pic_cnt_present_flag() != 0);
+
+    // The trailing fields are optional, and only allowed in High profiles.
+    if flags.transform_8x8_mode_flag() != 0 || pps.second_chroma_qp_index_offset != pps.chroma_qp_index_offset {
+        writer.flag(flags.transform_8x8_mode_flag() != 0);
+        writer.flag(false); // pic_scaling_matrix_present_flag
+        writer.se(i32::from(pps.second_chroma_qp_index_offset));
+    }
+
+    nal_unit(NAL_HEADER_PPS, &writer.finish())
+}
+
+fn nal_unit(header: u8, rbsp: &[u8]) -> Vec<u8> {
+    let mut rval = START_CODE.to_vec();
+
+    rval.push(header);
+    rval.extend(emulation_prevention(rbsp));
+    rval
+}
+
+#[cfg(test)]
+mod test {
+    use super::std_level_idc;
+    use ash::vk::native::{StdVideoH264LevelIdc_STD_VIDEO_H264_LEVEL_IDC_1_0, StdVideoH264LevelIdc_STD_VIDEO_H264_LEVEL_IDC_5_1};
+
+    #[test]
+    fn maps_level_idc() {
+        assert_eq!(std_level_idc(10), Some(StdVideoH264LevelIdc_STD_VIDEO_H264_LEVEL_IDC_1_0));
+        assert_eq!(std_level_idc(51), Some(StdVideoH264LevelIdc_STD_VIDEO_H264_LEVEL_IDC_5_1));
+        assert_eq!(std_level_idc(9), None);
+    }
+}
@@ -1,4 +1,19 @@
 //! Operations related to H.264 codecs.
+mod decoder;
+mod encodeinfo;
 mod h264inspector;
+mod headers;
+mod parameters;
+mod pipelineddecoder;
+mod poc;
+mod pushdecoder;
+mod slice;
 
+pub use decoder::H264Decoder;
+pub use encodeinfo::{H264EncodeInfo, H264Profile};
 pub use h264inspector::H264StreamInspector;
+pub(crate) use parameters::{color_description, crop_rect, max_num_reorder_frames};
+pub use pipelineddecoder::H264PipelinedDecoder;
+pub(crate) use poc::PicOrderCntState;
+pub use pushdecoder::H264PushDecoder;
+pub(crate) use slice::{DecRefPicMarking, MemoryManagementControlOperation, SliceHeader};
@@ -0,0 +1,567 @@
+use crate::video::ColorDescription;
+use ash::vk::native::{
+    StdVideoH264HrdParameters, StdVideoH264PictureParameterSet, StdVideoH264PpsFlags,
+    StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_BASELINE, StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_MAIN,
+    StdVideoH264SequenceParameterSet, StdVideoH264SequenceParameterSetVui, StdVideoH264SpsFlags, StdVideoH264SpsVuiFlags,
+};
+use ash::vk::{Extent2D, Offset2D, Rect2D};
+use h264_reader::nal::pps::PicParameterSet;
+use h264_reader::nal::sps::{
+    AspectRatioInfo, ChromaFormat, FrameMbsFlags, HrdParameters, OverscanAppropriate, PicOrderCntType, SeqParameterSet, VideoFormat,
+    VuiParameters,
+};
+use std::ptr::null;
+
+/// `profile_idc` of the Extended profile, which has no `StdVideoH264ProfileIdc` constant.
+const STD_VIDEO_H264_PROFILE_IDC_EXTENDED: u32 = 88;
+
+/// Owns `StdVideoH264*` parameter sets, and everything they point to, so they can be handed to Vulkan.
+///
+/// All pointed-to structs are boxed, so their addresses remain stable when this type moves.
+///
+/// `h264_reader` does not retain scaling matrices, so streams with custom scaling lists are decoded with flat ones.
+#[allow(clippy::vec_box)]
+#[derive(Default)]
+pub(crate) struct StdParameterSets {
+    pub sps: Vec<StdVideoH264SequenceParameterSet>,
+    pub pps: Vec<StdVideoH264PictureParameterSet>,
+    offsets_for_ref_frame: Vec<Box<[i32]>>,
+    vuis: Vec<Box<StdVideoH264SequenceParameterSetVui>>,
+    hrds: Vec<Box<StdVideoH264HrdParameters>>,
+}
+
+impl StdParameterSets {
+    pub(crate) fn new<'a>(sps: impl Iterator<Item = &'a SeqParameterSet>, pps: impl Iterator<Item = &'a PicParameterSet>) -> Self {
+        let mut rval = Self::default();
+
+        for x in sps {
+            rval.push_sps(x);
+        }
+
+        for x in pps {
+            rval.push_pps(x);
+        }
+
+        rval
+    }
+
+    fn push_sps(&mut self, sps: &SeqParameterSet) {
+        let mut flags = StdVideoH264SpsFlags {
+            _bitfield_align_1: [],
+            _bitfield_1: Default::default(),
+            __bindgen_padding_0: 0,
+        };
+
+        let constraint_flags = sps.constraint_flags;
+
+        flags.set_constraint_set0_flag(constraint_flags.flag0() as u32);
+        flags.set_constraint_set1_flag(constraint_flags.flag1() as u32);
+        flags.set_constraint_set2_flag(constraint_flags.flag2() as u32);
+        flags.set_constraint_set3_flag(constraint_flags.flag3() as u32);
+        flags.set_constraint_set4_flag(constraint_flags.flag4() as u32);
+        flags.set_constraint_set5_flag(constraint_flags.flag5() as u32);
+        flags.set_direct_8x8_inference_flag(sps.direct_8x8_inference_flag as u32);
+        flags.set_separate_colour_plane_flag(sps.chroma_info.separate_colour_plane_flag as u32);
+        flags.set_gaps_in_frame_num_value_allowed_flag(sps.gaps_in_frame_num_value_allowed_flag as u32);
+        flags.set_qpprime_y_zero_transform_bypass_flag(sps.chroma_info.qpprime_y_zero_transform_bypass_flag as u32);
+        flags.set_frame_cropping_flag(sps.frame_cropping.is_some() as u32);
+        flags.set_vui_parameters_present_flag(sps.vui_parameters.is_some() as u32);
+
+        match sps.frame_mbs_flags {
+            FrameMbsFlags::Frames => flags.set_frame_mbs_only_flag(1),
+            FrameMbsFlags::Fields {
+                mb_adaptive_frame_field_flag,
+            } => flags.set_mb_adaptive_frame_field_flag(mb_adaptive_frame_field_flag as u32),
+        }
+
+        let (pic_order_cnt_type, log2_max_pic_order_cnt_lsb_minus4, offset_for_non_ref_pic, offset_for_top_to_bottom_field, offsets) =
+            match &sps.pic_order_cnt {
+                PicOrderCntType::TypeZero {
+                    log2_max_pic_order_cnt_lsb_minus4,
+                } => (0, *log2_max_pic_order_cnt_lsb_minus4, 0, 0, Box::default()),
+                PicOrderCntType::TypeOne {
+                    delta_pic_order_always_zero_flag,
+                    offset_for_non_ref_pic,
+                    offset_for_top_to_bottom_field,
+                    offsets_for_ref_frame,
+                } => {
+                    flags.set_delta_pic_order_always_zero_flag(*delta_pic_order_always_zero_flag as u32);
+                    let offsets = offsets_for_ref_frame.clone().into_boxed_slice();
+                    (1, 0, *offset_for_non_ref_pic, *offset_for_top_to_bottom_field, offsets)
+                }
+                PicOrderCntType::TypeTwo => (2, 0, 0, 0, Box::default()),
+            };
+
+        let chroma_format_idc = chroma_format_idc(sps);
+
+        let crop = sps.frame_cropping.clone().unwrap_or_default();
+
+        let vui = sps.vui_parameters.as_ref().map(|x| {
+            let hrd = x
+                .nal_hrd_parameters
+                .as_ref()
+                .or(x.vcl_hrd_parameters.as_ref())
+                .map(hrd_to_std)
+                .map(Box::new);
+            let mut vui = Box::new(vui_to_std(x));
+
+            if let Some(hrd) = hrd {
+                vui.pHrdParameters = &*hrd;
+                self.hrds.push(hrd);
+            }
+
+            vui
+        });
+
+        self.sps.push(StdVideoH264SequenceParameterSet {
+            flags,
+            profile_idc: std_profile_idc(sps),
+            level_idc: std_level_idc(sps.level_idc, constraint_flags.flag3()),
+            chroma_format_idc,
+            seq_parameter_set_id: sps.seq_parameter_set_id.id(),
+            bit_depth_luma_minus8: sps.chroma_info.bit_depth_luma_minus8,
+            bit_depth_chroma_minus8: sps.chroma_info.bit_depth_chroma_minus8,
+            log2_max_frame_num_minus4: sps.log2_max_frame_num_minus4,
+            pic_order_cnt_type,
+            offset_for_non_ref_pic,
+            offset_for_top_to_bottom_field,
+            log2_max_pic_order_cnt_lsb_minus4,
+            num_ref_frames_in_pic_order_cnt_cycle: offsets.len() as u8,
+            max_num_ref_frames: sps.max_num_ref_frames as u8,
+            reserved1: 0,
+            pic_width_in_mbs_minus1: sps.pic_width_in_mbs_minus1,
+            pic_height_in_map_units_minus1: sps.pic_height_in_map_units_minus1,
+            frame_crop_left_offset: crop.left_offset,
+            frame_crop_right_offset: crop.right_offset,
+            frame_crop_top_offset: crop.top_offset,
+            frame_crop_bottom_offset: crop.bottom_offset,
+            reserved2: 0,
+            pOffsetForRefFrame: if offsets.is_empty() { null() } else { offsets.as_ptr() },
+            pScalingLists: null(),
+            pSequenceParameterSetVui: vui.as_deref().map_or(null(), |x| x),
+        });
+
+        self.offsets_for_ref_frame.push(offsets);
+        self.vuis.extend(vui);
+    }
+
+    fn push_pps(&mut self, pps: &PicParameterSet) {
+        let mut flags = StdVideoH264PpsFlags {
+            _bitfield_align_1: Default::default(),
+            _bitfield_1: Default::default(),
+            __bindgen_padding_0: Default::default(),
+        };
+
+        let transform_8x8_mode_flag = pps.extension.as_ref().is_some_and(|x| x.transform_8x8_mode_flag);
+        let second_chroma_qp_index_offset = pps
+            .extension
+            .as_ref()
+            .map_or(pps.chroma_qp_index_offset, |x| x.second_chroma_qp_index_offset);
+
+        flags.set_transform_8x8_mode_flag(transform_8x8_mode_flag as u32);
+        flags.set_redundant_pic_cnt_present_flag(pps.redundant_pic_cnt_present_flag as u32);
+        flags.set_constrained_intra_pred_flag(pps.constrained_intra_pred_flag as u32);
+        flags.set_deblocking_filter_control_present_flag(pps.deblocking_filter_control_present_flag as u32);
+        flags.set_weighted_pred_flag(pps.weighted_pred_flag as u32);
+        flags.set_bottom_field_pic_order_in_frame_present_flag(pps.bottom_field_pic_order_in_frame_present_flag as u32);
+        flags.set_entropy_coding_mode_flag(pps.entropy_coding_mode_flag as u32);
+
+        self.pps.push(StdVideoH264PictureParameterSet {
+            flags,
+            seq_parameter_set_id: pps.seq_parameter_set_id.id(),
+            pic_parameter_set_id: pps.pic_parameter_set_id.id(),
+            num_ref_idx_l0_default_active_minus1: pps.num_ref_idx_l0_default_active_minus1 as u8,
+            num_ref_idx_l1_default_active_minus1: pps.num_ref_idx_l1_default_active_minus1 as u8,
+            weighted_bipred_idc: pps.weighted_bipred_idc as u32,
+            pic_init_qp_minus26: pps.pic_init_qp_minus26 as i8,
+            pic_init_qs_minus26: pps.pic_init_qs_minus26 as i8,
+            chroma_qp_index_offset: pps.chroma_qp_index_offset as i8,
+            second_chroma_qp_index_offset: second_chroma_qp_index_offset as i8,
+            pScalingLists: null(),
+        });
+    }
+}
+
+/// `StdVideoH264ProfileIdc` to decode a stream with `sps` as.
+///
+/// Vulkan only knows Baseline, Main, High and High 4:4:4 Predictive, but Extended profile streams
+/// with `constraint_set0/1_flag` set conform to (and can be decoded as) Baseline or Main.
+pub(crate) fn std_profile_idc(sps: &SeqParameterSet) -> u32 {
+    let profile_idc = u8::from(sps.profile_idc) as u32;
+
+    match profile_idc {
+        STD_VIDEO_H264_PROFILE_IDC_EXTENDED if sps.constraint_flags.flag1() => StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_MAIN,
+        STD_VIDEO_H264_PROFILE_IDC_EXTENDED if sps.constraint_flags.flag0() => StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_BASELINE,
+        _ => profile_idc,
+    }
+}
+
+/// Number of pictures that may precede another in decode order but follow it in display order.
+///
+/// Without bitstream restrictions in the VUI this is the DPB size (A.3.1), which is derived from the level.
+pub(crate) fn max_num_reorder_frames(sps: &SeqParameterSet) -> u32 {
+    if let Some(restrictions) = sps.vui_parameters.as_ref().and_then(|x| x.bitstream_restrictions.as_ref()) {
+        return restrictions.max_num_reorder_frames;
+    }
+
+    // Intra-only profiles with `constraint_set3_flag` can't reorder.
+    if matches!(u8::from(sps.profile_idc), 44 | 86 | 100 | 110 | 122 | 244) && sps.constraint_flags.flag3() {
+        return 0;
+    }
+
+    let max_dpb_mbs = match sps.level_idc {
+        9 | 10 => 396,
+        11 => 900,
+        12 | 13 | 20 => 2376,
+        21 => 4752,
+        22 | 30 => 8100,
+        31 => 18000,
+        32 => 20480,
+        40 | 41 => 32768,
+        42 => 34816,
+        50 => 110400,
+        51 | 52 => 184320,
+        _ => 696320,
+    };
+
+    let frame_height_in_mbs = match sps.frame_mbs_flags {
+        FrameMbsFlags::Frames => sps.pic_height_in_map_units_minus1 + 1,
+        FrameMbsFlags::Fields { .. } => (sps.pic_height_in_map_units_minus1 + 1) * 2,
+    };
+
+    (max_dpb_mbs / ((sps.pic_width_in_mbs_minus1 + 1) * frame_height_in_mbs)).min(16)
+}
+
+/// The frame cropping rectangle of `sps` in luma samples (7.4.2.1.1), or the whole coded frame without cropping.
+pub(crate) fn crop_rect(sps: &SeqParameterSet) -> Rect2D {
+    let field_factor = match sps.frame_mbs_flags {
+        FrameMbsFlags::Frames => 1,
+        FrameMbsFlags::Fields { .. } => 2,
+    };
+
+    let width = (sps.pic_width_in_mbs_minus1 + 1) * 16;
+    let height = (sps.pic_height_in_map_units_minus1 + 1) * 16 * field_factor;
+
+    let (crop_unit_x, crop_unit_y) = match sps.chroma_info.chroma_format {
+        _ if sps.chroma_info.separate_colour_plane_flag => (1, field_factor),
+        ChromaFormat::YUV420 => (2, 2 * field_factor),
+        ChromaFormat::YUV422 => (2, field_factor),
+        _ => (1, field_factor),
+    };
+
+    let Some(cropping) = &sps.frame_cropping else {
+        return Rect2D::default().extent(Extent2D { width, height });
+    };
+
+    let left = cropping.left_offset * crop_unit_x;
+    let top = cropping.top_offset * crop_unit_y;
+    let right = cropping.right_offset * crop_unit_x;
+    let bottom = cropping.bottom_offset * crop_unit_y;
+
+    Rect2D::default()
+        .offset(Offset2D::default().x(left as i32).y(top as i32))
+        .extent(Extent2D {
+            width: width.saturating_sub(left + right),
+            height: height.saturating_sub(top + bottom),
+        })
+}
+
+/// The color description of the VUI of `sps`, if any.
+pub(crate) fn color_description(sps: &SeqParameterSet) -> ColorDescription {
+    let Some(signal) = sps.vui_parameters.as_ref().and_then(|x| x.video_signal_type.as_ref()) else {
+        return ColorDescription::default();
+    };
+
+    let (color_primaries, transfer_characteristics, matrix_coefficients) = signal.colour_description.as_ref().map_or((2, 2, 2), |x| {
+        (x.colour_primaries, x.transfer_characteristics, x.matrix_coefficients)
+    });
+
+    ColorDescription::new(
+        color_primaries,
+        transfer_characteristics,
+        matrix_coefficients,
+        signal.video_full_range_flag,
+    )
+}
+
+pub(crate) fn chroma_format_idc(sps: &SeqParameterSet) -> u32 {
+    match sps.chroma_info.chroma_format {
+        ChromaFormat::Monochrome => 0,
+        ChromaFormat::YUV420 => 1,
+        ChromaFormat::YUV422 => 2,
+        ChromaFormat::YUV444 => 3,
+        ChromaFormat::Invalid(x) => x,
+    }
+}
+
+/// Maps `level_idc` to `StdVideoH264LevelIdc`, level 1b is signalled as 11 with `constraint_set3_flag`.
+fn std_level_idc(level_idc: u8, constraint_set3_flag: bool) -> u32 {
+    match level_idc {
+        10 => 0,
+        11 if constraint_set3_flag => 1,
+        9 => 1,
+        11 => 2,
+        12 => 3,
+        13 => 4,
+        20 => 5,
+        21 => 6,
+        22 => 7,
+        30 => 8,
+        31 => 9,
+        32 => 10,
+        40 => 11,
+        41 => 12,
+        42 => 13,
+        50 => 14,
+        51 => 15,
+        52 => 16,
+        60 => 17,
+        61 => 18,
+        _ => 19,
+    }
+}
+
+fn vui_to_std(vui: &VuiParameters) -> StdVideoH264SequenceParameterSetVui {
+    let mut flags = StdVideoH264SpsVuiFlags {
+        _bitfield_align_1: [],
+        _bitfield_1: Default::default(),
+        __bindgen_padding_0: 0,
+    };
+
+    let (aspect_ratio_idc, sar_width, sar_height) = match &vui.aspect_ratio_info {
+        Some(AspectRatioInfo::Extended(w, h)) => (255, *w, *h),
+        Some(AspectRatioInfo::Reserved(x)) => (*x as u32, 0, 0),
+        Some(x) => (aspect_ratio_idc(x), 0, 0),
+        None => (0, 0, 0),
+    };
+
+    flags.set_aspect_ratio_info_present_flag(vui.aspect_ratio_info.is_some() as u32);
+    flags.set_overscan_info_present_flag((vui.overscan_appropriate != OverscanAppropriate::Unspecified) as u32);
+    flags.set_overscan_appropriate_flag((vui.overscan_appropriate == OverscanAppropriate::Appropriate) as u32);
+    flags.set_video_signal_type_present_flag(vui.video_signal_type.is_some() as u32);
+    flags.set_chroma_loc_info_present_flag(vui.chroma_loc_info.is_some() as u32);
+    flags.set_timing_info_present_flag(vui.timing_info.is_some() as u32);
+    flags.set_nal_hrd_parameters_present_flag(vui.nal_hrd_parameters.is_some() as u32);
+    flags.set_vcl_hrd_parameters_present_flag(vui.vcl_hrd_parameters.is_some() as u32);
+    flags.set_bitstream_restriction_flag(vui.bitstream_restrictions.is_some() as u32);
+
+    // Unspecified values as per E.2.1.
+    let (mut video_format, mut colour_primaries, mut transfer_characteristics, mut matrix_coefficients) = (5, 2, 2, 2);
+
+    if let Some(signal) = &vui.video_signal_type {
+        video_format = match signal.video_format {
+            VideoFormat::Component => 0,
+            VideoFormat::PAL => 1,
+            VideoFormat::NTSC => 2,
+            VideoFormat::SECAM => 3,
+            VideoFormat::MAC => 4,
+            VideoFormat::Unspecified => 5,
+            VideoFormat::Reserved(x) => x,
+        };
+
+        flags.set_video_full_range_flag(signal.video_full_range_flag as u32);
+        flags.set_color_description_present_flag(signal.colour_description.is_some() as u32);
+
+        if let Some(colour) = &signal.colour_description {
+            colour_primaries = colour.colour_primaries;
+            transfer_characteristics = colour.transfer_characteristics;
+            matrix_coefficients = colour.matrix_coefficients;
+        }
+    }
+
+    let (num_units_in_tick, time_scale) = match &vui.timing_info {
+        Some(x) => {
+            flags.set_fixed_frame_rate_flag(x.fixed_frame_rate_flag as u32);
+            (x.num_units_in_tick, x.time_scale)
+        }
+        None => (0, 0),
+    };
+
+    let (max_num_reorder_frames, max_dec_frame_buffering) = vui
+        .bitstream_restrictions
+        .as_ref()
+        .map_or((0, 0), |x| (x.max_num_reorder_frames as u8, x.max_dec_frame_buffering as u8));
+
+    let chroma_loc_info = vui.chroma_loc_info.as_ref();
+
+    StdVideoH264SequenceParameterSetVui {
+        flags,
+        aspect_ratio_idc,
+        sar_width,
+        sar_height,
+        video_format,
+        colour_primaries,
+        transfer_characteristics,
+        matrix_coefficients,
+        num_units_in_tick,
+        time_scale,
+        max_num_reorder_frames,
+        max_dec_frame_buffering,
+        chroma_sample_loc_type_top_field: chroma_loc_info.map_or(0, |x| x.chroma_sample_loc_type_top_field as u8),
+        chroma_sample_loc_type_bottom_field: chroma_loc_info.map_or(0, |x| x.chroma_sample_loc_type_bottom_field as u8),
+        reserved1: 0,
+        pHrdParameters: null(),
+    }
+}
+
+fn aspect_ratio_idc(aspect_ratio_info: &AspectRatioInfo) -> u32 {
+    match aspect_ratio_info {
+        AspectRatioInfo::Unspecified => 0,
+        AspectRatioInfo::Ratio1_1 => 1,
+        AspectRatioInfo::Ratio12_11 => 2,
+        AspectRatioInfo::Ratio10_11 => 3,
+        AspectRatioInfo::Ratio16_11 => 4,
+        AspectRatioInfo::Ratio40_33 => 5,
+        AspectRatioInfo::Ratio24_11 => 6,
+        AspectRatioInfo::Ratio20_11 => 7,
+        AspectRatioInfo::Ratio32_11 => 8,
+        AspectRatioInfo::Ratio80_33 => 9,
+        AspectRatioInfo::Ratio18_11 => 10,
+        AspectRatioInfo::Ratio15_11 => 11,
+        AspectRatioInfo::Ratio64_33 => 12,
+        AspectRatioInfo::Ratio160_99 => 13,
+        AspectRatioInfo::Ratio4_3 => 14,
+        AspectRatioInfo::Ratio3_2 => 15,
+        AspectRatioInfo::Ratio2_1 => 16,
+        AspectRatioInfo::Reserved(x) => *x as u32,
+        AspectRatioInfo::Extended(_, _) => 255,
+    }
+}
+
+fn hrd_to_std(hrd: &HrdParameters) -> StdVideoH264HrdParameters {
+    let mut rval = StdVideoH264HrdParameters {
+        cpb_cnt_minus1: hrd.cpb_specs.len().saturating_sub(1) as u8,
+        bit_rate_scale: hrd.bit_rate_scale,
+        cpb_size_scale: hrd.cpb_size_scale,
+        reserved1: 0,
+        bit_rate_value_minus1: Default::default(),
+        cpb_size_value_minus1: Default::default(),
+        cbr_flag: Default::default(),
+        initial_cpb_removal_delay_length_minus1: hrd.initial_cpb_removal_delay_length_minus1 as u32,
+        cpb_removal_delay_length_minus1: hrd.cpb_removal_delay_length_minus1 as u32,
+        dpb_output_delay_length_minus1: hrd.dpb_output_delay_length_minus1 as u32,
+        time_offset_length: hrd.time_offset_length as u32,
+    };
+
+    for (i, spec) in hrd.cpb_specs.iter().take(32).enumerate() {
+        rval.bit_rate_value_minus1[i] = spec.bit_rate_value_minus1;
+        rval.cpb_size_value_minus1[i] = spec.cpb_size_value_minus1;
+        rval.cbr_flag[i] = spec.cbr_flag as u8;
+    }
+
+    rval
+}
+
+#[cfg(test)]
+mod test {
+    use super::{color_description, crop_rect, max_num_reorder_frames, std_profile_idc, StdParameterSets};
+    use ash::vk::{Extent2D, Offset2D};
+    use h264_reader::nal::pps::PicParameterSet;
+    use h264_reader::nal::sps::{
+        ColourDescription, ConstraintFlags, FrameCropping, ProfileIdc, SeqParameterSet, VideoFormat, VideoSignalType, VuiParameters,
+    };
+    use h264_reader::rbsp::BitReader;
+    use h264_reader::Context;
+
+    #[test]
+    fn converts_parameter_sets() {
+        let sps = [0x42, 0x00, 0x0a, 0xf4, 0xf2];
+        let pps = [0xde, 0x3c, 0x80];
+
+        let mut context = Context::default();
+        context.put_seq_param_set(SeqParameterSet::from_bits(BitReader::new(&sps[..])).unwrap());
+        context.put_pic_param_set(PicParameterSet::from_bits(&context, BitReader::new(&pps[..])).unwrap());
+
+        let std = StdParameterSets::new(context.sps(), context.pps());
+
+        assert_eq!(std.sps.len(), 1);
+        assert_eq!(std.pps.len(), 1);
+        assert_eq!(std.sps[0].profile_idc, 66);
+        assert_eq!(std.sps[0].max_num_ref_frames, 1);
+        assert_eq!(std.sps[0].flags.frame_mbs_only_flag(), 1);
+        assert_eq!(std.pps[0].flags.bottom_field_pic_order_in_frame_present_flag(), 1);
+        assert_eq!(std.pps[0].flags.deblocking_filter_control_present_flag(), 1);
+    }
+
+    #[test]
+    fn maps_profile_idc() {
+        let data = [0x42, 0x00, 0x0a, 0xf4, 0xf2];
+        let mut sps = SeqParameterSet::from_bits(BitReader::new(&data[..])).unwrap();
+
+        assert_eq!(std_profile_idc(&sps), 66);
+
+        sps.profile_idc = ProfileIdc::from(88);
+        sps.constraint_flags = ConstraintFlags::from(0b1100_0000);
+        assert_eq!(std_profile_idc(&sps), 77);
+
+        sps.constraint_flags = ConstraintFlags::from(0b1000_0000);
+        assert_eq!(std_profile_idc(&sps), 66);
+
+        sps.profile_idc = ProfileIdc::from(100);
+        assert_eq!(std_profile_idc(&sps), 100);
+    }
+
+    #[test]
+    fn derives_max_num_reorder_frames() {
+        let data = [0x42, 0x00, 0x0a, 0xf4, 0xf2];
+        let mut sps = SeqParameterSet::from_bits(BitReader::new(&data[..])).unwrap();
+
+        sps.level_idc = 41;
+        sps.pic_width_in_mbs_minus1 = 119;
+        sps.pic_height_in_map_units_minus1 = 67;
+        assert_eq!(max_num_reorder_frames(&sps), 4);
+
+        sps.pic_width_in_mbs_minus1 = 0;
+        sps.pic_height_in_map_units_minus1 = 0;
+        assert_eq!(max_num_reorder_frames(&sps), 16);
+    }
+
+    #[test]
+    fn derives_crop_rect() {
+        let data = [0x42, 0x00, 0x0a, 0xf4, 0xf2];
+        let mut sps = SeqParameterSet::from_bits(BitReader::new(&data[..])).unwrap();
+
+        sps.pic_width_in_mbs_minus1 = 119;
+        sps.pic_height_in_map_units_minus1 = 67;
+        assert_eq!(crop_rect(&sps).extent, Extent2D { width: 1920, height: 1088 });
+
+        sps.frame_cropping = Some(FrameCropping {
+            left_offset: 1,
+            right_offset: 0,
+            top_offset: 0,
+            bottom_offset: 4,
+        });
+
+        let rect = crop_rect(&sps);
+
+        assert_eq!(rect.offset, Offset2D { x: 2, y: 0 });
+        assert_eq!(rect.extent, Extent2D { width: 1918, height: 1080 });
+    }
+
+    #[test]
+    fn reads_color_description() {
+        let data = [0x42, 0x00, 0x0a, 0xf4, 0xf2];
+        let mut sps = SeqParameterSet::from_bits(BitReader::new(&data[..])).unwrap();
+
+        assert_eq!(color_description(&sps).matrix_coefficients(), 2);
+
+        sps.vui_parameters = Some(VuiParameters {
+            video_signal_type: Some(VideoSignalType {
+                video_format: VideoFormat::Unspecified,
+                video_full_range_flag: true,
+                colour_description: Some(ColourDescription {
+                    colour_primaries: 1,
+                    transfer_characteristics: 1,
+                    matrix_coefficients: 1,
+                }),
+            }),
+            ..Default::default()
+        });
+
+        let color = color_description(&sps);
+
+        assert_eq!(color.color_primaries(), 1);
+        assert_eq!(color.matrix_coefficients(), 1);
+        assert!(color.full_range());
+    }
+}
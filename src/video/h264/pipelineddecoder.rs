@@ -0,0 +1,239 @@
+use crate::device::Device;
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::queue::SubmitHandle;
+use crate::semaphore::Semaphore;
+use crate::video::h264::decoder::{DecodedPicture, DisplayOrder};
+use crate::video::h264::H264Decoder;
+use crate::video::utils::offset_in;
+use crate::video::{access_units, Frame};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// Decodes a H.264 stream on background threads, overlapping CPU and GPU work.
+///
+/// Where [`H264Decoder::decode`] parses, decodes and reads back one access unit after another, this runs these
+/// stages on threads of their own, connected by bounded channels:
+///
+/// - splitting pushed chunks into access units,
+/// - submitting access units for decoding on the decode queue, and for copying their pictures into one of `depth`
+///   host visible buffers on the compute queue,
+/// - waiting for these copies, reading back the buffers, and passing frames to `on_frame` in display order.
+///
+/// Copies are chained to their decodes through a timeline semaphore on the GPU, so the decode thread never waits for
+/// them. While a frame is copied and read back (and handled by `on_frame`) the next ones are decoded already. As with
+/// [`H264PushDecoder`](crate::video::h264::H264PushDecoder), chunks need not align with NAL units or access units:
+///
+/// ```rust,ignore
+/// let (sender, receiver) = std::sync::mpsc::channel();
+/// let decoder = H264Decoder::new(&device, 512, 512)?;
+/// let mut pipeline = H264PipelinedDecoder::new(&device, decoder, 3, move |frame| _ = sender.send(frame))?;
+///
+/// while let Some(chunk) = network.receive() {
+///     pipeline.push(&chunk)?;
+/// }
+///
+/// let decoder = pipeline.finish()?;
+/// ```
+pub struct H264PipelinedDecoder {
+    chunks: SyncSender<Vec<u8>>,
+    parse: JoinHandle<()>,
+    decode: JoinHandle<Result<H264Decoder, Error>>,
+    readback: JoinHandle<Result<DisplayOrder, Error>>,
+}
+
+impl H264PipelinedDecoder {
+    /// Starts decoding with `decoder`, with up to `depth` pictures in flight between stages.
+    ///
+    /// `on_frame` is called on the readback thread. Needs [`VideoFeatures::timeline_semaphore`](crate::VideoFeatures::timeline_semaphore).
+    pub fn new(
+        device: &Device,
+        mut decoder: H264Decoder,
+        depth: usize,
+        mut on_frame: impl FnMut(Frame) + Send + 'static,
+    ) -> Result<Self, Error> {
+        let depth = depth.max(1);
+        let readbacks = (0..depth)
+            .map(|_| decoder.new_readback(device).map(Arc::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        let semaphore = Semaphore::new(device, 0)?;
+        let mut display = std::mem::replace(decoder.display_order(), DisplayOrder::new(0));
+
+        let (chunk_sender, chunk_receiver) = sync_channel::<Vec<u8>>(depth);
+        let (access_unit_sender, access_unit_receiver) = sync_channel::<Vec<u8>>(depth);
+        let (picture_sender, picture_receiver) = sync_channel::<(DecodedPicture, usize, SubmitHandle)>(depth);
+        let (free_sender, free_receiver) = sync_channel::<usize>(depth);
+
+        for index in 0..depth {
+            _ = free_sender.send(index);
+        }
+
+        // Each stage ends once its input is closed, or its output was, e.g., as a later stage failed.
+        let parse = spawn("vulkan_video parse", move || {
+            let mut buffer = Vec::new();
+
+            for chunk in chunk_receiver {
+                buffer.extend_from_slice(&chunk);
+
+                let mut access_units = access_units(&buffer).peekable();
+                let mut consumed = 0;
+
+                // The last access unit might still grow.
+                while let Some(access_unit) = access_units.next() {
+                    let Some(next) = access_units.peek() else {
+                        break;
+                    };
+
+                    if access_unit_sender.send(access_unit.to_vec()).is_err() {
+                        return;
+                    }
+
+                    consumed = offset_in(&buffer, next);
+                }
+
+                drop(access_units);
+                buffer.drain(..consumed);
+            }
+
+            for access_unit in access_units(&buffer) {
+                if access_unit_sender.send(access_unit.to_vec()).is_err() {
+                    return;
+                }
+            }
+        })?;
+
+        let decode_readbacks = readbacks.clone();
+        let decode = spawn("vulkan_video decode", move || {
+            let mut value = 0;
+
+            for access_unit in access_unit_receiver {
+                // Buffers are returned once read back, so we never overwrite a picture not read yet.
+                let Ok(index) = free_receiver.recv() else {
+                    break;
+                };
+
+                let (picture, copied) = decoder.submit_into(&access_unit, None, &decode_readbacks[index], &semaphore, value)?;
+                value += 2;
+
+                if picture_sender.send((picture, index, copied)).is_err() {
+                    break;
+                }
+            }
+
+            Ok(decoder)
+        })?;
+
+        let readback = spawn("vulkan_video readback", move || {
+            for (picture, index, copied) in picture_receiver {
+                // Can't time out without timeout.
+                copied.wait(u64::MAX)?;
+
+                let frame = readbacks[index].download(&picture)?;
+
+                // Fails only if decoding stopped already.
+                _ = free_sender.send(index);
+
+                for frame in display.push(&picture, frame) {
+                    on_frame(frame);
+                }
+            }
+
+            for frame in display.flush() {
+                on_frame(frame);
+            }
+
+            Ok(display)
+        })?;
+
+        Ok(Self {
+            chunks: chunk_sender,
+            parse,
+            decode,
+            readback,
+        })
+    }
+
+    /// Appends `chunk` to the stream, blocking while the pipeline is full.
+    ///
+    /// Fails with [`Variant::PipelineStopped`] once decoding failed, [`Self::finish`] then returns the cause.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        self.chunks
+            .send(chunk.to_vec())
+            .map_err(|_| error!(Variant::PipelineStopped, "Decoding stopped, see finish() for the cause."))
+    }
+
+    /// Decodes the rest of the stream and delivers all remaining frames, returning the decoder for reuse.
+    pub fn finish(self) -> Result<H264Decoder, Error> {
+        let Self {
+            chunks,
+            parse,
+            decode,
+            readback,
+        } = self;
+
+        drop(chunks);
+        join(parse);
+
+        let decoder = join(decode);
+        let display = join(readback);
+        let mut decoder = decoder?;
+
+        *decoder.display_order() = display?;
+
+        Ok(decoder)
+    }
+}
+
+fn spawn<T: Send + 'static>(name: &str, f: impl FnOnce() -> T + Send + 'static) -> Result<JoinHandle<T>, Error> {
+    Ok(std::thread::Builder::new().name(name.to_string()).spawn(f)?)
+}
+
+/// Joins `thread`, forwarding its panic, if any.
+fn join<T>(thread: JoinHandle<T>) -> T {
+    thread.join().unwrap_or_else(|e| std::panic::resume_unwind(e))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::device::Device;
+    use crate::error::Error;
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::video::h264::{H264Decoder, H264PipelinedDecoder};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    #[cfg(not(miri))]
+    fn decode_pipelined() -> Result<(), Error> {
+        let h264_data = include_bytes!("../../../tests/videos/multi_512x512.h264");
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let expected = H264Decoder::new(&device, 512, 512)?
+            .frames(h264_data)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (sender, receiver) = channel();
+        let decoder = H264Decoder::new(&device, 512, 512)?;
+        let mut pipeline = H264PipelinedDecoder::new(&device, decoder, 3, move |frame| _ = sender.send(frame))?;
+
+        for chunk in h264_data.chunks(1000) {
+            pipeline.push(chunk)?;
+        }
+
+        pipeline.finish()?;
+
+        let frames = receiver.try_iter().collect::<Vec<_>>();
+
+        assert_eq!(frames.len(), expected.len());
+        assert!(frames
+            .iter()
+            .zip(&expected)
+            .all(|(x, y)| x.luma() == y.luma() && x.chroma() == y.chroma()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+use crate::video::h264::slice::SliceHeader;
+use h264_reader::nal::sps::{PicOrderCntType, SeqParameterSet};
+
+/// Derives picture order counts (8.2.1) for consecutive pictures of a H.264 stream.
+///
+/// Has to see every picture in decoding order, since all three `pic_order_cnt_type` modes depend
+/// on state carried over from previous (reference) pictures.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PicOrderCntState {
+    prev_pic_order_cnt_msb: i32,
+    prev_pic_order_cnt_lsb: i32,
+    prev_frame_num_offset: i32,
+    prev_frame_num: i32,
+}
+
+impl PicOrderCntState {
+    /// Computes `[TopFieldOrderCnt, BottomFieldOrderCnt]` of the picture `header` belongs to.
+    ///
+    /// For field pictures only the entry of the decoded field is meaningful, the other one is 0.
+    pub fn compute(&mut self, sps: &SeqParameterSet, header: &SliceHeader) -> [i32; 2] {
+        match &sps.pic_order_cnt {
+            PicOrderCntType::TypeZero {
+                log2_max_pic_order_cnt_lsb_minus4,
+            } => self.compute_type_0(*log2_max_pic_order_cnt_lsb_minus4, header),
+            PicOrderCntType::TypeOne {
+                offset_for_non_ref_pic,
+                offset_for_top_to_bottom_field,
+                offsets_for_ref_frame,
+                ..
+            } => {
+                let frame_num_offset = self.frame_num_offset(sps, header);
+                let expected = expected_pic_order_cnt(frame_num_offset, header, *offset_for_non_ref_pic, offsets_for_ref_frame);
+
+                let top = expected + header.delta_pic_order_cnt[0];
+                let bottom = match header.field_pic_flag {
+                    false => top + offset_for_top_to_bottom_field + header.delta_pic_order_cnt[1],
+                    true => expected + offset_for_top_to_bottom_field + header.delta_pic_order_cnt[0],
+                };
+
+                self.update_frame_num(frame_num_offset, header);
+                field_order_cnt(header, top, bottom)
+            }
+            PicOrderCntType::TypeTwo => {
+                let frame_num_offset = self.frame_num_offset(sps, header);
+                let temp = match header.nal_ref_idc {
+                    _ if header.idr => 0,
+                    0 => 2 * (frame_num_offset + header.frame_num as i32) - 1,
+                    _ => 2 * (frame_num_offset + header.frame_num as i32),
+                };
+
+                self.update_frame_num(frame_num_offset, header);
+                field_order_cnt(header, temp, temp)
+            }
+        }
+    }
+
+    /// 8.2.1.1, `pic_order_cnt_type == 0`.
+    fn compute_type_0(&mut self, log2_max_pic_order_cnt_lsb_minus4: u8, header: &SliceHeader) -> [i32; 2] {
+        let max_pic_order_cnt_lsb = 1 << (log2_max_pic_order_cnt_lsb_minus4 as i32 + 4);
+        let lsb = header.pic_order_cnt_lsb as i32;
+
+        if header.idr {
+            self.prev_pic_order_cnt_msb = 0;
+            self.prev_pic_order_cnt_lsb = 0;
+        }
+
+        let (prev_msb, prev_lsb) = (self.prev_pic_order_cnt_msb, self.prev_pic_order_cnt_lsb);
+
+        let msb = if lsb < prev_lsb && prev_lsb - lsb >= max_pic_order_cnt_lsb / 2 {
+            prev_msb + max_pic_order_cnt_lsb
+        } else if lsb > prev_lsb && lsb - prev_lsb > max_pic_order_cnt_lsb / 2 {
+            prev_msb - max_pic_order_cnt_lsb
+        } else {
+            prev_msb
+        };
+
+        let top = msb + lsb;
+        let bottom = match header.field_pic_flag {
+            false => top + header.delta_pic_order_cnt_bottom,
+            true => msb + lsb,
+        };
+
+        // Only reference pictures serve as `prevPicOrderCnt*` for the next picture.
+        if header.nal_ref_idc != 0 {
+            if header.has_mmco5() {
+                // After a MMCO 5 the picture's POC is rebased to 0 (8.2.1, `tempPicOrderCnt`).
+                self.prev_pic_order_cnt_msb = 0;
+                self.prev_pic_order_cnt_lsb = match header.field_pic_flag {
+                    false => top - top.min(bottom),
+                    true => 0,
+                };
+            } else {
+                self.prev_pic_order_cnt_msb = msb;
+                self.prev_pic_order_cnt_lsb = lsb;
+            }
+        }
+
+        field_order_cnt(header, top, bottom)
+    }
+
+    /// `FrameNumOffset` as in 8.2.1.2 and 8.2.1.3.
+    fn frame_num_offset(&self, sps: &SeqParameterSet, header: &SliceHeader) -> i32 {
+        if header.idr {
+            0
+        } else if self.prev_frame_num > header.frame_num as i32 {
+            self.prev_frame_num_offset + (1 << sps.log2_max_frame_num())
+        } else {
+            self.prev_frame_num_offset
+        }
+    }
+
+    fn update_frame_num(&mut self, frame_num_offset: i32, header: &SliceHeader) {
+        // A MMCO 5 resets `frame_num` and `FrameNumOffset` as seen by the next picture.
+        if header.has_mmco5() {
+            self.prev_frame_num_offset = 0;
+            self.prev_frame_num = 0;
+        } else {
+            self.prev_frame_num_offset = frame_num_offset;
+            self.prev_frame_num = header.frame_num as i32;
+        }
+    }
+}
+
+/// `expectedPicOrderCnt` as in 8.2.1.2.
+fn expected_pic_order_cnt(frame_num_offset: i32, header: &SliceHeader, offset_for_non_ref_pic: i32, offsets_for_ref_frame: &[i32]) -> i32 {
+    let cycle_length = offsets_for_ref_frame.len() as i32;
+
+    let mut abs_frame_num = match cycle_length {
+        0 => 0,
+        _ => frame_num_offset + header.frame_num as i32,
+    };
+
+    if header.nal_ref_idc == 0 && abs_frame_num > 0 {
+        abs_frame_num -= 1;
+    }
+
+    let mut expected = 0;
+
+    if abs_frame_num > 0 {
+        let cycle_cnt = (abs_frame_num - 1) / cycle_length;
+        let frame_num_in_cycle = (abs_frame_num - 1) % cycle_length;
+        let expected_delta_per_cycle = offsets_for_ref_frame.iter().sum::<i32>();
+
+        expected = cycle_cnt * expected_delta_per_cycle;
+        expected += offsets_for_ref_frame[..=frame_num_in_cycle as usize].iter().sum::<i32>();
+    }
+
+    if header.nal_ref_idc == 0 {
+        expected += offset_for_non_ref_pic;
+    }
+
+    expected
+}
+
+/// Keeps only the order count(s) of the fields actually present in the picture.
+fn field_order_cnt(header: &SliceHeader, top: i32, bottom: i32) -> [i32; 2] {
+    match (header.field_pic_flag, header.bottom_field_flag) {
+        (false, _) => [top, bottom],
+        (true, false) => [top, 0],
+        (true, true) => [0, bottom],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PicOrderCntState;
+    use crate::video::h264::slice::{DecRefPicMarking, MemoryManagementControlOperation, SliceHeader};
+    use h264_reader::nal::sps::{PicOrderCntType, SeqParameterSet};
+    use h264_reader::rbsp::BitReader;
+
+    /// Baseline 16x16 SPS with `log2_max_frame_num = 4` and the given POC type.
+    fn sps(pic_order_cnt: PicOrderCntType) -> SeqParameterSet {
+        let data = [0x42, 0x00, 0x0a, 0xf4, 0xf2];
+        let mut sps = SeqParameterSet::from_bits(BitReader::new(&data[..])).unwrap();
+        sps.log2_max_frame_num_minus4 = 0;
+        sps.pic_order_cnt = pic_order_cnt;
+        sps
+    }
+
+    fn slice(frame_num: u32, nal_ref_idc: u8) -> SliceHeader {
+        SliceHeader {
+            idr: frame_num == 0 && nal_ref_idc != 0,
+            nal_ref_idc,
+            frame_num,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn poc_type_0() {
+        let sps = sps(PicOrderCntType::TypeZero {
+            log2_max_pic_order_cnt_lsb_minus4: 0,
+        });
+        let mut state = PicOrderCntState::default();
+
+        // I0 P1 B2 with lsb wrapping at 16.
+        let lsbs = [(0, 3, 0), (1, 14, 2), (2, 4, 0), (3, 10, 2)];
+        let mut pocs = Vec::new();
+
+        for (frame_num, lsb, nal_ref_idc) in lsbs {
+            let header = SliceHeader {
+                pic_order_cnt_lsb: lsb,
+                delta_pic_order_cnt_bottom: 1,
+                ..slice(frame_num, nal_ref_idc)
+            };
+
+            pocs.push(state.compute(&sps, &header));
+        }
+
+        assert_eq!(pocs, [[3, 4], [-2, -1], [4, 5], [-6, -5]]);
+    }
+
+    #[test]
+    fn poc_type_0_mmco5() {
+        let sps = sps(PicOrderCntType::TypeZero {
+            log2_max_pic_order_cnt_lsb_minus4: 0,
+        });
+        let mut state = PicOrderCntState::default();
+
+        let mmco5 = SliceHeader {
+            pic_order_cnt_lsb: 8,
+            dec_ref_pic_marking: Some(DecRefPicMarking {
+                operations: vec![MemoryManagementControlOperation::AllUnused],
+                ..Default::default()
+            }),
+            ..slice(3, 1)
+        };
+
+        state.compute(&sps, &slice(0, 1));
+        assert_eq!(state.compute(&sps, &mmco5), [8, 8]);
+
+        // Following pictures are relative to the (rebased) MMCO 5 picture with POC 0.
+        let next = SliceHeader {
+            pic_order_cnt_lsb: 14,
+            ..slice(1, 1)
+        };
+
+        assert_eq!(state.compute(&sps, &next), [-2, -2]);
+    }
+
+    #[test]
+    fn poc_type_1() {
+        let sps = sps(PicOrderCntType::TypeOne {
+            delta_pic_order_always_zero_flag: false,
+            offset_for_non_ref_pic: -2,
+            offset_for_top_to_bottom_field: 1,
+            offsets_for_ref_frame: vec![4, 2],
+        });
+        let mut state = PicOrderCntState::default();
+
+        let pocs = [(0, 1), (1, 1), (2, 0), (2, 1)]
+            .into_iter()
+            .map(|(frame_num, nal_ref_idc)| state.compute(&sps, &slice(frame_num, nal_ref_idc)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(pocs, [[0, 1], [4, 5], [2, 3], [6, 7]]);
+    }
+
+    #[test]
+    fn poc_type_2() {
+        let sps = sps(PicOrderCntType::TypeTwo);
+        let mut state = PicOrderCntState::default();
+
+        // frame_num wraps at 16, FrameNumOffset has to carry over.
+        let pocs = [(0, 1), (1, 1), (15, 1), (0, 0), (0, 1)]
+            .into_iter()
+            .map(|(frame_num, nal_ref_idc)| SliceHeader {
+                idr: false,
+                ..slice(frame_num, nal_ref_idc)
+            })
+            .map(|header| state.compute(&sps, &header)[0])
+            .collect::<Vec<_>>();
+
+        assert_eq!(pocs, [0, 2, 30, 31, 32]);
+    }
+}
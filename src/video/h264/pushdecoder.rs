@@ -0,0 +1,115 @@
+use crate::error::Error;
+use crate::video::h264::H264Decoder;
+use crate::video::utils::offset_in;
+use crate::video::{access_units, Frame};
+
+/// Decodes a H.264 stream fed in chunks of any size, e.g., as received from the network, delivering frames to a callback.
+///
+/// Chunks need not align with NAL units or access units. Access units are decoded once the next one starts, and
+/// frames are passed to the callback in display order as soon as they are ready. To hand them to another thread,
+/// use a channel:
+///
+/// ```rust,ignore
+/// let (sender, receiver) = std::sync::mpsc::channel();
+/// let mut decoder = H264PushDecoder::new(H264Decoder::new(&device, 512, 512)?, move |frame| _ = sender.send(frame));
+///
+/// while let Some(chunk) = network.receive() {
+///     decoder.push(&chunk)?;
+/// }
+///
+/// decoder.finish()?;
+/// ```
+pub struct H264PushDecoder<F: FnMut(Frame)> {
+    decoder: H264Decoder,
+    /// Bitstream received but not decoded yet, starting with the (possibly incomplete) last access unit.
+    buffer: Vec<u8>,
+    on_frame: F,
+}
+
+impl<F: FnMut(Frame)> H264PushDecoder<F> {
+    pub fn new(decoder: H264Decoder, on_frame: F) -> Self {
+        Self {
+            decoder,
+            buffer: Vec::new(),
+            on_frame,
+        }
+    }
+
+    /// Appends `chunk` to the stream, decoding all access units completed by it.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut access_units = access_units(&self.buffer).peekable();
+        let mut consumed = 0;
+
+        // The last access unit might still grow.
+        while let Some(access_unit) = access_units.next() {
+            let Some(next) = access_units.peek() else {
+                break;
+            };
+
+            for frame in self.decoder.decode(access_unit)? {
+                (self.on_frame)(frame);
+            }
+
+            consumed = offset_in(&self.buffer, next);
+        }
+
+        drop(access_units);
+        self.buffer.drain(..consumed);
+
+        Ok(())
+    }
+
+    /// Decodes the rest of the stream and delivers all remaining frames, returning the decoder for reuse.
+    pub fn finish(mut self) -> Result<H264Decoder, Error> {
+        for access_unit in access_units(&self.buffer) {
+            for frame in self.decoder.decode(access_unit)? {
+                (self.on_frame)(frame);
+            }
+        }
+
+        for frame in self.decoder.flush() {
+            (self.on_frame)(frame);
+        }
+
+        Ok(self.decoder)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::device::Device;
+    use crate::error::Error;
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::video::access_units;
+    use crate::video::h264::{H264Decoder, H264PushDecoder};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    #[cfg(not(miri))]
+    fn decode_chunks() -> Result<(), Error> {
+        let h264_data = include_bytes!("../../../tests/videos/multi_512x512.h264");
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let (sender, receiver) = channel();
+        let mut decoder = H264PushDecoder::new(H264Decoder::new(&device, 512, 512)?, move |frame| _ = sender.send(frame));
+
+        for chunk in h264_data.chunks(1000) {
+            decoder.push(chunk)?;
+        }
+
+        decoder.finish()?;
+
+        let frames = receiver.try_iter().collect::<Vec<_>>();
+
+        assert_eq!(frames.len(), access_units(h264_data).count());
+        assert_eq!(frames[0].luma()[0], 108);
+
+        Ok(())
+    }
+}
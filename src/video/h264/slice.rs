@@ -0,0 +1,434 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::video::bitstream::{strip_start_code, BitReader};
+use h264_reader::nal::pps::{ParamSetId, PicParameterSet};
+use h264_reader::nal::sps::{ChromaFormat, FrameMbsFlags, PicOrderCntType, SeqParameterSet};
+use h264_reader::Context;
+
+const NAL_UNIT_TYPE_SLICE: u8 = 1;
+const NAL_UNIT_TYPE_SLICE_IDR: u8 = 5;
+
+/// Slice types, as in Table 7-6 (modulo 5).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum SliceType {
+    P,
+    B,
+    I,
+    SP,
+    SI,
+}
+
+impl SliceType {
+    fn from_u32(slice_type: u32) -> Result<Self, Error> {
+        match slice_type % 5 {
+            0 => Ok(Self::P),
+            1 => Ok(Self::B),
+            2 => Ok(Self::I),
+            3 => Ok(Self::SP),
+            4 => Ok(Self::SI),
+            _ => Err(error!(Variant::InvalidBitstream, "Invalid slice type.")),
+        }
+    }
+
+    fn is_intra(self) -> bool {
+        matches!(self, Self::I | Self::SI)
+    }
+}
+
+/// A single memory management control operation, see 7.4.3.3.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum MemoryManagementControlOperation {
+    /// `memory_management_control_operation == 1`
+    ShortTermUnused { difference_of_pic_nums_minus1: u32 },
+    /// `memory_management_control_operation == 2`
+    LongTermUnused { long_term_pic_num: u32 },
+    /// `memory_management_control_operation == 3`
+    ShortTermToLongTerm {
+        difference_of_pic_nums_minus1: u32,
+        long_term_frame_idx: u32,
+    },
+    /// `memory_management_control_operation == 4`
+    MaxLongTermFrameIdx { max_long_term_frame_idx_plus1: u32 },
+    /// `memory_management_control_operation == 5`
+    AllUnused,
+    /// `memory_management_control_operation == 6`
+    CurrentToLongTerm { long_term_frame_idx: u32 },
+}
+
+/// The `dec_ref_pic_marking()` syntax, only present for reference pictures.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct DecRefPicMarking {
+    pub no_output_of_prior_pics_flag: bool,
+    pub long_term_reference_flag: bool,
+    /// Empty if `adaptive_ref_pic_marking_mode_flag` is not set, i.e., the sliding window is used.
+    pub operations: Vec<MemoryManagementControlOperation>,
+}
+
+impl DecRefPicMarking {
+    pub fn has_mmco5(&self) -> bool {
+        self.operations.contains(&MemoryManagementControlOperation::AllUnused)
+    }
+}
+
+/// The parts of a H.264 slice header (7.3.3) we need to drive decoding.
+///
+/// `h264_reader` keeps most of these private, so we parse them ourselves.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct SliceHeader {
+    pub nal_ref_idc: u8,
+    pub idr: bool,
+    pub first_mb_in_slice: u32,
+    pub intra: bool,
+    pub pic_parameter_set_id: u8,
+    pub seq_parameter_set_id: u8,
+    pub frame_num: u32,
+    pub field_pic_flag: bool,
+    pub bottom_field_flag: bool,
+    pub idr_pic_id: u32,
+    pub pic_order_cnt_lsb: u32,
+    pub delta_pic_order_cnt_bottom: i32,
+    pub delta_pic_order_cnt: [i32; 2],
+    /// `None` for non-reference pictures.
+    pub dec_ref_pic_marking: Option<DecRefPicMarking>,
+}
+
+impl SliceHeader {
+    /// Parses the slice header of a slice NAL unit, with or without start code.
+    ///
+    /// The SPS and PPS the slice refers to must already be in `context`.
+    pub fn parse(nal: &[u8], context: &Context) -> Result<Self, Error> {
+        let nal = strip_start_code(nal);
+        let header = *nal.first().ok_or_else(|| error!(Variant::InvalidBitstream, "Empty NAL unit."))?;
+        let nal_unit_type = header & 0x1f;
+
+        if nal_unit_type != NAL_UNIT_TYPE_SLICE && nal_unit_type != NAL_UNIT_TYPE_SLICE_IDR {
+            return Err(error!(Variant::InvalidBitstream, "NAL unit is not a slice."));
+        }
+
+        // Headers have no length limit, e.g., with long reference list modifications, but we only read (and unescape)
+        // as far as needed, instead of copying slices of possibly megabytes.
+        let mut r = BitReader::new_escaped(&nal[1..]);
+
+        let mut rval = Self {
+            nal_ref_idc: (header >> 5) & 0x3,
+            idr: nal_unit_type == NAL_UNIT_TYPE_SLICE_IDR,
+            first_mb_in_slice: r.ue()?,
+            ..Default::default()
+        };
+
+        let slice_type = SliceType::from_u32(r.ue()?)?;
+        rval.intra = slice_type.is_intra();
+
+        let pps = param_set_id(r.ue()?)
+            .and_then(|x| context.pps_by_id(x))
+            .ok_or_else(|| error!(Variant::InvalidBitstream, "Slice refers to unknown PPS."))?;
+        let sps = context
+            .sps_by_id(pps.seq_parameter_set_id)
+            .ok_or_else(|| error!(Variant::InvalidBitstream, "Slice refers to unknown SPS."))?;
+
+        rval.pic_parameter_set_id = pps.pic_parameter_set_id.id();
+        rval.seq_parameter_set_id = sps.seq_parameter_set_id.id();
+
+        if sps.chroma_info.separate_colour_plane_flag {
+            r.skip(2)?; // colour_plane_id
+        }
+
+        rval.frame_num = r.u(sps.log2_max_frame_num() as u32)?;
+
+        if let FrameMbsFlags::Fields { .. } = sps.frame_mbs_flags {
+            rval.field_pic_flag = r.flag()?;
+
+            if rval.field_pic_flag {
+                rval.bottom_field_flag = r.flag()?;
+            }
+        }
+
+        if rval.idr {
+            rval.idr_pic_id = r.ue()?;
+        }
+
+        let bottom_field_pic_order = pps.bottom_field_pic_order_in_frame_present_flag && !rval.field_pic_flag;
+
+        match &sps.pic_order_cnt {
+            PicOrderCntType::TypeZero {
+                log2_max_pic_order_cnt_lsb_minus4,
+            } => {
+                rval.pic_order_cnt_lsb = r.u(*log2_max_pic_order_cnt_lsb_minus4 as u32 + 4)?;
+
+                if bottom_field_pic_order {
+                    rval.delta_pic_order_cnt_bottom = r.se()?;
+                }
+            }
+            PicOrderCntType::TypeOne {
+                delta_pic_order_always_zero_flag: false,
+                ..
+            } => {
+                rval.delta_pic_order_cnt[0] = r.se()?;
+
+                if bottom_field_pic_order {
+                    rval.delta_pic_order_cnt[1] = r.se()?;
+                }
+            }
+            _ => {}
+        }
+
+        if pps.redundant_pic_cnt_present_flag {
+            r.ue()?; // redundant_pic_cnt
+        }
+
+        if rval.nal_ref_idc != 0 {
+            skip_to_dec_ref_pic_marking(&mut r, sps, pps, slice_type)?;
+            rval.dec_ref_pic_marking = Some(parse_dec_ref_pic_marking(&mut r, rval.idr)?);
+        }
+
+        Ok(rval)
+    }
+
+    /// If this slice has a `memory_management_control_operation` equal to 5.
+    pub fn has_mmco5(&self) -> bool {
+        self.dec_ref_pic_marking.as_ref().is_some_and(|x| x.has_mmco5())
+    }
+}
+
+fn param_set_id(id: u32) -> Option<ParamSetId> {
+    ParamSetId::from_u32(id).ok()
+}
+
+/// Skips reference list and prediction weight syntax, which Vulkan parses itself.
+fn skip_to_dec_ref_pic_marking(
+    r: &mut BitReader,
+    sps: &SeqParameterSet,
+    pps: &PicParameterSet,
+    slice_type: SliceType,
+) -> Result<(), Error> {
+    let mut num_ref_idx_active_minus1 = [pps.num_ref_idx_l0_default_active_minus1, pps.num_ref_idx_l1_default_active_minus1];
+
+    if slice_type == SliceType::B {
+        r.flag()?; // direct_spatial_mv_pred_flag
+    }
+
+    if matches!(slice_type, SliceType::P | SliceType::SP | SliceType::B) && r.flag()? {
+        num_ref_idx_active_minus1[0] = r.ue()?;
+
+        if slice_type == SliceType::B {
+            num_ref_idx_active_minus1[1] = r.ue()?;
+        }
+    }
+
+    let lists = match slice_type {
+        SliceType::B => 2,
+        SliceType::P | SliceType::SP => 1,
+        SliceType::I | SliceType::SI => 0,
+    };
+
+    // ref_pic_list_modification()
+    for _ in 0..lists {
+        if r.flag()? {
+            loop {
+                match r.ue()? {
+                    0..=2 => _ = r.ue()?,
+                    3 => break,
+                    _ => return Err(error!(Variant::InvalidBitstream, "Invalid modification_of_pic_nums_idc.")),
+                }
+            }
+        }
+    }
+
+    let weighted = match slice_type {
+        SliceType::P | SliceType::SP => pps.weighted_pred_flag,
+        SliceType::B => pps.weighted_bipred_idc == 1,
+        _ => false,
+    };
+
+    // pred_weight_table()
+    if weighted {
+        // ChromaArrayType != 0
+        let has_chroma = !sps.chroma_info.separate_colour_plane_flag && sps.chroma_info.chroma_format != ChromaFormat::Monochrome;
+
+        r.ue()?; // luma_log2_weight_denom
+
+        if has_chroma {
+            r.ue()?; // chroma_log2_weight_denom
+        }
+
+        for num_ref_idx in num_ref_idx_active_minus1.iter().take(lists) {
+            for _ in 0..=*num_ref_idx {
+                if r.flag()? {
+                    r.se()?;
+                    r.se()?;
+                }
+
+                if has_chroma && r.flag()? {
+                    for _ in 0..4 {
+                        r.se()?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_dec_ref_pic_marking(r: &mut BitReader, idr: bool) -> Result<DecRefPicMarking, Error> {
+    let mut rval = DecRefPicMarking::default();
+
+    if idr {
+        rval.no_output_of_prior_pics_flag = r.flag()?;
+        rval.long_term_reference_flag = r.flag()?;
+        return Ok(rval);
+    }
+
+    // adaptive_ref_pic_marking_mode_flag
+    if !r.flag()? {
+        return Ok(rval);
+    }
+
+    loop {
+        let operation = match r.ue()? {
+            0 => break,
+            1 => MemoryManagementControlOperation::ShortTermUnused {
+                difference_of_pic_nums_minus1: r.ue()?,
+            },
+            2 => MemoryManagementControlOperation::LongTermUnused {
+                long_term_pic_num: r.ue()?,
+            },
+            3 => MemoryManagementControlOperation::ShortTermToLongTerm {
+                difference_of_pic_nums_minus1: r.ue()?,
+                long_term_frame_idx: r.ue()?,
+            },
+            4 => MemoryManagementControlOperation::MaxLongTermFrameIdx {
+                max_long_term_frame_idx_plus1: r.ue()?,
+            },
+            5 => MemoryManagementControlOperation::AllUnused,
+            6 => MemoryManagementControlOperation::CurrentToLongTerm {
+                long_term_frame_idx: r.ue()?,
+            },
+            _ => return Err(error!(Variant::InvalidBitstream, "Invalid memory_management_control_operation.")),
+        };
+
+        rval.operations.push(operation);
+    }
+
+    Ok(rval)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MemoryManagementControlOperation, SliceHeader};
+    use crate::error::Error;
+    use crate::video::bitstream::{emulation_prevention, BitWriter};
+    use h264_reader::nal::pps::ParamSetId;
+    use h264_reader::nal::pps::PicParameterSet;
+    use h264_reader::nal::sps::SeqParameterSet;
+    use h264_reader::nal::sps::{FrameMbsFlags, PicOrderCntType};
+    use h264_reader::rbsp::BitReader;
+    use h264_reader::Context;
+
+    /// Baseline 16x16 stream, `pic_order_cnt_type == 0` with bottom field POC deltas.
+    fn context() -> Context {
+        let sps = [0x42, 0x00, 0x0a, 0xf4, 0xf2];
+        let pps = [0xde, 0x3c, 0x80];
+
+        let mut context = Context::default();
+        context.put_seq_param_set(SeqParameterSet::from_bits(BitReader::new(&sps[..])).unwrap());
+        context.put_pic_param_set(PicParameterSet::from_bits(&context, BitReader::new(&pps[..])).unwrap());
+        context
+    }
+
+    #[test]
+    fn parse_idr_slice() -> Result<(), Error> {
+        let header = SliceHeader::parse(&[0x00, 0x00, 0x01, 0x65, 0x88, 0x81, 0x02, 0xe0], &context())?;
+
+        assert!(header.idr);
+        assert!(header.intra);
+        assert_eq!(header.nal_ref_idc, 3);
+        assert_eq!(header.idr_pic_id, 3);
+        assert!(!header.has_mmco5());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_p_slice_with_mmco() -> Result<(), Error> {
+        let header = SliceHeader::parse(&[0x41, 0x9a, 0x70, 0xca, 0x9b, 0x80], &context())?;
+        let operations = &header.dec_ref_pic_marking.as_ref().unwrap().operations;
+
+        assert!(!header.idr);
+        assert!(!header.intra);
+        assert_eq!(header.frame_num, 3);
+        assert_eq!(header.pic_order_cnt_lsb, 8);
+        assert_eq!(header.delta_pic_order_cnt_bottom, -1);
+        assert_eq!(
+            operations,
+            &[
+                MemoryManagementControlOperation::ShortTermUnused {
+                    difference_of_pic_nums_minus1: 0
+                },
+                MemoryManagementControlOperation::AllUnused
+            ]
+        );
+        assert!(header.has_mmco5());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_long_slice_header() -> Result<(), Error> {
+        let context = context();
+        let pps = context.pps_by_id(ParamSetId::from_u32(0).unwrap()).unwrap();
+        let sps = context.sps_by_id(pps.seq_parameter_set_id).unwrap();
+        let mut writer = BitWriter::new();
+
+        writer.ue(0); // first_mb_in_slice
+        writer.ue(5); // slice_type P
+        writer.ue(0); // pic_parameter_set_id
+        writer.u(sps.log2_max_frame_num() as u32, 1);
+
+        if let FrameMbsFlags::Fields { .. } = sps.frame_mbs_flags {
+            writer.flag(false); // field_pic_flag
+        }
+
+        if let PicOrderCntType::TypeZero {
+            log2_max_pic_order_cnt_lsb_minus4,
+        } = sps.pic_order_cnt
+        {
+            writer.u(log2_max_pic_order_cnt_lsb_minus4 as u32 + 4, 2);
+
+            if pps.bottom_field_pic_order_in_frame_present_flag {
+                writer.se(0);
+            }
+        }
+
+        writer.flag(false); // num_ref_idx_active_override_flag
+        writer.flag(true); // ref_pic_list_modification_flag_l0
+
+        // Way more than 256 bytes of reference list modifications, which are valid nonetheless.
+        for _ in 0..200 {
+            writer.ue(0);
+            writer.ue(1000);
+        }
+
+        writer.ue(3);
+        writer.flag(true); // adaptive_ref_pic_marking_mode_flag
+        writer.ue(1);
+        writer.ue(0);
+        writer.ue(0);
+
+        let mut nal = vec![0x41];
+        nal.extend(emulation_prevention(&writer.finish()));
+
+        let header = SliceHeader::parse(&nal, &context)?;
+        let operations = &header.dec_ref_pic_marking.as_ref().unwrap().operations;
+
+        assert!(nal.len() > 256);
+        assert_eq!(
+            operations,
+            &[MemoryManagementControlOperation::ShortTermUnused {
+                difference_of_pic_nums_minus1: 0
+            }]
+        );
+
+        Ok(())
+    }
+}
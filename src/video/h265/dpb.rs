@@ -0,0 +1,471 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::video::h265::SliceSegmentHeader;
+use ash::vk::native::{StdVideoDecodeH265ReferenceInfo, StdVideoDecodeH265ReferenceInfoFlags};
+use ash::vk::{VideoDecodeH265DpbSlotInfoKHR, VideoPictureResourceInfoKHR, VideoReferenceSlotInfoKHR};
+
+/// Entries of each of the `RefPicSet*` lists Vulkan takes, `STD_VIDEO_DECODE_H265_REF_PIC_SET_LIST_SIZE`.
+const REF_PIC_SET_LIST_SIZE: usize = 8;
+
+/// Marks unused entries of the `RefPicSet*` lists.
+const NO_REFERENCE_PICTURE: u8 = 0xff;
+
+/// A H.265 picture living in one of the DPB slots of a video session.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct H265DpbSlot {
+    index: u32,
+    pic_order_cnt: i32,
+    long_term: bool,
+}
+
+impl H265DpbSlot {
+    /// Index of the DPB slot this picture is stored in.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// `PicOrderCntVal` of the picture.
+    pub fn pic_order_cnt(&self) -> i32 {
+        self.pic_order_cnt
+    }
+
+    pub fn long_term(&self) -> bool {
+        self.long_term
+    }
+
+    pub(crate) fn std_reference_info(&self) -> StdVideoDecodeH265ReferenceInfo {
+        let mut flags = StdVideoDecodeH265ReferenceInfoFlags {
+            _bitfield_align_1: [],
+            _bitfield_1: Default::default(),
+            __bindgen_padding_0: Default::default(),
+        };
+
+        flags.set_used_for_long_term_reference(self.long_term as u32);
+
+        StdVideoDecodeH265ReferenceInfo {
+            flags,
+            PicOrderCntVal: self.pic_order_cnt,
+        }
+    }
+}
+
+/// DPB slots of the references the picture being decoded uses, the `RefPicSet*` lists of `StdVideoDecodeH265PictureInfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RefPicSets {
+    pub st_curr_before: [u8; REF_PIC_SET_LIST_SIZE],
+    pub st_curr_after: [u8; REF_PIC_SET_LIST_SIZE],
+    pub lt_curr: [u8; REF_PIC_SET_LIST_SIZE],
+}
+
+impl Default for RefPicSets {
+    fn default() -> Self {
+        Self {
+            st_curr_before: [NO_REFERENCE_PICTURE; REF_PIC_SET_LIST_SIZE],
+            st_curr_after: [NO_REFERENCE_PICTURE; REF_PIC_SET_LIST_SIZE],
+            lt_curr: [NO_REFERENCE_PICTURE; REF_PIC_SET_LIST_SIZE],
+        }
+    }
+}
+
+/// Tracks which DPB slots hold which H.265 reference pictures.
+///
+/// Unlike H.264, each picture states the references it keeps in its reference picture set (8.3.2), so the manager
+/// derives the picture order count and the references of a picture from its slice segment header:
+///
+/// ```rust,ignore
+/// let header = stream_inspector.slice_segment_header(first_slice_segment)?;
+/// let slot = dpb.next_slot_for(&header)?;
+/// let decode = DecodeH265::new(..).dpb(&dpb, slot, &dpb_views).slice_segment_header(&header);
+/// // submit decode ...
+/// dpb.mark_reference(slot)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct H265DpbSlotManager {
+    max_slots: u32,
+    /// Pictures marked as used for reference, in decoding order.
+    references: Vec<H265DpbSlot>,
+    /// References of the picture returned by [`Self::next_slot_for`] last.
+    ref_pic_sets: RefPicSets,
+    /// `PicOrderCntVal` of `prevTid0Pic` (8.3.1).
+    prev_tid0_pic_order_cnt: i32,
+    /// If no picture was decoded yet, which makes a CRA picture start a coded video sequence like an IDR picture.
+    first_picture: bool,
+}
+
+impl H265DpbSlotManager {
+    /// Creates a new manager for a session with `max_slots` DPB slots.
+    pub fn new(max_slots: u32) -> Self {
+        Self {
+            max_slots,
+            references: Vec::new(),
+            ref_pic_sets: RefPicSets::default(),
+            prev_tid0_pic_order_cnt: 0,
+            first_picture: true,
+        }
+    }
+
+    /// Returns the slot the picture `header` belongs to should be decoded into.
+    ///
+    /// Derives the picture order count (8.3.1) and applies the reference picture set (8.3.2) of the picture, i.e.,
+    /// pictures it does not keep stop being references. The slot does not become a reference until
+    /// [`mark_reference`](Self::mark_reference) is called.
+    pub fn next_slot_for(&mut self, header: &SliceSegmentHeader) -> Result<H265DpbSlot, Error> {
+        let max_pic_order_cnt_lsb = 1i32 << header.log2_max_pic_order_cnt_lsb;
+        let no_rasl_output = header.is_idr() || header.is_bla() || (header.is_irap() && self.first_picture);
+        let pic_order_cnt_lsb = header.slice_pic_order_cnt_lsb as i32;
+
+        let pic_order_cnt_msb = match header.is_irap() && no_rasl_output {
+            true => 0,
+            false => {
+                let prev_lsb = self.prev_tid0_pic_order_cnt & (max_pic_order_cnt_lsb - 1);
+                let prev_msb = self.prev_tid0_pic_order_cnt - prev_lsb;
+
+                if pic_order_cnt_lsb < prev_lsb && prev_lsb - pic_order_cnt_lsb >= max_pic_order_cnt_lsb / 2 {
+                    prev_msb + max_pic_order_cnt_lsb
+                } else if pic_order_cnt_lsb > prev_lsb && pic_order_cnt_lsb - prev_lsb > max_pic_order_cnt_lsb / 2 {
+                    prev_msb - max_pic_order_cnt_lsb
+                } else {
+                    prev_msb
+                }
+            }
+        };
+
+        let pic_order_cnt = pic_order_cnt_msb
+            .checked_add(pic_order_cnt_lsb)
+            .ok_or_else(|| error!(Variant::InvalidBitstream, "Picture order count overflows."))?;
+
+        if no_rasl_output {
+            self.references.clear();
+        }
+
+        self.ref_pic_sets = self.apply_ref_pic_set(header, pic_order_cnt, max_pic_order_cnt_lsb)?;
+
+        if header.temporal_id == 0 && !header.is_leading() && !header.is_sub_layer_non_reference() {
+            self.prev_tid0_pic_order_cnt = pic_order_cnt;
+        }
+
+        self.first_picture = false;
+
+        let index = (0..self.max_slots)
+            .find(|x| self.references.iter().all(|r| r.index != *x))
+            .ok_or_else(|| error!(Variant::NoFreeDpbSlot))?;
+
+        Ok(H265DpbSlot {
+            index,
+            pic_order_cnt,
+            long_term: false,
+        })
+    }
+
+    /// Keeps the references in the RPS of `header`, marking its long-term ones as such, and drops all others.
+    fn apply_ref_pic_set(
+        &mut self,
+        header: &SliceSegmentHeader,
+        pic_order_cnt: i32,
+        max_pic_order_cnt_lsb: i32,
+    ) -> Result<RefPicSets, Error> {
+        let mut rval = RefPicSets::default();
+
+        if header.is_idr() {
+            self.references.clear();
+            return Ok(rval);
+        }
+
+        // References not matched yet, and those kept, with whether they are long-term now.
+        let mut candidates = self.references.clone();
+        let mut kept = Vec::with_capacity(self.references.len());
+        let mut curr = [Vec::new(), Vec::new(), Vec::new()];
+        let missing = || error!(Variant::InvalidBitstream, "Picture references a picture not in the DPB.");
+
+        // Long-term references first, as these may also match short-term references by their POC LSBs.
+        for long_term_ref in &header.long_term_refs {
+            let mut poc = long_term_ref.poc_lsb_lt as i64;
+
+            if long_term_ref.delta_poc_msb_present_flag {
+                poc += pic_order_cnt as i64
+                    - long_term_ref.delta_poc_msb_cycle_lt as i64 * max_pic_order_cnt_lsb as i64
+                    - (pic_order_cnt & (max_pic_order_cnt_lsb - 1)) as i64;
+            }
+
+            let matches = |x: &H265DpbSlot| match long_term_ref.delta_poc_msb_present_flag {
+                true => x.pic_order_cnt as i64 == poc,
+                false => (x.pic_order_cnt & (max_pic_order_cnt_lsb - 1)) as i64 == poc,
+            };
+
+            match candidates.iter().position(matches) {
+                Some(i) => {
+                    let slot = candidates.remove(i);
+
+                    if long_term_ref.used_by_curr_pic_lt {
+                        curr[2].push(slot.index);
+                    }
+
+                    kept.push((slot.index, true));
+                }
+                // Foll entries may be missing, e.g., after seeking.
+                None if long_term_ref.used_by_curr_pic_lt => return Err(missing()),
+                None => {}
+            }
+        }
+
+        let rps = &header.short_term_ref_pic_set;
+        let negative = rps.delta_poc_s0.iter().zip(rps.used_by_curr_pic_s0).take(rps.num_negative_pics);
+        let positive = rps.delta_poc_s1.iter().zip(rps.used_by_curr_pic_s1).take(rps.num_positive_pics);
+
+        for (list, (delta, used)) in negative.map(|x| (0, x)).chain(positive.map(|x| (1, x))) {
+            let poc = pic_order_cnt as i64 + *delta as i64;
+
+            match candidates.iter().position(|x| !x.long_term && x.pic_order_cnt as i64 == poc) {
+                Some(i) => {
+                    let slot = candidates.remove(i);
+
+                    if used {
+                        curr[list].push(slot.index);
+                    }
+
+                    kept.push((slot.index, false));
+                }
+                None if used => return Err(missing()),
+                None => {}
+            }
+        }
+
+        // Everything not in the RPS is marked as unused for reference.
+        self.references
+            .retain_mut(|x| match kept.iter().find(|(index, _)| *index == x.index) {
+                Some((_, long_term)) => {
+                    x.long_term |= long_term;
+                    true
+                }
+                None => false,
+            });
+
+        for (list, indices) in [&mut rval.st_curr_before, &mut rval.st_curr_after, &mut rval.lt_curr]
+            .into_iter()
+            .zip(&curr)
+        {
+            if indices.len() > REF_PIC_SET_LIST_SIZE {
+                return Err(error!(Variant::InvalidBitstream, "Too many references in reference picture set."));
+            }
+
+            for (entry, index) in list.iter_mut().zip(indices) {
+                *entry = *index as u8;
+            }
+        }
+
+        Ok(rval)
+    }
+
+    /// Marks a decoded picture as short-term reference, as all H.265 pictures are once decoded.
+    ///
+    /// The reference picture sets of later pictures decide how long it stays one.
+    pub fn mark_reference(&mut self, slot: H265DpbSlot) -> Result<(), Error> {
+        self.references.retain(|x| x.index != slot.index);
+
+        if self.references.len() >= self.max_slots as usize {
+            return Err(error!(Variant::NoFreeDpbSlot));
+        }
+
+        self.references.push(slot);
+
+        Ok(())
+    }
+
+    /// Removes all references, e.g., when seeking. The next picture must be an IRAP picture.
+    pub fn reset(&mut self) {
+        self.references.clear();
+        self.ref_pic_sets = RefPicSets::default();
+        self.prev_tid0_pic_order_cnt = 0;
+        self.first_picture = true;
+    }
+
+    /// All references, in decoding order.
+    pub fn references(&self) -> &[H265DpbSlot] {
+        &self.references
+    }
+
+    pub fn max_slots(&self) -> u32 {
+        self.max_slots
+    }
+
+    /// DPB slots of the references of the picture returned by [`Self::next_slot_for`] last.
+    pub(crate) fn ref_pic_sets(&self) -> &RefPicSets {
+        &self.ref_pic_sets
+    }
+
+    /// Produces the reference slot infos for decoding into `setup` while referencing all references.
+    ///
+    /// `resources` are the picture resources for each DPB slot, indexed by slot index.
+    pub(crate) fn reference_slots<'a>(
+        &self,
+        setup: &H265DpbSlot,
+        resources: &[VideoPictureResourceInfoKHR<'a>],
+    ) -> Result<H265ReferenceSlots<'a>, Error> {
+        let slots = self
+            .references
+            .iter()
+            .filter(|x| x.index != setup.index)
+            .chain(std::iter::once(setup))
+            .copied()
+            .collect::<Vec<_>>();
+
+        let mut rval = H265ReferenceSlots {
+            std_infos: slots.iter().map(|x| x.std_reference_info()).collect(),
+            dpb_infos: Vec::with_capacity(slots.len()),
+            picture_resources: Vec::with_capacity(slots.len()),
+            slots: Vec::with_capacity(slots.len()),
+        };
+
+        for slot in &slots {
+            let resource = resources
+                .get(slot.index as usize)
+                .copied()
+                .ok_or_else(|| error!(Variant::NoFreeDpbSlot, "No picture resource for DPB slot."))?;
+
+            rval.picture_resources.push(resource);
+        }
+
+        // From here on the vectors above must not be resized anymore, as the infos below point into them.
+        for std_info in &rval.std_infos {
+            rval.dpb_infos.push(VideoDecodeH265DpbSlotInfoKHR {
+                p_std_reference_info: std_info,
+                ..Default::default()
+            });
+        }
+
+        for (i, slot) in slots.iter().enumerate() {
+            rval.slots.push(VideoReferenceSlotInfoKHR {
+                p_next: (&rval.dpb_infos[i] as *const VideoDecodeH265DpbSlotInfoKHR).cast(),
+                slot_index: slot.index as i32,
+                p_picture_resource: &rval.picture_resources[i],
+                ..Default::default()
+            });
+        }
+
+        Ok(rval)
+    }
+}
+
+/// Reference slot infos for a single H.265 decode operation, and everything they point to.
+///
+/// All pointees live on the heap, so moving this struct is fine, but the vectors must not be modified.
+pub(crate) struct H265ReferenceSlots<'a> {
+    std_infos: Vec<StdVideoDecodeH265ReferenceInfo>,
+    dpb_infos: Vec<VideoDecodeH265DpbSlotInfoKHR<'a>>,
+    picture_resources: Vec<VideoPictureResourceInfoKHR<'a>>,
+    slots: Vec<VideoReferenceSlotInfoKHR<'a>>,
+}
+
+impl<'a> H265ReferenceSlots<'a> {
+    /// Slots referenced by the decode operation (i.e., without the setup slot).
+    pub(crate) fn references(&self) -> &[VideoReferenceSlotInfoKHR<'a>] {
+        &self.slots[..self.slots.len() - 1]
+    }
+
+    /// The slot the decoded picture is written to.
+    pub(crate) fn setup(&self) -> &VideoReferenceSlotInfoKHR<'a> {
+        &self.slots[self.slots.len() - 1]
+    }
+
+    /// Slots to bind when beginning video coding, the setup slot is marked as not yet active.
+    pub(crate) fn begin_coding_slots(&self) -> Vec<VideoReferenceSlotInfoKHR<'a>> {
+        let mut rval = self.slots.clone();
+
+        if let Some(setup) = rval.last_mut() {
+            setup.slot_index = -1;
+        }
+
+        rval
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::{Error, Variant};
+    use crate::video::h265::parameters::ShortTermRefPicSet;
+    use crate::video::h265::{H265DpbSlotManager, SliceSegmentHeader};
+
+    const IDR_W_RADL: u8 = 19;
+    const TRAIL_R: u8 = 1;
+
+    /// A picture with 4 bit POC LSBs, referencing pictures before it by their delta POCs.
+    fn header(nal_unit_type: u8, slice_pic_order_cnt_lsb: u32, negative: &[(i32, bool)]) -> SliceSegmentHeader {
+        let mut short_term_ref_pic_set = ShortTermRefPicSet {
+            num_negative_pics: negative.len(),
+            ..Default::default()
+        };
+
+        for (i, (delta, used)) in negative.iter().enumerate() {
+            short_term_ref_pic_set.delta_poc_s0[i] = *delta;
+            short_term_ref_pic_set.used_by_curr_pic_s0[i] = *used;
+        }
+
+        SliceSegmentHeader {
+            nal_unit_type,
+            first_slice_segment_in_pic_flag: true,
+            slice_pic_order_cnt_lsb,
+            log2_max_pic_order_cnt_lsb: 4,
+            short_term_ref_pic_set,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reference_picture_set_drops_references() -> Result<(), Error> {
+        let mut dpb = H265DpbSlotManager::new(4);
+
+        let idr = dpb.next_slot_for(&header(IDR_W_RADL, 0, &[]))?;
+        dpb.mark_reference(idr)?;
+
+        let p1 = dpb.next_slot_for(&header(TRAIL_R, 1, &[(-1, true)]))?;
+        assert_eq!((p1.index(), p1.pic_order_cnt()), (1, 1));
+        assert_eq!(dpb.ref_pic_sets().st_curr_before, [0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        dpb.mark_reference(p1)?;
+
+        // POC 0 stays in the DPB, but isn't referenced by this picture.
+        let p2 = dpb.next_slot_for(&header(TRAIL_R, 2, &[(-1, true), (-2, false)]))?;
+        assert_eq!(dpb.ref_pic_sets().st_curr_before[..2], [1, 0xff]);
+        assert_eq!(dpb.references().len(), 2);
+        dpb.mark_reference(p2)?;
+
+        // POC 0 and 1 are dropped, so slot 0 can be reused.
+        let p3 = dpb.next_slot_for(&header(TRAIL_R, 3, &[(-1, true)]))?;
+        let pocs = dpb.references().iter().map(|x| x.pic_order_cnt()).collect::<Vec<_>>();
+        assert_eq!(pocs, [2]);
+        assert_eq!(p3.index(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pic_order_cnt_wraps() -> Result<(), Error> {
+        let mut dpb = H265DpbSlotManager::new(4);
+        let mut pocs = Vec::new();
+
+        for (nal_unit_type, lsb) in [(IDR_W_RADL, 0), (TRAIL_R, 6), (TRAIL_R, 12), (TRAIL_R, 2), (TRAIL_R, 9)] {
+            let slot = dpb.next_slot_for(&header(nal_unit_type, lsb, &[]))?;
+            pocs.push(slot.pic_order_cnt());
+            dpb.mark_reference(slot)?;
+        }
+
+        assert_eq!(pocs, [0, 6, 12, 18, 25]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_missing_reference() -> Result<(), Error> {
+        let mut dpb = H265DpbSlotManager::new(4);
+
+        let idr = dpb.next_slot_for(&header(IDR_W_RADL, 0, &[]))?;
+        dpb.mark_reference(idr)?;
+
+        let result = dpb.next_slot_for(&header(TRAIL_R, 1, &[(-5, true)]));
+        assert!(result.is_err_and(|e| matches!(e.variant(), Variant::InvalidBitstream)));
+
+        // Pictures merely kept for later ones may be missing.
+        dpb.next_slot_for(&header(TRAIL_R, 2, &[(-2, true), (-5, false)]))?;
+
+        Ok(())
+    }
+}
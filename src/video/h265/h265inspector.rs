@@ -0,0 +1,277 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::video::bitstream::{strip_start_code, BitReader};
+use crate::video::h265::parameters::{Pps, Sps, StdParameterSets, Vps};
+use crate::video::h265::slice::SliceSegmentHeader;
+use crate::video::profile::{chroma_subsampling, component_bit_depth};
+use crate::video::{StreamInspector, VideoProfileInfoBundle};
+use ash::vk::native::StdVideoH265ProfileIdc_STD_VIDEO_H265_PROFILE_IDC_MAIN;
+use ash::vk::{VideoCodecOperationFlagsKHR, VideoProfileListInfoKHR};
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::ptr::addr_of;
+
+const NAL_UNIT_TYPE_VPS: u8 = 32;
+const NAL_UNIT_TYPE_SPS: u8 = 33;
+const NAL_UNIT_TYPE_PPS: u8 = 34;
+
+/// Parses H.265 NAL units and returns meta data we need to feed into Vulkan.
+#[derive(Default)]
+pub struct H265StreamInspector {
+    vps: BTreeMap<u8, Vps>,
+    sps: BTreeMap<u8, Sps>,
+    pps: BTreeMap<u8, Pps>,
+}
+
+impl H265StreamInspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds a single NAL unit (with or without start code), parameter sets are remembered, everything else is ignored.
+    pub fn feed_nal(&mut self, nal: &[u8]) -> Result<(), Error> {
+        let nal = strip_start_code(nal);
+
+        if nal.len() < 2 {
+            return Err(error!(Variant::InvalidBitstream, "NAL unit too short."));
+        }
+
+        let nal_unit_type = (nal[0] >> 1) & 0x3f;
+        // Unescaped while reading, as slices (which we ignore) can be megabytes.
+        let mut reader = BitReader::new_escaped(&nal[2..]);
+
+        match nal_unit_type {
+            NAL_UNIT_TYPE_VPS => {
+                let vps = Vps::parse(&mut reader)?;
+                self.vps.insert(vps.vps_video_parameter_set_id, vps);
+            }
+            NAL_UNIT_TYPE_SPS => {
+                let sps = Sps::parse(&mut reader)?;
+                self.sps.insert(sps.sps_seq_parameter_set_id, sps);
+            }
+            NAL_UNIT_TYPE_PPS => {
+                let pps = Pps::parse(&mut reader)?;
+                self.pps.insert(pps.pps_pic_parameter_set_id, pps);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Width and height of the first SPS seen, if any.
+    pub fn extent(&self) -> Option<(u32, u32)> {
+        self.sps
+            .values()
+            .next()
+            .map(|x| (x.pic_width_in_luma_samples, x.pic_height_in_luma_samples))
+    }
+
+    /// Parses the header of the first slice segment of a picture, using the SPS and PPS seen so far.
+    ///
+    /// Feed it to [`H265DpbSlotManager::next_slot_for`](crate::video::h265::H265DpbSlotManager::next_slot_for) and
+    /// [`DecodeH265::slice_segment_header`](crate::ops::DecodeH265::slice_segment_header).
+    pub fn slice_segment_header(&self, nal: &[u8]) -> Result<SliceSegmentHeader, Error> {
+        SliceSegmentHeader::parse(nal, &self.sps, &self.pps)
+    }
+
+    pub(crate) fn std_parameter_sets(&self) -> StdParameterSets {
+        StdParameterSets::new(self.vps.values(), self.sps.values(), self.pps.values())
+    }
+
+    pub fn profiles<'f>(&self) -> Pin<Box<VideoProfileInfoBundle<'f>>> {
+        let mut inner = Box::pin(VideoProfileInfoBundle::default());
+
+        let m = unsafe { inner.as_mut().get_unchecked_mut() };
+
+        let sps = self.sps.values().next();
+
+        m.info_h265.std_profile_idc = sps.map_or(StdVideoH265ProfileIdc_STD_VIDEO_H265_PROFILE_IDC_MAIN, |x| {
+            x.profile_tier_level.general_profile_idc as u32
+        });
+
+        m.info.p_next = addr_of!(m.info_h265).cast();
+        m.info.video_codec_operation = VideoCodecOperationFlagsKHR::DECODE_H265;
+        m.info.chroma_subsampling = chroma_subsampling(sps.map_or(1, |x| x.chroma_format_idc));
+        m.info.luma_bit_depth = component_bit_depth(sps.map_or(8, |x| x.bit_depth_luma_minus8 + 8));
+        m.info.chroma_bit_depth = component_bit_depth(sps.map_or(8, |x| x.bit_depth_chroma_minus8 + 8));
+
+        m.list = VideoProfileListInfoKHR {
+            p_profiles: addr_of!(m.info),
+            profile_count: 1,
+            ..Default::default()
+        };
+
+        inner
+    }
+}
+
+impl StreamInspector for H265StreamInspector {
+    fn profiles<'f>(&self) -> Pin<Box<VideoProfileInfoBundle<'f>>> {
+        self.profiles()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::{Error, Variant};
+    use crate::video::bitstream::{emulation_prevention, BitWriter};
+    use crate::video::h265::H265StreamInspector;
+    use crate::video::nal_units;
+    use ash::vk::VideoCodecOperationFlagsKHR;
+
+    // Parameter sets of a 64x48 main profile stream.
+    const VPS: &[u8] = &[
+        0x00, 0x00, 0x00, 0x01, 0x40, 0x01, 0x0c, 0x01, 0xff, 0xff, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x03, 0x00, 0x1e, 0x95, 0x94, 0x09,
+    ];
+    const SPS: &[u8] = &[
+        0x00, 0x00, 0x00, 0x01, 0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00, 0x03, 0x00, 0x1e,
+        0xa0, 0x20, 0x83, 0x16, 0x59, 0x59, 0x52, 0x93, 0x0b, 0x80, 0x40, 0x00, 0x00, 0xfa, 0x00, 0x00, 0x18, 0x6a, 0x02,
+    ];
+    const PPS: &[u8] = &[0x00, 0x00, 0x00, 0x01, 0x44, 0x01, 0xc0, 0x73, 0xc0, 0x89];
+
+    #[test]
+    fn inspect_h265_parameter_sets() -> Result<(), Error> {
+        let mut inspector = H265StreamInspector::new();
+
+        inspector.feed_nal(VPS)?;
+        inspector.feed_nal(SPS)?;
+        inspector.feed_nal(PPS)?;
+
+        assert_eq!(inspector.extent(), Some((64, 48)));
+
+        let sps = &inspector.sps[&0];
+        assert_eq!(sps.profile_tier_level.general_profile_idc, 1);
+        assert_eq!(sps.profile_tier_level.general_level_idc, 30);
+        assert_eq!(sps.log2_max_pic_order_cnt_lsb_minus4, 4);
+        assert_eq!(sps.chroma_format_idc, 1);
+
+        let std = inspector.std_parameter_sets();
+        assert_eq!(std.vps.len(), 1);
+        assert_eq!(std.sps.len(), 1);
+        assert_eq!(std.pps.len(), 1);
+        assert_eq!(std.sps[0].pic_width_in_luma_samples, 64);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_truncated_sps() {
+        let mut inspector = H265StreamInspector::new();
+
+        assert!(inspector.feed_nal(&SPS[..12]).is_err());
+    }
+
+    /// A 64x48 main profile SPS, with short-term RPS written by `st_ref_pic_sets`.
+    fn sps_with_rps(num_short_term_ref_pic_sets: u32, st_ref_pic_sets: impl FnOnce(&mut BitWriter)) -> Vec<u8> {
+        let mut writer = BitWriter::new();
+
+        writer.u(4, 0); // sps_video_parameter_set_id
+        writer.u(3, 0); // sps_max_sub_layers_minus1
+        writer.flag(true); // sps_temporal_id_nesting_flag
+        writer.u(8, 0x01); // general_profile_space, general_tier_flag, general_profile_idc
+        writer.u(32, 0x6000_0000); // general_profile_compatibility_flags
+        writer.u(4, 0b1001); // general_progressive_source_flag .. general_frame_only_constraint_flag
+        writer.u(32, 0);
+        writer.u(12, 0);
+        writer.u(8, 30); // general_level_idc
+        writer.ue(0); // sps_seq_parameter_set_id
+        writer.ue(1); // chroma_format_idc
+        writer.ue(64);
+        writer.ue(48);
+        writer.flag(false); // conformance_window_flag
+        writer.ue(0); // bit_depth_luma_minus8
+        writer.ue(0); // bit_depth_chroma_minus8
+        writer.ue(4); // log2_max_pic_order_cnt_lsb_minus4
+        writer.flag(true); // sps_sub_layer_ordering_info_present_flag
+        writer.ue(4);
+        writer.ue(0);
+        writer.ue(0);
+        writer.ue(0); // log2_min_luma_coding_block_size_minus3
+        writer.ue(1);
+        writer.ue(0);
+        writer.ue(1);
+        writer.ue(0);
+        writer.ue(0);
+        writer.flag(false); // scaling_list_enabled_flag
+        writer.flag(false); // amp_enabled_flag
+        writer.flag(false); // sample_adaptive_offset_enabled_flag
+        writer.flag(false); // pcm_enabled_flag
+        writer.ue(num_short_term_ref_pic_sets);
+        st_ref_pic_sets(&mut writer);
+
+        let mut nal = vec![0x00, 0x00, 0x00, 0x01, 0x42, 0x01];
+        nal.extend(emulation_prevention(&writer.finish()));
+        nal
+    }
+
+    #[test]
+    fn rejects_malformed_short_term_rps() {
+        // 32 delta POCs, which a set predicted from it would have turned into a 33 bit flag mask.
+        let too_many = sps_with_rps(2, |writer| {
+            writer.ue(16);
+            writer.ue(16);
+
+            for _ in 0..32 {
+                writer.ue(0);
+                writer.flag(true);
+            }
+
+            writer.flag(true); // inter_ref_pic_set_prediction_flag
+            writer.flag(false);
+            writer.ue(0);
+
+            for _ in 0..=32 {
+                writer.flag(true);
+            }
+        });
+
+        // Delta POCs summing up beyond `i32`.
+        let overflowing = sps_with_rps(1, |writer| {
+            writer.ue(2);
+            writer.ue(0);
+
+            for _ in 0..2 {
+                writer.ue(0x7fff_fffe);
+                writer.flag(true);
+            }
+        });
+
+        for sps in [too_many, overflowing] {
+            let mut inspector = H265StreamInspector::new();
+            let result = inspector.feed_nal(&sps);
+
+            assert!(result.is_err_and(|e| matches!(e.variant(), Variant::InvalidBitstream)));
+        }
+    }
+
+    #[test]
+    fn get_profile_info_list() -> Result<(), Error> {
+        let inspector = H265StreamInspector::new();
+        let mut profiles = inspector.profiles();
+        let infos = unsafe { &mut profiles.as_mut().get_unchecked_mut().list };
+
+        unsafe {
+            assert_eq!(infos.profile_count, 1);
+            assert_eq!((*infos.p_profiles).video_codec_operation, VideoCodecOperationFlagsKHR::DECODE_H265);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn inspect_h265_stream() -> Result<(), Error> {
+        let h265_data = include_bytes!("../../../tests/videos/single_64x48.h265");
+
+        let mut inspector = H265StreamInspector::new();
+
+        for nal in nal_units(h265_data) {
+            inspector.feed_nal(nal)?;
+        }
+
+        assert_eq!(inspector.extent(), Some((64, 48)));
+
+        Ok(())
+    }
+}
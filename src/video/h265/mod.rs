@@ -0,0 +1,11 @@
+//! Operations related to H.265 codecs.
+mod dpb;
+mod h265inspector;
+mod parameters;
+mod slice;
+
+pub use dpb::{H265DpbSlot, H265DpbSlotManager};
+pub use h265inspector::H265StreamInspector;
+pub use slice::SliceSegmentHeader;
+
+pub(crate) use dpb::H265ReferenceSlots;
@@ -0,0 +1,1459 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::video::bitstream::BitReader;
+use ash::vk::native::{
+    StdVideoH265DecPicBufMgr, StdVideoH265LevelIdc, StdVideoH265LongTermRefPicsSps, StdVideoH265PictureParameterSet, StdVideoH265PpsFlags,
+    StdVideoH265ProfileTierLevel, StdVideoH265ProfileTierLevelFlags, StdVideoH265ScalingLists, StdVideoH265SequenceParameterSet,
+    StdVideoH265SequenceParameterSetVui, StdVideoH265ShortTermRefPicSet, StdVideoH265ShortTermRefPicSetFlags, StdVideoH265SpsFlags,
+    StdVideoH265SpsVuiFlags, StdVideoH265VideoParameterSet, StdVideoH265VpsFlags,
+};
+use std::ptr::null;
+
+const MAX_SUB_LAYERS: usize = 7;
+
+/// Default 8x8 (and larger) intra scaling list, Table 7-6.
+const DEFAULT_SCALING_LIST_INTRA: [u8; 64] = [
+    16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 17, 16, 17, 16, 17, 18, 17, 18, 18, 17, 18, 21, 19, 20, 21, 20, 19, 21, 24, 22, 22, 24, 24, 22,
+    22, 24, 25, 25, 27, 30, 27, 25, 25, 29, 31, 35, 35, 31, 29, 36, 41, 44, 41, 36, 47, 54, 54, 47, 65, 70, 65, 88, 88, 115,
+];
+
+/// Default 8x8 (and larger) inter scaling list, Table 7-6.
+const DEFAULT_SCALING_LIST_INTER: [u8; 64] = [
+    16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 17, 17, 17, 17, 17, 18, 18, 18, 18, 18, 18, 20, 20, 20, 20, 20, 20, 20, 24, 24, 24, 24, 24, 24,
+    24, 24, 25, 25, 25, 25, 25, 25, 25, 28, 28, 28, 28, 28, 28, 33, 33, 33, 33, 33, 41, 41, 41, 41, 54, 54, 54, 71, 71, 91,
+];
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct ProfileTierLevel {
+    pub general_tier_flag: bool,
+    pub general_profile_idc: u8,
+    pub general_progressive_source_flag: bool,
+    pub general_interlaced_source_flag: bool,
+    pub general_non_packed_constraint_flag: bool,
+    pub general_frame_only_constraint_flag: bool,
+    pub general_level_idc: u8,
+}
+
+impl ProfileTierLevel {
+    fn parse(reader: &mut BitReader, max_sub_layers_minus1: u8) -> Result<Self, Error> {
+        let _general_profile_space = reader.u(2)?;
+        let general_tier_flag = reader.flag()?;
+        let general_profile_idc = reader.u(5)? as u8;
+        let _general_profile_compatibility_flags = reader.u(32)?;
+        let general_progressive_source_flag = reader.flag()?;
+        let general_interlaced_source_flag = reader.flag()?;
+        let general_non_packed_constraint_flag = reader.flag()?;
+        let general_frame_only_constraint_flag = reader.flag()?;
+        reader.skip(44)?; // Various constraint flags we don't care about.
+        let general_level_idc = reader.u(8)? as u8;
+
+        let mut sub_layer_profile_present = [false; MAX_SUB_LAYERS];
+        let mut sub_layer_level_present = [false; MAX_SUB_LAYERS];
+
+        for i in 0..max_sub_layers_minus1 as usize {
+            sub_layer_profile_present[i] = reader.flag()?;
+            sub_layer_level_present[i] = reader.flag()?;
+        }
+
+        if max_sub_layers_minus1 > 0 {
+            reader.skip(2 * (8 - max_sub_layers_minus1 as usize))?;
+        }
+
+        for i in 0..max_sub_layers_minus1 as usize {
+            if sub_layer_profile_present[i] {
+                reader.skip(88)?;
+            }
+
+            if sub_layer_level_present[i] {
+                reader.skip(8)?;
+            }
+        }
+
+        Ok(Self {
+            general_tier_flag,
+            general_profile_idc,
+            general_progressive_source_flag,
+            general_interlaced_source_flag,
+            general_non_packed_constraint_flag,
+            general_frame_only_constraint_flag,
+            general_level_idc,
+        })
+    }
+
+    /// Maps `general_level_idc` (30 times the level number) to the Vulkan enum.
+    fn std_level_idc(&self) -> StdVideoH265LevelIdc {
+        match self.general_level_idc {
+            0..=30 => 0,
+            31..=60 => 1,
+            61..=63 => 2,
+            64..=90 => 3,
+            91..=93 => 4,
+            94..=120 => 5,
+            121..=123 => 6,
+            124..=150 => 7,
+            151..=153 => 8,
+            154..=156 => 9,
+            157..=180 => 10,
+            181..=183 => 11,
+            _ => 12,
+        }
+    }
+
+    fn to_std(self) -> StdVideoH265ProfileTierLevel {
+        let mut flags = StdVideoH265ProfileTierLevelFlags {
+            _bitfield_align_1: [],
+            _bitfield_1: Default::default(),
+            __bindgen_padding_0: Default::default(),
+        };
+
+        flags.set_general_tier_flag(self.general_tier_flag as u32);
+        flags.set_general_progressive_source_flag(self.general_progressive_source_flag as u32);
+        flags.set_general_interlaced_source_flag(self.general_interlaced_source_flag as u32);
+        flags.set_general_non_packed_constraint_flag(self.general_non_packed_constraint_flag as u32);
+        flags.set_general_frame_only_constraint_flag(self.general_frame_only_constraint_flag as u32);
+
+        StdVideoH265ProfileTierLevel {
+            flags,
+            general_profile_idc: self.general_profile_idc as u32,
+            general_level_idc: self.std_level_idc(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct DecPicBufMgr {
+    pub max_latency_increase_plus1: [u32; MAX_SUB_LAYERS],
+    pub max_dec_pic_buffering_minus1: [u8; MAX_SUB_LAYERS],
+    pub max_num_reorder_pics: [u8; MAX_SUB_LAYERS],
+}
+
+impl DecPicBufMgr {
+    fn parse(reader: &mut BitReader, max_sub_layers_minus1: u8, ordering_info_present: bool) -> Result<Self, Error> {
+        let mut rval = Self::default();
+        let first = if ordering_info_present { 0 } else { max_sub_layers_minus1 as usize };
+
+        for i in first..=max_sub_layers_minus1 as usize {
+            rval.max_dec_pic_buffering_minus1[i] = reader.ue()? as u8;
+            rval.max_num_reorder_pics[i] = reader.ue()? as u8;
+            rval.max_latency_increase_plus1[i] = reader.ue()?;
+        }
+
+        // Values of lower sub-layers are inferred from the highest one if not present.
+        for i in 0..first {
+            rval.max_dec_pic_buffering_minus1[i] = rval.max_dec_pic_buffering_minus1[first];
+            rval.max_num_reorder_pics[i] = rval.max_num_reorder_pics[first];
+            rval.max_latency_increase_plus1[i] = rval.max_latency_increase_plus1[first];
+        }
+
+        Ok(rval)
+    }
+
+    fn to_std(self) -> StdVideoH265DecPicBufMgr {
+        StdVideoH265DecPicBufMgr {
+            max_latency_increase_plus1: self.max_latency_increase_plus1,
+            max_dec_pic_buffering_minus1: self.max_dec_pic_buffering_minus1,
+            max_num_reorder_pics: self.max_num_reorder_pics,
+        }
+    }
+}
+
+/// Scaling lists in coded (up-right diagonal) order, as expected by Vulkan.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScalingLists {
+    pub list_4x4: [[u8; 16]; 6],
+    pub list_8x8: [[u8; 64]; 6],
+    pub list_16x16: [[u8; 64]; 6],
+    pub list_32x32: [[u8; 64]; 2],
+    pub dc_16x16: [u8; 6],
+    pub dc_32x32: [u8; 2],
+}
+
+impl Default for ScalingLists {
+    fn default() -> Self {
+        let defaults = [
+            DEFAULT_SCALING_LIST_INTRA,
+            DEFAULT_SCALING_LIST_INTRA,
+            DEFAULT_SCALING_LIST_INTRA,
+            DEFAULT_SCALING_LIST_INTER,
+            DEFAULT_SCALING_LIST_INTER,
+            DEFAULT_SCALING_LIST_INTER,
+        ];
+
+        Self {
+            list_4x4: [[16; 16]; 6],
+            list_8x8: defaults,
+            list_16x16: defaults,
+            list_32x32: [DEFAULT_SCALING_LIST_INTRA, DEFAULT_SCALING_LIST_INTER],
+            dc_16x16: [16; 6],
+            dc_32x32: [16; 2],
+        }
+    }
+}
+
+impl ScalingLists {
+    fn parse(reader: &mut BitReader) -> Result<Self, Error> {
+        let defaults = Self::default();
+        let mut rval = Self::default();
+
+        for size_id in 0..4 {
+            let step = if size_id == 3 { 3 } else { 1 };
+
+            for matrix_id in (0..6).step_by(step) {
+                let coef_num = 64.min(1 << (4 + (size_id << 1)));
+                let scaling_list_pred_mode_flag = reader.flag()?;
+
+                if !scaling_list_pred_mode_flag {
+                    let delta = reader.ue()? as usize * step;
+
+                    if delta > matrix_id {
+                        return Err(error!(Variant::InvalidBitstream, "Invalid scaling_list_pred_matrix_id_delta."));
+                    }
+
+                    let (source, dc) = if delta == 0 {
+                        (defaults.list(size_id, matrix_id), 16)
+                    } else {
+                        (rval.list(size_id, matrix_id - delta), rval.dc(size_id, matrix_id - delta))
+                    };
+
+                    rval.list_mut(size_id, matrix_id)[..coef_num].copy_from_slice(&source[..coef_num]);
+                    rval.set_dc(size_id, matrix_id, dc);
+                } else {
+                    let mut next_coef = 8;
+
+                    if size_id > 1 {
+                        let dc = reader.se()? + 8;
+                        next_coef = dc;
+                        rval.set_dc(size_id, matrix_id, dc as u8);
+                    }
+
+                    for i in 0..coef_num {
+                        let delta = reader.se()?;
+                        next_coef = (next_coef + delta + 256).rem_euclid(256);
+                        rval.list_mut(size_id, matrix_id)[i] = next_coef as u8;
+                    }
+                }
+            }
+        }
+
+        Ok(rval)
+    }
+
+    fn list(&self, size_id: usize, matrix_id: usize) -> [u8; 64] {
+        let mut rval = [0; 64];
+
+        match size_id {
+            0 => rval[..16].copy_from_slice(&self.list_4x4[matrix_id]),
+            1 => rval = self.list_8x8[matrix_id],
+            2 => rval = self.list_16x16[matrix_id],
+            _ => rval = self.list_32x32[matrix_id / 3],
+        }
+
+        rval
+    }
+
+    fn list_mut(&mut self, size_id: usize, matrix_id: usize) -> &mut [u8] {
+        match size_id {
+            0 => &mut self.list_4x4[matrix_id],
+            1 => &mut self.list_8x8[matrix_id],
+            2 => &mut self.list_16x16[matrix_id],
+            _ => &mut self.list_32x32[matrix_id / 3],
+        }
+    }
+
+    fn dc(&self, size_id: usize, matrix_id: usize) -> u8 {
+        match size_id {
+            2 => self.dc_16x16[matrix_id],
+            3 => self.dc_32x32[matrix_id / 3],
+            _ => 16,
+        }
+    }
+
+    fn set_dc(&mut self, size_id: usize, matrix_id: usize, dc: u8) {
+        match size_id {
+            2 => self.dc_16x16[matrix_id] = dc,
+            3 => self.dc_32x32[matrix_id / 3] = dc,
+            _ => {}
+        }
+    }
+
+    fn to_std(self) -> StdVideoH265ScalingLists {
+        StdVideoH265ScalingLists {
+            ScalingList4x4: self.list_4x4,
+            ScalingList8x8: self.list_8x8,
+            ScalingList16x16: self.list_16x16,
+            ScalingList32x32: self.list_32x32,
+            ScalingListDCCoef16x16: self.dc_16x16,
+            ScalingListDCCoef32x32: self.dc_32x32,
+        }
+    }
+}
+
+/// A short-term reference picture set, both the syntax elements and the derived delta POCs.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct ShortTermRefPicSet {
+    pub inter_ref_pic_set_prediction_flag: bool,
+    pub delta_idx_minus1: u32,
+    pub delta_rps_sign: bool,
+    pub abs_delta_rps_minus1: u32,
+    pub used_by_curr_pic_flag: u32,
+    pub use_delta_flag: u32,
+    pub num_negative_pics: usize,
+    pub num_positive_pics: usize,
+    pub delta_poc_s0: [i32; 16],
+    pub delta_poc_s1: [i32; 16],
+    pub used_by_curr_pic_s0: [bool; 16],
+    pub used_by_curr_pic_s1: [bool; 16],
+}
+
+impl ShortTermRefPicSet {
+    pub fn num_delta_pocs(&self) -> usize {
+        self.num_negative_pics + self.num_positive_pics
+    }
+
+    /// Parses `st_ref_pic_set(idx)`, `previous` are the sets parsed so far.
+    pub(crate) fn parse(reader: &mut BitReader, idx: usize, num_sets: usize, previous: &[ShortTermRefPicSet]) -> Result<Self, Error> {
+        let mut rval = Self::default();
+
+        if idx != 0 {
+            rval.inter_ref_pic_set_prediction_flag = reader.flag()?;
+        }
+
+        if rval.inter_ref_pic_set_prediction_flag {
+            if idx == num_sets {
+                rval.delta_idx_minus1 = reader.ue()?;
+            }
+
+            rval.delta_rps_sign = reader.flag()?;
+            rval.abs_delta_rps_minus1 = reader.ue()?;
+
+            if rval.abs_delta_rps_minus1 > 0x7fff {
+                return Err(error!(Variant::InvalidBitstream, "Invalid abs_delta_rps_minus1."));
+            }
+
+            let reference = idx
+                .checked_sub(rval.delta_idx_minus1 as usize + 1)
+                .and_then(|x| previous.get(x))
+                .ok_or_else(|| error!(Variant::InvalidBitstream, "Invalid short-term RPS reference."))?;
+
+            let delta_rps = (1 - 2 * rval.delta_rps_sign as i32) * (rval.abs_delta_rps_minus1 as i32 + 1);
+            let mut used_by_curr_pic = [false; 33];
+            let mut use_delta = [true; 33];
+
+            for j in 0..=reference.num_delta_pocs() {
+                used_by_curr_pic[j] = reader.flag()?;
+
+                if !used_by_curr_pic[j] {
+                    use_delta[j] = reader.flag()?;
+                }
+
+                rval.used_by_curr_pic_flag |= (used_by_curr_pic[j] as u32) << j;
+                rval.use_delta_flag |= (use_delta[j] as u32) << j;
+            }
+
+            rval.derive_from(reference, delta_rps, &used_by_curr_pic, &use_delta)?;
+        } else {
+            let num_negative_pics = reader.ue()? as usize;
+            let num_positive_pics = reader.ue()? as usize;
+
+            // Also keeps the flags of sets predicted from this one within 16 bits.
+            if num_negative_pics.saturating_add(num_positive_pics) > 16 {
+                return Err(error!(Variant::InvalidBitstream, "Too many pictures in short-term RPS."));
+            }
+
+            let invalid_delta = || error!(Variant::InvalidBitstream, "Invalid delta POC in short-term RPS.");
+            let mut poc = 0i32;
+
+            for i in 0..num_negative_pics {
+                let delta = i32::try_from(reader.ue()?).map_err(|_| invalid_delta())?;
+                poc = poc.checked_sub(delta).and_then(|x| x.checked_sub(1)).ok_or_else(invalid_delta)?;
+                rval.delta_poc_s0[i] = poc;
+                rval.used_by_curr_pic_s0[i] = reader.flag()?;
+            }
+
+            poc = 0;
+
+            for i in 0..num_positive_pics {
+                let delta = i32::try_from(reader.ue()?).map_err(|_| invalid_delta())?;
+                poc = poc.checked_add(delta).and_then(|x| x.checked_add(1)).ok_or_else(invalid_delta)?;
+                rval.delta_poc_s1[i] = poc;
+                rval.used_by_curr_pic_s1[i] = reader.flag()?;
+            }
+
+            rval.num_negative_pics = num_negative_pics;
+            rval.num_positive_pics = num_positive_pics;
+        }
+
+        Ok(rval)
+    }
+
+    /// Derives the delta POCs of an inter-predicted set, equations 7-61 and 7-62.
+    fn derive_from(
+        &mut self,
+        reference: &ShortTermRefPicSet,
+        delta_rps: i32,
+        used: &[bool; 33],
+        use_delta: &[bool; 33],
+    ) -> Result<(), Error> {
+        let mut s0 = Vec::with_capacity(16);
+        let mut s1 = Vec::with_capacity(16);
+        let num_negative = reference.num_negative_pics;
+        let num_delta = reference.num_delta_pocs();
+        let shifted = |x: i32| {
+            x.checked_add(delta_rps)
+                .ok_or_else(|| error!(Variant::InvalidBitstream, "Invalid delta POC in short-term RPS."))
+        };
+
+        for j in (0..reference.num_positive_pics).rev() {
+            let d_poc = shifted(reference.delta_poc_s1[j])?;
+
+            if d_poc < 0 && use_delta[num_negative + j] {
+                s0.push((d_poc, used[num_negative + j]));
+            }
+        }
+
+        if delta_rps < 0 && use_delta[num_delta] {
+            s0.push((delta_rps, used[num_delta]));
+        }
+
+        for j in 0..num_negative {
+            let d_poc = shifted(reference.delta_poc_s0[j])?;
+
+            if d_poc < 0 && use_delta[j] {
+                s0.push((d_poc, used[j]));
+            }
+        }
+
+        for j in (0..num_negative).rev() {
+            let d_poc = shifted(reference.delta_poc_s0[j])?;
+
+            if d_poc > 0 && use_delta[j] {
+                s1.push((d_poc, used[j]));
+            }
+        }
+
+        if delta_rps > 0 && use_delta[num_delta] {
+            s1.push((delta_rps, used[num_delta]));
+        }
+
+        for j in 0..reference.num_positive_pics {
+            let d_poc = shifted(reference.delta_poc_s1[j])?;
+
+            if d_poc > 0 && use_delta[num_negative + j] {
+                s1.push((d_poc, used[num_negative + j]));
+            }
+        }
+
+        if s0.len() + s1.len() > 16 {
+            return Err(error!(Variant::InvalidBitstream, "Too many pictures in short-term RPS."));
+        }
+
+        for (i, (poc, used)) in s0.iter().enumerate() {
+            self.delta_poc_s0[i] = *poc;
+            self.used_by_curr_pic_s0[i] = *used;
+        }
+
+        for (i, (poc, used)) in s1.iter().enumerate() {
+            self.delta_poc_s1[i] = *poc;
+            self.used_by_curr_pic_s1[i] = *used;
+        }
+
+        self.num_negative_pics = s0.len();
+        self.num_positive_pics = s1.len();
+
+        Ok(())
+    }
+
+    fn to_std(self) -> StdVideoH265ShortTermRefPicSet {
+        let mut flags = StdVideoH265ShortTermRefPicSetFlags {
+            _bitfield_align_1: [],
+            _bitfield_1: Default::default(),
+            __bindgen_padding_0: Default::default(),
+        };
+
+        flags.set_inter_ref_pic_set_prediction_flag(self.inter_ref_pic_set_prediction_flag as u32);
+        flags.set_delta_rps_sign(self.delta_rps_sign as u32);
+
+        let mut delta_poc_s0_minus1 = [0; 16];
+        let mut delta_poc_s1_minus1 = [0; 16];
+        let mut used_by_curr_pic_s0_flag = 0;
+        let mut used_by_curr_pic_s1_flag = 0;
+
+        let mut previous = 0;
+
+        for (i, (minus1, poc)) in delta_poc_s0_minus1
+            .iter_mut()
+            .zip(self.delta_poc_s0)
+            .take(self.num_negative_pics)
+            .enumerate()
+        {
+            *minus1 = (previous - poc - 1) as u16;
+            used_by_curr_pic_s0_flag |= (self.used_by_curr_pic_s0[i] as u16) << i;
+            previous = poc;
+        }
+
+        previous = 0;
+
+        for (i, (minus1, poc)) in delta_poc_s1_minus1
+            .iter_mut()
+            .zip(self.delta_poc_s1)
+            .take(self.num_positive_pics)
+            .enumerate()
+        {
+            *minus1 = (poc - previous - 1) as u16;
+            used_by_curr_pic_s1_flag |= (self.used_by_curr_pic_s1[i] as u16) << i;
+            previous = poc;
+        }
+
+        StdVideoH265ShortTermRefPicSet {
+            flags,
+            delta_idx_minus1: self.delta_idx_minus1,
+            use_delta_flag: self.use_delta_flag as u16,
+            abs_delta_rps_minus1: self.abs_delta_rps_minus1 as u16,
+            used_by_curr_pic_flag: self.used_by_curr_pic_flag as u16,
+            used_by_curr_pic_s0_flag,
+            used_by_curr_pic_s1_flag,
+            reserved1: 0,
+            reserved2: 0,
+            reserved3: 0,
+            num_negative_pics: self.num_negative_pics as u8,
+            num_positive_pics: self.num_positive_pics as u8,
+            delta_poc_s0_minus1,
+            delta_poc_s1_minus1,
+        }
+    }
+}
+
+/// Skips `hrd_parameters()`, we don't forward HRD information to Vulkan.
+fn skip_hrd_parameters(reader: &mut BitReader, common_inf_present: bool, max_sub_layers_minus1: u8) -> Result<(), Error> {
+    let mut nal_hrd = false;
+    let mut vcl_hrd = false;
+    let mut sub_pic_hrd_params = false;
+
+    if common_inf_present {
+        nal_hrd = reader.flag()?;
+        vcl_hrd = reader.flag()?;
+
+        if nal_hrd || vcl_hrd {
+            sub_pic_hrd_params = reader.flag()?;
+
+            if sub_pic_hrd_params {
+                reader.skip(8 + 5 + 1 + 5)?;
+            }
+
+            reader.skip(4 + 4)?;
+
+            if sub_pic_hrd_params {
+                reader.skip(4)?;
+            }
+
+            reader.skip(5 + 5 + 5)?;
+        }
+    }
+
+    for _ in 0..=max_sub_layers_minus1 {
+        let fixed_pic_rate_general = reader.flag()?;
+        let fixed_pic_rate_within_cvs = if fixed_pic_rate_general { true } else { reader.flag()? };
+        let mut low_delay_hrd = false;
+        let mut cpb_cnt_minus1 = 0;
+
+        if fixed_pic_rate_within_cvs {
+            reader.ue()?;
+        } else {
+            low_delay_hrd = reader.flag()?;
+        }
+
+        if !low_delay_hrd {
+            cpb_cnt_minus1 = reader.ue()?;
+        }
+
+        for present in [nal_hrd, vcl_hrd] {
+            if !present {
+                continue;
+            }
+
+            for _ in 0..=cpb_cnt_minus1 {
+                reader.ue()?;
+                reader.ue()?;
+
+                if sub_pic_hrd_params {
+                    reader.ue()?;
+                    reader.ue()?;
+                }
+
+                reader.flag()?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Vui {
+    pub aspect_ratio_info_present_flag: bool,
+    pub aspect_ratio_idc: u8,
+    pub sar_width: u16,
+    pub sar_height: u16,
+    pub overscan_info_present_flag: bool,
+    pub overscan_appropriate_flag: bool,
+    pub video_signal_type_present_flag: bool,
+    pub video_format: u8,
+    pub video_full_range_flag: bool,
+    pub colour_description_present_flag: bool,
+    pub colour_primaries: u8,
+    pub transfer_characteristics: u8,
+    pub matrix_coeffs: u8,
+    pub chroma_loc_info_present_flag: bool,
+    pub chroma_sample_loc_type_top_field: u8,
+    pub chroma_sample_loc_type_bottom_field: u8,
+    pub neutral_chroma_indication_flag: bool,
+    pub field_seq_flag: bool,
+    pub frame_field_info_present_flag: bool,
+    pub default_display_window_flag: bool,
+    pub def_disp_win_left_offset: u16,
+    pub def_disp_win_right_offset: u16,
+    pub def_disp_win_top_offset: u16,
+    pub def_disp_win_bottom_offset: u16,
+    pub vui_timing_info_present_flag: bool,
+    pub vui_num_units_in_tick: u32,
+    pub vui_time_scale: u32,
+    pub vui_poc_proportional_to_timing_flag: bool,
+    pub vui_num_ticks_poc_diff_one_minus1: u32,
+    pub bitstream_restriction_flag: bool,
+    pub tiles_fixed_structure_flag: bool,
+    pub motion_vectors_over_pic_boundaries_flag: bool,
+    pub restricted_ref_pic_lists_flag: bool,
+    pub min_spatial_segmentation_idc: u16,
+    pub max_bytes_per_pic_denom: u8,
+    pub max_bits_per_min_cu_denom: u8,
+    pub log2_max_mv_length_horizontal: u8,
+    pub log2_max_mv_length_vertical: u8,
+}
+
+impl Vui {
+    fn parse(reader: &mut BitReader, max_sub_layers_minus1: u8) -> Result<Self, Error> {
+        let mut rval = Self {
+            video_format: 5,
+            colour_primaries: 2,
+            transfer_characteristics: 2,
+            matrix_coeffs: 2,
+            motion_vectors_over_pic_boundaries_flag: true,
+            max_bytes_per_pic_denom: 2,
+            max_bits_per_min_cu_denom: 1,
+            log2_max_mv_length_horizontal: 15,
+            log2_max_mv_length_vertical: 15,
+            ..Default::default()
+        };
+
+        rval.aspect_ratio_info_present_flag = reader.flag()?;
+
+        if rval.aspect_ratio_info_present_flag {
+            rval.aspect_ratio_idc = reader.u(8)? as u8;
+
+            if rval.aspect_ratio_idc == 255 {
+                rval.sar_width = reader.u(16)? as u16;
+                rval.sar_height = reader.u(16)? as u16;
+            }
+        }
+
+        rval.overscan_info_present_flag = reader.flag()?;
+
+        if rval.overscan_info_present_flag {
+            rval.overscan_appropriate_flag = reader.flag()?;
+        }
+
+        rval.video_signal_type_present_flag = reader.flag()?;
+
+        if rval.video_signal_type_present_flag {
+            rval.video_format = reader.u(3)? as u8;
+            rval.video_full_range_flag = reader.flag()?;
+            rval.colour_description_present_flag = reader.flag()?;
+
+            if rval.colour_description_present_flag {
+                rval.colour_primaries = reader.u(8)? as u8;
+                rval.transfer_characteristics = reader.u(8)? as u8;
+                rval.matrix_coeffs = reader.u(8)? as u8;
+            }
+        }
+
+        rval.chroma_loc_info_present_flag = reader.flag()?;
+
+        if rval.chroma_loc_info_present_flag {
+            rval.chroma_sample_loc_type_top_field = reader.ue()? as u8;
+            rval.chroma_sample_loc_type_bottom_field = reader.ue()? as u8;
+        }
+
+        rval.neutral_chroma_indication_flag = reader.flag()?;
+        rval.field_seq_flag = reader.flag()?;
+        rval.frame_field_info_present_flag = reader.flag()?;
+        rval.default_display_window_flag = reader.flag()?;
+
+        if rval.default_display_window_flag {
+            rval.def_disp_win_left_offset = reader.ue()? as u16;
+            rval.def_disp_win_right_offset = reader.ue()? as u16;
+            rval.def_disp_win_top_offset = reader.ue()? as u16;
+            rval.def_disp_win_bottom_offset = reader.ue()? as u16;
+        }
+
+        rval.vui_timing_info_present_flag = reader.flag()?;
+
+        if rval.vui_timing_info_present_flag {
+            rval.vui_num_units_in_tick = reader.u(32)?;
+            rval.vui_time_scale = reader.u(32)?;
+            rval.vui_poc_proportional_to_timing_flag = reader.flag()?;
+
+            if rval.vui_poc_proportional_to_timing_flag {
+                rval.vui_num_ticks_poc_diff_one_minus1 = reader.ue()?;
+            }
+
+            if reader.flag()? {
+                skip_hrd_parameters(reader, true, max_sub_layers_minus1)?;
+            }
+        }
+
+        rval.bitstream_restriction_flag = reader.flag()?;
+
+        if rval.bitstream_restriction_flag {
+            rval.tiles_fixed_structure_flag = reader.flag()?;
+            rval.motion_vectors_over_pic_boundaries_flag = reader.flag()?;
+            rval.restricted_ref_pic_lists_flag = reader.flag()?;
+            rval.min_spatial_segmentation_idc = reader.ue()? as u16;
+            rval.max_bytes_per_pic_denom = reader.ue()? as u8;
+            rval.max_bits_per_min_cu_denom = reader.ue()? as u8;
+            rval.log2_max_mv_length_horizontal = reader.ue()? as u8;
+            rval.log2_max_mv_length_vertical = reader.ue()? as u8;
+        }
+
+        Ok(rval)
+    }
+
+    fn to_std(self) -> StdVideoH265SequenceParameterSetVui {
+        let mut flags = StdVideoH265SpsVuiFlags {
+            _bitfield_align_1: [],
+            _bitfield_1: Default::default(),
+            __bindgen_padding_0: Default::default(),
+        };
+
+        flags.set_aspect_ratio_info_present_flag(self.aspect_ratio_info_present_flag as u32);
+        flags.set_overscan_info_present_flag(self.overscan_info_present_flag as u32);
+        flags.set_overscan_appropriate_flag(self.overscan_appropriate_flag as u32);
+        flags.set_video_signal_type_present_flag(self.video_signal_type_present_flag as u32);
+        flags.set_video_full_range_flag(self.video_full_range_flag as u32);
+        flags.set_colour_description_present_flag(self.colour_description_present_flag as u32);
+        flags.set_chroma_loc_info_present_flag(self.chroma_loc_info_present_flag as u32);
+        flags.set_neutral_chroma_indication_flag(self.neutral_chroma_indication_flag as u32);
+        flags.set_field_seq_flag(self.field_seq_flag as u32);
+        flags.set_frame_field_info_present_flag(self.frame_field_info_present_flag as u32);
+        flags.set_default_display_window_flag(self.default_display_window_flag as u32);
+        flags.set_vui_timing_info_present_flag(self.vui_timing_info_present_flag as u32);
+        flags.set_vui_poc_proportional_to_timing_flag(self.vui_poc_proportional_to_timing_flag as u32);
+        flags.set_vui_hrd_parameters_present_flag(0); // HRD parameters are skipped while parsing.
+        flags.set_bitstream_restriction_flag(self.bitstream_restriction_flag as u32);
+        flags.set_tiles_fixed_structure_flag(self.tiles_fixed_structure_flag as u32);
+        flags.set_motion_vectors_over_pic_boundaries_flag(self.motion_vectors_over_pic_boundaries_flag as u32);
+        flags.set_restricted_ref_pic_lists_flag(self.restricted_ref_pic_lists_flag as u32);
+
+        StdVideoH265SequenceParameterSetVui {
+            flags,
+            aspect_ratio_idc: self.aspect_ratio_idc as u32,
+            sar_width: self.sar_width,
+            sar_height: self.sar_height,
+            video_format: self.video_format,
+            colour_primaries: self.colour_primaries,
+            transfer_characteristics: self.transfer_characteristics,
+            matrix_coeffs: self.matrix_coeffs,
+            chroma_sample_loc_type_top_field: self.chroma_sample_loc_type_top_field,
+            chroma_sample_loc_type_bottom_field: self.chroma_sample_loc_type_bottom_field,
+            reserved1: 0,
+            reserved2: 0,
+            def_disp_win_left_offset: self.def_disp_win_left_offset,
+            def_disp_win_right_offset: self.def_disp_win_right_offset,
+            def_disp_win_top_offset: self.def_disp_win_top_offset,
+            def_disp_win_bottom_offset: self.def_disp_win_bottom_offset,
+            vui_num_units_in_tick: self.vui_num_units_in_tick,
+            vui_time_scale: self.vui_time_scale,
+            vui_num_ticks_poc_diff_one_minus1: self.vui_num_ticks_poc_diff_one_minus1,
+            min_spatial_segmentation_idc: self.min_spatial_segmentation_idc,
+            reserved3: 0,
+            max_bytes_per_pic_denom: self.max_bytes_per_pic_denom,
+            max_bits_per_min_cu_denom: self.max_bits_per_min_cu_denom,
+            log2_max_mv_length_horizontal: self.log2_max_mv_length_horizontal,
+            log2_max_mv_length_vertical: self.log2_max_mv_length_vertical,
+            pHrdParameters: null(),
+        }
+    }
+}
+
+/// Video parameter set, 7.3.2.1.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Vps {
+    pub vps_video_parameter_set_id: u8,
+    pub vps_max_sub_layers_minus1: u8,
+    pub vps_temporal_id_nesting_flag: bool,
+    pub vps_sub_layer_ordering_info_present_flag: bool,
+    pub profile_tier_level: ProfileTierLevel,
+    pub dec_pic_buf_mgr: DecPicBufMgr,
+    pub vps_timing_info_present_flag: bool,
+    pub vps_num_units_in_tick: u32,
+    pub vps_time_scale: u32,
+    pub vps_poc_proportional_to_timing_flag: bool,
+    pub vps_num_ticks_poc_diff_one_minus1: u32,
+}
+
+impl Vps {
+    pub(crate) fn parse(reader: &mut BitReader) -> Result<Self, Error> {
+        let mut rval = Self {
+            vps_video_parameter_set_id: reader.u(4)? as u8,
+            ..Default::default()
+        };
+
+        reader.skip(1 + 1 + 6)?; // base layer flags, vps_max_layers_minus1
+        rval.vps_max_sub_layers_minus1 = reader.u(3)? as u8;
+        rval.vps_temporal_id_nesting_flag = reader.flag()?;
+        reader.skip(16)?; // vps_reserved_0xffff_16bits
+
+        if rval.vps_max_sub_layers_minus1 as usize >= MAX_SUB_LAYERS {
+            return Err(error!(Variant::InvalidBitstream, "Invalid vps_max_sub_layers_minus1."));
+        }
+
+        rval.profile_tier_level = ProfileTierLevel::parse(reader, rval.vps_max_sub_layers_minus1)?;
+        rval.vps_sub_layer_ordering_info_present_flag = reader.flag()?;
+        rval.dec_pic_buf_mgr = DecPicBufMgr::parse(
+            reader,
+            rval.vps_max_sub_layers_minus1,
+            rval.vps_sub_layer_ordering_info_present_flag,
+        )?;
+
+        let vps_max_layer_id = reader.u(6)?;
+        let vps_num_layer_sets_minus1 = reader.ue()?;
+
+        for _ in 1..=vps_num_layer_sets_minus1 {
+            reader.skip(vps_max_layer_id as usize + 1)?;
+        }
+
+        rval.vps_timing_info_present_flag = reader.flag()?;
+
+        if rval.vps_timing_info_present_flag {
+            rval.vps_num_units_in_tick = reader.u(32)?;
+            rval.vps_time_scale = reader.u(32)?;
+            rval.vps_poc_proportional_to_timing_flag = reader.flag()?;
+
+            if rval.vps_poc_proportional_to_timing_flag {
+                rval.vps_num_ticks_poc_diff_one_minus1 = reader.ue()?;
+            }
+        }
+
+        Ok(rval)
+    }
+}
+
+/// Sequence parameter set, 7.3.2.2.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Sps {
+    pub sps_video_parameter_set_id: u8,
+    pub sps_max_sub_layers_minus1: u8,
+    pub sps_temporal_id_nesting_flag: bool,
+    pub profile_tier_level: ProfileTierLevel,
+    pub sps_seq_parameter_set_id: u8,
+    pub chroma_format_idc: u8,
+    pub separate_colour_plane_flag: bool,
+    pub pic_width_in_luma_samples: u32,
+    pub pic_height_in_luma_samples: u32,
+    pub conformance_window_flag: bool,
+    pub conf_win_left_offset: u32,
+    pub conf_win_right_offset: u32,
+    pub conf_win_top_offset: u32,
+    pub conf_win_bottom_offset: u32,
+    pub bit_depth_luma_minus8: u8,
+    pub bit_depth_chroma_minus8: u8,
+    pub log2_max_pic_order_cnt_lsb_minus4: u8,
+    pub sps_sub_layer_ordering_info_present_flag: bool,
+    pub dec_pic_buf_mgr: DecPicBufMgr,
+    pub log2_min_luma_coding_block_size_minus3: u8,
+    pub log2_diff_max_min_luma_coding_block_size: u8,
+    pub log2_min_luma_transform_block_size_minus2: u8,
+    pub log2_diff_max_min_luma_transform_block_size: u8,
+    pub max_transform_hierarchy_depth_inter: u8,
+    pub max_transform_hierarchy_depth_intra: u8,
+    pub scaling_list_enabled_flag: bool,
+    pub sps_scaling_list_data_present_flag: bool,
+    pub scaling_lists: Option<ScalingLists>,
+    pub amp_enabled_flag: bool,
+    pub sample_adaptive_offset_enabled_flag: bool,
+    pub pcm_enabled_flag: bool,
+    pub pcm_sample_bit_depth_luma_minus1: u8,
+    pub pcm_sample_bit_depth_chroma_minus1: u8,
+    pub log2_min_pcm_luma_coding_block_size_minus3: u8,
+    pub log2_diff_max_min_pcm_luma_coding_block_size: u8,
+    pub pcm_loop_filter_disabled_flag: bool,
+    pub short_term_ref_pic_sets: Vec<ShortTermRefPicSet>,
+    pub long_term_ref_pics_present_flag: bool,
+    pub lt_ref_pic_poc_lsb_sps: Vec<u32>,
+    pub used_by_curr_pic_lt_sps_flag: u32,
+    pub sps_temporal_mvp_enabled_flag: bool,
+    pub strong_intra_smoothing_enabled_flag: bool,
+    pub vui: Option<Vui>,
+    pub sps_extension_present_flag: bool,
+    pub sps_range_extension_flag: bool,
+    pub transform_skip_rotation_enabled_flag: bool,
+    pub transform_skip_context_enabled_flag: bool,
+    pub implicit_rdpcm_enabled_flag: bool,
+    pub explicit_rdpcm_enabled_flag: bool,
+    pub extended_precision_processing_flag: bool,
+    pub intra_smoothing_disabled_flag: bool,
+    pub high_precision_offsets_enabled_flag: bool,
+    pub persistent_rice_adaptation_enabled_flag: bool,
+    pub cabac_bypass_alignment_enabled_flag: bool,
+}
+
+impl Sps {
+    pub(crate) fn parse(reader: &mut BitReader) -> Result<Self, Error> {
+        let mut rval = Self {
+            sps_video_parameter_set_id: reader.u(4)? as u8,
+            sps_max_sub_layers_minus1: reader.u(3)? as u8,
+            sps_temporal_id_nesting_flag: reader.flag()?,
+            ..Default::default()
+        };
+
+        if rval.sps_max_sub_layers_minus1 as usize >= MAX_SUB_LAYERS {
+            return Err(error!(Variant::InvalidBitstream, "Invalid sps_max_sub_layers_minus1."));
+        }
+
+        rval.profile_tier_level = ProfileTierLevel::parse(reader, rval.sps_max_sub_layers_minus1)?;
+        rval.sps_seq_parameter_set_id = reader.ue()? as u8;
+        rval.chroma_format_idc = reader.ue()? as u8;
+
+        if rval.chroma_format_idc == 3 {
+            rval.separate_colour_plane_flag = reader.flag()?;
+        }
+
+        rval.pic_width_in_luma_samples = reader.ue()?;
+        rval.pic_height_in_luma_samples = reader.ue()?;
+        rval.conformance_window_flag = reader.flag()?;
+
+        if rval.conformance_window_flag {
+            rval.conf_win_left_offset = reader.ue()?;
+            rval.conf_win_right_offset = reader.ue()?;
+            rval.conf_win_top_offset = reader.ue()?;
+            rval.conf_win_bottom_offset = reader.ue()?;
+        }
+
+        rval.bit_depth_luma_minus8 = reader.ue()? as u8;
+        rval.bit_depth_chroma_minus8 = reader.ue()? as u8;
+        rval.log2_max_pic_order_cnt_lsb_minus4 = reader.ue()? as u8;
+        rval.sps_sub_layer_ordering_info_present_flag = reader.flag()?;
+        rval.dec_pic_buf_mgr = DecPicBufMgr::parse(
+            reader,
+            rval.sps_max_sub_layers_minus1,
+            rval.sps_sub_layer_ordering_info_present_flag,
+        )?;
+        rval.log2_min_luma_coding_block_size_minus3 = reader.ue()? as u8;
+        rval.log2_diff_max_min_luma_coding_block_size = reader.ue()? as u8;
+        rval.log2_min_luma_transform_block_size_minus2 = reader.ue()? as u8;
+        rval.log2_diff_max_min_luma_transform_block_size = reader.ue()? as u8;
+        rval.max_transform_hierarchy_depth_inter = reader.ue()? as u8;
+        rval.max_transform_hierarchy_depth_intra = reader.ue()? as u8;
+        rval.scaling_list_enabled_flag = reader.flag()?;
+
+        if rval.scaling_list_enabled_flag {
+            rval.sps_scaling_list_data_present_flag = reader.flag()?;
+
+            rval.scaling_lists = if rval.sps_scaling_list_data_present_flag {
+                Some(ScalingLists::parse(reader)?)
+            } else {
+                Some(ScalingLists::default())
+            };
+        }
+
+        rval.amp_enabled_flag = reader.flag()?;
+        rval.sample_adaptive_offset_enabled_flag = reader.flag()?;
+        rval.pcm_enabled_flag = reader.flag()?;
+
+        if rval.pcm_enabled_flag {
+            rval.pcm_sample_bit_depth_luma_minus1 = reader.u(4)? as u8;
+            rval.pcm_sample_bit_depth_chroma_minus1 = reader.u(4)? as u8;
+            rval.log2_min_pcm_luma_coding_block_size_minus3 = reader.ue()? as u8;
+            rval.log2_diff_max_min_pcm_luma_coding_block_size = reader.ue()? as u8;
+            rval.pcm_loop_filter_disabled_flag = reader.flag()?;
+        }
+
+        let num_short_term_ref_pic_sets = reader.ue()? as usize;
+
+        if num_short_term_ref_pic_sets > 64 {
+            return Err(error!(Variant::InvalidBitstream, "Invalid num_short_term_ref_pic_sets."));
+        }
+
+        for i in 0..num_short_term_ref_pic_sets {
+            let set = ShortTermRefPicSet::parse(reader, i, num_short_term_ref_pic_sets, &rval.short_term_ref_pic_sets)?;
+            rval.short_term_ref_pic_sets.push(set);
+        }
+
+        rval.long_term_ref_pics_present_flag = reader.flag()?;
+
+        if rval.long_term_ref_pics_present_flag {
+            let num_long_term_ref_pics_sps = reader.ue()?;
+
+            if num_long_term_ref_pics_sps > 32 {
+                return Err(error!(Variant::InvalidBitstream, "Invalid num_long_term_ref_pics_sps."));
+            }
+
+            for i in 0..num_long_term_ref_pics_sps {
+                let lsb = reader.u(rval.log2_max_pic_order_cnt_lsb_minus4 as u32 + 4)?;
+                rval.lt_ref_pic_poc_lsb_sps.push(lsb);
+                rval.used_by_curr_pic_lt_sps_flag |= (reader.flag()? as u32) << i;
+            }
+        }
+
+        rval.sps_temporal_mvp_enabled_flag = reader.flag()?;
+        rval.strong_intra_smoothing_enabled_flag = reader.flag()?;
+
+        if reader.flag()? {
+            rval.vui = Some(Vui::parse(reader, rval.sps_max_sub_layers_minus1)?);
+        }
+
+        rval.sps_extension_present_flag = reader.flag()?;
+
+        if rval.sps_extension_present_flag {
+            rval.sps_range_extension_flag = reader.flag()?;
+            reader.skip(1 + 1 + 1 + 4)?; // multilayer, 3d, scc, 4bits
+
+            if rval.sps_range_extension_flag {
+                rval.transform_skip_rotation_enabled_flag = reader.flag()?;
+                rval.transform_skip_context_enabled_flag = reader.flag()?;
+                rval.implicit_rdpcm_enabled_flag = reader.flag()?;
+                rval.explicit_rdpcm_enabled_flag = reader.flag()?;
+                rval.extended_precision_processing_flag = reader.flag()?;
+                rval.intra_smoothing_disabled_flag = reader.flag()?;
+                rval.high_precision_offsets_enabled_flag = reader.flag()?;
+                rval.persistent_rice_adaptation_enabled_flag = reader.flag()?;
+                rval.cabac_bypass_alignment_enabled_flag = reader.flag()?;
+            }
+        }
+
+        Ok(rval)
+    }
+}
+
+/// Picture parameter set, 7.3.2.3.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Pps {
+    pub pps_pic_parameter_set_id: u8,
+    pub pps_seq_parameter_set_id: u8,
+    pub dependent_slice_segments_enabled_flag: bool,
+    pub output_flag_present_flag: bool,
+    pub num_extra_slice_header_bits: u8,
+    pub sign_data_hiding_enabled_flag: bool,
+    pub cabac_init_present_flag: bool,
+    pub num_ref_idx_l0_default_active_minus1: u8,
+    pub num_ref_idx_l1_default_active_minus1: u8,
+    pub init_qp_minus26: i8,
+    pub constrained_intra_pred_flag: bool,
+    pub transform_skip_enabled_flag: bool,
+    pub cu_qp_delta_enabled_flag: bool,
+    pub diff_cu_qp_delta_depth: u8,
+    pub pps_cb_qp_offset: i8,
+    pub pps_cr_qp_offset: i8,
+    pub pps_slice_chroma_qp_offsets_present_flag: bool,
+    pub weighted_pred_flag: bool,
+    pub weighted_bipred_flag: bool,
+    pub transquant_bypass_enabled_flag: bool,
+    pub tiles_enabled_flag: bool,
+    pub entropy_coding_sync_enabled_flag: bool,
+    pub num_tile_columns_minus1: u8,
+    pub num_tile_rows_minus1: u8,
+    pub uniform_spacing_flag: bool,
+    pub column_width_minus1: [u16; 19],
+    pub row_height_minus1: [u16; 21],
+    pub loop_filter_across_tiles_enabled_flag: bool,
+    pub pps_loop_filter_across_slices_enabled_flag: bool,
+    pub deblocking_filter_control_present_flag: bool,
+    pub deblocking_filter_override_enabled_flag: bool,
+    pub pps_deblocking_filter_disabled_flag: bool,
+    pub pps_beta_offset_div2: i8,
+    pub pps_tc_offset_div2: i8,
+    pub pps_scaling_list_data_present_flag: bool,
+    pub scaling_lists: Option<ScalingLists>,
+    pub lists_modification_present_flag: bool,
+    pub log2_parallel_merge_level_minus2: u8,
+    pub slice_segment_header_extension_present_flag: bool,
+    pub pps_extension_present_flag: bool,
+    pub pps_range_extension_flag: bool,
+    pub log2_max_transform_skip_block_size_minus2: u8,
+    pub cross_component_prediction_enabled_flag: bool,
+    pub chroma_qp_offset_list_enabled_flag: bool,
+    pub diff_cu_chroma_qp_offset_depth: u8,
+    pub chroma_qp_offset_list_len_minus1: u8,
+    pub cb_qp_offset_list: [i8; 6],
+    pub cr_qp_offset_list: [i8; 6],
+    pub log2_sao_offset_scale_luma: u8,
+    pub log2_sao_offset_scale_chroma: u8,
+}
+
+impl Pps {
+    pub(crate) fn parse(reader: &mut BitReader) -> Result<Self, Error> {
+        let mut rval = Self {
+            pps_pic_parameter_set_id: reader.ue()? as u8,
+            pps_seq_parameter_set_id: reader.ue()? as u8,
+            dependent_slice_segments_enabled_flag: reader.flag()?,
+            output_flag_present_flag: reader.flag()?,
+            num_extra_slice_header_bits: reader.u(3)? as u8,
+            sign_data_hiding_enabled_flag: reader.flag()?,
+            cabac_init_present_flag: reader.flag()?,
+            num_ref_idx_l0_default_active_minus1: reader.ue()? as u8,
+            num_ref_idx_l1_default_active_minus1: reader.ue()? as u8,
+            init_qp_minus26: reader.se()? as i8,
+            constrained_intra_pred_flag: reader.flag()?,
+            transform_skip_enabled_flag: reader.flag()?,
+            cu_qp_delta_enabled_flag: reader.flag()?,
+            ..Default::default()
+        };
+
+        if rval.cu_qp_delta_enabled_flag {
+            rval.diff_cu_qp_delta_depth = reader.ue()? as u8;
+        }
+
+        rval.pps_cb_qp_offset = reader.se()? as i8;
+        rval.pps_cr_qp_offset = reader.se()? as i8;
+        rval.pps_slice_chroma_qp_offsets_present_flag = reader.flag()?;
+        rval.weighted_pred_flag = reader.flag()?;
+        rval.weighted_bipred_flag = reader.flag()?;
+        rval.transquant_bypass_enabled_flag = reader.flag()?;
+        rval.tiles_enabled_flag = reader.flag()?;
+        rval.entropy_coding_sync_enabled_flag = reader.flag()?;
+
+        if rval.tiles_enabled_flag {
+            rval.num_tile_columns_minus1 = reader.ue()? as u8;
+            rval.num_tile_rows_minus1 = reader.ue()? as u8;
+            rval.uniform_spacing_flag = reader.flag()?;
+
+            if rval.num_tile_columns_minus1 as usize > rval.column_width_minus1.len()
+                || rval.num_tile_rows_minus1 as usize > rval.row_height_minus1.len()
+            {
+                return Err(error!(Variant::InvalidBitstream, "Too many tiles."));
+            }
+
+            if !rval.uniform_spacing_flag {
+                for i in 0..rval.num_tile_columns_minus1 as usize {
+                    rval.column_width_minus1[i] = reader.ue()? as u16;
+                }
+
+                for i in 0..rval.num_tile_rows_minus1 as usize {
+                    rval.row_height_minus1[i] = reader.ue()? as u16;
+                }
+            }
+
+            rval.loop_filter_across_tiles_enabled_flag = reader.flag()?;
+        }
+
+        rval.pps_loop_filter_across_slices_enabled_flag = reader.flag()?;
+        rval.deblocking_filter_control_present_flag = reader.flag()?;
+
+        if rval.deblocking_filter_control_present_flag {
+            rval.deblocking_filter_override_enabled_flag = reader.flag()?;
+            rval.pps_deblocking_filter_disabled_flag = reader.flag()?;
+
+            if !rval.pps_deblocking_filter_disabled_flag {
+                rval.pps_beta_offset_div2 = reader.se()? as i8;
+                rval.pps_tc_offset_div2 = reader.se()? as i8;
+            }
+        }
+
+        rval.pps_scaling_list_data_present_flag = reader.flag()?;
+
+        if rval.pps_scaling_list_data_present_flag {
+            rval.scaling_lists = Some(ScalingLists::parse(reader)?);
+        }
+
+        rval.lists_modification_present_flag = reader.flag()?;
+        rval.log2_parallel_merge_level_minus2 = reader.ue()? as u8;
+        rval.slice_segment_header_extension_present_flag = reader.flag()?;
+        rval.pps_extension_present_flag = reader.flag()?;
+
+        if rval.pps_extension_present_flag {
+            rval.pps_range_extension_flag = reader.flag()?;
+            reader.skip(1 + 1 + 1 + 4)?; // multilayer, 3d, scc, 4bits
+
+            if rval.pps_range_extension_flag {
+                if rval.transform_skip_enabled_flag {
+                    rval.log2_max_transform_skip_block_size_minus2 = reader.ue()? as u8;
+                }
+
+                rval.cross_component_prediction_enabled_flag = reader.flag()?;
+                rval.chroma_qp_offset_list_enabled_flag = reader.flag()?;
+
+                if rval.chroma_qp_offset_list_enabled_flag {
+                    rval.diff_cu_chroma_qp_offset_depth = reader.ue()? as u8;
+                    rval.chroma_qp_offset_list_len_minus1 = reader.ue()? as u8;
+
+                    if rval.chroma_qp_offset_list_len_minus1 >= 6 {
+                        return Err(error!(Variant::InvalidBitstream, "Invalid chroma_qp_offset_list_len_minus1."));
+                    }
+
+                    for i in 0..=rval.chroma_qp_offset_list_len_minus1 as usize {
+                        rval.cb_qp_offset_list[i] = reader.se()? as i8;
+                        rval.cr_qp_offset_list[i] = reader.se()? as i8;
+                    }
+                }
+
+                rval.log2_sao_offset_scale_luma = reader.ue()? as u8;
+                rval.log2_sao_offset_scale_chroma = reader.ue()? as u8;
+            }
+        }
+
+        Ok(rval)
+    }
+}
+
+/// Owns `StdVideoH265*` parameter sets, and everything they point to, so they can be handed to Vulkan.
+///
+/// All pointed-to structs are boxed, so their addresses remain stable when this type moves.
+#[allow(clippy::vec_box)]
+#[derive(Default)]
+pub(crate) struct StdParameterSets {
+    pub vps: Vec<StdVideoH265VideoParameterSet>,
+    pub sps: Vec<StdVideoH265SequenceParameterSet>,
+    pub pps: Vec<StdVideoH265PictureParameterSet>,
+    profile_tier_levels: Vec<Box<StdVideoH265ProfileTierLevel>>,
+    dec_pic_buf_mgrs: Vec<Box<StdVideoH265DecPicBufMgr>>,
+    scaling_lists: Vec<Box<StdVideoH265ScalingLists>>,
+    short_term_ref_pic_sets: Vec<Box<[StdVideoH265ShortTermRefPicSet]>>,
+    long_term_ref_pics: Vec<Box<StdVideoH265LongTermRefPicsSps>>,
+    vuis: Vec<Box<StdVideoH265SequenceParameterSetVui>>,
+}
+
+impl StdParameterSets {
+    pub(crate) fn new<'a>(
+        vps: impl Iterator<Item = &'a Vps>,
+        sps: impl Iterator<Item = &'a Sps>,
+        pps: impl Iterator<Item = &'a Pps>,
+    ) -> Self {
+        let mut rval = Self::default();
+
+        for x in vps {
+            rval.push_vps(x);
+        }
+
+        for x in sps {
+            rval.push_sps(x);
+        }
+
+        for x in pps {
+            rval.push_pps(x);
+        }
+
+        rval
+    }
+
+    fn push_vps(&mut self, vps: &Vps) {
+        let mut flags = StdVideoH265VpsFlags {
+            _bitfield_align_1: [],
+            _bitfield_1: Default::default(),
+            __bindgen_padding_0: Default::default(),
+        };
+
+        flags.set_vps_temporal_id_nesting_flag(vps.vps_temporal_id_nesting_flag as u32);
+        flags.set_vps_sub_layer_ordering_info_present_flag(vps.vps_sub_layer_ordering_info_present_flag as u32);
+        flags.set_vps_timing_info_present_flag(vps.vps_timing_info_present_flag as u32);
+        flags.set_vps_poc_proportional_to_timing_flag(vps.vps_poc_proportional_to_timing_flag as u32);
+
+        let profile_tier_level = Box::new(vps.profile_tier_level.to_std());
+        let dec_pic_buf_mgr = Box::new(vps.dec_pic_buf_mgr.to_std());
+
+        self.vps.push(StdVideoH265VideoParameterSet {
+            flags,
+            vps_video_parameter_set_id: vps.vps_video_parameter_set_id,
+            vps_max_sub_layers_minus1: vps.vps_max_sub_layers_minus1,
+            reserved1: 0,
+            reserved2: 0,
+            vps_num_units_in_tick: vps.vps_num_units_in_tick,
+            vps_time_scale: vps.vps_time_scale,
+            vps_num_ticks_poc_diff_one_minus1: vps.vps_num_ticks_poc_diff_one_minus1,
+            reserved3: 0,
+            pDecPicBufMgr: &*dec_pic_buf_mgr,
+            pHrdParameters: null(),
+            pProfileTierLevel: &*profile_tier_level,
+        });
+
+        self.profile_tier_levels.push(profile_tier_level);
+        self.dec_pic_buf_mgrs.push(dec_pic_buf_mgr);
+    }
+
+    fn push_sps(&mut self, sps: &Sps) {
+        let mut flags = StdVideoH265SpsFlags {
+            _bitfield_align_1: [],
+            _bitfield_1: Default::default(),
+        };
+
+        flags.set_sps_temporal_id_nesting_flag(sps.sps_temporal_id_nesting_flag as u32);
+        flags.set_separate_colour_plane_flag(sps.separate_colour_plane_flag as u32);
+        flags.set_conformance_window_flag(sps.conformance_window_flag as u32);
+        flags.set_sps_sub_layer_ordering_info_present_flag(sps.sps_sub_layer_ordering_info_present_flag as u32);
+        flags.set_scaling_list_enabled_flag(sps.scaling_list_enabled_flag as u32);
+        flags.set_sps_scaling_list_data_present_flag(sps.sps_scaling_list_data_present_flag as u32);
+        flags.set_amp_enabled_flag(sps.amp_enabled_flag as u32);
+        flags.set_sample_adaptive_offset_enabled_flag(sps.sample_adaptive_offset_enabled_flag as u32);
+        flags.set_pcm_enabled_flag(sps.pcm_enabled_flag as u32);
+        flags.set_pcm_loop_filter_disabled_flag(sps.pcm_loop_filter_disabled_flag as u32);
+        flags.set_long_term_ref_pics_present_flag(sps.long_term_ref_pics_present_flag as u32);
+        flags.set_sps_temporal_mvp_enabled_flag(sps.sps_temporal_mvp_enabled_flag as u32);
+        flags.set_strong_intra_smoothing_enabled_flag(sps.strong_intra_smoothing_enabled_flag as u32);
+        flags.set_vui_parameters_present_flag(sps.vui.is_some() as u32);
+        flags.set_sps_extension_present_flag(sps.sps_extension_present_flag as u32);
+        flags.set_sps_range_extension_flag(sps.sps_range_extension_flag as u32);
+        flags.set_transform_skip_rotation_enabled_flag(sps.transform_skip_rotation_enabled_flag as u32);
+        flags.set_transform_skip_context_enabled_flag(sps.transform_skip_context_enabled_flag as u32);
+        flags.set_implicit_rdpcm_enabled_flag(sps.implicit_rdpcm_enabled_flag as u32);
+        flags.set_explicit_rdpcm_enabled_flag(sps.explicit_rdpcm_enabled_flag as u32);
+        flags.set_extended_precision_processing_flag(sps.extended_precision_processing_flag as u32);
+        flags.set_intra_smoothing_disabled_flag(sps.intra_smoothing_disabled_flag as u32);
+        flags.set_high_precision_offsets_enabled_flag(sps.high_precision_offsets_enabled_flag as u32);
+        flags.set_persistent_rice_adaptation_enabled_flag(sps.persistent_rice_adaptation_enabled_flag as u32);
+        flags.set_cabac_bypass_alignment_enabled_flag(sps.cabac_bypass_alignment_enabled_flag as u32);
+
+        let profile_tier_level = Box::new(sps.profile_tier_level.to_std());
+        let dec_pic_buf_mgr = Box::new(sps.dec_pic_buf_mgr.to_std());
+        let scaling_lists = sps.scaling_lists.map(|x| Box::new(x.to_std()));
+        let vui = sps.vui.map(|x| Box::new(x.to_std()));
+        let short_term_ref_pic_sets = sps.short_term_ref_pic_sets.iter().map(|x| x.to_std()).collect::<Box<[_]>>();
+
+        let mut lt_ref_pic_poc_lsb_sps = [0; 32];
+        lt_ref_pic_poc_lsb_sps[..sps.lt_ref_pic_poc_lsb_sps.len()].copy_from_slice(&sps.lt_ref_pic_poc_lsb_sps);
+
+        let long_term_ref_pics = Box::new(StdVideoH265LongTermRefPicsSps {
+            used_by_curr_pic_lt_sps_flag: sps.used_by_curr_pic_lt_sps_flag,
+            lt_ref_pic_poc_lsb_sps,
+        });
+
+        self.sps.push(StdVideoH265SequenceParameterSet {
+            flags,
+            chroma_format_idc: sps.chroma_format_idc as u32,
+            pic_width_in_luma_samples: sps.pic_width_in_luma_samples,
+            pic_height_in_luma_samples: sps.pic_height_in_luma_samples,
+            sps_video_parameter_set_id: sps.sps_video_parameter_set_id,
+            sps_max_sub_layers_minus1: sps.sps_max_sub_layers_minus1,
+            sps_seq_parameter_set_id: sps.sps_seq_parameter_set_id,
+            bit_depth_luma_minus8: sps.bit_depth_luma_minus8,
+            bit_depth_chroma_minus8: sps.bit_depth_chroma_minus8,
+            log2_max_pic_order_cnt_lsb_minus4: sps.log2_max_pic_order_cnt_lsb_minus4,
+            log2_min_luma_coding_block_size_minus3: sps.log2_min_luma_coding_block_size_minus3,
+            log2_diff_max_min_luma_coding_block_size: sps.log2_diff_max_min_luma_coding_block_size,
+            log2_min_luma_transform_block_size_minus2: sps.log2_min_luma_transform_block_size_minus2,
+            log2_diff_max_min_luma_transform_block_size: sps.log2_diff_max_min_luma_transform_block_size,
+            max_transform_hierarchy_depth_inter: sps.max_transform_hierarchy_depth_inter,
+            max_transform_hierarchy_depth_intra: sps.max_transform_hierarchy_depth_intra,
+            num_short_term_ref_pic_sets: sps.short_term_ref_pic_sets.len() as u8,
+            num_long_term_ref_pics_sps: sps.lt_ref_pic_poc_lsb_sps.len() as u8,
+            pcm_sample_bit_depth_luma_minus1: sps.pcm_sample_bit_depth_luma_minus1,
+            pcm_sample_bit_depth_chroma_minus1: sps.pcm_sample_bit_depth_chroma_minus1,
+            log2_min_pcm_luma_coding_block_size_minus3: sps.log2_min_pcm_luma_coding_block_size_minus3,
+            log2_diff_max_min_pcm_luma_coding_block_size: sps.log2_diff_max_min_pcm_luma_coding_block_size,
+            reserved1: 0,
+            reserved2: 0,
+            palette_max_size: 0,
+            delta_palette_max_predictor_size: 0,
+            motion_vector_resolution_control_idc: 0,
+            sps_num_palette_predictor_initializers_minus1: 0,
+            conf_win_left_offset: sps.conf_win_left_offset,
+            conf_win_right_offset: sps.conf_win_right_offset,
+            conf_win_top_offset: sps.conf_win_top_offset,
+            conf_win_bottom_offset: sps.conf_win_bottom_offset,
+            pProfileTierLevel: &*profile_tier_level,
+            pDecPicBufMgr: &*dec_pic_buf_mgr,
+            pScalingLists: scaling_lists.as_deref().map_or(null(), |x| x),
+            pShortTermRefPicSet: short_term_ref_pic_sets.as_ptr(),
+            pLongTermRefPicsSps: &*long_term_ref_pics,
+            pSequenceParameterSetVui: vui.as_deref().map_or(null(), |x| x),
+            pPredictorPaletteEntries: null(),
+        });
+
+        self.profile_tier_levels.push(profile_tier_level);
+        self.dec_pic_buf_mgrs.push(dec_pic_buf_mgr);
+        self.scaling_lists.extend(scaling_lists);
+        self.vuis.extend(vui);
+        self.short_term_ref_pic_sets.push(short_term_ref_pic_sets);
+        self.long_term_ref_pics.push(long_term_ref_pics);
+    }
+
+    fn push_pps(&mut self, pps: &Pps) {
+        let mut flags = StdVideoH265PpsFlags {
+            _bitfield_align_1: [],
+            _bitfield_1: Default::default(),
+        };
+
+        flags.set_dependent_slice_segments_enabled_flag(pps.dependent_slice_segments_enabled_flag as u32);
+        flags.set_output_flag_present_flag(pps.output_flag_present_flag as u32);
+        flags.set_sign_data_hiding_enabled_flag(pps.sign_data_hiding_enabled_flag as u32);
+        flags.set_cabac_init_present_flag(pps.cabac_init_present_flag as u32);
+        flags.set_constrained_intra_pred_flag(pps.constrained_intra_pred_flag as u32);
+        flags.set_transform_skip_enabled_flag(pps.transform_skip_enabled_flag as u32);
+        flags.set_cu_qp_delta_enabled_flag(pps.cu_qp_delta_enabled_flag as u32);
+        flags.set_pps_slice_chroma_qp_offsets_present_flag(pps.pps_slice_chroma_qp_offsets_present_flag as u32);
+        flags.set_weighted_pred_flag(pps.weighted_pred_flag as u32);
+        flags.set_weighted_bipred_flag(pps.weighted_bipred_flag as u32);
+        flags.set_transquant_bypass_enabled_flag(pps.transquant_bypass_enabled_flag as u32);
+        flags.set_tiles_enabled_flag(pps.tiles_enabled_flag as u32);
+        flags.set_entropy_coding_sync_enabled_flag(pps.entropy_coding_sync_enabled_flag as u32);
+        flags.set_uniform_spacing_flag(pps.uniform_spacing_flag as u32);
+        flags.set_loop_filter_across_tiles_enabled_flag(pps.loop_filter_across_tiles_enabled_flag as u32);
+        flags.set_pps_loop_filter_across_slices_enabled_flag(pps.pps_loop_filter_across_slices_enabled_flag as u32);
+        flags.set_deblocking_filter_control_present_flag(pps.deblocking_filter_control_present_flag as u32);
+        flags.set_deblocking_filter_override_enabled_flag(pps.deblocking_filter_override_enabled_flag as u32);
+        flags.set_pps_deblocking_filter_disabled_flag(pps.pps_deblocking_filter_disabled_flag as u32);
+        flags.set_pps_scaling_list_data_present_flag(pps.pps_scaling_list_data_present_flag as u32);
+        flags.set_lists_modification_present_flag(pps.lists_modification_present_flag as u32);
+        flags.set_slice_segment_header_extension_present_flag(pps.slice_segment_header_extension_present_flag as u32);
+        flags.set_pps_extension_present_flag(pps.pps_extension_present_flag as u32);
+        flags.set_cross_component_prediction_enabled_flag(pps.cross_component_prediction_enabled_flag as u32);
+        flags.set_chroma_qp_offset_list_enabled_flag(pps.chroma_qp_offset_list_enabled_flag as u32);
+        flags.set_pps_range_extension_flag(pps.pps_range_extension_flag as u32);
+
+        let sps_video_parameter_set_id = self
+            .sps
+            .iter()
+            .find(|x| x.sps_seq_parameter_set_id == pps.pps_seq_parameter_set_id)
+            .map_or(0, |x| x.sps_video_parameter_set_id);
+
+        let scaling_lists = pps.scaling_lists.map(|x| Box::new(x.to_std()));
+
+        self.pps.push(StdVideoH265PictureParameterSet {
+            flags,
+            pps_pic_parameter_set_id: pps.pps_pic_parameter_set_id,
+            pps_seq_parameter_set_id: pps.pps_seq_parameter_set_id,
+            sps_video_parameter_set_id,
+            num_extra_slice_header_bits: pps.num_extra_slice_header_bits,
+            num_ref_idx_l0_default_active_minus1: pps.num_ref_idx_l0_default_active_minus1,
+            num_ref_idx_l1_default_active_minus1: pps.num_ref_idx_l1_default_active_minus1,
+            init_qp_minus26: pps.init_qp_minus26,
+            diff_cu_qp_delta_depth: pps.diff_cu_qp_delta_depth,
+            pps_cb_qp_offset: pps.pps_cb_qp_offset,
+            pps_cr_qp_offset: pps.pps_cr_qp_offset,
+            pps_beta_offset_div2: pps.pps_beta_offset_div2,
+            pps_tc_offset_div2: pps.pps_tc_offset_div2,
+            log2_parallel_merge_level_minus2: pps.log2_parallel_merge_level_minus2,
+            log2_max_transform_skip_block_size_minus2: pps.log2_max_transform_skip_block_size_minus2,
+            diff_cu_chroma_qp_offset_depth: pps.diff_cu_chroma_qp_offset_depth,
+            chroma_qp_offset_list_len_minus1: pps.chroma_qp_offset_list_len_minus1,
+            cb_qp_offset_list: pps.cb_qp_offset_list,
+            cr_qp_offset_list: pps.cr_qp_offset_list,
+            log2_sao_offset_scale_luma: pps.log2_sao_offset_scale_luma,
+            log2_sao_offset_scale_chroma: pps.log2_sao_offset_scale_chroma,
+            pps_act_y_qp_offset_plus5: 0,
+            pps_act_cb_qp_offset_plus5: 0,
+            pps_act_cr_qp_offset_plus3: 0,
+            pps_num_palette_predictor_initializers: 0,
+            luma_bit_depth_entry_minus8: 0,
+            chroma_bit_depth_entry_minus8: 0,
+            num_tile_columns_minus1: pps.num_tile_columns_minus1,
+            num_tile_rows_minus1: pps.num_tile_rows_minus1,
+            reserved1: 0,
+            reserved2: 0,
+            column_width_minus1: pps.column_width_minus1,
+            row_height_minus1: pps.row_height_minus1,
+            reserved3: 0,
+            pScalingLists: scaling_lists.as_deref().map_or(null(), |x| x),
+            pPredictorPaletteEntries: null(),
+        });
+
+        self.scaling_lists.extend(scaling_lists);
+    }
+}
@@ -0,0 +1,308 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::video::bitstream::{strip_start_code, BitReader};
+use crate::video::h265::parameters::{Pps, ShortTermRefPicSet, Sps};
+use std::collections::BTreeMap;
+
+/// NAL unit types of coded slice segments, as in Table 7-1.
+const NAL_UNIT_TYPE_RASL_R: u8 = 9;
+const NAL_UNIT_TYPE_BLA_W_LP: u8 = 16;
+const NAL_UNIT_TYPE_IDR_W_RADL: u8 = 19;
+const NAL_UNIT_TYPE_IDR_N_LP: u8 = 20;
+const NAL_UNIT_TYPE_RSV_IRAP_23: u8 = 23;
+
+/// A long-term reference picture entry of a slice segment header, see 7.4.7.1.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct LongTermRef {
+    /// `PocLsbLt`, either coded in the slice header or taken from the SPS.
+    pub poc_lsb_lt: u32,
+    /// `UsedByCurrPicLt`.
+    pub used_by_curr_pic_lt: bool,
+    pub delta_poc_msb_present_flag: bool,
+    /// `DeltaPocMsbCycleLt`, accumulated as in (7-52).
+    pub delta_poc_msb_cycle_lt: u32,
+}
+
+/// The parts of a H.265 slice segment header (7.3.6.1) needed to drive decoding, see [`H265StreamInspector::slice_segment_header`](crate::video::h265::H265StreamInspector::slice_segment_header).
+///
+/// Only the header of the first slice segment of a picture is of interest, parsing stops after the reference picture set.
+#[derive(Debug, Default, Clone)]
+pub struct SliceSegmentHeader {
+    pub(crate) nal_unit_type: u8,
+    /// `TemporalId`, i.e., `nuh_temporal_id_plus1 - 1`.
+    pub(crate) temporal_id: u8,
+    pub(crate) first_slice_segment_in_pic_flag: bool,
+    pub(crate) no_output_of_prior_pics_flag: bool,
+    pub(crate) slice_pic_parameter_set_id: u8,
+    pub(crate) seq_parameter_set_id: u8,
+    pub(crate) video_parameter_set_id: u8,
+    pub(crate) pic_output_flag: bool,
+    pub(crate) slice_pic_order_cnt_lsb: u32,
+    /// `log2_max_pic_order_cnt_lsb_minus4 + 4` of the SPS, to derive the picture order count.
+    pub(crate) log2_max_pic_order_cnt_lsb: u8,
+    pub(crate) short_term_ref_pic_set_sps_flag: bool,
+    pub(crate) short_term_ref_pic_set_idx: u8,
+    /// The short-term RPS of the picture, coded in the slice header or selected from the SPS, empty for IDR pictures.
+    pub(crate) short_term_ref_pic_set: ShortTermRefPicSet,
+    /// Bits of the short-term RPS coded in the slice header, 0 if taken from the SPS.
+    pub(crate) num_bits_for_st_ref_pic_set_in_slice: u16,
+    /// `NumDeltaPocs[RefRpsIdx]` if the short-term RPS of the slice header is predicted from one of the SPS.
+    pub(crate) num_delta_pocs_of_ref_rps_idx: u8,
+    pub(crate) long_term_refs: Vec<LongTermRef>,
+}
+
+impl SliceSegmentHeader {
+    /// Parses the slice segment header of a slice segment NAL unit, with or without start code.
+    ///
+    /// The SPS and PPS the slice segment refers to must be in `sps` and `pps`.
+    pub(crate) fn parse(nal: &[u8], sps: &BTreeMap<u8, Sps>, pps: &BTreeMap<u8, Pps>) -> Result<Self, Error> {
+        let nal = strip_start_code(nal);
+
+        if nal.len() < 2 {
+            return Err(error!(Variant::InvalidBitstream, "NAL unit too short."));
+        }
+
+        let nal_unit_type = (nal[0] >> 1) & 0x3f;
+
+        if nal_unit_type > NAL_UNIT_TYPE_RSV_IRAP_23 {
+            return Err(error!(Variant::InvalidBitstream, "NAL unit is not a slice segment."));
+        }
+
+        let mut rval = Self {
+            nal_unit_type,
+            temporal_id: (nal[1] & 0x7).saturating_sub(1),
+            pic_output_flag: true,
+            ..Default::default()
+        };
+
+        // As with H.264 we only read (and unescape) as far as needed, slices can be megabytes.
+        let mut r = BitReader::new_escaped(&nal[2..]);
+
+        rval.first_slice_segment_in_pic_flag = r.flag()?;
+
+        if rval.is_irap() {
+            rval.no_output_of_prior_pics_flag = r.flag()?;
+        }
+
+        let pps = u8::try_from(r.ue()?)
+            .ok()
+            .and_then(|x| pps.get(&x))
+            .ok_or_else(|| error!(Variant::InvalidBitstream, "Slice segment refers to unknown PPS."))?;
+        let sps = sps
+            .get(&pps.pps_seq_parameter_set_id)
+            .ok_or_else(|| error!(Variant::InvalidBitstream, "Slice segment refers to unknown SPS."))?;
+
+        rval.slice_pic_parameter_set_id = pps.pps_pic_parameter_set_id;
+        rval.seq_parameter_set_id = sps.sps_seq_parameter_set_id;
+        rval.video_parameter_set_id = sps.sps_video_parameter_set_id;
+        rval.log2_max_pic_order_cnt_lsb = sps.log2_max_pic_order_cnt_lsb_minus4 + 4;
+
+        if !rval.first_slice_segment_in_pic_flag {
+            return Err(error!(
+                Variant::InvalidBitstream,
+                "Picture does not start with its first slice segment."
+            ));
+        }
+
+        r.skip(pps.num_extra_slice_header_bits as usize)?; // slice_reserved_flag
+        r.ue()?; // slice_type
+
+        if pps.output_flag_present_flag {
+            rval.pic_output_flag = r.flag()?;
+        }
+
+        if sps.separate_colour_plane_flag {
+            r.skip(2)?; // colour_plane_id
+        }
+
+        if rval.is_idr() {
+            return Ok(rval);
+        }
+
+        let log2_max_pic_order_cnt_lsb = rval.log2_max_pic_order_cnt_lsb as u32;
+        let num_short_term_ref_pic_sets = sps.short_term_ref_pic_sets.len();
+
+        rval.slice_pic_order_cnt_lsb = r.u(log2_max_pic_order_cnt_lsb)?;
+        rval.short_term_ref_pic_set_sps_flag = r.flag()?;
+
+        if !rval.short_term_ref_pic_set_sps_flag {
+            let start = r.bits_read();
+            let set = ShortTermRefPicSet::parse(
+                &mut r,
+                num_short_term_ref_pic_sets,
+                num_short_term_ref_pic_sets,
+                &sps.short_term_ref_pic_sets,
+            )?;
+
+            if set.inter_ref_pic_set_prediction_flag {
+                let reference = num_short_term_ref_pic_sets - (set.delta_idx_minus1 as usize + 1);
+                rval.num_delta_pocs_of_ref_rps_idx = sps.short_term_ref_pic_sets[reference].num_delta_pocs() as u8;
+            }
+
+            rval.short_term_ref_pic_set = set;
+            rval.num_bits_for_st_ref_pic_set_in_slice = (r.bits_read() - start) as u16;
+        } else {
+            if num_short_term_ref_pic_sets > 1 {
+                rval.short_term_ref_pic_set_idx = r.u(ceil_log2(num_short_term_ref_pic_sets))? as u8;
+            }
+
+            rval.short_term_ref_pic_set = *sps
+                .short_term_ref_pic_sets
+                .get(rval.short_term_ref_pic_set_idx as usize)
+                .ok_or_else(|| error!(Variant::InvalidBitstream, "Slice segment refers to unknown short-term RPS."))?;
+        }
+
+        if sps.long_term_ref_pics_present_flag {
+            let num_long_term_ref_pics_sps = sps.lt_ref_pic_poc_lsb_sps.len();
+            let num_long_term_sps = match num_long_term_ref_pics_sps {
+                0 => 0,
+                _ => r.ue()? as usize,
+            };
+            let num_long_term_pics = r.ue()? as usize;
+
+            // At most one per DPB slot can be a reference, this just keeps malformed streams from allocating.
+            if num_long_term_sps > num_long_term_ref_pics_sps || num_long_term_sps + num_long_term_pics > 32 {
+                return Err(error!(Variant::InvalidBitstream, "Too many long-term reference pictures."));
+            }
+
+            for i in 0..num_long_term_sps + num_long_term_pics {
+                let mut long_term_ref = LongTermRef::default();
+
+                if i < num_long_term_sps {
+                    let lt_idx_sps = match num_long_term_ref_pics_sps {
+                        1 => 0,
+                        x => r.u(ceil_log2(x))? as usize,
+                    };
+
+                    long_term_ref.poc_lsb_lt = *sps
+                        .lt_ref_pic_poc_lsb_sps
+                        .get(lt_idx_sps)
+                        .ok_or_else(|| error!(Variant::InvalidBitstream, "Invalid lt_idx_sps."))?;
+                    long_term_ref.used_by_curr_pic_lt = sps.used_by_curr_pic_lt_sps_flag & (1 << lt_idx_sps) != 0;
+                } else {
+                    long_term_ref.poc_lsb_lt = r.u(log2_max_pic_order_cnt_lsb)?;
+                    long_term_ref.used_by_curr_pic_lt = r.flag()?;
+                }
+
+                long_term_ref.delta_poc_msb_present_flag = r.flag()?;
+
+                if long_term_ref.delta_poc_msb_present_flag {
+                    long_term_ref.delta_poc_msb_cycle_lt = r.ue()?;
+                }
+
+                // Cycles accumulate within the entries of the SPS, and within those of the slice header (7-52).
+                if i != 0 && i != num_long_term_sps {
+                    let previous = rval.long_term_refs[i - 1].delta_poc_msb_cycle_lt;
+                    long_term_ref.delta_poc_msb_cycle_lt = long_term_ref.delta_poc_msb_cycle_lt.saturating_add(previous);
+                }
+
+                rval.long_term_refs.push(long_term_ref);
+            }
+        }
+
+        Ok(rval)
+    }
+
+    /// If this is an IRAP picture, i.e., BLA, CRA or IDR.
+    pub(crate) fn is_irap(&self) -> bool {
+        (NAL_UNIT_TYPE_BLA_W_LP..=NAL_UNIT_TYPE_RSV_IRAP_23).contains(&self.nal_unit_type)
+    }
+
+    pub(crate) fn is_idr(&self) -> bool {
+        matches!(self.nal_unit_type, NAL_UNIT_TYPE_IDR_W_RADL | NAL_UNIT_TYPE_IDR_N_LP)
+    }
+
+    /// If this is a BLA picture, which like IDR pictures starts a new coded video sequence.
+    pub(crate) fn is_bla(&self) -> bool {
+        (NAL_UNIT_TYPE_BLA_W_LP..NAL_UNIT_TYPE_IDR_W_RADL).contains(&self.nal_unit_type)
+    }
+
+    /// If this is a RADL or RASL picture, i.e., a leading picture.
+    pub(crate) fn is_leading(&self) -> bool {
+        (6..=NAL_UNIT_TYPE_RASL_R).contains(&self.nal_unit_type)
+    }
+
+    /// If this is a sub-layer non-reference picture, which pictures of the same sub-layer never reference.
+    pub(crate) fn is_sub_layer_non_reference(&self) -> bool {
+        self.nal_unit_type <= 14 && self.nal_unit_type.is_multiple_of(2)
+    }
+}
+
+/// `Ceil(Log2(x))`, the bits of indices into `x` entries.
+fn ceil_log2(x: usize) -> u32 {
+    x.next_power_of_two().trailing_zeros()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error;
+    use crate::error::{Error, Variant};
+    use crate::video::bitstream::{emulation_prevention, BitWriter};
+    use crate::video::h265::H265StreamInspector;
+    use crate::video::nal_units;
+
+    #[test]
+    fn parse_idr_slice_segment_header() -> Result<(), Error> {
+        let h265_data = include_bytes!("../../../tests/videos/single_64x48.h265");
+        let mut inspector = H265StreamInspector::new();
+
+        for nal in nal_units(h265_data) {
+            inspector.feed_nal(nal)?;
+        }
+
+        let slice = nal_units(h265_data).last().ok_or_else(|| error!(Variant::InvalidBitstream))?;
+        let header = inspector.slice_segment_header(slice)?;
+
+        assert!(header.is_idr());
+        assert!(header.is_irap());
+        assert!(header.first_slice_segment_in_pic_flag);
+        assert_eq!(header.slice_pic_order_cnt_lsb, 0);
+        assert_eq!(header.short_term_ref_pic_set.num_delta_pocs(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_slice_segment_header_with_rps() -> Result<(), Error> {
+        let h265_data = include_bytes!("../../../tests/videos/single_64x48.h265");
+        let mut inspector = H265StreamInspector::new();
+
+        // Parameter sets of the clip, its SPS has no short-term RPS and no long-term references.
+        for nal in nal_units(h265_data).take(3) {
+            inspector.feed_nal(nal)?;
+        }
+
+        let mut writer = BitWriter::new();
+
+        writer.flag(true); // first_slice_segment_in_pic_flag
+        writer.ue(0); // slice_pic_parameter_set_id
+        writer.ue(1); // slice_type
+        writer.u(8, 5); // slice_pic_order_cnt_lsb
+        writer.flag(false); // short_term_ref_pic_set_sps_flag
+        writer.ue(1); // num_negative_pics
+        writer.ue(1); // num_positive_pics
+        writer.ue(0); // delta_poc_s0_minus1
+        writer.flag(true); // used_by_curr_pic_s0_flag
+        writer.ue(2); // delta_poc_s1_minus1
+        writer.flag(false); // used_by_curr_pic_s1_flag
+        writer.flag(true); // slice_temporal_mvp_enabled_flag, never read
+
+        // TRAIL_R, TemporalId 0.
+        let mut nal = vec![0x00, 0x00, 0x01, 0x02, 0x01];
+        nal.extend(emulation_prevention(&writer.finish()));
+
+        let header = inspector.slice_segment_header(&nal)?;
+        let rps = header.short_term_ref_pic_set;
+
+        assert!(!header.is_irap());
+        assert!(!header.is_sub_layer_non_reference());
+        assert_eq!(header.slice_pic_order_cnt_lsb, 5);
+        assert_eq!((rps.num_negative_pics, rps.num_positive_pics), (1, 1));
+        assert_eq!((rps.delta_poc_s0[0], rps.used_by_curr_pic_s0[0]), (-1, true));
+        assert_eq!((rps.delta_poc_s1[0], rps.used_by_curr_pic_s1[0]), (3, false));
+        // 3 + 3 + 1 + 1 + 3 + 1 bits.
+        assert_eq!(header.num_bits_for_st_ref_pic_set_in_slice, 12);
+
+        Ok(())
+    }
+}
@@ -2,14 +2,51 @@
 
 #![allow(unused_imports)]
 
+mod bitstream;
+mod dpb;
+mod encodesession;
+mod encodesessionparameters;
+mod frame;
+mod framepool;
+#[cfg(feature = "test-utils")]
+mod golden;
+mod gop;
 pub mod h264;
+pub mod h265;
+mod profile;
+mod qualitylevel;
+mod query;
+mod ratecontrol;
+mod reorder;
+mod seek;
 mod session;
 mod sessionparameters;
 mod utils;
+#[cfg(feature = "y4m")]
+mod y4m;
 
-pub use session::VideoSession;
+pub use dpb::{DpbSlot, DpbSlotManager};
+pub use encodesession::VideoEncodeSession;
+pub use encodesessionparameters::VideoEncodeSessionParameters;
+pub use frame::{ColorDescription, Frame};
+pub use framepool::{FramePool, PooledImage};
+#[cfg(feature = "test-utils")]
+pub use golden::{FrameHash, Goldens};
+pub use gop::{FrameType, Gop, GopConfig, GopFrame};
+pub use profile::{StreamInspector, VideoProfileInfoBundle};
+pub use qualitylevel::QualityLevelProperties;
+pub use query::{EncodeFeedback, VideoQueryPool};
+pub use ratecontrol::{RateControlInfo, RateControlMode};
+pub use seek::{seek, SeekPoint};
+pub use session::{VideoSession, VideoSessionInfo};
 pub use sessionparameters::VideoSessionParameters;
-pub use utils::nal_units;
+pub use utils::{access_units, nal_units, nal_units_indexed};
+#[cfg(feature = "y4m")]
+pub use y4m::Y4mWriter;
 
+pub(crate) use dpb::ReferenceSlots;
+pub(crate) use encodesession::VideoEncodeSessionShared;
+pub(crate) use encodesessionparameters::VideoEncodeSessionParametersShared;
+pub(crate) use query::VideoQueryPoolShared;
 pub(crate) use session::VideoSessionShared;
 pub(crate) use sessionparameters::VideoSessionParametersShared;
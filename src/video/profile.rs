@@ -0,0 +1,92 @@
+use ash::vk::{
+    Format, VideoChromaSubsamplingFlagsKHR, VideoComponentBitDepthFlagsKHR, VideoDecodeH264ProfileInfoKHR, VideoDecodeH265ProfileInfoKHR,
+    VideoEncodeH264ProfileInfoKHR, VideoProfileInfoKHR, VideoProfileListInfoKHR,
+};
+use std::marker::PhantomPinned;
+use std::pin::Pin;
+
+/// Self-referential profile structs handed to Vulkan, only the codec struct matching `info.video_codec_operation` is linked.
+#[derive(Default)]
+pub struct VideoProfileInfoBundle<'a> {
+    pub(crate) info_h264: VideoDecodeH264ProfileInfoKHR<'a>,
+    pub(crate) info_h265: VideoDecodeH265ProfileInfoKHR<'a>,
+    pub(crate) info_h264_encode: VideoEncodeH264ProfileInfoKHR<'a>,
+    pub(crate) info: VideoProfileInfoKHR<'a>,
+    pub(crate) list: VideoProfileListInfoKHR<'a>,
+    _pinned: PhantomPinned,
+}
+
+impl VideoProfileInfoBundle<'_> {
+    /// Preferred multi-planar image format for the profile's chroma subsampling and bit depth, if there is one.
+    pub fn format(&self) -> Option<Format> {
+        use VideoChromaSubsamplingFlagsKHR as C;
+        use VideoComponentBitDepthFlagsKHR as B;
+
+        match (self.info.chroma_subsampling, self.info.luma_bit_depth) {
+            (C::TYPE_420, B::TYPE_8) => Some(Format::G8_B8R8_2PLANE_420_UNORM),
+            (C::TYPE_420, B::TYPE_10) => Some(Format::G10X6_B10X6R10X6_2PLANE_420_UNORM_3PACK16),
+            (C::TYPE_420, B::TYPE_12) => Some(Format::G12X4_B12X4R12X4_2PLANE_420_UNORM_3PACK16),
+            (C::TYPE_422, B::TYPE_8) => Some(Format::G8_B8R8_2PLANE_422_UNORM),
+            (C::TYPE_422, B::TYPE_10) => Some(Format::G10X6_B10X6R10X6_2PLANE_422_UNORM_3PACK16),
+            (C::TYPE_422, B::TYPE_12) => Some(Format::G12X4_B12X4R12X4_2PLANE_422_UNORM_3PACK16),
+            (C::TYPE_444, B::TYPE_8) => Some(Format::G8_B8R8_2PLANE_444_UNORM),
+            (C::TYPE_444, B::TYPE_10) => Some(Format::G10X6_B10X6R10X6_2PLANE_444_UNORM_3PACK16),
+            (C::TYPE_444, B::TYPE_12) => Some(Format::G12X4_B12X4R12X4_2PLANE_444_UNORM_3PACK16),
+            _ => None,
+        }
+    }
+}
+
+/// Converts a `chroma_format_idc` as found in parameter sets to Vulkan's flags.
+pub(crate) fn chroma_subsampling(chroma_format_idc: u8) -> VideoChromaSubsamplingFlagsKHR {
+    match chroma_format_idc {
+        0 => VideoChromaSubsamplingFlagsKHR::MONOCHROME,
+        1 => VideoChromaSubsamplingFlagsKHR::TYPE_420,
+        2 => VideoChromaSubsamplingFlagsKHR::TYPE_422,
+        3 => VideoChromaSubsamplingFlagsKHR::TYPE_444,
+        _ => VideoChromaSubsamplingFlagsKHR::INVALID,
+    }
+}
+
+/// Converts a bit depth as found in parameter sets to Vulkan's flags.
+pub(crate) fn component_bit_depth(bit_depth: u8) -> VideoComponentBitDepthFlagsKHR {
+    match bit_depth {
+        8 => VideoComponentBitDepthFlagsKHR::TYPE_8,
+        10 => VideoComponentBitDepthFlagsKHR::TYPE_10,
+        12 => VideoComponentBitDepthFlagsKHR::TYPE_12,
+        _ => VideoComponentBitDepthFlagsKHR::INVALID,
+    }
+}
+
+/// Something that inspected a video stream and knows which Vulkan video profile is needed to decode it.
+///
+/// For encoding, this is whatever describes the stream to be produced, e.g., [`H264EncodeInfo`](crate::video::h264::H264EncodeInfo).
+pub trait StreamInspector {
+    fn profiles<'f>(&self) -> Pin<Box<VideoProfileInfoBundle<'f>>>;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::video::profile::{chroma_subsampling, component_bit_depth};
+    use crate::video::VideoProfileInfoBundle;
+    use ash::vk::{Format, VideoChromaSubsamplingFlagsKHR};
+
+    #[test]
+    fn format_from_bit_depth() {
+        let mut profiles = VideoProfileInfoBundle::default();
+        profiles.info.chroma_subsampling = VideoChromaSubsamplingFlagsKHR::TYPE_420;
+
+        profiles.info.luma_bit_depth = component_bit_depth(8);
+        assert_eq!(profiles.format(), Some(Format::G8_B8R8_2PLANE_420_UNORM));
+
+        profiles.info.luma_bit_depth = component_bit_depth(10);
+        assert_eq!(profiles.format(), Some(Format::G10X6_B10X6R10X6_2PLANE_420_UNORM_3PACK16));
+
+        profiles.info.luma_bit_depth = component_bit_depth(9);
+        assert_eq!(profiles.format(), None);
+
+        profiles.info.chroma_subsampling = chroma_subsampling(2);
+        profiles.info.luma_bit_depth = component_bit_depth(10);
+        assert_eq!(profiles.format(), Some(Format::G10X6_B10X6R10X6_2PLANE_422_UNORM_3PACK16));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::video::{GopConfig, RateControlInfo, RateControlMode};
+use ash::vk::{VideoEncodeH264QualityLevelPropertiesKHR, VideoEncodeRateControlModeFlagsKHR};
+
+/// Settings an implementation prefers at one encode quality level.
+///
+/// Implementations differ a lot in what they do by default, these are their suggestions for getting the
+/// best results at a level. For the GOP structure and frame counts, 0 means unlimited.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct QualityLevelProperties {
+    quality_level: u32,
+    rate_control_mode: RateControlMode,
+    gop_frame_count: u32,
+    idr_period: u32,
+    b_frames: u32,
+    constant_qp: i32,
+    max_l0_references: u32,
+    max_l1_references: u32,
+    cabac: bool,
+}
+
+impl QualityLevelProperties {
+    pub(crate) fn new(
+        quality_level: u32,
+        rate_control_mode: VideoEncodeRateControlModeFlagsKHR,
+        properties_h264: &VideoEncodeH264QualityLevelPropertiesKHR,
+    ) -> Self {
+        Self {
+            quality_level,
+            rate_control_mode: RateControlMode::from_native(rate_control_mode),
+            gop_frame_count: properties_h264.preferred_gop_frame_count,
+            idr_period: properties_h264.preferred_idr_period,
+            b_frames: properties_h264.preferred_consecutive_b_frame_count,
+            constant_qp: properties_h264.preferred_constant_qp.qp_p,
+            max_l0_references: properties_h264.preferred_max_l0_reference_count,
+            max_l1_references: properties_h264.preferred_max_l1_reference_count,
+            cabac: properties_h264.preferred_std_entropy_coding_mode_flag != 0,
+        }
+    }
+
+    /// The level these properties are for, see [`VideoSessionInfo::quality_level`](crate::video::VideoSessionInfo::quality_level).
+    pub fn quality_level(&self) -> u32 {
+        self.quality_level
+    }
+
+    pub fn rate_control_mode(&self) -> RateControlMode {
+        self.rate_control_mode
+    }
+
+    /// Distance between intra pictures.
+    pub fn gop_frame_count(&self) -> u32 {
+        self.gop_frame_count
+    }
+
+    /// Distance between IDR pictures.
+    pub fn idr_period(&self) -> u32 {
+        self.idr_period
+    }
+
+    /// Number of consecutive B-frames.
+    pub fn b_frames(&self) -> u32 {
+        self.b_frames
+    }
+
+    /// QP of P-frames when rate control is disabled.
+    pub fn constant_qp(&self) -> i32 {
+        self.constant_qp
+    }
+
+    pub fn max_l0_references(&self) -> u32 {
+        self.max_l0_references
+    }
+
+    pub fn max_l1_references(&self) -> u32 {
+        self.max_l1_references
+    }
+
+    /// If CABAC is preferred over CAVLC entropy coding.
+    pub fn cabac(&self) -> bool {
+        self.cabac
+    }
+
+    /// A GOP structure following these preferences.
+    pub fn gop_config(&self) -> GopConfig {
+        let b_frames = match self.max_l1_references {
+            0 => 0,
+            _ => self.b_frames,
+        };
+
+        GopConfig::new()
+            .idr_period(self.idr_period)
+            .b_frames(b_frames)
+            .max_references(self.max_l0_references.max(1))
+    }
+
+    /// Rate control in the preferred mode, with default bitrates.
+    pub fn rate_control(&self) -> RateControlInfo {
+        RateControlInfo::new().mode(self.rate_control_mode).qp(self.constant_qp)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::video::qualitylevel::QualityLevelProperties;
+    use crate::video::RateControlMode;
+    use ash::vk::{VideoEncodeH264QpKHR, VideoEncodeH264QualityLevelPropertiesKHR, VideoEncodeRateControlModeFlagsKHR};
+
+    #[test]
+    fn preferred_settings() {
+        let properties_h264 = VideoEncodeH264QualityLevelPropertiesKHR::default()
+            .preferred_idr_period(30)
+            .preferred_consecutive_b_frame_count(2)
+            .preferred_constant_qp(VideoEncodeH264QpKHR {
+                qp_i: 20,
+                qp_p: 24,
+                qp_b: 28,
+            })
+            .preferred_max_l0_reference_count(0)
+            .preferred_max_l1_reference_count(0);
+
+        let quality_level = QualityLevelProperties::new(1, VideoEncodeRateControlModeFlagsKHR::DISABLED, &properties_h264);
+        let gop_config = quality_level.gop_config();
+        let rate_control = quality_level.rate_control();
+
+        assert_eq!(quality_level.quality_level(), 1);
+        assert_eq!(gop_config.get_idr_period(), 30);
+        assert_eq!(gop_config.get_b_frames(), 0);
+        assert_eq!(gop_config.get_max_references(), 1);
+        assert_eq!(rate_control.get_mode(), RateControlMode::ConstantQp);
+        assert_eq!(rate_control.constant_qp(), 24);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::device::{Device, DeviceShared};
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::video::StreamInspector;
+use ash::vk::{
+    QueryPool, QueryPoolCreateInfo, QueryPoolVideoEncodeFeedbackCreateInfoKHR, QueryResultFlags, QueryResultStatusKHR, QueryType,
+    VideoEncodeFeedbackFlagsKHR,
+};
+use std::ptr::addr_of;
+use std::sync::Arc;
+
+pub(crate) struct VideoQueryPoolShared {
+    shared_device: Arc<DeviceShared>,
+    native_query_pool: QueryPool,
+    query_type: QueryType,
+    count: u32,
+}
+
+impl VideoQueryPoolShared {
+    pub fn new(device: &Device, stream_inspector: &impl StreamInspector, query_type: QueryType, count: u32) -> Result<Self, Error> {
+        let shared_device = device.shared();
+        let native_device = shared_device.native();
+        let profiles = stream_inspector.profiles();
+
+        let mut create_info = QueryPoolCreateInfo::default().query_type(query_type).query_count(count);
+        let mut feedback_create_info = QueryPoolVideoEncodeFeedbackCreateInfoKHR::default().encode_feedback_flags(
+            VideoEncodeFeedbackFlagsKHR::BITSTREAM_BUFFER_OFFSET | VideoEncodeFeedbackFlagsKHR::BITSTREAM_BYTES_WRITTEN,
+        );
+
+        // Video queries are tied to the profile they are used with.
+        match query_type {
+            QueryType::VIDEO_ENCODE_FEEDBACK_KHR => {
+                feedback_create_info.p_next = addr_of!(profiles.info).cast();
+                create_info.p_next = addr_of!(feedback_create_info).cast();
+            }
+            _ => create_info.p_next = addr_of!(profiles.info).cast(),
+        }
+
+        unsafe {
+            let native_query_pool = native_device.create_query_pool(&create_info, None)?;
+
+            Ok(Self {
+                shared_device,
+                native_query_pool,
+                query_type,
+                count,
+            })
+        }
+    }
+
+    pub(crate) fn native(&self) -> QueryPool {
+        self.native_query_pool
+    }
+}
+
+impl Drop for VideoQueryPoolShared {
+    fn drop(&mut self) {
+        let native_device = self.shared_device.native();
+
+        unsafe {
+            native_device.destroy_query_pool(self.native_query_pool, None);
+        }
+    }
+}
+
+/// Where an encode operation wrote its bitstream, as reported by an encode feedback query.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EncodeFeedback {
+    offset: u32,
+    bytes_written: u32,
+    status: QueryResultStatusKHR,
+}
+
+impl EncodeFeedback {
+    /// Offset of the bitstream, relative to the start of the range given in [`EncodeInfo`](crate::ops::EncodeInfo).
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Number of bitstream bytes written.
+    pub fn bytes_written(&self) -> u32 {
+        self.bytes_written
+    }
+
+    /// Result status of the encode, offset and size are only meaningful if [`QueryResultStatusKHR::COMPLETE`].
+    pub fn status(&self) -> QueryResultStatusKHR {
+        self.status
+    }
+}
+
+/// Queries that report whether video operations (e.g., a [`DecodeH264`](crate::ops::DecodeH264)) succeeded, or
+/// how much data encode operations produced.
+pub struct VideoQueryPool {
+    shared: Arc<VideoQueryPoolShared>,
+}
+
+impl VideoQueryPool {
+    /// Creates `count` result status queries for streams with the profile of `stream_inspector`.
+    pub fn new(device: &Device, stream_inspector: &impl StreamInspector, count: u32) -> Result<Self, Error> {
+        let shared = VideoQueryPoolShared::new(device, stream_inspector, QueryType::RESULT_STATUS_ONLY_KHR, count)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    /// Creates `count` encode feedback queries for streams encoded with the profile of `stream_inspector`.
+    ///
+    /// Besides the result status, these report the offset and size of the encoded bitstream, see [`Self::encode_feedback`].
+    pub fn new_encode_feedback(device: &Device, stream_inspector: &impl StreamInspector, count: u32) -> Result<Self, Error> {
+        let shared = VideoQueryPoolShared::new(device, stream_inspector, QueryType::VIDEO_ENCODE_FEEDBACK_KHR, count)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    pub fn count(&self) -> u32 {
+        self.shared.count
+    }
+
+    /// Status of all queries, [`QueryResultStatusKHR::NOT_READY`] for those that did not complete (or were never used).
+    pub fn results(&self) -> Result<Vec<QueryResultStatusKHR>, Error> {
+        match self.shared.query_type {
+            QueryType::VIDEO_ENCODE_FEEDBACK_KHR => Ok(self.encode_feedback()?.iter().map(|x| x.status).collect()),
+            _ => {
+                let mut results = vec![0i32; self.shared.count as usize];
+                self.query_results(&mut results)?;
+                Ok(results.into_iter().map(QueryResultStatusKHR::from_raw).collect())
+            }
+        }
+    }
+
+    /// Bitstream offset and size of all queries, only available for pools created with [`Self::new_encode_feedback`].
+    pub fn encode_feedback(&self) -> Result<Vec<EncodeFeedback>, Error> {
+        if self.shared.query_type != QueryType::VIDEO_ENCODE_FEEDBACK_KHR {
+            return Err(error!(Variant::FeatureNotSupported, "Not an encode feedback query pool."));
+        }
+
+        // Feedback values in the order of their flag bits, followed by the status.
+        let mut results = vec![[0u32; 3]; self.shared.count as usize];
+        self.query_results(&mut results)?;
+
+        Ok(results
+            .into_iter()
+            .map(|[offset, bytes_written, status]| EncodeFeedback {
+                offset,
+                bytes_written,
+                status: QueryResultStatusKHR::from_raw(status as i32),
+            })
+            .collect())
+    }
+
+    fn query_results<T: Copy>(&self, results: &mut [T]) -> Result<(), Error> {
+        let native_device = self.shared.shared_device.native();
+
+        unsafe {
+            // Without `WAIT` unfinished queries report `NOT_READY`, which is what we want.
+            let rval = native_device.get_query_pool_results(self.shared.native_query_pool, 0, results, QueryResultFlags::WITH_STATUS_KHR);
+
+            match rval {
+                Ok(()) | Err(ash::vk::Result::NOT_READY) => Ok(()),
+                Err(e) => Err(e.into()),
+            }
+        }
+    }
+
+    pub(crate) fn shared(&self) -> Arc<VideoQueryPoolShared> {
+        self.shared.clone()
+    }
+
+    /// The raw `VkQueryPool`.
+    pub fn raw(&self) -> QueryPool {
+        self.shared.native()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::device::Device;
+    use crate::error::Error;
+    use crate::instance::{Instance, InstanceInfo};
+    use crate::physicaldevice::PhysicalDevice;
+    use crate::video::h264::H264StreamInspector;
+    use crate::video::VideoQueryPool;
+    use ash::vk::QueryResultStatusKHR;
+
+    #[test]
+    #[cfg(not(miri))]
+    fn create_query_pool() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let h264inspector = H264StreamInspector::new();
+
+        let queries = VideoQueryPool::new(&device, &h264inspector, 4)?;
+
+        assert_eq!(queries.count(), 4);
+        assert!(queries.results()?.iter().all(|x| *x != QueryResultStatusKHR::ERROR));
+        assert!(queries.encode_feedback().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::video::encodesession::VideoEncodeCapabilities;
+use ash::vk::{VideoEncodeRateControlInfoKHR, VideoEncodeRateControlLayerInfoKHR, VideoEncodeRateControlModeFlagsKHR};
+
+/// How an encoder distributes bits over frames.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum RateControlMode {
+    /// Whatever the implementation does by default.
+    #[default]
+    Default,
+    /// No rate control, every frame is encoded with the same QP.
+    ConstantQp,
+    /// Constant bitrate, the average bitrate is also the peak bitrate.
+    Cbr,
+    /// Variable bitrate, with the given average and peak bitrates.
+    Vbr,
+}
+
+impl RateControlMode {
+    pub(crate) fn native(&self) -> VideoEncodeRateControlModeFlagsKHR {
+        match self {
+            Self::Default => VideoEncodeRateControlModeFlagsKHR::DEFAULT,
+            Self::ConstantQp => VideoEncodeRateControlModeFlagsKHR::DISABLED,
+            Self::Cbr => VideoEncodeRateControlModeFlagsKHR::CBR,
+            Self::Vbr => VideoEncodeRateControlModeFlagsKHR::VBR,
+        }
+    }
+
+    pub(crate) fn from_native(native: VideoEncodeRateControlModeFlagsKHR) -> Self {
+        match native {
+            VideoEncodeRateControlModeFlagsKHR::DISABLED => Self::ConstantQp,
+            VideoEncodeRateControlModeFlagsKHR::CBR => Self::Cbr,
+            VideoEncodeRateControlModeFlagsKHR::VBR => Self::Vbr,
+            _ => Self::Default,
+        }
+    }
+}
+
+/// Specifies the rate control of an encode, see [`EncodeH264::rate_control`](crate::ops::EncodeH264::rate_control).
+///
+/// Bitrates are in bits per second. The virtual buffer (VBV) sizes are given in milliseconds of data at the average
+/// bitrate, as Vulkan expects them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateControlInfo {
+    mode: RateControlMode,
+    qp: i32,
+    bitrate: u64,
+    peak_bitrate: u64,
+    vbv_buffer_size: u32,
+    vbv_initial_fullness: u32,
+    frame_rate: (u32, u32),
+}
+
+impl Default for RateControlInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateControlInfo {
+    pub fn new() -> Self {
+        Self {
+            mode: RateControlMode::Default,
+            qp: 26,
+            bitrate: 5_000_000,
+            peak_bitrate: 5_000_000,
+            vbv_buffer_size: 1000,
+            vbv_initial_fullness: 500,
+            frame_rate: (30, 1),
+        }
+    }
+
+    /// Constant QP mode, encoding every frame with `qp`.
+    pub fn cqp(qp: i32) -> Self {
+        Self::new().mode(RateControlMode::ConstantQp).qp(qp)
+    }
+
+    /// Constant bitrate mode with `bitrate`.
+    pub fn cbr(bitrate: u64) -> Self {
+        Self::new().mode(RateControlMode::Cbr).bitrate(bitrate).peak_bitrate(bitrate)
+    }
+
+    /// Variable bitrate mode with an average `bitrate` that may peak at `peak_bitrate`.
+    pub fn vbr(bitrate: u64, peak_bitrate: u64) -> Self {
+        Self::new().mode(RateControlMode::Vbr).bitrate(bitrate).peak_bitrate(peak_bitrate)
+    }
+
+    pub fn mode(mut self, mode: RateControlMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn get_mode(&self) -> RateControlMode {
+        self.mode
+    }
+
+    /// QP used in [`RateControlMode::ConstantQp`], ignored otherwise.
+    pub fn qp(mut self, qp: i32) -> Self {
+        self.qp = qp;
+        self
+    }
+
+    pub fn get_qp(&self) -> i32 {
+        self.qp
+    }
+
+    /// Average bitrate.
+    pub fn bitrate(mut self, bitrate: u64) -> Self {
+        self.bitrate = bitrate;
+        self
+    }
+
+    pub fn get_bitrate(&self) -> u64 {
+        self.bitrate
+    }
+
+    /// Peak bitrate, only used in [`RateControlMode::Vbr`] (CBR peaks at the average bitrate).
+    pub fn peak_bitrate(mut self, peak_bitrate: u64) -> Self {
+        self.peak_bitrate = peak_bitrate;
+        self
+    }
+
+    pub fn get_peak_bitrate(&self) -> u64 {
+        self.peak_bitrate
+    }
+
+    /// Size of the virtual buffer in milliseconds.
+    pub fn vbv_buffer_size(mut self, milliseconds: u32) -> Self {
+        self.vbv_buffer_size = milliseconds;
+        self
+    }
+
+    pub fn get_vbv_buffer_size(&self) -> u32 {
+        self.vbv_buffer_size
+    }
+
+    /// Initial fullness of the virtual buffer in milliseconds, must not exceed its size.
+    pub fn vbv_initial_fullness(mut self, milliseconds: u32) -> Self {
+        self.vbv_initial_fullness = milliseconds;
+        self
+    }
+
+    pub fn get_vbv_initial_fullness(&self) -> u32 {
+        self.vbv_initial_fullness
+    }
+
+    /// Frame rate as `numerator / denominator` frames per second, which the rate control budgets bits for.
+    pub fn frame_rate(mut self, numerator: u32, denominator: u32) -> Self {
+        self.frame_rate = (numerator, denominator);
+        self
+    }
+
+    pub fn get_frame_rate(&self) -> (u32, u32) {
+        self.frame_rate
+    }
+
+    /// The `constantQp` of slices, which must be 0 unless rate control is disabled.
+    pub(crate) fn constant_qp(&self) -> i32 {
+        match self.mode {
+            RateControlMode::ConstantQp => self.qp,
+            _ => 0,
+        }
+    }
+
+    /// Layers of `VideoEncodeRateControlInfoKHR`, only CBR and VBR have (exactly) one.
+    pub(crate) fn native_layers(&self) -> Vec<VideoEncodeRateControlLayerInfoKHR<'static>> {
+        let peak_bitrate = match self.mode {
+            RateControlMode::Cbr => self.bitrate,
+            _ => self.peak_bitrate,
+        };
+
+        match self.mode {
+            RateControlMode::Cbr | RateControlMode::Vbr => vec![VideoEncodeRateControlLayerInfoKHR::default()
+                .average_bitrate(self.bitrate)
+                .max_bitrate(peak_bitrate)
+                .frame_rate_numerator(self.frame_rate.0)
+                .frame_rate_denominator(self.frame_rate.1)],
+            RateControlMode::Default | RateControlMode::ConstantQp => Vec::new(),
+        }
+    }
+
+    /// The `VideoEncodeRateControlInfoKHR` for this, with `layers` from [`Self::native_layers`].
+    pub(crate) fn native<'a>(&self, layers: &'a [VideoEncodeRateControlLayerInfoKHR<'a>]) -> VideoEncodeRateControlInfoKHR<'a> {
+        let info = VideoEncodeRateControlInfoKHR::default()
+            .rate_control_mode(self.mode.native())
+            .layers(layers);
+
+        match self.mode {
+            RateControlMode::Cbr | RateControlMode::Vbr => info
+                .virtual_buffer_size_in_ms(self.vbv_buffer_size)
+                .initial_virtual_buffer_size_in_ms(self.vbv_initial_fullness),
+            RateControlMode::Default | RateControlMode::ConstantQp => info,
+        }
+    }
+
+    /// Checks this can be used with a session having `capabilities`.
+    pub(crate) fn validate(&self, capabilities: &VideoEncodeCapabilities) -> Result<(), Error> {
+        let bitrate_mode = matches!(self.mode, RateControlMode::Cbr | RateControlMode::Vbr);
+
+        if self.mode != RateControlMode::Default && !capabilities.rate_control_modes().contains(self.mode.native()) {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "Rate control mode {:?} not supported.", self.mode
+            ));
+        }
+
+        if self.mode == RateControlMode::ConstantQp && !(capabilities.min_qp()..=capabilities.max_qp()).contains(&self.qp) {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "QP {} not in supported range {}..={}.",
+                self.qp,
+                capabilities.min_qp(),
+                capabilities.max_qp()
+            ));
+        }
+
+        if bitrate_mode && (self.bitrate == 0 || self.frame_rate.0 == 0 || self.frame_rate.1 == 0) {
+            return Err(error!(Variant::FeatureNotSupported, "Bitrate and frame rate must not be 0."));
+        }
+
+        if self.mode == RateControlMode::Vbr && self.peak_bitrate < self.bitrate {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "Peak bitrate must be at least the average bitrate."
+            ));
+        }
+
+        let peak_bitrate = match self.mode {
+            RateControlMode::Cbr => self.bitrate,
+            _ => self.peak_bitrate.max(self.bitrate),
+        };
+
+        if bitrate_mode && peak_bitrate > capabilities.max_bitrate() {
+            return Err(error!(
+                Variant::FeatureNotSupported,
+                "Bitrate exceeds maximum of {} bits/s.",
+                capabilities.max_bitrate()
+            ));
+        }
+
+        if bitrate_mode && self.vbv_initial_fullness > self.vbv_buffer_size {
+            return Err(error!(Variant::FeatureNotSupported, "Initial VBV fullness exceeds VBV size."));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::video::encodesession::VideoEncodeCapabilities;
+    use crate::video::{RateControlInfo, RateControlMode};
+    use ash::vk::VideoEncodeRateControlModeFlagsKHR;
+
+    fn capabilities() -> VideoEncodeCapabilities {
+        VideoEncodeCapabilities::new(
+            VideoEncodeRateControlModeFlagsKHR::DISABLED | VideoEncodeRateControlModeFlagsKHR::CBR,
+            10_000_000,
+            0..=51,
+        )
+    }
+
+    #[test]
+    fn native_layers() {
+        let cbr = RateControlInfo::cbr(1_000_000).peak_bitrate(2_000_000);
+        let vbr = RateControlInfo::vbr(1_000_000, 2_000_000).frame_rate(60, 1);
+
+        assert_eq!(cbr.native_layers()[0].max_bitrate, 1_000_000);
+        assert_eq!(vbr.native_layers()[0].max_bitrate, 2_000_000);
+        assert_eq!(vbr.native_layers()[0].frame_rate_numerator, 60);
+        assert!(RateControlInfo::cqp(20).native_layers().is_empty());
+        assert_eq!(RateControlInfo::cqp(20).constant_qp(), 20);
+        assert_eq!(RateControlInfo::cbr(1_000_000).constant_qp(), 0);
+    }
+
+    #[test]
+    fn validate_against_capabilities() {
+        let capabilities = capabilities();
+
+        assert!(RateControlInfo::new().validate(&capabilities).is_ok());
+        assert!(RateControlInfo::cqp(30).validate(&capabilities).is_ok());
+        assert!(RateControlInfo::cbr(5_000_000).validate(&capabilities).is_ok());
+        assert!(RateControlInfo::cqp(60).validate(&capabilities).is_err());
+        assert!(RateControlInfo::cbr(20_000_000).validate(&capabilities).is_err());
+        assert!(RateControlInfo::vbr(1_000_000, 2_000_000).validate(&capabilities).is_err());
+        assert!(RateControlInfo::cbr(1_000_000)
+            .vbv_buffer_size(100)
+            .validate(&capabilities)
+            .is_err());
+        assert_eq!(RateControlInfo::new().get_mode(), RateControlMode::Default);
+    }
+}
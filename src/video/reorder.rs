@@ -0,0 +1,63 @@
+/// Holds decoded pictures back until they can be output in display order (C.4.5.3).
+///
+/// Pictures are pushed in decode order with their picture order count, and released lowest count first once more
+/// than `max_num_reorder_frames` are waiting.
+#[derive(Debug, Clone)]
+pub(crate) struct ReorderBuffer<T> {
+    max_num_reorder_frames: usize,
+    pending: Vec<(i32, T)>,
+}
+
+impl<T> ReorderBuffer<T> {
+    pub fn new(max_num_reorder_frames: u32) -> Self {
+        Self {
+            max_num_reorder_frames: max_num_reorder_frames as usize,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn set_max_num_reorder_frames(&mut self, max_num_reorder_frames: u32) {
+        self.max_num_reorder_frames = max_num_reorder_frames as usize;
+    }
+
+    /// Adds a picture, returning the pictures that can be output now in display order.
+    pub fn push(&mut self, pic_order_cnt: i32, picture: T) -> Vec<T> {
+        // Pictures with the same order count keep their decode order.
+        let position = self.pending.partition_point(|(x, _)| *x <= pic_order_cnt);
+        let excess = (self.pending.len() + 1).saturating_sub(self.max_num_reorder_frames);
+
+        self.pending.insert(position, (pic_order_cnt, picture));
+        self.pending.drain(..excess).map(|(_, x)| x).collect()
+    }
+
+    /// Returns all pictures held back in display order, e.g., at the end of the stream or before an IDR picture.
+    pub fn flush(&mut self) -> Vec<T> {
+        self.pending.drain(..).map(|(_, x)| x).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ReorderBuffer;
+
+    #[test]
+    fn outputs_in_display_order() {
+        let mut buffer = ReorderBuffer::new(2);
+
+        // I0 P6 B2 B4 in decode order.
+        assert!(buffer.push(0, 'I').is_empty());
+        assert!(buffer.push(6, 'P').is_empty());
+        assert_eq!(buffer.push(2, 'b'), ['I']);
+        assert_eq!(buffer.push(4, 'B'), ['b']);
+        assert_eq!(buffer.flush(), ['B', 'P']);
+        assert!(buffer.flush().is_empty());
+    }
+
+    #[test]
+    fn no_reordering() {
+        let mut buffer = ReorderBuffer::new(0);
+
+        assert_eq!(buffer.push(4, 1), [1]);
+        assert_eq!(buffer.push(0, 2), [2]);
+    }
+}
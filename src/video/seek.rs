@@ -0,0 +1,121 @@
+use crate::video::bitstream::{rbsp, BitReader};
+use crate::video::utils::{nal_header, offset_in};
+use crate::video::{access_units, nal_units};
+
+const NAL_UNIT_TYPE_SLICE_IDR: u8 = 5;
+const NAL_UNIT_TYPE_SPS: u8 = 7;
+const NAL_UNIT_TYPE_PPS: u8 = 8;
+
+/// A position in a H.264 stream decoding can start from, see [`seek`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeekPoint<'a> {
+    offset: usize,
+    parameter_sets: Vec<&'a [u8]>,
+}
+
+impl<'a> SeekPoint<'a> {
+    /// Byte offset of the IDR access unit within the stream.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// SPS and PPS NAL units active at this point, SPS first.
+    ///
+    /// Only parameter sets preceding the access unit are returned, the access unit itself may bring more.
+    /// Feed these to the decoder before the access unit at [`offset`](Self::offset).
+    pub fn parameter_sets(&self) -> &[&'a [u8]] {
+        &self.parameter_sets
+    }
+}
+
+/// Finds the first IDR access unit of a H.264 stream starting at or after byte `position`.
+///
+/// Decoding can only start at IDR pictures, as everything else may reference pictures before it. Since
+/// parameter sets are usually only sent once, this also collects the latest SPS and PPS (per id) seen before.
+///
+/// ```rust,ignore
+/// let seek_point = seek(h264_data, position).ok_or(..)?;
+///
+/// for nal in seek_point.parameter_sets() {
+///     stream_inspector.feed_nal(nal)?;
+/// }
+///
+/// for access_unit in access_units(&h264_data[seek_point.offset()..]) {
+///     // decode ...
+/// }
+/// ```
+pub fn seek(stream: &[u8], position: usize) -> Option<SeekPoint<'_>> {
+    let mut sps = Vec::new();
+    let mut pps = Vec::new();
+
+    for access_unit in access_units(stream) {
+        let offset = offset_in(stream, access_unit);
+        let is_idr = nal_units(access_unit).any(|x| nal_unit_type(x) == Some(NAL_UNIT_TYPE_SLICE_IDR));
+
+        if is_idr && offset >= position {
+            let parameter_sets = sps.into_iter().chain(pps).map(|(_, nal)| nal).collect();
+
+            return Some(SeekPoint { offset, parameter_sets });
+        }
+
+        for nal in nal_units(access_unit) {
+            match nal_unit_type(nal) {
+                Some(NAL_UNIT_TYPE_SPS) => replace_by_id(&mut sps, nal, 24),
+                Some(NAL_UNIT_TYPE_PPS) => replace_by_id(&mut pps, nal, 0),
+                _ => {}
+            }
+        }
+    }
+
+    None
+}
+
+fn nal_unit_type(nal: &[u8]) -> Option<u8> {
+    nal_header(nal).map(|(nal_unit_type, _)| nal_unit_type)
+}
+
+/// Stores a parameter set, replacing an earlier one with the same id. The id is a `ue(v)` at bit `id_position`.
+fn replace_by_id<'a>(sets: &mut Vec<(u32, &'a [u8])>, nal: &'a [u8], id_position: usize) {
+    let Some((_, payload)) = nal_header(nal) else {
+        return;
+    };
+
+    let payload = rbsp(payload);
+    let mut reader = BitReader::new(&payload);
+
+    // Damaged parameter sets are ignored, decoding would fail with them anyway.
+    let Ok(id) = reader.skip(id_position).and_then(|_| reader.ue()) else {
+        return;
+    };
+
+    sets.retain(|(x, _)| *x != id);
+    sets.push((id, nal));
+}
+
+#[cfg(test)]
+mod test {
+    use super::seek;
+
+    #[test]
+    fn seeks_to_idr() {
+        let sps = [0, 0, 1, 0x67, 0x42, 0x00, 0x0a, 0xf4];
+        let sps_1 = [0, 0, 1, 0x67, 0x42, 0x00, 0x0a, 0x40];
+        let pps = [0, 0, 1, 0x68, 0xce];
+        let pps_new = [0, 0, 1, 0x68, 0xce, 0x01];
+        let idr = [0, 0, 1, 0x65, 0x88, 0x84];
+        let p = [0, 0, 1, 0x41, 0x9a, 0x02];
+
+        let stream = [&sps[..], &pps, &idr, &p, &sps_1, &pps_new, &p, &idr, &p].concat();
+        let second_idr = stream.len() - idr.len() - p.len();
+
+        let seek_point = seek(&stream, 0).unwrap();
+        assert_eq!(seek_point.offset(), 0);
+        assert!(seek_point.parameter_sets().is_empty());
+
+        let seek_point = seek(&stream, 1).unwrap();
+        assert_eq!(seek_point.offset(), second_idr);
+        assert_eq!(seek_point.parameter_sets(), [&sps[..], &sps_1, &pps_new]);
+
+        assert!(seek(&stream, second_idr + 1).is_none());
+    }
+}
@@ -1,35 +1,299 @@
-use crate::allocation::{Allocation, MemoryTypeIndex};
+use crate::allocation::Allocation;
+use crate::allocator::{Allocator, DedicatedAllocator};
 use crate::device::{Device, DeviceShared};
 use crate::error;
 use crate::error::{Error, Variant};
-use crate::video::h264::H264StreamInspector;
+use crate::resources::MemoryRequirements;
+use crate::video::{StreamInspector, VideoProfileInfoBundle};
 use ash::khr::{
     video_decode_queue::DeviceFn as KhrVideoDecodeQueueDeviceFn,
     video_queue::{DeviceFn as KhrVideoQueueDeviceFn, InstanceFn as KhrVideoQueueInstanceFn},
 };
-use ash::vk::native::{StdVideoH264ProfileIdc, StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_BASELINE};
 use ash::vk::{
-    self, BindVideoSessionMemoryInfoKHR, ExtensionProperties, Extent2D, Format, ImageUsageFlags, PhysicalDeviceVideoFormatInfoKHR,
-    VideoCapabilitiesKHR, VideoChromaSubsamplingFlagsKHR, VideoCodecOperationFlagsKHR, VideoComponentBitDepthFlagsKHR,
+    self, BindVideoSessionMemoryInfoKHR, ExtensionProperties, Extent2D, Format, ImageTiling, ImageUsageFlags, MemoryPropertyFlags,
+    Offset2D, PhysicalDeviceVideoFormatInfoKHR, VideoCapabilitiesKHR, VideoCapabilityFlagsKHR, VideoCodecOperationFlagsKHR,
     VideoDecodeCapabilitiesKHR, VideoDecodeCapabilityFlagsKHR, VideoDecodeH264CapabilitiesKHR, VideoDecodeH264PictureLayoutFlagsKHR,
-    VideoDecodeH264ProfileInfoKHR, VideoFormatPropertiesKHR, VideoProfileInfoKHR, VideoProfileListInfoKHR, VideoSessionCreateFlagsKHR,
+    VideoDecodeH265CapabilitiesKHR, VideoFormatPropertiesKHR, VideoProfileInfoKHR, VideoProfileListInfoKHR, VideoSessionCreateFlagsKHR,
     VideoSessionCreateInfoKHR, VideoSessionKHR, VideoSessionMemoryRequirementsKHR,
 };
+use std::fmt::{Debug, Formatter};
 use std::ptr::{null, null_mut};
 use std::sync::Arc;
 
 pub(crate) struct VideoDecodeCapabilities {
     flags: VideoDecodeCapabilityFlagsKHR,
+    field_offset_granularity: Offset2D,
 }
 impl From<VideoDecodeCapabilitiesKHR<'_>> for VideoDecodeCapabilities {
     fn from(value: VideoDecodeCapabilitiesKHR) -> Self {
-        Self { flags: value.flags }
+        Self {
+            flags: value.flags,
+            field_offset_granularity: Offset2D::default(),
+        }
     }
 }
 impl VideoDecodeCapabilities {
     pub(crate) fn flags(&self) -> VideoDecodeCapabilityFlagsKHR {
         self.flags
     }
+
+    /// Alignment of the bottom field's offset for H.264 `INTERLACED_SEPARATE_PLANES` layouts.
+    pub(crate) fn field_offset_granularity(&self) -> Offset2D {
+        self.field_offset_granularity
+    }
+}
+
+/// Specifies how to create a [`VideoSession`].
+///
+/// Defaults to 512x512 content with 17 DPB slots and up to 16 active references. Unless given, picture and
+/// reference formats are chosen among those the device supports for the stream's profile.
+#[derive(Clone)]
+pub struct VideoSessionInfo {
+    max_coded_extent: Extent2D,
+    max_dpb_slots: u32,
+    max_active_reference_pictures: u32,
+    picture_format: Option<Format>,
+    reference_picture_format: Option<Format>,
+    flags: VideoSessionCreateFlagsKHR,
+    quality_level: u32,
+    allocator: Option<Arc<dyn Allocator>>,
+}
+
+impl Debug for VideoSessionInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VideoSessionInfo")
+            .field("max_coded_extent", &self.max_coded_extent)
+            .field("max_dpb_slots", &self.max_dpb_slots)
+            .field("max_active_reference_pictures", &self.max_active_reference_pictures)
+            .field("picture_format", &self.picture_format)
+            .field("reference_picture_format", &self.reference_picture_format)
+            .field("flags", &self.flags)
+            .field("quality_level", &self.quality_level)
+            .field("allocator", &self.allocator.is_some())
+            .finish()
+    }
+}
+
+impl Default for VideoSessionInfo {
+    fn default() -> Self {
+        Self {
+            max_coded_extent: Extent2D { width: 512, height: 512 },
+            max_dpb_slots: 17,
+            max_active_reference_pictures: 16,
+            picture_format: None,
+            reference_picture_format: None,
+            flags: VideoSessionCreateFlagsKHR::empty(),
+            quality_level: 0,
+            allocator: None,
+        }
+    }
+}
+
+impl VideoSessionInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Largest picture size this session will decode.
+    pub fn max_coded_extent(mut self, max_coded_extent: Extent2D) -> Self {
+        self.max_coded_extent = max_coded_extent;
+        self
+    }
+
+    pub fn get_max_coded_extent(&self) -> Extent2D {
+        self.max_coded_extent
+    }
+
+    pub fn max_dpb_slots(mut self, max_dpb_slots: u32) -> Self {
+        self.max_dpb_slots = max_dpb_slots;
+        self
+    }
+
+    pub fn get_max_dpb_slots(&self) -> u32 {
+        self.max_dpb_slots
+    }
+
+    pub fn max_active_reference_pictures(mut self, max_active_reference_pictures: u32) -> Self {
+        self.max_active_reference_pictures = max_active_reference_pictures;
+        self
+    }
+
+    pub fn get_max_active_reference_pictures(&self) -> u32 {
+        self.max_active_reference_pictures
+    }
+
+    /// Format of decode output images.
+    pub fn picture_format(mut self, picture_format: Format) -> Self {
+        self.picture_format = Some(picture_format);
+        self
+    }
+
+    pub fn get_picture_format(&self) -> Option<Format> {
+        self.picture_format
+    }
+
+    /// Format of DPB images.
+    pub fn reference_picture_format(mut self, reference_picture_format: Format) -> Self {
+        self.reference_picture_format = Some(reference_picture_format);
+        self
+    }
+
+    pub fn get_reference_picture_format(&self) -> Option<Format> {
+        self.reference_picture_format
+    }
+
+    pub fn flags(mut self, flags: VideoSessionCreateFlagsKHR) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn get_flags(&self) -> VideoSessionCreateFlagsKHR {
+        self.flags
+    }
+
+    /// Quality level of encode sessions, ignored when decoding.
+    ///
+    /// Higher levels may give better quality at the cost of encode speed, see [`VideoEncodeSession::quality_levels`](crate::video::VideoEncodeSession::quality_levels)
+    /// for the levels a device supports.
+    pub fn quality_level(mut self, quality_level: u32) -> Self {
+        self.quality_level = quality_level;
+        self
+    }
+
+    pub fn get_quality_level(&self) -> u32 {
+        self.quality_level
+    }
+
+    /// Allocates the memory the session needs through `allocator`, instead of one allocation per requirement.
+    pub fn allocator(mut self, allocator: Arc<dyn Allocator>) -> Self {
+        self.allocator = Some(allocator);
+        self
+    }
+
+    pub fn get_allocator(&self) -> Option<Arc<dyn Allocator>> {
+        self.allocator.clone()
+    }
+}
+
+/// Formats the device supports for images with `usage` in sessions for `profile`.
+pub(crate) unsafe fn video_formats(
+    get_video_format_properties: vk::PFN_vkGetPhysicalDeviceVideoFormatPropertiesKHR,
+    physical_device: vk::PhysicalDevice,
+    profile: &VideoProfileInfoKHR,
+    usage: ImageUsageFlags,
+) -> Result<Vec<Format>, Error> {
+    let formats = video_format_tilings(get_video_format_properties, physical_device, profile, usage)?;
+
+    Ok(formats.into_iter().map(|(format, _)| format).collect())
+}
+
+/// Like [`video_formats`], but with the tiling each format is supported in, formats may be listed once per tiling.
+pub(crate) unsafe fn video_format_tilings(
+    get_video_format_properties: vk::PFN_vkGetPhysicalDeviceVideoFormatPropertiesKHR,
+    physical_device: vk::PhysicalDevice,
+    profile: &VideoProfileInfoKHR,
+    usage: ImageUsageFlags,
+) -> Result<Vec<(Format, ImageTiling)>, Error> {
+    let profiles = &[*profile];
+    let mut video_profile_list_info = VideoProfileListInfoKHR::default().profiles(profiles);
+
+    let video_format_info = PhysicalDeviceVideoFormatInfoKHR::default()
+        .image_usage(usage)
+        .push_next(&mut video_profile_list_info);
+
+    let mut num_video_format_properties = 0;
+
+    (get_video_format_properties)(physical_device, &video_format_info, &mut num_video_format_properties, null_mut()).result()?;
+
+    let mut video_format_properties = vec![VideoFormatPropertiesKHR::default(); num_video_format_properties as usize];
+
+    (get_video_format_properties)(
+        physical_device,
+        &video_format_info,
+        &mut num_video_format_properties,
+        video_format_properties.as_mut_ptr(),
+    )
+    .result()?;
+
+    Ok(video_format_properties
+        .iter()
+        .take(num_video_format_properties as usize)
+        .map(|x| (x.format, x.image_tiling))
+        .collect())
+}
+
+/// Allocates (through `allocator`, if given) and binds the memory `native_session` needs, which must be kept alive as long as the session is.
+pub(crate) unsafe fn bind_session_memory(
+    device: &Device,
+    allocator: Option<&dyn Allocator>,
+    queue_fns: &KhrVideoQueueDeviceFn,
+    native_session: VideoSessionKHR,
+) -> Result<Vec<Allocation>, Error> {
+    let dedicated_allocator = DedicatedAllocator::new(device);
+    let allocator = allocator.unwrap_or(&dedicated_allocator);
+    let native_device = device.shared().native();
+    let bind_video_session_memory = queue_fns.bind_video_session_memory_khr;
+    let memory_requirements = queue_fns.get_video_session_memory_requirements_khr;
+
+    let mut video_session_count = 0;
+    let mut allocations = Vec::new();
+    let mut bindings = Vec::new();
+
+    memory_requirements(native_device.handle(), native_session, &mut video_session_count, null_mut()).result()?;
+
+    let mut video_session_requirements = vec![VideoSessionMemoryRequirementsKHR::default(); video_session_count as usize];
+
+    memory_requirements(
+        native_device.handle(),
+        native_session,
+        &mut video_session_count,
+        video_session_requirements.as_mut_ptr(),
+    )
+    .result()?;
+
+    let video_session_requirements = &video_session_requirements[0..video_session_count as usize];
+
+    for r in video_session_requirements {
+        // TODO: Better logic to select memory type? Without flags this picks the first supported one.
+        let requirements = MemoryRequirements::from_native(r.memory_requirements);
+        let (allocation, offset) = allocator.allocate(&requirements, MemoryPropertyFlags::empty())?;
+        let bind = BindVideoSessionMemoryInfoKHR::default()
+            .memory(allocation.native())
+            .memory_bind_index(r.memory_bind_index)
+            .memory_size(r.memory_requirements.size)
+            .memory_offset(offset);
+
+        allocations.push(allocation);
+        bindings.push(bind);
+    }
+
+    bind_video_session_memory(native_device.handle(), native_session, bindings.len() as u32, bindings.as_ptr()).result()?;
+
+    Ok(allocations)
+}
+
+/// Picks the `requested` format if given, otherwise the `preferred` one or whatever the device lists first.
+pub(crate) fn select_format(requested: Option<Format>, preferred: Option<Format>, supported: &[Format]) -> Result<Format, Error> {
+    match requested {
+        Some(x) if supported.contains(&x) => Ok(x),
+        Some(x) => Err(error!(
+            Variant::UnsupportedProfile,
+            "Format {x:?} not supported for this profile, device supports {supported:?}."
+        )),
+        None => preferred
+            .filter(|x| supported.contains(x))
+            .or_else(|| supported.first().copied())
+            .ok_or_else(|| error!(Variant::UnsupportedProfile, "Device supports no image format for this profile.")),
+    }
+}
+
+/// The `std_profile_idc` of whichever codec `profiles` is for.
+fn profile_idc(profiles: &VideoProfileInfoBundle) -> u32 {
+    if profiles.info.video_codec_operation == VideoCodecOperationFlagsKHR::DECODE_H265 {
+        profiles.info_h265.std_profile_idc
+    } else {
+        profiles.info_h264.std_profile_idc
+    }
 }
 
 pub(crate) struct VideoSessionShared {
@@ -38,12 +302,18 @@ pub(crate) struct VideoSessionShared {
     native_decode_queue_fns: KhrVideoDecodeQueueDeviceFn,
     // native_video_instance_fns: KhrVideoQueueInstanceFn,
     native_session: VideoSessionKHR,
-    // allocations: Vec<Allocation>,
+    /// Memory bound to the session, only freed after the session was destroyed in `drop`.
+    #[allow(unused)]
+    allocations: Vec<Allocation>,
     decode_capabilities: VideoDecodeCapabilities,
+    capability_flags: VideoCapabilityFlagsKHR,
+    h264_picture_layout: VideoDecodeH264PictureLayoutFlagsKHR,
+    linear_output: bool,
+    info: VideoSessionInfo,
 }
 
 impl VideoSessionShared {
-    pub fn new(device: &Device, stream_inspector: &H264StreamInspector) -> Result<Self, Error> {
+    pub fn new(device: &Device, stream_inspector: &impl StreamInspector, info: &VideoSessionInfo) -> Result<Self, Error> {
         let shared_device = device.shared();
         let shared_instance = shared_device.instance();
 
@@ -51,32 +321,26 @@ impl VideoSessionShared {
         let native_instance = shared_instance.native();
         let native_entry = shared_instance.native_entry();
 
-        let extension_name = c"VK_STD_vulkan_video_codec_h264_decode";
+        let profiles = stream_inspector.profiles();
+        let codec_operation = profiles.info.video_codec_operation;
+
+        let extension_name = if codec_operation == VideoCodecOperationFlagsKHR::DECODE_H265 {
+            c"VK_STD_vulkan_video_codec_h265_decode"
+        } else {
+            c"VK_STD_vulkan_video_codec_h264_decode"
+        };
         let extension_version = vk::make_api_version(0, 1, 0, 0);
 
         let extensions_names = ExtensionProperties::default()
             .spec_version(extension_version)
             .extension_name(extension_name)?;
 
-        let profiles = stream_inspector.profiles();
-
         let queue_family_index = shared_device
             .physical_device()
             .queue_family_infos()
             .any_decode()
             .ok_or_else(|| error!(Variant::QueueNotFound))?;
 
-        let video_session_create_info = VideoSessionCreateInfoKHR::default()
-            .queue_family_index(queue_family_index)
-            .flags(VideoSessionCreateFlagsKHR::empty())
-            .video_profile(&profiles.info)
-            .picture_format(Format::G8_B8R8_2PLANE_420_UNORM)
-            .max_coded_extent(Extent2D { width: 512, height: 512 })
-            .reference_picture_format(Format::G8_B8R8_2PLANE_420_UNORM)
-            .max_dpb_slots(17)
-            .max_active_reference_pictures(16)
-            .std_header_version(&extensions_names);
-
         let result = unsafe {
             let queue_fns = KhrVideoQueueDeviceFn::load(
                 |x| {
@@ -103,96 +367,95 @@ impl VideoSessionShared {
             let get_physical_device_video_format_properties_khr = video_instance_fn.get_physical_device_video_format_properties_khr;
             let get_physical_device_video_capabilities = video_instance_fn.get_physical_device_video_capabilities_khr;
             let create_video_session = queue_fns.create_video_session_khr;
-            let bind_video_session_memory = queue_fns.bind_video_session_memory_khr;
-            let memory_requirements = queue_fns.get_video_session_memory_requirements_khr;
 
-            let mut video_decode_h264_profile =
-                VideoDecodeH264ProfileInfoKHR::default().std_profile_idc(StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_BASELINE);
-
-            let video_profile = VideoProfileInfoKHR::default()
-                .push_next(&mut video_decode_h264_profile)
-                .video_codec_operation(VideoCodecOperationFlagsKHR::DECODE_H264)
-                .chroma_subsampling(VideoChromaSubsamplingFlagsKHR::TYPE_420)
-                .chroma_bit_depth(VideoComponentBitDepthFlagsKHR::TYPE_8)
-                .luma_bit_depth(VideoComponentBitDepthFlagsKHR::TYPE_8);
+            let video_profile = profiles.info;
 
             let mut video_decode_h264_capabilities = VideoDecodeH264CapabilitiesKHR::default();
+            let mut video_decode_h265_capabilities = VideoDecodeH265CapabilitiesKHR::default();
 
             let mut video_decode_capabilities = VideoDecodeCapabilitiesKHR::default();
 
             // Does this order matter?  It seems to work without relevant validation failures either way.
-            let mut video_capabilities = VideoCapabilitiesKHR::default()
-                .push_next(&mut video_decode_capabilities)
-                .push_next(&mut video_decode_h264_capabilities);
-
-            (get_physical_device_video_capabilities)(shared_device.physical_device().native(), &video_profile, &mut video_capabilities)
-                .result()?;
-
-            let array = &[video_profile];
-
-            let mut video_profile_list_info = VideoProfileListInfoKHR::default().profiles(array);
+            let mut video_capabilities = VideoCapabilitiesKHR::default().push_next(&mut video_decode_capabilities);
 
-            let video_format_info = PhysicalDeviceVideoFormatInfoKHR::default()
-                .image_usage(ImageUsageFlags::VIDEO_DECODE_DPB_KHR)
-                .push_next(&mut video_profile_list_info);
+            // Only the capabilities struct matching the codec may be chained.
+            video_capabilities = if codec_operation == VideoCodecOperationFlagsKHR::DECODE_H265 {
+                video_capabilities.push_next(&mut video_decode_h265_capabilities)
+            } else {
+                video_capabilities.push_next(&mut video_decode_h264_capabilities)
+            };
 
-            let mut num_video_format_properties = 0;
-
-            (get_physical_device_video_format_properties_khr)(
-                shared_device.physical_device().native(),
-                &video_format_info,
-                &mut num_video_format_properties,
-                null_mut(),
-            )
-            .result()?;
-
-            let mut video_format_properties = vec![VideoFormatPropertiesKHR::default(); num_video_format_properties as usize];
+            (get_physical_device_video_capabilities)(shared_device.physical_device().native(), &video_profile, &mut video_capabilities)
+                .result()
+                .map_err(|e| match e {
+                    vk::Result::ERROR_VIDEO_PROFILE_OPERATION_NOT_SUPPORTED_KHR
+                    | vk::Result::ERROR_VIDEO_PROFILE_FORMAT_NOT_SUPPORTED_KHR
+                    | vk::Result::ERROR_VIDEO_PICTURE_LAYOUT_NOT_SUPPORTED_KHR
+                    | vk::Result::ERROR_VIDEO_PROFILE_CODEC_NOT_SUPPORTED_KHR => error!(
+                        Variant::UnsupportedProfile,
+                        "Device cannot decode {codec_operation:?} with profile_idc {} ({e}).",
+                        profile_idc(&profiles)
+                    ),
+                    e => e.into(),
+                })?;
+
+            let capability_flags = video_capabilities.flags;
+
+            // With coinciding DPB and output, the same images have to support both usages.
+            let (usage_dst, usage_dpb) = match video_decode_capabilities
+                .flags
+                .contains(VideoDecodeCapabilityFlagsKHR::DPB_AND_OUTPUT_COINCIDE)
+            {
+                true => (
+                    ImageUsageFlags::VIDEO_DECODE_DST_KHR | ImageUsageFlags::VIDEO_DECODE_DPB_KHR,
+                    ImageUsageFlags::VIDEO_DECODE_DST_KHR | ImageUsageFlags::VIDEO_DECODE_DPB_KHR,
+                ),
+                false => (ImageUsageFlags::VIDEO_DECODE_DST_KHR, ImageUsageFlags::VIDEO_DECODE_DPB_KHR),
+            };
+
+            let physical_device = shared_device.physical_device().native();
+            let format_tilings_dst = video_format_tilings(
+                get_physical_device_video_format_properties_khr,
+                physical_device,
+                &video_profile,
+                usage_dst,
+            )?;
+            let formats_dst = format_tilings_dst.iter().map(|(format, _)| *format).collect::<Vec<_>>();
+            let formats_dpb = video_formats(
+                get_physical_device_video_format_properties_khr,
+                physical_device,
+                &video_profile,
+                usage_dpb,
+            )?;
+
+            let mut info = info.clone();
+            info.picture_format = Some(select_format(info.picture_format, profiles.format(), &formats_dst)?);
+            info.reference_picture_format = Some(select_format(info.reference_picture_format, profiles.format(), &formats_dpb)?);
+
+            let linear_output = format_tilings_dst.contains(&(info.picture_format.unwrap_or_default(), ImageTiling::LINEAR));
+
+            // With `VK_KHR_video_maintenance1` queries can be recorded as part of decode operations.
+            if shared_device.video_features().inline_queries() {
+                info.flags |= VideoSessionCreateFlagsKHR::INLINE_QUERIES;
+            }
 
-            (get_physical_device_video_format_properties_khr)(
-                shared_device.physical_device().native(),
-                &video_format_info,
-                &mut num_video_format_properties,
-                video_format_properties.as_mut_ptr(),
-            )
-            .result()?;
+            let video_session_create_info = VideoSessionCreateInfoKHR::default()
+                .queue_family_index(queue_family_index)
+                .flags(info.flags)
+                .video_profile(&profiles.info)
+                .picture_format(info.picture_format.unwrap_or_default())
+                .max_coded_extent(info.max_coded_extent)
+                .reference_picture_format(info.reference_picture_format.unwrap_or_default())
+                .max_dpb_slots(info.max_dpb_slots)
+                .max_active_reference_pictures(info.max_active_reference_pictures)
+                .std_header_version(&extensions_names);
 
             let mut native_session = VideoSessionKHR::default();
-            let mut video_session_count = 0;
-            let mut allocations = Vec::new();
-            let mut bindings = Vec::new();
 
             create_video_session(native_device.handle(), &video_session_create_info, null(), &mut native_session).result()?;
+            shared_device.set_default_name(native_session, "VideoSession");
 
-            memory_requirements(native_device.handle(), native_session, &mut video_session_count, null_mut()).result()?;
-
-            let mut video_session_requirements = vec![VideoSessionMemoryRequirementsKHR::default(); video_session_count as usize];
-
-            memory_requirements(
-                native_device.handle(),
-                native_session,
-                &mut video_session_count,
-                video_session_requirements.as_mut_ptr(),
-            )
-            .result()?;
-
-            let video_session_requirements = &video_session_requirements[0..video_session_count as usize];
-
-            for (i, r) in video_session_requirements.iter().enumerate() {
-                let supported_types = r.memory_requirements.memory_type_bits;
-                let best_type = MemoryTypeIndex::new(supported_types.trailing_zeros()); // TODO: Better logic to select memory type?
-
-                let allocation = Allocation::new(device, r.memory_requirements.size, best_type)?;
-                let bind = BindVideoSessionMemoryInfoKHR::default()
-                    .memory(allocation.native())
-                    .memory_bind_index(i as u32)
-                    .memory_size(r.memory_requirements.size)
-                    .memory_offset(0);
-
-                allocations.push(allocation);
-                bindings.push(bind);
-            }
-
-            bind_video_session_memory(native_device.handle(), native_session, bindings.len() as u32, bindings.as_ptr()).result()?;
+            let allocations = bind_session_memory(device, info.allocator.as_deref(), &queue_fns, native_session)?;
 
             Ok(Self {
                 shared_device,
@@ -200,8 +463,15 @@ impl VideoSessionShared {
                 native_decode_queue_fns: decode_queue_fns,
                 // native_video_instance_fns: video_instance_fn,
                 native_session,
-                // allocations,
-                decode_capabilities: video_decode_capabilities.into(),
+                allocations,
+                decode_capabilities: VideoDecodeCapabilities {
+                    field_offset_granularity: video_decode_h264_capabilities.field_offset_granularity,
+                    ..video_decode_capabilities.into()
+                },
+                capability_flags,
+                h264_picture_layout: profiles.info_h264.picture_layout,
+                linear_output,
+                info,
             })
         };
         result
@@ -230,6 +500,24 @@ impl VideoSessionShared {
     pub(crate) fn decode_capabilities(&self) -> &VideoDecodeCapabilities {
         &self.decode_capabilities
     }
+
+    /// If not [`VideoCapabilityFlagsKHR::SEPARATE_REFERENCE_IMAGES`], all DPB slots must live in array layers of one image.
+    pub(crate) fn capability_flags(&self) -> VideoCapabilityFlagsKHR {
+        self.capability_flags
+    }
+
+    /// How H.264 field pictures are laid out in images, only relevant for H.264 sessions.
+    pub(crate) fn h264_picture_layout(&self) -> VideoDecodeH264PictureLayoutFlagsKHR {
+        self.h264_picture_layout
+    }
+
+    pub(crate) fn linear_output(&self) -> bool {
+        self.linear_output
+    }
+
+    pub(crate) fn info(&self) -> &VideoSessionInfo {
+        &self.info
+    }
 }
 
 impl Drop for VideoSessionShared {
@@ -249,8 +537,8 @@ pub struct VideoSession {
 }
 
 impl VideoSession {
-    pub fn new(device: &Device, stream_inspector: &H264StreamInspector) -> Result<Self, Error> {
-        let shared = VideoSessionShared::new(device, stream_inspector)?;
+    pub fn new(device: &Device, stream_inspector: &impl StreamInspector, info: &VideoSessionInfo) -> Result<Self, Error> {
+        let shared = VideoSessionShared::new(device, stream_inspector, info)?;
 
         Ok(Self { shared: Arc::new(shared) })
     }
@@ -258,6 +546,41 @@ impl VideoSession {
     pub(crate) fn shared(&self) -> Arc<VideoSessionShared> {
         self.shared.clone()
     }
+
+    /// The raw `VkVideoSessionKHR`.
+    pub fn raw(&self) -> VideoSessionKHR {
+        self.shared.native()
+    }
+
+    /// Names this session in validation messages and debugging tools, instead of the default `vulkan_video::VideoSession#N`.
+    ///
+    /// Does nothing if `VK_EXT_debug_utils` isn't available.
+    pub fn set_name(&self, name: &str) -> Result<(), Error> {
+        self.shared.device().set_object_name(self.raw(), name)
+    }
+
+    /// The loaded `VK_KHR_video_queue` functions, e.g., to record your own coding scopes.
+    pub fn raw_queue_fns(&self) -> KhrVideoQueueDeviceFn {
+        self.shared.queue_fns()
+    }
+
+    /// The loaded `VK_KHR_video_decode_queue` functions.
+    pub fn raw_decode_fns(&self) -> KhrVideoDecodeQueueDeviceFn {
+        self.shared.decode_fns()
+    }
+
+    /// The info this session was created with, picture and reference formats are those actually used.
+    pub fn info(&self) -> VideoSessionInfo {
+        self.shared.info().clone()
+    }
+
+    /// If decode output images may have [`ImageTiling::LINEAR`], so they can be read via [`Image::map`](crate::resources::Image::map)
+    /// instead of being copied into a buffer first.
+    ///
+    /// Such images also need host visible memory, see [`HeapInfos::any_host_visible_for`](crate::HeapInfos::any_host_visible_for).
+    pub fn supports_linear_output(&self) -> bool {
+        self.shared.linear_output()
+    }
 }
 
 #[cfg(test)]
@@ -267,7 +590,21 @@ mod test {
     use crate::instance::{Instance, InstanceInfo};
     use crate::physicaldevice::PhysicalDevice;
     use crate::video::h264::H264StreamInspector;
-    use crate::video::session::VideoSession;
+    use crate::video::session::{select_format, VideoSession, VideoSessionInfo};
+    use ash::vk::{Extent2D, Format};
+
+    #[test]
+    fn select_supported_format() {
+        let nv12 = Format::G8_B8R8_2PLANE_420_UNORM;
+        let nv16 = Format::G8_B8R8_2PLANE_422_UNORM;
+        let supported = [nv16, nv12];
+
+        assert_eq!(select_format(None, Some(nv12), &supported).ok(), Some(nv12));
+        assert_eq!(select_format(None, None, &supported).ok(), Some(nv16));
+        assert_eq!(select_format(Some(nv16), Some(nv12), &supported).ok(), Some(nv16));
+        assert!(select_format(Some(Format::G8_B8R8_2PLANE_444_UNORM), None, &supported).is_err());
+        assert!(select_format(None, Some(nv12), &[]).is_err());
+    }
 
     #[test]
     #[cfg(not(miri))]
@@ -278,7 +615,27 @@ mod test {
         let device = Device::new(&physical_device)?;
         let h264inspector = H264StreamInspector::new();
 
-        _ = VideoSession::new(&device, &h264inspector)?;
+        _ = VideoSession::new(&device, &h264inspector, &VideoSessionInfo::new())?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn create_session_with_info() -> Result<(), Error> {
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let h264inspector = H264StreamInspector::new();
+        let info = VideoSessionInfo::new()
+            .max_coded_extent(Extent2D { width: 1920, height: 1088 })
+            .max_dpb_slots(5)
+            .max_active_reference_pictures(4);
+
+        let session = VideoSession::new(&device, &h264inspector, &info)?;
+
+        assert_eq!(session.info().get_max_dpb_slots(), 5);
 
         Ok(())
     }
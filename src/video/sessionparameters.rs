@@ -1,144 +1,49 @@
-use crate::error::Error;
+use crate::error;
+use crate::error::{Error, Variant};
 use crate::video::h264::H264StreamInspector;
+use crate::video::h265::H265StreamInspector;
 use crate::video::session::{VideoSession, VideoSessionShared};
-use ash::vk::native::{
-    StdVideoH264HrdParameters, StdVideoH264PictureParameterSet, StdVideoH264PpsFlags, StdVideoH264ScalingLists,
-    StdVideoH264SequenceParameterSet, StdVideoH264SequenceParameterSetVui, StdVideoH264SpsFlags, StdVideoH264SpsVuiFlags,
-};
 use ash::vk::{
-    VideoDecodeH264SessionParametersAddInfoKHR, VideoDecodeH264SessionParametersCreateInfoKHR, VideoSessionParametersCreateInfoKHR,
+    VideoCodecOperationFlagsKHR, VideoDecodeH264SessionParametersAddInfoKHR, VideoDecodeH264SessionParametersCreateInfoKHR,
+    VideoDecodeH265SessionParametersAddInfoKHR, VideoDecodeH265SessionParametersCreateInfoKHR, VideoSessionParametersCreateInfoKHR,
     VideoSessionParametersKHR, VideoSessionParametersUpdateInfoKHR,
 };
-use std::ptr::{addr_of, addr_of_mut, null};
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::ptr::null;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Tracks what was added to session parameters, as Vulkan only allows adding parameter sets with new ids.
+#[derive(Default)]
+struct UpdateState {
+    update_sequence_count: u32,
+    parameter_set_nals: BTreeMap<(u8, u8), Vec<u8>>,
+}
 
 pub(crate) struct VideoSessionParametersShared {
     shared_session: Arc<VideoSessionShared>,
     native_parameters: VideoSessionParametersKHR,
+    /// Codec of the parameter sets, e.g., [`VideoCodecOperationFlagsKHR::DECODE_H264`].
+    codec_operation: VideoCodecOperationFlagsKHR,
+    update_state: Mutex<UpdateState>,
 }
 
 impl VideoSessionParametersShared {
-    pub fn new(shared_session: Arc<VideoSessionShared>, _stream_inspector: &H264StreamInspector) -> Result<Self, Error> {
+    pub fn new(shared_session: Arc<VideoSessionShared>, stream_inspector: &H264StreamInspector) -> Result<Self, Error> {
         let native_session = shared_session.native();
         let native_device = shared_session.device().native();
         let native_queue_fns = shared_session.queue_fns();
 
-        let hrd = StdVideoH264HrdParameters {
-            cpb_cnt_minus1: 0,
-            bit_rate_scale: 0,
-            cpb_size_scale: 0,
-            reserved1: 0,
-            bit_rate_value_minus1: Default::default(),
-            cpb_size_value_minus1: Default::default(),
-            cbr_flag: Default::default(),
-            initial_cpb_removal_delay_length_minus1: 23,
-            cpb_removal_delay_length_minus1: 0,
-            dpb_output_delay_length_minus1: 0,
-            time_offset_length: 0,
-        };
-
-        let mut vui_flags = StdVideoH264SpsVuiFlags {
-            _bitfield_align_1: [],
-            _bitfield_1: Default::default(),
-            __bindgen_padding_0: 0,
-        };
-
-        vui_flags.set_video_signal_type_present_flag(1);
-        vui_flags.set_color_description_present_flag(1);
-        vui_flags.set_bitstream_restriction_flag(1);
-
-        let vui = StdVideoH264SequenceParameterSetVui {
-            flags: vui_flags,
-            aspect_ratio_idc: 0,
-            sar_width: 1,
-            sar_height: 1,
-            video_format: 2,
-            colour_primaries: 5,
-            transfer_characteristics: 6,
-            matrix_coefficients: 6,
-            num_units_in_tick: 0,
-            time_scale: 0,
-            max_num_reorder_frames: 0,
-            max_dec_frame_buffering: 16,
-            chroma_sample_loc_type_top_field: 0,
-            chroma_sample_loc_type_bottom_field: 0,
-            reserved1: 0,
-            pHrdParameters: &hrd,
-        };
-
-        let mut flags = StdVideoH264SpsFlags {
-            _bitfield_align_1: [],
-            _bitfield_1: Default::default(),
-            __bindgen_padding_0: 0,
-        };
-
-        flags.set_frame_mbs_only_flag(1);
-        flags.set_vui_parameters_present_flag(1);
-        flags.set_direct_8x8_inference_flag(1);
-
-        let sps_info = StdVideoH264SequenceParameterSet {
-            flags,
-            profile_idc: 100,
-            level_idc: 8,
-            chroma_format_idc: 1,
-            seq_parameter_set_id: 0,
-            bit_depth_luma_minus8: 0,
-            bit_depth_chroma_minus8: 0,
-            log2_max_frame_num_minus4: 0,
-            pic_order_cnt_type: 2,
-            offset_for_non_ref_pic: 0,
-            offset_for_top_to_bottom_field: 0,
-            log2_max_pic_order_cnt_lsb_minus4: 0,
-            num_ref_frames_in_pic_order_cnt_cycle: 0,
-            max_num_ref_frames: 0,
-            reserved1: 0,
-            pic_width_in_mbs_minus1: 31,
-            pic_height_in_map_units_minus1: 31,
-            frame_crop_left_offset: 0,
-            frame_crop_right_offset: 0,
-            frame_crop_top_offset: 0,
-            frame_crop_bottom_offset: 0,
-            reserved2: 0,
-            pOffsetForRefFrame: null(),
-            pScalingLists: null(),
-            pSequenceParameterSetVui: &vui,
-        };
-
-        let mut pps_flags = StdVideoH264PpsFlags {
-            _bitfield_align_1: Default::default(),
-            _bitfield_1: Default::default(),
-            __bindgen_padding_0: Default::default(),
-        };
-
-        pps_flags.set_transform_8x8_mode_flag(1);
-        pps_flags.set_deblocking_filter_control_present_flag(1);
-        pps_flags.set_entropy_coding_mode_flag(1);
-
-        let pps_info = StdVideoH264PictureParameterSet {
-            flags: pps_flags,
-            seq_parameter_set_id: 0,
-            pic_parameter_set_id: 0,
-            num_ref_idx_l0_default_active_minus1: 0,
-            num_ref_idx_l1_default_active_minus1: 0,
-            weighted_bipred_idc: 0,
-            pic_init_qp_minus26: -6,
-            pic_init_qs_minus26: 0,
-            chroma_qp_index_offset: 0,
-            second_chroma_qp_index_offset: 0,
-            pScalingLists: null(),
-        };
-
-        let sps_array = &[sps_info];
-        let pps_array = &[pps_info];
-
-        let create_info = VideoDecodeH264SessionParametersAddInfoKHR::default()
-            .std_sp_ss(sps_array)
-            .std_pp_ss(pps_array);
+        // Owns everything the `StdVideoH264*` structs point to, must outlive the create call below.
+        let parameter_sets = stream_inspector.std_parameter_sets();
+
+        let add_info = VideoDecodeH264SessionParametersAddInfoKHR::default()
+            .std_sp_ss(&parameter_sets.sps)
+            .std_pp_ss(&parameter_sets.pps);
 
         let mut video_decode_h264session_parameters_create_info = VideoDecodeH264SessionParametersCreateInfoKHR::default()
             .max_std_sps_count(32)
             .max_std_pps_count(256)
-            .parameters_add_info(&create_info);
+            .parameters_add_info(&add_info);
 
         let session_create_info = VideoSessionParametersCreateInfoKHR::default()
             .video_session(native_session)
@@ -147,18 +52,117 @@ impl VideoSessionParametersShared {
         unsafe {
             let mut native_parameters = VideoSessionParametersKHR::null();
             let create_video_session_parameters = native_queue_fns.create_video_session_parameters_khr;
-            // let update_video_session_parameters = native_queue_fns.update_video_session_parameters_khr;
 
             create_video_session_parameters(native_device.handle(), &session_create_info, null(), &mut native_parameters).result()?;
-            // update_video_session_parameters(native_device.handle(), native_parameters, &update).result()?;
 
             Ok(Self {
                 shared_session,
                 native_parameters,
+                codec_operation: VideoCodecOperationFlagsKHR::DECODE_H264,
+                update_state: Mutex::new(UpdateState {
+                    update_sequence_count: 0,
+                    parameter_set_nals: stream_inspector.parameter_set_nals().clone(),
+                }),
+            })
+        }
+    }
+
+    pub fn new_h265(shared_session: Arc<VideoSessionShared>, stream_inspector: &H265StreamInspector) -> Result<Self, Error> {
+        let native_session = shared_session.native();
+        let native_device = shared_session.device().native();
+        let native_queue_fns = shared_session.queue_fns();
+
+        // Owns everything the `StdVideoH265*` structs point to, must outlive the create call below.
+        let parameter_sets = stream_inspector.std_parameter_sets();
+
+        let add_info = VideoDecodeH265SessionParametersAddInfoKHR::default()
+            .std_vp_ss(&parameter_sets.vps)
+            .std_sp_ss(&parameter_sets.sps)
+            .std_pp_ss(&parameter_sets.pps);
+
+        let mut video_decode_h265_session_parameters_create_info = VideoDecodeH265SessionParametersCreateInfoKHR::default()
+            .max_std_vps_count(16)
+            .max_std_sps_count(16)
+            .max_std_pps_count(64)
+            .parameters_add_info(&add_info);
+
+        let session_create_info = VideoSessionParametersCreateInfoKHR::default()
+            .video_session(native_session)
+            .push_next(&mut video_decode_h265_session_parameters_create_info);
+
+        unsafe {
+            let mut native_parameters = VideoSessionParametersKHR::null();
+            let create_video_session_parameters = native_queue_fns.create_video_session_parameters_khr;
+
+            create_video_session_parameters(native_device.handle(), &session_create_info, null(), &mut native_parameters).result()?;
+
+            Ok(Self {
+                shared_session,
+                native_parameters,
+                codec_operation: VideoCodecOperationFlagsKHR::DECODE_H265,
+                update_state: Mutex::new(UpdateState::default()),
             })
         }
     }
 
+    pub fn update(&self, stream_inspector: &H264StreamInspector) -> Result<(), Error> {
+        if self.codec_operation != VideoCodecOperationFlagsKHR::DECODE_H264 {
+            return Err(error!(
+                Variant::UnsupportedProfile,
+                "Only H.264 session parameters can be updated, got {:?}.", self.codec_operation
+            ));
+        }
+
+        let native_device = self.shared_session.device().native();
+        let native_queue_fns = self.shared_session.queue_fns();
+
+        // The state is only modified after successful updates, so it's consistent even if poisoned.
+        let mut update_state = self.update_state.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut new_parameter_sets = Vec::new();
+
+        for (key, nal) in stream_inspector.parameter_set_nals() {
+            match update_state.parameter_set_nals.get(key) {
+                Some(x) if x == nal => {}
+                Some(_) => {
+                    return Err(error!(
+                        Variant::ParameterSetChanged,
+                        "Parameter set {key:?} changed, recreate session parameters."
+                    ))
+                }
+                None => new_parameter_sets.push(*key),
+            }
+        }
+
+        if new_parameter_sets.is_empty() {
+            return Ok(());
+        }
+
+        let parameter_sets = stream_inspector.std_parameter_sets_for(|x| new_parameter_sets.contains(&x));
+
+        let mut add_info = VideoDecodeH264SessionParametersAddInfoKHR::default()
+            .std_sp_ss(&parameter_sets.sps)
+            .std_pp_ss(&parameter_sets.pps);
+
+        let update_info = VideoSessionParametersUpdateInfoKHR::default()
+            .update_sequence_count(update_state.update_sequence_count + 1)
+            .push_next(&mut add_info);
+
+        unsafe {
+            let update_video_session_parameters = native_queue_fns.update_video_session_parameters_khr;
+
+            update_video_session_parameters(native_device.handle(), self.native_parameters, &update_info).result()?;
+        }
+
+        update_state.update_sequence_count += 1;
+
+        for key in new_parameter_sets {
+            let nal = stream_inspector.parameter_set_nals()[&key].clone();
+            update_state.parameter_set_nals.insert(key, nal);
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn native(&self) -> VideoSessionParametersKHR {
         self.native_parameters
     }
@@ -193,19 +197,42 @@ impl VideoSessionParameters {
         Ok(Self { shared: Arc::new(shared) })
     }
 
+    pub fn new_h265(session: &VideoSession, stream_inspector: &H265StreamInspector) -> Result<Self, Error> {
+        let shared = VideoSessionParametersShared::new_h265(session.shared(), stream_inspector)?;
+
+        Ok(Self { shared: Arc::new(shared) })
+    }
+
+    /// Adds SPS and PPS the stream inspector has seen since these parameters were created or last updated.
+    ///
+    /// Repeated parameter sets are ignored. Vulkan does not allow replacing a parameter set, so if one
+    /// changed (same id, different content) this fails with [`Variant::ParameterSetChanged`] and new
+    /// session parameters have to be created instead. Parameters created with [`Self::new_h265`] can't be
+    /// updated yet and fail with [`Variant::UnsupportedProfile`].
+    pub fn update(&self, stream_inspector: &H264StreamInspector) -> Result<(), Error> {
+        self.shared.update(stream_inspector)
+    }
+
     pub(crate) fn shared(&self) -> Arc<VideoSessionParametersShared> {
         self.shared.clone()
     }
+
+    /// The raw `VkVideoSessionParametersKHR`.
+    pub fn raw(&self) -> VideoSessionParametersKHR {
+        self.shared.native()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use crate::device::Device;
-    use crate::error::Error;
+    use crate::error::{Error, Variant};
     use crate::instance::{Instance, InstanceInfo};
     use crate::physicaldevice::PhysicalDevice;
     use crate::video::h264::H264StreamInspector;
-    use crate::video::session::VideoSession;
+    use crate::video::h265::H265StreamInspector;
+    use crate::video::nal_units;
+    use crate::video::session::{VideoSession, VideoSessionInfo};
     use crate::video::sessionparameters::VideoSessionParameters;
 
     #[test]
@@ -216,10 +243,66 @@ mod test {
         let physical_device = PhysicalDevice::new_any(&instance)?;
         let device = Device::new(&physical_device)?;
         let h264inspector = H264StreamInspector::new();
-        let session = VideoSession::new(&device, &h264inspector)?;
+        let session = VideoSession::new(&device, &h264inspector, &VideoSessionInfo::new())?;
 
         _ = VideoSessionParameters::new(&session, &h264inspector)?;
 
         Ok(())
     }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn update_session_parameters() -> Result<(), Error> {
+        let sps = [0x00, 0x00, 0x01, 0x67, 0x42, 0x00, 0x0a, 0xf4, 0xf2];
+        let pps = [0x00, 0x00, 0x01, 0x68, 0xde, 0x3c, 0x80];
+        let pps_changed = [0x00, 0x00, 0x01, 0x68, 0xde, 0x3e, 0x80];
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let mut h264inspector = H264StreamInspector::new();
+        let session = VideoSession::new(&device, &h264inspector, &VideoSessionInfo::new())?;
+        let parameters = VideoSessionParameters::new(&session, &h264inspector)?;
+
+        h264inspector.feed_nal(&sps)?;
+        h264inspector.feed_nal(&pps)?;
+        parameters.update(&h264inspector)?;
+
+        // Repeating parameter sets is fine, changing them isn't.
+        h264inspector.feed_nal(&pps)?;
+        parameters.update(&h264inspector)?;
+
+        h264inspector.feed_nal(&pps_changed)?;
+        let changed = parameters.update(&h264inspector);
+        assert!(changed.is_err_and(|e| matches!(e.variant(), Variant::ParameterSetChanged)));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn create_session_parameters_h265() -> Result<(), Error> {
+        let h265_data = include_bytes!("../../tests/videos/single_64x48.h265");
+
+        let instance_info = InstanceInfo::new().app_name("MyApp")?.app_version(100).validation(true);
+        let instance = Instance::new(&instance_info)?;
+        let physical_device = PhysicalDevice::new_any(&instance)?;
+        let device = Device::new(&physical_device)?;
+        let mut h265inspector = H265StreamInspector::new();
+
+        for nal in nal_units(h265_data) {
+            h265inspector.feed_nal(nal)?;
+        }
+
+        let session = VideoSession::new(&device, &h265inspector, &VideoSessionInfo::new())?;
+
+        let parameters = VideoSessionParameters::new_h265(&session, &h265inspector)?;
+
+        // Adding H.264 parameter sets to them would be invalid.
+        let update = parameters.update(&H264StreamInspector::new());
+        assert!(update.is_err_and(|e| matches!(e.variant(), Variant::UnsupportedProfile)));
+
+        Ok(())
+    }
 }
@@ -67,9 +67,81 @@ pub fn nal_units(mut stream: &[u8]) -> impl Iterator<Item = &[u8]> {
     })
 }
 
+/// Like [`nal_units`], but also returns the byte offset of each NAL unit within `stream`.
+///
+/// Handy if the whole stream was uploaded into a single buffer, as each NAL unit can then be decoded
+/// with `DecodeInfo::new(offset as u64, nal.len() as u64)`.
+pub fn nal_units_indexed(stream: &[u8]) -> impl Iterator<Item = (usize, &[u8])> {
+    nal_units(stream).map(move |nal| (offset_in(stream, nal), nal))
+}
+
+/// Splits a H.264 bitstream into access units, i.e., everything needed to decode one frame.
+///
+/// Access units are returned as contiguous slices of `stream`, including any SPS, PPS, SEI or access unit
+/// delimiters preceding the frame's slices, so each can be passed to a decoder as-is:
+///
+/// ```rust,ignore
+/// for access_unit in access_units(h264_data) {
+///     let frames = decoder.decode(access_unit)?;
+/// }
+/// ```
+///
+/// A new access unit starts (7.4.1.2.3) with the first non-VCL NAL unit (AUD, SPS, PPS, SEI, ...) after a
+/// slice, or with a slice having `first_mb_in_slice == 0`. Arbitrary slice order is not supported.
+pub fn access_units(stream: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut nals = nal_units_indexed(stream).peekable();
+
+    std::iter::from_fn(move || {
+        let (start, first) = nals.next()?;
+        let mut end = start + first.len();
+        let mut has_slice = is_h264_slice(first);
+
+        while let Some((offset, nal)) = nals.peek() {
+            if has_slice && starts_h264_access_unit(nal) {
+                break;
+            }
+
+            has_slice |= is_h264_slice(nal);
+            end = offset + nal.len();
+            nals.next();
+        }
+
+        Some(&stream[start..end])
+    })
+}
+
+/// Offset of `sub` within `stream`, `sub` must be a subslice of `stream`.
+pub(crate) fn offset_in(stream: &[u8], sub: &[u8]) -> usize {
+    sub.as_ptr() as usize - stream.as_ptr() as usize
+}
+
+/// The H.264 NAL unit type and the payload following the NAL unit header.
+pub(crate) fn nal_header(nal: &[u8]) -> Option<(u8, &[u8])> {
+    let start = nal.iter().position(|x| *x == 1)? + 1;
+    let header = *nal.get(start)?;
+
+    Some((header & 0x1f, &nal[start + 1..]))
+}
+
+/// Coded slice NAL unit types (1 - 5).
+fn is_h264_slice(nal: &[u8]) -> bool {
+    nal_header(nal).is_some_and(|(nal_unit_type, _)| (1..=5).contains(&nal_unit_type))
+}
+
+/// If `nal` can only be the first NAL unit of a new access unit, given the current one already has a slice.
+fn starts_h264_access_unit(nal: &[u8]) -> bool {
+    match nal_header(nal) {
+        // Slices starting at the first macroblock, `ue(v)` of 0 is a single `1` bit.
+        Some((1 | 5, payload)) => payload.first().is_some_and(|x| x & 0x80 != 0),
+        // SEI, SPS, PPS, AUD, and the reserved / prefix types 14 - 18.
+        Some((6..=9 | 14..=18, _)) => true,
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::nal_units;
+    use super::{access_units, nal_units, nal_units_indexed};
 
     #[test]
     fn splits_at_nal() {
@@ -109,4 +181,39 @@ mod test {
         assert_eq!(split.next().unwrap(), &[0, 0, 1]);
         assert!(split.next().is_none());
     }
+
+    #[test]
+    fn indexes_nal_units() {
+        let stream = [7, 0, 0, 0, 1, 2, 0, 0, 1, 2, 3, 0, 0, 1];
+        let indexed = nal_units_indexed(&stream)
+            .map(|(offset, nal)| (offset, nal.len()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(indexed, [(2, 4), (6, 5), (11, 3)]);
+
+        for (offset, nal) in nal_units_indexed(&stream) {
+            assert_eq!(&stream[offset..offset + nal.len()], nal);
+        }
+    }
+
+    #[test]
+    fn splits_at_access_unit() {
+        let sps = [0, 0, 1, 0x67, 0x42];
+        let pps = [0, 0, 1, 0x68, 0xce];
+        let idr = [0, 0, 1, 0x65, 0x88, 0x84];
+        let idr_second_slice = [0, 0, 1, 0x65, 0x12, 0x34];
+        let p = [0, 0, 1, 0x41, 0x9a, 0x02];
+        let aud = [0, 0, 1, 0x09, 0xf0];
+
+        let stream = [&sps[..], &pps, &idr, &idr_second_slice, &p, &aud, &p, &p].concat();
+        let units = access_units(&stream).collect::<Vec<_>>();
+
+        assert_eq!(units.len(), 4);
+        assert_eq!(units[0], [&sps[..], &pps, &idr, &idr_second_slice].concat());
+        assert_eq!(units[1], p);
+        assert_eq!(units[2], [&aud[..], &p].concat());
+        assert_eq!(units[3], p);
+
+        assert!(access_units(&[]).next().is_none());
+    }
 }
@@ -0,0 +1,151 @@
+use crate::error;
+use crate::error::{Error, Variant};
+use crate::video::Frame;
+use std::io::Write;
+
+/// Writes decoded frames as YUV4MPEG2 (`.y4m`) stream, e.g., to check decode output with `mpv` or `ffplay`.
+///
+/// Frames are converted to planar YUV, the header is written along with the first frame, whose size, bit depth and
+/// chroma subsampling all following frames must match.
+///
+/// ```rust,ignore
+/// let mut y4m = Y4mWriter::new(File::create("out.y4m")?).frame_rate(25, 1);
+///
+/// for frame in decoder.decode(access_unit)? {
+///     y4m.write(&frame)?;
+/// }
+/// ```
+pub struct Y4mWriter<W: Write> {
+    writer: W,
+    frame_rate: (u32, u32),
+    header: Option<String>,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    /// Creates a writer for 30 frames per second.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            frame_rate: (30, 1),
+            header: None,
+        }
+    }
+
+    /// Frame rate as `numerator / denominator` frames per second, has to be set before the first frame is written.
+    pub fn frame_rate(mut self, numerator: u32, denominator: u32) -> Self {
+        self.frame_rate = (numerator, denominator);
+        self
+    }
+
+    /// Appends `frame`, writing the stream header first if this is the first frame.
+    pub fn write(&mut self, frame: &Frame) -> Result<(), Error> {
+        let header = self.header_for(frame)?;
+
+        match &self.header {
+            Some(x) if *x != header => {
+                return Err(error!(
+                    Variant::FeatureNotSupported,
+                    "Y4M streams can't change frame size or format."
+                ))
+            }
+            Some(_) => {}
+            None => {
+                self.writer.write_all(header.as_bytes())?;
+                self.header = Some(header);
+            }
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+
+        let data = frame.to_vec_i420();
+
+        match frame.bytes_per_sample() {
+            1 => self.writer.write_all(&data)?,
+            _ => {
+                // Samples are stored in the most significant bits, Y4M expects them in the least significant ones.
+                let shift = 16 - u32::from(frame.bit_depth());
+                let samples = data
+                    .chunks_exact(2)
+                    .flat_map(|x| (u16::from_le_bytes([x[0], x[1]]) >> shift).to_le_bytes())
+                    .collect::<Vec<_>>();
+
+                self.writer.write_all(&samples)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn into_inner(mut self) -> Result<W, Error> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn header_for(&self, frame: &Frame) -> Result<String, Error> {
+        let subsampling = match (frame.chroma_width() < frame.width(), frame.chroma_height() < frame.height()) {
+            (true, true) => "420",
+            (true, false) => "422",
+            (false, false) => "444",
+            _ => return Err(error!(Variant::FeatureNotSupported, "Unsupported chroma subsampling.")),
+        };
+
+        let depth = match frame.bit_depth() {
+            8 => String::new(),
+            x => format!("p{x}"),
+        };
+
+        Ok(format!(
+            "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C{subsampling}{depth}\n",
+            frame.width(),
+            frame.height(),
+            self.frame_rate.0,
+            self.frame_rate.1
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::Error;
+    use crate::video::{Frame, Y4mWriter};
+    use ash::vk::Format;
+
+    #[test]
+    fn writes_header_and_frames() -> Result<(), Error> {
+        let frame = Frame::new(Format::G8_B8R8_2PLANE_420_UNORM, 2, 2, 8, vec![1, 1, 1, 1, 2, 3]);
+        let mut y4m = Y4mWriter::new(Vec::new()).frame_rate(25, 1);
+
+        y4m.write(&frame)?;
+        y4m.write(&frame)?;
+
+        let data = y4m.into_inner()?;
+        let header = b"YUV4MPEG2 W2 H2 F25:1 Ip A1:1 C420\n";
+        let frame = b"FRAME\n\x01\x01\x01\x01\x02\x03";
+
+        assert_eq!(data, [&header[..], frame, frame].concat());
+
+        Ok(())
+    }
+
+    #[test]
+    fn shifts_high_bit_depth() -> Result<(), Error> {
+        let frame = Frame::new(
+            Format::G10X6_B10X6R10X6_2PLANE_420_UNORM_3PACK16,
+            2,
+            2,
+            10,
+            vec![0xc0, 0xff, 0xc0, 0xff, 0xc0, 0xff, 0xc0, 0xff, 0, 0x80, 0, 0x80],
+        );
+        let mut y4m = Y4mWriter::new(Vec::new());
+
+        y4m.write(&frame)?;
+
+        let data = y4m.into_inner()?;
+
+        assert!(data.starts_with(b"YUV4MPEG2 W2 H2 F30:1 Ip A1:1 C420p10\n"));
+        assert!(data.ends_with(&[0xff, 0x03, 0x00, 0x02, 0x00, 0x02]));
+
+        Ok(())
+    }
+}
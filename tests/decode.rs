@@ -2,7 +2,7 @@
 // use vulkan_video::ops::{AddToCommandBuffer, CopyImage2Buffer, DecodeH264, DecodeInfo, FillBuffer};
 // use vulkan_video::resources::{Buffer, BufferInfo, Image, ImageInfo, ImageView, ImageViewInfo};
 // use vulkan_video::video::h264::H264StreamInspector;
-// use vulkan_video::video::{nal_units, VideoSession, VideoSessionParameters};
+// use vulkan_video::video::{nal_units_indexed, VideoSession, VideoSessionParameters};
 // use vulkan_video::{error, Allocation, CommandBuffer, Device, Error, Instance, InstanceInfo, PhysicalDevice, Queue, Variant};
 //
 // #[test]
@@ -66,13 +66,11 @@
 //     let buffer_info_output = BufferInfo::new().size(512 * 512 * 4);
 //     let buffer_output = Buffer::new(&allocation_output, &buffer_info_output)?;
 //
-//     let mut offset = 0;
-//
-//     for nal in nal_units(h264_data) {
+//     for (offset, nal) in nal_units_indexed(h264_data) {
 //         let video_session = VideoSession::new(&device, &stream_inspector)?;
 //         let video_session_parameters = VideoSessionParameters::new(&video_session, &stream_inspector)?;
 //
-//         let decode_info = DecodeInfo::new(offset, nal.len() as u64);
+//         let decode_info = DecodeInfo::new(offset as u64, nal.len() as u64);
 //
 //         let fill = FillBuffer::new(&buffer_output, 0);
 //         let decode = DecodeH264::new(&buffer_h264, &video_session_parameters, &image_view_dst, &decode_info);
@@ -88,8 +86,6 @@
 //         let mut data_out = [0u8; 512 * 512 * 4];
 //         buffer_output.download_into(&mut data_out)?;
 //
-//         offset += nal.len() as u64;
-//
 //         dbg!(&data_out[0..10]);
 //     }
 //
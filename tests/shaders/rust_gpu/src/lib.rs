@@ -0,0 +1,26 @@
+//! Example kernels in Rust, load the resulting module with `RustGpuModule`.
+//!
+//! Build with [rust-gpu](https://github.com/Rust-GPU/rust-gpu), e.g., `cargo gpu build` in this directory.
+#![no_std]
+
+use spirv_std::glam::UVec3;
+use spirv_std::spirv;
+
+/// Like `hello_world.glsl`, use with `Parameters<(&Buffer, &Buffer, &Buffer)>`.
+#[spirv(compute(threads(32)))]
+pub fn add(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] data0: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] data1: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] data2: &[u32],
+) {
+    let idx = id.x as usize;
+
+    data0[idx] = data1[idx] + data2[idx];
+}
+
+/// Zeroes a buffer, use with `Parameters<(&Buffer,)>`.
+#[spirv(compute(threads(32)))]
+pub fn clear(#[spirv(global_invocation_id)] id: UVec3, #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] data: &mut [u32]) {
+    data[id.x as usize] = 0;
+}